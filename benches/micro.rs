@@ -1,13 +1,150 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+// List storage micro-benchmarks
+//
+// The quicklist keeps runs of elements in listpack nodes, so indexing skips
+// whole nodes, LINSERT rewrites one small buffer, and LMPOP-style pops stay
+// constant per element whatever the list length. The linked-list groups
+// show the previous layout for comparison. The command groups go through
+// the LPUSH/LINDEX handlers on a stored key, which edit the list in place,
+// so their cost should not grow with the list either.
 
-fn bench_placeholder(c: &mut Criterion) {
-    c.bench_function("placeholder", |b| {
-        b.iter(|| {
-            // Placeholder benchmark
-            1 + 1
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use redis_rust::commands::list;
+use redis_rust::storage::db::Database;
+use redis_rust::storage::quicklist::QuickList;
+use std::collections::LinkedList;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn element(i: usize) -> Bytes {
+    Bytes::from(format!("element:{}", i))
+}
+
+fn quicklist(size: usize) -> QuickList {
+    (0..size).map(element).collect()
+}
+
+fn linked_list(size: usize) -> LinkedList<Bytes> {
+    (0..size).map(element).collect()
+}
+
+fn bench_lindex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list_lindex_middle");
+    for size in SIZES {
+        let list = quicklist(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| black_box(list.get(black_box(size / 2)).map(|e| e.len())))
+        });
+    }
+    group.finish();
+}
+
+fn bench_linsert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list_linsert_middle");
+    for size in SIZES {
+        let mut list = quicklist(size);
+        // LINSERT before the middle element, then take it out again
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                list.insert(black_box(size / 2), b"pivot-neighbour");
+                list.remove(size / 2);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_lpos(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list_lpos_rank_from_tail");
+    for size in SIZES {
+        let list = quicklist(size);
+        let wanted = element(size - 10);
+        // LPOS key element RANK -1 finds a recent element without a full scan
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| list.iter().rev().position(|e| e.as_ref() == black_box(&wanted[..])))
+        });
+    }
+    group.finish();
+}
+
+fn bench_lmpop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list_lmpop_count_10");
+    for size in SIZES {
+        let mut list = quicklist(size);
+        // LMPOP 1 key LEFT COUNT 10, then refill the tail like a producer
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                let popped: Vec<Bytes> = (0..10).filter_map(|_| list.pop_front()).collect();
+                for e in &popped {
+                    list.push_back(e);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// A database holding `size` elements at "list"
+fn stored_list(runtime: &Runtime, size: usize) -> Arc<Database> {
+    let db = Arc::new(Database::new(1));
+    let mut push = vec![b"list".to_vec()];
+    push.extend((0..size).map(|i| element(i).to_vec()));
+    runtime.block_on(list::rpush(&db, 0, push));
+    db
+}
+
+fn bench_lpush_command(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("list_command_lpush_lpop");
+    for size in SIZES {
+        let db = stored_list(&runtime, size);
+        // LPUSH then LPOP, so the list keeps its length
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    list::lpush(&db, 0, vec![b"list".to_vec(), b"head".to_vec()]).await;
+                    list::lpop(&db, 0, vec![b"list".to_vec()]).await
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_lindex_command(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("list_command_lindex_head");
+    for size in SIZES {
+        let db = stored_list(&runtime, size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| runtime.block_on(list::lindex(&db, 0, vec![b"list".to_vec(), b"0".to_vec()])))
+        });
+    }
+    group.finish();
+}
+
+/// The previous layout: every indexed access walked the linked nodes
+fn bench_linked_list_index_walk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("linked_list_index_walk");
+    for size in SIZES {
+        let list = linked_list(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| black_box(list.iter().nth(black_box(size / 2)).map(|e| e.len())))
         });
-    });
+    }
+    group.finish();
 }
 
-criterion_group!(benches, bench_placeholder);
+criterion_group!(
+    benches,
+    bench_lindex,
+    bench_linsert,
+    bench_lpos,
+    bench_lmpop,
+    bench_lpush_command,
+    bench_lindex_command,
+    bench_linked_list_index_walk
+);
 criterion_main!(benches);
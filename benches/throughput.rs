@@ -1,13 +1,92 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+// Pipelined throughput benchmarks
+//
+// Runs a server in-process and drives it over TCP with SET and GET
+// pipelines of increasing depth. A connection drains every buffered
+// command before flushing the replies in one write, so commands per
+// second should climb with depth instead of staying at one round trip
+// and one reply write per command.
 
-fn bench_throughput_placeholder(c: &mut Criterion) {
-    c.bench_function("throughput_placeholder", |b| {
-        b.iter(|| {
-            // Placeholder throughput benchmark
-            1 + 1
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use redis_rust::protocol::{RespParser, RespSerializer, RespValue};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+
+const PORT: u16 = 17290;
+const DEPTHS: [usize; 4] = [1, 16, 128, 1024];
+
+fn start_server(runtime: &Runtime, dir: &TempDir) {
+    let mut config = ServerConfig::default().with_port(PORT);
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join("dump.rdb").to_string_lossy().to_string();
+
+    runtime.block_on(async {
+        let server = RedisServer::new(config).await.unwrap();
+        tokio::spawn(async move {
+            let _ = server.run().await;
         });
+        tokio::time::sleep(Duration::from_millis(100)).await;
     });
 }
 
-criterion_group!(benches, bench_throughput_placeholder);
-criterion_main!(benches);
+/// `depth` copies of a command, serialized back to back
+fn pipeline(depth: usize, args: &[&str]) -> Vec<u8> {
+    let frame = RespValue::Array(Some(
+        args.iter()
+            .map(|arg| RespValue::BulkString(Some(arg.as_bytes().to_vec())))
+            .collect(),
+    ));
+    RespSerializer::serialize(&frame).repeat(depth)
+}
+
+/// Send a pipeline and wait until all of its replies have arrived
+async fn round_trip(stream: &mut TcpStream, buffer: &mut BytesMut, request: &[u8], depth: usize) {
+    stream.write_all(request).await.unwrap();
+    let mut replies = 0;
+    while replies < depth {
+        match RespParser::check_complete(buffer) {
+            Ok(Some(len)) => {
+                let _ = buffer.split_to(len);
+                replies += 1;
+            }
+            _ => {
+                if stream.read_buf(buffer).await.unwrap() == 0 {
+                    panic!("server closed the connection");
+                }
+            }
+        }
+    }
+}
+
+fn bench_pipeline(c: &mut Criterion, runtime: &Runtime, name: &str, args: &[&str]) {
+    let mut group = c.benchmark_group(name);
+    for depth in DEPTHS {
+        let request = pipeline(depth, args);
+        let mut stream = runtime
+            .block_on(TcpStream::connect(("127.0.0.1", PORT)))
+            .unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut buffer = BytesMut::with_capacity(64 * 1024);
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, &depth| {
+            b.iter(|| runtime.block_on(round_trip(&mut stream, &mut buffer, &request, depth)))
+        });
+    }
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let runtime = Runtime::new().unwrap();
+    start_server(&runtime, &dir);
+    bench_pipeline(c, &runtime, "pipeline_set", &["SET", "bench:key", "value"]);
+    bench_pipeline(c, &runtime, "pipeline_get", &["GET", "bench:key"]);
+}
+
+criterion_group!(throughput, benches);
+criterion_main!(throughput);
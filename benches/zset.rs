@@ -0,0 +1,155 @@
+// Sorted set rank benchmarks
+//
+// ZRANK, ZRANGE by index and ZREMRANGEBYRANK rest on the skiplist's rank
+// queries. Their cost should stay nearly flat from a thousand to a million
+// members, while walking an ordered map (the previous layout) grows
+// linearly. The command groups time ZRANK and ZADD through their handlers
+// on a stored key, which read and edit the set in place, so they should
+// stay flat as well.
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ordered_float::OrderedFloat;
+use redis_rust::commands::zset as commands;
+use redis_rust::storage::db::Database;
+use redis_rust::storage::types::{RedisValue, ZSet};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+fn member(i: usize) -> Bytes {
+    Bytes::from(format!("player:{}", i))
+}
+
+/// Scores spread so that insertion order differs from rank order
+fn score(i: usize) -> f64 {
+    ((i * 7919) % 1_000_003) as f64
+}
+
+fn leaderboard(size: usize) -> ZSet {
+    let mut zset = ZSet::new();
+    for i in 0..size {
+        zset.insert(member(i), score(i));
+    }
+    zset
+}
+
+fn bench_zrank(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zset_rank");
+    for size in SIZES {
+        let zset = leaderboard(size);
+        let probe = member(size / 2);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| black_box(zset.rank(black_box(&probe))))
+        });
+    }
+    group.finish();
+}
+
+fn bench_range_by_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zset_range_by_index");
+    for size in SIZES {
+        let zset = leaderboard(size);
+        // ZRANGE key <middle> <middle + 9>
+        let start = size / 2;
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| zset.range_by_rank(black_box(start), start + 10).count())
+        });
+    }
+    group.finish();
+}
+
+fn bench_remove_by_rank(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zset_remove_by_rank");
+    for size in SIZES {
+        let mut zset = leaderboard(size);
+        let rank = size / 2;
+        // ZREMRANGEBYRANK key <middle> <middle>, then put the member back
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                let (member, score) = zset
+                    .get_by_rank(black_box(rank))
+                    .map(|(m, s)| (Bytes::copy_from_slice(m), s))
+                    .unwrap();
+                zset.remove(&member);
+                zset.insert(member, score);
+            })
+        });
+    }
+    group.finish();
+}
+
+/// A database holding a leaderboard of `size` members at "board"
+fn stored_leaderboard(size: usize) -> Arc<Database> {
+    let db = Arc::new(Database::new(1));
+    let instance = db.get_db(0).unwrap();
+    let mut zset = instance.new_zset();
+    for i in 0..size {
+        zset.insert(member(i), score(i));
+    }
+    instance.set("board".to_string(), RedisValue::ZSet(zset));
+    db
+}
+
+fn bench_zrank_command(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("zset_command_zrank");
+    for size in SIZES {
+        let db = stored_leaderboard(size);
+        let args = vec![b"board".to_vec(), member(size / 2).to_vec()];
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| runtime.block_on(commands::zrank(&db, 0, args.clone())))
+        });
+    }
+    group.finish();
+}
+
+fn bench_zadd_command(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("zset_command_zadd_update");
+    for size in SIZES {
+        let db = stored_leaderboard(size);
+        // Rescore one member back and forth, so the set keeps its size
+        let args = [
+            vec![b"board".to_vec(), b"-1".to_vec(), member(size / 2).to_vec()],
+            vec![b"board".to_vec(), score(size / 2).to_string().into_bytes(), member(size / 2).to_vec()],
+        ];
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    commands::zadd(&db, 0, args[0].clone()).await;
+                    commands::zadd(&db, 0, args[1].clone()).await
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+/// The previous layout: finding a rank meant walking the ordered map
+fn bench_btreemap_rank_walk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("btreemap_rank_walk");
+    group.sample_size(10);
+    for size in SIZES {
+        let map: BTreeMap<(OrderedFloat<f64>, Bytes), ()> =
+            (0..size).map(|i| ((OrderedFloat(score(i)), member(i)), ())).collect();
+        let probe = member(size / 2);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| map.keys().position(|(_, m)| m == black_box(&probe)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_zrank,
+    bench_range_by_index,
+    bench_remove_by_rank,
+    bench_zrank_command,
+    bench_zadd_command,
+    bench_btreemap_rank_walk
+);
+criterion_main!(benches);
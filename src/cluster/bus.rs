@@ -0,0 +1,1479 @@
+// Cluster bus - node to node communication on port + 10000
+//
+// Every node keeps one outbound link per known node and sends PINGs over
+// it; the peer answers each PING (or MEET) with a PONG on the same
+// connection. Both carry the sender's view of itself (role, slots, epochs)
+// and a gossip section about a few other nodes, which is how nodes learn
+// about each other and about failures.
+//
+// Failure detection: a node that does not answer a PING within
+// cluster-node-timeout is flagged PFAIL. Masters gossip their PFAIL view;
+// once a majority of the masters serving slots reports the same node, it
+// is flagged FAIL and a FAIL message is broadcast to everybody.
+//
+// Replicas of a FAIL master then elect one of them to take over its slots;
+// see failover.rs.
+//
+// Sharded pub/sub: an SPUBLISH is forwarded to the other nodes of the
+// shard in a PUBLISHSHARD message, and subscribers of shard channels whose
+// slot is no longer served by the shard are unsubscribed by the cron.
+
+use crate::cluster::failover::{
+    replica_rank, FailoverState, DEFAULT_VALIDITY_FACTOR, MANUAL_FAILOVER_TIMEOUT, REPL_PING_PERIOD,
+};
+use crate::cluster::message::{
+    ClusterMessage, GossipEntry, MessageBody, MessageHeader, MessageType, FLAG_HANDSHAKE,
+    FLAG_NOADDR, MFLAG_FORCEACK, MFLAG_PAUSED,
+};
+use crate::cluster::node::{ClusterNode, NodeFlags};
+use crate::cluster::{key_hash_slot, mstime, save_cluster_config, served_by_my_master, ClusterState, MigrationManager};
+use crate::config::Config;
+use crate::pubsub::PubSub;
+use crate::replication::{start_replication, ReplicationBacklog, ReplicationInfo};
+use crate::storage::db::Database;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+/// Cron period
+const CRON_INTERVAL: Duration = Duration::from_millis(100);
+/// A FAIL flag on a master serving slots is cleared after this many node
+/// timeouts if the node is reachable again
+const FAIL_UNDO_TIME_MULT: u64 = 2;
+/// Failure reports older than this many node timeouts are ignored
+const FAIL_REPORT_VALIDITY_MULT: u64 = 2;
+/// How long a forgotten node is kept out of gossip (milliseconds)
+const BLACKLIST_TTL: u64 = 60_000;
+
+/// Outbound connection to another node
+struct Link {
+    id: u64,
+    /// Creation time (milliseconds)
+    created: u64,
+    tx: mpsc::UnboundedSender<ClusterMessage>,
+    task: AbortHandle,
+}
+
+/// Connection opened by another node
+struct InboundLink {
+    /// Sender of the last message, once known
+    node_id: Option<String>,
+    /// Creation time (milliseconds)
+    created: u64,
+}
+
+/// A bus connection as shown by CLUSTER LINKS
+#[derive(Debug, Clone)]
+pub struct LinkInfo {
+    /// "to" for links we opened, "from" for links opened by the peer
+    pub direction: &'static str,
+    pub node_id: String,
+    /// Creation time (milliseconds)
+    pub create_time: u64,
+    pub events: &'static str,
+}
+
+/// The cluster bus of the local node
+pub struct ClusterBus {
+    cluster: Arc<ClusterState>,
+    migration: Arc<MigrationManager>,
+    config: Arc<Config>,
+    /// Outbound links, by node id
+    links: Mutex<HashMap<String, Link>>,
+    /// Inbound connections, by link id
+    inbound: Mutex<HashMap<u64, InboundLink>>,
+    next_link_id: AtomicU64,
+    /// Forgotten nodes -> time until which gossip about them is ignored
+    blacklist: Mutex<HashMap<String, u64>>,
+    /// Dataset and replication state, used when the node changes role
+    db: Arc<Database>,
+    repl_info: Arc<ReplicationInfo>,
+    backlog: Arc<ReplicationBacklog>,
+    failover: Mutex<FailoverState>,
+    /// Local pub/sub, for shard messages published on other nodes
+    pubsub: Arc<PubSub>,
+}
+
+impl ClusterBus {
+    pub fn new(cluster: Arc<ClusterState>, migration: Arc<MigrationManager>, config: Arc<Config>) -> Self {
+        Self {
+            cluster,
+            migration,
+            config,
+            links: Mutex::new(HashMap::new()),
+            inbound: Mutex::new(HashMap::new()),
+            next_link_id: AtomicU64::new(1),
+            blacklist: Mutex::new(HashMap::new()),
+            db: Arc::new(Database::new(16)),
+            repl_info: Arc::new(ReplicationInfo::new()),
+            backlog: Arc::new(ReplicationBacklog::new()),
+            failover: Mutex::new(FailoverState::new()),
+            pubsub: Arc::new(PubSub::new()),
+        }
+    }
+
+    /// Use the server's dataset and replication state, so that failovers
+    /// and role changes decided by the cluster drive replication
+    pub fn with_replication(
+        mut self,
+        db: Arc<Database>,
+        repl_info: Arc<ReplicationInfo>,
+        backlog: Arc<ReplicationBacklog>,
+    ) -> Self {
+        self.db = db;
+        self.repl_info = repl_info;
+        self.backlog = backlog;
+        self
+    }
+
+    /// Deliver shard messages from other nodes to the server's subscribers
+    pub fn with_pubsub(mut self, pubsub: Arc<PubSub>) -> Self {
+        self.pubsub = pubsub;
+        self
+    }
+
+    /// Listen on port + 10000 and start the cron
+    pub async fn start(self: &Arc<Self>, bind: &str, port: u16) -> anyhow::Result<()> {
+        let bus_port = port
+            .checked_add(10000)
+            .ok_or_else(|| anyhow::anyhow!("port {} is too high for a cluster bus", port))?;
+        let listener = TcpListener::bind((bind, bus_port)).await?;
+        info!("Cluster bus listening on {}:{}", bind, bus_port);
+
+        if let Ok(ip) = bind.parse::<IpAddr>() {
+            self.cluster.set_my_addr(SocketAddr::new(ip, port));
+        }
+
+        let bus = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let bus = Arc::clone(&bus);
+                        tokio::spawn(async move { bus.run_inbound(stream, peer).await });
+                    }
+                    Err(e) => warn!("Cluster bus accept error: {}", e),
+                }
+            }
+        });
+
+        let bus = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CRON_INTERVAL);
+            let mut iteration: u64 = 0;
+            loop {
+                interval.tick().await;
+                bus.cron(iteration);
+                iteration += 1;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// The node table this bus keeps up to date
+    pub fn cluster(&self) -> &Arc<ClusterState> {
+        &self.cluster
+    }
+
+    /// cluster-node-timeout in milliseconds
+    pub fn node_timeout(&self) -> u64 {
+        self.config
+            .get_int("cluster-node-timeout")
+            .filter(|&t| t > 0)
+            .unwrap_or(15000) as u64
+    }
+
+    /// Start a handshake with the node at `addr`, greeting it with a MEET
+    /// so that it accepts us. Returns false if one is already in progress.
+    pub fn meet(&self, addr: SocketAddr) -> bool {
+        self.start_handshake(addr, true)
+    }
+
+    /// Add a placeholder node for `addr`; the real id is learned from the
+    /// first reply
+    fn start_handshake(&self, addr: SocketAddr, meet: bool) -> bool {
+        if addr.ip().is_unspecified() || addr.port() == 0 {
+            return false;
+        }
+        let in_progress = self
+            .cluster
+            .nodes
+            .iter()
+            .any(|r| r.value().in_handshake() && r.value().addr == Some(addr));
+        if in_progress {
+            return false;
+        }
+
+        let mut node = ClusterNode::new(random_node_id(), Some(addr));
+        node.add_flag(NodeFlags::Handshake);
+        if meet {
+            node.add_flag(NodeFlags::Meet);
+        }
+        debug!("Starting cluster handshake with {}", addr);
+        self.cluster.add_node(node);
+        true
+    }
+
+    /// Forget a node and close its link
+    pub fn drop_node(&self, node_id: &str) {
+        self.cluster.remove_node(node_id);
+        if let Some(link) = self.links.lock().unwrap().remove(node_id) {
+            link.task.abort();
+        }
+        // Reports made by the node no longer count
+        for mut node in self.cluster.nodes.iter_mut() {
+            node.fail_reports.remove(node_id);
+        }
+    }
+
+    /// CLUSTER FORGET: drop a node and ignore gossip about it for a minute
+    /// so that the other nodes do not teach it back to us
+    pub fn forget(&self, node_id: &str) {
+        self.blacklist
+            .lock()
+            .unwrap()
+            .insert(node_id.to_string(), mstime() + BLACKLIST_TTL);
+        self.drop_node(node_id);
+    }
+
+    /// Whether `node_id` was forgotten less than a minute ago
+    fn is_blacklisted(&self, node_id: &str) -> bool {
+        let now = mstime();
+        let mut blacklist = self.blacklist.lock().unwrap();
+        blacklist.retain(|_, expire| *expire > now);
+        blacklist.contains_key(node_id)
+    }
+
+    /// CLUSTER RESET: close every link and reset the node table
+    pub fn reset(&self, hard: bool) {
+        for (_, link) in self.links.lock().unwrap().drain() {
+            link.task.abort();
+        }
+        self.blacklist.lock().unwrap().clear();
+        self.cluster.reset(hard);
+    }
+
+    /// Path of nodes.conf
+    pub fn config_file(&self) -> String {
+        self.config
+            .get("cluster-config-file")
+            .unwrap_or_else(|| "nodes.conf".to_string())
+    }
+
+    /// Write nodes.conf now
+    pub fn save_config(&self) -> std::io::Result<()> {
+        self.cluster.take_dirty();
+        save_cluster_config(&self.cluster, self.cluster.current_epoch(), &self.config_file())
+    }
+
+    /// Write nodes.conf if the node table changed since the last save
+    pub fn flush_config(&self) {
+        if self.cluster.take_dirty() {
+            if let Err(e) = save_cluster_config(&self.cluster, self.cluster.current_epoch(), &self.config_file()) {
+                warn!("Could not save the cluster config {}: {}", self.config_file(), e);
+            }
+        }
+    }
+
+    /// Every open bus connection, outbound links first
+    pub fn links(&self) -> Vec<LinkInfo> {
+        let mut links: Vec<LinkInfo> = self
+            .links
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| self.cluster.get_node(id).is_some_and(|n| !n.in_handshake()))
+            .map(|(id, link)| LinkInfo {
+                direction: "to",
+                node_id: id.clone(),
+                create_time: link.created,
+                events: "rw",
+            })
+            .collect();
+        links.extend(self.inbound.lock().unwrap().values().filter_map(|link| {
+            let node_id = link.node_id.clone()?;
+            Some(LinkInfo { direction: "from", node_id, create_time: link.created, events: "r" })
+        }));
+        links
+    }
+
+    /// Build a message of the given type describing the local node.
+    /// PING, PONG and MEET get a gossip section about other nodes.
+    pub fn build_message(&self, msg_type: MessageType, target: Option<&str>) -> ClusterMessage {
+        let myself = self.cluster.myself().unwrap_or_else(|| ClusterNode::new(self.cluster.my_id(), None));
+        let mut header = MessageHeader::for_node(msg_type, &myself, self.cluster.current_epoch());
+        header.state_ok = self.cluster.state_ok();
+        header.offset = self.repl_info.master_offset();
+        if self.writes_paused() {
+            header.mflags |= MFLAG_PAUSED;
+        }
+
+        // Replicas advertise the slots and epoch of their master
+        if let Some(master) = myself.master_id.as_deref().and_then(|id| self.cluster.get_node(id)) {
+            header.slots = master.slots.iter().collect();
+            header.config_epoch = master.config_epoch;
+        }
+
+        let body = match msg_type {
+            MessageType::Ping | MessageType::Pong | MessageType::Meet => {
+                MessageBody::Gossip(self.gossip_section(target))
+            }
+            _ => MessageBody::Empty,
+        };
+        ClusterMessage { header, body }
+    }
+
+    /// Pick the nodes to gossip about: a random tenth of the cluster (at
+    /// least three) plus every node we flag PFAIL
+    fn gossip_section(&self, target: Option<&str>) -> Vec<GossipEntry> {
+        let candidates: Vec<ClusterNode> = self
+            .cluster
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| {
+                n.id != self.cluster.my_id()
+                    && Some(n.id.as_str()) != target
+                    && !n.in_handshake()
+                    && n.addr.is_some()
+            })
+            .collect();
+
+        let total = self.cluster.nodes.len();
+        let wanted = (total / 10).max(3).min(total.saturating_sub(2));
+        let mut rng = rand::thread_rng();
+        let mut chosen: Vec<&ClusterNode> = candidates.choose_multiple(&mut rng, wanted).collect();
+        for node in candidates.iter().filter(|n| n.is_pfail()) {
+            if !chosen.iter().any(|c| c.id == node.id) {
+                chosen.push(node);
+            }
+        }
+
+        chosen.into_iter().map(GossipEntry::for_node).collect()
+    }
+
+    /// Queue a message on the link to `node_id`
+    fn send_to(&self, node_id: &str, msg: ClusterMessage) -> bool {
+        let links = self.links.lock().unwrap();
+        match links.get(node_id) {
+            Some(link) => link.tx.send(msg).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Send a PING to a node, remembering when it was sent
+    fn send_ping(&self, node_id: &str) {
+        let msg = self.build_message(MessageType::Ping, Some(node_id));
+        if self.send_to(node_id, msg) {
+            if let Some(mut node) = self.cluster.nodes.get_mut(node_id) {
+                if node.ping_sent == 0 {
+                    node.ping_sent = mstime();
+                }
+            }
+        }
+    }
+
+    /// Queue a message on every link
+    fn broadcast(&self, msg: ClusterMessage) {
+        for link in self.links.lock().unwrap().values() {
+            let _ = link.tx.send(msg.clone());
+        }
+    }
+
+    /// Tell every node that `node_id` failed
+    fn broadcast_fail(&self, node_id: &str) {
+        warn!("Marking node {} as failing (quorum reached)", node_id);
+        let mut msg = self.build_message(MessageType::Fail, None);
+        msg.body = MessageBody::Fail { node_id: node_id.to_string() };
+        self.broadcast(msg);
+    }
+
+    /// Forward an SPUBLISH to the other nodes of our shard: our master and
+    /// its replicas, or our replicas when we are the master
+    pub fn publish_shard(&self, channel: &[u8], message: &[u8]) {
+        let me = self.cluster.my_id();
+        let master = self.cluster.myself().and_then(|n| n.master_id).unwrap_or_else(|| me.clone());
+        let peers: Vec<String> = self
+            .cluster
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| n.id != me && !n.in_handshake())
+            .filter(|n| n.id == master || n.master_id.as_deref() == Some(master.as_str()))
+            .map(|n| n.id)
+            .collect();
+        if peers.is_empty() {
+            return;
+        }
+
+        let mut msg = self.build_message(MessageType::PublishShard, None);
+        msg.body = MessageBody::Publish { channel: channel.to_vec(), message: message.to_vec() };
+        for node_id in peers {
+            self.send_to(&node_id, msg.clone());
+        }
+    }
+
+    /// Unsubscribe clients from the shard channels whose slot our shard
+    /// no longer serves, e.g. after it was migrated or deleted
+    fn drop_lost_shard_channels(&self) {
+        let me = self.cluster.my_id();
+        let dropped = self.pubsub.drop_shard_channels(|channel| {
+            let slot = key_hash_slot(channel.as_bytes());
+            self.cluster.get_slot_node(slot).as_deref() != Some(me.as_str())
+                && !served_by_my_master(&self.cluster, slot)
+        });
+        if !dropped.is_empty() {
+            debug!("Unsubscribed clients from moved shard channels {:?}", dropped);
+        }
+    }
+
+    /// Process a message. `link_node` is the node an outbound link was
+    /// opened for (None for inbound connections); it is updated when the
+    /// handshake reveals the peer's real id. Returns the reply to send.
+    pub fn handle_message(
+        &self,
+        msg: ClusterMessage,
+        peer_ip: Option<IpAddr>,
+        link_node: Option<&mut String>,
+    ) -> Option<ClusterMessage> {
+        self.cluster.record_received();
+        let header = &msg.header;
+        let msg_type = header.msg_type;
+
+        if header.sender == self.cluster.my_id() {
+            // We met ourselves through our own address
+            if let Some(node_id) = link_node {
+                if self.cluster.get_node(node_id).is_some_and(|n| n.in_handshake()) {
+                    self.drop_node(node_id);
+                }
+            }
+            return None;
+        }
+
+        if let Some(node_id) = link_node {
+            if *node_id != header.sender && self.complete_handshake(node_id, header, peer_ip) {
+                *node_id = header.sender.clone();
+            }
+        }
+
+        let known = self
+            .cluster
+            .get_node(&header.sender)
+            .is_some_and(|n| !n.in_handshake());
+
+        if !known && msg_type == MessageType::Meet {
+            // Greet it back; its id is learned from its reply
+            if let Some(addr) = header.addr(peer_ip) {
+                self.start_handshake(addr, false);
+            }
+        }
+
+        let mut reply = match msg_type {
+            MessageType::Ping | MessageType::Meet => {
+                Some(self.build_message(MessageType::Pong, Some(&header.sender)))
+            }
+            _ => None,
+        };
+
+        // Only known nodes can change our view of the cluster
+        if !known {
+            return reply;
+        }
+
+        self.cluster.observe_epoch(header.current_epoch);
+        if matches!(msg_type, MessageType::Ping | MessageType::Pong | MessageType::Meet) {
+            self.update_sender(header, peer_ip);
+        }
+
+        match &msg.body {
+            MessageBody::Gossip(entries) => self.process_gossip(header, entries),
+            MessageBody::Fail { node_id } => {
+                if self.cluster.mark_node_failed(node_id) {
+                    info!("FAIL message received from {} about {}", header.sender, node_id);
+                }
+            }
+            MessageBody::Publish { channel, message } => {
+                self.pubsub.spublish(&String::from_utf8_lossy(channel), message.clone());
+            }
+            MessageBody::Empty => match msg_type {
+                MessageType::FailoverAuthRequest => reply = self.handle_auth_request(header),
+                MessageType::FailoverAuthAck => self.handle_auth_ack(header),
+                MessageType::MfStart => self.handle_mf_start(header),
+                _ => {}
+            },
+        }
+
+        reply
+    }
+
+    /// Replace the placeholder of a handshake with the node's real id.
+    /// Returns true if the link now belongs to the sender.
+    fn complete_handshake(&self, placeholder: &str, header: &MessageHeader, peer_ip: Option<IpAddr>) -> bool {
+        let Some(mut node) = self.cluster.get_node(placeholder) else {
+            return false;
+        };
+        let link = self.links.lock().unwrap().remove(placeholder);
+
+        if !node.in_handshake() {
+            // A different node answers on this address now: forget the
+            // address until gossip tells us where the node went
+            warn!("Node {} replied with id {}, dropping its address", placeholder, header.sender);
+            if let Some(mut entry) = self.cluster.nodes.get_mut(placeholder) {
+                entry.addr = None;
+                entry.add_flag(NodeFlags::NoAddr);
+            }
+            return false;
+        }
+
+        self.cluster.remove_node(placeholder);
+        if self.cluster.get_node(&header.sender).is_some() {
+            // Already known under its real id: the placeholder was a duplicate
+            return false;
+        }
+
+        info!("Handshake with node {} completed", header.sender);
+        node.id = header.sender.clone();
+        node.remove_flag(&NodeFlags::Handshake);
+        node.remove_flag(&NodeFlags::Meet);
+        node.add_flag(if header.master_id.is_some() { NodeFlags::Slave } else { NodeFlags::Master });
+        if let Some(addr) = header.addr(peer_ip) {
+            node.addr = Some(addr);
+        }
+        self.cluster.add_node(node);
+        if let Some(link) = link {
+            self.links.lock().unwrap().insert(header.sender.clone(), link);
+        }
+        true
+    }
+
+    /// Apply what the sender says about itself
+    fn update_sender(&self, header: &MessageHeader, peer_ip: Option<IpAddr>) {
+        let now = mstime();
+        let timeout = self.node_timeout();
+
+        let mut changed = false;
+        if let Some(mut node) = self.cluster.nodes.get_mut(&header.sender) {
+            if let Some(addr) = header.addr(peer_ip) {
+                if node.addr != Some(addr) {
+                    info!("Address of node {} updated to {}", node.id, addr);
+                    node.addr = Some(addr);
+                    changed = true;
+                }
+            }
+            changed |= node.is_master() != header.master_id.is_none()
+                || node.master_id != header.master_id
+                || (header.master_id.is_none() && node.config_epoch != header.config_epoch);
+            node.repl_offset = header.offset;
+
+            if header.msg_type == MessageType::Pong {
+                node.pong_recv = now;
+                node.ping_sent = 0;
+                if node.is_pfail() {
+                    node.remove_flag(&NodeFlags::PFail);
+                } else if node.is_failed() {
+                    // Replicas and masters without slots are cleared right
+                    // away, masters with slots only after a while
+                    let undo = node.slots.is_empty()
+                        || now.saturating_sub(node.fail_time) > timeout * FAIL_UNDO_TIME_MULT;
+                    if node.is_slave() || undo {
+                        info!("Clear FAIL state for node {}: is reachable again", node.id);
+                        node.remove_flag(&NodeFlags::Fail);
+                    }
+                }
+            }
+
+            match &header.master_id {
+                None => {
+                    node.remove_flag(&NodeFlags::Slave);
+                    node.add_flag(NodeFlags::Master);
+                    node.master_id = None;
+                    node.config_epoch = header.config_epoch;
+                }
+                Some(master_id) => {
+                    node.remove_flag(&NodeFlags::Master);
+                    node.add_flag(NodeFlags::Slave);
+                    node.master_id = Some(master_id.clone());
+                }
+            }
+        }
+        if changed {
+            self.cluster.mark_dirty();
+        }
+
+        let my_master = self.cluster.myself().and_then(|n| n.master_id);
+        if my_master.as_deref() == Some(header.sender.as_str()) && header.mflags & MFLAG_PAUSED != 0 {
+            // Our master paused its clients for our manual failover: this
+            // is the offset we need to reach
+            let mut failover = self.failover.lock().unwrap();
+            if failover.manual_in_progress(now) && failover.mf_master_offset.is_none() {
+                info!("Received replication offset for paused master manual failover: {}", header.offset);
+                failover.mf_master_offset = Some(header.offset);
+            }
+        }
+
+        if header.master_id.is_some() {
+            // A master turned replica no longer serves its slots
+            let slots = self.cluster.get_node(&header.sender).map(|n| n.get_slots()).unwrap_or_default();
+            if !slots.is_empty() {
+                self.cluster.remove_slots_from_node(&header.sender, slots);
+            }
+            return;
+        }
+
+        let master_slots = |id: &Option<String>| {
+            id.as_deref().and_then(|id| self.cluster.get_node(id)).map(|n| n.slots.len()).unwrap_or(0)
+        };
+        let my_master_had = master_slots(&my_master);
+
+        let claimed = header.slots.to_set();
+        let lost = self.cluster.update_slots_from(&header.sender, header.config_epoch, &claimed, |slot| {
+            self.migration.is_importing(slot)
+        });
+        if !lost.is_empty() {
+            warn!(
+                "{} slots moved to node {} with a greater config epoch",
+                lost.len(),
+                header.sender
+            );
+        }
+
+        // The sender took the last slot of our shard (it was failed over):
+        // follow it as a replica
+        let shard_taken = if self.cluster.is_myself_master() {
+            !lost.is_empty() && self.cluster.count_my_slots() == 0
+        } else {
+            my_master.as_deref().is_some_and(|m| m != header.sender)
+                && my_master_had > 0
+                && master_slots(&my_master) == 0
+        };
+        if shard_taken {
+            info!("Configuration change detected. Reconfiguring myself as a replica of {}", header.sender);
+            self.replicate(&header.sender);
+        }
+
+        self.handle_epoch_collision(header);
+    }
+
+    /// Two masters with the same config epoch: the one with the smaller
+    /// id takes a new epoch so that every master ends up with a unique one
+    fn handle_epoch_collision(&self, header: &MessageHeader) {
+        if !header.is_master()
+            || !self.cluster.is_myself_master()
+            || header.config_epoch != self.cluster.my_config_epoch()
+            || header.sender <= self.cluster.my_id()
+        {
+            return;
+        }
+        let epoch = self.cluster.bump_current_epoch();
+        self.cluster.set_my_config_epoch(epoch);
+        info!("configEpoch collision with node {}, configEpoch set to {}", header.sender, epoch);
+    }
+
+    /// Become a replica of `master_id` and start replicating from it
+    pub fn replicate(&self, master_id: &str) {
+        let Some(addr) = self.cluster.get_node(master_id).and_then(|n| n.addr) else {
+            warn!("Can't replicate node {}: address unknown", master_id);
+            return;
+        };
+        self.failover.lock().unwrap().reset_manual();
+        self.cluster.set_my_master(master_id);
+        start_replication(
+            &addr.ip().to_string(),
+            addr.port(),
+            &self.repl_info,
+            &self.backlog,
+            &self.db,
+            &self.config,
+        );
+    }
+
+    /// Whether writes are paused because a replica is taking over from us
+    pub fn writes_paused(&self) -> bool {
+        self.failover.lock().unwrap().writes_paused(mstime())
+    }
+
+    /// CLUSTER FAILOVER [FORCE]: start a manual failover on a replica. The
+    /// default mode waits for the master to pause and for the replication
+    /// offsets to match; FORCE starts the election right away.
+    pub fn start_manual_failover(&self, force: bool) {
+        let mut failover = self.failover.lock().unwrap();
+        failover.reset_manual();
+        failover.mf_end = mstime() + MANUAL_FAILOVER_TIMEOUT;
+        if force {
+            info!("Forced failover user request accepted.");
+            failover.mf_can_start = true;
+            return;
+        }
+        drop(failover);
+
+        info!("Manual failover user request accepted.");
+        if let Some(master_id) = self.cluster.myself().and_then(|n| n.master_id) {
+            let msg = self.build_message(MessageType::MfStart, None);
+            self.send_to(&master_id, msg);
+        }
+    }
+
+    /// CLUSTER FAILOVER TAKEOVER: become master without an election
+    pub fn takeover(&self) {
+        info!("Taking over the master (user request).");
+        let (_, epoch) = self.cluster.bump_epoch_without_consensus();
+        self.promote(epoch);
+    }
+
+    /// Replace our master: serve its slots with `config_epoch` and tell
+    /// everybody about it
+    fn promote(&self, config_epoch: u64) {
+        self.repl_info.set_master();
+        let slots = self.cluster.promote_myself(config_epoch);
+        {
+            let mut failover = self.failover.lock().unwrap();
+            failover.reset_manual();
+            failover.schedule(0, 0);
+        }
+        info!(
+            "Failover won: I'm the new master serving {} slots with configEpoch {}",
+            slots.len(),
+            config_epoch
+        );
+        self.broadcast(self.build_message(MessageType::Pong, None));
+    }
+
+    /// Master side: vote for a replica that wants to replace its master
+    fn handle_auth_request(&self, header: &MessageHeader) -> Option<ClusterMessage> {
+        let now = mstime();
+        if !self.cluster.is_myself_master() || self.cluster.count_my_slots() == 0 {
+            return None;
+        }
+        let current_epoch = self.cluster.current_epoch();
+        if header.current_epoch < current_epoch {
+            return None;
+        }
+        let master_id = header.master_id.as_deref()?;
+        let master = self.cluster.get_node(master_id)?;
+
+        let mut failover = self.failover.lock().unwrap();
+        if failover.last_vote_epoch == current_epoch {
+            debug!("Failover auth denied to {}: already voted for epoch {}", header.sender, current_epoch);
+            return None;
+        }
+        if !master.is_failed() && header.mflags & MFLAG_FORCEACK == 0 {
+            debug!("Failover auth denied to {}: its master is up", header.sender);
+            return None;
+        }
+        if now.saturating_sub(master.voted_time) < self.node_timeout() * 2 {
+            debug!("Failover auth denied to {}: voted for a replica of {} recently", header.sender, master_id);
+            return None;
+        }
+        // Every slot it claims must not have a newer owner than its master
+        for slot in header.slots.to_set() {
+            let owner_epoch = self
+                .cluster
+                .get_slot_node(slot)
+                .and_then(|id| self.cluster.get_node(&id))
+                .map(|n| n.config_epoch)
+                .unwrap_or(0);
+            if owner_epoch > header.config_epoch {
+                debug!("Failover auth denied to {}: slot {} has a newer config", header.sender, slot);
+                return None;
+            }
+        }
+
+        failover.last_vote_epoch = current_epoch;
+        drop(failover);
+        if let Some(mut node) = self.cluster.nodes.get_mut(master_id) {
+            node.voted_time = now;
+        }
+        info!("Failover auth granted to {} for epoch {}", header.sender, current_epoch);
+        Some(self.build_message(MessageType::FailoverAuthAck, Some(&header.sender)))
+    }
+
+    /// Replica side: count a vote for the current election
+    fn handle_auth_ack(&self, header: &MessageHeader) {
+        let voter_serves_slots = self
+            .cluster
+            .get_node(&header.sender)
+            .is_some_and(|n| n.is_master() && !n.slots.is_empty());
+        let mut failover = self.failover.lock().unwrap();
+        if voter_serves_slots && failover.auth_sent && header.current_epoch >= failover.auth_epoch {
+            failover.auth_count += 1;
+            debug!("Failover auth ack from {} ({} votes)", header.sender, failover.auth_count);
+        }
+    }
+
+    /// Master side: a replica asks us to pause for its manual failover
+    fn handle_mf_start(&self, header: &MessageHeader) {
+        let my_id = self.cluster.my_id();
+        let is_my_replica = self
+            .cluster
+            .get_node(&header.sender)
+            .is_some_and(|n| n.master_id.as_deref() == Some(my_id.as_str()));
+        if !is_my_replica || !self.cluster.is_myself_master() {
+            return;
+        }
+
+        info!("Manual failover requested by replica {}", header.sender);
+        {
+            let mut failover = self.failover.lock().unwrap();
+            failover.reset_manual();
+            failover.mf_end = mstime() + MANUAL_FAILOVER_TIMEOUT;
+            failover.mf_replica = Some(header.sender.clone());
+        }
+        // Tell the replica which offset to reach
+        self.send_ping(&header.sender);
+    }
+
+    /// Abort a manual failover that took too long, and let a replica
+    /// start its election once it caught up with its paused master
+    fn manual_failover_check(&self) {
+        let now = mstime();
+        let mut failover = self.failover.lock().unwrap();
+        if failover.mf_end == 0 {
+            return;
+        }
+        if now >= failover.mf_end {
+            warn!("Manual failover timed out.");
+            failover.reset_manual();
+            return;
+        }
+
+        if let Some(replica) = failover.mf_replica.clone() {
+            // Keep the replica informed about our paused offset
+            drop(failover);
+            self.send_ping(&replica);
+        } else if !failover.mf_can_start && failover.mf_master_offset == Some(self.repl_info.master_offset()) {
+            info!("All master replication stream processed, manual failover can start.");
+            failover.mf_can_start = true;
+        }
+    }
+
+    /// Replica side of the automatic (and manual) failover, run from the
+    /// cron: wait for our turn, ask the masters for votes and take over
+    /// once a majority agreed
+    fn handle_replica_failover(&self) {
+        let Some(myself) = self.cluster.myself() else { return };
+        let Some(master) = myself.master_id.as_deref().and_then(|id| self.cluster.get_node(id)) else {
+            return;
+        };
+        let now = mstime();
+        let timeout = self.node_timeout();
+        let auth_timeout = (timeout * 2).max(2000);
+        let auth_retry_time = auth_timeout * 2;
+
+        let mut failover = self.failover.lock().unwrap();
+        let manual = failover.manual_in_progress(now) && failover.mf_can_start;
+        if (!master.is_failed() && !manual) || master.slots.is_empty() {
+            return;
+        }
+        if !manual {
+            if self.config.get("cluster-replica-no-failover").as_deref() == Some("yes") {
+                return;
+            }
+            // Don't fail over with data that is too old
+            let factor = self
+                .config
+                .get_int("cluster-replica-validity-factor")
+                .map(|f| f.max(0) as u64)
+                .unwrap_or(DEFAULT_VALIDITY_FACTOR);
+            let data_age = now.saturating_sub(master.pong_recv).saturating_sub(timeout);
+            if factor > 0 && data_age > timeout * factor + REPL_PING_PERIOD {
+                return;
+            }
+        }
+
+        let my_offset = self.repl_info.master_offset();
+        let rank = || {
+            let others = self
+                .cluster
+                .get_replicas(&master.id)
+                .into_iter()
+                .filter(|n| n.id != myself.id && !n.is_failed())
+                .map(|n| n.repl_offset);
+            replica_rank(my_offset, others)
+        };
+
+        // The previous election timed out: start a new one
+        if now.saturating_sub(failover.auth_time) > auth_retry_time {
+            if manual {
+                failover.schedule(now, 0);
+            } else {
+                use rand::Rng;
+                let rank = rank();
+                let jitter = rand::thread_rng().gen_range(0..500);
+                failover.schedule(now + FailoverState::election_delay(rank, jitter), rank);
+            }
+            info!(
+                "Start of election delayed for {} milliseconds (rank #{}, offset {}).",
+                failover.auth_time.saturating_sub(now),
+                failover.auth_rank,
+                my_offset
+            );
+            return;
+        }
+
+        if !manual {
+            failover.update_rank(rank());
+        }
+        if now < failover.auth_time || now - failover.auth_time > auth_timeout {
+            return;
+        }
+
+        if !failover.auth_sent {
+            failover.auth_epoch = self.cluster.bump_current_epoch();
+            failover.auth_sent = true;
+            info!("Starting a failover election for epoch {}.", failover.auth_epoch);
+            drop(failover);
+            let mut msg = self.build_message(MessageType::FailoverAuthRequest, None);
+            if manual {
+                msg.header.mflags |= MFLAG_FORCEACK;
+            }
+            self.broadcast(msg);
+            return;
+        }
+
+        let needed_quorum = self.cluster.size() / 2 + 1;
+        if failover.auth_count >= needed_quorum {
+            let epoch = failover.auth_epoch;
+            drop(failover);
+            self.promote(epoch.max(self.cluster.my_config_epoch()));
+        }
+    }
+
+    /// Learn about other nodes from the gossip section
+    fn process_gossip(&self, header: &MessageHeader, entries: &[GossipEntry]) {
+        let reporter_is_master = header.master_id.is_none() && header.is_master();
+
+        for entry in entries {
+            if entry.node_id == self.cluster.my_id() {
+                continue;
+            }
+
+            if self.cluster.nodes.contains_key(&entry.node_id) {
+                if reporter_is_master {
+                    if entry.is_failing() {
+                        if self.cluster.add_failure_report(&entry.node_id, &header.sender) {
+                            debug!("Node {} reported node {} as not reachable", header.sender, entry.node_id);
+                        }
+                        if self.cluster.mark_failing_if_needed(&entry.node_id, self.node_timeout()) {
+                            self.broadcast_fail(&entry.node_id);
+                        }
+                    } else {
+                        self.cluster.del_failure_report(&entry.node_id, &header.sender);
+                    }
+                }
+            } else if entry.flags & (FLAG_NOADDR | FLAG_HANDSHAKE) == 0 && !self.is_blacklisted(&entry.node_id) {
+                if let Some(addr) = entry.addr() {
+                    self.start_handshake(addr, false);
+                }
+            }
+        }
+    }
+
+    /// Periodic work: keep links up, ping nodes, detect failures
+    fn cron(self: &Arc<Self>, iteration: u64) {
+        let now = mstime();
+        let timeout = self.node_timeout();
+        let handshake_timeout = timeout.max(1000);
+
+        for node in self.cluster.get_all_nodes() {
+            if node.id == self.cluster.my_id() {
+                continue;
+            }
+            if node.in_handshake() && now.saturating_sub(node.ctime) > handshake_timeout {
+                debug!("Handshake with {:?} timed out", node.addr);
+                self.drop_node(&node.id);
+                continue;
+            }
+            if !self.links.lock().unwrap().contains_key(&node.id) {
+                self.connect(&node);
+            }
+        }
+
+        // Every second ping the node we have not heard from the longest,
+        // among a few random ones
+        if iteration.is_multiple_of(10) {
+            let mut candidates: Vec<ClusterNode> = self
+                .cluster
+                .get_all_nodes()
+                .into_iter()
+                .filter(|n| n.id != self.cluster.my_id() && !n.in_handshake() && n.ping_sent == 0)
+                .collect();
+            candidates.shuffle(&mut rand::thread_rng());
+            if let Some(node) = candidates.iter().take(5).min_by_key(|n| n.pong_recv) {
+                self.send_ping(&node.id);
+            }
+        }
+
+        for node in self.cluster.get_all_nodes() {
+            if node.id == self.cluster.my_id() || node.in_handshake() {
+                continue;
+            }
+
+            // No answer for half the timeout on a link older than the
+            // timeout: try a fresh connection
+            if node.ping_sent != 0 && now.saturating_sub(node.ping_sent) > timeout / 2 {
+                let mut links = self.links.lock().unwrap();
+                if links.get(&node.id).is_some_and(|l| now.saturating_sub(l.created) > timeout) {
+                    if let Some(link) = links.remove(&node.id) {
+                        link.task.abort();
+                    }
+                }
+            }
+
+            // Make sure every node is pinged at least every timeout / 2
+            if node.ping_sent == 0 && now.saturating_sub(node.pong_recv) > timeout / 2 {
+                self.send_ping(&node.id);
+            }
+
+            if node.ping_sent != 0
+                && now.saturating_sub(node.ping_sent) > timeout
+                && !node.is_pfail()
+                && !node.is_failed()
+            {
+                if let Some(mut entry) = self.cluster.nodes.get_mut(&node.id) {
+                    entry.add_flag(NodeFlags::PFail);
+                }
+                debug!("*** NODE {} possibly failing", node.id);
+            }
+        }
+
+        let validity = timeout * FAIL_REPORT_VALIDITY_MULT;
+        let pfail: Vec<String> = self
+            .cluster
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| n.is_pfail())
+            .map(|n| n.id)
+            .collect();
+        for node_id in pfail {
+            self.cluster.count_failure_reports(&node_id, validity);
+            if self.cluster.mark_failing_if_needed(&node_id, timeout) {
+                self.broadcast_fail(&node_id);
+            }
+        }
+
+        self.manual_failover_check();
+        self.handle_replica_failover();
+        self.drop_lost_shard_channels();
+        self.flush_config();
+    }
+
+    /// Open an outbound link to `node` and queue the first PING (or MEET)
+    fn connect(self: &Arc<Self>, node: &ClusterNode) {
+        let Some(addr) = node.bus_addr() else {
+            return;
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let msg_type = if node.flags.contains(&NodeFlags::Meet) { MessageType::Meet } else { MessageType::Ping };
+        let _ = tx.send(self.build_message(msg_type, Some(&node.id)));
+
+        if let Some(mut entry) = self.cluster.nodes.get_mut(&node.id) {
+            // If the connection never comes up the node still times out
+            if entry.ping_sent == 0 {
+                entry.ping_sent = mstime();
+            }
+        }
+
+        let link_id = self.next_link_id.fetch_add(1, Ordering::Relaxed);
+        let bus = Arc::clone(self);
+        let node_id = node.id.clone();
+        let timeout = Duration::from_millis(self.node_timeout());
+        let task = tokio::spawn(async move { bus.run_link(node_id, link_id, addr, timeout, rx).await });
+
+        self.links
+            .lock()
+            .unwrap()
+            .insert(node.id.clone(), Link { id: link_id, created: mstime(), tx, task: task.abort_handle() });
+    }
+
+    /// Drive an outbound link until it fails
+    async fn run_link(
+        self: Arc<Self>,
+        mut node_id: String,
+        link_id: u64,
+        addr: SocketAddr,
+        timeout: Duration,
+        mut rx: mpsc::UnboundedReceiver<ClusterMessage>,
+    ) {
+        let stream = match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+            Ok(Ok(stream)) => Some(stream),
+            _ => None,
+        };
+
+        if let Some(stream) = stream {
+            let _ = stream.set_nodelay(true);
+            self.set_link_state(&node_id, "connected");
+            let (mut reader, mut writer) = stream.into_split();
+
+            loop {
+                tokio::select! {
+                    msg = rx.recv() => {
+                        let Some(msg) = msg else { break };
+                        if writer.write_all(&msg.encode()).await.is_err() {
+                            break;
+                        }
+                        self.cluster.record_sent();
+                        // The MEET only needs to be sent once
+                        if msg.header.msg_type == MessageType::Meet {
+                            if let Some(mut node) = self.cluster.nodes.get_mut(&node_id) {
+                                node.remove_flag(&NodeFlags::Meet);
+                            }
+                        }
+                    }
+                    msg = read_message(&mut reader) => {
+                        let Ok(msg) = msg else { break };
+                        if let Some(reply) = self.handle_message(msg, Some(addr.ip()), Some(&mut node_id)) {
+                            if writer.write_all(&reply.encode()).await.is_err() {
+                                break;
+                            }
+                            self.cluster.record_sent();
+                        }
+                    }
+                }
+            }
+        }
+
+        self.set_link_state(&node_id, "disconnected");
+        let mut links = self.links.lock().unwrap();
+        if links.get(&node_id).is_some_and(|l| l.id == link_id) {
+            links.remove(&node_id);
+        }
+    }
+
+    fn set_link_state(&self, node_id: &str, state: &str) {
+        if let Some(mut node) = self.cluster.nodes.get_mut(node_id) {
+            node.link_state = state.to_string();
+        }
+    }
+
+    /// Serve a connection opened by another node
+    async fn run_inbound(self: Arc<Self>, stream: TcpStream, peer: SocketAddr) {
+        let link_id = self.next_link_id.fetch_add(1, Ordering::Relaxed);
+        self.inbound
+            .lock()
+            .unwrap()
+            .insert(link_id, InboundLink { node_id: None, created: mstime() });
+        self.serve_inbound(stream, peer, link_id).await;
+        self.inbound.lock().unwrap().remove(&link_id);
+    }
+
+    async fn serve_inbound(&self, mut stream: TcpStream, peer: SocketAddr, link_id: u64) {
+        let _ = stream.set_nodelay(true);
+        loop {
+            let msg = match read_message(&mut stream).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    debug!("Cluster bus connection from {} closed: {}", peer, e);
+                    return;
+                }
+            };
+
+            if self.cluster.nodes.contains_key(&msg.header.sender) {
+                if let Some(link) = self.inbound.lock().unwrap().get_mut(&link_id) {
+                    link.node_id = Some(msg.header.sender.clone());
+                }
+            }
+
+            // Learn our own address from the first MEET if we bound to a
+            // wildcard address
+            if msg.header.msg_type == MessageType::Meet {
+                let my_addr = self.cluster.myself().and_then(|n| n.addr);
+                if let (Some(addr), Ok(local)) = (my_addr, stream.local_addr()) {
+                    if addr.ip().is_unspecified() {
+                        self.cluster.set_my_addr(SocketAddr::new(local.ip(), addr.port()));
+                    }
+                }
+            }
+
+            if let Some(reply) = self.handle_message(msg, Some(peer.ip()), None) {
+                if stream.write_all(&reply.encode()).await.is_err() {
+                    return;
+                }
+                self.cluster.record_sent();
+            }
+        }
+    }
+}
+
+/// Read one message from the bus
+async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<ClusterMessage> {
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+
+    let mut prefix = [0u8; 8];
+    reader.read_exact(&mut prefix).await?;
+    let len = ClusterMessage::frame_len(&prefix).map_err(invalid)?;
+
+    let mut data = vec![0u8; len];
+    data[..8].copy_from_slice(&prefix);
+    reader.read_exact(&mut data[8..]).await?;
+    ClusterMessage::decode(&data).map_err(invalid)
+}
+
+/// Random 40 hex chars id for a node whose real id is not known yet
+fn random_node_id() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..40).map(|_| format!("{:x}", rng.gen_range(0..16u8))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus() -> ClusterBus {
+        ClusterBus::new(
+            Arc::new(ClusterState::new(true)),
+            Arc::new(MigrationManager::new()),
+            Arc::new(Config::new()),
+        )
+    }
+
+    fn header_from(node: &ClusterNode, msg_type: MessageType, epoch: u64) -> ClusterMessage {
+        ClusterMessage {
+            header: MessageHeader::for_node(msg_type, node, epoch),
+            body: MessageBody::Gossip(vec![]),
+        }
+    }
+
+    #[test]
+    fn test_ping_gets_pong() {
+        let bus = bus();
+        let other = ClusterNode::new_master("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        let reply = bus.handle_message(header_from(&other, MessageType::Ping, 0), None, None).unwrap();
+        assert_eq!(reply.header.msg_type, MessageType::Pong);
+        assert_eq!(reply.header.sender, bus.cluster.my_id());
+        // Unknown senders are not added by a PING
+        assert!(bus.cluster.get_node(&other.id).is_none());
+    }
+
+    #[test]
+    fn test_meet_starts_handshake_and_pong_completes_it() {
+        let bus = bus();
+        let mut other = ClusterNode::new_master("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+
+        bus.handle_message(header_from(&other, MessageType::Meet, 0), None, None);
+        let placeholder = bus
+            .cluster
+            .get_all_nodes()
+            .into_iter()
+            .find(|n| n.in_handshake())
+            .expect("handshake node");
+        assert_eq!(placeholder.addr, other.addr);
+
+        // The reply on the outbound link reveals the real id and slots
+        other.add_slot_range(0, 9);
+        other.config_epoch = 3;
+        let mut link_node = placeholder.id.clone();
+        bus.handle_message(header_from(&other, MessageType::Pong, 5), None, Some(&mut link_node));
+
+        assert_eq!(link_node, other.id);
+        assert!(bus.cluster.get_node(&placeholder.id).is_none());
+        let node = bus.cluster.get_node(&other.id).unwrap();
+        assert!(node.is_master() && !node.in_handshake());
+        assert_eq!(bus.cluster.get_slot_node(5), Some(other.id.clone()));
+        assert_eq!(node.config_epoch, 3);
+        assert_eq!(bus.cluster.current_epoch(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_higher_config_epoch_wins_slots() {
+        let bus = bus();
+        bus.cluster.add_slot(100);
+        bus.cluster.set_my_config_epoch(1);
+
+        // An id below ours, so that equal epochs do not make us bump ours
+        let mut other = ClusterNode::new_master("0".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        bus.cluster.add_node(other.clone());
+        other.add_slot(100);
+
+        other.config_epoch = 1;
+        bus.handle_message(header_from(&other, MessageType::Ping, 1), None, None);
+        assert!(bus.cluster.owns_slot(100));
+
+        other.config_epoch = 2;
+        bus.handle_message(header_from(&other, MessageType::Ping, 2), None, None);
+        assert_eq!(bus.cluster.get_slot_node(100), Some(other.id.clone()));
+        assert_eq!(bus.cluster.count_my_slots(), 0);
+        // Left without slots, we follow the node that took them
+        assert_eq!(bus.cluster.myself().unwrap().master_id, Some(other.id.clone()));
+    }
+
+    #[test]
+    fn test_epoch_collision_bumps_smaller_id() {
+        let bus = bus();
+        // Our id is a hex timestamp, "f..." sorts after it
+        let other = ClusterNode::new_master("f".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        bus.cluster.add_node(other.clone());
+
+        bus.handle_message(header_from(&other, MessageType::Ping, 0), None, None);
+        assert_eq!(bus.cluster.my_config_epoch(), 1);
+        assert_eq!(bus.cluster.current_epoch(), 1);
+    }
+
+    #[test]
+    fn test_failure_reports_promote_to_fail() {
+        let bus = bus();
+        bus.cluster.add_slot(0);
+
+        let mut masters = Vec::new();
+        for (i, c) in ["b", "c"].iter().enumerate() {
+            let mut node = ClusterNode::new_master(c.repeat(40), Some(format!("127.0.0.1:700{}", i + 1).parse().unwrap()));
+            node.add_slot(i as u16 + 1);
+            bus.cluster.add_node(node.clone());
+            bus.cluster.assign_slots_to_node(&node.id, vec![i as u16 + 1]);
+            masters.push(node);
+        }
+        let mut victim = masters[1].clone();
+        victim.add_flag(NodeFlags::PFail);
+
+        // Three masters serve slots: two votes are needed
+        let mut ping = header_from(&masters[0], MessageType::Ping, 0);
+        ping.body = MessageBody::Gossip(vec![GossipEntry::for_node(&victim)]);
+        bus.handle_message(ping.clone(), None, None);
+        assert!(!bus.cluster.get_node(&victim.id).unwrap().is_failed());
+        assert_eq!(bus.cluster.get_node(&victim.id).unwrap().fail_reports.len(), 1);
+
+        // Our own PFAIL view is the second vote
+        bus.cluster.nodes.get_mut(&victim.id).unwrap().add_flag(NodeFlags::PFail);
+        bus.handle_message(ping, None, None);
+        let node = bus.cluster.get_node(&victim.id).unwrap();
+        assert!(node.is_failed());
+        assert!(!node.is_pfail());
+        assert!(!bus.cluster.state_ok());
+    }
+
+    #[test]
+    fn test_fail_message_and_recovery() {
+        let bus = bus();
+        let reporter = ClusterNode::new_master("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        let replica = ClusterNode::new_replica("c".repeat(40), Some("127.0.0.1:7002".parse().unwrap()), reporter.id.clone());
+        bus.cluster.add_node(reporter.clone());
+        bus.cluster.add_node(replica.clone());
+
+        let mut fail = header_from(&reporter, MessageType::Fail, 0);
+        fail.body = MessageBody::Fail { node_id: replica.id.clone() };
+        bus.handle_message(fail, None, None);
+        assert!(bus.cluster.get_node(&replica.id).unwrap().is_failed());
+
+        // A replica answering again is cleared right away
+        bus.handle_message(header_from(&replica, MessageType::Pong, 0), None, None);
+        assert!(!bus.cluster.get_node(&replica.id).unwrap().is_failed());
+    }
+
+    #[test]
+    fn test_gossip_about_unknown_node_starts_handshake() {
+        let bus = bus();
+        let sender = ClusterNode::new_master("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        let stranger = ClusterNode::new_master("c".repeat(40), Some("127.0.0.1:7002".parse().unwrap()));
+        bus.cluster.add_node(sender.clone());
+
+        let mut ping = header_from(&sender, MessageType::Ping, 0);
+        ping.body = MessageBody::Gossip(vec![GossipEntry::for_node(&stranger)]);
+        bus.handle_message(ping.clone(), None, None);
+        bus.handle_message(ping, None, None);
+
+        let handshakes: Vec<_> = bus.cluster.get_all_nodes().into_iter().filter(|n| n.in_handshake()).collect();
+        assert_eq!(handshakes.len(), 1);
+        assert_eq!(handshakes[0].addr, stranger.addr);
+    }
+
+    /// A failed master "b" with one slot, its replicas "c" and "d", and us
+    /// as a master serving slot 0
+    fn failover_cluster() -> (ClusterBus, ClusterNode, ClusterNode, ClusterNode) {
+        let bus = bus();
+        bus.cluster.add_slot(0);
+        let mut master = ClusterNode::new_master("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()));
+        master.add_slot(1);
+        master.config_epoch = 1;
+        bus.cluster.add_node(master.clone());
+        bus.cluster.assign_slots_to_node(&master.id, vec![1]);
+        let replica = |c: &str, port| {
+            let mut node = ClusterNode::new_replica(c.repeat(40), Some(format!("127.0.0.1:{}", port).parse().unwrap()), master.id.clone());
+            node.add_slot(1);
+            node.config_epoch = 1;
+            bus.cluster.add_node(node.clone());
+            node
+        };
+        let (c, d) = (replica("c", 7002), replica("d", 7003));
+        (bus, master, c, d)
+    }
+
+    fn auth_request(replica: &ClusterNode, epoch: u64) -> ClusterMessage {
+        ClusterMessage {
+            header: MessageHeader::for_node(MessageType::FailoverAuthRequest, replica, epoch),
+            body: MessageBody::Empty,
+        }
+    }
+
+    #[test]
+    fn test_failover_vote_once_per_epoch() {
+        let (bus, master, c, d) = failover_cluster();
+        bus.cluster.observe_epoch(2);
+
+        // The master is still up: no vote unless the replica forces it
+        assert!(bus.handle_message(auth_request(&c, 2), None, None).is_none());
+        let mut forced = auth_request(&c, 2);
+        forced.header.mflags |= MFLAG_FORCEACK;
+        let ack = bus.handle_message(forced, None, None).unwrap();
+        assert_eq!(ack.header.msg_type, MessageType::FailoverAuthAck);
+        assert_eq!(ack.header.sender, bus.cluster.my_id());
+
+        // One vote per epoch, and none for the same master right after
+        bus.cluster.nodes.get_mut(&master.id).unwrap().add_flag(NodeFlags::Fail);
+        assert!(bus.handle_message(auth_request(&d, 2), None, None).is_none());
+        bus.cluster.observe_epoch(3);
+        assert!(bus.handle_message(auth_request(&d, 3), None, None).is_none());
+
+        // A stale epoch is never granted
+        bus.cluster.nodes.get_mut(&master.id).unwrap().voted_time = 0;
+        assert!(bus.handle_message(auth_request(&d, 2), None, None).is_none());
+        assert!(bus.handle_message(auth_request(&d, 3), None, None).is_some());
+    }
+
+    #[test]
+    fn test_failover_vote_denied_for_stale_slots() {
+        let (bus, master, c, _) = failover_cluster();
+        bus.cluster.nodes.get_mut(&master.id).unwrap().add_flag(NodeFlags::Fail);
+        // Slot 1 moved to a node with a newer config than the replica knows
+        bus.cluster.nodes.get_mut(&master.id).unwrap().config_epoch = 5;
+        assert!(bus.handle_message(auth_request(&c, 0), None, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failover_acks_and_takeover() {
+        let (bus, master, c, _) = failover_cluster();
+        let me = bus.cluster.my_id();
+        // Become "c" in this cluster: replace it with ourselves
+        bus.cluster.remove_node(&c.id);
+        bus.cluster.set_my_master(&master.id);
+
+        // Acks only count during an election, from masters serving slots
+        let voter = ClusterMessage {
+            header: MessageHeader::for_node(MessageType::FailoverAuthAck, &master, 4),
+            body: MessageBody::Empty,
+        };
+        bus.handle_message(voter.clone(), None, None);
+        assert_eq!(bus.failover.lock().unwrap().auth_count, 0);
+        {
+            let mut failover = bus.failover.lock().unwrap();
+            failover.auth_sent = true;
+            failover.auth_epoch = 4;
+        }
+        bus.handle_message(voter, None, None);
+        assert_eq!(bus.failover.lock().unwrap().auth_count, 1);
+
+        bus.takeover();
+        let myself = bus.cluster.myself().unwrap();
+        assert!(myself.is_master());
+        assert_eq!(bus.cluster.get_slot_node(1), Some(me));
+        assert!(myself.config_epoch > master.config_epoch);
+        assert!(bus.cluster.get_node(&master.id).unwrap().slots.is_empty());
+    }
+}
@@ -39,34 +39,45 @@ impl Default for ConfigEpoch {
 
 /// Save cluster configuration to nodes.conf
 ///
-/// Format (one line per node):
+/// Format (one line per node, each with its own config epoch):
 /// <id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> <slot> ... <slot>
 ///
+/// followed by the current epoch of the cluster:
+/// vars currentEpoch <epoch>
+///
 /// Example:
 /// 07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:6379@16379 myself,master - 0 0 1 connected 0-5460
+/// vars currentEpoch 1
+///
+/// The file is written to a temporary path first and renamed over the old
+/// one, so a crash never leaves a truncated nodes.conf behind.
 pub fn save_cluster_config(
     cluster: &Arc<ClusterState>,
-    config_epoch: u64,
+    current_epoch: u64,
     file_path: &str,
 ) -> io::Result<()> {
     if !cluster.enabled {
         return Ok(()); // Don't save if cluster mode disabled
     }
 
+    let tmp_path = format!("{}.tmp-{}", file_path, std::process::id());
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(file_path)?;
+        .open(&tmp_path)?;
 
-    let nodes = cluster.get_all_nodes();
+    let mut nodes = cluster.get_all_nodes();
+    nodes.sort_by(|a, b| b.is_myself().cmp(&a.is_myself()).then_with(|| a.id.cmp(&b.id)));
 
-    for node in nodes {
-        let line = format_node_config_line(&node, config_epoch);
+    for node in nodes.iter().filter(|n| !n.in_handshake()) {
+        let line = format_node_config_line(node, node.config_epoch);
         writeln!(file, "{}", line)?;
     }
+    writeln!(file, "vars currentEpoch {}", current_epoch.max(cluster.current_epoch()))?;
 
     file.sync_all()?;
+    std::fs::rename(&tmp_path, file_path)?;
     Ok(())
 }
 
@@ -137,7 +148,24 @@ pub fn load_cluster_config(
             continue;
         }
 
-        if let Some((node, epoch)) = parse_node_config_line(&line) {
+        if let Some(vars) = line.strip_prefix("vars ") {
+            let parts: Vec<&str> = vars.split_whitespace().collect();
+            for pair in parts.chunks(2) {
+                if let [name, value] = pair {
+                    if *name == "currentEpoch" {
+                        max_epoch = max_epoch.max(value.parse().unwrap_or(0));
+                    }
+                }
+            }
+            continue;
+        }
+
+        if let Some((mut node, epoch)) = parse_node_config_line(&line) {
+            node.config_epoch = epoch;
+            if node.is_myself() {
+                // The saved identity of this node replaces the fresh one
+                cluster.set_my_id(node.id.clone());
+            }
             cluster.add_node(node.clone());
 
             // Update slot assignments
@@ -150,6 +178,9 @@ pub fn load_cluster_config(
         }
     }
 
+    cluster.observe_epoch(max_epoch);
+    // What was just loaded is what is on disk
+    cluster.take_dirty();
     Ok(max_epoch)
 }
 
@@ -386,6 +417,36 @@ mod tests {
         assert_eq!(epoch.get(), 2);
     }
 
+    #[test]
+    fn test_save_and_load_keeps_identity_and_epochs() {
+        let cluster = Arc::new(ClusterState::new(true));
+        let my_id = cluster.my_id();
+        cluster.assign_slots_to_node(&my_id, (0..=100).collect());
+        cluster.set_my_config_epoch(3);
+        cluster.observe_epoch(7);
+
+        let mut other = ClusterNode::new_master("other".to_string(), Some("127.0.0.1:7001".parse().unwrap()));
+        other.config_epoch = 5;
+        cluster.add_node(other);
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap();
+        save_cluster_config(&cluster, cluster.current_epoch(), path).unwrap();
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.lines().next().unwrap().starts_with(&my_id));
+        assert!(content.contains("vars currentEpoch 7"));
+
+        let cluster2 = Arc::new(ClusterState::new(true));
+        assert_eq!(load_cluster_config(&cluster2, path).unwrap(), 7);
+        assert_eq!(cluster2.my_id(), my_id);
+        assert_eq!(cluster2.get_all_nodes().len(), 2);
+        assert_eq!(cluster2.my_config_epoch(), 3);
+        assert_eq!(cluster2.current_epoch(), 7);
+        assert_eq!(cluster2.get_node("other").unwrap().config_epoch, 5);
+        assert!(cluster2.owns_slot(100));
+    }
+
     #[test]
     fn test_load_nonexistent_config() {
         let cluster = Arc::new(ClusterState::new(true));
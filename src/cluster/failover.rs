@@ -0,0 +1,150 @@
+// Replica failover state
+//
+// A replica whose master is flagged FAIL waits a delay that grows with its
+// rank (replicas with the most data go first), bumps the current epoch and
+// asks every master for its vote with a FAILOVER_AUTH_REQUEST. A master
+// votes at most once per epoch, and only once per failed master in two
+// node timeouts. The replica that collects votes from a majority of the
+// masters serving slots takes over the slots of its master with the
+// election epoch as its new config epoch.
+//
+// A manual failover (CLUSTER FAILOVER) starts on the replica with an
+// MFSTART message: the master pauses writes and announces its replication
+// offset, and the replica starts an election that the masters accept even
+// though its master is not failing once it has caught up with that offset.
+
+/// Replica validity factor used when cluster-replica-validity-factor is unset
+pub const DEFAULT_VALIDITY_FACTOR: u64 = 10;
+/// How long a manual failover may take before it is aborted (milliseconds)
+pub const MANUAL_FAILOVER_TIMEOUT: u64 = 5000;
+/// Extra delay per rank position before asking for votes (milliseconds)
+pub const RANK_DELAY: u64 = 1000;
+/// Replication ping period the data age allowance accounts for (milliseconds)
+pub const REPL_PING_PERIOD: u64 = 10_000;
+
+/// Election and manual failover bookkeeping of the local node
+#[derive(Debug, Default)]
+pub struct FailoverState {
+    /// When the current (or last) election may start asking for votes
+    pub auth_time: u64,
+    /// Votes received in the current election
+    pub auth_count: usize,
+    /// Whether the vote request of the current election went out
+    pub auth_sent: bool,
+    /// Rank used to compute the delay of the current election
+    pub auth_rank: usize,
+    /// Epoch of the current election
+    pub auth_epoch: u64,
+    /// Last epoch this master voted in
+    pub last_vote_epoch: u64,
+    /// Manual failover deadline, 0 when none is in progress
+    pub mf_end: u64,
+    /// Master side: the replica we paused writes for
+    pub mf_replica: Option<String>,
+    /// Replica side: offset the paused master announced
+    pub mf_master_offset: Option<u64>,
+    /// Replica side: caught up with the master, the election may start
+    pub mf_can_start: bool,
+}
+
+impl FailoverState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort any manual failover in progress
+    pub fn reset_manual(&mut self) {
+        self.mf_end = 0;
+        self.mf_replica = None;
+        self.mf_master_offset = None;
+        self.mf_can_start = false;
+    }
+
+    /// Whether a manual failover is in progress at `now`
+    pub fn manual_in_progress(&self, now: u64) -> bool {
+        self.mf_end != 0 && now < self.mf_end
+    }
+
+    /// Master side: clients are paused for a manual failover
+    pub fn writes_paused(&self, now: u64) -> bool {
+        self.manual_in_progress(now) && self.mf_replica.is_some()
+    }
+
+    /// Delay before a replica of the given rank asks for votes: half a
+    /// second so the FAIL message spreads, a random jitter so replicas
+    /// don't all start together, and a second per better replica
+    pub fn election_delay(rank: usize, jitter: u64) -> u64 {
+        500 + jitter + rank as u64 * RANK_DELAY
+    }
+
+    /// Prepare a new election starting at `auth_time`
+    pub fn schedule(&mut self, auth_time: u64, rank: usize) {
+        self.auth_time = auth_time;
+        self.auth_count = 0;
+        self.auth_sent = false;
+        self.auth_rank = rank;
+    }
+
+    /// The rank got worse while waiting: push the election back
+    pub fn update_rank(&mut self, rank: usize) {
+        if !self.auth_sent && rank > self.auth_rank {
+            self.auth_time += (rank - self.auth_rank) as u64 * RANK_DELAY;
+            self.auth_rank = rank;
+        }
+    }
+}
+
+/// Rank of a replica among the replicas of the same master: the number
+/// of replicas with a greater replication offset
+pub fn replica_rank(my_offset: u64, other_offsets: impl IntoIterator<Item = u64>) -> usize {
+    other_offsets.into_iter().filter(|&offset| offset > my_offset).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replica_rank() {
+        assert_eq!(replica_rank(100, vec![]), 0);
+        assert_eq!(replica_rank(100, vec![50, 100]), 0);
+        assert_eq!(replica_rank(100, vec![150, 90, 101]), 2);
+    }
+
+    #[test]
+    fn test_election_delay_grows_with_rank() {
+        let mut state = FailoverState::new();
+        state.schedule(1000 + FailoverState::election_delay(0, 0), 0);
+        assert_eq!(state.auth_time, 1500);
+
+        state.update_rank(2);
+        assert_eq!(state.auth_time, 1500 + 2 * RANK_DELAY);
+        assert_eq!(state.auth_rank, 2);
+
+        // A better rank never makes the election start earlier
+        state.update_rank(1);
+        assert_eq!(state.auth_time, 1500 + 2 * RANK_DELAY);
+
+        state.auth_sent = true;
+        state.update_rank(3);
+        assert_eq!(state.auth_rank, 2);
+    }
+
+    #[test]
+    fn test_manual_failover_pause() {
+        let mut state = FailoverState::new();
+        assert!(!state.writes_paused(0));
+
+        state.mf_end = 1000 + MANUAL_FAILOVER_TIMEOUT;
+        assert!(state.manual_in_progress(1000));
+        // Only the master pauses
+        assert!(!state.writes_paused(1000));
+        state.mf_replica = Some("replica".to_string());
+        assert!(state.writes_paused(1000));
+        assert!(!state.writes_paused(1000 + MANUAL_FAILOVER_TIMEOUT));
+
+        state.reset_manual();
+        assert!(!state.manual_in_progress(1000));
+        assert!(!state.writes_paused(1000));
+    }
+}
@@ -0,0 +1,245 @@
+// Key extraction for cluster routing
+//
+// Mirrors the key specs Redis publishes through COMMAND INFO: most
+// commands take their keys from a fixed range of arguments, a few (EVAL,
+// ZUNIONSTORE, XREAD, MIGRATE, ...) have to parse their arguments to find
+// them.
+
+/// Fixed key positions: arguments `first..=last` every `step`, where a
+/// negative `last` counts from the end (-1 is the last argument)
+#[derive(Debug, Clone, Copy)]
+struct KeyRange {
+    first: usize,
+    last: isize,
+    step: usize,
+}
+
+const fn range(first: usize, last: isize, step: usize) -> KeyRange {
+    KeyRange { first, last, step }
+}
+
+/// A single key right after the command name
+const ONE: KeyRange = range(1, 1, 1);
+/// Every argument is a key
+const ALL: KeyRange = range(1, -1, 1);
+
+/// How a command's keys are located
+enum KeySpec {
+    Range(KeyRange),
+    /// `numkeys` at `at`, followed by that many keys; `dest` when the
+    /// first argument is a destination key as well
+    NumKeys { at: usize, dest: bool },
+    /// Keys of XREAD / XREADGROUP, the first half of the arguments after STREAMS
+    Streams,
+    /// GEORADIUS / SORT style: one key plus the destination after a STORE option
+    StoreOption,
+    /// MIGRATE host port key|"" db timeout [... KEYS key ...]
+    Migrate,
+}
+
+/// Key spec and write flag of a command, or None for keyless commands
+fn command_spec(cmd: &str) -> Option<(KeySpec, bool)> {
+    use KeySpec::*;
+
+    let spec = match cmd {
+        // Reads of a single key
+        "GET" | "STRLEN" | "GETRANGE" | "SUBSTR" | "GETBIT" | "BITCOUNT" | "BITPOS" | "BITFIELD_RO"
+        | "TYPE" | "TTL" | "PTTL" | "EXPIRETIME" | "PEXPIRETIME" | "DUMP" | "LLEN" | "LRANGE"
+        | "LINDEX" | "LPOS" | "HGET" | "HEXISTS" | "HGETALL" | "HKEYS" | "HVALS" | "HLEN" | "HMGET"
+        | "HSTRLEN" | "HSCAN" | "HRANDFIELD" | "HTTL" | "HPTTL" | "HEXPIRETIME" | "HPEXPIRETIME"
+        | "SMEMBERS" | "SISMEMBER" | "SMISMEMBER" | "SCARD" | "SRANDMEMBER" | "SSCAN" | "ZSCORE"
+        | "ZMSCORE" | "ZCARD" | "ZCOUNT" | "ZLEXCOUNT" | "ZRANGE" | "ZREVRANGE" | "ZRANGEBYSCORE"
+        | "ZREVRANGEBYSCORE" | "ZRANGEBYLEX" | "ZREVRANGEBYLEX" | "ZRANK" | "ZREVRANK"
+        | "ZRANDMEMBER" | "ZSCAN" | "GEOPOS" | "GEODIST" | "GEOHASH" | "GEOSEARCH"
+        | "GEORADIUS_RO" | "GEORADIUSBYMEMBER_RO" | "XLEN" | "XRANGE" | "XREVRANGE"
+        | "XPENDING" | "SORT_RO" => (Range(ONE), false),
+
+        // Writes of a single key
+        "SET" | "SETEX" | "PSETEX" | "SETNX" | "GETSET" | "GETEX" | "GETDEL" | "APPEND"
+        | "SETRANGE" | "INCR" | "DECR" | "INCRBY" | "DECRBY" | "INCRBYFLOAT" | "SETBIT"
+        | "BITFIELD" | "PFADD" | "EXPIRE" | "EXPIREAT" | "PEXPIRE" | "PEXPIREAT" | "PERSIST"
+        | "RESTORE" | "MOVE" | "LPUSH" | "RPUSH" | "LPUSHX" | "RPUSHX" | "LPOP" | "RPOP"
+        | "LSET" | "LTRIM" | "LREM" | "LINSERT" | "HSET" | "HSETNX" | "HMSET" | "HDEL"
+        | "HINCRBY" | "HINCRBYFLOAT" | "HEXPIRE" | "HPEXPIRE" | "HEXPIREAT" | "HPEXPIREAT"
+        | "HPERSIST" | "HGETDEL" | "HGETEX" | "HSETEX" | "SADD" | "SREM" | "SPOP" | "ZADD"
+        | "ZREM" | "ZINCRBY" | "ZPOPMIN" | "ZPOPMAX" | "ZREMRANGEBYRANK" | "ZREMRANGEBYSCORE"
+        | "ZREMRANGEBYLEX" | "GEOADD" | "XADD" | "XDEL" | "XTRIM" | "XACK" | "XCLAIM"
+        | "XAUTOCLAIM" | "XSETID" => (Range(ONE), true),
+
+        // Subcommand first, then the key
+        "OBJECT" | "XINFO" | "MEMORY" => (Range(range(2, 2, 1)), false),
+        "XGROUP" => (Range(range(2, 2, 1)), true),
+
+        // Several keys
+        "EXISTS" | "MGET" | "PFCOUNT" | "SINTER" | "SUNION" | "SDIFF" | "TOUCH" | "WATCH" => {
+            (Range(ALL), false)
+        }
+        "DEL" | "UNLINK" | "PFMERGE" | "SINTERSTORE" | "SUNIONSTORE" | "SDIFFSTORE" => {
+            (Range(ALL), true)
+        }
+        "MSET" | "MSETNX" => (Range(range(1, -1, 2)), true),
+        "RENAME" | "RENAMENX" | "COPY" | "SMOVE" | "RPOPLPUSH" | "LMOVE" | "BLMOVE"
+        | "BRPOPLPUSH" | "ZRANGESTORE" | "GEOSEARCHSTORE" => (Range(range(1, 2, 1)), true),
+        "LCS" => (Range(range(1, 2, 1)), false),
+        // The last argument is the timeout
+        "BLPOP" | "BRPOP" | "BZPOPMIN" | "BZPOPMAX" => (Range(range(1, -2, 1)), true),
+        "BITOP" => (Range(range(2, -1, 1)), true),
+
+        // numkeys-prefixed key lists
+        "EVAL" | "EVALSHA" | "FCALL" => (NumKeys { at: 2, dest: false }, true),
+        "EVAL_RO" | "EVALSHA_RO" | "FCALL_RO" => (NumKeys { at: 2, dest: false }, false),
+        "ZUNION" | "ZINTER" | "ZDIFF" | "ZINTERCARD" | "SINTERCARD" => {
+            (NumKeys { at: 1, dest: false }, false)
+        }
+        "LMPOP" | "ZMPOP" => (NumKeys { at: 1, dest: false }, true),
+        "BLMPOP" | "BZMPOP" => (NumKeys { at: 2, dest: false }, true),
+        "ZUNIONSTORE" | "ZINTERSTORE" | "ZDIFFSTORE" => (NumKeys { at: 2, dest: true }, true),
+
+        "XREAD" => (Streams, false),
+        "XREADGROUP" => (Streams, true),
+        "GEORADIUS" | "GEORADIUSBYMEMBER" | "SORT" => (StoreOption, true),
+        "MIGRATE" => (Migrate, true),
+
+        // Shard channels hash to slots like keys
+        "SPUBLISH" => (Range(ONE), false),
+        "SSUBSCRIBE" | "SUNSUBSCRIBE" => (Range(ALL), false),
+
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// Keys accessed by a command, in argument order. `args` includes the
+/// command name; `cmd` is its upper-cased form. Keyless commands and
+/// malformed argument lists yield no keys and are left to the command
+/// itself to reject.
+pub fn command_keys<'a>(cmd: &str, args: &'a [Vec<u8>]) -> Vec<&'a [u8]> {
+    let Some((spec, _)) = command_spec(cmd) else {
+        return Vec::new();
+    };
+
+    let mut positions = Vec::new();
+    match spec {
+        KeySpec::Range(keys) => positions.extend(expand(keys, args.len())),
+        KeySpec::NumKeys { at, dest } => {
+            if dest && args.len() > 1 {
+                positions.push(1);
+            }
+            let numkeys = args
+                .get(at)
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or(0);
+            positions.extend((at + 1..at + 1 + numkeys).take_while(|&i| i < args.len()));
+        }
+        KeySpec::Streams => {
+            if let Some(at) = find_option(args, 1, b"STREAMS") {
+                let rest = args.len() - at - 1;
+                positions.extend(at + 1..at + 1 + rest / 2);
+            }
+        }
+        KeySpec::StoreOption => {
+            if args.len() > 1 {
+                positions.push(1);
+            }
+            for option in [&b"STORE"[..], b"STOREDIST"] {
+                if let Some(at) = find_option(args, 2, option) {
+                    if at + 1 < args.len() {
+                        positions.push(at + 1);
+                    }
+                }
+            }
+        }
+        KeySpec::Migrate => {
+            if let Some(at) = find_option(args, 6, b"KEYS") {
+                positions.extend(at + 1..args.len());
+            } else if args.len() > 3 && !args[3].is_empty() {
+                positions.push(3);
+            }
+        }
+    }
+
+    positions.into_iter().map(|i| args[i].as_slice()).collect()
+}
+
+/// Whether a command may modify the keyspace. Replicas only serve reads
+/// to clients that sent READONLY.
+pub fn is_write_command(cmd: &str) -> bool {
+    command_spec(cmd).is_some_and(|(_, write)| write)
+}
+
+/// Argument positions of a fixed key range
+fn expand(keys: KeyRange, argc: usize) -> impl Iterator<Item = usize> {
+    let last = if keys.last < 0 {
+        argc as isize + keys.last
+    } else {
+        keys.last
+    };
+    let end = (last + 1).clamp(0, argc as isize) as usize;
+    (keys.first..end).step_by(keys.step)
+}
+
+/// Position of a case-insensitive option name, searching from `from`
+fn find_option(args: &[Vec<u8>], from: usize, name: &[u8]) -> Option<usize> {
+    (from..args.len()).find(|&i| args[i].eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<Vec<u8>> {
+        line.split_whitespace().map(|a| a.as_bytes().to_vec()).collect()
+    }
+
+    fn keys(line: &str) -> Vec<String> {
+        let args = args(line);
+        let cmd = String::from_utf8_lossy(&args[0]).to_uppercase();
+        command_keys(&cmd, &args)
+            .into_iter()
+            .map(|k| String::from_utf8_lossy(k).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_key_positions() {
+        assert_eq!(keys("HGETALL h"), vec!["h"]);
+        assert_eq!(keys("ZRANGE z 0 -1"), vec!["z"]);
+        assert_eq!(keys("SUNIONSTORE d a b"), vec!["d", "a", "b"]);
+        assert_eq!(keys("MSET a 1 b 2"), vec!["a", "b"]);
+        assert_eq!(keys("RENAME a b"), vec!["a", "b"]);
+        assert_eq!(keys("BLPOP a b 0"), vec!["a", "b"]);
+        assert_eq!(keys("BITOP AND d a b"), vec!["d", "a", "b"]);
+        assert_eq!(keys("OBJECT ENCODING k"), vec!["k"]);
+        assert_eq!(keys("SPUBLISH ch msg"), vec!["ch"]);
+        assert_eq!(keys("SSUBSCRIBE a b"), vec!["a", "b"]);
+        assert!(keys("PING").is_empty());
+        assert!(keys("GET").is_empty());
+    }
+
+    #[test]
+    fn test_movable_keys() {
+        assert_eq!(keys("EVAL script 2 a b arg"), vec!["a", "b"]);
+        assert!(keys("EVAL script 0 arg").is_empty());
+        assert_eq!(keys("EVAL script 5 a"), vec!["a"]);
+        assert_eq!(keys("ZUNIONSTORE d 2 a b WEIGHTS 1 2"), vec!["d", "a", "b"]);
+        assert_eq!(keys("ZINTER 2 a b"), vec!["a", "b"]);
+        assert_eq!(keys("XREAD COUNT 2 STREAMS a b 0 0"), vec!["a", "b"]);
+        assert_eq!(keys("XREADGROUP GROUP g c STREAMS s >"), vec!["s"]);
+        assert_eq!(keys("SORT k BY w STORE d"), vec!["k", "d"]);
+        assert_eq!(keys("GEORADIUS g 0 0 1 km STOREDIST d"), vec!["g", "d"]);
+        assert_eq!(keys("MIGRATE h 1 k 0 100"), vec!["k"]);
+        let mut migrate = args("MIGRATE h 1 x 0 100 REPLACE KEYS a b");
+        migrate[3].clear();
+        assert_eq!(command_keys("MIGRATE", &migrate), vec![&b"a"[..], b"b"]);
+    }
+
+    #[test]
+    fn test_write_flag() {
+        assert!(is_write_command("SET"));
+        assert!(is_write_command("EVAL"));
+        assert!(!is_write_command("GET"));
+        assert!(!is_write_command("EVAL_RO"));
+        assert!(!is_write_command("PING"));
+    }
+}
@@ -0,0 +1,566 @@
+// Cluster bus wire format
+//
+// Every message starts with a fixed header describing the sender (id,
+// epochs, role, claimed slots, address), followed by a type specific body.
+// All integers are big-endian.
+//
+//   "RCmb" | totlen u32 | version u16 | type u16 | count u16
+//   current_epoch u64 | config_epoch u64 | offset u64
+//   sender [40] | slots [2048] | master [40] | ip [46]
+//   port u16 | cport u16 | flags u16 | state u8 | mflags u8
+//
+// PING, PONG and MEET carry `count` gossip entries about other nodes,
+// FAIL carries the id of the failed node. The failover messages
+// (FAILOVER_AUTH_REQUEST / FAILOVER_AUTH_ACK / MFSTART) are header only.
+// PUBLISHSHARD carries an SPUBLISH for the other nodes of the shard:
+//
+//   channel_len u32 | message_len u32 | channel | message
+
+use crate::cluster::node::{ClusterNode, NodeFlags};
+use crate::cluster::CLUSTER_SLOTS;
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+/// Message signature
+pub const SIGNATURE: &[u8; 4] = b"RCmb";
+/// Protocol version
+pub const VERSION: u16 = 1;
+/// Length of a node id
+pub const NAME_LEN: usize = 40;
+/// Room for a textual IPv4/IPv6 address
+pub const IP_LEN: usize = 46;
+/// Size of the claimed slots bitmap
+pub const SLOTS_BYTES: usize = CLUSTER_SLOTS as usize / 8;
+/// Size of the fixed header
+pub const HEADER_LEN: usize = 4 + 4 + 2 + 2 + 2 + 8 * 3 + NAME_LEN + SLOTS_BYTES + NAME_LEN + IP_LEN + 2 + 2 + 2 + 1 + 1;
+/// Size of a gossip entry
+pub const GOSSIP_LEN: usize = NAME_LEN + 4 + 4 + IP_LEN + 2 + 2 + 2;
+/// Upper bound accepted for a single message
+pub const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+// Node flag bits, as used by Redis
+pub const FLAG_MYSELF: u16 = 1;
+pub const FLAG_MASTER: u16 = 2;
+pub const FLAG_SLAVE: u16 = 4;
+pub const FLAG_PFAIL: u16 = 8;
+pub const FLAG_FAIL: u16 = 16;
+pub const FLAG_HANDSHAKE: u16 = 32;
+pub const FLAG_NOADDR: u16 = 64;
+
+// Message flags (mflags)
+/// The master paused its clients for a manual failover
+pub const MFLAG_PAUSED: u8 = 1;
+/// Vote for this failover even though the master is not failing
+pub const MFLAG_FORCEACK: u8 = 2;
+
+/// Convert node flags to their wire representation
+pub fn flags_to_bits(flags: &[NodeFlags]) -> u16 {
+    flags.iter().fold(0, |bits, flag| {
+        bits | match flag {
+            NodeFlags::Myself => FLAG_MYSELF,
+            NodeFlags::Master => FLAG_MASTER,
+            NodeFlags::Slave => FLAG_SLAVE,
+            NodeFlags::PFail => FLAG_PFAIL,
+            NodeFlags::Fail => FLAG_FAIL,
+            NodeFlags::Handshake => FLAG_HANDSHAKE,
+            NodeFlags::NoAddr => FLAG_NOADDR,
+            NodeFlags::NoFlags | NodeFlags::Meet => 0,
+        }
+    })
+}
+
+/// Message types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Ping,
+    Pong,
+    Meet,
+    Fail,
+    /// A replica asks the masters to vote for its promotion
+    FailoverAuthRequest,
+    /// A master grants its vote
+    FailoverAuthAck,
+    /// A replica asks its master to start a manual failover
+    MfStart,
+    /// A sharded pub/sub message for the other nodes of the shard
+    PublishShard,
+}
+
+impl MessageType {
+    fn code(self) -> u16 {
+        match self {
+            MessageType::Ping => 0,
+            MessageType::Pong => 1,
+            MessageType::Meet => 2,
+            MessageType::Fail => 3,
+            MessageType::FailoverAuthRequest => 5,
+            MessageType::FailoverAuthAck => 6,
+            MessageType::MfStart => 8,
+            MessageType::PublishShard => 10,
+        }
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        match code {
+            0 => Some(MessageType::Ping),
+            1 => Some(MessageType::Pong),
+            2 => Some(MessageType::Meet),
+            3 => Some(MessageType::Fail),
+            5 => Some(MessageType::FailoverAuthRequest),
+            6 => Some(MessageType::FailoverAuthAck),
+            8 => Some(MessageType::MfStart),
+            10 => Some(MessageType::PublishShard),
+            _ => None,
+        }
+    }
+}
+
+/// Bitmap of the slots a node claims
+#[derive(Clone, PartialEq, Eq)]
+pub struct SlotBitmap(Box<[u8; SLOTS_BYTES]>);
+
+impl SlotBitmap {
+    pub fn new() -> Self {
+        Self(Box::new([0; SLOTS_BYTES]))
+    }
+
+    pub fn set(&mut self, slot: u16) {
+        self.0[slot as usize / 8] |= 1 << (slot % 8);
+    }
+
+    pub fn contains(&self, slot: u16) -> bool {
+        self.0[slot as usize / 8] & (1 << (slot % 8)) != 0
+    }
+
+    pub fn to_set(&self) -> HashSet<u16> {
+        (0..CLUSTER_SLOTS).filter(|&s| self.contains(s)).collect()
+    }
+}
+
+impl Default for SlotBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for SlotBitmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SlotBitmap({} slots)", self.0.iter().map(|b| b.count_ones()).sum::<u32>())
+    }
+}
+
+impl<'a> FromIterator<&'a u16> for SlotBitmap {
+    fn from_iter<I: IntoIterator<Item = &'a u16>>(iter: I) -> Self {
+        let mut bitmap = Self::new();
+        for &slot in iter {
+            bitmap.set(slot);
+        }
+        bitmap
+    }
+}
+
+/// Sender description carried by every message
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageHeader {
+    pub msg_type: MessageType,
+    pub current_epoch: u64,
+    pub config_epoch: u64,
+    /// Replication offset of the sender
+    pub offset: u64,
+    pub sender: String,
+    pub slots: SlotBitmap,
+    /// Master of the sender, if it is a replica
+    pub master_id: Option<String>,
+    /// Empty when the sender does not know its own address
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub flags: u16,
+    /// Whether the sender sees the cluster as ok
+    pub state_ok: bool,
+    pub mflags: u8,
+}
+
+impl MessageHeader {
+    /// Header describing `node`
+    pub fn for_node(msg_type: MessageType, node: &ClusterNode, current_epoch: u64) -> Self {
+        let (ip, port) = match node.addr {
+            Some(addr) if !addr.ip().is_unspecified() => (addr.ip().to_string(), addr.port()),
+            Some(addr) => (String::new(), addr.port()),
+            None => (String::new(), 0),
+        };
+
+        Self {
+            msg_type,
+            current_epoch,
+            config_epoch: node.config_epoch,
+            offset: 0,
+            sender: node.id.clone(),
+            slots: node.slots.iter().collect(),
+            master_id: node.master_id.clone(),
+            ip,
+            port,
+            cport: port.wrapping_add(10000),
+            flags: flags_to_bits(&node.flags),
+            state_ok: true,
+            mflags: 0,
+        }
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.flags & FLAG_MASTER != 0
+    }
+
+    /// Address of the sender, falling back to the peer's IP when the
+    /// sender did not announce one
+    pub fn addr(&self, peer_ip: Option<IpAddr>) -> Option<SocketAddr> {
+        let ip = if self.ip.is_empty() {
+            peer_ip?
+        } else {
+            self.ip.parse().ok()?
+        };
+        Some(SocketAddr::new(ip, self.port))
+    }
+}
+
+/// What a node knows about another node
+#[derive(Debug, Clone, PartialEq)]
+pub struct GossipEntry {
+    pub node_id: String,
+    /// Seconds since the epoch
+    pub ping_sent: u32,
+    pub pong_recv: u32,
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub flags: u16,
+}
+
+impl GossipEntry {
+    pub fn for_node(node: &ClusterNode) -> Self {
+        let (ip, port) = node
+            .addr
+            .map(|a| (a.ip().to_string(), a.port()))
+            .unwrap_or_default();
+        Self {
+            node_id: node.id.clone(),
+            ping_sent: (node.ping_sent / 1000) as u32,
+            pong_recv: (node.pong_recv / 1000) as u32,
+            ip,
+            port,
+            cport: port.wrapping_add(10000),
+            flags: flags_to_bits(&node.flags),
+        }
+    }
+
+    pub fn addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::new(self.ip.parse().ok()?, self.port))
+    }
+
+    /// Whether the sender considers this node failing
+    pub fn is_failing(&self) -> bool {
+        self.flags & (FLAG_PFAIL | FLAG_FAIL) != 0
+    }
+}
+
+/// Type specific payload
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageBody {
+    Gossip(Vec<GossipEntry>),
+    Fail { node_id: String },
+    Publish { channel: Vec<u8>, message: Vec<u8> },
+    Empty,
+}
+
+/// A cluster bus message
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterMessage {
+    pub header: MessageHeader,
+    pub body: MessageBody,
+}
+
+impl ClusterMessage {
+    /// Encode the message into a buffer
+    pub fn encode(&self) -> BytesMut {
+        let h = &self.header;
+        let count = match &self.body {
+            MessageBody::Gossip(entries) => entries.len(),
+            MessageBody::Fail { .. } | MessageBody::Publish { .. } | MessageBody::Empty => 0,
+        };
+
+        let mut buf = BytesMut::with_capacity(HEADER_LEN + count * GOSSIP_LEN + NAME_LEN);
+        buf.put_slice(SIGNATURE);
+        buf.put_u32(0); // patched below
+        buf.put_u16(VERSION);
+        buf.put_u16(h.msg_type.code());
+        buf.put_u16(count as u16);
+        buf.put_u64(h.current_epoch);
+        buf.put_u64(h.config_epoch);
+        buf.put_u64(h.offset);
+        put_fixed(&mut buf, h.sender.as_bytes(), NAME_LEN);
+        buf.put_slice(&h.slots.0[..]);
+        put_fixed(&mut buf, h.master_id.as_deref().unwrap_or("").as_bytes(), NAME_LEN);
+        put_fixed(&mut buf, h.ip.as_bytes(), IP_LEN);
+        buf.put_u16(h.port);
+        buf.put_u16(h.cport);
+        buf.put_u16(h.flags);
+        buf.put_u8(h.state_ok as u8);
+        buf.put_u8(h.mflags);
+
+        match &self.body {
+            MessageBody::Gossip(entries) => {
+                for entry in entries {
+                    put_fixed(&mut buf, entry.node_id.as_bytes(), NAME_LEN);
+                    buf.put_u32(entry.ping_sent);
+                    buf.put_u32(entry.pong_recv);
+                    put_fixed(&mut buf, entry.ip.as_bytes(), IP_LEN);
+                    buf.put_u16(entry.port);
+                    buf.put_u16(entry.cport);
+                    buf.put_u16(entry.flags);
+                }
+            }
+            MessageBody::Fail { node_id } => put_fixed(&mut buf, node_id.as_bytes(), NAME_LEN),
+            MessageBody::Publish { channel, message } => {
+                buf.put_u32(channel.len() as u32);
+                buf.put_u32(message.len() as u32);
+                buf.put_slice(channel);
+                buf.put_slice(message);
+            }
+            MessageBody::Empty => {}
+        }
+
+        let len = buf.len() as u32;
+        buf[4..8].copy_from_slice(&len.to_be_bytes());
+        buf
+    }
+
+    /// Total message length announced by the first 8 bytes of a message
+    pub fn frame_len(prefix: &[u8; 8]) -> Result<usize, String> {
+        if &prefix[..4] != SIGNATURE {
+            return Err("invalid cluster bus signature".to_string());
+        }
+        let len = u32::from_be_bytes([prefix[4], prefix[5], prefix[6], prefix[7]]) as usize;
+        if !(HEADER_LEN..=MAX_MESSAGE_LEN).contains(&len) {
+            return Err(format!("invalid cluster bus message length {}", len));
+        }
+        Ok(len)
+    }
+
+    /// Decode a complete message
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_LEN {
+            return Err("truncated cluster bus message".to_string());
+        }
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&data[..8]);
+        if Self::frame_len(&prefix)? != data.len() {
+            return Err("cluster bus message length mismatch".to_string());
+        }
+
+        let mut buf = &data[8..];
+        let version = buf.get_u16();
+        if version != VERSION {
+            return Err(format!("unsupported cluster bus version {}", version));
+        }
+        let type_code = buf.get_u16();
+        let msg_type = MessageType::from_code(type_code)
+            .ok_or_else(|| format!("unknown cluster bus message type {}", type_code))?;
+        let count = buf.get_u16() as usize;
+        let current_epoch = buf.get_u64();
+        let config_epoch = buf.get_u64();
+        let offset = buf.get_u64();
+        let sender = get_fixed(&mut buf, NAME_LEN);
+        let mut slots = SlotBitmap::new();
+        buf.copy_to_slice(&mut slots.0[..]);
+        let master_id = Some(get_fixed(&mut buf, NAME_LEN)).filter(|id| !id.is_empty());
+        let ip = get_fixed(&mut buf, IP_LEN);
+        let port = buf.get_u16();
+        let cport = buf.get_u16();
+        let flags = buf.get_u16();
+        let state_ok = buf.get_u8() != 0;
+        let mflags = buf.get_u8();
+
+        let header = MessageHeader {
+            msg_type,
+            current_epoch,
+            config_epoch,
+            offset,
+            sender,
+            slots,
+            master_id,
+            ip,
+            port,
+            cport,
+            flags,
+            state_ok,
+            mflags,
+        };
+
+        let body = match msg_type {
+            MessageType::Ping | MessageType::Pong | MessageType::Meet => {
+                if buf.remaining() != count * GOSSIP_LEN {
+                    return Err("gossip section length mismatch".to_string());
+                }
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entries.push(GossipEntry {
+                        node_id: get_fixed(&mut buf, NAME_LEN),
+                        ping_sent: buf.get_u32(),
+                        pong_recv: buf.get_u32(),
+                        ip: get_fixed(&mut buf, IP_LEN),
+                        port: buf.get_u16(),
+                        cport: buf.get_u16(),
+                        flags: buf.get_u16(),
+                    });
+                }
+                MessageBody::Gossip(entries)
+            }
+            MessageType::Fail => {
+                if buf.remaining() != NAME_LEN {
+                    return Err("invalid FAIL message length".to_string());
+                }
+                MessageBody::Fail { node_id: get_fixed(&mut buf, NAME_LEN) }
+            }
+            MessageType::PublishShard => {
+                if buf.remaining() < 8 {
+                    return Err("truncated PUBLISHSHARD message".to_string());
+                }
+                let channel_len = buf.get_u32() as usize;
+                let message_len = buf.get_u32() as usize;
+                if buf.remaining() != channel_len + message_len {
+                    return Err("invalid PUBLISHSHARD message length".to_string());
+                }
+                let channel = buf[..channel_len].to_vec();
+                let message = buf[channel_len..].to_vec();
+                MessageBody::Publish { channel, message }
+            }
+            MessageType::FailoverAuthRequest | MessageType::FailoverAuthAck | MessageType::MfStart => {
+                if buf.has_remaining() {
+                    return Err("unexpected payload in failover message".to_string());
+                }
+                MessageBody::Empty
+            }
+        };
+
+        Ok(Self { header, body })
+    }
+}
+
+/// Write `data` into a NUL padded field of `len` bytes
+fn put_fixed(buf: &mut BytesMut, data: &[u8], len: usize) {
+    let n = data.len().min(len);
+    buf.put_slice(&data[..n]);
+    buf.put_bytes(0, len - n);
+}
+
+/// Read a NUL padded field of `len` bytes
+fn get_fixed(buf: &mut &[u8], len: usize) -> String {
+    let field = &buf[..len];
+    let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+    let value = String::from_utf8_lossy(&field[..end]).to_string();
+    buf.advance(len);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_node() -> ClusterNode {
+        let mut node = ClusterNode::new_master("a".repeat(40), Some("127.0.0.1:7000".parse().unwrap()));
+        node.add_flag(NodeFlags::Myself);
+        node.add_slot_range(0, 100);
+        node.add_slot(16383);
+        node.config_epoch = 7;
+        node
+    }
+
+    #[test]
+    fn test_ping_roundtrip() {
+        let node = sample_node();
+        let mut other = ClusterNode::new_replica("b".repeat(40), Some("127.0.0.1:7001".parse().unwrap()), node.id.clone());
+        other.add_flag(NodeFlags::PFail);
+
+        let msg = ClusterMessage {
+            header: MessageHeader::for_node(MessageType::Ping, &node, 9),
+            body: MessageBody::Gossip(vec![GossipEntry::for_node(&other)]),
+        };
+        let encoded = msg.encode();
+        assert_eq!(encoded.len(), HEADER_LEN + GOSSIP_LEN);
+
+        let decoded = ClusterMessage::decode(&encoded).unwrap();
+        assert_eq!(decoded, msg);
+        assert_eq!(decoded.header.current_epoch, 9);
+        assert_eq!(decoded.header.config_epoch, 7);
+        assert!(decoded.header.is_master());
+        assert_eq!(decoded.header.slots.to_set(), node.slots);
+        assert_eq!(decoded.header.addr(None), node.addr);
+
+        match decoded.body {
+            MessageBody::Gossip(entries) => {
+                assert_eq!(entries[0].node_id, other.id);
+                assert!(entries[0].is_failing());
+                assert_eq!(entries[0].flags & FLAG_SLAVE, FLAG_SLAVE);
+                assert_eq!(entries[0].addr(), other.addr);
+            }
+            _ => panic!("Expected gossip body"),
+        }
+    }
+
+    #[test]
+    fn test_fail_roundtrip() {
+        let msg = ClusterMessage {
+            header: MessageHeader::for_node(MessageType::Fail, &sample_node(), 1),
+            body: MessageBody::Fail { node_id: "c".repeat(40) },
+        };
+        assert_eq!(ClusterMessage::decode(&msg.encode()).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_publish_shard_roundtrip() {
+        let msg = ClusterMessage {
+            header: MessageHeader::for_node(MessageType::PublishShard, &sample_node(), 1),
+            body: MessageBody::Publish { channel: b"orders".to_vec(), message: b"hello".to_vec() },
+        };
+        let data = msg.encode();
+        assert_eq!(data.len(), HEADER_LEN + 8 + 11);
+        assert_eq!(ClusterMessage::decode(&data).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_failover_messages_roundtrip() {
+        for msg_type in [MessageType::FailoverAuthRequest, MessageType::FailoverAuthAck, MessageType::MfStart] {
+            let mut header = MessageHeader::for_node(msg_type, &sample_node(), 9);
+            header.mflags = MFLAG_FORCEACK;
+            let msg = ClusterMessage { header, body: MessageBody::Empty };
+            let data = msg.encode();
+            assert_eq!(data.len(), HEADER_LEN);
+            assert_eq!(ClusterMessage::decode(&data).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        let mut encoded = ClusterMessage {
+            header: MessageHeader::for_node(MessageType::Pong, &sample_node(), 1),
+            body: MessageBody::Gossip(vec![]),
+        }
+        .encode();
+
+        assert!(ClusterMessage::decode(&encoded[..HEADER_LEN - 1]).is_err());
+        encoded[0] = b'X';
+        assert!(ClusterMessage::decode(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_unannounced_ip_uses_peer() {
+        let node = ClusterNode::new_master("a".repeat(40), Some("0.0.0.0:7000".parse().unwrap()));
+        let header = MessageHeader::for_node(MessageType::Meet, &node, 0);
+        assert!(header.ip.is_empty());
+
+        let peer: IpAddr = "10.0.0.5".parse().unwrap();
+        assert_eq!(header.addr(Some(peer)), Some("10.0.0.5:7000".parse().unwrap()));
+        assert_eq!(header.addr(None), None);
+    }
+}
@@ -40,6 +40,11 @@ impl MigrationManager {
         self.slot_states.remove(&slot);
     }
 
+    /// Forget every importing / migrating slot
+    pub fn clear(&self) {
+        self.slot_states.clear();
+    }
+
     /// Get the state of a slot
     pub fn get_state(&self, slot: u16) -> SlotState {
         self.slot_states
@@ -143,23 +148,30 @@ pub fn cluster_setslot_node(
         return RespValue::Error(format!("ERR Unknown node {}", node_id));
     }
 
+    let finishing_import = node_id == cluster.my_id() && migration.is_importing(slot);
+
     // Assign the slot
     cluster.assign_slots_to_node(&node_id, vec![slot]);
 
     // Mark as stable
     migration.set_stable(slot);
 
+    // Claim the imported slot with a config epoch that beats the old owner,
+    // so the rest of the cluster learns about the move through gossip
+    if finishing_import {
+        cluster.bump_epoch_without_consensus();
+    }
+
     RespValue::SimpleString("OK".to_string())
 }
 
 /// CLUSTER GETKEYSINSLOT <slot> <count>
 /// Get up to <count> keys in a specific slot
-/// (Placeholder - requires database integration)
 pub fn cluster_getkeysinslot(
     cluster: &Arc<ClusterState>,
-    _db: &Arc<Database>,
-    _db_index: usize,
-    _slot: u16,
+    db: &Arc<Database>,
+    db_index: usize,
+    slot: u16,
     count: i64,
 ) -> RespValue {
     if !cluster.enabled {
@@ -170,27 +182,30 @@ pub fn cluster_getkeysinslot(
         return RespValue::Error("ERR count must be positive".to_string());
     }
 
-    // TODO: Implement actual key scanning when database API is available
-    // For now, return empty array
-    RespValue::Array(Some(vec![]))
+    let keys = db
+        .get_db(db_index)
+        .map_or_else(Vec::new, |db| db.get_keys_in_slot(slot, count as usize));
+    RespValue::Array(Some(
+        keys.into_iter()
+            .map(|key| RespValue::BulkString(Some(key.into_bytes())))
+            .collect(),
+    ))
 }
 
 /// CLUSTER COUNTKEYSINSLOT <slot>
 /// Count keys in a specific slot
-/// (Placeholder - requires database integration)
 pub fn cluster_countkeysinslot(
     cluster: &Arc<ClusterState>,
-    _db: &Arc<Database>,
-    _db_index: usize,
-    _slot: u16,
+    db: &Arc<Database>,
+    db_index: usize,
+    slot: u16,
 ) -> RespValue {
     if !cluster.enabled {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
-    // TODO: Implement actual key counting when database API is available
-    // For now, return 0
-    RespValue::Integer(0)
+    let count = db.get_db(db_index).map_or(0, |db| db.count_keys_in_slot(slot));
+    RespValue::Integer(count as i64)
 }
 
 #[cfg(test)]
@@ -343,4 +358,23 @@ mod tests {
         assert_eq!(cluster.get_slot_node(400), Some("new_owner".to_string()));
         assert_eq!(migration.get_state(400), SlotState::Stable);
     }
+
+    #[test]
+    fn test_cluster_setslot_node_finishes_import() {
+        let cluster = Arc::new(ClusterState::new(true));
+        let migration = Arc::new(MigrationManager::new());
+
+        let mut source = crate::cluster::node::ClusterNode::new_master("source".to_string(), None);
+        source.config_epoch = 3;
+        cluster.add_node(source);
+        cluster.assign_slots_to_node("source", vec![500]);
+        migration.set_importing(500, "source".to_string());
+
+        let result = cluster_setslot_node(&cluster, &migration, 500, cluster.my_id());
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert!(cluster.owns_slot(500));
+        assert!(!migration.is_importing(500));
+        // Our claim must win against the old owner's config
+        assert!(cluster.my_config_epoch() > 3);
+    }
 }
@@ -5,21 +5,39 @@ pub mod slots;
 pub mod redirection;
 pub mod migration;
 pub mod config;
+pub mod message;
+pub mod bus;
+pub mod failover;
+pub mod keys;
 
 use dashmap::DashMap;
-use node::ClusterNode;
-use std::sync::Arc;
+use node::{ClusterNode, NodeFlags};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 pub use slots::{key_hash_slot, CLUSTER_SLOTS};
-pub use redirection::{check_slot_ownership, check_multi_key_slot, SlotState};
+pub use keys::{command_keys, is_write_command};
+pub use redirection::{
+    check_keys_redirection, check_multi_key_slot, check_slot_ownership, served_by_my_master, SlotState,
+};
 pub use migration::MigrationManager;
 pub use config::{ConfigEpoch, save_cluster_config, load_cluster_config, auto_save_cluster_config};
+pub use bus::ClusterBus;
 
 /// Cluster state management
 pub struct ClusterState {
     pub enabled: bool,
-    pub my_id: String,
+    my_id: RwLock<String>,
     pub slot_map: Arc<DashMap<u16, String>>, // slot -> node_id
     pub nodes: Arc<DashMap<String, ClusterNode>>, // node_id -> node
+    /// Highest epoch seen in the cluster
+    current_epoch: AtomicU64,
+    /// Cluster bus messages sent / received
+    stats_messages_sent: AtomicU64,
+    stats_messages_received: AtomicU64,
+    /// Set when the node table changed and nodes.conf needs rewriting
+    todo_save: AtomicBool,
 }
 
 impl ClusterState {
@@ -36,12 +54,40 @@ impl ClusterState {
 
         Self {
             enabled,
-            my_id,
+            my_id: RwLock::new(my_id),
             slot_map: Arc::new(DashMap::new()),
             nodes,
+            current_epoch: AtomicU64::new(0),
+            stats_messages_sent: AtomicU64::new(0),
+            stats_messages_received: AtomicU64::new(0),
+            todo_save: AtomicBool::new(false),
         }
     }
 
+    /// This node's ID
+    pub fn my_id(&self) -> String {
+        self.my_id.read().unwrap().clone()
+    }
+
+    /// Take over a new identity (e.g. the one stored in nodes.conf).
+    /// The local node entry and the slots it serves are re-keyed.
+    pub fn set_my_id(&self, new_id: String) {
+        let old_id = std::mem::replace(&mut *self.my_id.write().unwrap(), new_id.clone());
+        if old_id == new_id {
+            return;
+        }
+        if let Some((_, mut node)) = self.nodes.remove(&old_id) {
+            node.id = new_id.clone();
+            self.nodes.insert(new_id.clone(), node);
+        }
+        for mut entry in self.slot_map.iter_mut() {
+            if *entry.value() == old_id {
+                *entry.value_mut() = new_id.clone();
+            }
+        }
+        self.mark_dirty();
+    }
+
     /// Generate a unique node ID (40 hex chars)
     fn generate_node_id() -> String {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -53,21 +99,36 @@ impl ClusterState {
         format!("{:040x}", timestamp)
     }
 
+    /// Flag the node table as changed so the next flush rewrites nodes.conf
+    pub fn mark_dirty(&self) {
+        self.todo_save.store(true, Ordering::SeqCst);
+    }
+
+    /// Clear the dirty flag, returning whether it was set
+    pub fn take_dirty(&self) -> bool {
+        self.todo_save.swap(false, Ordering::SeqCst)
+    }
+
     /// Assign a slot to this node
     pub fn add_slot(&self, slot: u16) {
-        self.slot_map.insert(slot, self.my_id.clone());
+        self.assign_slots_to_node(&self.my_id(), vec![slot]);
     }
 
     /// Remove slot assignment from this node
     pub fn del_slot(&self, slot: u16) {
-        self.slot_map.remove(&slot);
+        if let Some((_, owner)) = self.slot_map.remove(&slot) {
+            if let Some(mut node) = self.nodes.get_mut(&owner) {
+                node.remove_slot(slot);
+            }
+            self.mark_dirty();
+        }
     }
 
     /// Check if this node owns a slot
     pub fn owns_slot(&self, slot: u16) -> bool {
         self.slot_map
             .get(&slot)
-            .map(|r| r.value() == &self.my_id)
+            .map(|r| *r.value() == self.my_id())
             .unwrap_or(false)
     }
 
@@ -78,29 +139,35 @@ impl ClusterState {
 
     /// Get all slots owned by this node
     pub fn get_my_slots(&self) -> Vec<u16> {
+        let my_id = self.my_id();
         self.slot_map
             .iter()
-            .filter(|r| r.value() == &self.my_id)
+            .filter(|r| *r.value() == my_id)
             .map(|r| *r.key())
             .collect()
     }
 
     /// Count how many slots this node owns
     pub fn count_my_slots(&self) -> usize {
+        let my_id = self.my_id();
         self.slot_map
             .iter()
-            .filter(|r| r.value() == &self.my_id)
+            .filter(|r| *r.value() == my_id)
             .count()
     }
 
     /// Add a node to the cluster
     pub fn add_node(&self, node: ClusterNode) {
         self.nodes.insert(node.id.clone(), node);
+        self.mark_dirty();
     }
 
-    /// Remove a node from the cluster
+    /// Remove a node from the cluster, unassigning the slots it served
     pub fn remove_node(&self, node_id: &str) {
-        self.nodes.remove(node_id);
+        if self.nodes.remove(node_id).is_some() {
+            self.slot_map.retain(|_, owner| owner != node_id);
+            self.mark_dirty();
+        }
     }
 
     /// Get a node by ID
@@ -135,9 +202,15 @@ impl ClusterState {
 
     /// Update node slot assignment
     pub fn assign_slots_to_node(&self, node_id: &str, slots: Vec<u16>) {
-        // Update slot_map
+        // Update slot_map, taking the slots away from their previous owners
         for slot in &slots {
-            self.slot_map.insert(*slot, node_id.to_string());
+            if let Some(previous) = self.slot_map.insert(*slot, node_id.to_string()) {
+                if previous != node_id {
+                    if let Some(mut node) = self.nodes.get_mut(&previous) {
+                        node.remove_slot(*slot);
+                    }
+                }
+            }
         }
 
         // Update node's slots if it exists
@@ -146,17 +219,14 @@ impl ClusterState {
                 node_ref.add_slot(slot);
             }
         }
+        self.mark_dirty();
     }
 
     /// Remove slots from a node
     pub fn remove_slots_from_node(&self, node_id: &str, slots: Vec<u16>) {
         // Remove from slot_map
         for slot in &slots {
-            if let Some(owner) = self.slot_map.get(slot) {
-                if owner.value() == node_id {
-                    self.slot_map.remove(slot);
-                }
-            }
+            self.slot_map.remove_if(slot, |_, owner| owner == node_id);
         }
 
         // Remove from node's slots if it exists
@@ -165,7 +235,309 @@ impl ClusterState {
                 node_ref.remove_slot(slot);
             }
         }
+        self.mark_dirty();
+    }
+
+    /// A copy of the local node
+    pub fn myself(&self) -> Option<ClusterNode> {
+        self.get_node(&self.my_id())
+    }
+
+    /// Set the address other nodes reach us on
+    pub fn set_my_addr(&self, addr: SocketAddr) {
+        if let Some(mut node) = self.nodes.get_mut(&self.my_id()) {
+            node.addr = Some(addr);
+        }
+    }
+
+    /// Whether the local node is a master
+    pub fn is_myself_master(&self) -> bool {
+        self.nodes
+            .get(&self.my_id())
+            .map(|n| n.is_master())
+            .unwrap_or(false)
+    }
+
+    /// Highest epoch seen in the cluster
+    pub fn current_epoch(&self) -> u64 {
+        self.current_epoch.load(Ordering::SeqCst)
+    }
+
+    /// Raise the current epoch to `epoch` if it is greater
+    pub fn observe_epoch(&self, epoch: u64) {
+        if self.current_epoch.fetch_max(epoch, Ordering::SeqCst) < epoch {
+            self.mark_dirty();
+        }
+    }
+
+    /// Increment the current epoch and return the new value
+    pub fn bump_current_epoch(&self) -> u64 {
+        self.mark_dirty();
+        self.current_epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Config epoch of the local node
+    pub fn my_config_epoch(&self) -> u64 {
+        self.nodes
+            .get(&self.my_id())
+            .map(|n| n.config_epoch)
+            .unwrap_or(0)
+    }
+
+    /// Set the config epoch of the local node
+    pub fn set_my_config_epoch(&self, epoch: u64) {
+        if let Some(mut node) = self.nodes.get_mut(&self.my_id()) {
+            node.config_epoch = epoch;
+        }
+        self.observe_epoch(epoch);
+        self.mark_dirty();
     }
+
+    /// Give the local node a fresh config epoch unless it already holds
+    /// a non-zero epoch that no other master shares or exceeds.
+    /// Returns (bumped, resulting config epoch).
+    pub fn bump_epoch_without_consensus(&self) -> (bool, u64) {
+        let my_id = self.my_id();
+        let my_epoch = self.my_config_epoch();
+        let max_epoch = self
+            .nodes
+            .iter()
+            .filter(|r| *r.key() != my_id)
+            .map(|r| r.value().config_epoch)
+            .max()
+            .unwrap_or(0);
+
+        if my_epoch == 0 || my_epoch <= max_epoch || my_epoch < self.current_epoch() {
+            // The new epoch must beat every config epoch we know of
+            self.observe_epoch(max_epoch);
+            let epoch = self.bump_current_epoch();
+            self.set_my_config_epoch(epoch);
+            (true, epoch)
+        } else {
+            (false, my_epoch)
+        }
+    }
+
+    /// Turn the local node into a replica of `master_id`
+    pub fn set_my_master(&self, master_id: &str) {
+        let my_id = self.my_id();
+        let slots: Vec<u16> = self.get_my_slots();
+        if !slots.is_empty() {
+            self.remove_slots_from_node(&my_id, slots);
+        }
+        if let Some(mut node) = self.nodes.get_mut(&my_id) {
+            node.remove_flag(&NodeFlags::Master);
+            node.add_flag(NodeFlags::Slave);
+            node.master_id = Some(master_id.to_string());
+        }
+        self.mark_dirty();
+    }
+
+    /// Turn the local replica into a master that serves the slots of its
+    /// old master, with the given config epoch. Returns the slots taken.
+    pub fn promote_myself(&self, config_epoch: u64) -> Vec<u16> {
+        let my_id = self.my_id();
+        let old_master = self.myself().and_then(|n| n.master_id);
+        let slots = old_master
+            .as_deref()
+            .and_then(|id| self.get_node(id))
+            .map(|n| n.get_slots())
+            .unwrap_or_default();
+
+        if let Some(mut node) = self.nodes.get_mut(&my_id) {
+            node.remove_flag(&NodeFlags::Slave);
+            node.add_flag(NodeFlags::Master);
+            node.master_id = None;
+        }
+        if !slots.is_empty() {
+            self.assign_slots_to_node(&my_id, slots.clone());
+        }
+        self.set_my_config_epoch(config_epoch);
+        self.mark_dirty();
+        slots
+    }
+
+    /// CLUSTER RESET: forget every other node and every slot assignment.
+    /// The local node becomes an empty master; a hard reset also takes a
+    /// new node id and sets the epochs back to zero.
+    pub fn reset(&self, hard: bool) {
+        let my_id = self.my_id();
+        self.nodes.retain(|id, _| *id == my_id);
+        self.slot_map.clear();
+        if let Some(mut node) = self.nodes.get_mut(&my_id) {
+            node.slots.clear();
+            node.remove_flag(&NodeFlags::Slave);
+            node.add_flag(NodeFlags::Master);
+            node.master_id = None;
+            node.fail_reports.clear();
+            if hard {
+                node.config_epoch = 0;
+            }
+        }
+        if hard {
+            self.current_epoch.store(0, Ordering::SeqCst);
+            self.set_my_id(Self::generate_node_id());
+        }
+        self.mark_dirty();
+    }
+
+    /// Number of masters serving at least one slot
+    pub fn size(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|r| r.value().is_master() && !r.value().slots.is_empty())
+            .count()
+    }
+
+    /// The cluster is ok unless some assigned slot is served by a FAIL node
+    pub fn state_ok(&self) -> bool {
+        !self
+            .nodes
+            .iter()
+            .any(|r| r.value().is_failed() && !r.value().slots.is_empty())
+    }
+
+    /// Record a cluster bus message sent
+    pub fn record_sent(&self) {
+        self.stats_messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a cluster bus message received
+    pub fn record_received(&self) {
+        self.stats_messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Cluster bus messages (sent, received)
+    pub fn message_stats(&self) -> (u64, u64) {
+        (
+            self.stats_messages_sent.load(Ordering::Relaxed),
+            self.stats_messages_received.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Record that `reporter` sees `node_id` as failing.
+    /// Returns true if this is a new report.
+    pub fn add_failure_report(&self, node_id: &str, reporter: &str) -> bool {
+        match self.nodes.get_mut(node_id) {
+            Some(mut node) => node
+                .fail_reports
+                .insert(reporter.to_string(), mstime())
+                .is_none(),
+            None => false,
+        }
+    }
+
+    /// Forget the failure report `reporter` made about `node_id`
+    pub fn del_failure_report(&self, node_id: &str, reporter: &str) -> bool {
+        match self.nodes.get_mut(node_id) {
+            Some(mut node) => node.fail_reports.remove(reporter).is_some(),
+            None => false,
+        }
+    }
+
+    /// Drop reports older than `max_age_ms` and count the remaining ones
+    pub fn count_failure_reports(&self, node_id: &str, max_age_ms: u64) -> usize {
+        let now = mstime();
+        match self.nodes.get_mut(node_id) {
+            Some(mut node) => {
+                node.fail_reports
+                    .retain(|_, time| now.saturating_sub(*time) <= max_age_ms);
+                node.fail_reports.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Promote a PFAIL node to FAIL when a majority of the masters agree.
+    /// Returns true if the node was just flagged FAIL.
+    pub fn mark_failing_if_needed(&self, node_id: &str, node_timeout: u64) -> bool {
+        let needed_quorum = self.size() / 2 + 1;
+
+        match self.nodes.get(node_id) {
+            Some(node) if node.is_pfail() && !node.is_failed() => {}
+            _ => return false,
+        }
+
+        let mut failures = self.count_failure_reports(node_id, node_timeout * 2);
+        // Our own PFAIL view counts when we are a master
+        if self.is_myself_master() {
+            failures += 1;
+        }
+        if failures < needed_quorum {
+            return false;
+        }
+
+        self.mark_node_failed(node_id)
+    }
+
+    /// Flag a node as FAIL. Returns false if it already was.
+    pub fn mark_node_failed(&self, node_id: &str) -> bool {
+        if node_id == self.my_id() {
+            return false;
+        }
+        match self.nodes.get_mut(node_id) {
+            Some(mut node) if !node.is_failed() => {
+                node.remove_flag(&NodeFlags::PFail);
+                node.add_flag(NodeFlags::Fail);
+                node.fail_time = mstime();
+                drop(node);
+                self.mark_dirty();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Apply the slots a master claims with its config epoch: every
+    /// claimed slot that is unassigned or owned with an older epoch moves
+    /// to the sender, and slots it no longer claims are unassigned.
+    /// Slots for which `importing` returns true are left alone.
+    /// Returns the slots the local node lost.
+    pub fn update_slots_from(
+        &self,
+        sender_id: &str,
+        sender_epoch: u64,
+        claimed: &HashSet<u16>,
+        importing: impl Fn(u16) -> bool,
+    ) -> Vec<u16> {
+        let my_id = self.my_id();
+        let mut lost = Vec::new();
+        let mut taken = Vec::new();
+
+        for &slot in claimed {
+            let owner = self.get_slot_node(slot);
+            if owner.as_deref() == Some(sender_id) || importing(slot) {
+                continue;
+            }
+            let owner_epoch = owner
+                .as_ref()
+                .and_then(|id| self.nodes.get(id).map(|n| n.config_epoch));
+            if owner_epoch.is_none_or(|epoch| epoch < sender_epoch) {
+                if owner.as_deref() == Some(my_id.as_str()) {
+                    lost.push(slot);
+                }
+                taken.push(slot);
+            }
+        }
+        if !taken.is_empty() {
+            self.assign_slots_to_node(sender_id, taken);
+        }
+
+        let dropped: Vec<u16> = match self.nodes.get(sender_id) {
+            Some(node) => node.slots.iter().copied().filter(|s| !claimed.contains(s)).collect(),
+            None => Vec::new(),
+        };
+        if !dropped.is_empty() {
+            self.remove_slots_from_node(sender_id, dropped);
+        }
+
+        lost
+    }
+}
+
+/// Current time in milliseconds
+pub(crate) fn mstime() -> u64 {
+    ClusterNode::current_time_millis()
 }
 
 impl Default for ClusterState {
@@ -182,7 +554,7 @@ mod tests {
     fn test_cluster_state_creation() {
         let state = ClusterState::new(true);
         assert!(state.enabled);
-        assert_eq!(state.my_id.len(), 40); // 40 hex chars
+        assert_eq!(state.my_id().len(), 40); // 40 hex chars
         assert_eq!(state.count_my_slots(), 0);
     }
 
@@ -235,7 +607,7 @@ mod tests {
         state.add_slot(0);
         let node_id = state.get_slot_node(0);
         assert!(node_id.is_some());
-        assert_eq!(node_id.unwrap(), state.my_id);
+        assert_eq!(node_id.unwrap(), state.my_id());
     }
 
     #[test]
@@ -267,11 +639,11 @@ mod tests {
         let replica = ClusterNode::new_replica(
             "replica1".to_string(),
             None,
-            state.my_id.clone()
+            state.my_id()
         );
         state.add_node(replica);
 
-        let replicas = state.get_replicas(&state.my_id);
+        let replicas = state.get_replicas(&state.my_id());
         assert_eq!(replicas.len(), 1);
         assert_eq!(replicas[0].id, "replica1");
     }
@@ -1,6 +1,6 @@
 // Cluster node management
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -23,6 +23,8 @@ pub enum NodeFlags {
     NoAddr,
     /// Node address needs update
     NoFlags,
+    /// Send a MEET instead of a PING on the next handshake (not shown)
+    Meet,
 }
 
 impl NodeFlags {
@@ -36,6 +38,7 @@ impl NodeFlags {
             NodeFlags::Handshake => "handshake",
             NodeFlags::NoAddr => "noaddr",
             NodeFlags::NoFlags => "noflags",
+            NodeFlags::Meet => "meet",
         }
     }
 
@@ -86,21 +89,42 @@ pub struct ClusterNode {
 
     /// Hash slots assigned to this node (for masters)
     pub slots: HashSet<u16>,
+
+    /// Masters that reported this node as PFAIL/FAIL -> report time (ms)
+    pub fail_reports: HashMap<String, u64>,
+
+    /// When the node was flagged FAIL (milliseconds)
+    pub fail_time: u64,
+
+    /// Creation time, used to expire stale handshakes (milliseconds)
+    pub ctime: u64,
+
+    /// Replication offset the node last announced
+    pub repl_offset: u64,
+
+    /// When we last voted for a replica of this master (milliseconds)
+    pub voted_time: u64,
 }
 
 impl ClusterNode {
     /// Create a new cluster node
     pub fn new(id: String, addr: Option<SocketAddr>) -> Self {
+        let now = Self::current_time_millis();
         Self {
             id,
             addr,
             flags: vec![],
             master_id: None,
             ping_sent: 0,
-            pong_recv: Self::current_time_millis(),
+            pong_recv: now,
             config_epoch: 0,
             link_state: "connected".to_string(),
             slots: HashSet::new(),
+            fail_reports: HashMap::new(),
+            fail_time: 0,
+            ctime: now,
+            repl_offset: 0,
+            voted_time: 0,
         }
     }
 
@@ -134,6 +158,27 @@ impl ClusterNode {
         self.flags.contains(&NodeFlags::Fail)
     }
 
+    /// Check if this node is flagged as possibly failing (PFAIL)
+    pub fn is_pfail(&self) -> bool {
+        self.flags.contains(&NodeFlags::PFail)
+    }
+
+    /// Check if the handshake with this node is still in progress
+    pub fn in_handshake(&self) -> bool {
+        self.flags.contains(&NodeFlags::Handshake)
+    }
+
+    /// Check if this entry describes the local node
+    pub fn is_myself(&self) -> bool {
+        self.flags.contains(&NodeFlags::Myself)
+    }
+
+    /// Cluster bus port (data port + 10000)
+    pub fn bus_addr(&self) -> Option<SocketAddr> {
+        self.addr
+            .and_then(|a| a.port().checked_add(10000).map(|p| SocketAddr::new(a.ip(), p)))
+    }
+
     /// Add a flag to this node
     pub fn add_flag(&mut self, flag: NodeFlags) {
         if !self.flags.contains(&flag) {
@@ -146,13 +191,29 @@ impl ClusterNode {
         self.flags.retain(|f| f != flag);
     }
 
-    /// Convert flags to comma-separated string
+    /// Convert flags to comma-separated string, in the order Redis prints them
     pub fn flags_to_string(&self) -> String {
-        self.flags
+        const ORDER: [NodeFlags; 7] = [
+            NodeFlags::Myself,
+            NodeFlags::Master,
+            NodeFlags::Slave,
+            NodeFlags::PFail,
+            NodeFlags::Fail,
+            NodeFlags::Handshake,
+            NodeFlags::NoAddr,
+        ];
+
+        let flags: Vec<&str> = ORDER
             .iter()
+            .filter(|f| self.flags.contains(f))
             .map(|f| f.to_string())
-            .collect::<Vec<_>>()
-            .join(",")
+            .collect();
+
+        if flags.is_empty() {
+            NodeFlags::NoFlags.to_string().to_string()
+        } else {
+            flags.join(",")
+        }
     }
 
     /// Assign a slot to this node
@@ -213,14 +274,10 @@ impl ClusterNode {
     pub fn to_cluster_nodes_line(&self) -> String {
         let id = &self.id;
         let addr = self.addr
-            .map(|a| a.to_string())
-            .unwrap_or_else(|| ":0".to_string());
+            .map(|a| format!("{}@{}", a, a.port() as u32 + 10000))
+            .unwrap_or_else(|| ":0@0".to_string());
 
-        let flags = self.flags
-            .iter()
-            .map(|f| f.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+        let flags = self.flags_to_string();
 
         let master = self.master_id.as_deref()
             .unwrap_or("-");
@@ -255,7 +312,7 @@ impl ClusterNode {
     }
 
     /// Get current time in milliseconds
-    fn current_time_millis() -> u64 {
+    pub(crate) fn current_time_millis() -> u64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
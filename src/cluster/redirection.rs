@@ -1,6 +1,6 @@
 // Cluster redirection logic - MOVED and ASK errors
 
-use crate::cluster::ClusterState;
+use crate::cluster::{ClusterState, MigrationManager};
 use crate::protocol::RespValue;
 use std::sync::Arc;
 
@@ -16,11 +16,13 @@ pub enum SlotState {
 }
 
 /// Check if a key belongs to this node, return redirection if needed
-/// Returns None if this node owns the slot, or a MOVED/ASK error
+/// Returns None if this node owns the slot, or a MOVED/ASK error.
+/// A slot we are importing is served to clients that sent ASKING.
 pub fn check_slot_ownership(
     cluster: &Arc<ClusterState>,
+    migration: &MigrationManager,
     key: &[u8],
-    _asking: bool,
+    asking: bool,
 ) -> Option<RespValue> {
     if !cluster.enabled {
         return None; // Not in cluster mode
@@ -33,6 +35,11 @@ pub fn check_slot_ownership(
         return None; // We own it, proceed
     }
 
+    // The owner redirected the client here while moving the slot to us
+    if asking && migration.is_importing(slot) {
+        return None;
+    }
+
     // We don't own this slot - need to redirect
     if let Some(owner_id) = cluster.get_slot_node(slot) {
         // Get the node info to find address
@@ -55,7 +62,8 @@ pub fn check_slot_ownership(
 }
 
 /// Check if key access should return ASK redirection during migration
-/// This is used when a slot is being migrated
+/// This is used when a slot is being migrated and the key is no longer
+/// (or not yet) stored locally
 pub fn check_ask_redirection(
     cluster: &Arc<ClusterState>,
     key: &[u8],
@@ -69,7 +77,7 @@ pub fn check_ask_redirection(
 
     match slot_state {
         SlotState::Migrating { to_node } => {
-            // Slot is migrating - return ASK if key doesn't exist locally
+            // Slot is migrating - the key lives on the target now
             if let Some(node) = cluster.get_node(to_node) {
                 if let Some(addr) = node.addr {
                     return Some(RespValue::Error(format!(
@@ -89,6 +97,44 @@ pub fn check_ask_redirection(
     }
 }
 
+/// Redirection for a command whose keys all hash to the same slot.
+/// In a slot we are migrating, keys that are gone were moved to the
+/// target: ASK if all of them are missing, TRYAGAIN if only some are.
+pub fn check_keys_redirection(
+    cluster: &Arc<ClusterState>,
+    migration: &MigrationManager,
+    keys: &[&[u8]],
+    asking: bool,
+    key_exists: impl Fn(&[u8]) -> bool,
+) -> Option<RespValue> {
+    let first = keys.first()?;
+    if let Some(redirect) = check_slot_ownership(cluster, migration, first, asking) {
+        return Some(redirect);
+    }
+
+    let state = migration.get_state(crate::cluster::key_hash_slot(first));
+    if !matches!(state, SlotState::Migrating { .. }) {
+        return None;
+    }
+    let missing = keys.iter().filter(|key| !key_exists(key)).count();
+    if missing == 0 {
+        None
+    } else if missing == keys.len() {
+        check_ask_redirection(cluster, first, &state)
+    } else {
+        Some(RespValue::Error(
+            "TRYAGAIN Multiple keys request during rehashing of slot".to_string(),
+        ))
+    }
+}
+
+/// Whether `slot` belongs to the master this node replicates, so that
+/// clients which sent READONLY can read it here instead of being redirected
+pub fn served_by_my_master(cluster: &Arc<ClusterState>, slot: u16) -> bool {
+    let master_id = cluster.myself().and_then(|me| me.master_id);
+    master_id.is_some() && cluster.get_slot_node(slot) == master_id
+}
+
 /// Parse MOVED error from response
 /// Format: "MOVED 3999 127.0.0.1:6381"
 pub fn parse_moved_error(error: &str) -> Option<(u16, String)> {
@@ -141,7 +187,7 @@ mod tests {
     #[test]
     fn test_check_slot_ownership_not_enabled() {
         let cluster = Arc::new(ClusterState::new(false));
-        let result = check_slot_ownership(&cluster, b"mykey", false);
+        let result = check_slot_ownership(&cluster, &MigrationManager::new(), b"mykey", false);
         assert!(result.is_none()); // No redirection when cluster disabled
     }
 
@@ -153,7 +199,7 @@ mod tests {
         cluster.add_slot(0);
 
         // Find a key that hashes to slot 0
-        let result = check_slot_ownership(&cluster, b"mykey", false);
+        let result = check_slot_ownership(&cluster, &MigrationManager::new(), b"mykey", false);
 
         // If we own the slot, no redirection
         let slot = crate::cluster::key_hash_slot(b"mykey");
@@ -181,7 +227,7 @@ mod tests {
             let key = format!("key{}", i);
             let slot = crate::cluster::key_hash_slot(key.as_bytes());
             if slot == 100 {
-                let result = check_slot_ownership(&cluster, key.as_bytes(), false);
+                let result = check_slot_ownership(&cluster, &MigrationManager::new(), key.as_bytes(), false);
                 match result {
                     Some(RespValue::Error(msg)) => {
                         assert!(msg.starts_with("MOVED 100"));
@@ -194,6 +240,65 @@ mod tests {
         }
     }
 
+    /// A key of `slot`, and a cluster where we own it and "target" is known
+    fn migrating_cluster(slot_key: &str) -> (Arc<ClusterState>, MigrationManager, u16) {
+        let cluster = Arc::new(ClusterState::new(true));
+        let slot = crate::cluster::key_hash_slot(slot_key.as_bytes());
+        cluster.add_slot(slot);
+        cluster.add_node(crate::cluster::node::ClusterNode::new_master(
+            "target".to_string(),
+            SocketAddr::from_str("127.0.0.1:7001").ok(),
+        ));
+        (cluster, MigrationManager::new(), slot)
+    }
+
+    #[test]
+    fn test_ask_for_missing_keys_in_migrating_slot() {
+        let (cluster, migration, slot) = migrating_cluster("{user}");
+        migration.set_migrating(slot, "target".to_string());
+        let present = |key: &[u8]| key == b"{user}:a";
+
+        let keys: [&[u8]; 1] = [b"{user}:a"];
+        assert!(check_keys_redirection(&cluster, &migration, &keys, false, present).is_none());
+
+        let keys: [&[u8]; 1] = [b"{user}:b"];
+        assert_eq!(
+            check_keys_redirection(&cluster, &migration, &keys, false, present),
+            Some(RespValue::Error(format!("ASK {} 127.0.0.1:7001", slot)))
+        );
+
+        let keys: [&[u8]; 2] = [b"{user}:a", b"{user}:b"];
+        match check_keys_redirection(&cluster, &migration, &keys, false, present) {
+            Some(RespValue::Error(msg)) => assert!(msg.starts_with("TRYAGAIN")),
+            other => panic!("Expected TRYAGAIN, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_asking_served_in_importing_slot() {
+        let (cluster, migration, slot) = migrating_cluster("{user}");
+        // The slot belongs to "target", we are importing it
+        cluster.assign_slots_to_node("target", vec![slot]);
+        migration.set_importing(slot, "target".to_string());
+
+        match check_slot_ownership(&cluster, &migration, b"{user}:a", false) {
+            Some(RespValue::Error(msg)) => assert!(msg.starts_with("MOVED")),
+            other => panic!("Expected MOVED, got {:?}", other),
+        }
+        assert!(check_slot_ownership(&cluster, &migration, b"{user}:a", true).is_none());
+    }
+
+    #[test]
+    fn test_served_by_my_master() {
+        let (cluster, _, slot) = migrating_cluster("{user}");
+        assert!(!served_by_my_master(&cluster, slot));
+
+        cluster.assign_slots_to_node("target", vec![slot]);
+        cluster.nodes.get_mut(&cluster.my_id()).unwrap().master_id = Some("target".to_string());
+        assert!(served_by_my_master(&cluster, slot));
+        assert!(!served_by_my_master(&cluster, slot + 1));
+    }
+
     #[test]
     fn test_parse_moved_error() {
         let result = parse_moved_error("MOVED 3999 127.0.0.1:6381");
@@ -1,9 +1,11 @@
 // CLIENT command implementation
 
 use crate::protocol::RespValue;
-use crate::server::client_info::ClientRegistry;
+use crate::server::client_info::{ClientRegistry, ClientType, KillFilter};
 use crate::server::slowlog::SlowLog;
+use crate::server::tracking::TrackingOptions;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// CLIENT command - Manage client connections
 pub async fn client(
@@ -42,43 +44,103 @@ pub async fn client(
                 None => RespValue::BulkString(None),
             }
         }
-        "LIST" => {
-            // List all client connections
-            let client_list = client_registry.list();
-            RespValue::BulkString(Some(client_list.into_bytes()))
-        }
+        "LIST" => client_list(client_registry, &args[1..]),
+        "INFO" => match client_registry.info(client_id) {
+            Some(info) => RespValue::BulkString(Some(info.into_bytes())),
+            None => RespValue::BulkString(None),
+        },
         "PAUSE" => {
-            // Pause client processing
-            if args.len() != 2 {
-                return RespValue::Error("ERR wrong number of arguments".to_string());
+            // CLIENT PAUSE timeout [WRITE|ALL]
+            if args.len() != 2 && args.len() != 3 {
+                return RespValue::Error("ERR wrong number of arguments for 'client|pause' command".to_string());
             }
-            // TODO: Implement client pause
+            let timeout = match std::str::from_utf8(&args[1]).ok().and_then(|t| t.parse::<u64>().ok()) {
+                Some(timeout) => timeout,
+                None => return RespValue::Error("ERR timeout is not an integer or out of range".to_string()),
+            };
+            let all = match args.get(2).map(|mode| String::from_utf8_lossy(mode).to_uppercase()) {
+                None => true,
+                Some(mode) if mode == "ALL" => true,
+                Some(mode) if mode == "WRITE" => false,
+                Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+            };
+            client_registry.pause(Duration::from_millis(timeout), all);
             RespValue::SimpleString("OK".to_string())
         }
         "UNPAUSE" => {
-            // Unpause client processing
-            // TODO: Implement client unpause
+            client_registry.unpause();
             RespValue::SimpleString("OK".to_string())
         }
-        "KILL" => {
-            // Kill client connection
-            if args.len() < 2 {
-                return RespValue::Error("ERR wrong number of arguments".to_string());
+        "KILL" => client_kill(client_registry, client_id, &args[1..]),
+        "NO-EVICT" | "NO-TOUCH" => {
+            if args.len() != 2 {
+                return RespValue::Error(format!(
+                    "ERR wrong number of arguments for 'client|{}' command",
+                    subcommand.to_lowercase()
+                ));
+            }
+            let on = match String::from_utf8_lossy(&args[1]).to_lowercase().as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            };
+            // Recorded for CLIENT LIST; nothing is evicted or LRU-tracked yet
+            if subcommand == "NO-EVICT" {
+                client_registry.set_no_evict(client_id, on);
+            } else {
+                client_registry.set_no_touch(client_id, on);
+            }
+            RespValue::SimpleString("OK".to_string())
+        }
+        "SETINFO" => {
+            if args.len() != 3 {
+                return RespValue::Error("ERR wrong number of arguments for 'client|setinfo' command".to_string());
+            }
+            let attr = String::from_utf8_lossy(&args[1]).to_lowercase();
+            let value = String::from_utf8_lossy(&args[2]).to_string();
+            if attr != "lib-name" && attr != "lib-ver" {
+                return RespValue::Error(format!("ERR Unrecognized option '{}'", String::from_utf8_lossy(&args[1])));
+            }
+            if value.chars().any(|c| !c.is_ascii_graphic()) {
+                return RespValue::Error(format!("ERR {} cannot contain spaces, newlines or special characters.", attr));
+            }
+            if attr == "lib-name" {
+                client_registry.set_lib_info(client_id, Some(value), None);
+            } else {
+                client_registry.set_lib_info(client_id, None, Some(value));
             }
-            // TODO: Implement client kill
             RespValue::SimpleString("OK".to_string())
         }
         "ID" => {
             // Get client ID
             RespValue::Integer(client_id as i64)
         }
+        "TRACKING" => client_tracking(client_registry, client_id, &args[1..]),
+        "CACHING" => {
+            if args.len() != 2 {
+                return RespValue::Error("ERR wrong number of arguments for 'client|caching' command".to_string());
+            }
+            let yes = match String::from_utf8_lossy(&args[1]).to_lowercase().as_str() {
+                "yes" => true,
+                "no" => false,
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            };
+            match client_registry.tracking().set_caching(client_id, yes) {
+                Ok(()) => RespValue::SimpleString("OK".to_string()),
+                Err(e) => RespValue::Error(e),
+            }
+        }
+        "GETREDIR" => RespValue::Integer(client_registry.tracking().redirect(client_id)),
         "REPLY" => {
-            // Control reply mode (ON/OFF/SKIP)
+            // The connection applies the mode; queued and scripted calls
+            // only get it validated
             if args.len() != 2 {
-                return RespValue::Error("ERR wrong number of arguments".to_string());
+                return RespValue::Error("ERR wrong number of arguments for 'client|reply' command".to_string());
+            }
+            match String::from_utf8_lossy(&args[1]).to_uppercase().as_str() {
+                "ON" | "OFF" | "SKIP" => RespValue::SimpleString("OK".to_string()),
+                _ => RespValue::Error("ERR syntax error".to_string()),
             }
-            // TODO: Implement reply control
-            RespValue::SimpleString("OK".to_string())
         }
         _ => RespValue::Error(format!(
             "ERR Unknown subcommand '{}'. Try CLIENT HELP.",
@@ -87,6 +149,138 @@ pub async fn client(
     }
 }
 
+/// CLIENT LIST [TYPE normal|master|replica|pubsub] [ID client-id ...]
+fn client_list(client_registry: &Arc<ClientRegistry>, args: &[Vec<u8>]) -> RespValue {
+    let mut kind = None;
+    let mut ids = Vec::new();
+    match args.first().map(|a| String::from_utf8_lossy(a).to_uppercase()) {
+        None => {}
+        Some(option) if option == "TYPE" && args.len() == 2 => {
+            let name = String::from_utf8_lossy(&args[1]);
+            match ClientType::parse(&name) {
+                Some(parsed) => kind = Some(parsed),
+                None => return RespValue::Error(format!("ERR Unknown client type '{}'", name)),
+            }
+        }
+        Some(option) if option == "ID" && args.len() > 1 => {
+            for id in &args[1..] {
+                match std::str::from_utf8(id).ok().and_then(|id| id.parse::<u64>().ok()) {
+                    Some(id) if id > 0 => ids.push(id),
+                    _ => return RespValue::Error("ERR Invalid client ID".to_string()),
+                }
+            }
+        }
+        Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+    }
+    RespValue::BulkString(Some(client_registry.list_filtered(kind, &ids).into_bytes()))
+}
+
+/// CLIENT KILL ip:port, or CLIENT KILL <filter> <value> ... with the ID,
+/// ADDR, LADDR, USER, TYPE, SKIPME and MAXAGE filters
+fn client_kill(client_registry: &Arc<ClientRegistry>, client_id: u64, args: &[Vec<u8>]) -> RespValue {
+    match args.len() {
+        0 => return RespValue::Error("ERR wrong number of arguments for 'client|kill' command".to_string()),
+        // The old form replies OK, or an error when nothing matched
+        1 => {
+            return match client_registry.kill_by_addr(&String::from_utf8_lossy(&args[0])) {
+                0 => RespValue::Error("ERR No such client".to_string()),
+                _ => RespValue::SimpleString("OK".to_string()),
+            };
+        }
+        n if n % 2 == 1 => return RespValue::Error("ERR syntax error".to_string()),
+        _ => {}
+    }
+
+    let mut filter = KillFilter::default();
+    for pair in args.chunks(2) {
+        let value = String::from_utf8_lossy(&pair[1]).to_string();
+        match String::from_utf8_lossy(&pair[0]).to_uppercase().as_str() {
+            "ID" => match value.parse::<u64>() {
+                Ok(id) if id > 0 => filter.id = Some(id),
+                _ => return RespValue::Error("ERR client-id should be greater than 0".to_string()),
+            },
+            "ADDR" => filter.addr = Some(value),
+            "LADDR" => filter.laddr = Some(value),
+            "USER" => {
+                // Connections are all the default user until ACLs apply to them
+                if value != "default" {
+                    return RespValue::Error(format!("ERR No such user '{}'", value));
+                }
+                filter.user = Some(value);
+            }
+            "TYPE" => match ClientType::parse(&value) {
+                Some(kind) => filter.kind = Some(kind),
+                None => return RespValue::Error(format!("ERR Unknown client type '{}'", value)),
+            },
+            "SKIPME" => match value.to_lowercase().as_str() {
+                "yes" => filter.skip_me = true,
+                "no" => filter.skip_me = false,
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            },
+            "MAXAGE" => match value.parse::<u64>() {
+                Ok(age) => filter.max_age = Some(age),
+                Err(_) => return RespValue::Error("ERR syntax error".to_string()),
+            },
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+    }
+
+    RespValue::Integer(client_registry.kill_matching(&filter, client_id) as i64)
+}
+
+/// CLIENT TRACKING ON|OFF [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+fn client_tracking(client_registry: &Arc<ClientRegistry>, client_id: u64, args: &[Vec<u8>]) -> RespValue {
+    let on = match args.first().map(|a| String::from_utf8_lossy(a).to_uppercase()) {
+        Some(mode) if mode == "ON" => true,
+        Some(mode) if mode == "OFF" => false,
+        Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+        None => return RespValue::Error("ERR wrong number of arguments for 'client|tracking' command".to_string()),
+    };
+
+    let mut options = TrackingOptions::default();
+    let mut i = 1;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "REDIRECT" if i + 1 < args.len() => {
+                match std::str::from_utf8(&args[i + 1]).ok().and_then(|id| id.parse::<u64>().ok()) {
+                    Some(id) => options.redirect = Some(id),
+                    None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+                }
+                i += 1;
+            }
+            "PREFIX" if i + 1 < args.len() => {
+                options.prefixes.push(args[i + 1].clone());
+                i += 1;
+            }
+            "BCAST" => options.bcast = true,
+            "OPTIN" => options.optin = true,
+            "OPTOUT" => options.optout = true,
+            "NOLOOP" => options.noloop = true,
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+        i += 1;
+    }
+
+    let tracking = client_registry.tracking();
+    if !on {
+        tracking.disable(client_id);
+        return RespValue::SimpleString("OK".to_string());
+    }
+    if !options.bcast && !options.prefixes.is_empty() {
+        return RespValue::Error("ERR PREFIX option requires BCAST mode to be enabled".to_string());
+    }
+    if options.optin && options.optout {
+        return RespValue::Error("ERR You can't use both OPTIN and OPTOUT".to_string());
+    }
+    if options.bcast && (options.optin || options.optout) {
+        return RespValue::Error("ERR OPTIN and OPTOUT are not compatible with BCAST".to_string());
+    }
+    match tracking.enable(client_id, options) {
+        Ok(()) => RespValue::SimpleString("OK".to_string()),
+        Err(e) => RespValue::Error(e),
+    }
+}
+
 /// SLOWLOG command - Manage slow query log
 pub async fn slowlog(slowlog: &Arc<SlowLog>, args: Vec<Vec<u8>>) -> RespValue {
     if args.is_empty() {
@@ -263,10 +457,36 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_client_tracking_options() {
+        let registry = Arc::new(ClientRegistry::new());
+        let client_id = registry.register("127.0.0.1:54321".to_string(), 8);
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        registry.tracking().register_client(client_id, tx);
+        let run = |args: &[&str]| {
+            client(&registry, client_id, args.iter().map(|a| a.as_bytes().to_vec()).collect())
+        };
+
+        assert_eq!(run(&["GETREDIR"]).await, RespValue::Integer(-1));
+        assert!(matches!(run(&["TRACKING", "ON", "PREFIX", "a"]).await, RespValue::Error(e) if e.contains("BCAST")));
+        assert!(matches!(run(&["TRACKING", "ON", "OPTIN", "OPTOUT"]).await, RespValue::Error(_)));
+        assert!(matches!(run(&["TRACKING", "ON", "REDIRECT", "999999"]).await, RespValue::Error(e) if e.contains("does not exist")));
+        assert!(matches!(run(&["CACHING", "yes"]).await, RespValue::Error(_)));
+
+        let id = client_id.to_string();
+        assert_eq!(run(&["TRACKING", "ON", "REDIRECT", &id, "OPTIN"]).await, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(run(&["GETREDIR"]).await, RespValue::Integer(client_id as i64));
+        assert_eq!(run(&["CACHING", "yes"]).await, RespValue::SimpleString("OK".to_string()));
+        assert!(matches!(run(&["CACHING", "no"]).await, RespValue::Error(_)));
+
+        assert_eq!(run(&["TRACKING", "OFF"]).await, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(run(&["GETREDIR"]).await, RespValue::Integer(-1));
+    }
+
     #[tokio::test]
     async fn test_slowlog_get() {
-        let slowlog = Arc::new(SlowLog::new());
-        let result = slowlog(&slowlog, vec![b"GET".to_vec()]).await;
+        let log = Arc::new(SlowLog::new());
+        let result = slowlog(&log, vec![b"GET".to_vec()]).await;
         match result {
             RespValue::Array(Some(arr)) => assert_eq!(arr.len(), 0),
             _ => panic!("Expected Array"),
@@ -2,6 +2,7 @@
 
 use crate::protocol::RespValue;
 use crate::storage::db::Database;
+use crate::storage::notify::EventClass;
 use crate::storage::RedisValue;
 use bytes::Bytes;
 use std::sync::Arc;
@@ -74,6 +75,7 @@ pub async fn setbit(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
 
     // Store back
     db_instance.set(key.to_string(), RedisValue::String(Bytes::from(bytes)));
+    db_instance.notify(EventClass::STRING, "setbit", key);
 
     RespValue::Integer(old_value as i64)
 }
@@ -106,7 +108,7 @@ pub async fn getbit(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
 
     let db_instance = db.get_db(db_index).unwrap();
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(val) => match val.as_string() {
             Some(s) => {
                 let byte_offset = offset / 8;
@@ -126,6 +128,60 @@ pub async fn getbit(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 }
 
+/// Whether a BITCOUNT / BITPOS range counts bytes or bits
+#[derive(Clone, Copy, PartialEq)]
+enum RangeUnit {
+    Byte,
+    Bit,
+}
+
+fn parse_i64(arg: &[u8]) -> Result<i64, RespValue> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| RespValue::Error("ERR value is not an integer or out of range".to_string()))
+}
+
+fn parse_range_unit(arg: Option<&Vec<u8>>) -> Result<RangeUnit, RespValue> {
+    match arg {
+        None => Ok(RangeUnit::Byte),
+        Some(a) if a.eq_ignore_ascii_case(b"BYTE") => Ok(RangeUnit::Byte),
+        Some(a) if a.eq_ignore_ascii_case(b"BIT") => Ok(RangeUnit::Bit),
+        Some(_) => Err(RespValue::Error("ERR syntax error".to_string())),
+    }
+}
+
+/// Resolve an inclusive start/end range over a string of `len_bytes` into
+/// bit positions. Negative indexes count from the end; None if the range
+/// is empty.
+fn resolve_bit_range(start: i64, end: i64, unit: RangeUnit, len_bytes: usize) -> Option<(usize, usize)> {
+    let len = match unit {
+        RangeUnit::Byte => len_bytes as i64,
+        RangeUnit::Bit => len_bytes as i64 * 8,
+    };
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { (len + end).max(0) } else { end.min(len - 1) };
+    if len == 0 || start > end {
+        return None;
+    }
+    match unit {
+        RangeUnit::Byte => Some((start as usize * 8, end as usize * 8 + 7)),
+        RangeUnit::Bit => Some((start as usize, end as usize)),
+    }
+}
+
+/// Number of set bits between two bit positions, inclusive
+fn count_bits(bytes: &[u8], start: usize, end: usize) -> u64 {
+    let (first, last) = (start / 8, end / 8);
+    let head = 0xFFu8 >> (start % 8);
+    let tail = 0xFFu8 << (7 - end % 8);
+    if first == last {
+        return (bytes[first] & head & tail).count_ones() as u64;
+    }
+    let middle: u64 = bytes[first + 1..last].iter().map(|b| b.count_ones() as u64).sum();
+    (bytes[first] & head).count_ones() as u64 + middle + (bytes[last] & tail).count_ones() as u64
+}
+
 /// BITCOUNT key [start end [BYTE|BIT]]
 /// Count set bits in a string
 pub async fn bitcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
@@ -134,15 +190,30 @@ pub async fn bitcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
             "ERR wrong number of arguments for 'bitcount' command".to_string(),
         );
     }
+    if args.len() == 2 || args.len() > 4 {
+        return RespValue::Error("ERR syntax error".to_string());
+    }
 
     let key = match std::str::from_utf8(&args[0]) {
         Ok(s) => s,
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
+    let range = if args.len() >= 3 {
+        let parsed = parse_i64(&args[1]).and_then(|start| {
+            Ok((start, parse_i64(&args[2])?, parse_range_unit(args.get(3))?))
+        });
+        match parsed {
+            Ok(range) => Some(range),
+            Err(e) => return e,
+        }
+    } else {
+        None
+    };
+
     let db_instance = db.get_db(db_index).unwrap();
 
-    let value = match db_instance.get(key) {
+    let value = match db_instance.lookup_read(key) {
         Some(val) => match val.as_string() {
             Some(s) => s.to_vec(),
             None => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
@@ -150,41 +221,11 @@ pub async fn bitcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
         None => return RespValue::Integer(0), // Empty string has 0 bits set
     };
 
-    // Parse start/end range if provided (simplified version - byte mode only)
-    let (start, end) = if args.len() >= 3 {
-        let start_val = match std::str::from_utf8(&args[1]) {
-            Ok(s) => match s.parse::<i64>() {
-                Ok(n) => n,
-                Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-            },
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        };
-
-        let end_val = match std::str::from_utf8(&args[2]) {
-            Ok(s) => match s.parse::<i64>() {
-                Ok(n) => n,
-                Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-            },
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        };
-
-        // Convert negative indices
-        let len = value.len() as i64;
-        let start = if start_val < 0 { (len + start_val).max(0) } else { start_val.min(len) };
-        let end = if end_val < 0 { (len + end_val).max(-1) } else { end_val.min(len - 1) };
-
-        (start as usize, end as usize)
-    } else {
-        (0, value.len().saturating_sub(1))
-    };
-
-    // Count bits in range
-    let mut count = 0u64;
-    for i in start..=end.min(value.len().saturating_sub(1)) {
-        count += value[i].count_ones() as u64;
+    let (start, end, unit) = range.unwrap_or((0, -1, RangeUnit::Byte));
+    match resolve_bit_range(start, end, unit, value.len()) {
+        Some((start, end)) => RespValue::Integer(count_bits(&value, start, end) as i64),
+        None => RespValue::Integer(0),
     }
-
-    RespValue::Integer(count as i64)
 }
 
 /// BITPOS key bit [start [end [BYTE|BIT]]]
@@ -195,6 +236,9 @@ pub async fn bitpos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             "ERR wrong number of arguments for 'bitpos' command".to_string(),
         );
     }
+    if args.len() > 5 {
+        return RespValue::Error("ERR syntax error".to_string());
+    }
 
     let key = match std::str::from_utf8(&args[0]) {
         Ok(s) => s,
@@ -210,72 +254,54 @@ pub async fn bitpos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         Err(_) => return RespValue::Error("ERR bit should be 0 or 1".to_string()),
     };
 
+    let start = match args.get(2).map(|a| parse_i64(a)).transpose() {
+        Ok(start) => start.unwrap_or(0),
+        Err(e) => return e,
+    };
+    let end_arg = match args.get(3).map(|a| parse_i64(a)).transpose() {
+        Ok(end) => end,
+        Err(e) => return e,
+    };
+    let unit = match parse_range_unit(args.get(4)) {
+        Ok(unit) => unit,
+        Err(e) => return e,
+    };
+
     let db_instance = db.get_db(db_index).unwrap();
 
-    let value = match db_instance.get(key) {
+    let value = match db_instance.lookup_read(key) {
         Some(val) => match val.as_string() {
             Some(s) => s.to_vec(),
             None => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
         },
-        None => return RespValue::Integer(-1), // Key doesn't exist
+        // A missing key is an empty string: all clear bits
+        None => return RespValue::Integer(if bit == 0 { 0 } else { -1 }),
     };
 
-    if value.is_empty() {
-        return RespValue::Integer(-1);
-    }
-
-    // Parse start/end range (simplified - byte mode only)
-    let (start, end) = if args.len() >= 4 {
-        let start_val = match std::str::from_utf8(&args[2]) {
-            Ok(s) => match s.parse::<i64>() {
-                Ok(n) => n,
-                Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-            },
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        };
-
-        let end_val = match std::str::from_utf8(&args[3]) {
-            Ok(s) => match s.parse::<i64>() {
-                Ok(n) => n,
-                Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-            },
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        };
-
-        let len = value.len() as i64;
-        let start = if start_val < 0 { (len + start_val).max(0) } else { start_val.min(len) };
-        let end = if end_val < 0 { (len + end_val).max(-1) } else { end_val.min(len - 1) };
-
-        (start as usize, end as usize)
-    } else if args.len() == 3 {
-        let start_val = match std::str::from_utf8(&args[2]) {
-            Ok(s) => match s.parse::<i64>() {
-                Ok(n) => n,
-                Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-            },
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        };
-
-        let len = value.len() as i64;
-        let start = if start_val < 0 { (len + start_val).max(0) } else { start_val.min(len) };
-
-        (start as usize, value.len().saturating_sub(1))
-    } else {
-        (0, value.len().saturating_sub(1))
+    let (start, end) = match resolve_bit_range(start, end_arg.unwrap_or(-1), unit, value.len()) {
+        Some(range) => range,
+        None => return RespValue::Integer(-1),
     };
 
-    // Find first occurrence of bit
-    for byte_idx in start..=end.min(value.len().saturating_sub(1)) {
-        let byte = value[byte_idx];
-
-        for bit_idx in 0..8 {
-            let bit_value = (byte >> (7 - bit_idx)) & 1;
-            if bit_value == bit {
-                return RespValue::Integer((byte_idx * 8 + bit_idx) as i64);
-            }
+    // Find first occurrence of bit, skipping whole bytes without it
+    let skip = if bit == 1 { 0x00 } else { 0xFF };
+    let mut pos = start;
+    while pos <= end {
+        if pos % 8 == 0 && pos + 7 <= end && value[pos / 8] == skip {
+            pos += 8;
+            continue;
+        }
+        if (value[pos / 8] >> (7 - pos % 8)) & 1 == bit {
+            return RespValue::Integer(pos as i64);
         }
+        pos += 1;
     }
 
+    // Looking for a clear bit with no end given, the string is considered
+    // padded with zeros on the right
+    if bit == 0 && end_arg.is_none() {
+        return RespValue::Integer(value.len() as i64 * 8);
+    }
     RespValue::Integer(-1) // Not found
 }
 
@@ -375,13 +401,278 @@ pub async fn bitop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     // Store result
     if result_len > 0 {
         db_instance.set(destkey.to_string(), RedisValue::String(Bytes::from(result)));
-    } else {
-        db_instance.delete(destkey);
+        db_instance.notify(EventClass::STRING, "set", destkey);
+    } else if db_instance.delete(destkey) {
+        db_instance.notify(EventClass::GENERIC, "del", destkey);
     }
 
     RespValue::Integer(result_len as i64)
 }
 
+/// How BITFIELD SET and INCRBY handle values that do not fit the field
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// A BITFIELD integer type: i1..i64 or u1..u63
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct FieldType {
+    signed: bool,
+    bits: u32,
+}
+
+impl FieldType {
+    fn parse(arg: &[u8]) -> Result<Self, RespValue> {
+        let error = || {
+            RespValue::Error(
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                    .to_string(),
+            )
+        };
+        let signed = match arg.first() {
+            Some(b'i') | Some(b'I') => true,
+            Some(b'u') | Some(b'U') => false,
+            _ => return Err(error()),
+        };
+        let bits: u32 = std::str::from_utf8(&arg[1..])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(error)?;
+        let max_bits = if signed { 64 } else { 63 };
+        if bits == 0 || bits > max_bits {
+            return Err(error());
+        }
+        Ok(Self { signed, bits })
+    }
+
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1i128 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1i128 << (self.bits - 1)) - 1
+        } else {
+            (1i128 << self.bits) - 1
+        }
+    }
+
+    /// Fit `value` into the field, or None when it overflows under FAIL
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                let modulus = 1i128 << self.bits;
+                let wrapped = value.rem_euclid(modulus);
+                Some(if wrapped > self.max() { wrapped - modulus } else { wrapped } as i64)
+            }
+            Overflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum FieldOp {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+/// One GET / SET / INCRBY of a BITFIELD call, with the OVERFLOW mode in
+/// effect at its position
+#[derive(Debug)]
+struct FieldCommand {
+    op: FieldOp,
+    field: FieldType,
+    offset: usize,
+    overflow: Overflow,
+}
+
+/// Parse a field offset: a bit offset, or `#n` for the n-th field of its width
+fn parse_field_offset(arg: &[u8], field: FieldType) -> Result<usize, RespValue> {
+    let error = || RespValue::Error("ERR bit offset is not an integer or out of range".to_string());
+    let (multiplier, digits) = match arg.strip_prefix(b"#") {
+        Some(rest) => (field.bits as u64, rest),
+        None => (1, arg),
+    };
+    let offset = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(error)?;
+    // Redis limits strings to 512MB
+    if offset + field.bits as u64 > 1 << 32 {
+        return Err(error());
+    }
+    Ok(offset as usize)
+}
+
+fn parse_bitfield(args: &[Vec<u8>]) -> Result<Vec<FieldCommand>, RespValue> {
+    let syntax = || RespValue::Error("ERR syntax error".to_string());
+    let mut commands = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut i = 0;
+    while i < args.len() {
+        let sub = String::from_utf8_lossy(&args[i]).to_uppercase();
+        if sub == "OVERFLOW" {
+            overflow = match args.get(i + 1).map(|a| String::from_utf8_lossy(a).to_uppercase()) {
+                Some(mode) if mode == "WRAP" => Overflow::Wrap,
+                Some(mode) if mode == "SAT" => Overflow::Sat,
+                Some(mode) if mode == "FAIL" => Overflow::Fail,
+                Some(_) => return Err(RespValue::Error("ERR Invalid OVERFLOW type specified".to_string())),
+                None => return Err(syntax()),
+            };
+            i += 2;
+            continue;
+        }
+
+        let arity = match sub.as_str() {
+            "GET" => 3,
+            "SET" | "INCRBY" => 4,
+            _ => return Err(syntax()),
+        };
+        if i + arity > args.len() {
+            return Err(syntax());
+        }
+        let field = FieldType::parse(&args[i + 1])?;
+        let offset = parse_field_offset(&args[i + 2], field)?;
+        let op = match sub.as_str() {
+            "GET" => FieldOp::Get,
+            "SET" => FieldOp::Set(parse_i64(&args[i + 3])?),
+            _ => FieldOp::IncrBy(parse_i64(&args[i + 3])?),
+        };
+        commands.push(FieldCommand { op, field, offset, overflow });
+        i += arity;
+    }
+    Ok(commands)
+}
+
+/// Read a big-endian field; bits past the end of the string are zero
+fn read_field(bytes: &[u8], offset: usize, field: FieldType) -> i64 {
+    let mut value: u64 = 0;
+    for pos in offset..offset + field.bits as usize {
+        let bit = bytes.get(pos / 8).map_or(0, |b| (b >> (7 - pos % 8)) & 1);
+        value = (value << 1) | bit as u64;
+    }
+    if field.signed && field.bits < 64 && value >> (field.bits - 1) & 1 == 1 {
+        // Sign-extend
+        value |= u64::MAX << field.bits;
+    }
+    value as i64
+}
+
+/// Write a big-endian field, growing the string as needed
+fn write_field(bytes: &mut Vec<u8>, offset: usize, field: FieldType, value: i64) {
+    let end = offset + field.bits as usize;
+    if bytes.len() < end.div_ceil(8) {
+        bytes.resize(end.div_ceil(8), 0);
+    }
+    let value = value as u64;
+    for (i, pos) in (offset..end).enumerate() {
+        let bit = (value >> (field.bits as usize - 1 - i)) & 1;
+        let mask = 1u8 << (7 - pos % 8);
+        if bit == 1 {
+            bytes[pos / 8] |= mask;
+        } else {
+            bytes[pos / 8] &= !mask;
+        }
+    }
+}
+
+async fn bitfield_generic(
+    db: &Arc<Database>,
+    db_index: usize,
+    args: Vec<Vec<u8>>,
+    read_only: bool,
+) -> RespValue {
+    let name = if read_only { "bitfield_ro" } else { "bitfield" };
+    if args.is_empty() {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", name));
+    }
+
+    let key = match std::str::from_utf8(&args[0]) {
+        Ok(s) => s,
+        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
+    };
+
+    let commands = match parse_bitfield(&args[1..]) {
+        Ok(commands) => commands,
+        Err(e) => return e,
+    };
+    let writes = commands.iter().any(|c| c.op != FieldOp::Get);
+    if read_only && (writes || args[1..].iter().any(|a| a.eq_ignore_ascii_case(b"OVERFLOW"))) {
+        return RespValue::Error("ERR BITFIELD_RO only supports the GET subcommand".to_string());
+    }
+
+    let db_instance = db.get_db(db_index).unwrap();
+
+    let mut bytes = match db_instance.get(key) {
+        Some(val) => match val.as_string() {
+            Some(s) => s.to_vec(),
+            None => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
+        },
+        None => Vec::new(),
+    };
+
+    let mut results = Vec::with_capacity(commands.len());
+    for command in &commands {
+        let FieldCommand { field, offset, overflow, .. } = *command;
+        let old = read_field(&bytes, offset, field);
+        let result = match command.op {
+            FieldOp::Get => Some(old),
+            FieldOp::Set(value) => field.fit(value as i128, overflow).map(|new| {
+                write_field(&mut bytes, offset, field, new);
+                old
+            }),
+            FieldOp::IncrBy(increment) => field
+                .fit(old as i128 + increment as i128, overflow)
+                .inspect(|&new| write_field(&mut bytes, offset, field, new)),
+        };
+        results.push(match result {
+            Some(value) => RespValue::Integer(value),
+            None => RespValue::BulkString(None),
+        });
+    }
+
+    if writes {
+        db_instance.set(key.to_string(), RedisValue::String(Bytes::from(bytes)));
+        db_instance.notify(EventClass::STRING, "setbit", key);
+    }
+
+    RespValue::Array(Some(results))
+}
+
+/// BITFIELD key [GET type offset] [SET type offset value]
+///   [INCRBY type offset increment] [OVERFLOW WRAP|SAT|FAIL] ...
+/// Read and update integers of arbitrary width packed into a string
+pub async fn bitfield(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    bitfield_generic(db, db_index, args, false).await
+}
+
+/// BITFIELD_RO key [GET type offset ...]
+/// Read-only BITFIELD, allowed on replicas
+pub async fn bitfield_ro(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    bitfield_generic(db, db_index, args, true).await
+}
+
+/// Whether a BITFIELD call (command name included) has a SET or INCRBY
+/// and so needs to be logged and propagated
+pub fn bitfield_writes(args: &[Vec<u8>]) -> bool {
+    args.iter()
+        .skip(2)
+        .any(|a| a.eq_ignore_ascii_case(b"SET") || a.eq_ignore_ascii_case(b"INCRBY"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -417,4 +708,120 @@ mod tests {
         let result = bitcount(&db, 0, vec![b"key".to_vec()]).await;
         assert_eq!(result, RespValue::Integer(3));
     }
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    fn set_raw(db: &Arc<Database>, key: &str, bytes: &[u8]) {
+        db.get_db(0).unwrap().set(key.to_string(), RedisValue::String(Bytes::from(bytes.to_vec())));
+    }
+
+    fn ints(values: &[i64]) -> RespValue {
+        RespValue::Array(Some(values.iter().map(|&v| RespValue::Integer(v)).collect()))
+    }
+
+    #[tokio::test]
+    async fn test_bitcount_bitpos_ranges() {
+        let db = Arc::new(Database::new(16));
+        set_raw(&db, "foo", b"foobar");
+
+        assert_eq!(bitcount(&db, 0, args(&["foo", "0", "0"])).await, RespValue::Integer(4));
+        assert_eq!(bitcount(&db, 0, args(&["foo", "1", "1", "BYTE"])).await, RespValue::Integer(6));
+        assert_eq!(bitcount(&db, 0, args(&["foo", "5", "30", "BIT"])).await, RespValue::Integer(17));
+        assert_eq!(bitcount(&db, 0, args(&["foo", "-2", "-3"])).await, RespValue::Integer(0));
+        assert_eq!(
+            bitcount(&db, 0, args(&["foo", "0"])).await,
+            RespValue::Error("ERR syntax error".to_string())
+        );
+
+        set_raw(&db, "a", b"\x00\xff\xf0");
+        assert_eq!(bitpos(&db, 0, args(&["a", "1", "0"])).await, RespValue::Integer(8));
+        assert_eq!(bitpos(&db, 0, args(&["a", "1", "2", "-1", "BYTE"])).await, RespValue::Integer(16));
+        assert_eq!(bitpos(&db, 0, args(&["a", "1", "7", "15", "BIT"])).await, RespValue::Integer(8));
+        assert_eq!(bitpos(&db, 0, args(&["a", "0", "8", "-1", "BIT"])).await, RespValue::Integer(20));
+
+        // Clear bits past the end count only when no end is given
+        set_raw(&db, "ones", b"\xff\xff\xff");
+        assert_eq!(bitpos(&db, 0, args(&["ones", "0"])).await, RespValue::Integer(24));
+        assert_eq!(bitpos(&db, 0, args(&["ones", "0", "0", "-1"])).await, RespValue::Integer(-1));
+        assert_eq!(bitpos(&db, 0, args(&["missing", "0"])).await, RespValue::Integer(0));
+        assert_eq!(bitpos(&db, 0, args(&["missing", "1"])).await, RespValue::Integer(-1));
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_get_set_incrby() {
+        let db = Arc::new(Database::new(16));
+
+        let reply = bitfield(&db, 0, args(&["k", "INCRBY", "i5", "100", "1", "GET", "u4", "0"])).await;
+        assert_eq!(reply, ints(&[1, 0]));
+
+        // `#` offsets are multiplied by the type width; SET returns the old value
+        let reply = bitfield(&db, 0, args(&["c", "SET", "i8", "#0", "100", "SET", "i8", "#1", "-56"])).await;
+        assert_eq!(reply, ints(&[0, 0]));
+        let reply = bitfield(&db, 0, args(&["c", "GET", "u8", "8", "GET", "i8", "#0"])).await;
+        assert_eq!(reply, ints(&[200, 100]));
+        assert_eq!(
+            db.get_db(0).unwrap().get("c").unwrap().as_string().unwrap().as_ref(),
+            &[100u8, 200]
+        );
+
+        // Full-width fields
+        let reply = bitfield(
+            &db,
+            0,
+            args(&["w", "SET", "i64", "0", "-9223372036854775808", "INCRBY", "i64", "0", "-1"]),
+        )
+        .await;
+        assert_eq!(reply, ints(&[0, i64::MAX]));
+        let reply = bitfield(&db, 0, args(&["w", "SET", "u63", "1", "9223372036854775807", "GET", "u63", "1"])).await;
+        assert_eq!(reply, ints(&[i64::MAX, i64::MAX]));
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_overflow() {
+        let db = Arc::new(Database::new(16));
+        let incr = args(&["k", "INCRBY", "u2", "100", "1", "OVERFLOW", "SAT", "INCRBY", "u2", "102", "1"]);
+        let mut replies = Vec::new();
+        for _ in 0..4 {
+            replies.push(bitfield(&db, 0, incr.clone()).await);
+        }
+        assert_eq!(replies, vec![ints(&[1, 1]), ints(&[2, 2]), ints(&[3, 3]), ints(&[0, 3])]);
+
+        // FAIL leaves the field untouched and replies nil
+        let reply = bitfield(
+            &db,
+            0,
+            args(&["f", "OVERFLOW", "FAIL", "SET", "i4", "0", "8", "INCRBY", "i4", "0", "-9", "INCRBY", "i4", "0", "-8"]),
+        )
+        .await;
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![RespValue::BulkString(None), RespValue::BulkString(None), RespValue::Integer(-8)]))
+        );
+        let reply = bitfield(&db, 0, args(&["f", "OVERFLOW", "SAT", "SET", "i4", "0", "100", "GET", "i4", "0"])).await;
+        assert_eq!(reply, ints(&[-8, 7]));
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_errors_and_ro() {
+        let db = Arc::new(Database::new(16));
+
+        let reply = bitfield(&db, 0, args(&["k", "GET", "u64", "0"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("Invalid bitfield type")));
+        let reply = bitfield(&db, 0, args(&["k", "GET", "i8", "-1"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("bit offset")));
+        let reply = bitfield(&db, 0, args(&["k", "OVERFLOW", "BOUNCE"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("OVERFLOW")));
+        let reply = bitfield(&db, 0, args(&["k", "SET", "i8", "0"])).await;
+        assert_eq!(reply, RespValue::Error("ERR syntax error".to_string()));
+
+        let reply = bitfield_ro(&db, 0, args(&["k", "SET", "i8", "0", "1"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("only supports the GET")));
+        assert_eq!(bitfield_ro(&db, 0, args(&["k", "GET", "i8", "0"])).await, ints(&[0]));
+        assert!(db.get_db(0).unwrap().get("k").is_none());
+
+        assert!(bitfield_writes(&args(&["BITFIELD", "k", "GET", "i8", "0", "incrby", "i8", "0", "1"])));
+        assert!(!bitfield_writes(&args(&["BITFIELD", "set", "GET", "i8", "0"])));
+    }
 }
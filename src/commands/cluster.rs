@@ -1,7 +1,12 @@
 // CLUSTER command implementation
 
-use crate::cluster::{key_hash_slot, ClusterState};
+use crate::cluster::{key_hash_slot, ClusterBus, ClusterState, MigrationManager};
+use crate::config::Config;
 use crate::protocol::RespValue;
+use crate::replication::{start_replication, ReplicationBacklog, ReplicationInfo};
+use crate::storage::db::Database;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 /// CLUSTER NODES - List all cluster nodes
@@ -81,6 +86,10 @@ pub fn cluster_addslots(cluster: &Arc<ClusterState>, slots: Vec<u16>) -> RespVal
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
+    if let Some(slot) = find_duplicate(&slots) {
+        return RespValue::Error(format!("ERR Slot {} specified multiple times", slot));
+    }
+
     // Check if any slot is already assigned
     for &slot in &slots {
         if let Some(owner) = cluster.get_slot_node(slot) {
@@ -99,50 +108,130 @@ pub fn cluster_addslots(cluster: &Arc<ClusterState>, slots: Vec<u16>) -> RespVal
     RespValue::SimpleString("OK".to_string())
 }
 
+/// Keys dropped along with their slots, one list per database, for the
+/// caller to log as deletions
+pub type DroppedKeys = Vec<Vec<String>>;
+
 /// CLUSTER DELSLOTS slot [slot ...]
-/// Remove slot assignments from this node
-pub fn cluster_delslots(cluster: &Arc<ClusterState>, slots: Vec<u16>) -> RespValue {
+/// Remove slot assignments from this node, dropping the keys they held
+pub fn cluster_delslots(cluster: &Arc<ClusterState>, db: &Arc<Database>, slots: Vec<u16>) -> (RespValue, DroppedKeys) {
     if !cluster.enabled {
-        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+        return (RespValue::Error("ERR This instance has cluster support disabled".to_string()), Vec::new());
+    }
+
+    if let Some(slot) = find_duplicate(&slots) {
+        return (RespValue::Error(format!("ERR Slot {} specified multiple times", slot)), Vec::new());
+    }
+    if let Some(slot) = slots.iter().find(|&&slot| cluster.get_slot_node(slot).is_none()) {
+        return (RespValue::Error(format!("ERR Slot {} is already unassigned", slot)), Vec::new());
+    }
+
+    (RespValue::SimpleString("OK".to_string()), drop_slots(cluster, db, slots))
+}
+
+/// CLUSTER FLUSHSLOTS
+/// Remove every slot from this node along with its keys
+pub fn cluster_flushslots(cluster: &Arc<ClusterState>, db: &Arc<Database>) -> (RespValue, DroppedKeys) {
+    if !cluster.enabled {
+        return (RespValue::Error("ERR This instance has cluster support disabled".to_string()), Vec::new());
     }
 
-    // Remove all specified slots
+    (RespValue::SimpleString("OK".to_string()), drop_slots(cluster, db, cluster.get_my_slots()))
+}
+
+/// Unassign `slots` from this node and delete their keys
+fn drop_slots(cluster: &ClusterState, db: &Database, slots: impl IntoIterator<Item = u16>) -> DroppedKeys {
+    let mut dropped = vec![Vec::new(); db.num_dbs()];
     for slot in slots {
         cluster.del_slot(slot);
+        for (all, keys) in dropped.iter_mut().zip(db.delete_slot(slot)) {
+            all.extend(keys);
+        }
     }
+    dropped
+}
 
-    RespValue::SimpleString("OK".to_string())
+/// CLUSTER ADDSLOTSRANGE start end [start end ...]
+pub fn cluster_addslotsrange(cluster: &Arc<ClusterState>, ranges: Vec<(u16, u16)>) -> RespValue {
+    match expand_slot_ranges(&ranges) {
+        Ok(slots) => cluster_addslots(cluster, slots),
+        Err(e) => e,
+    }
 }
 
-/// CLUSTER MEET ip port
-/// Add a node to the cluster (placeholder - full implementation needs gossip protocol)
-pub fn cluster_meet(
+/// CLUSTER DELSLOTSRANGE start end [start end ...]
+pub fn cluster_delslotsrange(
     cluster: &Arc<ClusterState>,
-    _ip: String,
-    _port: u16,
-) -> RespValue {
-    if !cluster.enabled {
+    db: &Arc<Database>,
+    ranges: Vec<(u16, u16)>,
+) -> (RespValue, DroppedKeys) {
+    match expand_slot_ranges(&ranges) {
+        Ok(slots) => cluster_delslots(cluster, db, slots),
+        Err(e) => (e, Vec::new()),
+    }
+}
+
+fn expand_slot_ranges(ranges: &[(u16, u16)]) -> Result<Vec<u16>, RespValue> {
+    let mut slots = Vec::new();
+    for &(start, end) in ranges {
+        if start > end {
+            return Err(RespValue::Error(format!(
+                "ERR start slot number {} is greater than end slot number {}",
+                start, end
+            )));
+        }
+        slots.extend(start..=end);
+    }
+    Ok(slots)
+}
+
+fn find_duplicate(slots: &[u16]) -> Option<u16> {
+    let mut seen = HashSet::new();
+    slots.iter().copied().find(|slot| !seen.insert(*slot))
+}
+
+/// CLUSTER MEET ip port
+/// Start a handshake with another node; it joins once it answers
+pub fn cluster_meet(bus: &Arc<ClusterBus>, ip: &str, port: &str) -> RespValue {
+    if !bus.cluster().enabled {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
-    // TODO: Implement actual handshake and node addition
-    // For now, just return OK
+    let addr = match (ip.parse::<IpAddr>(), port.parse::<u16>()) {
+        (Ok(ip), Ok(port)) if !ip.is_unspecified() && port != 0 && port <= u16::MAX - 10000 => {
+            SocketAddr::new(ip, port)
+        }
+        _ => {
+            return RespValue::Error(format!("ERR Invalid node address specified: {}:{}", ip, port));
+        }
+    };
+
+    // A handshake already in progress with the same address is fine
+    bus.meet(addr);
     RespValue::SimpleString("OK".to_string())
 }
 
 /// CLUSTER FORGET node-id
 /// Remove a node from the cluster
-pub fn cluster_forget(cluster: &Arc<ClusterState>, node_id: String) -> RespValue {
+pub fn cluster_forget(bus: &Arc<ClusterBus>, node_id: String) -> RespValue {
+    let cluster = bus.cluster();
     if !cluster.enabled {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
     // Don't allow forgetting myself
-    if node_id == cluster.my_id {
+    if node_id == cluster.my_id() {
         return RespValue::Error("ERR I tried hard but I can't forget myself...".to_string());
     }
+    if cluster.get_node(&node_id).is_none() {
+        return RespValue::Error(format!("ERR Unknown node {}", node_id));
+    }
+    let my_master = cluster.myself().and_then(|n| n.master_id);
+    if my_master.as_deref() == Some(node_id.as_str()) {
+        return RespValue::Error("ERR Can't forget my master!".to_string());
+    }
 
-    cluster.remove_node(&node_id);
+    bus.forget(&node_id);
     RespValue::SimpleString("OK".to_string())
 }
 
@@ -150,42 +239,305 @@ pub fn cluster_forget(cluster: &Arc<ClusterState>, node_id: String) -> RespValue
 /// Make this node a replica of the specified master
 pub fn cluster_replicate(
     cluster: &Arc<ClusterState>,
-    _master_id: String,
+    db: &Arc<Database>,
+    repl_info: &Arc<ReplicationInfo>,
+    backlog: &Arc<ReplicationBacklog>,
+    config: &Arc<Config>,
+    master_id: String,
+) -> RespValue {
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let master = match cluster.get_node(&master_id) {
+        Some(node) if !node.in_handshake() => node,
+        _ => return RespValue::Error(format!("ERR Unknown node {}", master_id)),
+    };
+    if master_id == cluster.my_id() {
+        return RespValue::Error("ERR Can't replicate myself".to_string());
+    }
+    if master.is_slave() {
+        return RespValue::Error("ERR I can only replicate a master, not a replica.".to_string());
+    }
+    if cluster.is_myself_master() && (cluster.count_my_slots() > 0 || !db.is_empty()) {
+        return RespValue::Error(
+            "ERR To set a master the node must be empty and without assigned slots.".to_string(),
+        );
+    }
+    let Some(addr) = master.addr else {
+        return RespValue::Error(format!("ERR Node {} has no known address", master_id));
+    };
+
+    cluster.set_my_master(&master_id);
+    start_replication(&addr.ip().to_string(), addr.port(), repl_info, backlog, db, config);
+    RespValue::SimpleString("OK".to_string())
+}
+
+/// CLUSTER RESET [HARD|SOFT]
+/// Forget every other node and slot; a replica also drops its dataset
+pub fn cluster_reset(
+    bus: &Arc<ClusterBus>,
+    db: &Arc<Database>,
+    repl_info: &Arc<ReplicationInfo>,
+    migration: &Arc<MigrationManager>,
+    hard: bool,
 ) -> RespValue {
+    let cluster = bus.cluster();
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    if cluster.is_myself_master() {
+        if !db.is_empty() {
+            return RespValue::Error(
+                "ERR CLUSTER RESET can't be called with master nodes containing keys".to_string(),
+            );
+        }
+    } else {
+        repl_info.set_master();
+        for index in 0..db.num_dbs() {
+            if let Some(instance) = db.get_db(index) {
+                instance.clear();
+            }
+        }
+    }
+
+    migration.clear();
+    bus.reset(hard);
+    RespValue::SimpleString("OK".to_string())
+}
+
+/// CLUSTER FAILOVER [FORCE|TAKEOVER]
+/// Make this replica replace its master
+pub fn cluster_failover(bus: &Arc<ClusterBus>, mode: Option<&str>) -> RespValue {
+    let cluster = bus.cluster();
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let (force, takeover) = match mode.map(|m| m.to_uppercase()).as_deref() {
+        None => (false, false),
+        Some("FORCE") => (true, false),
+        Some("TAKEOVER") => (true, true),
+        Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+    };
+
+    if cluster.is_myself_master() {
+        return RespValue::Error("ERR You should send CLUSTER FAILOVER to a replica".to_string());
+    }
+    let master = cluster.myself().and_then(|n| n.master_id).and_then(|id| cluster.get_node(&id));
+    let Some(master) = master else {
+        return RespValue::Error("ERR I'm a replica but my master is unknown to me".to_string());
+    };
+    if !force && (master.is_failed() || master.link_state == "disconnected") {
+        return RespValue::Error("ERR Master is down or failed, please use CLUSTER FAILOVER FORCE".to_string());
+    }
+
+    if takeover {
+        bus.takeover();
+    } else {
+        bus.start_manual_failover(force);
+    }
+    RespValue::SimpleString("OK".to_string())
+}
+
+/// CLUSTER SHARDS - Slot ranges and nodes of every shard
+pub fn cluster_shards(cluster: &Arc<ClusterState>, repl_info: &Arc<ReplicationInfo>) -> RespValue {
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let bulk = |s: &str| RespValue::BulkString(Some(s.as_bytes().to_vec()));
+    let mut masters = cluster.get_master_nodes();
+    masters.retain(|n| !n.in_handshake());
+    masters.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let my_id = cluster.my_id();
+    let mut shards = Vec::new();
+    for master in masters {
+        let mut slots = Vec::new();
+        for (start, end) in master.get_slot_ranges() {
+            slots.push(RespValue::Integer(start as i64));
+            slots.push(RespValue::Integer(end as i64));
+        }
+
+        let mut members = vec![master.clone()];
+        members.extend(cluster.get_replicas(&master.id));
+        let nodes = members
+            .iter()
+            .map(|node| {
+                let ip = node.addr.map(|a| a.ip().to_string()).unwrap_or_default();
+                let port = node.addr.map(|a| a.port()).unwrap_or(0);
+                let health = if node.is_failed() || node.is_pfail() { "fail" } else { "online" };
+                let offset = if node.id == my_id { repl_info.master_offset() } else { node.repl_offset };
+                RespValue::Array(Some(vec![
+                    bulk("id"),
+                    bulk(&node.id),
+                    bulk("port"),
+                    RespValue::Integer(port as i64),
+                    bulk("ip"),
+                    bulk(&ip),
+                    bulk("endpoint"),
+                    bulk(&ip),
+                    bulk("role"),
+                    bulk(if node.is_master() { "master" } else { "replica" }),
+                    bulk("replication-offset"),
+                    RespValue::Integer(offset as i64),
+                    bulk("health"),
+                    bulk(health),
+                ]))
+            })
+            .collect();
+
+        shards.push(RespValue::Array(Some(vec![
+            bulk("slots"),
+            RespValue::Array(Some(slots)),
+            bulk("nodes"),
+            RespValue::Array(Some(nodes)),
+        ])));
+    }
+
+    RespValue::Array(Some(shards))
+}
+
+/// CLUSTER LINKS - Open cluster bus connections
+pub fn cluster_links(bus: &Arc<ClusterBus>) -> RespValue {
+    if !bus.cluster().enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let bulk = |s: &str| RespValue::BulkString(Some(s.as_bytes().to_vec()));
+    let links = bus
+        .links()
+        .into_iter()
+        .map(|link| {
+            RespValue::Array(Some(vec![
+                bulk("direction"),
+                bulk(link.direction),
+                bulk("node"),
+                bulk(&link.node_id),
+                bulk("create-time"),
+                RespValue::Integer(link.create_time as i64),
+                bulk("events"),
+                bulk(link.events),
+                bulk("send-buffer-allocated"),
+                RespValue::Integer(0),
+                bulk("send-buffer-used"),
+                RespValue::Integer(0),
+            ]))
+        })
+        .collect();
+
+    RespValue::Array(Some(links))
+}
+
+/// CLUSTER COUNT-FAILURE-REPORTS node-id
+pub fn cluster_count_failure_reports(bus: &Arc<ClusterBus>, node_id: String) -> RespValue {
+    let cluster = bus.cluster();
     if !cluster.enabled {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
+    if cluster.get_node(&node_id).is_none() {
+        return RespValue::Error(format!("ERR Unknown node {}", node_id));
+    }
+
+    let validity = bus.node_timeout() * 2;
+    RespValue::Integer(cluster.count_failure_reports(&node_id, validity) as i64)
+}
+
+/// CLUSTER SET-CONFIG-EPOCH epoch
+/// Only allowed on a fresh node, before it joins a cluster
+pub fn cluster_set_config_epoch(cluster: &Arc<ClusterState>, epoch: &str) -> RespValue {
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let epoch = match epoch.parse::<u64>() {
+        Ok(e) => e,
+        Err(_) => return RespValue::Error(format!("ERR Invalid config epoch specified: {}", epoch)),
+    };
+    if cluster.nodes.len() > 1 {
+        return RespValue::Error(
+            "ERR The user can assign a config epoch only when the node does not know any other node."
+                .to_string(),
+        );
+    }
+    if cluster.my_config_epoch() != 0 {
+        return RespValue::Error("ERR Node config epoch is already non-zero".to_string());
+    }
 
-    // TODO: Implement actual replication setup
-    // For now, just return OK
+    cluster.set_my_config_epoch(epoch);
     RespValue::SimpleString("OK".to_string())
 }
 
+/// CLUSTER BUMPEPOCH - Take a new config epoch if ours is not unique
+pub fn cluster_bumpepoch(cluster: &Arc<ClusterState>) -> RespValue {
+    if !cluster.enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    let (bumped, epoch) = cluster.bump_epoch_without_consensus();
+    let status = if bumped { "BUMPED" } else { "STILL" };
+    RespValue::SimpleString(format!("{} {}", status, epoch))
+}
+
+/// CLUSTER SAVECONFIG - Force nodes.conf to disk
+pub fn cluster_saveconfig(bus: &Arc<ClusterBus>) -> RespValue {
+    if !bus.cluster().enabled {
+        return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+    }
+
+    match bus.save_config() {
+        Ok(()) => RespValue::SimpleString("OK".to_string()),
+        Err(e) => RespValue::Error(format!("ERR error saving the cluster node config: {}", e)),
+    }
+}
+
 /// CLUSTER INFO - Get cluster state information
 pub fn cluster_info(cluster: &Arc<ClusterState>) -> RespValue {
     if !cluster.enabled {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
-    let state = if cluster.enabled { "ok" } else { "fail" };
+    let state = if cluster.state_ok() { "ok" } else { "fail" };
     let slots_assigned = cluster.slot_map.len();
-    let slots_ok = slots_assigned;
+    let mut slots_pfail = 0;
+    let mut slots_fail = 0;
+    for node in cluster.get_all_nodes() {
+        if node.is_failed() {
+            slots_fail += node.slots.len();
+        } else if node.is_pfail() {
+            slots_pfail += node.slots.len();
+        }
+    }
+    let slots_ok = slots_assigned.saturating_sub(slots_pfail + slots_fail);
     let known_nodes = cluster.nodes.len();
-    let size = cluster.get_master_nodes().len();
+    let size = cluster.size();
+    let (messages_sent, messages_received) = cluster.message_stats();
 
     let info = format!(
         "cluster_state:{}\n\
          cluster_slots_assigned:{}\n\
          cluster_slots_ok:{}\n\
-         cluster_slots_pfail:0\n\
-         cluster_slots_fail:0\n\
+         cluster_slots_pfail:{}\n\
+         cluster_slots_fail:{}\n\
          cluster_known_nodes:{}\n\
          cluster_size:{}\n\
-         cluster_current_epoch:0\n\
-         cluster_my_epoch:0\n\
-         cluster_stats_messages_sent:0\n\
-         cluster_stats_messages_received:0\n",
-        state, slots_assigned, slots_ok, known_nodes, size
+         cluster_current_epoch:{}\n\
+         cluster_my_epoch:{}\n\
+         cluster_stats_messages_sent:{}\n\
+         cluster_stats_messages_received:{}\n",
+        state,
+        slots_assigned,
+        slots_ok,
+        slots_pfail,
+        slots_fail,
+        known_nodes,
+        size,
+        cluster.current_epoch(),
+        cluster.my_config_epoch(),
+        messages_sent,
+        messages_received
     );
 
     RespValue::BulkString(Some(info.into_bytes()))
@@ -197,7 +549,7 @@ pub fn cluster_myid(cluster: &Arc<ClusterState>) -> RespValue {
         return RespValue::Error("ERR This instance has cluster support disabled".to_string());
     }
 
-    RespValue::BulkString(Some(cluster.my_id.as_bytes().to_vec()))
+    RespValue::BulkString(Some(cluster.my_id().as_bytes().to_vec()))
 }
 
 /// CLUSTER KEYSLOT key - Get hash slot for a key
@@ -206,24 +558,6 @@ pub fn cluster_keyslot(key: &[u8]) -> RespValue {
     RespValue::Integer(slot as i64)
 }
 
-/// CLUSTER COUNTKEYSINSLOT slot - Count keys in a slot
-/// (Placeholder - requires integration with database)
-pub fn cluster_countkeysinslot(_cluster: &Arc<ClusterState>, _slot: u16) -> RespValue {
-    // TODO: Integrate with database to count keys
-    RespValue::Integer(0)
-}
-
-/// CLUSTER GETKEYSINSLOT slot count - Get keys in a slot
-/// (Placeholder - requires integration with database)
-pub fn cluster_getkeysinslot(
-    _cluster: &Arc<ClusterState>,
-    _slot: u16,
-    _count: i64,
-) -> RespValue {
-    // TODO: Integrate with database to retrieve keys
-    RespValue::Array(Some(vec![]))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,7 +588,7 @@ mod tests {
             RespValue::BulkString(Some(data)) => {
                 let id = String::from_utf8(data).unwrap();
                 assert_eq!(id.len(), 40); // 40 hex chars
-                assert_eq!(id, cluster.my_id);
+                assert_eq!(id, cluster.my_id());
             }
             _ => panic!("Expected BulkString"),
         }
@@ -301,12 +635,48 @@ mod tests {
         cluster.add_slot(0);
         cluster.add_slot(100);
 
-        let result = cluster_delslots(&cluster, vec![0, 100]);
+        let (result, _) = cluster_delslots(&cluster, &Arc::new(Database::new(16)), vec![0, 100]);
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
         assert!(!cluster.owns_slot(0));
         assert!(!cluster.owns_slot(100));
     }
 
+    #[test]
+    fn test_cluster_delslots_drops_keys() {
+        use crate::cluster::migration::{cluster_countkeysinslot, cluster_getkeysinslot};
+        use crate::storage::types::RedisValue;
+
+        let cluster = Arc::new(ClusterState::new(true));
+        let db = Arc::new(Database::with_slot_index(16));
+        let slot = key_hash_slot(b"{a}");
+        let other = key_hash_slot(b"{b}");
+        cluster.assign_slots_to_node(&cluster.my_id(), vec![slot, other]);
+        let db0 = db.get_db(0).unwrap();
+        for key in ["{a}:1", "{a}:2", "{b}:1"] {
+            db0.set(key.to_string(), RedisValue::String(key.into()));
+        }
+
+        assert_eq!(cluster_countkeysinslot(&cluster, &db, 0, slot), RespValue::Integer(2));
+        assert_eq!(
+            cluster_getkeysinslot(&cluster, &db, 0, slot, 1),
+            RespValue::Array(Some(vec![RespValue::BulkString(Some(b"{a}:1".to_vec()))]))
+        );
+
+        let (result, dropped) = cluster_delslots(&cluster, &db, vec![slot]);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(dropped[0], vec!["{a}:1", "{a}:2"]);
+        assert!(dropped[1..].iter().all(Vec::is_empty));
+        assert_eq!(cluster_countkeysinslot(&cluster, &db, 0, slot), RespValue::Integer(0));
+        assert!(!db0.exists("{a}:1"));
+        assert!(db0.exists("{b}:1"));
+
+        let (result, dropped) = cluster_flushslots(&cluster, &db);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(dropped[0], vec!["{b}:1"]);
+        assert_eq!(cluster.count_my_slots(), 0);
+        assert!(db0.is_empty());
+    }
+
     #[test]
     fn test_cluster_nodes() {
         let cluster = Arc::new(ClusterState::new(true));
@@ -315,7 +685,7 @@ mod tests {
         match result {
             RespValue::BulkString(Some(data)) => {
                 let output = String::from_utf8(data).unwrap();
-                assert!(output.contains(&cluster.my_id));
+                assert!(output.contains(&cluster.my_id()));
                 assert!(output.contains("myself"));
             }
             _ => panic!("Expected BulkString"),
@@ -333,7 +703,7 @@ mod tests {
 
         // Set address for the node
         let addr = SocketAddr::from_str("127.0.0.1:7000").ok();
-        if let Some(mut node) = cluster.nodes.get_mut(&cluster.my_id) {
+        if let Some(mut node) = cluster.nodes.get_mut(&cluster.my_id()) {
             node.addr = addr;
         }
 
@@ -363,4 +733,170 @@ mod tests {
             _ => panic!("Expected Error"),
         }
     }
+
+    fn bus() -> Arc<ClusterBus> {
+        Arc::new(ClusterBus::new(
+            Arc::new(ClusterState::new(true)),
+            Arc::new(MigrationManager::new()),
+            Arc::new(Config::new()),
+        ))
+    }
+
+    fn error_of(reply: RespValue) -> String {
+        match reply {
+            RespValue::Error(msg) => msg,
+            other => panic!("Expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cluster_slotsrange() {
+        let cluster = Arc::new(ClusterState::new(true));
+        let db = Arc::new(Database::new(16));
+
+        let result = cluster_addslotsrange(&cluster, vec![(0, 99), (200, 200)]);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(cluster.count_my_slots(), 101);
+
+        let msg = error_of(cluster_addslotsrange(&cluster, vec![(10, 5)]));
+        assert_eq!(msg, "ERR start slot number 10 is greater than end slot number 5");
+        let msg = error_of(cluster_addslotsrange(&cluster, vec![(300, 310), (305, 306)]));
+        assert_eq!(msg, "ERR Slot 305 specified multiple times");
+
+        let msg = error_of(cluster_delslotsrange(&cluster, &db, vec![(90, 110)]).0);
+        assert_eq!(msg, "ERR Slot 100 is already unassigned");
+        assert!(cluster.owns_slot(90));
+
+        let (result, _) = cluster_delslotsrange(&cluster, &db, vec![(50, 99)]);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(cluster.count_my_slots(), 51);
+        assert_eq!(cluster.myself().unwrap().slots.len(), 51);
+    }
+
+    #[test]
+    fn test_cluster_set_config_epoch_and_bumpepoch() {
+        let cluster = Arc::new(ClusterState::new(true));
+
+        let msg = error_of(cluster_set_config_epoch(&cluster, "-1"));
+        assert_eq!(msg, "ERR Invalid config epoch specified: -1");
+
+        assert_eq!(cluster_set_config_epoch(&cluster, "5"), RespValue::SimpleString("OK".to_string()));
+        assert_eq!(cluster.my_config_epoch(), 5);
+        assert_eq!(cluster.current_epoch(), 5);
+        let msg = error_of(cluster_set_config_epoch(&cluster, "6"));
+        assert_eq!(msg, "ERR Node config epoch is already non-zero");
+
+        // Unique and up to date: nothing to do
+        assert_eq!(cluster_bumpepoch(&cluster), RespValue::SimpleString("STILL 5".to_string()));
+
+        // Another master with the same epoch forces a bump
+        let mut other = crate::cluster::node::ClusterNode::new_master("other".to_string(), None);
+        other.config_epoch = 5;
+        cluster.add_node(other);
+        assert_eq!(cluster_bumpepoch(&cluster), RespValue::SimpleString("BUMPED 6".to_string()));
+
+        let msg = error_of(cluster_set_config_epoch(&cluster, "9"));
+        assert!(msg.contains("does not know any other node"));
+    }
+
+    #[test]
+    fn test_cluster_forget() {
+        let bus = bus();
+        let cluster = Arc::clone(bus.cluster());
+
+        let msg = error_of(cluster_forget(&bus, cluster.my_id()));
+        assert!(msg.contains("can't forget myself"));
+        let msg = error_of(cluster_forget(&bus, "nosuchnode".to_string()));
+        assert_eq!(msg, "ERR Unknown node nosuchnode");
+
+        let other = crate::cluster::node::ClusterNode::new_master("other".to_string(), None);
+        cluster.add_node(other);
+        cluster.assign_slots_to_node("other", vec![7, 8]);
+        cluster.set_my_master("other");
+        let msg = error_of(cluster_forget(&bus, "other".to_string()));
+        assert_eq!(msg, "ERR Can't forget my master!");
+
+        cluster.reset(false);
+        cluster.add_node(crate::cluster::node::ClusterNode::new_master("other".to_string(), None));
+        cluster.assign_slots_to_node("other", vec![7, 8]);
+        assert_eq!(cluster_forget(&bus, "other".to_string()), RespValue::SimpleString("OK".to_string()));
+        assert!(cluster.get_node("other").is_none());
+        assert_eq!(cluster.get_slot_node(7), None);
+    }
+
+    #[test]
+    fn test_cluster_reset() {
+        let bus = bus();
+        let cluster = Arc::clone(bus.cluster());
+        let db = Arc::new(Database::new(16));
+        let repl_info = Arc::new(ReplicationInfo::new());
+        let migration = Arc::new(MigrationManager::new());
+
+        let old_id = cluster.my_id();
+        cluster.add_slot(1);
+        cluster.set_my_config_epoch(3);
+        cluster.add_node(crate::cluster::node::ClusterNode::new_master("other".to_string(), None));
+        migration.set_importing(5, "other".to_string());
+
+        let result = cluster_reset(&bus, &db, &repl_info, &migration, false);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(cluster.my_id(), old_id);
+        assert_eq!(cluster.get_all_nodes().len(), 1);
+        assert!(cluster.slot_map.is_empty());
+        assert_eq!(cluster.my_config_epoch(), 3);
+        assert!(!migration.is_importing(5));
+
+        let result = cluster_reset(&bus, &db, &repl_info, &migration, true);
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_ne!(cluster.my_id(), old_id);
+        assert_eq!(cluster.myself().unwrap().id, cluster.my_id());
+        assert_eq!(cluster.my_config_epoch(), 0);
+        assert_eq!(cluster.current_epoch(), 0);
+    }
+
+    #[test]
+    fn test_cluster_replicate_checks() {
+        let cluster = Arc::new(ClusterState::new(true));
+        let db = Arc::new(Database::new(16));
+        let repl_info = Arc::new(ReplicationInfo::new());
+        let backlog = Arc::new(ReplicationBacklog::new());
+        let config = Arc::new(Config::new());
+        let replicate = |id: &str| cluster_replicate(&cluster, &db, &repl_info, &backlog, &config, id.to_string());
+
+        assert_eq!(error_of(replicate("nosuchnode")), "ERR Unknown node nosuchnode");
+        assert_eq!(error_of(replicate(&cluster.my_id())), "ERR Can't replicate myself");
+
+        cluster.add_node(crate::cluster::node::ClusterNode::new_replica("r".to_string(), None, "m".to_string()));
+        assert_eq!(error_of(replicate("r")), "ERR I can only replicate a master, not a replica.");
+
+        cluster.add_node(crate::cluster::node::ClusterNode::new_master("m".to_string(), None));
+        cluster.add_slot(0);
+        assert!(error_of(replicate("m")).contains("must be empty and without assigned slots"));
+    }
+
+    #[test]
+    fn test_cluster_shards() {
+        let cluster = Arc::new(ClusterState::new(true));
+        cluster.assign_slots_to_node(&cluster.my_id(), (0..=10).chain(20..=20).collect());
+
+        let shards = match cluster_shards(&cluster, &Arc::new(ReplicationInfo::new())) {
+            RespValue::Array(Some(shards)) => shards,
+            other => panic!("Expected Array, got {:?}", other),
+        };
+        assert_eq!(shards.len(), 1);
+        let RespValue::Array(Some(shard)) = &shards[0] else { panic!("Expected shard map") };
+        assert_eq!(
+            shard[1],
+            RespValue::Array(Some(vec![
+                RespValue::Integer(0),
+                RespValue::Integer(10),
+                RespValue::Integer(20),
+                RespValue::Integer(20),
+            ]))
+        );
+        let RespValue::Array(Some(nodes)) = &shard[3] else { panic!("Expected node list") };
+        let RespValue::Array(Some(node)) = &nodes[0] else { panic!("Expected node map") };
+        assert_eq!(node[1], RespValue::BulkString(Some(cluster.my_id().into_bytes())));
+        assert_eq!(node[9], RespValue::BulkString(Some(b"master".to_vec())));
+    }
 }
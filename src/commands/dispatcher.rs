@@ -85,6 +85,8 @@ impl CommandDispatcher {
             "BITCOUNT" => super::bitmap::bitcount(db, *db_index, args).await,
             "BITPOS" => super::bitmap::bitpos(db, *db_index, args).await,
             "BITOP" => super::bitmap::bitop(db, *db_index, args).await,
+            "BITFIELD" => super::bitmap::bitfield(db, *db_index, args).await,
+            "BITFIELD_RO" => super::bitmap::bitfield_ro(db, *db_index, args).await,
 
             // HyperLogLog commands
             "PFADD" => super::hyperloglog::pfadd(db, *db_index, args).await,
@@ -102,7 +104,7 @@ impl CommandDispatcher {
             "SAVE" => super::server_cmds::save(db).await,
             "BGSAVE" => super::server_cmds::bgsave(db).await,
             "BGREWRITEAOF" => super::server_cmds::bgrewriteaof(db, aof).await,
-            "INFO" => super::info_cmd::info(db, repl_info, args).await,
+            "INFO" => super::info_cmd::info(db, repl_info, config, args).await,
             "CLIENT" => super::admin_cmds::client(client_registry, client_id, args).await,
             "SLOWLOG" => super::admin_cmds::slowlog(slowlog, args).await,
             "COMMAND" => super::admin_cmds::command(args).await,
@@ -123,7 +125,7 @@ impl CommandDispatcher {
                     let rest_args = args[1..].to_vec();
                     match subcmd.as_str() {
                         "GET" => super::server_cmds::config_get(config, rest_args).await,
-                        "SET" => super::server_cmds::config_set(config, rest_args).await,
+                        "SET" => super::server_cmds::config_set(db, config, rest_args).await,
                         _ => RespValue::Error(format!("ERR Unknown CONFIG subcommand '{}'", subcmd)),
                     }
                 }
@@ -148,6 +150,9 @@ impl CommandDispatcher {
             "BLMOVE" => super::list::blmove(db, *db_index, args).await,
             "LPOS" => super::list::lpos(db, *db_index, args).await,
             "LMOVE" => super::list::lmove(db, *db_index, args).await,
+            "LINSERT" => super::list::linsert(db, *db_index, args).await,
+            "LMPOP" => super::list::lmpop(db, *db_index, args).await,
+            "BLMPOP" => super::list::blmpop(db, *db_index, args).await,
 
             // Hash commands
             "HSET" => super::hash::hset(db, *db_index, args).await,
@@ -165,6 +170,18 @@ impl CommandDispatcher {
             "HINCRBYFLOAT" => super::hash::hincrbyfloat(db, *db_index, args).await,
             "HSTRLEN" => super::hash::hstrlen(db, *db_index, args).await,
             "HSCAN" => super::hash::hscan(db, *db_index, args).await,
+            "HEXPIRE" => super::hash::hexpire(db, *db_index, args).await,
+            "HPEXPIRE" => super::hash::hpexpire(db, *db_index, args).await,
+            "HEXPIREAT" => super::hash::hexpireat(db, *db_index, args).await,
+            "HPEXPIREAT" => super::hash::hpexpireat(db, *db_index, args).await,
+            "HTTL" => super::hash::httl(db, *db_index, args).await,
+            "HPTTL" => super::hash::hpttl(db, *db_index, args).await,
+            "HEXPIRETIME" => super::hash::hexpiretime(db, *db_index, args).await,
+            "HPEXPIRETIME" => super::hash::hpexpiretime(db, *db_index, args).await,
+            "HPERSIST" => super::hash::hpersist(db, *db_index, args).await,
+            "HGETDEL" => super::hash::hgetdel(db, *db_index, args).await,
+            "HGETEX" => super::hash::hgetex(db, *db_index, args).await,
+            "HSETEX" => super::hash::hsetex(db, *db_index, args).await,
             "HRANDFIELD" => super::hash::hrandfield(db, *db_index, args).await,
 
             // Set commands
@@ -193,6 +210,7 @@ impl CommandDispatcher {
             "ZCOUNT" => super::zset::zcount(db, *db_index, args).await,
             "ZRANGE" => super::zset::zrange(db, *db_index, args).await,
             "ZREVRANGE" => super::zset::zrevrange(db, *db_index, args).await,
+            "ZRANGESTORE" => super::zset::zrangestore(db, *db_index, args).await,
             "ZRANGEBYSCORE" => super::zset::zrangebyscore(db, *db_index, args).await,
             "ZRANK" => super::zset::zrank(db, *db_index, args).await,
             "ZREVRANK" => super::zset::zrevrank(db, *db_index, args).await,
@@ -203,11 +221,17 @@ impl CommandDispatcher {
             "ZREMRANGEBYSCORE" => super::zset::zremrangebyscore(db, *db_index, args).await,
             "BZPOPMIN" => super::zset::bzpopmin(db, *db_index, args).await,
             "BZPOPMAX" => super::zset::bzpopmax(db, *db_index, args).await,
+            "ZMPOP" => super::zset::zmpop(db, *db_index, args).await,
+            "BZMPOP" => super::zset::bzmpop(db, *db_index, args).await,
+            "ZRANDMEMBER" => super::zset::zrandmember(db, *db_index, args).await,
             "ZMSCORE" => super::zset::zmscore(db, *db_index, args).await,
             "ZDIFF" => super::zset::zdiff(db, *db_index, args).await,
             "ZDIFFSTORE" => super::zset::zdiffstore(db, *db_index, args).await,
             "ZUNIONSTORE" => super::zset::zunionstore(db, *db_index, args).await,
             "ZINTERSTORE" => super::zset::zinterstore(db, *db_index, args).await,
+            "ZUNION" => super::zset::zunion(db, *db_index, args).await,
+            "ZINTER" => super::zset::zinter(db, *db_index, args).await,
+            "ZINTERCARD" => super::zset::zintercard(db, *db_index, args).await,
             "ZREVRANGEBYSCORE" => super::zset::zrevrangebyscore(db, *db_index, args).await,
             "ZLEXCOUNT" => super::zset::zlexcount(db, *db_index, args).await,
             "ZRANGEBYLEX" => super::zset::zrangebylex(db, *db_index, args).await,
@@ -220,6 +244,12 @@ impl CommandDispatcher {
             "GEOPOS" => super::geo::geopos(db, *db_index, args).await,
             "GEODIST" => super::geo::geodist(db, *db_index, args).await,
             "GEOHASH" => super::geo::geohash(db, *db_index, args).await,
+            "GEOSEARCH" => super::geo::geosearch(db, *db_index, args).await,
+            "GEOSEARCHSTORE" => super::geo::geosearchstore(db, *db_index, args).await,
+            "GEORADIUS" => super::geo::georadius(db, *db_index, args).await,
+            "GEORADIUS_RO" => super::geo::georadius_ro(db, *db_index, args).await,
+            "GEORADIUSBYMEMBER" => super::geo::georadiusbymember(db, *db_index, args).await,
+            "GEORADIUSBYMEMBER_RO" => super::geo::georadiusbymember_ro(db, *db_index, args).await,
 
             // Stream commands
             "XADD" => super::stream::xadd(db, *db_index, args).await,
@@ -229,6 +259,14 @@ impl CommandDispatcher {
             "XDEL" => super::stream::xdel(db, *db_index, args).await,
             "XREAD" => super::stream::xread(db, *db_index, args).await,
             "XTRIM" => super::stream::xtrim(db, *db_index, args).await,
+            "XSETID" => super::stream::xsetid(db, *db_index, args).await,
+            "XGROUP" => super::stream_group::xgroup(db, *db_index, args).await,
+            "XREADGROUP" => super::stream_group::xreadgroup(db, *db_index, args).await,
+            "XACK" => super::stream_group::xack(db, *db_index, args).await,
+            "XPENDING" => super::stream_group::xpending(db, *db_index, args).await,
+            "XCLAIM" => super::stream_group::xclaim(db, *db_index, args).await,
+            "XAUTOCLAIM" => super::stream_group::xautoclaim(db, *db_index, args).await,
+            "XINFO" => super::stream_group::xinfo(db, *db_index, args).await,
 
             // Expiration commands
             "EXPIRE" => super::expiration::expire(db, *db_index, args).await,
@@ -241,6 +279,8 @@ impl CommandDispatcher {
 
             // Pub/Sub commands (PUBLISH only - SUBSCRIBE handled separately)
             "PUBLISH" => super::pubsub_cmds::publish(pubsub, args).await,
+            "SPUBLISH" => super::pubsub_cmds::spublish(pubsub, args).await,
+            "PUBSUB" => super::pubsub_cmds::pubsub_command(pubsub, args).await,
 
             // Script commands
             "EVAL" => super::script_cmds::eval(db, *db_index, script_cache, args).await,
@@ -248,7 +288,7 @@ impl CommandDispatcher {
             "SCRIPT" => super::script_cmds::script(db, *db_index, script_cache, args).await,
 
             // Replication commands
-            "REPLICAOF" | "SLAVEOF" => super::replication_cmds::replicaof(repl_info, repl_backlog, db, args).await,
+            "REPLICAOF" | "SLAVEOF" => super::replication_cmds::replicaof(repl_info, repl_backlog, db, config, args).await,
             "ROLE" => super::replication_cmds::role(repl_info).await,
             "PSYNC" => super::replication_cmds::psync(repl_info, repl_backlog, args).await,
             "REPLCONF" => super::replication_cmds::replconf(propagator, args).await,
@@ -261,6 +301,7 @@ impl CommandDispatcher {
             "MOVE" => super::key_mgmt::move_key(db, *db_index, args).await,
             "DUMP" => super::key_mgmt::dump(db, *db_index, args).await,
             "RESTORE" => super::key_mgmt::restore(db, *db_index, args).await,
+            "MIGRATE" => super::key_mgmt::migrate(db, *db_index, args).await,
             "SCAN" => super::key_mgmt::scan(db, *db_index, args).await,
             "TOUCH" => super::key_mgmt::touch(db, *db_index, args).await,
             "UNLINK" => super::key_mgmt::unlink(db, *db_index, args).await,
@@ -2,6 +2,7 @@
 
 use crate::protocol::RespValue;
 use crate::storage::db::{current_timestamp_ms, Database};
+use crate::storage::notify::EventClass;
 use std::sync::Arc;
 
 /// EXPIRE key seconds
@@ -30,6 +31,7 @@ pub async fn expire(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
 
     let expire_at_ms = current_timestamp_ms() + (seconds as u64 * 1000);
     if db_instance.set_expiry(key, expire_at_ms) {
+        db_instance.notify(EventClass::GENERIC, "expire", key);
         RespValue::Integer(1)
     } else {
         RespValue::Integer(0)
@@ -64,6 +66,7 @@ pub async fn expireat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
 
     let expire_at_ms = (timestamp as u64) * 1000;
     if db_instance.set_expiry(key, expire_at_ms) {
+        db_instance.notify(EventClass::GENERIC, "expire", key);
         RespValue::Integer(1)
     } else {
         RespValue::Integer(0)
@@ -98,6 +101,7 @@ pub async fn pexpire(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
 
     let expire_at_ms = current_timestamp_ms() + milliseconds as u64;
     if db_instance.set_expiry(key, expire_at_ms) {
+        db_instance.notify(EventClass::GENERIC, "expire", key);
         RespValue::Integer(1)
     } else {
         RespValue::Integer(0)
@@ -131,6 +135,7 @@ pub async fn pexpireat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
     };
 
     if db_instance.set_expiry(key, timestamp_ms) {
+        db_instance.notify(EventClass::GENERIC, "expire", key);
         RespValue::Integer(1)
     } else {
         RespValue::Integer(0)
@@ -200,6 +205,7 @@ pub async fn persist(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     };
 
     if db_instance.persist(key) {
+        db_instance.notify(EventClass::GENERIC, "persist", key);
         RespValue::Integer(1)
     } else {
         RespValue::Integer(0)
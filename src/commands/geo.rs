@@ -3,7 +3,8 @@
 
 use crate::protocol::RespValue;
 use crate::storage::db::Database;
-use crate::storage::types::RedisValue;
+use crate::storage::notify::EventClass;
+use crate::storage::types::{RedisValue, ZSet};
 use bytes::Bytes;
 use std::sync::Arc;
 use std::f64::consts::PI;
@@ -151,7 +152,7 @@ pub async fn geoadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => crate::storage::types::ZSet::new(),
+        None => db_instance.new_zset(),
     };
 
     let mut added = 0;
@@ -193,12 +194,7 @@ pub async fn geoadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         let score = geohash as f64;
 
         // Add to ZSet
-        let existed = zset.members.contains_key(&member);
-        zset.scores.insert(
-            (ordered_float::OrderedFloat(score), member.clone()),
-            (),
-        );
-        zset.members.insert(member, score);
+        let existed = zset.insert(member, score).is_some();
 
         if !existed {
             added += 1;
@@ -208,7 +204,8 @@ pub async fn geoadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 
     // Store ZSet
-    db_instance.set(key, RedisValue::ZSet(zset));
+    db_instance.set(key.clone(), RedisValue::ZSet(zset));
+    db_instance.notify(EventClass::ZSET, "zadd", &key);
 
     RespValue::Integer(added)
 }
@@ -230,7 +227,7 @@ pub async fn geopos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
+    let zset = match db_instance.lookup_read(key) {
         Some(RedisValue::ZSet(z)) => z,
         Some(_) => {
             return RespValue::Error(
@@ -251,7 +248,7 @@ pub async fn geopos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     for member_bytes in &args[1..] {
         let member = Bytes::from(member_bytes.clone());
 
-        if let Some(&score) = zset.members.get(&member) {
+        if let Some(score) = zset.score(&member) {
             let geohash = score as u64;
             let (longitude, latitude) = geohash_decode(geohash);
 
@@ -290,7 +287,7 @@ pub async fn geodist(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
+    let zset = match db_instance.lookup_read(key) {
         Some(RedisValue::ZSet(z)) => z,
         Some(_) => {
             return RespValue::Error(
@@ -303,13 +300,13 @@ pub async fn geodist(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     let member1 = Bytes::from(args[1].clone());
     let member2 = Bytes::from(args[2].clone());
 
-    let score1 = match zset.members.get(&member1) {
-        Some(&s) => s,
+    let score1 = match zset.score(&member1) {
+        Some(s) => s,
         None => return RespValue::Null,
     };
 
-    let score2 = match zset.members.get(&member2) {
-        Some(&s) => s,
+    let score2 = match zset.score(&member2) {
+        Some(s) => s,
         None => return RespValue::Null,
     };
 
@@ -339,7 +336,7 @@ pub async fn geohash(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
+    let zset = match db_instance.lookup_read(key) {
         Some(RedisValue::ZSet(z)) => z,
         Some(_) => {
             return RespValue::Error(
@@ -359,7 +356,7 @@ pub async fn geohash(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     for member_bytes in &args[1..] {
         let member = Bytes::from(member_bytes.clone());
 
-        if let Some(&score) = zset.members.get(&member) {
+        if let Some(score) = zset.score(&member) {
             let geohash = score as u64;
             let hash_str = geohash_to_string(geohash);
             result.push(RespValue::BulkString(Some(hash_str.into_bytes())));
@@ -371,6 +368,580 @@ pub async fn geohash(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     RespValue::Array(Some(result))
 }
 
+/// Where a search is centered
+enum GeoOrigin {
+    Member(Bytes),
+    LonLat(f64, f64),
+}
+
+/// The area a search covers, in meters
+#[derive(Clone, Copy)]
+enum GeoShape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GeoSort {
+    Unsorted,
+    Asc,
+    Desc,
+}
+
+/// Which command's syntax the options follow
+#[derive(Clone, Copy, PartialEq)]
+enum GeoCommand {
+    Search,
+    SearchStore,
+    Radius,
+    RadiusRo,
+}
+
+/// A parsed GEOSEARCH / GEORADIUS request
+struct GeoQuery {
+    origin: Option<GeoOrigin>,
+    shape: Option<GeoShape>,
+    unit: String,
+    sort: GeoSort,
+    count: Option<usize>,
+    any: bool,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store: Option<String>,
+    store_dist: bool,
+    /// GEOSEARCHSTORE rather than GEORADIUS STORE, for the stored event
+    search: bool,
+}
+
+/// A member found by a search
+struct GeoMatch {
+    member: Bytes,
+    hash: u64,
+    longitude: f64,
+    latitude: f64,
+    /// Distance from the search center, in meters
+    distance: f64,
+}
+
+fn syntax_error() -> RespValue {
+    RespValue::Error("ERR syntax error".to_string())
+}
+
+fn parse_f64(arg: &[u8]) -> Option<f64> {
+    std::str::from_utf8(arg).ok()?.parse::<f64>().ok().filter(|v| !v.is_nan())
+}
+
+/// Parse a distance unit, returning its lowercase name
+fn parse_unit(arg: &[u8]) -> Result<String, RespValue> {
+    let unit = String::from_utf8_lossy(arg).to_lowercase();
+    match unit.as_str() {
+        "m" | "km" | "mi" | "ft" => Ok(unit),
+        _ => Err(RespValue::Error(
+            "ERR unsupported unit provided. please use M, KM, FT, MI".to_string(),
+        )),
+    }
+}
+
+/// Parse a distance given in `unit_arg` units into meters
+fn parse_distance(value: &[u8], unit_arg: &[u8]) -> Result<(f64, String), RespValue> {
+    let distance = match parse_f64(value) {
+        Some(d) if d >= 0.0 => d,
+        Some(_) => return Err(RespValue::Error("ERR radius cannot be negative".to_string())),
+        None => return Err(RespValue::Error("ERR need numeric radius".to_string())),
+    };
+    let unit = parse_unit(unit_arg)?;
+    Ok((distance / convert_distance(1.0, &unit), unit))
+}
+
+fn parse_lonlat(lon: &[u8], lat: &[u8]) -> Result<(f64, f64), RespValue> {
+    let (longitude, latitude) = match (parse_f64(lon), parse_f64(lat)) {
+        (Some(lon), Some(lat)) => (lon, lat),
+        _ => return Err(RespValue::Error("ERR value is not a valid float".to_string())),
+    };
+    if !(GEOHASH_LONG_MIN..=GEOHASH_LONG_MAX).contains(&longitude)
+        || !(GEOHASH_LAT_MIN..=GEOHASH_LAT_MAX).contains(&latitude)
+    {
+        return Err(RespValue::Error(format!(
+            "ERR invalid longitude,latitude pair {:.6},{:.6}",
+            longitude, latitude
+        )));
+    }
+    Ok((longitude, latitude))
+}
+
+impl GeoQuery {
+    fn new() -> Self {
+        Self {
+            origin: None,
+            shape: None,
+            unit: "m".to_string(),
+            sort: GeoSort::Unsorted,
+            count: None,
+            any: false,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+            store: None,
+            store_dist: false,
+            search: false,
+        }
+    }
+
+    /// Parse the options following the key (and, for GEORADIUS, the
+    /// positional center and radius)
+    fn parse_options(&mut self, args: &[Vec<u8>], command: GeoCommand) -> Result<(), RespValue> {
+        let is_search = matches!(command, GeoCommand::Search | GeoCommand::SearchStore);
+        self.search = is_search;
+        let mut i = 0;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            let remaining = args.len() - i - 1;
+            match option.as_str() {
+                "WITHCOORD" => self.with_coord = true,
+                "WITHDIST" => self.with_dist = true,
+                "WITHHASH" => self.with_hash = true,
+                "ASC" => self.sort = GeoSort::Asc,
+                "DESC" => self.sort = GeoSort::Desc,
+                "COUNT" if remaining >= 1 => {
+                    let count = std::str::from_utf8(&args[i + 1])
+                        .ok()
+                        .and_then(|s| s.parse::<i64>().ok())
+                        .ok_or_else(|| {
+                            RespValue::Error("ERR value is not an integer or out of range".to_string())
+                        })?;
+                    if count <= 0 {
+                        return Err(RespValue::Error("ERR COUNT must be > 0".to_string()));
+                    }
+                    self.count = Some(count as usize);
+                    i += 1;
+                    if args.get(i + 1).is_some_and(|a| a.eq_ignore_ascii_case(b"ANY")) {
+                        self.any = true;
+                        i += 1;
+                    }
+                }
+                "FROMMEMBER" if is_search && remaining >= 1 => {
+                    if self.origin.is_some() {
+                        return Err(Self::origin_error());
+                    }
+                    self.origin = Some(GeoOrigin::Member(Bytes::from(args[i + 1].clone())));
+                    i += 1;
+                }
+                "FROMLONLAT" if is_search && remaining >= 2 => {
+                    if self.origin.is_some() {
+                        return Err(Self::origin_error());
+                    }
+                    let (lon, lat) = parse_lonlat(&args[i + 1], &args[i + 2])?;
+                    self.origin = Some(GeoOrigin::LonLat(lon, lat));
+                    i += 2;
+                }
+                "BYRADIUS" if is_search && remaining >= 2 => {
+                    if self.shape.is_some() {
+                        return Err(Self::shape_error());
+                    }
+                    let (radius, unit) = parse_distance(&args[i + 1], &args[i + 2])?;
+                    self.shape = Some(GeoShape::Radius(radius));
+                    self.unit = unit;
+                    i += 2;
+                }
+                "BYBOX" if is_search && remaining >= 3 => {
+                    if self.shape.is_some() {
+                        return Err(Self::shape_error());
+                    }
+                    let (width, unit) = parse_distance(&args[i + 1], &args[i + 3])?;
+                    let (height, _) = parse_distance(&args[i + 2], &args[i + 3])?;
+                    self.shape = Some(GeoShape::Box { width, height });
+                    self.unit = unit;
+                    i += 3;
+                }
+                "STOREDIST" if command == GeoCommand::SearchStore => self.store_dist = true,
+                "STORE" | "STOREDIST" if command == GeoCommand::Radius && remaining >= 1 => {
+                    self.store = Some(String::from_utf8_lossy(&args[i + 1]).to_string());
+                    self.store_dist = option == "STOREDIST";
+                    i += 1;
+                }
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
+        }
+
+        if is_search {
+            if self.origin.is_none() {
+                return Err(Self::origin_error());
+            }
+            if self.shape.is_none() {
+                return Err(Self::shape_error());
+            }
+        }
+        let with_any = self.with_coord || self.with_dist || self.with_hash;
+        if command == GeoCommand::SearchStore && with_any {
+            return Err(syntax_error());
+        }
+        if self.store.is_some() && with_any {
+            return Err(RespValue::Error(
+                "ERR STORE option in GEORADIUS is not compatible with WITHDIST, WITHHASH and WITHCOORD options"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn origin_error() -> RespValue {
+        RespValue::Error(
+            "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH".to_string(),
+        )
+    }
+
+    fn shape_error() -> RespValue {
+        RespValue::Error("ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH".to_string())
+    }
+}
+
+/// Split a cell hash into its longitude (odd bits) and latitude (even bits)
+/// cell indexes
+fn deinterleave(hash: u64, step: usize) -> (u64, u64) {
+    let mut lon = 0;
+    let mut lat = 0;
+    for bit in 0..step {
+        lon |= ((hash >> (bit * 2 + 1)) & 1) << bit;
+        lat |= ((hash >> (bit * 2)) & 1) << bit;
+    }
+    (lon, lat)
+}
+
+fn interleave(lon: u64, lat: u64, step: usize) -> u64 {
+    let mut hash = 0;
+    for bit in 0..step {
+        hash |= ((lon >> bit) & 1) << (bit * 2 + 1);
+        hash |= ((lat >> bit) & 1) << (bit * 2);
+    }
+    hash
+}
+
+/// How far, in degrees of longitude and latitude, a point inside `shape`
+/// can be from the center at `latitude`. Longitude spans of 360 or more
+/// mean the area reaches a pole.
+fn shape_extent(shape: GeoShape, latitude: f64) -> (f64, f64) {
+    let (half_width, half_height) = match shape {
+        GeoShape::Radius(r) => (r, r),
+        GeoShape::Box { width, height } => (width / 2.0, height / 2.0),
+    };
+    let lat_angle = half_height / EARTH_RADIUS_M;
+    let max_lat = (latitude.abs().to_radians() + lat_angle).min(PI / 2.0);
+
+    // A circle's widest longitude span is at its center latitude; a box
+    // measures its width along each point's own parallel
+    let lon_angle = half_width / EARTH_RADIUS_M;
+    let (ratio, factor) = match shape {
+        GeoShape::Radius(_) => (lon_angle.min(PI / 2.0).sin() / latitude.to_radians().cos(), 1.0),
+        GeoShape::Box { .. } => ((lon_angle / 2.0).min(PI / 2.0).sin() / max_lat.cos(), 2.0),
+    };
+    let lon_span = if lon_angle >= PI || !(0.0..1.0).contains(&ratio) {
+        360.0
+    } else {
+        (factor * ratio.asin()).to_degrees()
+    };
+    (lon_span, lat_angle.to_degrees())
+}
+
+/// Score ranges of the geohash cells that can hold points inside `shape`:
+/// the cell containing the center plus its eight neighbors, at the finest
+/// precision where a cell is at least as large as the searched area
+fn search_ranges(longitude: f64, latitude: f64, shape: GeoShape) -> Vec<(u64, u64)> {
+    let (lon_span, lat_span) = shape_extent(shape, latitude);
+    let mut step = GEOHASH_STEP;
+    while step > 0
+        && (360.0 / (1u64 << step) as f64 <= lon_span || 180.0 / (1u64 << step) as f64 <= lat_span)
+    {
+        step -= 1;
+    }
+
+    let shift = (GEOHASH_STEP - step) * 2;
+    let cells = 1i64 << step;
+    let (lon_idx, lat_idx) = deinterleave(geohash_encode(longitude, latitude) >> shift, step);
+
+    let mut ranges = Vec::with_capacity(9);
+    for dlat in -1..=1 {
+        let lat = lat_idx as i64 + dlat;
+        if !(0..cells).contains(&lat) {
+            continue;
+        }
+        for dlon in -1..=1 {
+            // Longitude wraps around the antimeridian
+            let lon = (lon_idx as i64 + dlon).rem_euclid(cells);
+            let hash = interleave(lon as u64, lat as u64, step);
+            ranges.push((hash << shift, (hash + 1) << shift));
+        }
+    }
+    ranges.sort_unstable();
+    ranges.dedup();
+    ranges
+}
+
+/// Distance from the center if the point lies inside `shape`
+fn distance_if_inside(shape: GeoShape, lon: f64, lat: f64, point_lon: f64, point_lat: f64) -> Option<f64> {
+    match shape {
+        GeoShape::Radius(radius) => {
+            let distance = haversine_distance(lon, lat, point_lon, point_lat);
+            (distance <= radius).then_some(distance)
+        }
+        GeoShape::Box { width, height } => {
+            if haversine_distance(lon, lat, lon, point_lat) > height / 2.0
+                || haversine_distance(lon, point_lat, point_lon, point_lat) > width / 2.0
+            {
+                return None;
+            }
+            Some(haversine_distance(lon, lat, point_lon, point_lat))
+        }
+    }
+}
+
+/// Members of `zset` inside the query's area, scanning only the geohash
+/// cells around the center
+fn search_zset(zset: &ZSet, longitude: f64, latitude: f64, query: &GeoQuery) -> Vec<GeoMatch> {
+    let shape = query.shape.expect("shape is checked while parsing");
+    let limit = if query.any { query.count } else { None };
+    let mut matches = Vec::new();
+
+    'cells: for (start, end) in search_ranges(longitude, latitude, shape) {
+        let from = zset.count_below(start as f64, false);
+        let to = zset.count_below(end as f64, false);
+        for (member, score) in zset.range_by_rank(from, to) {
+            let hash = score as u64;
+            let (point_lon, point_lat) = geohash_decode(hash);
+            if let Some(distance) = distance_if_inside(shape, longitude, latitude, point_lon, point_lat) {
+                matches.push(GeoMatch {
+                    member: Bytes::copy_from_slice(member),
+                    hash,
+                    longitude: point_lon,
+                    latitude: point_lat,
+                    distance,
+                });
+                if limit == Some(matches.len()) {
+                    break 'cells;
+                }
+            }
+        }
+    }
+
+    // COUNT without ANY returns the closest members
+    let sort = match query.sort {
+        GeoSort::Unsorted if query.count.is_some() && !query.any => GeoSort::Asc,
+        sort => sort,
+    };
+    match sort {
+        GeoSort::Asc => matches.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        GeoSort::Desc => matches.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        GeoSort::Unsorted => {}
+    }
+    if let Some(count) = query.count {
+        matches.truncate(count);
+    }
+    matches
+}
+
+/// Run a parsed query against the zset at `key`, replying with the matches
+/// or storing them when the query has a destination
+async fn run_query(db: &Arc<Database>, db_index: usize, key: &str, query: GeoQuery) -> RespValue {
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let zset = match db_instance.lookup_read(key) {
+        Some(RedisValue::ZSet(z)) => Some(z),
+        Some(_) => {
+            return RespValue::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            )
+        }
+        None => None,
+    };
+
+    let matches = match &zset {
+        Some(zset) => {
+            let (longitude, latitude) = match &query.origin {
+                Some(GeoOrigin::LonLat(lon, lat)) => (*lon, *lat),
+                Some(GeoOrigin::Member(member)) => match zset.score(member) {
+                    Some(score) => geohash_decode(score as u64),
+                    None => {
+                        return RespValue::Error(
+                            "ERR could not decode requested zset member".to_string(),
+                        )
+                    }
+                },
+                None => unreachable!("origin is checked while parsing"),
+            };
+            search_zset(zset, longitude, latitude, &query)
+        }
+        None => Vec::new(),
+    };
+
+    if let Some(dest) = query.store {
+        let mut result = db_instance.new_zset();
+        for m in matches {
+            let score = if query.store_dist {
+                convert_distance(m.distance, &query.unit)
+            } else {
+                m.hash as f64
+            };
+            result.insert(m.member, score);
+        }
+        let count = result.len() as i64;
+        if !result.is_empty() {
+            db_instance.set(dest.clone(), RedisValue::ZSet(result));
+            let event = if query.search { "geosearchstore" } else { "georadiusstore" };
+            db_instance.notify(EventClass::ZSET, event, &dest);
+        } else if db_instance.delete(&dest) {
+            db_instance.notify(EventClass::GENERIC, "del", &dest);
+        }
+        return RespValue::Integer(count);
+    }
+
+    let plain = !(query.with_coord || query.with_dist || query.with_hash);
+    let items = matches
+        .into_iter()
+        .map(|m| {
+            let member = RespValue::BulkString(Some(m.member.to_vec()));
+            if plain {
+                return member;
+            }
+            let mut item = vec![member];
+            if query.with_dist {
+                let distance = convert_distance(m.distance, &query.unit);
+                item.push(RespValue::BulkString(Some(format!("{:.4}", distance).into_bytes())));
+            }
+            if query.with_hash {
+                item.push(RespValue::Integer(m.hash as i64));
+            }
+            if query.with_coord {
+                item.push(RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(m.longitude.to_string().into_bytes())),
+                    RespValue::BulkString(Some(m.latitude.to_string().into_bytes())),
+                ])));
+            }
+            RespValue::Array(Some(item))
+        })
+        .collect();
+    RespValue::Array(Some(items))
+}
+
+/// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+///   BYRADIUS radius unit | BYBOX width height unit
+///   [ASC|DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+/// Find members inside a circle or box
+pub async fn geosearch(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 5 {
+        return RespValue::Error("ERR wrong number of arguments for 'geosearch' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let mut query = GeoQuery::new();
+    if let Err(e) = query.parse_options(&args[1..], GeoCommand::Search) {
+        return e;
+    }
+    run_query(db, db_index, &key, query).await
+}
+
+/// GEOSEARCHSTORE destination source <GEOSEARCH options> [STOREDIST]
+/// Store the members GEOSEARCH would return, scored by geohash or distance
+pub async fn geosearchstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 6 {
+        return RespValue::Error(
+            "ERR wrong number of arguments for 'geosearchstore' command".to_string(),
+        );
+    }
+
+    let key = String::from_utf8_lossy(&args[1]).to_string();
+    let mut query = GeoQuery::new();
+    if let Err(e) = query.parse_options(&args[2..], GeoCommand::SearchStore) {
+        return e;
+    }
+    query.store = Some(String::from_utf8_lossy(&args[0]).to_string());
+    run_query(db, db_index, &key, query).await
+}
+
+/// Shared by GEORADIUS and GEORADIUS_RO: key longitude latitude radius unit [options]
+async fn georadius_generic(
+    db: &Arc<Database>,
+    db_index: usize,
+    args: Vec<Vec<u8>>,
+    command: GeoCommand,
+    name: &str,
+) -> RespValue {
+    if args.len() < 5 {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", name));
+    }
+
+    let mut query = GeoQuery::new();
+    let parsed = parse_lonlat(&args[1], &args[2]).and_then(|(lon, lat)| {
+        query.origin = Some(GeoOrigin::LonLat(lon, lat));
+        let (radius, unit) = parse_distance(&args[3], &args[4])?;
+        query.shape = Some(GeoShape::Radius(radius));
+        query.unit = unit;
+        query.parse_options(&args[5..], command)
+    });
+    if let Err(e) = parsed {
+        return e;
+    }
+    run_query(db, db_index, &String::from_utf8_lossy(&args[0]), query).await
+}
+
+/// Shared by GEORADIUSBYMEMBER and GEORADIUSBYMEMBER_RO: key member radius unit [options]
+async fn georadiusbymember_generic(
+    db: &Arc<Database>,
+    db_index: usize,
+    args: Vec<Vec<u8>>,
+    command: GeoCommand,
+    name: &str,
+) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", name));
+    }
+
+    let mut query = GeoQuery::new();
+    query.origin = Some(GeoOrigin::Member(Bytes::from(args[1].clone())));
+    let parsed = parse_distance(&args[2], &args[3]).and_then(|(radius, unit)| {
+        query.shape = Some(GeoShape::Radius(radius));
+        query.unit = unit;
+        query.parse_options(&args[4..], command)
+    });
+    if let Err(e) = parsed {
+        return e;
+    }
+    run_query(db, db_index, &String::from_utf8_lossy(&args[0]), query).await
+}
+
+/// GEORADIUS key longitude latitude radius unit [WITHCOORD] [WITHDIST] [WITHHASH]
+///   [COUNT count [ANY]] [ASC|DESC] [STORE key] [STOREDIST key]
+pub async fn georadius(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    georadius_generic(db, db_index, args, GeoCommand::Radius, "georadius").await
+}
+
+/// GEORADIUS_RO: GEORADIUS without STORE / STOREDIST
+pub async fn georadius_ro(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    georadius_generic(db, db_index, args, GeoCommand::RadiusRo, "georadius_ro").await
+}
+
+/// GEORADIUSBYMEMBER key member radius unit [options as GEORADIUS]
+pub async fn georadiusbymember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    georadiusbymember_generic(db, db_index, args, GeoCommand::Radius, "georadiusbymember").await
+}
+
+/// GEORADIUSBYMEMBER_RO: GEORADIUSBYMEMBER without STORE / STOREDIST
+pub async fn georadiusbymember_ro(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    georadiusbymember_generic(db, db_index, args, GeoCommand::RadiusRo, "georadiusbymember_ro").await
+}
+
+/// Whether a GEORADIUS / GEORADIUSBYMEMBER call (command name included)
+/// writes, i.e. has a STORE or STOREDIST option. Options never start
+/// before the sixth argument.
+pub fn has_store_option(args: &[Vec<u8>]) -> bool {
+    args.iter()
+        .skip(5)
+        .any(|a| a.eq_ignore_ascii_case(b"STORE") || a.eq_ignore_ascii_case(b"STOREDIST"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -473,4 +1044,209 @@ mod tests {
             panic!("Expected bulk string result");
         }
     }
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    async fn sicily() -> Arc<Database> {
+        let db = Arc::new(Database::new(16));
+        geoadd(
+            &db,
+            0,
+            args(&[
+                "Sicily", "13.361389", "38.115556", "Palermo", "15.087269", "37.502669", "Catania",
+                "12.758489", "38.788135", "edge1", "17.241510", "38.788135", "edge2",
+            ]),
+        )
+        .await;
+        db
+    }
+
+    fn names(reply: RespValue) -> Vec<String> {
+        match reply {
+            RespValue::Array(Some(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    RespValue::BulkString(Some(b)) => String::from_utf8(b).unwrap(),
+                    RespValue::Array(Some(parts)) => match &parts[0] {
+                        RespValue::BulkString(Some(b)) => String::from_utf8(b.clone()).unwrap(),
+                        other => panic!("unexpected member {:?}", other),
+                    },
+                    other => panic!("unexpected item {:?}", other),
+                })
+                .collect(),
+            other => panic!("expected array, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_geosearch_radius_and_box() {
+        let db = sicily().await;
+
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "200", "km", "ASC", "WITHDIST"]),
+        )
+        .await;
+        let RespValue::Array(Some(items)) = &reply else { panic!("expected array") };
+        assert_eq!(
+            items[0],
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"Catania".to_vec())),
+                RespValue::BulkString(Some(b"56.4413".to_vec())),
+            ]))
+        );
+        assert_eq!(names(reply), vec!["Catania", "Palermo"]);
+
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMLONLAT", "15", "37", "BYBOX", "400", "400", "km", "DESC"]),
+        )
+        .await;
+        assert_eq!(names(reply), vec!["edge1", "edge2", "Palermo", "Catania"]);
+
+        // COUNT without ANY returns the closest
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMMEMBER", "Palermo", "BYRADIUS", "500", "km", "COUNT", "2"]),
+        )
+        .await;
+        assert_eq!(names(reply), vec!["Palermo", "edge1"]);
+
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMMEMBER", "Palermo", "BYRADIUS", "500", "km", "COUNT", "3", "ANY"]),
+        )
+        .await;
+        assert_eq!(names(reply).len(), 3);
+
+        let reply = georadiusbymember_ro(
+            &db,
+            0,
+            args(&["Sicily", "Catania", "100", "km", "WITHHASH", "WITHCOORD"]),
+        )
+        .await;
+        let RespValue::Array(Some(items)) = reply else { panic!("expected array") };
+        let RespValue::Array(Some(parts)) = &items[0] else { panic!("expected item") };
+        assert_eq!(parts[1], RespValue::Integer(geohash_encode(15.087269, 37.502669) as i64));
+        assert!(matches!(&parts[2], RespValue::Array(Some(c)) if c.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn test_geosearch_errors_and_missing_key() {
+        let db = sicily().await;
+
+        let reply = geosearch(&db, 0, args(&["Sicily", "FROMLONLAT", "15", "37", "COUNT", "1"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("BYRADIUS and BYBOX")));
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMMEMBER", "Rome", "BYRADIUS", "1", "km"]),
+        )
+        .await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("could not decode")));
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "1", "parsec"]),
+        )
+        .await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("unsupported unit")));
+        let reply = georadius(
+            &db,
+            0,
+            args(&["Sicily", "15", "37", "200", "km", "WITHDIST", "STORE", "out"]),
+        )
+        .await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("not compatible")));
+        let reply = georadius_ro(&db, 0, args(&["Sicily", "15", "37", "200", "km", "STORE", "out"])).await;
+        assert_eq!(reply, RespValue::Error("ERR syntax error".to_string()));
+
+        let reply = geosearch(
+            &db,
+            0,
+            args(&["nokey", "FROMMEMBER", "Rome", "BYRADIUS", "1", "km"]),
+        )
+        .await;
+        assert_eq!(reply, RespValue::Array(Some(vec![])));
+    }
+
+    #[tokio::test]
+    async fn test_geosearchstore_and_georadius_store() {
+        let db = sicily().await;
+
+        let reply = geosearchstore(
+            &db,
+            0,
+            args(&["dists", "Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "200", "km", "STOREDIST"]),
+        )
+        .await;
+        assert_eq!(reply, RespValue::Integer(2));
+        let Some(RedisValue::ZSet(dists)) = db.get_db(0).unwrap().get("dists") else {
+            panic!("expected a zset")
+        };
+        let catania = dists.score(b"Catania").unwrap();
+        assert!((catania - 56.4413).abs() < 0.001);
+
+        georadius(&db, 0, args(&["Sicily", "15", "37", "200", "km", "STORE", "hashes"])).await;
+        let Some(RedisValue::ZSet(hashes)) = db.get_db(0).unwrap().get("hashes") else {
+            panic!("expected a zset")
+        };
+        let sicily = match db.get_db(0).unwrap().get("Sicily") {
+            Some(RedisValue::ZSet(z)) => z,
+            _ => panic!("expected a zset"),
+        };
+        assert_eq!(hashes.score(b"Palermo"), sicily.score(b"Palermo"));
+
+        // An empty result deletes the destination
+        let reply = georadius(&db, 0, args(&["Sicily", "0", "0", "1", "km", "STORE", "hashes"])).await;
+        assert_eq!(reply, RespValue::Integer(0));
+        assert!(db.get_db(0).unwrap().get("hashes").is_none());
+
+        assert!(has_store_option(&args(&["GEORADIUS", "k", "0", "0", "1", "km", "STOREDIST", "d"])));
+        assert!(!has_store_option(&args(&["GEORADIUSBYMEMBER", "k", "STORE", "1", "km"])));
+    }
+
+    #[test]
+    fn test_search_ranges_cover_the_shape() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+
+        // Compare the neighbor-cell scan with a brute-force scan, including
+        // areas crossing the antimeridian and reaching the poles
+        for _ in 0..200 {
+            let lon = rng.gen_range(-180.0..180.0);
+            let lat = rng.gen_range(-89.0..89.0);
+            let shape = if rng.gen_bool(0.5) {
+                GeoShape::Radius(rng.gen_range(1.0..2_000_000.0))
+            } else {
+                GeoShape::Box {
+                    width: rng.gen_range(1.0..4_000_000.0),
+                    height: rng.gen_range(1.0..4_000_000.0),
+                }
+            };
+            let ranges = search_ranges(lon, lat, shape);
+            for _ in 0..200 {
+                let point_lon = (lon + rng.gen_range(-40.0..40.0) + 540.0) % 360.0 - 180.0;
+                let point_lat: f64 = (lat + rng.gen_range(-20.0..20.0)).clamp(-85.0, 85.0);
+                let hash = geohash_encode(point_lon, point_lat);
+                let (dlon, dlat) = geohash_decode(hash);
+                if distance_if_inside(shape, lon, lat, dlon, dlat).is_some() {
+                    assert!(
+                        ranges.iter().any(|&(start, end)| (start..end).contains(&hash)),
+                        "({}, {}) missed searching around ({}, {})",
+                        dlon,
+                        dlat,
+                        lon,
+                        lat
+                    );
+                }
+            }
+        }
+    }
 }
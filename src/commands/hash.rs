@@ -1,10 +1,11 @@
 // Hash command handlers
 
+use super::key_mgmt::ScanOptions;
 use crate::protocol::RespValue;
-use crate::storage::db::Database;
-use crate::storage::types::RedisValue;
+use crate::storage::db::{current_timestamp_ms, Database, DbInstance};
+use crate::storage::notify::EventClass;
+use crate::storage::types::{HashValue, RedisValue};
 use bytes::Bytes;
-use std::collections::HashMap;
 use std::sync::Arc;
 
 /// HSET key field value [field value ...]
@@ -30,7 +31,7 @@ pub async fn hset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashMap::new(),
+        None => db_instance.new_hash(),
     };
 
     let mut added = 0;
@@ -38,12 +39,17 @@ pub async fn hset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         let field = Bytes::from(chunk[0].clone());
         let value = Bytes::from(chunk[1].clone());
 
-        if hash.insert(field, value).is_none() {
+        if hash.insert(field, value) {
             added += 1;
         }
     }
 
-    db_instance.set(key, RedisValue::Hash(hash));
+    db_instance.set(key.clone(), RedisValue::Hash(hash));
+    // Overwritten fields lose their TTL
+    for chunk in args[1..].chunks(2) {
+        db_instance.persist_hash_field(&key, &chunk[0]);
+    }
+    db_instance.notify(EventClass::HASH, "hset", &key);
     RespValue::Integer(added)
 }
 
@@ -65,7 +71,7 @@ pub async fn hget(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => match hash.get(&field) {
             Some(value) => RespValue::BulkString(Some(value.to_vec())),
             None => RespValue::BulkString(None),
@@ -106,15 +112,13 @@ pub async fn hdel(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
     let mut deleted = 0;
     for field_bytes in &args[1..] {
         let field = Bytes::from(field_bytes.clone());
-        if hash.remove(&field).is_some() {
+        if hash.remove(&field) {
             deleted += 1;
         }
     }
 
-    if hash.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::Hash(hash));
+    if deleted > 0 {
+        store_hash(&db_instance, &key, hash, "hdel");
     }
 
     RespValue::Integer(deleted)
@@ -138,7 +142,7 @@ pub async fn hexists(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             RespValue::Integer(if hash.contains_key(&field) { 1 } else { 0 })
         }
@@ -165,7 +169,7 @@ pub async fn hgetall(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             let mut result = Vec::new();
             for (field, value) in hash.iter() {
@@ -197,11 +201,11 @@ pub async fn hkeys(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             let result: Vec<RespValue> = hash
-                .keys()
-                .map(|k| RespValue::BulkString(Some(k.to_vec())))
+                .iter()
+                .map(|(k, _)| RespValue::BulkString(Some(k.to_vec())))
                 .collect();
             RespValue::Array(Some(result))
         }
@@ -228,11 +232,11 @@ pub async fn hvals(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             let result: Vec<RespValue> = hash
-                .values()
-                .map(|v| RespValue::BulkString(Some(v.to_vec())))
+                .iter()
+                .map(|(_, v)| RespValue::BulkString(Some(v.to_vec())))
                 .collect();
             RespValue::Array(Some(result))
         }
@@ -259,7 +263,7 @@ pub async fn hlen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => RespValue::Integer(hash.len() as i64),
         Some(_) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -284,7 +288,7 @@ pub async fn hmget(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             let mut result = Vec::new();
             for field_bytes in &args[1..] {
@@ -329,7 +333,7 @@ pub async fn hmset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashMap::new(),
+        None => db_instance.new_hash(),
     };
 
     for chunk in args[1..].chunks(2) {
@@ -338,7 +342,12 @@ pub async fn hmset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         hash.insert(field, value);
     }
 
-    db_instance.set(key, RedisValue::Hash(hash));
+    db_instance.set(key.clone(), RedisValue::Hash(hash));
+    // Overwritten fields lose their TTL
+    for chunk in args[1..].chunks(2) {
+        db_instance.persist_hash_field(&key, &chunk[0]);
+    }
+    db_instance.notify(EventClass::HASH, "hset", &key);
     RespValue::SimpleString("OK".to_string())
 }
 
@@ -368,7 +377,7 @@ pub async fn hsetnx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashMap::new(),
+        None => db_instance.new_hash(),
     };
 
     if hash.contains_key(&field) {
@@ -376,7 +385,8 @@ pub async fn hsetnx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 
     hash.insert(field, value);
-    db_instance.set(key, RedisValue::Hash(hash));
+    db_instance.set(key.clone(), RedisValue::Hash(hash));
+    db_instance.notify(EventClass::HASH, "hset", &key);
     RespValue::Integer(1)
 }
 
@@ -415,7 +425,7 @@ pub async fn hincrby(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashMap::new(),
+        None => db_instance.new_hash(),
     };
 
     let current_value = match hash.get(&field) {
@@ -437,7 +447,8 @@ pub async fn hincrby(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     };
 
     hash.insert(field, Bytes::from(new_value.to_string().into_bytes()));
-    db_instance.set(key, RedisValue::Hash(hash));
+    db_instance.set(key.clone(), RedisValue::Hash(hash));
+    db_instance.notify(EventClass::HASH, "hincrby", &key);
     RespValue::Integer(new_value)
 }
 
@@ -476,7 +487,7 @@ pub async fn hincrbyfloat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashMap::new(),
+        None => db_instance.new_hash(),
     };
 
     let current_value = match hash.get(&field) {
@@ -500,7 +511,8 @@ pub async fn hincrbyfloat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>
     };
 
     hash.insert(field, Bytes::from(formatted.clone().into_bytes()));
-    db_instance.set(key, RedisValue::Hash(hash));
+    db_instance.set(key.clone(), RedisValue::Hash(hash));
+    db_instance.notify(EventClass::HASH, "hincrbyfloat", &key);
     RespValue::BulkString(Some(formatted.into_bytes()))
 }
 
@@ -522,7 +534,7 @@ pub async fn hstrlen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => match hash.get(&field) {
             Some(value) => RespValue::Integer(value.len() as i64),
             None => RespValue::Integer(0),
@@ -534,7 +546,7 @@ pub async fn hstrlen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 }
 
-/// HSCAN key cursor [MATCH pattern] [COUNT count]
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
 /// Incrementally iterate hash fields and values
 pub async fn hscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
     if args.len() < 2 {
@@ -546,12 +558,9 @@ pub async fn hscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let cursor = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
+    let options = match ScanOptions::parse(&args[1..], &["NOVALUES"]) {
+        Ok(options) => options,
+        Err(e) => return e,
     };
 
     let db_instance = match db.get_db(db_index) {
@@ -559,61 +568,29 @@ pub async fn hscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Parse optional COUNT parameter
-    let mut count = 10; // Default count
-    let mut i = 2;
-    while i < args.len() {
-        if let Ok(s) = std::str::from_utf8(&args[i]) {
-            if s.to_uppercase() == "COUNT" && i + 1 < args.len() {
-                if let Ok(c_str) = std::str::from_utf8(&args[i + 1]) {
-                    if let Ok(c) = c_str.parse::<usize>() {
-                        count = c;
-                    }
-                }
-                i += 2;
-            } else if s.to_uppercase() == "MATCH" && i + 1 < args.len() {
-                // MATCH pattern support (basic implementation - just skip for now)
-                i += 2;
-            } else {
-                i += 1;
+    // Read in place: copying the hash would make every step O(n)
+    let page = db_instance.lookup_read_with(key, |value| {
+        let RedisValue::Hash(hash) = value else {
+            return None;
+        };
+        let (fields, next_cursor) = hash.scan(options.cursor, options.count);
+
+        let mut results = Vec::new();
+        for (field, value) in fields.into_iter().filter(|(field, _)| options.matches(field)) {
+            results.push(RespValue::BulkString(Some(field.to_vec())));
+            if !options.novalues {
+                results.push(RespValue::BulkString(Some(value.to_vec())));
             }
-        } else {
-            i += 1;
         }
-    }
+        Some(ScanOptions::reply(next_cursor, results))
+    });
 
-    match db_instance.get(key) {
-        Some(RedisValue::Hash(hash)) => {
-            let fields: Vec<_> = hash.iter().collect();
-            let start = cursor;
-            let end = (start + count).min(fields.len());
-            let next_cursor = if end >= fields.len() { 0 } else { end };
-
-            // Build results array with field-value pairs
-            let mut results = Vec::new();
-            for i in start..end {
-                if let Some((field, value)) = fields.get(i) {
-                    results.push(RespValue::BulkString(Some(field.to_vec())));
-                    results.push(RespValue::BulkString(Some(value.to_vec())));
-                }
-            }
-
-            // Return [next_cursor, [field1, value1, field2, value2, ...]]
-            RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(next_cursor.to_string().into_bytes())),
-                RespValue::Array(Some(results)),
-            ]))
-        }
-        Some(_) => RespValue::Error(
+    match page {
+        Some(Some(reply)) => reply,
+        Some(None) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
         ),
-        None => {
-            // Empty hash - return cursor 0 and empty array
-            RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(b"0".to_vec())),
-                RespValue::Array(Some(vec![])),
-            ]))
-        }
+        None => ScanOptions::reply(0, vec![]),
     }
 }
 
@@ -657,7 +634,7 @@ pub async fn hrandfield(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         false
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Hash(hash)) => {
             if hash.is_empty() {
                 return if count.is_some() {
@@ -734,6 +711,606 @@ pub async fn hrandfield(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
     }
 }
 
+/// Largest accepted field expiration time in milliseconds, as in Redis
+const MAX_FIELD_EXPIRE_MS: u64 = (1 << 48) - 1;
+
+/// Condition on a field's current TTL for HEXPIRE and friends
+#[derive(Clone, Copy, PartialEq)]
+enum ExpireCondition {
+    Always,
+    /// Only fields without a TTL
+    Nx,
+    /// Only fields with a TTL
+    Xx,
+    /// Only when the new time is later; no TTL counts as never expiring
+    Gt,
+    /// Only when the new time is earlier
+    Lt,
+}
+
+impl ExpireCondition {
+    fn parse(arg: &[u8]) -> Option<Self> {
+        match String::from_utf8_lossy(arg).to_uppercase().as_str() {
+            "NX" => Some(Self::Nx),
+            "XX" => Some(Self::Xx),
+            "GT" => Some(Self::Gt),
+            "LT" => Some(Self::Lt),
+            _ => None,
+        }
+    }
+
+    fn allows(&self, current: Option<u64>, new: u64) -> bool {
+        match (self, current) {
+            (Self::Always, _) => true,
+            (Self::Nx, current) => current.is_none(),
+            (Self::Xx, current) => current.is_some(),
+            (Self::Gt, Some(current)) => new > current,
+            (Self::Gt, None) => false,
+            (Self::Lt, Some(current)) => new < current,
+            (Self::Lt, None) => true,
+        }
+    }
+}
+
+/// How an expiration argument is given
+#[derive(Clone, Copy)]
+enum ExpireUnit {
+    Seconds,
+    Millis,
+    UnixSeconds,
+    UnixMillis,
+}
+
+impl ExpireUnit {
+    /// The option name (EX, PX, EXAT, PXAT) selecting this unit, if any
+    fn from_option(option: &str) -> Option<Self> {
+        match option {
+            "EX" => Some(Self::Seconds),
+            "PX" => Some(Self::Millis),
+            "EXAT" => Some(Self::UnixSeconds),
+            "PXAT" => Some(Self::UnixMillis),
+            _ => None,
+        }
+    }
+
+    /// Absolute expiration time in milliseconds for `arg` in this unit
+    fn expire_at(&self, arg: &[u8], command: &str) -> Result<u64, RespValue> {
+        let value = std::str::from_utf8(arg)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| RespValue::Error("ERR value is not an integer or out of range".to_string()))?;
+        let invalid = || RespValue::Error(format!("ERR invalid expire time in '{}' command", command));
+        let value = u64::try_from(value).map_err(|_| invalid())?;
+        let at = match self {
+            Self::Seconds => value.checked_mul(1000).and_then(|ms| ms.checked_add(current_timestamp_ms())),
+            Self::Millis => value.checked_add(current_timestamp_ms()),
+            Self::UnixSeconds => value.checked_mul(1000),
+            Self::UnixMillis => Some(value),
+        };
+        at.filter(|&at| at <= MAX_FIELD_EXPIRE_MS).ok_or_else(invalid)
+    }
+}
+
+/// Parse `FIELDS numfields field [field ...]` starting at `args[at]`. Each
+/// field takes `per_field` arguments (2 for field-value pairs), which must
+/// be exactly the rest of `args`.
+fn parse_fields(args: &[Vec<u8>], at: usize, per_field: usize) -> Result<&[Vec<u8>], RespValue> {
+    if !args.get(at).is_some_and(|a| a.eq_ignore_ascii_case(b"FIELDS")) {
+        return Err(RespValue::Error(
+            "ERR Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        ));
+    }
+    let count = args
+        .get(at + 1)
+        .and_then(|a| std::str::from_utf8(a).ok())
+        .and_then(|s| s.parse::<i64>().ok())
+        .filter(|&n| n > 0)
+        .ok_or_else(|| RespValue::Error("ERR Parameter `numFields` should be greater than 0".to_string()))?;
+    let rest = &args[at + 2..];
+    if rest.len() as u64 != count as u64 * per_field as u64 {
+        return Err(RespValue::Error(
+            "ERR The `numfields` parameter must match the number of arguments".to_string(),
+        ));
+    }
+    Ok(rest)
+}
+
+/// Fetch the hash at `key`: None if missing, an error for other types
+fn get_hash(
+    db_instance: &DbInstance,
+    key: &str,
+) -> Result<Option<HashValue>, RespValue> {
+    match db_instance.get(key) {
+        Some(RedisValue::Hash(h)) => Ok(Some(h)),
+        Some(_) => Err(RespValue::Error(
+            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Store a hash back after an `event` that removed fields, deleting the
+/// key once it has none
+fn store_hash(db_instance: &DbInstance, key: &str, hash: HashValue, event: &str) {
+    let emptied = hash.is_empty();
+    if emptied {
+        db_instance.delete(key);
+    } else {
+        db_instance.set(key.to_string(), RedisValue::Hash(hash));
+    }
+    db_instance.notify(EventClass::HASH, event, key);
+    if emptied {
+        db_instance.notify(EventClass::GENERIC, "del", key);
+    }
+}
+
+fn integers(values: Vec<i64>) -> RespValue {
+    RespValue::Array(Some(values.into_iter().map(RespValue::Integer).collect()))
+}
+
+/// Set the expiration of `fields` to `expire_at` where `condition` allows,
+/// deleting fields whose time has already passed. Replies per field with
+/// -2 (no such field), 0 (condition not met), 1 (set) or 2 (deleted).
+fn expire_fields(
+    db_instance: &DbInstance,
+    key: &str,
+    mut hash: HashValue,
+    fields: &[Vec<u8>],
+    expire_at: u64,
+    condition: ExpireCondition,
+) -> Vec<i64> {
+    let now = current_timestamp_ms();
+    let mut deleted = false;
+    let mut expiring = false;
+    let replies = fields
+        .iter()
+        .map(|field| {
+            if !hash.contains_key(field.as_slice()) {
+                return -2;
+            }
+            if !condition.allows(db_instance.hash_field_expiry(key, field), expire_at) {
+                return 0;
+            }
+            if expire_at <= now {
+                hash.remove(field.as_slice());
+                deleted = true;
+                return 2;
+            }
+            db_instance.set_hash_field_expiry(key, Bytes::from(field.clone()), expire_at);
+            expiring = true;
+            1
+        })
+        .collect();
+    if expiring {
+        db_instance.notify(EventClass::HASH, "hexpire", key);
+    }
+    if deleted {
+        store_hash(db_instance, key, hash, "hdel");
+    }
+    replies
+}
+
+/// HEXPIRE / HPEXPIRE / HEXPIREAT / HPEXPIREAT
+/// key time [NX | XX | GT | LT] FIELDS numfields field [field ...]
+async fn hexpire_generic(
+    db: &Arc<Database>,
+    db_index: usize,
+    args: Vec<Vec<u8>>,
+    unit: ExpireUnit,
+    command: &str,
+) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", command));
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let expire_at = match unit.expire_at(&args[1], command) {
+        Ok(at) => at,
+        Err(e) => return e,
+    };
+    let (condition, fields_at) = match ExpireCondition::parse(&args[2]) {
+        Some(condition) => (condition, 3),
+        None => (ExpireCondition::Always, 2),
+    };
+    let fields = match parse_fields(&args, fields_at, 1) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    match get_hash(&db_instance, &key) {
+        Ok(Some(hash)) => integers(expire_fields(&db_instance, &key, hash, fields, expire_at, condition)),
+        Ok(None) => integers(vec![-2; fields.len()]),
+        Err(e) => e,
+    }
+}
+
+/// HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub async fn hexpire(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    hexpire_generic(db, db_index, args, ExpireUnit::Seconds, "hexpire").await
+}
+
+/// HPEXPIRE key milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub async fn hpexpire(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    hexpire_generic(db, db_index, args, ExpireUnit::Millis, "hpexpire").await
+}
+
+/// HEXPIREAT key unix-time-seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub async fn hexpireat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    hexpire_generic(db, db_index, args, ExpireUnit::UnixSeconds, "hexpireat").await
+}
+
+/// HPEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub async fn hpexpireat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    hexpire_generic(db, db_index, args, ExpireUnit::UnixMillis, "hpexpireat").await
+}
+
+/// What HTTL and friends report for a field with a TTL
+#[derive(Clone, Copy)]
+enum TtlReply {
+    Seconds,
+    Millis,
+    UnixSeconds,
+    UnixMillis,
+}
+
+/// HTTL / HPTTL / HEXPIRETIME / HPEXPIRETIME key FIELDS numfields field [field ...]
+/// Replies per field with -2 (no such field), -1 (no TTL) or the TTL
+async fn httl_generic(
+    db: &Arc<Database>,
+    db_index: usize,
+    args: Vec<Vec<u8>>,
+    reply: TtlReply,
+    command: &str,
+) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", command));
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let fields = match parse_fields(&args, 1, 1) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let hash = match get_hash(&db_instance, &key) {
+        Ok(Some(hash)) => hash,
+        Ok(None) => return integers(vec![-2; fields.len()]),
+        Err(e) => return e,
+    };
+
+    let now = current_timestamp_ms();
+    integers(
+        fields
+            .iter()
+            .map(|field| {
+                if !hash.contains_key(field.as_slice()) {
+                    return -2;
+                }
+                let Some(at) = db_instance.hash_field_expiry(&key, field) else {
+                    return -1;
+                };
+                let remaining = at.saturating_sub(now);
+                (match reply {
+                    TtlReply::Seconds => remaining.div_ceil(1000),
+                    TtlReply::Millis => remaining,
+                    TtlReply::UnixSeconds => at / 1000,
+                    TtlReply::UnixMillis => at,
+                }) as i64
+            })
+            .collect(),
+    )
+}
+
+/// HTTL key FIELDS numfields field [field ...]
+pub async fn httl(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    httl_generic(db, db_index, args, TtlReply::Seconds, "httl").await
+}
+
+/// HPTTL key FIELDS numfields field [field ...]
+pub async fn hpttl(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    httl_generic(db, db_index, args, TtlReply::Millis, "hpttl").await
+}
+
+/// HEXPIRETIME key FIELDS numfields field [field ...]
+pub async fn hexpiretime(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    httl_generic(db, db_index, args, TtlReply::UnixSeconds, "hexpiretime").await
+}
+
+/// HPEXPIRETIME key FIELDS numfields field [field ...]
+pub async fn hpexpiretime(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    httl_generic(db, db_index, args, TtlReply::UnixMillis, "hpexpiretime").await
+}
+
+/// HPERSIST key FIELDS numfields field [field ...]
+/// Remove field TTLs; replies per field with -2 (no such field), -1 (no
+/// TTL) or 1 (removed)
+pub async fn hpersist(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'hpersist' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let fields = match parse_fields(&args, 1, 1) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let hash = match get_hash(&db_instance, &key) {
+        Ok(Some(hash)) => hash,
+        Ok(None) => return integers(vec![-2; fields.len()]),
+        Err(e) => return e,
+    };
+
+    let replies: Vec<i64> = fields
+        .iter()
+        .map(|field| match hash.contains_key(field.as_slice()) {
+            false => -2,
+            true if db_instance.persist_hash_field(&key, field) => 1,
+            true => -1,
+        })
+        .collect();
+    if replies.contains(&1) {
+        db_instance.notify(EventClass::HASH, "hpersist", &key);
+    }
+    integers(replies)
+}
+
+fn field_values(hash: Option<&HashValue>, fields: &[Vec<u8>]) -> RespValue {
+    RespValue::Array(Some(
+        fields
+            .iter()
+            .map(|field| {
+                RespValue::BulkString(hash.and_then(|h| h.get(field.as_slice())).map(|v| v.to_vec()))
+            })
+            .collect(),
+    ))
+}
+
+/// HGETDEL key FIELDS numfields field [field ...]
+/// Return the values of fields and delete them
+pub async fn hgetdel(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'hgetdel' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let fields = match parse_fields(&args, 1, 1) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let mut hash = match get_hash(&db_instance, &key) {
+        Ok(Some(hash)) => hash,
+        Ok(None) => return field_values(None, fields),
+        Err(e) => return e,
+    };
+
+    let reply = field_values(Some(&hash), fields);
+    let before = hash.len();
+    for field in fields {
+        hash.remove(field.as_slice());
+    }
+    if hash.len() != before {
+        store_hash(&db_instance, &key, hash, "hdel");
+    }
+    reply
+}
+
+/// HGETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+///   PXAT unix-time-milliseconds | PERSIST] FIELDS numfields field [field ...]
+/// Return the values of fields, optionally setting or removing their TTLs
+pub async fn hgetex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'hgetex' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let option = String::from_utf8_lossy(&args[1]).to_uppercase();
+    let (expire_at, persist, fields_at) = match (option.as_str(), ExpireUnit::from_option(&option)) {
+        (_, Some(unit)) if args.len() > 2 => match unit.expire_at(&args[2], "hgetex") {
+            Ok(at) => (Some(at), false, 3),
+            Err(e) => return e,
+        },
+        ("PERSIST", _) => (None, true, 2),
+        _ => (None, false, 1),
+    };
+    let fields = match parse_fields(&args, fields_at, 1) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let hash = match get_hash(&db_instance, &key) {
+        Ok(Some(hash)) => hash,
+        Ok(None) => return field_values(None, fields),
+        Err(e) => return e,
+    };
+
+    let reply = field_values(Some(&hash), fields);
+    if let Some(at) = expire_at {
+        expire_fields(&db_instance, &key, hash, fields, at, ExpireCondition::Always);
+    } else if persist {
+        let mut persisted = false;
+        for field in fields {
+            if hash.contains_key(field.as_slice()) {
+                persisted |= db_instance.persist_hash_field(&key, field);
+            }
+        }
+        if persisted {
+            db_instance.notify(EventClass::HASH, "hpersist", &key);
+        }
+    }
+    reply
+}
+
+/// HSETEX key [FNX | FXX] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+///   PXAT unix-time-milliseconds | KEEPTTL] FIELDS numfields field value [field value ...]
+/// Set fields and their TTL; FNX only if none of the fields exist, FXX
+/// only if all of them do. Replies 1 if the fields were set, 0 otherwise.
+pub async fn hsetex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 5 {
+        return RespValue::Error("ERR wrong number of arguments for 'hsetex' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let mut only_new = false;
+    let mut only_existing = false;
+    let mut expire_at = None;
+    let mut keep_ttl = false;
+    let mut i = 1;
+    while i < args.len() && !args[i].eq_ignore_ascii_case(b"FIELDS") {
+        let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+        let ttl_given = expire_at.is_some() || keep_ttl;
+        match (option.as_str(), ExpireUnit::from_option(&option)) {
+            ("FNX", _) if !only_existing => only_new = true,
+            ("FXX", _) if !only_new => only_existing = true,
+            ("KEEPTTL", _) if !ttl_given => keep_ttl = true,
+            (_, Some(unit)) if !ttl_given && i + 1 < args.len() => {
+                match unit.expire_at(&args[i + 1], "hsetex") {
+                    Ok(at) => expire_at = Some(at),
+                    Err(e) => return e,
+                }
+                i += 1;
+            }
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+        i += 1;
+    }
+    let pairs = match parse_fields(&args, i, 2) {
+        Ok(pairs) => pairs,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let mut hash = match get_hash(&db_instance, &key) {
+        Ok(hash) => hash.unwrap_or_default(),
+        Err(e) => return e,
+    };
+
+    let exists = |field: &Vec<u8>| hash.contains_key(field.as_slice());
+    if (only_new && pairs.chunks(2).any(|p| exists(&p[0])))
+        || (only_existing && !pairs.chunks(2).all(|p| exists(&p[0])))
+    {
+        return RespValue::Integer(0);
+    }
+
+    for pair in pairs.chunks(2) {
+        hash.insert(Bytes::from(pair[0].clone()), Bytes::from(pair[1].clone()));
+    }
+    db_instance.set(key.clone(), RedisValue::Hash(hash.clone()));
+    db_instance.notify(EventClass::HASH, "hset", &key);
+
+    match expire_at {
+        Some(at) => {
+            let fields: Vec<Vec<u8>> = pairs.chunks(2).map(|p| p[0].clone()).collect();
+            expire_fields(&db_instance, &key, hash, &fields, at, ExpireCondition::Always);
+        }
+        // Without KEEPTTL, overwriting a field clears its TTL
+        None if !keep_ttl => {
+            for pair in pairs.chunks(2) {
+                db_instance.persist_hash_field(&key, &pair[0]);
+            }
+        }
+        None => {}
+    }
+    RespValue::Integer(1)
+}
+
+/// Commands that replay to the same result as the hash write `args`
+/// (command name included): relative field TTLs become absolute, and
+/// HGETEX becomes the TTL change it made, if any
+pub fn propagated_commands(args: &[Vec<u8>]) -> Vec<Vec<Vec<u8>>> {
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let absolute = |unit: ExpireUnit, arg: &[u8]| {
+        unit.expire_at(arg, &name).ok().map(|at| at.to_string().into_bytes())
+    };
+
+    match name.as_str() {
+        "HEXPIRE" | "HPEXPIRE" | "HEXPIREAT" if args.len() > 2 => {
+            let unit = match name.as_str() {
+                "HEXPIRE" => ExpireUnit::Seconds,
+                "HPEXPIRE" => ExpireUnit::Millis,
+                _ => ExpireUnit::UnixSeconds,
+            };
+            let Some(at) = absolute(unit, &args[2]) else {
+                return vec![];
+            };
+            let mut command = vec![b"HPEXPIREAT".to_vec(), args[1].clone(), at];
+            command.extend_from_slice(&args[3..]);
+            vec![command]
+        }
+        "HGETEX" if args.len() > 2 => {
+            let option = String::from_utf8_lossy(&args[2]).to_uppercase();
+            match ExpireUnit::from_option(&option) {
+                Some(unit) if args.len() > 3 => {
+                    let Some(at) = absolute(unit, &args[3]) else {
+                        return vec![];
+                    };
+                    let mut command = vec![b"HPEXPIREAT".to_vec(), args[1].clone(), at];
+                    command.extend_from_slice(&args[4..]);
+                    vec![command]
+                }
+                _ if option == "PERSIST" => {
+                    let mut command = vec![b"HPERSIST".to_vec(), args[1].clone()];
+                    command.extend_from_slice(&args[3..]);
+                    vec![command]
+                }
+                // A plain read
+                _ => vec![],
+            }
+        }
+        "HSETEX" => {
+            let mut command = args.to_vec();
+            let fields_at = command
+                .iter()
+                .skip(2)
+                .position(|a| a.eq_ignore_ascii_case(b"FIELDS"))
+                .map_or(command.len(), |p| p + 2);
+            for i in 2..fields_at.saturating_sub(1) {
+                let option = String::from_utf8_lossy(&command[i]).to_uppercase();
+                if let Some(unit @ (ExpireUnit::Seconds | ExpireUnit::Millis | ExpireUnit::UnixSeconds)) =
+                    ExpireUnit::from_option(&option)
+                {
+                    if let Some(at) = absolute(unit, &command[i + 1]) {
+                        command[i] = b"PXAT".to_vec();
+                        command[i + 1] = at;
+                    }
+                }
+            }
+            vec![command]
+        }
+        _ => vec![args.to_vec()],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -804,4 +1381,181 @@ mod tests {
         let result = hexists(&db, 0, vec![b"myhash".to_vec(), b"field1".to_vec()]).await;
         assert_eq!(result, RespValue::Integer(0));
     }
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    fn ints(values: &[i64]) -> RespValue {
+        RespValue::Array(Some(values.iter().map(|&v| RespValue::Integer(v)).collect()))
+    }
+
+    #[tokio::test]
+    async fn test_hexpire_conditions_and_ttls() {
+        let db = Arc::new(Database::new(16));
+        hset(&db, 0, args(&["h", "a", "1", "b", "2", "c", "3"])).await;
+
+        let reply = hexpire(&db, 0, args(&["h", "100", "FIELDS", "2", "a", "missing"])).await;
+        assert_eq!(reply, ints(&[1, -2]));
+        // NX skips fields with a TTL, XX fields without one
+        let reply = hexpire(&db, 0, args(&["h", "200", "NX", "FIELDS", "2", "a", "b"])).await;
+        assert_eq!(reply, ints(&[0, 1]));
+        let reply = hexpire(&db, 0, args(&["h", "300", "XX", "FIELDS", "2", "a", "c"])).await;
+        assert_eq!(reply, ints(&[1, 0]));
+        // GT never applies to a field without TTL, LT always does
+        let reply = hpexpire(&db, 0, args(&["h", "50000", "GT", "FIELDS", "2", "a", "c"])).await;
+        assert_eq!(reply, ints(&[0, 0]));
+        let reply = hpexpire(&db, 0, args(&["h", "50000", "LT", "FIELDS", "2", "a", "c"])).await;
+        assert_eq!(reply, ints(&[1, 1]));
+
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "2", "b", "x"])).await, ints(&[200, -2]));
+        let RespValue::Array(Some(ttls)) = hpttl(&db, 0, args(&["h", "FIELDS", "1", "a"])).await else {
+            panic!("expected array")
+        };
+        assert!(matches!(ttls[0], RespValue::Integer(ms) if ms > 49_000 && ms <= 50_000));
+
+        let reply = hexpireat(&db, 0, args(&["h", "4000000000", "FIELDS", "1", "b"])).await;
+        assert_eq!(reply, ints(&[1]));
+        assert_eq!(hexpiretime(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[4000000000]));
+        assert_eq!(hpexpiretime(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[4000000000000]));
+
+        assert_eq!(hpersist(&db, 0, args(&["h", "FIELDS", "3", "b", "b", "x"])).await, ints(&[1, -1, -2]));
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[-1]));
+
+        // HSET overwriting a field clears its TTL
+        hset(&db, 0, args(&["h", "a", "new"])).await;
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "a"])).await, ints(&[-1]));
+
+        // A time in the past deletes the field, and the key with the last one
+        let reply = hpexpireat(&db, 0, args(&["h", "1", "FIELDS", "3", "a", "b", "c"])).await;
+        assert_eq!(reply, ints(&[2, 2, 2]));
+        assert!(db.get_db(0).unwrap().get("h").is_none());
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "a"])).await, ints(&[-2]));
+    }
+
+    #[tokio::test]
+    async fn test_hexpire_errors() {
+        let db = Arc::new(Database::new(16));
+        hset(&db, 0, args(&["h", "a", "1"])).await;
+
+        let reply = hexpire(&db, 0, args(&["h", "10", "FIELDS", "2", "a"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("must match")));
+        let reply = hexpire(&db, 0, args(&["h", "10", "FIELDS", "0", "a"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("greater than 0")));
+        let reply = hexpire(&db, 0, args(&["h", "10", "a", "FIELDS", "1", "a"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("FIELDS is missing")));
+        let reply = hexpire(&db, 0, args(&["h", "-1", "FIELDS", "1", "a"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("invalid expire time")));
+        let reply = hpexpireat(&db, 0, args(&["h", "281474976710656", "FIELDS", "1", "a"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("invalid expire time")));
+    }
+
+    #[tokio::test]
+    async fn test_fields_expire_lazily() {
+        let db = Arc::new(Database::new(16));
+        hset(&db, 0, args(&["h", "a", "1", "b", "2"])).await;
+        hpexpire(&db, 0, args(&["h", "20", "FIELDS", "1", "a"])).await;
+        tokio::time::sleep(std::time::Duration::from_millis(40)).await;
+
+        assert_eq!(hlen(&db, 0, args(&["h"])).await, RespValue::Integer(1));
+        assert_eq!(hget(&db, 0, args(&["h", "a"])).await, RespValue::BulkString(None));
+
+        // A re-created field does not inherit the old TTL
+        hset(&db, 0, args(&["h", "a", "again"])).await;
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "a"])).await, ints(&[-1]));
+
+        hpexpire(&db, 0, args(&["h", "20", "FIELDS", "2", "a", "b"])).await;
+        tokio::time::sleep(std::time::Duration::from_millis(40)).await;
+        assert!(!db.get_db(0).unwrap().exists("h"));
+    }
+
+    #[tokio::test]
+    async fn test_hgetdel_hgetex_hsetex() {
+        let db = Arc::new(Database::new(16));
+        hset(&db, 0, args(&["h", "a", "1", "b", "2", "c", "3"])).await;
+
+        let reply = hgetdel(&db, 0, args(&["h", "FIELDS", "2", "a", "x"])).await;
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![RespValue::BulkString(Some(b"1".to_vec())), RespValue::BulkString(None)]))
+        );
+        assert_eq!(hlen(&db, 0, args(&["h"])).await, RespValue::Integer(2));
+
+        let reply = hgetex(&db, 0, args(&["h", "EX", "100", "FIELDS", "1", "b"])).await;
+        assert_eq!(reply, RespValue::Array(Some(vec![RespValue::BulkString(Some(b"2".to_vec()))])));
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[100]));
+        hgetex(&db, 0, args(&["h", "PERSIST", "FIELDS", "1", "b"])).await;
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[-1]));
+
+        // FNX: none may exist; FXX: all must exist
+        let reply = hsetex(&db, 0, args(&["h", "FNX", "EX", "50", "FIELDS", "2", "b", "9", "d", "4"])).await;
+        assert_eq!(reply, RespValue::Integer(0));
+        let reply = hsetex(&db, 0, args(&["h", "FXX", "PX", "50000", "FIELDS", "2", "b", "9", "c", "8"])).await;
+        assert_eq!(reply, RespValue::Integer(1));
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "2", "b", "c"])).await, ints(&[50, 50]));
+        assert_eq!(hget(&db, 0, args(&["h", "b"])).await, RespValue::BulkString(Some(b"9".to_vec())));
+
+        // KEEPTTL keeps it; plain HSETEX clears it
+        hsetex(&db, 0, args(&["h", "KEEPTTL", "FIELDS", "1", "b", "10"])).await;
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[50]));
+        hsetex(&db, 0, args(&["h", "FIELDS", "1", "b", "11"])).await;
+        assert_eq!(httl(&db, 0, args(&["h", "FIELDS", "1", "b"])).await, ints(&[-1]));
+
+        let reply = hsetex(&db, 0, args(&["h", "EX", "1", "KEEPTTL", "FIELDS", "1", "b", "1"])).await;
+        assert_eq!(reply, RespValue::Error("ERR syntax error".to_string()));
+
+        // Renaming the hash keeps its field TTLs
+        crate::commands::key_mgmt::rename(&db, 0, args(&["h", "h2"])).await;
+        assert_eq!(httl(&db, 0, args(&["h2", "FIELDS", "1", "c"])).await, ints(&[50]));
+    }
+
+    #[test]
+    fn test_propagated_commands_use_absolute_times() {
+        let now = current_timestamp_ms();
+        let absolute = |command: &[Vec<u8>], at: usize| -> u64 {
+            String::from_utf8_lossy(&command[at]).parse().unwrap()
+        };
+
+        let out = propagated_commands(&args(&["HEXPIRE", "h", "10", "NX", "FIELDS", "1", "a"]));
+        assert_eq!(out[0][0], b"HPEXPIREAT");
+        assert!(absolute(&out[0], 2) >= now + 10_000);
+        assert_eq!(&out[0][3..], &args(&["NX", "FIELDS", "1", "a"])[..]);
+
+        let out = propagated_commands(&args(&["HGETEX", "h", "PX", "500", "FIELDS", "1", "a"]));
+        assert_eq!(out[0][0], b"HPEXPIREAT");
+        assert_eq!(&out[0][3..], &args(&["FIELDS", "1", "a"])[..]);
+        let out = propagated_commands(&args(&["HGETEX", "h", "PERSIST", "FIELDS", "1", "a"]));
+        assert_eq!(out, vec![args(&["HPERSIST", "h", "FIELDS", "1", "a"])]);
+        assert!(propagated_commands(&args(&["HGETEX", "h", "FIELDS", "1", "a"])).is_empty());
+
+        let out = propagated_commands(&args(&["HSETEX", "FIELDS", "FXX", "EX", "5", "FIELDS", "1", "a", "v"]));
+        assert_eq!(out[0][3], b"PXAT");
+        assert!(absolute(&out[0], 4) >= now + 5_000);
+
+        let hset = args(&["HSET", "h", "a", "1"]);
+        assert_eq!(propagated_commands(&hset), vec![hset]);
+    }
+
+    #[tokio::test]
+    async fn test_hscan_novalues() {
+        let db = Arc::new(Database::new(16));
+        hset(&db, 0, args(&["h", "a", "1", "b", "2", "other", "3"])).await;
+
+        let reply = hscan(&db, 0, args(&["h", "0", "MATCH", "?", "NOVALUES"])).await;
+        let RespValue::Array(Some(reply)) = reply else { panic!("unexpected HSCAN reply") };
+        assert_eq!(reply[0], RespValue::BulkString(Some(b"0".to_vec())));
+        let RespValue::Array(Some(fields)) = &reply[1] else { panic!("unexpected HSCAN fields") };
+        let mut fields = fields.clone();
+        fields.sort_by_key(|f| format!("{:?}", f));
+        assert_eq!(
+            fields,
+            vec![RespValue::BulkString(Some(b"a".to_vec())), RespValue::BulkString(Some(b"b".to_vec()))]
+        );
+
+        let reply = hscan(&db, 0, args(&["h", "0", "COUNT", "1"])).await;
+        let RespValue::Array(Some(reply)) = reply else { panic!("unexpected HSCAN reply") };
+        assert_ne!(reply[0], RespValue::BulkString(Some(b"0".to_vec())));
+        // One field with its value
+        assert!(matches!(&reply[1], RespValue::Array(Some(pair)) if pair.len() == 2));
+    }
 }
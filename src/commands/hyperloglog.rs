@@ -3,6 +3,7 @@
 
 use crate::protocol::RespValue;
 use crate::storage::db::Database;
+use crate::storage::notify::EventClass;
 use crate::storage::types::RedisValue;
 use bytes::Bytes;
 use std::sync::Arc;
@@ -202,7 +203,10 @@ pub async fn pfadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     }
 
     // Store back
-    db_instance.set(key, RedisValue::String(Bytes::from(hll.to_bytes())));
+    db_instance.set(key.clone(), RedisValue::String(Bytes::from(hll.to_bytes())));
+    if changed {
+        db_instance.notify(EventClass::STRING, "pfadd", &key);
+    }
 
     RespValue::Integer(if changed { 1 } else { 0 })
 }
@@ -226,7 +230,7 @@ pub async fn pfcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             Err(_) => return RespValue::Error("ERR invalid key".to_string()),
         };
 
-        match db_instance.get(key) {
+        match db_instance.lookup_read(key) {
             Some(RedisValue::String(bytes)) => {
                 match HyperLogLog::from_bytes(&bytes) {
                     Some(hll) => return RespValue::Integer(hll.count() as i64),
@@ -303,7 +307,8 @@ pub async fn pfmerge(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 
     // Store result
-    db_instance.set(destkey, RedisValue::String(Bytes::from(merged.to_bytes())));
+    db_instance.set(destkey.clone(), RedisValue::String(Bytes::from(merged.to_bytes())));
+    db_instance.notify(EventClass::STRING, "pfadd", &destkey);
 
     RespValue::SimpleString("OK".to_string())
 }
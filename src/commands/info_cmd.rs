@@ -1,7 +1,8 @@
 // INFO command implementation
 
+use crate::config::Config;
 use crate::protocol::RespValue;
-use crate::replication::ReplicationInfo;
+use crate::replication::{ReplicationInfo, ReplicaState};
 use crate::storage::db::Database;
 use std::sync::Arc;
 
@@ -9,6 +10,7 @@ use std::sync::Arc;
 pub async fn info(
     db: &Arc<Database>,
     repl_info: &Arc<ReplicationInfo>,
+    config: &Arc<Config>,
     args: Vec<Vec<u8>>,
 ) -> RespValue {
     // Parse optional section argument
@@ -64,12 +66,16 @@ pub async fn info(
             info_lines.push(format!("master_replid:{}", repl_info.replication_id()));
         } else {
             info_lines.push("role:slave".to_string());
+            let priority = config.get_int("replica-priority").unwrap_or(100);
 
             if let crate::replication::ReplicationRole::Replica { master_host, master_port, state } = repl_info.role() {
                 info_lines.push(format!("master_host:{}", master_host));
                 info_lines.push(format!("master_port:{}", master_port));
-                info_lines.push(format!("master_link_status:{:?}", state).to_lowercase());
+                let link_status = if repl_info.master_link_up() { "up" } else { "down" };
+                info_lines.push(format!("master_link_status:{}", link_status));
+                info_lines.push(format!("master_sync_in_progress:{}", matches!(state, ReplicaState::WaitingFullSync | ReplicaState::ReceivingRdb) as u8));
                 info_lines.push(format!("slave_repl_offset:{}", repl_info.master_offset()));
+                info_lines.push(format!("slave_priority:{}", priority));
             }
         }
         info_lines.push("".to_string());
@@ -119,7 +125,7 @@ mod tests {
         let db = Arc::new(Database::new(16));
         let repl_info = Arc::new(ReplicationInfo::new());
 
-        let result = info(&db, &repl_info, vec![]).await;
+        let result = info(&db, &repl_info, &Arc::new(Config::new()), vec![]).await;
 
         match result {
             RespValue::BulkString(Some(data)) => {
@@ -137,7 +143,7 @@ mod tests {
         let db = Arc::new(Database::new(16));
         let repl_info = Arc::new(ReplicationInfo::new());
 
-        let result = info(&db, &repl_info, vec![b"replication".to_vec()]).await;
+        let result = info(&db, &repl_info, &Arc::new(Config::new()), vec![b"replication".to_vec()]).await;
 
         match result {
             RespValue::BulkString(Some(data)) => {
@@ -149,4 +155,23 @@ mod tests {
             _ => panic!("Expected BulkString"),
         }
     }
+
+    #[tokio::test]
+    async fn test_info_replica_fields() {
+        let db = Arc::new(Database::new(16));
+        let repl_info = Arc::new(ReplicationInfo::new());
+        repl_info.set_replica("127.0.0.1".to_string(), 6380);
+        let config = Arc::new(Config::new());
+        config.set("replica-priority".to_string(), "10".to_string()).unwrap();
+
+        let result = info(&db, &repl_info, &config, vec![b"replication".to_vec()]).await;
+        let info_str = String::from_utf8(result.as_bulk_string().unwrap().to_vec()).unwrap();
+        assert!(info_str.contains("master_link_status:down"));
+        assert!(info_str.contains("slave_priority:10"));
+
+        repl_info.update_replica_state(ReplicaState::Connected);
+        let result = info(&db, &repl_info, &config, vec![b"replication".to_vec()]).await;
+        let info_str = String::from_utf8(result.as_bulk_string().unwrap().to_vec()).unwrap();
+        assert!(info_str.contains("master_link_status:up"));
+    }
 }
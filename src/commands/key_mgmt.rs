@@ -1,11 +1,16 @@
 // Key management commands for Redis-Rust
-// Commands: RENAME, RENAMENX, COPY, MOVE, DUMP, RESTORE, SCAN, TOUCH, UNLINK, OBJECT
+// Commands: RENAME, RENAMENX, COPY, MOVE, DUMP, RESTORE, MIGRATE, SCAN, TOUCH, UNLINK, OBJECT
 
-use crate::protocol::RespValue;
-use crate::storage::db::Database;
+use crate::protocol::{RespParser, RespSerializer, RespValue};
+use crate::pubsub::PubSub;
+use crate::storage::db::{Database, DbInstance};
+use crate::storage::notify::EventClass;
 use crate::storage::types::RedisValue;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 
 /// RENAME key newkey
 /// Rename a key atomically
@@ -37,6 +42,7 @@ pub async fn rename(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
 
     // Get TTL if exists
     let ttl_ms = db_instance.get_ttl_ms(&key);
+    let field_ttls = db_instance.hash_field_expiries(&key);
 
     // Delete old key
     db_instance.delete(&key);
@@ -44,10 +50,13 @@ pub async fn rename(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     // Set new key with same value and TTL
     if ttl_ms > 0 {
         let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
-        db_instance.set_with_expiry(newkey, value, expire_at_ms);
+        db_instance.set_with_expiry(newkey.clone(), value, expire_at_ms);
     } else {
-        db_instance.set(newkey, value);
+        db_instance.set(newkey.clone(), value);
     }
+    db_instance.set_hash_field_expiries(&newkey, field_ttls);
+    db_instance.notify(EventClass::GENERIC, "rename_from", &key);
+    db_instance.notify(EventClass::GENERIC, "rename_to", &newkey);
 
     RespValue::SimpleString("OK".to_string())
 }
@@ -87,6 +96,7 @@ pub async fn renamenx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
 
     // Get TTL if exists
     let ttl_ms = db_instance.get_ttl_ms(&key);
+    let field_ttls = db_instance.hash_field_expiries(&key);
 
     // Delete old key
     db_instance.delete(&key);
@@ -94,10 +104,13 @@ pub async fn renamenx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
     // Set new key with same value and TTL
     if ttl_ms > 0 {
         let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
-        db_instance.set_with_expiry(newkey, value, expire_at_ms);
+        db_instance.set_with_expiry(newkey.clone(), value, expire_at_ms);
     } else {
-        db_instance.set(newkey, value);
+        db_instance.set(newkey.clone(), value);
     }
+    db_instance.set_hash_field_expiries(&newkey, field_ttls);
+    db_instance.notify(EventClass::GENERIC, "rename_from", &key);
+    db_instance.notify(EventClass::GENERIC, "rename_to", &newkey);
 
     RespValue::Integer(1)
 }
@@ -175,14 +188,17 @@ pub async fn copy(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
 
     // Get TTL if exists
     let ttl_ms = source_db.get_ttl_ms(&source);
+    let field_ttls = source_db.hash_field_expiries(&source);
 
     // Copy to destination
     if ttl_ms > 0 {
         let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
-        target_db.set_with_expiry(dest, value, expire_at_ms);
+        target_db.set_with_expiry(dest.clone(), value, expire_at_ms);
     } else {
-        target_db.set(dest, value);
+        target_db.set(dest.clone(), value);
     }
+    target_db.set_hash_field_expiries(&dest, field_ttls);
+    target_db.notify(EventClass::GENERIC, "copy_to", &dest);
 
     RespValue::Integer(1)
 }
@@ -234,6 +250,7 @@ pub async fn move_key(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
 
     // Get TTL if exists
     let ttl_ms = source_db.get_ttl_ms(&key);
+    let field_ttls = source_db.hash_field_expiries(&key);
 
     // Move to target
     if ttl_ms > 0 {
@@ -242,9 +259,12 @@ pub async fn move_key(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
     } else {
         target_db.set(key.clone(), value);
     }
+    target_db.set_hash_field_expiries(&key, field_ttls);
 
     // Delete from source
     source_db.delete(&key);
+    source_db.notify(EventClass::GENERIC, "move_from", &key);
+    target_db.notify(EventClass::GENERIC, "move_to", &key);
 
     RespValue::Integer(1)
 }
@@ -297,6 +317,7 @@ pub async fn unlink(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         };
 
         if db_instance.delete(key) {
+            db_instance.notify(EventClass::GENERIC, "del", key);
             count += 1;
         }
     }
@@ -357,6 +378,7 @@ pub async fn restore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     let serialized = &args[2];
 
     let mut replace = false;
+    let mut abs_ttl = false;
 
     // Parse optional arguments
     for i in 3..args.len() {
@@ -365,8 +387,10 @@ pub async fn restore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             Err(_) => continue,
         };
 
-        if arg == "REPLACE" {
-            replace = true;
+        match arg.as_str() {
+            "REPLACE" => replace = true,
+            "ABSTTL" => abs_ttl = true,
+            _ => {}
         }
     }
 
@@ -381,103 +405,312 @@ pub async fn restore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     }
 
     // Deserialize value
-    let value = match deserialize_value(serialized) {
+    let value = match deserialize_value(serialized, &db_instance) {
         Ok(v) => v,
         Err(e) => return RespValue::Error(format!("ERR {}", e)),
     };
 
     // Set key with optional TTL
     if ttl_ms > 0 {
-        let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
-        db_instance.set_with_expiry(key, value, expire_at_ms);
+        let now = crate::storage::db::current_timestamp_ms();
+        let expire_at_ms = if abs_ttl { ttl_ms as u64 } else { now + ttl_ms as u64 };
+        // An ABSTTL already in the past restores nothing
+        if expire_at_ms <= now {
+            db_instance.delete(&key);
+            return RespValue::SimpleString("OK".to_string());
+        }
+        db_instance.set_with_expiry(key.clone(), value, expire_at_ms);
     } else {
-        db_instance.set(key, value);
+        db_instance.set(key.clone(), value);
     }
+    db_instance.notify(EventClass::GENERIC, "restore", &key);
 
     RespValue::SimpleString("OK".to_string())
 }
 
-/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
-/// Iterate keys with cursor-based pagination
-pub async fn scan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.is_empty() {
-        return RespValue::Error("ERR wrong number of arguments for 'scan' command".to_string());
+/// The RESTORE `args` (command name included) in the form that replays to
+/// the same expiry: a relative TTL becomes an ABSTTL
+pub fn propagated_restore(args: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut command = args.to_vec();
+    let abs_ttl = args.iter().skip(4).any(|arg| arg.eq_ignore_ascii_case(b"ABSTTL"));
+    let ttl_ms = std::str::from_utf8(&args[2]).ok().and_then(|t| t.parse::<u64>().ok()).unwrap_or(0);
+    if ttl_ms > 0 && !abs_ttl {
+        command[2] = (crate::storage::db::current_timestamp_ms() + ttl_ms).to_string().into_bytes();
+        command.push(b"ABSTTL".to_vec());
     }
+    command
+}
 
-    let cursor: usize = match std::str::from_utf8(&args[0]) {
-        Ok(s) => match s.parse() {
-            Ok(c) => c,
-            Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
-    };
+/// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [AUTH password]
+/// [AUTH2 username password] [KEYS key ...]
+/// Move keys to another instance: each key is sent as a RESTORE-ASKING
+/// of its DUMP payload, and deleted locally once the target accepted it
+pub async fn migrate(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    migrate_keys(db, db_index, args).await.0
+}
 
-    let mut pattern = "*";
-    let mut count = 10;
+/// MIGRATE, also returning the keys it deleted here so the caller can log
+/// and propagate their removal
+pub async fn migrate_keys(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> (RespValue, Vec<String>) {
+    if args.len() < 5 {
+        return (RespValue::Error("ERR wrong number of arguments for 'migrate' command".to_string()), vec![]);
+    }
+
+    let host = String::from_utf8_lossy(&args[0]).to_string();
+    let parse_int = |arg: &[u8]| std::str::from_utf8(arg).ok().and_then(|s| s.parse::<i64>().ok());
+    let (Some(port), Some(dest_db), Some(timeout)) = (
+        parse_int(&args[1]).and_then(|p| u16::try_from(p).ok()),
+        parse_int(&args[3]),
+        parse_int(&args[4]),
+    ) else {
+        return (RespValue::Error("ERR value is not an integer or out of range".to_string()), vec![]);
+    };
+    let timeout = Duration::from_millis(if timeout <= 0 { 1000 } else { timeout as u64 });
 
-    // Parse options
-    let mut i = 1;
+    let mut copy = false;
+    let mut replace = false;
+    let mut auth: Vec<Vec<u8>> = Vec::new();
+    let mut keys: Vec<String> = Vec::new();
+    let mut i = 5;
     while i < args.len() {
-        let arg = match std::str::from_utf8(&args[i]) {
-            Ok(s) => s.to_uppercase(),
-            Err(_) => {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "COPY" => copy = true,
+            "REPLACE" => replace = true,
+            "AUTH" if i + 1 < args.len() => {
+                auth = vec![args[i + 1].clone()];
                 i += 1;
-                continue;
             }
-        };
-
-        match arg.as_str() {
-            "MATCH" => {
-                i += 1;
-                if i < args.len() {
-                    pattern = match std::str::from_utf8(&args[i]) {
-                        Ok(s) => s,
-                        Err(_) => "*",
-                    };
-                }
+            "AUTH2" if i + 2 < args.len() => {
+                auth = vec![args[i + 1].clone(), args[i + 2].clone()];
+                i += 2;
             }
-            "COUNT" => {
-                i += 1;
-                if i < args.len() {
-                    count = match std::str::from_utf8(&args[i]) {
-                        Ok(s) => s.parse().unwrap_or(10),
-                        Err(_) => 10,
-                    };
+            "KEYS" => {
+                if !args[2].is_empty() {
+                    let error = "ERR When using MIGRATE KEYS option, the key argument must be set to the empty string";
+                    return (RespValue::Error(error.to_string()), vec![]);
                 }
+                keys.extend(args[i + 1..].iter().map(|k| String::from_utf8_lossy(k).to_string()));
+                break;
             }
-            _ => {}
+            _ => return (RespValue::Error("ERR syntax error".to_string()), vec![]),
         }
         i += 1;
     }
+    if keys.is_empty() && !args[2].is_empty() {
+        keys.push(String::from_utf8_lossy(&args[2]).to_string());
+    }
 
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
+        None => return (RespValue::Error("ERR invalid database".to_string()), vec![]),
     };
 
-    // Get all keys matching pattern
-    let all_keys = db_instance.keys(pattern);
+    // Only keys that exist are sent: (key, ttl, payload)
+    let entries: Vec<(String, i64, Vec<u8>)> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let value = db_instance.get(&key)?;
+            let ttl = db_instance.get_ttl_ms(&key).max(0);
+            Some((key, ttl, serialize_value(&value)))
+        })
+        .collect();
+    if entries.is_empty() {
+        return (RespValue::SimpleString("NOKEY".to_string()), vec![]);
+    }
 
-    // Pagination: skip to cursor position
-    let start = cursor;
-    let end = std::cmp::min(start + count, all_keys.len());
+    let bulk = |data: &[u8]| RespValue::BulkString(Some(data.to_vec()));
+    let mut commands = Vec::new();
+    if !auth.is_empty() {
+        let mut command = vec![bulk(b"AUTH")];
+        command.extend(auth.iter().map(|a| bulk(a)));
+        commands.push(command);
+    }
+    commands.push(vec![bulk(b"SELECT"), bulk(dest_db.to_string().as_bytes())]);
+    let setup = commands.len();
+    for (key, ttl, payload) in &entries {
+        let mut command = vec![
+            bulk(b"RESTORE-ASKING"),
+            bulk(key.as_bytes()),
+            bulk(ttl.to_string().as_bytes()),
+            bulk(payload),
+        ];
+        if replace {
+            command.push(bulk(b"REPLACE"));
+        }
+        commands.push(command);
+    }
 
-    let keys: Vec<RespValue> = all_keys[start..end]
-        .iter()
-        .map(|k| RespValue::BulkString(Some(k.as_bytes().to_vec())))
-        .collect();
+    let mut stream = match tokio::time::timeout(timeout, TcpStream::connect((host.as_str(), port))).await {
+        Ok(Ok(stream)) => stream,
+        _ => return (RespValue::Error("IOERR error or timeout connecting to the client".to_string()), vec![]),
+    };
+    let mut request = Vec::new();
+    for command in commands {
+        request.extend(RespSerializer::serialize(&RespValue::Array(Some(command))));
+    }
+    let replies = match tokio::time::timeout(timeout, exchange(&mut stream, &request, entries.len() + setup)).await {
+        Ok(Ok(replies)) => replies,
+        _ => return (RespValue::Error("IOERR error or timeout reading to target instance".to_string()), vec![]),
+    };
 
-    // Calculate next cursor (0 means iteration complete)
-    let next_cursor = if end < all_keys.len() {
-        end.to_string()
-    } else {
-        "0".to_string()
+    if let Some(RespValue::Error(e)) = replies[..setup].iter().find(|r| matches!(r, RespValue::Error(_))) {
+        return (RespValue::Error(format!("ERR Target instance replied with error: {}", e)), vec![]);
+    }
+
+    let mut error = None;
+    let mut deleted = Vec::new();
+    for ((key, _, payload), reply) in entries.iter().zip(&replies[setup..]) {
+        match reply {
+            RespValue::Error(e) => {
+                error.get_or_insert_with(|| format!("ERR Target instance replied with error: {}", e));
+            }
+            // A key written while in flight stays here for the next MIGRATE
+            _ if !copy && db_instance.delete_if(key, |value| serialize_value(value) == *payload) => {
+                deleted.push(key.clone());
+            }
+            _ => {}
+        }
+    }
+
+    let response = match error {
+        Some(e) => RespValue::Error(e),
+        None => RespValue::SimpleString("OK".to_string()),
     };
+    (response, deleted)
+}
+
+/// Send a pipelined request and read `count` replies
+async fn exchange(stream: &mut TcpStream, request: &[u8], count: usize) -> anyhow::Result<Vec<RespValue>> {
+    stream.write_all(request).await?;
+    let mut buffer = BytesMut::new();
+    let mut replies = Vec::with_capacity(count);
+    while replies.len() < count {
+        if let Ok(Some(len)) = RespParser::check_complete(&buffer) {
+            let frame = buffer.split_to(len);
+            replies.push(RespParser::parse(&frame)?);
+            continue;
+        }
+        if stream.read_buf(&mut buffer).await? == 0 {
+            anyhow::bail!("connection closed");
+        }
+    }
+    Ok(replies)
+}
 
-    RespValue::Array(Some(vec![
-        RespValue::BulkString(Some(next_cursor.into_bytes())),
-        RespValue::Array(Some(keys)),
-    ]))
+/// Cursor and options shared by SCAN, HSCAN, SSCAN and ZSCAN
+pub struct ScanOptions {
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+    /// SCAN only: keep keys holding this type
+    pub type_name: Option<String>,
+    /// HSCAN only: return fields without their values
+    pub novalues: bool,
+}
+
+impl ScanOptions {
+    /// Parse `cursor [MATCH pattern] [COUNT count]` plus the options
+    /// named in `extra` ("TYPE" for SCAN, "NOVALUES" for HSCAN)
+    pub fn parse(args: &[Vec<u8>], extra: &[&str]) -> Result<Self, RespValue> {
+        let cursor = std::str::from_utf8(&args[0])
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or_else(|| RespValue::Error("ERR invalid cursor".to_string()))?;
+
+        let mut options = ScanOptions {
+            cursor,
+            pattern: None,
+            count: 10,
+            type_name: None,
+            novalues: false,
+        };
+        let syntax_error = || RespValue::Error("ERR syntax error".to_string());
+
+        let mut i = 1;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            let has_value = i + 1 < args.len();
+            match option.as_str() {
+                "MATCH" if has_value => {
+                    let pattern = String::from_utf8_lossy(&args[i + 1]).to_string();
+                    // "*" matches everything, so skip the matching altogether
+                    options.pattern = (pattern != "*").then_some(pattern);
+                    i += 2;
+                }
+                "COUNT" if has_value => {
+                    options.count = std::str::from_utf8(&args[i + 1])
+                        .ok()
+                        .and_then(|s| s.parse::<i64>().ok())
+                        .ok_or_else(|| {
+                            RespValue::Error("ERR value is not an integer or out of range".to_string())
+                        })?
+                        .try_into()
+                        .ok()
+                        .filter(|&count| count >= 1)
+                        .ok_or_else(syntax_error)?;
+                    i += 2;
+                }
+                "TYPE" if has_value && extra.contains(&"TYPE") => {
+                    options.type_name = Some(String::from_utf8_lossy(&args[i + 1]).to_lowercase());
+                    i += 2;
+                }
+                "NOVALUES" if extra.contains(&"NOVALUES") => {
+                    options.novalues = true;
+                    i += 1;
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Whether an element passes MATCH
+    pub fn matches(&self, element: &[u8]) -> bool {
+        match &self.pattern {
+            Some(pattern) => PubSub::match_pattern(&String::from_utf8_lossy(element), pattern),
+            None => true,
+        }
+    }
+
+    /// `[cursor, elements]`
+    pub fn reply(next_cursor: u64, elements: Vec<RespValue>) -> RespValue {
+        RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(next_cursor.to_string().into_bytes())),
+            RespValue::Array(Some(elements)),
+        ]))
+    }
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+/// Iterate keys with a cursor. Keys present for the whole iteration are
+/// returned exactly once even if others are written in between.
+pub async fn scan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.is_empty() {
+        return RespValue::Error("ERR wrong number of arguments for 'scan' command".to_string());
+    }
+
+    let options = match ScanOptions::parse(&args, &["TYPE"]) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let (keys, next_cursor) = db_instance.scan(options.cursor, options.count);
+    let keys = keys
+        .into_iter()
+        .filter(|key| options.matches(key.as_bytes()))
+        .filter(|key| match &options.type_name {
+            Some(wanted) => db_instance.type_name(key) == Some(wanted.as_str()),
+            None => true,
+        })
+        .map(|key| RespValue::BulkString(Some(key.into_bytes())))
+        .collect();
+
+    ScanOptions::reply(next_cursor, keys)
 }
 
 /// OBJECT subcommand [arguments]
@@ -529,17 +762,7 @@ pub async fn object(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             };
 
             match db_instance.get(key) {
-                Some(value) => {
-                    let encoding = match value {
-                        RedisValue::String(_) => "raw",
-                        RedisValue::List(_) => "linkedlist",
-                        RedisValue::Set(_) => "hashtable",
-                        RedisValue::Hash(_) => "hashtable",
-                        RedisValue::ZSet(_) => "skiplist",
-                        RedisValue::Stream(_) => "stream",
-                    };
-                    RespValue::SimpleString(encoding.to_string())
-                }
+                Some(value) => RespValue::SimpleString(value.encoding().to_string()),
                 None => RespValue::Null,
             }
         }
@@ -584,27 +807,27 @@ fn serialize_value(value: &RedisValue) -> Vec<u8> {
             result.push(1); // Type: List
             let len = (list.len() as u32).to_le_bytes();
             result.extend_from_slice(&len);
-            for item in list {
+            for item in list.iter() {
                 let item_len = (item.len() as u32).to_le_bytes();
                 result.extend_from_slice(&item_len);
-                result.extend_from_slice(item);
+                result.extend_from_slice(&item);
             }
         }
         RedisValue::Set(set) => {
             result.push(2); // Type: Set
             let len = (set.len() as u32).to_le_bytes();
             result.extend_from_slice(&len);
-            for item in set {
+            for item in set.iter() {
                 let item_len = (item.len() as u32).to_le_bytes();
                 result.extend_from_slice(&item_len);
-                result.extend_from_slice(item);
+                result.extend_from_slice(&item);
             }
         }
         RedisValue::Hash(hash) => {
             result.push(3); // Type: Hash
             let len = (hash.len() as u32).to_le_bytes();
             result.extend_from_slice(&len);
-            for (key, value) in hash {
+            for (key, value) in hash.iter() {
                 let key_len = (key.len() as u32).to_le_bytes();
                 result.extend_from_slice(&key_len);
                 result.extend_from_slice(key);
@@ -615,9 +838,9 @@ fn serialize_value(value: &RedisValue) -> Vec<u8> {
         }
         RedisValue::ZSet(zset) => {
             result.push(4); // Type: ZSet
-            let len = (zset.members.len() as u32).to_le_bytes();
+            let len = (zset.len() as u32).to_le_bytes();
             result.extend_from_slice(&len);
-            for (member, score) in &zset.members {
+            for (member, score) in zset.iter() {
                 let member_len = (member.len() as u32).to_le_bytes();
                 result.extend_from_slice(&member_len);
                 result.extend_from_slice(member);
@@ -633,8 +856,8 @@ fn serialize_value(value: &RedisValue) -> Vec<u8> {
     result
 }
 
-/// Deserialize bytes to a RedisValue
-fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
+/// Deserialize bytes to a RedisValue, building collections for `db_instance`
+fn deserialize_value(bytes: &[u8], db_instance: &DbInstance) -> Result<RedisValue, String> {
     if bytes.is_empty() {
         return Err("Empty serialized data".to_string());
     }
@@ -661,7 +884,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 return Err("Invalid list data".to_string());
             }
             let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-            let mut list = std::collections::LinkedList::new();
+            let mut list = db_instance.new_list();
             let mut pos = 4;
 
             for _ in 0..count {
@@ -673,8 +896,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 if pos + item_len > data.len() {
                     return Err("Invalid list item length".to_string());
                 }
-                let item = Bytes::copy_from_slice(&data[pos..pos + item_len]);
-                list.push_back(item);
+                list.push_back(&data[pos..pos + item_len]);
                 pos += item_len;
             }
 
@@ -686,7 +908,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 return Err("Invalid set data".to_string());
             }
             let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-            let mut set = std::collections::HashSet::new();
+            let mut set = db_instance.new_set();
             let mut pos = 4;
 
             for _ in 0..count {
@@ -711,7 +933,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 return Err("Invalid hash data".to_string());
             }
             let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-            let mut hash = std::collections::HashMap::new();
+            let mut hash = db_instance.new_hash();
             let mut pos = 4;
 
             for _ in 0..count {
@@ -750,7 +972,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 return Err("Invalid zset data".to_string());
             }
             let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-            let mut zset = crate::storage::types::ZSet::new();
+            let mut zset = db_instance.new_zset();
             let mut pos = 4;
 
             for _ in 0..count {
@@ -776,8 +998,7 @@ fn deserialize_value(bytes: &[u8]) -> Result<RedisValue, String> {
                 ]);
                 pos += 8;
 
-                zset.members.insert(member.clone(), score);
-                zset.scores.insert((ordered_float::OrderedFloat(score), member), ());
+                zset.insert(member, score);
             }
 
             Ok(RedisValue::ZSet(zset))
@@ -854,4 +1075,190 @@ mod tests {
         // Verify restored
         assert!(db_instance.exists("mykey"));
     }
+
+    #[tokio::test]
+    async fn test_restore_propagates_absolute_ttl() {
+        let db = Arc::new(Database::new(16));
+        let db_instance = db.get_db(0).unwrap();
+        db_instance.set("mykey".to_string(), RedisValue::String(Bytes::from("hello")));
+        let RespValue::BulkString(Some(payload)) = dump(&db, 0, vec![b"mykey".to_vec()]).await else {
+            panic!("Expected bulk string");
+        };
+        db_instance.delete("mykey");
+
+        // A relative TTL replays as the same absolute time
+        let args = vec![b"RESTORE".to_vec(), b"mykey".to_vec(), b"60000".to_vec(), payload.clone()];
+        let propagated = propagated_restore(&args);
+        assert_eq!(propagated.last().unwrap(), b"ABSTTL");
+        let at: u64 = String::from_utf8_lossy(&propagated[2]).parse().unwrap();
+        assert!(at > crate::storage::db::current_timestamp_ms() + 50_000);
+
+        let result = restore(&db, 0, propagated[1..].to_vec()).await;
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert!(db_instance.get_ttl_ms("mykey") > 50_000);
+
+        // An ABSTTL in the past restores nothing
+        let past = vec![b"other".to_vec(), b"1".to_vec(), payload, b"ABSTTL".to_vec()];
+        assert_eq!(restore(&db, 0, past).await, RespValue::SimpleString("OK".to_string()));
+        assert!(!db_instance.exists("other"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_arguments() {
+        let db = Arc::new(Database::new(16));
+        let args = |rest: &[&str]| rest.iter().map(|a| a.as_bytes().to_vec()).collect::<Vec<_>>();
+
+        // Nothing to send: no connection is attempted
+        let result = migrate(&db, 0, args(&["127.0.0.1", "1", "missing", "0", "100"])).await;
+        assert_eq!(result, RespValue::SimpleString("NOKEY".to_string()));
+        let result = migrate(&db, 0, args(&["127.0.0.1", "1", "", "0", "100", "KEYS", "a", "b"])).await;
+        assert_eq!(result, RespValue::SimpleString("NOKEY".to_string()));
+
+        let result = migrate(&db, 0, args(&["127.0.0.1", "1", "key", "0", "100", "KEYS", "a"])).await;
+        assert!(matches!(result, RespValue::Error(e) if e.contains("must be set to the empty string")));
+        let result = migrate(&db, 0, args(&["127.0.0.1", "port", "key", "0", "100"])).await;
+        assert!(matches!(result, RespValue::Error(e) if e.contains("not an integer")));
+        let result = migrate(&db, 0, args(&["127.0.0.1", "1", "key", "0", "100", "BOGUS"])).await;
+        assert_eq!(result, RespValue::Error("ERR syntax error".to_string()));
+    }
+
+    /// Follow SCAN cursors to the end, writing to the keyspace between calls
+    async fn scan_all(db: &Arc<Database>, options: &[&str], churn: bool) -> Vec<String> {
+        let mut seen = Vec::new();
+        let mut cursor = "0".to_string();
+        for call in 0.. {
+            let mut args = vec![cursor.clone().into_bytes()];
+            args.extend(options.iter().map(|o| o.as_bytes().to_vec()));
+            let reply = match scan(db, 0, args).await {
+                RespValue::Array(Some(reply)) => reply,
+                other => panic!("unexpected SCAN reply {:?}", other),
+            };
+            cursor = match &reply[0] {
+                RespValue::BulkString(Some(c)) => String::from_utf8(c.clone()).unwrap(),
+                other => panic!("unexpected cursor {:?}", other),
+            };
+            if let RespValue::Array(Some(keys)) = &reply[1] {
+                for key in keys {
+                    if let RespValue::BulkString(Some(key)) = key {
+                        seen.push(String::from_utf8(key.clone()).unwrap());
+                    }
+                }
+            }
+            if churn {
+                let db_instance = db.get_db(0).unwrap();
+                db_instance.set(format!("churn:{}", call), RedisValue::String(Bytes::from("v")));
+                db_instance.delete(&format!("churn:{}", call / 2));
+            }
+            if cursor == "0" {
+                break;
+            }
+        }
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_scan_cursor_survives_writes() {
+        let db = Arc::new(Database::new(16));
+        let db_instance = db.get_db(0).unwrap();
+        for i in 0..500 {
+            db_instance.set(format!("key:{}", i), RedisValue::String(Bytes::from("v")));
+        }
+        db_instance.set("list".to_string(), RedisValue::List(Default::default()));
+
+        // Keys present throughout come back exactly once despite the churn
+        let seen = scan_all(&db, &["COUNT", "20"], true).await;
+        for i in 0..500 {
+            let key = format!("key:{}", i);
+            assert_eq!(seen.iter().filter(|k| **k == key).count(), 1, "{}", key);
+        }
+
+        let lists = scan_all(&db, &["TYPE", "list"], false).await;
+        assert_eq!(lists, vec!["list"]);
+        let matched = scan_all(&db, &["MATCH", "key:4?", "COUNT", "1000"], false).await;
+        assert_eq!(matched.len(), 10);
+
+        let args = |parts: &[&str]| parts.iter().map(|a| a.as_bytes().to_vec()).collect::<Vec<_>>();
+        assert_eq!(
+            scan(&db, 0, args(&["0", "COUNT", "0"])).await,
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        assert_eq!(
+            scan(&db, 0, args(&["-1"])).await,
+            RespValue::Error("ERR invalid cursor".to_string())
+        );
+        assert_eq!(
+            scan(&db, 0, args(&["0", "NOVALUES"])).await,
+            RespValue::Error("ERR syntax error".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_object_encoding_converts_past_limits() {
+        use crate::storage::encoding::EncodingLimits;
+        use crate::storage::types::{HashValue, SetValue, ZSet};
+
+        let db = Arc::new(Database::new(16));
+        let db_instance = db.get_db(0).unwrap();
+        let encoding = |key: &str| {
+            let db = Arc::clone(&db);
+            let args = vec![b"ENCODING".to_vec(), key.as_bytes().to_vec()];
+            async move { object(&db, 0, args).await }
+        };
+        let simple = |s: &str| RespValue::SimpleString(s.to_string());
+
+        let mut hash = HashValue::new();
+        hash.insert(Bytes::from("f"), Bytes::from("v"));
+        db_instance.set("small-hash".to_string(), RedisValue::Hash(hash.clone()));
+        hash.insert(Bytes::from("long"), Bytes::from(vec![b'x'; 65]));
+        db_instance.set("long-hash".to_string(), RedisValue::Hash(hash.clone()));
+        assert_eq!(encoding("small-hash").await, simple("listpack"));
+        assert_eq!(encoding("long-hash").await, simple("hashtable"));
+        assert_eq!(hash.get(b"f"), Some(&b"v"[..]));
+        assert_eq!(hash.len(), 2);
+
+        // Only canonical integers go in the intset
+        let ints: SetValue = (0..512).map(|i| Bytes::from(i.to_string())).collect();
+        db_instance.set("ints".to_string(), RedisValue::Set(ints.clone()));
+        assert_eq!(encoding("ints").await, simple("intset"));
+        let mut padded = SetValue::new();
+        padded.insert(Bytes::from("01"));
+        padded.insert(Bytes::from("1"));
+        assert_eq!(padded.len(), 2);
+        assert_eq!(padded.encoding(), "listpack");
+        let mut many = ints;
+        many.insert(Bytes::from("512"));
+        assert_eq!(many.encoding(), "hashtable");
+        assert!(many.contains(b"511") && many.contains(b"512"));
+
+        let mut small = ZSet::new();
+        let mut large = ZSet::new();
+        for i in 0..129 {
+            let member = Bytes::from(format!("m{}", i));
+            let score = (i % 10) as f64;
+            if i < 128 {
+                small.insert(member.clone(), score);
+            }
+            large.insert(member, score);
+        }
+        assert_eq!(small.encoding(), "listpack");
+        assert_eq!(large.encoding(), "skiplist");
+        // Shrinking does not convert back, and both encodings order alike
+        large.remove(b"m128");
+        assert_eq!(large.encoding(), "skiplist");
+        assert!(small.iter().eq(large.iter()));
+        assert_eq!(small.rank(b"m15"), large.rank(b"m15"));
+        db_instance.set("zset".to_string(), RedisValue::ZSet(large));
+        assert_eq!(encoding("zset").await, simple("skiplist"));
+
+        // Another server's limits apply to the collections it creates
+        let limits = Arc::new(EncodingLimits::new());
+        assert!(limits.apply("hash-max-listpack-entries", "1"));
+        let other = Database::new(1).with_encoding(limits);
+        let mut hash = other.get_db(0).unwrap().new_hash();
+        hash.insert(Bytes::from("a"), Bytes::from("1"));
+        assert_eq!(hash.encoding(), "listpack");
+        hash.insert(Bytes::from("b"), Bytes::from("2"));
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(db_instance.new_hash().encoding(), "listpack");
+    }
 }
@@ -1,10 +1,14 @@
 // List command handlers
 
+use super::stream::wait_for_keys;
 use crate::protocol::RespValue;
+use crate::replication::write_gate;
 use crate::storage::db::Database;
+use crate::storage::db::DbInstance;
+use crate::storage::notify::EventClass;
+use crate::storage::quicklist::QuickList;
 use crate::storage::types::RedisValue;
 use bytes::Bytes;
-use std::collections::LinkedList;
 use std::sync::Arc;
 
 /// LPUSH key element [element ...]
@@ -23,23 +27,13 @@ pub async fn lpush(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut list = match db_instance.get(&key) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => LinkedList::new(),
+    // Each element goes to the front in turn, so the last one ends up first
+    let len = match push_list(&db_instance, &key, &args[1..], false) {
+        Ok(len) => len,
+        Err(e) => return RespValue::Error(e),
     };
-
-    // Push elements to front (in reverse order to maintain order)
-    for element in &args[1..] {
-        list.push_front(Bytes::from(element.clone()));
-    }
-
-    let len = list.len();
-    db_instance.set(key, RedisValue::List(list));
+    db_instance.notify(EventClass::LIST, "lpush", &key);
+    db.key_waiters().signal(db_index, &key);
     RespValue::Integer(len as i64)
 }
 
@@ -59,23 +53,12 @@ pub async fn rpush(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut list = match db_instance.get(&key) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => LinkedList::new(),
+    let len = match push_list(&db_instance, &key, &args[1..], true) {
+        Ok(len) => len,
+        Err(e) => return RespValue::Error(e),
     };
-
-    // Push elements to back
-    for element in &args[1..] {
-        list.push_back(Bytes::from(element.clone()));
-    }
-
-    let len = list.len();
-    db_instance.set(key, RedisValue::List(list));
+    db_instance.notify(EventClass::LIST, "rpush", &key);
+    db.key_waiters().signal(db_index, &key);
     RespValue::Integer(len as i64)
 }
 
@@ -113,50 +96,17 @@ pub async fn lpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut list = match db_instance.get(&key) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => return RespValue::BulkString(None),
+    let popped = match pop_elements(&db_instance, &key, count, false) {
+        Ok(popped) => popped,
+        Err(e) => return RespValue::Error(e),
     };
 
-    if list.is_empty() {
-        return RespValue::BulkString(None);
-    }
-
-    if count == 1 {
-        // Single pop
-        if let Some(value) = list.pop_front() {
-            if list.is_empty() {
-                db_instance.delete(&key);
-            } else {
-                db_instance.set(key, RedisValue::List(list));
-            }
-            RespValue::BulkString(Some(value.to_vec()))
-        } else {
-            RespValue::BulkString(None)
-        }
-    } else {
-        // Multiple pops
-        let mut results = Vec::new();
-        for _ in 0..count {
-            if let Some(value) = list.pop_front() {
-                results.push(RespValue::BulkString(Some(value.to_vec())));
-            } else {
-                break;
-            }
-        }
-
-        if list.is_empty() {
-            db_instance.delete(&key);
-        } else {
-            db_instance.set(key, RedisValue::List(list));
-        }
-
-        RespValue::Array(Some(results))
+    match popped.as_slice() {
+        [] => RespValue::BulkString(None),
+        [value] if count == 1 => RespValue::BulkString(Some(value.to_vec())),
+        _ => RespValue::Array(Some(
+            popped.iter().map(|value| RespValue::BulkString(Some(value.to_vec()))).collect(),
+        )),
     }
 }
 
@@ -194,50 +144,17 @@ pub async fn rpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut list = match db_instance.get(&key) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => return RespValue::BulkString(None),
+    let popped = match pop_elements(&db_instance, &key, count, true) {
+        Ok(popped) => popped,
+        Err(e) => return RespValue::Error(e),
     };
 
-    if list.is_empty() {
-        return RespValue::BulkString(None);
-    }
-
-    if count == 1 {
-        // Single pop
-        if let Some(value) = list.pop_back() {
-            if list.is_empty() {
-                db_instance.delete(&key);
-            } else {
-                db_instance.set(key, RedisValue::List(list));
-            }
-            RespValue::BulkString(Some(value.to_vec()))
-        } else {
-            RespValue::BulkString(None)
-        }
-    } else {
-        // Multiple pops
-        let mut results = Vec::new();
-        for _ in 0..count {
-            if let Some(value) = list.pop_back() {
-                results.push(RespValue::BulkString(Some(value.to_vec())));
-            } else {
-                break;
-            }
-        }
-
-        if list.is_empty() {
-            db_instance.delete(&key);
-        } else {
-            db_instance.set(key, RedisValue::List(list));
-        }
-
-        RespValue::Array(Some(results))
+    match popped.as_slice() {
+        [] => RespValue::BulkString(None),
+        [value] if count == 1 => RespValue::BulkString(Some(value.to_vec())),
+        _ => RespValue::Array(Some(
+            popped.iter().map(|value| RespValue::BulkString(Some(value.to_vec()))).collect(),
+        )),
     }
 }
 
@@ -257,11 +174,9 @@ pub async fn llen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::List(list)) => RespValue::Integer(list.len() as i64),
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+    match read_list(&db_instance, key, |list| list.len()) {
+        Some(Ok(len)) => RespValue::Integer(len as i64),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
@@ -302,32 +217,28 @@ pub async fn lrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::List(list)) => {
-            let len = list.len() as i64;
-            if len == 0 {
-                return RespValue::Array(Some(vec![]));
-            }
+    let range = read_list(&db_instance, key, |list| {
+        let len = list.len() as i64;
+        if len == 0 {
+            return Vec::new();
+        }
 
-            // Normalize indices
-            let start_idx = normalize_index(start, len);
-            let stop_idx = normalize_index(stop, len);
+        // Normalize indices
+        let start_idx = normalize_index(start, len);
+        let stop_idx = normalize_index(stop, len);
 
-            if start_idx > stop_idx || start_idx >= len {
-                return RespValue::Array(Some(vec![]));
-            }
+        if start_idx > stop_idx || start_idx >= len {
+            return Vec::new();
+        }
 
-            let items: Vec<_> = list.iter().collect();
-            let result: Vec<RespValue> = items[start_idx as usize..=(stop_idx as usize).min(len as usize - 1)]
-                .iter()
-                .map(|&bytes| RespValue::BulkString(Some(bytes.to_vec())))
-                .collect();
+        list.range(start_idx as usize, stop_idx as usize + 1)
+            .map(|bytes| RespValue::BulkString(Some(bytes.into_owned())))
+            .collect()
+    });
 
-            RespValue::Array(Some(result))
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+    match range {
+        Some(Ok(result)) => RespValue::Array(Some(result)),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Array(Some(vec![])),
     }
 }
@@ -358,24 +269,19 @@ pub async fn lindex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::List(list)) => {
-            let len = list.len() as i64;
-            let idx = normalize_index(index, len);
-
-            if idx < 0 || idx >= len {
-                return RespValue::BulkString(None);
-            }
+    let value = read_list(&db_instance, key, |list| {
+        let len = list.len() as i64;
+        let idx = normalize_index(index, len);
 
-            if let Some(value) = list.iter().nth(idx as usize) {
-                RespValue::BulkString(Some(value.to_vec()))
-            } else {
-                RespValue::BulkString(None)
-            }
+        if idx < 0 || idx >= len {
+            return None;
         }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+        list.get(idx as usize).map(|value| value.into_owned())
+    });
+
+    match value {
+        Some(Ok(value)) => RespValue::BulkString(value),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::BulkString(None),
     }
 }
@@ -401,35 +307,24 @@ pub async fn lset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
     };
 
-    let element = Bytes::from(args[2].clone());
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(&key) {
-        Some(RedisValue::List(list)) => {
-            let len = list.len() as i64;
-            let idx = normalize_index(index, len);
-
-            if idx < 0 || idx >= len {
-                return RespValue::Error("ERR index out of range".to_string());
-            }
-
-            // Convert to vec for indexed access
-            let mut items: Vec<_> = list.iter().cloned().collect();
-            items[idx as usize] = element;
-
-            // Rebuild list
-            let new_list: LinkedList<_> = items.into_iter().collect();
-            db_instance.set(key, RedisValue::List(new_list));
+    let updated = with_list_mut(&db_instance, &key, |list| {
+        let len = list.len() as i64;
+        let idx = normalize_index(index, len);
+        idx >= 0 && idx < len && list.set(idx as usize, &args[2])
+    });
 
+    match updated {
+        Some(Ok(true)) => {
+            db_instance.notify(EventClass::LIST, "lset", &key);
             RespValue::SimpleString("OK".to_string())
         }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+        Some(Ok(false)) => RespValue::Error("ERR index out of range".to_string()),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Error("ERR no such key".to_string()),
     }
 }
@@ -470,30 +365,26 @@ pub async fn ltrim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(&key) {
-        Some(RedisValue::List(list)) => {
-            let len = list.len() as i64;
-            let start_idx = normalize_index(start, len);
-            let stop_idx = normalize_index(stop, len);
+    let trimmed = with_list_mut(&db_instance, &key, |list| {
+        let len = list.len() as i64;
+        let start_idx = normalize_index(start, len);
+        let stop_idx = normalize_index(stop, len);
 
-            if start_idx > stop_idx || start_idx >= len {
-                // Remove all elements
-                db_instance.delete(&key);
-            } else {
-                let items: Vec<_> = list.iter().collect();
-                let trimmed: LinkedList<_> = items[start_idx as usize..=(stop_idx as usize).min(len as usize - 1)]
-                    .iter()
-                    .map(|&b| b.clone())
-                    .collect();
-
-                db_instance.set(key, RedisValue::List(trimmed));
-            }
+        if start_idx > stop_idx || start_idx >= len {
+            // Remove all elements
+            list.remove_range(0, len as usize);
+        } else {
+            list.remove_range(stop_idx as usize + 1, len as usize);
+            list.remove_range(0, start_idx as usize);
+        }
+    });
 
+    match trimmed {
+        Some(Ok(())) => {
+            finish_removal(&db_instance, &key, "ltrim");
             RespValue::SimpleString("OK".to_string())
         }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::SimpleString("OK".to_string()),
     }
 }
@@ -517,61 +408,47 @@ pub async fn lrem(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
     };
 
-    let element = Bytes::from(args[2].clone());
+    let element = &args[2];
 
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(&key) {
-        Some(RedisValue::List(list)) => {
-            let mut removed = 0;
-            let mut new_list = LinkedList::new();
-
-            if count == 0 {
-                // Remove all occurrences
-                for item in list.iter() {
-                    if item != &element {
-                        new_list.push_back(item.clone());
-                    } else {
-                        removed += 1;
-                    }
-                }
-            } else if count > 0 {
-                // Remove first count occurrences
-                let mut to_remove = count;
-                for item in list.iter() {
-                    if to_remove > 0 && item == &element {
-                        to_remove -= 1;
-                        removed += 1;
-                    } else {
-                        new_list.push_back(item.clone());
-                    }
-                }
+    let removed = with_list_mut(&db_instance, &key, |list| {
+        // 0 removes every occurrence, a negative count scans from the tail
+        let mut to_remove = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+        let mut keep = |item: &std::borrow::Cow<[u8]>| {
+            if to_remove > 0 && item.as_ref() == element.as_slice() {
+                to_remove -= 1;
+                false
             } else {
-                // Remove last |count| occurrences (scan from end)
-                let items: Vec<_> = list.iter().cloned().collect();
-                let mut to_remove = count.abs();
-                for item in items.iter().rev() {
-                    if to_remove > 0 && item == &element {
-                        to_remove -= 1;
-                        removed += 1;
-                    } else {
-                        new_list.push_front(item.clone());
-                    }
-                }
+                true
             }
+        };
+        let kept: Vec<Vec<u8>> = if count >= 0 {
+            list.iter().filter(|item| keep(item)).map(|item| item.into_owned()).collect()
+        } else {
+            let mut kept: Vec<_> = list.iter().rev().filter(|item| keep(item)).map(|item| item.into_owned()).collect();
+            kept.reverse();
+            kept
+        };
+        let removed = list.len() - kept.len();
+        if removed > 0 {
+            list.remove_range(0, list.len());
+            list.extend(kept);
+        }
+        removed
+    });
 
-            if new_list.is_empty() {
-                db_instance.delete(&key);
-            } else {
-                db_instance.set(key, RedisValue::List(new_list));
+    match removed {
+        Some(Ok(removed)) => {
+            if removed > 0 {
+                finish_removal(&db_instance, &key, "lrem");
             }
-
-            RespValue::Integer(removed)
+            RespValue::Integer(removed as i64)
         }
-        Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
@@ -592,22 +469,20 @@ pub async fn lpushx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Only push if key exists
-    if !db_instance.exists(&key) {
-        return RespValue::Integer(0);
-    }
+    let len = with_list_mut(&db_instance, &key, |list| {
+        for value in &args[1..] {
+            list.push_front(value);
+        }
+        list.len()
+    });
 
-    match db_instance.get(&key) {
-        Some(RedisValue::List(mut list)) => {
-            for i in 1..args.len() {
-                let value = Bytes::from(args[i].clone());
-                list.push_front(value);
-            }
-            let len = list.len();
-            db_instance.set(key, RedisValue::List(list));
+    // Only pushed if the key exists
+    match len {
+        Some(Ok(len)) => {
+            db_instance.notify(EventClass::LIST, "lpush", &key);
             RespValue::Integer(len as i64)
         }
-        Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
@@ -628,22 +503,20 @@ pub async fn rpushx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Only push if key exists
-    if !db_instance.exists(&key) {
-        return RespValue::Integer(0);
-    }
+    let len = with_list_mut(&db_instance, &key, |list| {
+        for value in &args[1..] {
+            list.push_back(value);
+        }
+        list.len()
+    });
 
-    match db_instance.get(&key) {
-        Some(RedisValue::List(mut list)) => {
-            for i in 1..args.len() {
-                let value = Bytes::from(args[i].clone());
-                list.push_back(value);
-            }
-            let len = list.len();
-            db_instance.set(key, RedisValue::List(list));
+    // Only pushed if the key exists
+    match len {
+        Some(Ok(len)) => {
+            db_instance.notify(EventClass::LIST, "rpush", &key);
             RespValue::Integer(len as i64)
         }
-        Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
@@ -669,39 +542,137 @@ pub async fn rpoplpush(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Get element from source
-    let element = match db_instance.get(&source) {
-        Some(RedisValue::List(mut list)) => {
-            if list.is_empty() {
-                return RespValue::BulkString(None);
+    match move_element(&db_instance, &source, &destination, true, false) {
+        Ok(Some(element)) => {
+            db.key_waiters().signal(db_index, &destination);
+            RespValue::BulkString(Some(element.to_vec()))
+        }
+        Ok(None) => RespValue::BulkString(None),
+        Err(e) => RespValue::Error(e),
+    }
+}
+
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// Run `f` on the list at `key` without cloning it. None if the key does
+/// not exist, a WRONGTYPE error if it is not a list
+fn read_list<R>(db_instance: &DbInstance, key: &str, f: impl FnOnce(&QuickList) -> R) -> Option<Result<R, String>> {
+    db_instance.lookup_read_with(key, |value| match value {
+        RedisValue::List(list) => Ok(f(list)),
+        _ => Err(WRONGTYPE.to_string()),
+    })
+}
+
+/// `read_list` for writes: the list is changed in place while its shard is
+/// locked, so concurrent writers of the key cannot lose each other's edits
+fn with_list_mut<R>(
+    db_instance: &DbInstance,
+    key: &str,
+    f: impl FnOnce(&mut QuickList) -> R,
+) -> Option<Result<R, String>> {
+    db_instance.with_value_mut(key, |value| match value {
+        RedisValue::List(list) => Ok(f(list)),
+        _ => Err(WRONGTYPE.to_string()),
+    })
+}
+
+/// Push `elements` one by one onto an end of the list at `key`, creating
+/// it if needed, and return the new length
+fn push_list(db_instance: &DbInstance, key: &str, elements: &[impl AsRef<[u8]>], right: bool) -> Result<usize, String> {
+    db_instance.with_value_or_insert(
+        key,
+        || RedisValue::List(db_instance.new_list()),
+        |value| match value {
+            RedisValue::List(list) => {
+                for element in elements {
+                    if right {
+                        list.push_back(element.as_ref());
+                    } else {
+                        list.push_front(element.as_ref());
+                    }
+                }
+                Ok(list.len())
             }
-            let elem = list.pop_back().unwrap();
-            if list.is_empty() {
-                db_instance.delete(&source);
+            _ => Err(WRONGTYPE.to_string()),
+        },
+    )
+}
+
+/// Pop up to `count` elements from an end of the list at `key`. Costs
+/// O(count), whatever the list length
+fn pop_elements(db_instance: &DbInstance, key: &str, count: usize, right: bool) -> Result<Vec<Bytes>, String> {
+    let popped = with_list_mut(db_instance, key, |list| {
+        (0..count)
+            .map_while(|_| if right { list.pop_back() } else { list.pop_front() })
+            .collect::<Vec<_>>()
+    });
+    let popped = match popped {
+        Some(popped) => popped?,
+        None => return Ok(Vec::new()),
+    };
+    if !popped.is_empty() {
+        finish_removal(db_instance, key, if right { "rpop" } else { "lpop" });
+    }
+    Ok(popped)
+}
+
+/// Move an element from an end of `source` to an end of `dest`, as LMOVE
+/// does. The destination is type checked before anything is popped, so a
+/// WRONGTYPE reply never drops the element. Moving within one list rotates
+/// it in place, so the key is never deleted and recreated on the way
+fn move_element(
+    db_instance: &DbInstance,
+    source: &str,
+    dest: &str,
+    from_right: bool,
+    to_right: bool,
+) -> Result<Option<Bytes>, String> {
+    let is_list = |value: &RedisValue| matches!(value, RedisValue::List(_));
+    match db_instance.with_value(source, is_list) {
+        None => return Ok(None),
+        Some(false) => return Err(WRONGTYPE.to_string()),
+        Some(true) => {}
+    }
+    if db_instance.with_value(dest, is_list) == Some(false) {
+        return Err(WRONGTYPE.to_string());
+    }
+
+    let pop_event = if from_right { "rpop" } else { "lpop" };
+    let push_event = if to_right { "rpush" } else { "lpush" };
+    if source == dest {
+        let element = with_list_mut(db_instance, source, |list| {
+            let element = if from_right { list.pop_back() } else { list.pop_front() }?;
+            if to_right {
+                list.push_back(&element);
             } else {
-                db_instance.set(source.clone(), RedisValue::List(list));
+                list.push_front(&element);
             }
-            elem
-        }
-        Some(_) => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
-        None => return RespValue::BulkString(None),
+            Some(element)
+        });
+        let Some(element) = element.transpose()?.flatten() else {
+            return Ok(None);
+        };
+        db_instance.notify(EventClass::LIST, pop_event, source);
+        db_instance.notify(EventClass::LIST, push_event, dest);
+        return Ok(Some(element));
+    }
+
+    let Some(element) = pop_elements(db_instance, source, 1, from_right)?.pop() else {
+        return Ok(None);
     };
+    push_list(db_instance, dest, std::slice::from_ref(&element), to_right)?;
+    db_instance.notify(EventClass::LIST, push_event, dest);
+    Ok(Some(element))
+}
 
-    // Push to destination
-    match db_instance.get(&destination) {
-        Some(RedisValue::List(mut list)) => {
-            list.push_front(element.clone());
-            db_instance.set(destination, RedisValue::List(list));
-        }
-        Some(_) => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
-        None => {
-            let mut new_list = LinkedList::new();
-            new_list.push_back(element.clone());
-            db_instance.set(destination, RedisValue::List(new_list));
-        }
+/// Publish `event` after elements were removed from the list at `key` in
+/// place, deleting the key once nothing is left
+fn finish_removal(db_instance: &DbInstance, key: &str, event: &str) {
+    let emptied = db_instance.delete_if(key, |value| matches!(value, RedisValue::List(list) if list.is_empty()));
+    db_instance.notify(EventClass::LIST, event, key);
+    if emptied {
+        db_instance.notify(EventClass::GENERIC, "del", key);
     }
-
-    RespValue::BulkString(Some(element.to_vec()))
 }
 
 // Helper function to normalize negative indices
@@ -750,21 +721,14 @@ pub async fn blpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 None => return RespValue::Error("ERR invalid database".to_string()),
             };
 
-            // Try to get and pop from this key
-            if let Some(RedisValue::List(mut list)) = db_instance.get(&key) {
-                if let Some(element) = list.pop_front() {
-                    // Update the list (or delete if empty)
-                    if list.is_empty() {
-                        db_instance.delete(&key);
-                    } else {
-                        db_instance.set(key.clone(), RedisValue::List(list));
-                    }
-                    // Return key and value as array
-                    return RespValue::Array(Some(vec![
-                        RespValue::BulkString(Some(key.into_bytes())),
-                        RespValue::BulkString(Some(element.to_vec())),
-                    ]));
-                }
+            // Keys holding another type are skipped
+            let popped = pop_elements(&db_instance, &key, 1, false).unwrap_or_default();
+            if let Some(element) = popped.into_iter().next() {
+                // Return key and value as array
+                return RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(key.into_bytes())),
+                    RespValue::BulkString(Some(element.to_vec())),
+                ]));
             }
         }
 
@@ -773,8 +737,8 @@ pub async fn blpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
             return RespValue::Null; // Timeout - return null
         }
 
-        // Sleep before next poll
-        tokio::time::sleep(poll_interval).await;
+        // Sleep before next poll, without holding up a resync
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
     }
 }
 
@@ -815,21 +779,14 @@ pub async fn brpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 None => return RespValue::Error("ERR invalid database".to_string()),
             };
 
-            // Try to get and pop from this key
-            if let Some(RedisValue::List(mut list)) = db_instance.get(&key) {
-                if let Some(element) = list.pop_back() {
-                    // Update the list (or delete if empty)
-                    if list.is_empty() {
-                        db_instance.delete(&key);
-                    } else {
-                        db_instance.set(key.clone(), RedisValue::List(list));
-                    }
-                    // Return key and value as array
-                    return RespValue::Array(Some(vec![
-                        RespValue::BulkString(Some(key.into_bytes())),
-                        RespValue::BulkString(Some(element.to_vec())),
-                    ]));
-                }
+            // Keys holding another type are skipped
+            let popped = pop_elements(&db_instance, &key, 1, true).unwrap_or_default();
+            if let Some(element) = popped.into_iter().next() {
+                // Return key and value as array
+                return RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(key.into_bytes())),
+                    RespValue::BulkString(Some(element.to_vec())),
+                ]));
             }
         }
 
@@ -838,8 +795,8 @@ pub async fn brpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
             return RespValue::Null;
         }
 
-        // Sleep before next poll
-        tokio::time::sleep(poll_interval).await;
+        // Sleep before next poll, without holding up a resync
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
     }
 }
 
@@ -895,47 +852,13 @@ pub async fn blmove(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             None => return RespValue::Error("ERR invalid database".to_string()),
         };
 
-        // Try to pop from source
-        if let Some(RedisValue::List(mut source_list)) = db_instance.get(&source) {
-            if !source_list.is_empty() {
-                // Pop element from source
-                let element = if wherefrom == "LEFT" {
-                    source_list.pop_front()
-                } else {
-                    source_list.pop_back()
-                };
-
-                if let Some(elem) = element {
-                    // Update source list
-                    if source_list.is_empty() {
-                        db_instance.delete(&source);
-                    } else {
-                        db_instance.set(source.clone(), RedisValue::List(source_list));
-                    }
-
-                    // Push to destination
-                    let mut dest_list = match db_instance.get(&dest) {
-                        Some(RedisValue::List(l)) => l,
-                        Some(_) => {
-                            return RespValue::Error(
-                                "WRONGTYPE Operation against a key holding the wrong kind of value"
-                                    .to_string(),
-                            )
-                        }
-                        None => LinkedList::new(),
-                    };
-
-                    if whereto == "LEFT" {
-                        dest_list.push_front(elem.clone());
-                    } else {
-                        dest_list.push_back(elem.clone());
-                    }
-
-                    db_instance.set(dest, RedisValue::List(dest_list));
-
-                    return RespValue::BulkString(Some(elem.to_vec()));
-                }
+        match move_element(&db_instance, &source, &dest, wherefrom == "RIGHT", whereto == "RIGHT") {
+            Ok(Some(element)) => {
+                db.key_waiters().signal(db_index, &dest);
+                return RespValue::BulkString(Some(element.to_vec()));
             }
+            Ok(None) => {}
+            Err(e) => return RespValue::Error(e),
         }
 
         // Check timeout
@@ -943,15 +866,17 @@ pub async fn blmove(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             return RespValue::Null;
         }
 
-        // Sleep before next poll
-        tokio::time::sleep(poll_interval).await;
+        // Sleep before next poll, without holding up a resync
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
     }
 }
 
 /// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
-/// Find position of element in list
+/// Find positions of element in list. A negative RANK scans from the tail;
+/// MAXLEN bounds the scan, so the cost is O(min(MAXLEN, position of the
+/// match)) rather than the list length
 pub async fn lpos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 2 {
+    if args.len() < 2 || !args.len().is_multiple_of(2) {
         return RespValue::Error("ERR wrong number of arguments for 'lpos' command".to_string());
     }
 
@@ -960,26 +885,30 @@ pub async fn lpos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let element = Bytes::from(args[1].clone());
+    let element = &args[1];
 
-    // Parse options (simplified - basic implementation)
+    let mut rank: i64 = 1;
     let mut count: Option<usize> = None;
-    let mut i = 2;
-    while i < args.len() {
-        if let Ok(s) = std::str::from_utf8(&args[i]) {
-            match s.to_uppercase().as_str() {
-                "COUNT" => {
-                    i += 1;
-                    if i < args.len() {
-                        if let Ok(num_str) = std::str::from_utf8(&args[i]) {
-                            count = num_str.parse::<usize>().ok();
-                        }
-                    }
-                }
-                _ => {}
+    let mut maxlen = 0usize;
+    for option in args[2..].chunks(2) {
+        let value = match parse_integer(&option[1]) {
+            Ok(v) => v,
+            Err(e) => return RespValue::Error(e),
+        };
+        match String::from_utf8_lossy(&option[0]).to_uppercase().as_str() {
+            "RANK" if value == 0 || value == i64::MIN => {
+                return RespValue::Error(
+                    "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list"
+                        .to_string(),
+                )
             }
+            "RANK" => rank = value,
+            "COUNT" if value < 0 => return RespValue::Error("ERR COUNT can't be negative".to_string()),
+            "COUNT" => count = Some(value as usize),
+            "MAXLEN" if value < 0 => return RespValue::Error("ERR MAXLEN can't be negative".to_string()),
+            "MAXLEN" => maxlen = value as usize,
+            _ => return RespValue::Error("ERR syntax error".to_string()),
         }
-        i += 1;
     }
 
     let db_instance = match db.get_db(db_index) {
@@ -987,44 +916,250 @@ pub async fn lpos(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let list = match db_instance.get(key) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    // COUNT 0 means every match
+    let wanted = match count {
+        Some(0) => usize::MAX,
+        Some(n) => n,
+        None => 1,
+    };
+    let skip = (rank.unsigned_abs() - 1) as usize;
+    let is_match = |item: &std::borrow::Cow<[u8]>| item.as_ref() == element.as_slice();
+    let positions = read_list(&db_instance, key, |list| {
+        let scanned = if maxlen == 0 { list.len() } else { maxlen.min(list.len()) };
+        let len = list.len();
+        if rank > 0 {
+            list.iter()
+                .take(scanned)
+                .enumerate()
+                .filter(|(_, item)| is_match(item))
+                .map(|(i, _)| i)
+                .skip(skip)
+                .take(wanted)
+                .collect::<Vec<usize>>()
+        } else {
+            list.iter()
+                .rev()
+                .take(scanned)
+                .enumerate()
+                .filter(|(_, item)| is_match(item))
+                .map(|(i, _)| len - 1 - i)
+                .skip(skip)
+                .take(wanted)
+                .collect()
         }
+    });
+    let positions = match positions {
+        Some(Ok(positions)) => positions,
+        Some(Err(e)) => return RespValue::Error(e),
+        None if count.is_some() => return RespValue::Array(Some(vec![])),
         None => return RespValue::BulkString(None),
     };
 
-    // Find positions of element
-    let mut positions = Vec::new();
-    for (idx, item) in list.iter().enumerate() {
-        if item == &element {
-            positions.push(idx as i64);
-            if let Some(max_count) = count {
-                if positions.len() >= max_count {
-                    break;
-                }
-            } else if positions.len() == 1 {
-                // If no COUNT, return first match only
-                return RespValue::Integer(positions[0]);
+    if count.is_some() {
+        RespValue::Array(Some(positions.into_iter().map(|p| RespValue::Integer(p as i64)).collect()))
+    } else {
+        match positions.first() {
+            Some(&p) => RespValue::Integer(p as i64),
+            None => RespValue::BulkString(None),
+        }
+    }
+}
+
+/// LINSERT key BEFORE|AFTER pivot element
+/// Insert element next to the first occurrence of pivot. Finding the pivot
+/// is O(position); the insertion itself only rewrites one node
+pub async fn linsert(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() != 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'linsert' command".to_string());
+    }
+
+    let key = match std::str::from_utf8(&args[0]) {
+        Ok(s) => s.to_string(),
+        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
+    };
+
+    let after = match String::from_utf8_lossy(&args[1]).to_uppercase().as_str() {
+        "BEFORE" => false,
+        "AFTER" => true,
+        _ => return RespValue::Error("ERR syntax error".to_string()),
+    };
+    let pivot = &args[2];
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let len = with_list_mut(&db_instance, &key, |list| {
+        let position = list.iter().position(|item| item.as_ref() == pivot.as_slice())?;
+        list.insert(position + after as usize, &args[3]);
+        Some(list.len())
+    });
+
+    match len {
+        Some(Ok(Some(len))) => {
+            db_instance.notify(EventClass::LIST, "linsert", &key);
+            RespValue::Integer(len as i64)
+        }
+        Some(Ok(None)) => RespValue::Integer(-1),
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
+    }
+}
+
+/// LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count]
+/// Pop up to count elements from the first non-empty list
+pub async fn lmpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 3 {
+        return RespValue::Error("ERR wrong number of arguments for 'lmpop' command".to_string());
+    }
+
+    let query = match MultiPop::parse(&args) {
+        Ok(q) => q,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    match query.pop(&db_instance) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => RespValue::Array(None),
+        Err(e) => RespValue::Error(e),
+    }
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]
+/// Blocking version of LMPOP; a timeout of 0 waits indefinitely. A blocked
+/// client sleeps until a push or move to one of its keys wakes it
+pub async fn blmpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'blmpop' command".to_string());
+    }
+
+    let timeout_secs = match std::str::from_utf8(&args[0]).ok().and_then(|s| s.parse::<f64>().ok()) {
+        Some(t) if t < 0.0 => return RespValue::Error("ERR timeout is negative".to_string()),
+        Some(t) if t.is_finite() => t,
+        _ => return RespValue::Error("ERR timeout is not a float or out of range".to_string()),
+    };
+
+    let query = match MultiPop::parse(&args[1..]) {
+        Ok(q) => q,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let deadline = (timeout_secs > 0.0)
+        .then(|| std::time::Instant::now() + std::time::Duration::from_secs_f64(timeout_secs));
+
+    loop {
+        // Register before popping, so a push in between still wakes us
+        let wait = db.key_waiters().register(db_index, &query.keys);
+        match query.pop(&db_instance) {
+            Ok(Some(reply)) => return reply,
+            Ok(None) => {}
+            Err(e) => return RespValue::Error(e),
+        }
+
+        if !wait_for_keys(&wait, deadline).await {
+            return RespValue::Array(None);
+        }
+    }
+}
+
+/// `numkeys key [key ...] LEFT|RIGHT [COUNT count]` of LMPOP and BLMPOP
+#[derive(Debug)]
+struct MultiPop {
+    keys: Vec<String>,
+    right: bool,
+    count: usize,
+}
+
+impl MultiPop {
+    fn parse(args: &[Vec<u8>]) -> Result<Self, String> {
+        let keys = super::zset::parse_numkeys(args)?;
+        let Some((side, options)) = args[1 + keys.len()..].split_first() else {
+            return Err("ERR syntax error".to_string());
+        };
+        let right = match String::from_utf8_lossy(side).to_uppercase().as_str() {
+            "LEFT" => false,
+            "RIGHT" => true,
+            _ => return Err("ERR syntax error".to_string()),
+        };
+        let count = match options {
+            [] => 1,
+            [option, value] if option.eq_ignore_ascii_case(b"COUNT") => match parse_integer(value)? {
+                n if n > 0 => n as usize,
+                _ => return Err("ERR count should be greater than 0".to_string()),
+            },
+            _ => return Err("ERR syntax error".to_string()),
+        };
+        Ok(MultiPop { keys, right, count })
+    }
+
+    /// Pop from the first non-empty key, replying `[key, [element, ...]]`;
+    /// None when every key is empty. Costs O(count), whatever the list length
+    fn pop(&self, db_instance: &DbInstance) -> Result<Option<RespValue>, String> {
+        for key in &self.keys {
+            let popped = pop_elements(db_instance, key, self.count, self.right)?;
+            if popped.is_empty() {
+                continue;
             }
+            let popped = popped.iter().map(|element| RespValue::BulkString(Some(element.to_vec()))).collect();
+
+            return Ok(Some(RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(key.as_bytes().to_vec())),
+                RespValue::Array(Some(popped)),
+            ]))));
         }
+        Ok(None)
     }
+}
 
-    if count.is_some() {
-        // Return array of positions
-        let results: Vec<RespValue> = positions
-            .into_iter()
-            .map(RespValue::Integer)
-            .collect();
-        RespValue::Array(Some(results))
-    } else if positions.is_empty() {
-        RespValue::BulkString(None)
+/// The commands to append to the AOF and send to replicas for a successful
+/// list write. LMPOP and BLMPOP become the LPOP/RPOP of what they actually
+/// popped, so a replay never blocks or picks another key.
+pub fn propagated_commands(args: &[Vec<u8>], response: &RespValue) -> Vec<Vec<Vec<u8>>> {
+    let numkeys_at = if args[0].eq_ignore_ascii_case(b"LMPOP") {
+        1
+    } else if args[0].eq_ignore_ascii_case(b"BLMPOP") {
+        2
     } else {
-        RespValue::Integer(positions[0])
-    }
+        return vec![args.to_vec()];
+    };
+
+    let RespValue::Array(Some(reply)) = response else {
+        return Vec::new();
+    };
+    let (Some(RespValue::BulkString(Some(key))), Some(RespValue::Array(Some(popped)))) =
+        (reply.first(), reply.get(1))
+    else {
+        return Vec::new();
+    };
+
+    let numkeys = args
+        .get(numkeys_at)
+        .and_then(|n| std::str::from_utf8(n).ok())
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0);
+    let pop = match args.get(numkeys_at + 1 + numkeys) {
+        Some(side) if side.eq_ignore_ascii_case(b"RIGHT") => b"RPOP".to_vec(),
+        _ => b"LPOP".to_vec(),
+    };
+    vec![vec![pop, key.clone(), popped.len().to_string().into_bytes()]]
+}
+
+fn parse_integer(bytes: &[u8]) -> Result<i64, String> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".to_string())
 }
 
 /// LMOVE source destination <LEFT|RIGHT> <LEFT|RIGHT>
@@ -1067,61 +1202,14 @@ pub async fn lmove(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Get source list
-    let mut source_list = match db_instance.get(&source) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    match move_element(&db_instance, &source, &dest, wherefrom == "RIGHT", whereto == "RIGHT") {
+        Ok(Some(element)) => {
+            db.key_waiters().signal(db_index, &dest);
+            RespValue::BulkString(Some(element.to_vec()))
         }
-        None => return RespValue::BulkString(None),
-    };
-
-    if source_list.is_empty() {
-        return RespValue::BulkString(None);
+        Ok(None) => RespValue::BulkString(None),
+        Err(e) => RespValue::Error(e),
     }
-
-    // Pop from source
-    let element = if wherefrom == "LEFT" {
-        source_list.pop_front()
-    } else {
-        source_list.pop_back()
-    };
-
-    let element = match element {
-        Some(e) => e,
-        None => return RespValue::BulkString(None),
-    };
-
-    // Update or delete source list
-    if source_list.is_empty() {
-        db_instance.delete(&source);
-    } else {
-        db_instance.set(source.clone(), RedisValue::List(source_list));
-    }
-
-    // Get or create destination list
-    let mut dest_list = match db_instance.get(&dest) {
-        Some(RedisValue::List(l)) => l,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => std::collections::LinkedList::new(),
-    };
-
-    // Push to destination
-    if whereto == "LEFT" {
-        dest_list.push_front(element.clone());
-    } else {
-        dest_list.push_back(element.clone());
-    }
-
-    db_instance.set(dest, RedisValue::List(dest_list));
-
-    RespValue::BulkString(Some(element.to_vec()))
 }
 
 #[cfg(test)]
@@ -1165,6 +1253,176 @@ mod tests {
         assert_eq!(result, RespValue::Integer(1));
     }
 
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    fn bulk(s: &str) -> RespValue {
+        RespValue::BulkString(Some(s.as_bytes().to_vec()))
+    }
+
+    #[tokio::test]
+    async fn test_linsert_and_lpos() {
+        let db = Arc::new(Database::new(16));
+        rpush(&db, 0, args(&["l", "a", "b", "c", "b", "d", "b"])).await;
+
+        assert_eq!(linsert(&db, 0, args(&["l", "BEFORE", "c", "x"])).await, RespValue::Integer(7));
+        assert_eq!(linsert(&db, 0, args(&["l", "after", "d", "y"])).await, RespValue::Integer(8));
+        assert_eq!(linsert(&db, 0, args(&["l", "AFTER", "zz", "y"])).await, RespValue::Integer(-1));
+        assert_eq!(linsert(&db, 0, args(&["none", "AFTER", "a", "y"])).await, RespValue::Integer(0));
+        assert_eq!(
+            linsert(&db, 0, args(&["l", "AROUND", "a", "y"])).await,
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        // a b x c b d y b
+        assert_eq!(lindex(&db, 0, args(&["l", "2"])).await, bulk("x"));
+        assert_eq!(lindex(&db, 0, args(&["l", "6"])).await, bulk("y"));
+
+        assert_eq!(lpos(&db, 0, args(&["l", "b"])).await, RespValue::Integer(1));
+        assert_eq!(lpos(&db, 0, args(&["l", "b", "RANK", "2"])).await, RespValue::Integer(4));
+        assert_eq!(lpos(&db, 0, args(&["l", "b", "RANK", "-1"])).await, RespValue::Integer(7));
+        assert_eq!(
+            lpos(&db, 0, args(&["l", "b", "RANK", "-2", "COUNT", "0"])).await,
+            RespValue::Array(Some(vec![RespValue::Integer(4), RespValue::Integer(1)]))
+        );
+        assert_eq!(
+            lpos(&db, 0, args(&["l", "b", "COUNT", "0", "MAXLEN", "5"])).await,
+            RespValue::Array(Some(vec![RespValue::Integer(1), RespValue::Integer(4)]))
+        );
+        assert_eq!(lpos(&db, 0, args(&["l", "b", "MAXLEN", "1"])).await, RespValue::BulkString(None));
+        assert_eq!(lpos(&db, 0, args(&["none", "b", "COUNT", "1"])).await, RespValue::Array(Some(vec![])));
+        assert!(matches!(lpos(&db, 0, args(&["l", "b", "RANK", "0"])).await, RespValue::Error(_)));
+        assert!(matches!(lpos(&db, 0, args(&["l", "b", "COUNT", "-1"])).await, RespValue::Error(_)));
+        assert!(matches!(lpos(&db, 0, args(&["l", "b", "COUNT"])).await, RespValue::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_lmpop() {
+        let db = Arc::new(Database::new(16));
+        rpush(&db, 0, args(&["l", "a", "b", "c"])).await;
+
+        let popped = lmpop(&db, 0, args(&["2", "empty", "l", "RIGHT", "COUNT", "2"])).await;
+        assert_eq!(
+            popped,
+            RespValue::Array(Some(vec![bulk("l"), RespValue::Array(Some(vec![bulk("c"), bulk("b")]))]))
+        );
+        let command = args(&["LMPOP", "2", "empty", "l", "RIGHT", "COUNT", "2"]);
+        assert_eq!(propagated_commands(&command, &popped), vec![args(&["RPOP", "l", "2"])]);
+
+        lmpop(&db, 0, args(&["1", "l", "LEFT", "COUNT", "10"])).await;
+        assert_eq!(llen(&db, 0, args(&["l"])).await, RespValue::Integer(0));
+        assert_eq!(lmpop(&db, 0, args(&["1", "l", "LEFT"])).await, RespValue::Array(None));
+        assert!(propagated_commands(&args(&["LMPOP", "1", "l", "LEFT"]), &RespValue::Array(None)).is_empty());
+        assert_eq!(
+            lmpop(&db, 0, args(&["1", "l", "LEFT", "COUNT", "0"])).await,
+            RespValue::Error("ERR count should be greater than 0".to_string())
+        );
+        assert_eq!(
+            blmpop(&db, 0, args(&["0.05", "1", "l", "LEFT"])).await,
+            RespValue::Array(None)
+        );
+
+        let waiter = {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move { blmpop(&db, 0, args(&["0", "1", "l", "LEFT"])).await })
+        };
+        while db.key_waiters().waiting(0, "l") == 0 {
+            tokio::task::yield_now().await;
+        }
+        rpush(&db, 0, args(&["l", "late"])).await;
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), waiter).await.unwrap().unwrap();
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![bulk("l"), RespValue::Array(Some(vec![bulk("late")]))]))
+        );
+        assert_eq!(db.key_waiters().waiting(0, "l"), 0);
+
+        // An element moved onto the key wakes a blocked client too
+        let waiter = {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move { blmpop(&db, 0, args(&["0", "1", "l", "RIGHT"])).await })
+        };
+        while db.key_waiters().waiting(0, "l") == 0 {
+            tokio::task::yield_now().await;
+        }
+        rpush(&db, 0, args(&["src", "moved"])).await;
+        lmove(&db, 0, args(&["src", "l", "LEFT", "LEFT"])).await;
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), waiter).await.unwrap().unwrap();
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![bulk("l"), RespValue::Array(Some(vec![bulk("moved")]))]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_list_edits() {
+        let db = Arc::new(Database::new(16));
+        let elements: Vec<String> = (0..2000).map(|i| format!("e{}", i)).collect();
+        let mut push = vec!["l".to_string()];
+        push.extend(elements.iter().cloned());
+        let push: Vec<&str> = push.iter().map(String::as_str).collect();
+        rpush(&db, 0, args(&push)).await;
+
+        match db.get_db(0).unwrap().get("l") {
+            Some(RedisValue::List(list)) => assert_eq!(list.encoding(), "quicklist"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert_eq!(lset(&db, 0, args(&["l", "1000", "changed"])).await, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(lindex(&db, 0, args(&["l", "1000"])).await, bulk("changed"));
+        assert_eq!(lindex(&db, 0, args(&["l", "-1"])).await, bulk("e1999"));
+        assert_eq!(
+            lrange(&db, 0, args(&["l", "998", "1001"])).await,
+            RespValue::Array(Some(vec![bulk("e998"), bulk("e999"), bulk("changed"), bulk("e1001")]))
+        );
+
+        assert_eq!(lrem(&db, 0, args(&["l", "-1", "changed"])).await, RespValue::Integer(1));
+        ltrim(&db, 0, args(&["l", "10", "-11"])).await;
+        assert_eq!(llen(&db, 0, args(&["l"])).await, RespValue::Integer(1979));
+        assert_eq!(lindex(&db, 0, args(&["l", "0"])).await, bulk("e10"));
+        assert_eq!(lindex(&db, 0, args(&["l", "-1"])).await, bulk("e1989"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_pushes_are_kept() {
+        let db = Arc::new(Database::new(16));
+        let pushers: Vec<_> = (0..8)
+            .map(|_| {
+                let db = Arc::clone(&db);
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        lpush(&db, 0, args(&["l", "x"])).await;
+                    }
+                })
+            })
+            .collect();
+        for pusher in pushers {
+            pusher.await.unwrap();
+        }
+        assert_eq!(llen(&db, 0, args(&["l"])).await, RespValue::Integer(1600));
+    }
+
+    #[tokio::test]
+    async fn test_lmove_checks_destination_first() {
+        let db = Arc::new(Database::new(16));
+        rpush(&db, 0, args(&["src", "a"])).await;
+        db.get_db(0).unwrap().set("str".to_string(), RedisValue::String(Bytes::from("v")));
+
+        assert!(matches!(lmove(&db, 0, args(&["src", "str", "LEFT", "LEFT"])).await, RespValue::Error(_)));
+        assert_eq!(llen(&db, 0, args(&["src"])).await, RespValue::Integer(1));
+        assert_eq!(lmove(&db, 0, args(&["none", "str", "LEFT", "LEFT"])).await, RespValue::BulkString(None));
+        assert_eq!(rpoplpush(&db, 0, args(&["src", "dst"])).await, bulk("a"));
+        assert!(!db.get_db(0).unwrap().exists("src"));
+        assert_eq!(lindex(&db, 0, args(&["dst", "0"])).await, bulk("a"));
+
+        // Within one list the element just moves to the other end
+        rpush(&db, 0, args(&["dst", "b"])).await;
+        assert_eq!(lmove(&db, 0, args(&["dst", "dst", "LEFT", "RIGHT"])).await, bulk("a"));
+        assert_eq!(lindex(&db, 0, args(&["dst", "0"])).await, bulk("b"));
+        assert_eq!(lindex(&db, 0, args(&["dst", "1"])).await, bulk("a"));
+        assert_eq!(lmove(&db, 0, args(&["none", "none", "LEFT", "RIGHT"])).await, RespValue::BulkString(None));
+    }
+
     #[test]
     fn test_normalize_index() {
         assert_eq!(normalize_index(0, 10), 0);
@@ -18,6 +18,7 @@ pub mod bitmap;
 pub mod hyperloglog;
 pub mod geo;
 pub mod stream;
+pub mod stream_group;
 pub mod key_mgmt;
 pub mod cluster;
 
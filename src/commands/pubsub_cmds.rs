@@ -187,6 +187,105 @@ pub async fn punsubscribe(
     responses
 }
 
+/// SPUBLISH shardchannel message
+pub async fn spublish(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() != 2 {
+        return RespValue::Error("ERR wrong number of arguments for 'spublish' command".to_string());
+    }
+
+    let channel = match std::str::from_utf8(&args[0]) {
+        Ok(s) => s,
+        Err(_) => return RespValue::Error("ERR invalid channel name".to_string()),
+    };
+
+    let count = pubsub.spublish(channel, args[1].clone());
+    RespValue::Integer(count as i64)
+}
+
+/// SSUBSCRIBE shardchannel [shardchannel ...]
+pub async fn ssubscribe(
+    pubsub: &Arc<PubSub>,
+    state: &mut SubscriptionState,
+    args: Vec<Vec<u8>>,
+) -> Vec<RespValue> {
+    if args.is_empty() {
+        return vec![RespValue::Error(
+            "ERR wrong number of arguments for 'ssubscribe' command".to_string(),
+        )];
+    }
+
+    let mut responses = Vec::new();
+
+    for channel_bytes in args {
+        let channel = match std::str::from_utf8(&channel_bytes) {
+            Ok(s) => s.to_string(),
+            Err(_) => {
+                responses.push(RespValue::Error("ERR invalid channel name".to_string()));
+                continue;
+            }
+        };
+
+        state.add_shard_channel(channel.clone());
+        let _rx = pubsub.get_or_create_shard_channel(&channel);
+
+        responses.push(RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"ssubscribe".to_vec())),
+            RespValue::BulkString(Some(channel.into_bytes())),
+            RespValue::Integer(state.shard_channels.len() as i64),
+        ])));
+    }
+
+    responses
+}
+
+/// SUNSUBSCRIBE [shardchannel [shardchannel ...]]
+pub async fn sunsubscribe(
+    pubsub: &Arc<PubSub>,
+    state: &mut SubscriptionState,
+    args: Vec<Vec<u8>>,
+) -> Vec<RespValue> {
+    let channels: Vec<String> = if args.is_empty() {
+        state.shard_channels.clone()
+    } else {
+        args.iter().map(|c| String::from_utf8_lossy(c).to_string()).collect()
+    };
+
+    let mut responses = Vec::new();
+    for channel in channels {
+        state.remove_shard_channel(&channel);
+        pubsub.cleanup_shard_channel(&channel);
+
+        responses.push(RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"sunsubscribe".to_vec())),
+            RespValue::BulkString(Some(channel.into_bytes())),
+            RespValue::Integer(state.shard_channels.len() as i64),
+        ])));
+    }
+
+    responses
+}
+
+/// PUBSUB <subcommand> [arg ...]
+pub async fn pubsub_command(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
+    if args.is_empty() {
+        return RespValue::Error("ERR wrong number of arguments for 'pubsub' command".to_string());
+    }
+
+    let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let rest = args[1..].to_vec();
+    match subcommand.as_str() {
+        "CHANNELS" => pubsub_channels(pubsub, rest).await,
+        "NUMSUB" => pubsub_numsub(pubsub, rest).await,
+        "NUMPAT" => pubsub_numpat(pubsub).await,
+        "SHARDCHANNELS" => pubsub_shardchannels(pubsub, rest).await,
+        "SHARDNUMSUB" => pubsub_shardnumsub(pubsub, rest).await,
+        _ => RespValue::Error(format!(
+            "ERR unknown subcommand '{}'. Try PUBSUB HELP.",
+            String::from_utf8_lossy(&args[0])
+        )),
+    }
+}
+
 /// PUBSUB CHANNELS [pattern]
 pub async fn pubsub_channels(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
     let pattern = if args.is_empty() {
@@ -201,7 +300,7 @@ pub async fn pubsub_channels(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespVa
     let channels = pubsub.active_channels();
     let filtered: Vec<RespValue> = channels
         .into_iter()
-        .filter(|ch| pattern == "*" || ch.contains(pattern))
+        .filter(|ch| PubSub::match_pattern(ch, pattern))
         .map(|ch| RespValue::BulkString(Some(ch.into_bytes())))
         .collect();
 
@@ -229,14 +328,41 @@ pub async fn pubsub_numsub(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValu
 
 /// PUBSUB NUMPAT
 pub async fn pubsub_numpat(pubsub: &Arc<PubSub>) -> RespValue {
-    // Count active patterns
-    let count = pubsub
-        .active_channels()
-        .iter()
-        .filter(|_| true) // In full implementation, would count pattern subscriptions
-        .count();
+    RespValue::Integer(pubsub.active_patterns() as i64)
+}
 
-    RespValue::Integer(count as i64)
+/// PUBSUB SHARDCHANNELS [pattern]
+pub async fn pubsub_shardchannels(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
+    let pattern = match args.first() {
+        None => "*",
+        Some(arg) => match std::str::from_utf8(arg) {
+            Ok(s) => s,
+            Err(_) => return RespValue::Error("ERR invalid pattern".to_string()),
+        },
+    };
+
+    let filtered: Vec<RespValue> = pubsub
+        .active_shard_channels()
+        .into_iter()
+        .filter(|ch| PubSub::match_pattern(ch, pattern))
+        .map(|ch| RespValue::BulkString(Some(ch.into_bytes())))
+        .collect();
+
+    RespValue::Array(Some(filtered))
+}
+
+/// PUBSUB SHARDNUMSUB [shardchannel [shardchannel ...]]
+pub async fn pubsub_shardnumsub(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
+    let mut result = Vec::new();
+
+    for channel_bytes in args {
+        let channel = String::from_utf8_lossy(&channel_bytes).to_string();
+        let count = pubsub.shard_channel_subscribers(&channel);
+        result.push(RespValue::BulkString(Some(channel.into_bytes())));
+        result.push(RespValue::Integer(count as i64));
+    }
+
+    RespValue::Array(Some(result))
 }
 
 #[cfg(test)]
@@ -282,4 +408,31 @@ mod tests {
         assert_eq!(responses.len(), 1);
         assert_eq!(state.patterns.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_ssubscribe_sunsubscribe() {
+        let pubsub = Arc::new(PubSub::new());
+        let mut state = SubscriptionState::new();
+
+        let responses = ssubscribe(&pubsub, &mut state, vec![b"{a}1".to_vec(), b"{a}2".to_vec()]).await;
+        assert_eq!(responses.len(), 2);
+        assert_eq!(state.shard_channels.len(), 2);
+        assert!(state.channels.is_empty());
+
+        // Shard channels are listed apart from plain ones
+        let _rx = pubsub.get_or_create_shard_channel("{a}1");
+        assert_eq!(
+            pubsub_command(&pubsub, vec![b"SHARDNUMSUB".to_vec(), b"{a}1".to_vec()]).await,
+            RespValue::Array(Some(vec![RespValue::BulkString(Some(b"{a}1".to_vec())), RespValue::Integer(1)]))
+        );
+        assert_eq!(
+            pubsub_command(&pubsub, vec![b"SHARDCHANNELS".to_vec()]).await,
+            RespValue::Array(Some(vec![RespValue::BulkString(Some(b"{a}1".to_vec()))]))
+        );
+        assert_eq!(pubsub_command(&pubsub, vec![b"CHANNELS".to_vec()]).await, RespValue::Array(Some(vec![])));
+
+        let responses = sunsubscribe(&pubsub, &mut state, vec![]).await;
+        assert_eq!(responses.len(), 2);
+        assert!(!state.is_subscribed());
+    }
 }
@@ -1,7 +1,8 @@
 // Replication commands (REPLICAOF, ROLE, PSYNC, etc.)
 
+use crate::config::Config;
 use crate::protocol::RespValue;
-use crate::replication::{ReplicationInfo, ReplicationRole, SyncHandler, CommandPropagator, ReplicaClient};
+use crate::replication::{ReplicationInfo, ReplicationRole, SyncHandler, CommandPropagator, start_replication};
 use crate::replication::backlog::ReplicationBacklog;
 use crate::storage::db::Database;
 use std::sync::Arc;
@@ -12,6 +13,7 @@ pub async fn replicaof(
     repl_info: &Arc<ReplicationInfo>,
     backlog: &Arc<ReplicationBacklog>,
     db: &Arc<Database>,
+    config: &Arc<Config>,
     args: Vec<Vec<u8>>,
 ) -> RespValue {
     if args.len() != 2 {
@@ -44,22 +46,7 @@ pub async fn replicaof(
     };
 
     info!("Configuring as replica of {}:{}", host, port);
-    repl_info.set_replica(host.to_string(), port);
-
-    // Start replication connection in background
-    let replica_client = ReplicaClient::new(
-        host.to_string(),
-        port,
-        Arc::clone(db),
-        Arc::clone(repl_info),
-        Arc::clone(backlog),
-    );
-
-    tokio::spawn(async move {
-        if let Err(e) = replica_client.start().await {
-            tracing::error!("Replication connection failed: {}", e);
-        }
-    });
+    start_replication(host, port, repl_info, backlog, db, config);
 
     RespValue::SimpleString("OK".to_string())
 }
@@ -135,7 +122,7 @@ pub async fn psync(
     if needs_full_sync {
         // Full resynchronization needed
         info!("PSYNC: Full resynchronization required");
-        SyncHandler::generate_fullresync_response(&repl_id)
+        SyncHandler::generate_fullresync_response(&repl_id, repl_info.master_offset())
     } else {
         // Partial resynchronization possible
         info!("PSYNC: Partial resynchronization from offset {}", offset);
@@ -267,8 +254,9 @@ mod tests {
         let repl_info = Arc::new(ReplicationInfo::new());
         let backlog = Arc::new(ReplicationBacklog::new());
         let db = Arc::new(Database::new(16));
+        let config = Arc::new(Config::new());
 
-        let result = replicaof(&repl_info, &backlog, &db, vec![b"NO".to_vec(), b"ONE".to_vec()]).await;
+        let result = replicaof(&repl_info, &backlog, &db, &config, vec![b"NO".to_vec(), b"ONE".to_vec()]).await;
 
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
         assert!(repl_info.is_master());
@@ -279,11 +267,13 @@ mod tests {
         let repl_info = Arc::new(ReplicationInfo::new());
         let backlog = Arc::new(ReplicationBacklog::new());
         let db = Arc::new(Database::new(16));
+        let config = Arc::new(Config::new());
 
         let result = replicaof(
             &repl_info,
             &backlog,
             &db,
+            &config,
             vec![b"127.0.0.1".to_vec(), b"6379".to_vec()],
         )
         .await;
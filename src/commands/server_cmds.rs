@@ -4,6 +4,7 @@ use crate::config::Config;
 use crate::persistence::{aof::AofManager, RdbSerializer};
 use crate::protocol::RespValue;
 use crate::storage::db::Database;
+use crate::storage::encoding;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -156,8 +157,9 @@ pub async fn config_get(config: &Arc<Config>, args: Vec<Vec<u8>>) -> RespValue {
         return RespValue::Array(Some(result));
     }
 
-    // Get specific parameter
-    match config.get(pattern) {
+    // Get specific parameter, accepting the old ziplist names
+    let name = encoding::canonical_name(pattern).unwrap_or(pattern);
+    match config.get(name) {
         Some(value) => {
             RespValue::Array(Some(vec![
                 RespValue::BulkString(Some(pattern.as_bytes().to_vec())),
@@ -169,13 +171,13 @@ pub async fn config_get(config: &Arc<Config>, args: Vec<Vec<u8>>) -> RespValue {
 }
 
 /// CONFIG SET - Set configuration parameter
-pub async fn config_set(config: &Arc<Config>, args: Vec<Vec<u8>>) -> RespValue {
+pub async fn config_set(db: &Arc<Database>, config: &Arc<Config>, args: Vec<Vec<u8>>) -> RespValue {
     if args.len() != 2 {
         return RespValue::Error("ERR wrong number of arguments for 'config|set' command".to_string());
     }
 
     let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s.to_string(),
+        Ok(s) => encoding::canonical_name(s).map(str::to_string).unwrap_or_else(|| s.to_string()),
         Err(_) => return RespValue::Error("ERR invalid parameter".to_string()),
     };
 
@@ -184,8 +186,13 @@ pub async fn config_set(config: &Arc<Config>, args: Vec<Vec<u8>>) -> RespValue {
         Err(_) => return RespValue::Error("ERR invalid value".to_string()),
     };
 
-    match config.set(key.clone(), value) {
-        Ok(_) => RespValue::SimpleString("OK".to_string()),
+    match config.set(key.clone(), value.clone()) {
+        Ok(_) => {
+            if key == "notify-keyspace-events" {
+                db.keyspace_events().configure(&value);
+            }
+            RespValue::SimpleString("OK".to_string())
+        }
         Err(e) => RespValue::Error(format!("ERR {}", e)),
     }
 }
@@ -297,4 +304,46 @@ mod tests {
         let result = select(&mut db_index, vec![b"20".to_vec()]).await;
         assert!(matches!(result, RespValue::Error(_)));
     }
+
+    #[tokio::test]
+    async fn test_config_accepts_ziplist_names() {
+        let db = Arc::new(Database::new(16));
+        let config = Arc::new(Config::new());
+        let result = config_set(&db, &config, vec![b"zset-max-ziplist-entries".to_vec(), b"4".to_vec()]).await;
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(config.get("zset-max-listpack-entries"), Some("4".to_string()));
+        assert_eq!(config.encoding_limits().zset_max_listpack_entries.get(), 4);
+        // Limits belong to one server's configuration
+        assert_eq!(Config::new().encoding_limits().zset_max_listpack_entries.get(), 128);
+
+        let result = config_get(&config, vec![b"hash-max-ziplist-value".to_vec()]).await;
+        assert_eq!(
+            result,
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"hash-max-ziplist-value".to_vec())),
+                RespValue::BulkString(Some(b"64".to_vec())),
+            ]))
+        );
+
+        let result = config_set(&db, &config, vec![b"set-max-intset-entries".to_vec(), b"-1".to_vec()]).await;
+        assert!(matches!(result, RespValue::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_config_set_notify_keyspace_events() {
+        let db = Arc::new(Database::new(16));
+        let config = Arc::new(Config::new());
+        let result = config_set(&db, &config, vec![b"notify-keyspace-events".to_vec(), b"KEA".to_vec()]).await;
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(db.keyspace_events().flags().to_config_string(), "AKE");
+
+        let result = config_set(&db, &config, vec![b"notify-keyspace-events".to_vec(), b"KEw".to_vec()]).await;
+        assert!(matches!(result, RespValue::Error(_)));
+        assert_eq!(config.get("notify-keyspace-events"), Some("KEA".to_string()));
+
+        // Evicted events are accepted even though they never fire
+        let result = config_set(&db, &config, vec![b"notify-keyspace-events".to_vec(), b"KEe".to_vec()]).await;
+        assert_eq!(result, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(db.keyspace_events().flags().to_config_string(), "eKE");
+    }
 }
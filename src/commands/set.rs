@@ -1,8 +1,10 @@
 // Set command handlers
 
+use super::key_mgmt::ScanOptions;
 use crate::protocol::RespValue;
-use crate::storage::db::Database;
-use crate::storage::types::RedisValue;
+use crate::storage::db::{Database, DbInstance};
+use crate::storage::notify::EventClass;
+use crate::storage::types::{RedisValue, SetValue};
 use bytes::Bytes;
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -30,7 +32,7 @@ pub async fn sadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashSet::new(),
+        None => db_instance.new_set(),
     };
 
     let mut added = 0;
@@ -40,7 +42,10 @@ pub async fn sadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         }
     }
 
-    db_instance.set(key, RedisValue::Set(set));
+    db_instance.set(key.clone(), RedisValue::Set(set));
+    if added > 0 {
+        db_instance.notify(EventClass::SET, "sadd", &key);
+    }
     RespValue::Integer(added)
 }
 
@@ -77,10 +82,8 @@ pub async fn srem(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         }
     }
 
-    if set.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::Set(set));
+    if removed > 0 {
+        store_set(&db_instance, &key, set, "srem");
     }
 
     RespValue::Integer(removed)
@@ -102,7 +105,7 @@ pub async fn smembers(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Set(set)) => {
             let members: Vec<RespValue> = set
                 .iter()
@@ -135,7 +138,7 @@ pub async fn sismember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Set(set)) => {
             RespValue::Integer(if set.contains(&member) { 1 } else { 0 })
         }
@@ -162,7 +165,7 @@ pub async fn scard(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Set(set)) => RespValue::Integer(set.len() as i64),
         Some(_) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -222,13 +225,9 @@ pub async fn spop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
     match count {
         None => {
             // Pop single element
-            if let Some(member) = set.iter().next().cloned() {
+            if let Some(member) = set.iter().next() {
                 set.remove(&member);
-                if set.is_empty() {
-                    db_instance.delete(&key);
-                } else {
-                    db_instance.set(key, RedisValue::Set(set));
-                }
+                store_set(&db_instance, &key, set, "spop");
                 RespValue::BulkString(Some(member.to_vec()))
             } else {
                 RespValue::BulkString(None)
@@ -237,7 +236,7 @@ pub async fn spop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Some(n) => {
             // Pop multiple elements
             let mut popped = Vec::new();
-            let mut members: Vec<_> = set.iter().cloned().collect();
+            let mut members: Vec<_> = set.iter().collect();
 
             for _ in 0..n.min(members.len()) {
                 if let Some(member) = members.pop() {
@@ -246,10 +245,8 @@ pub async fn spop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
                 }
             }
 
-            if set.is_empty() {
-                db_instance.delete(&key);
-            } else {
-                db_instance.set(key, RedisValue::Set(set));
+            if !popped.is_empty() {
+                store_set(&db_instance, &key, set, "spop");
             }
 
             RespValue::Array(Some(popped))
@@ -289,7 +286,7 @@ pub async fn srandmember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Set(set)) => {
             if set.is_empty() {
                 return if count.is_some() {
@@ -313,7 +310,7 @@ pub async fn srandmember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
                     let members: Vec<_> = set.iter().take(n.unsigned_abs() as usize).collect();
                     let result: Vec<RespValue> = members
                         .iter()
-                        .map(|&m| RespValue::BulkString(Some(m.to_vec())))
+                        .map(|m| RespValue::BulkString(Some(m.to_vec())))
                         .collect();
                     RespValue::Array(Some(result))
                 }
@@ -349,8 +346,8 @@ pub async fn sinter(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let mut result_set = match db_instance.get(first_key) {
-        Some(RedisValue::Set(s)) => s,
+    let mut result_set: HashSet<Bytes> = match db_instance.lookup_read(first_key) {
+        Some(RedisValue::Set(s)) => s.iter().collect(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -366,9 +363,9 @@ pub async fn sinter(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             Err(_) => continue,
         };
 
-        match db_instance.get(key) {
+        match db_instance.lookup_read(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.intersection(&set).cloned().collect();
+                result_set.retain(|m| set.contains(m));
             }
             Some(_) => {
                 return RespValue::Error(
@@ -410,9 +407,9 @@ pub async fn sunion(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             Err(_) => continue,
         };
 
-        match db_instance.get(key) {
+        match db_instance.lookup_read(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.union(&set).cloned().collect();
+                result_set.extend(set.iter());
             }
             Some(_) => {
                 return RespValue::Error(
@@ -448,8 +445,8 @@ pub async fn sdiff(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let mut result_set = match db_instance.get(first_key) {
-        Some(RedisValue::Set(s)) => s,
+    let mut result_set: HashSet<Bytes> = match db_instance.lookup_read(first_key) {
+        Some(RedisValue::Set(s)) => s.iter().collect(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -465,9 +462,9 @@ pub async fn sdiff(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
             Err(_) => continue,
         };
 
-        match db_instance.get(key) {
+        match db_instance.lookup_read(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.difference(&set).cloned().collect();
+                result_set.retain(|m| !set.contains(m));
             }
             Some(_) => {
                 return RespValue::Error(
@@ -514,8 +511,8 @@ pub async fn sinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let mut result_set = match db_instance.get(first_key) {
-        Some(RedisValue::Set(s)) => s,
+    let mut result_set: HashSet<Bytes> = match db_instance.get(first_key) {
+        Some(RedisValue::Set(s)) => s.iter().collect(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -533,7 +530,7 @@ pub async fn sinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
 
         match db_instance.get(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.intersection(&set).cloned().collect();
+                result_set.retain(|m| set.contains(m));
             }
             Some(_) => {
                 return RespValue::Error(
@@ -549,11 +546,7 @@ pub async fn sinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
     }
 
     let count = result_set.len();
-    if count > 0 {
-        db_instance.set(destination, RedisValue::Set(result_set));
-    } else {
-        db_instance.delete(&destination);
-    }
+    store_result(&db_instance, &destination, result_set, "sinterstore");
 
     RespValue::Integer(count as i64)
 }
@@ -586,7 +579,7 @@ pub async fn sunionstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
 
         match db_instance.get(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.union(&set).cloned().collect();
+                result_set.extend(set.iter());
             }
             Some(_) => {
                 return RespValue::Error(
@@ -598,11 +591,7 @@ pub async fn sunionstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
     }
 
     let count = result_set.len();
-    if count > 0 {
-        db_instance.set(destination, RedisValue::Set(result_set));
-    } else {
-        db_instance.delete(&destination);
-    }
+    store_result(&db_instance, &destination, result_set, "sunionstore");
 
     RespValue::Integer(count as i64)
 }
@@ -631,8 +620,8 @@ pub async fn sdiffstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let mut result_set = match db_instance.get(first_key) {
-        Some(RedisValue::Set(s)) => s,
+    let mut result_set: HashSet<Bytes> = match db_instance.get(first_key) {
+        Some(RedisValue::Set(s)) => s.iter().collect(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -650,7 +639,7 @@ pub async fn sdiffstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
 
         match db_instance.get(key) {
             Some(RedisValue::Set(set)) => {
-                result_set = result_set.difference(&set).cloned().collect();
+                result_set.retain(|m| !set.contains(m));
             }
             Some(_) => {
                 return RespValue::Error(
@@ -666,13 +655,37 @@ pub async fn sdiffstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
     }
 
     let count = result_set.len();
-    if count > 0 {
-        db_instance.set(destination, RedisValue::Set(result_set));
+    store_result(&db_instance, &destination, result_set, "sdiffstore");
+
+    RespValue::Integer(count as i64)
+}
+
+/// Store a set back after an `event` that removed members, deleting the
+/// key once it has none
+fn store_set(db_instance: &DbInstance, key: &str, set: SetValue, event: &str) {
+    let emptied = set.is_empty();
+    if emptied {
+        db_instance.delete(key);
     } else {
-        db_instance.delete(&destination);
+        db_instance.set(key.to_string(), RedisValue::Set(set));
+    }
+    db_instance.notify(EventClass::SET, event, key);
+    if emptied {
+        db_instance.notify(EventClass::GENERIC, "del", key);
     }
+}
 
-    RespValue::Integer(count as i64)
+/// Store the result of SINTERSTORE and friends at `destination`; an empty
+/// result deletes the key instead
+fn store_result(db_instance: &DbInstance, destination: &str, result: HashSet<Bytes>, event: &str) {
+    if !result.is_empty() {
+        let mut set = db_instance.new_set();
+        set.extend(result);
+        db_instance.set(destination.to_string(), RedisValue::Set(set));
+        db_instance.notify(EventClass::SET, event, destination);
+    } else if db_instance.delete(destination) {
+        db_instance.notify(EventClass::GENERIC, "del", destination);
+    }
 }
 
 /// SMOVE source destination member
@@ -714,11 +727,7 @@ pub async fn smove(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     }
 
     // Update or delete source
-    if source_set.is_empty() {
-        db_instance.delete(&source);
-    } else {
-        db_instance.set(source.clone(), RedisValue::Set(source_set));
-    }
+    store_set(&db_instance, &source, source_set, "srem");
 
     // Add to destination
     let mut dest_set = match db_instance.get(&destination) {
@@ -728,11 +737,12 @@ pub async fn smove(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => HashSet::new(),
+        None => db_instance.new_set(),
     };
 
     dest_set.insert(member);
-    db_instance.set(destination, RedisValue::Set(dest_set));
+    db_instance.set(destination.clone(), RedisValue::Set(dest_set));
+    db_instance.notify(EventClass::SET, "sadd", &destination);
 
     RespValue::Integer(1)
 }
@@ -754,7 +764,7 @@ pub async fn smismember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let set = match db_instance.get(key) {
+    let set = match db_instance.lookup_read(key) {
         Some(RedisValue::Set(s)) => s,
         Some(_) => {
             return RespValue::Error(
@@ -790,12 +800,9 @@ pub async fn sscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let cursor = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
+    let options = match ScanOptions::parse(&args[1..], &[]) {
+        Ok(options) => options,
+        Err(e) => return e,
     };
 
     let db_instance = match db.get_db(db_index) {
@@ -803,60 +810,27 @@ pub async fn sscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Parse optional COUNT parameter
-    let mut count = 10; // Default count
-    let mut i = 2;
-    while i < args.len() {
-        if let Ok(s) = std::str::from_utf8(&args[i]) {
-            if s.to_uppercase() == "COUNT" && i + 1 < args.len() {
-                if let Ok(c_str) = std::str::from_utf8(&args[i + 1]) {
-                    if let Ok(c) = c_str.parse::<usize>() {
-                        count = c;
-                    }
-                }
-                i += 2;
-            } else if s.to_uppercase() == "MATCH" && i + 1 < args.len() {
-                // MATCH pattern support (basic implementation - just skip for now)
-                i += 2;
-            } else {
-                i += 1;
-            }
-        } else {
-            i += 1;
-        }
-    }
-
-    match db_instance.get(key) {
-        Some(RedisValue::Set(set)) => {
-            let members: Vec<_> = set.iter().collect();
-            let start = cursor;
-            let end = (start + count).min(members.len());
-            let next_cursor = if end >= members.len() { 0 } else { end };
-
-            // Build results array with members
-            let mut results = Vec::new();
-            for i in start..end {
-                if let Some(member) = members.get(i) {
-                    results.push(RespValue::BulkString(Some(member.to_vec())));
-                }
-            }
-
-            // Return [next_cursor, [member1, member2, ...]]
-            RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(next_cursor.to_string().into_bytes())),
-                RespValue::Array(Some(results)),
-            ]))
-        }
-        Some(_) => RespValue::Error(
+    // Read in place: copying the set would make every step O(n)
+    let page = db_instance.lookup_read_with(key, |value| {
+        let RedisValue::Set(set) = value else {
+            return None;
+        };
+        let (members, next_cursor) = set.scan(options.cursor, options.count);
+
+        let results = members
+            .into_iter()
+            .filter(|member| options.matches(member))
+            .map(|member| RespValue::BulkString(Some(member.to_vec())))
+            .collect();
+        Some(ScanOptions::reply(next_cursor, results))
+    });
+
+    match page {
+        Some(Some(reply)) => reply,
+        Some(None) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
         ),
-        None => {
-            // Empty set - return cursor 0 and empty array
-            RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(b"0".to_vec())),
-                RespValue::Array(Some(vec![])),
-            ]))
-        }
+        None => ScanOptions::reply(0, vec![]),
     }
 }
 
@@ -936,4 +910,74 @@ mod tests {
             panic!("Expected array");
         }
     }
+
+    /// SSCAN `key` to the end with COUNT 10, calling `between` after each
+    /// step; returns every member seen and the number of calls
+    async fn sscan_all(db: &Arc<Database>, key: &str, mut between: impl FnMut(usize)) -> (Vec<Vec<u8>>, usize) {
+        let mut seen = Vec::new();
+        let mut cursor = b"0".to_vec();
+        for call in 1.. {
+            let reply = sscan(db, 0, vec![key.as_bytes().to_vec(), cursor, b"COUNT".to_vec(), b"10".to_vec()]).await;
+            let RespValue::Array(Some(reply)) = reply else { panic!("unexpected SSCAN reply") };
+            let RespValue::BulkString(Some(next)) = &reply[0] else { panic!("unexpected cursor") };
+            let RespValue::Array(Some(members)) = &reply[1] else { panic!("unexpected members") };
+            assert!(members.len() <= 20);
+            for member in members {
+                let RespValue::BulkString(Some(member)) = member else { panic!("unexpected member") };
+                seen.push(member.clone());
+            }
+            between(call);
+            cursor = next.clone();
+            if cursor == b"0" {
+                return (seen, call);
+            }
+        }
+        unreachable!()
+    }
+
+    #[tokio::test]
+    async fn test_sscan_steps_through_large_sets() {
+        let db = Arc::new(Database::new(16));
+        let instance = db.get_db(0).unwrap();
+        let mut set = instance.new_set();
+        set.extend((0..1000).map(|i| Bytes::from(format!("stable:{}", i))));
+        assert_eq!(set.encoding(), "hashtable");
+        instance.set("big".to_string(), RedisValue::Set(set));
+
+        // Members come and go between calls
+        let churn = |call: usize| {
+            instance.with_value_mut("big", |value| {
+                if let RedisValue::Set(set) = value {
+                    set.insert(Bytes::from(format!("new:{}", call)));
+                    set.remove(format!("new:{}", call / 2).as_bytes());
+                }
+            });
+        };
+        let (seen, calls) = sscan_all(&db, "big", churn).await;
+        assert!(calls < 150);
+        for i in 0..1000 {
+            let member = format!("stable:{}", i).into_bytes();
+            assert_eq!(seen.iter().filter(|m| **m == member).count(), 1);
+        }
+
+        // A listpack that converts mid-scan keeps its cursor meaning
+        let small: Vec<Vec<u8>> = (0..100).map(|i| format!("m{}", i).into_bytes()).collect();
+        let mut add = vec![b"small".to_vec()];
+        add.extend(small.iter().cloned());
+        sadd(&db, 0, add).await;
+        let grow = |call: usize| {
+            if call == 2 {
+                instance.with_value_mut("small", |value| {
+                    if let RedisValue::Set(set) = value {
+                        set.extend((0..100).map(|i| Bytes::from(format!("extra{}", i))));
+                        assert_eq!(set.encoding(), "hashtable");
+                    }
+                });
+            }
+        };
+        let (seen, _) = sscan_all(&db, "small", grow).await;
+        for member in &small {
+            assert_eq!(seen.iter().filter(|m| *m == member).count(), 1);
+        }
+    }
 }
@@ -2,45 +2,319 @@
 // Stream is an append-only log data structure for message queues
 
 use crate::protocol::RespValue;
-use crate::storage::db::Database;
+use crate::replication::write_gate;
+use crate::storage::db::{Database, DbInstance};
+use crate::storage::key_waiters::KeyWait;
+use crate::storage::notify::EventClass;
 use crate::storage::types::{RedisValue, Stream, StreamEntry, StreamId};
 use bytes::Bytes;
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Get current timestamp in milliseconds
-fn current_timestamp_ms() -> u64 {
+pub(crate) fn current_timestamp_ms() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_millis() as u64
 }
 
-/// Generate next stream ID
-fn generate_stream_id(stream: &Stream, explicit_ts: Option<u64>) -> Result<StreamId, String> {
-    let timestamp = explicit_ts.unwrap_or_else(current_timestamp_ms);
+/// Reply for one entry: [id, [field, value, ...]]
+pub(crate) fn entry_to_resp(id: &StreamId, entry: &StreamEntry) -> RespValue {
+    let mut fields_array = Vec::new();
+    for (field, value) in &entry.fields {
+        fields_array.push(RespValue::BulkString(Some(field.to_vec())));
+        fields_array.push(RespValue::BulkString(Some(value.to_vec())));
+    }
+    RespValue::Array(Some(vec![
+        RespValue::BulkString(Some(id.to_string().into_bytes())),
+        RespValue::Array(Some(fields_array)),
+    ]))
+}
+
+/// Commands to write to the AOF and send to replicas for a stream command
+/// that got `reply`. Generated IDs and claims depending on idle times are
+/// made explicit, so replaying them gives the same result at any time.
+pub fn propagated_commands(args: &[Vec<u8>], reply: &RespValue) -> Vec<Vec<Vec<u8>>> {
+    let cmd = String::from_utf8_lossy(&args[0]).to_uppercase();
+    match (cmd.as_str(), reply) {
+        // XADD key [options] * ... -> XADD key [options] <id> ...
+        ("XADD", RespValue::BulkString(Some(id))) => {
+            let mut rewritten = args.to_vec();
+            if let Ok(options) = parse_xadd_options(&args[1..]) {
+                rewritten[options.id_index + 1] = id.clone();
+            }
+            vec![rewritten]
+        }
+        // Replaying must not wait: XREADGROUP ... BLOCK ms ... -> XREADGROUP ...
+        ("XREADGROUP", _) => {
+            // Group and consumer names come first and are kept as they are
+            let mut rewritten = args[..4.min(args.len())].to_vec();
+            let mut i = rewritten.len();
+            while i < args.len() {
+                if args[i].eq_ignore_ascii_case(b"STREAMS") {
+                    rewritten.extend_from_slice(&args[i..]);
+                    break;
+                }
+                if args[i].eq_ignore_ascii_case(b"BLOCK") {
+                    i += 2;
+                    continue;
+                }
+                rewritten.push(args[i].clone());
+                i += 1;
+            }
+            vec![rewritten]
+        }
+        // XCLAIM key group consumer min-idle id... [options]
+        //   -> XCLAIM key group consumer 0 <claimed ids> [options]
+        ("XCLAIM", RespValue::Array(Some(claimed))) => {
+            let options = args[5..].iter().skip_while(|arg| {
+                let option = String::from_utf8_lossy(arg).to_uppercase();
+                !matches!(option.as_str(), "IDLE" | "TIME" | "RETRYCOUNT" | "FORCE" | "JUSTID" | "LASTID")
+            });
+            let ids = reply_ids(claimed);
+            claim_commands(&args[1..4], ids, options.cloned().collect())
+        }
+        // XAUTOCLAIM key group consumer min-idle start [COUNT n] [JUSTID]
+        //   -> XCLAIM key group consumer 0 <claimed and deleted ids> [JUSTID]
+        ("XAUTOCLAIM", RespValue::Array(Some(parts))) if parts.len() == 3 => {
+            let mut ids = Vec::new();
+            for part in &parts[1..] {
+                if let RespValue::Array(Some(entries)) = part {
+                    ids.extend(reply_ids(entries));
+                }
+            }
+            let justid = args[6..].iter().any(|arg| arg.eq_ignore_ascii_case(b"JUSTID"));
+            let options = if justid { vec![b"JUSTID".to_vec()] } else { vec![] };
+            claim_commands(&args[1..4], ids, options)
+        }
+        _ => vec![args.to_vec()],
+    }
+}
+
+/// IDs of entries in an XCLAIM-like reply, whether JUSTID or full entries
+fn reply_ids(entries: &[RespValue]) -> Vec<Vec<u8>> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            RespValue::BulkString(Some(id)) => Some(id.clone()),
+            RespValue::Array(Some(parts)) => match parts.first() {
+                Some(RespValue::BulkString(Some(id))) => Some(id.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// XCLAIM of `ids` for [key, group, consumer]; with nothing claimed, only
+/// the consumer's creation is left to replay
+fn claim_commands(target: &[Vec<u8>], ids: Vec<Vec<u8>>, options: Vec<Vec<u8>>) -> Vec<Vec<Vec<u8>>> {
+    if ids.is_empty() {
+        let mut command = vec![b"XGROUP".to_vec(), b"CREATECONSUMER".to_vec()];
+        command.extend_from_slice(target);
+        return vec![command];
+    }
+    let mut command = vec![b"XCLAIM".to_vec()];
+    command.extend_from_slice(target);
+    command.push(b"0".to_vec());
+    command.extend(ids);
+    command.extend(options);
+    vec![command]
+}
+
+const INVALID_ID: &str = "ERR Invalid stream ID specified as stream command argument";
+const TOP_ITEM: &str = "ERR The ID specified in XADD is equal or smaller than the target stream top item";
+
+/// Entries per node of Redis' stream radix tree. Approximate (`~`)
+/// trimming only removes whole nodes, which are emulated as runs of this
+/// many entries from the head of the stream.
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// How to trim a stream, from `MAXLEN|MINID [=|~] threshold [LIMIT count]`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Trim {
+    pub strategy: TrimStrategy,
+    pub approximate: bool,
+    /// Most entries to remove at once, approximate trimming only
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TrimStrategy {
+    /// Keep at most this many entries
+    MaxLen(usize),
+    /// Remove entries with a smaller ID
+    MinId(StreamId),
+}
+
+impl Trim {
+    /// Parse a trim clause starting at `args[i]` (MAXLEN or MINID);
+    /// returns it with the index of the argument after it
+    fn parse(args: &[Vec<u8>], mut i: usize) -> Result<(Trim, usize), RespValue> {
+        let syntax_error = || RespValue::Error("ERR syntax error".to_string());
+        let is_maxlen = args[i].eq_ignore_ascii_case(b"MAXLEN");
+        i += 1;
+
+        let mut approximate = false;
+        match args.get(i).map(|a| a.as_slice()) {
+            Some(b"~") => {
+                approximate = true;
+                i += 1;
+            }
+            Some(b"=") => i += 1,
+            _ => {}
+        }
+
+        let threshold = args.get(i).ok_or_else(syntax_error)?;
+        let strategy = if is_maxlen {
+            match std::str::from_utf8(threshold).ok().and_then(|s| s.parse::<i64>().ok()) {
+                Some(n) if n >= 0 => TrimStrategy::MaxLen(n as usize),
+                Some(_) => return Err(RespValue::Error("ERR The MAXLEN argument must be >= 0.".to_string())),
+                None => return Err(RespValue::Error("ERR value is not an integer or out of range".to_string())),
+            }
+        } else {
+            match parse_id_arg(threshold) {
+                Some(id) => TrimStrategy::MinId(id),
+                None => return Err(RespValue::Error(INVALID_ID.to_string())),
+            }
+        };
+        i += 1;
+
+        let mut limit = None;
+        if args.get(i).is_some_and(|a| a.eq_ignore_ascii_case(b"LIMIT")) {
+            let count = args.get(i + 1).ok_or_else(syntax_error)?;
+            let count = match std::str::from_utf8(count).ok().and_then(|s| s.parse::<i64>().ok()) {
+                Some(n) if n >= 0 => n as usize,
+                _ => return Err(RespValue::Error("ERR The LIMIT argument must be >= 0.".to_string())),
+            };
+            if !approximate {
+                return Err(RespValue::Error(
+                    "ERR syntax error, LIMIT cannot be used without the special ~ option".to_string(),
+                ));
+            }
+            limit = Some(count);
+            i += 2;
+        }
+
+        Ok((Trim { strategy, approximate, limit }, i))
+    }
+
+    /// Remove entries from the head of `stream`; returns how many
+    pub fn apply(&self, stream: &mut Stream) -> usize {
+        let removable = match &self.strategy {
+            TrimStrategy::MaxLen(max) => stream.len().saturating_sub(*max),
+            TrimStrategy::MinId(min) => stream.entries.range(..min.clone()).count(),
+        };
+
+        let count = if self.approximate {
+            // LIMIT 0 means no limit; the default bounds the work per call
+            let limit = match self.limit {
+                Some(0) => usize::MAX,
+                Some(limit) => limit,
+                None => 100 * STREAM_NODE_MAX_ENTRIES,
+            };
+            let count = removable.min(limit);
+            count - count % STREAM_NODE_MAX_ENTRIES
+        } else {
+            removable
+        };
+
+        for _ in 0..count {
+            if let Some((id, _)) = stream.entries.pop_first() {
+                if id > stream.max_deleted_id {
+                    stream.max_deleted_id = id;
+                }
+            }
+        }
+        count
+    }
+}
+
+/// An ID argument: "ms-seq", or "ms" alone meaning "ms-0"
+fn parse_id_arg(arg: &[u8]) -> Option<StreamId> {
+    let s = std::str::from_utf8(arg).ok()?;
+    StreamId::from_string(s).or_else(|| s.parse().ok().map(|ms| StreamId::new(ms, 0)))
+}
+
+/// Options of an XADD, with the index of its ID argument
+struct XaddOptions {
+    nomkstream: bool,
+    trim: Option<Trim>,
+    id_index: usize,
+}
+
+/// Parse `key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] id`
+fn parse_xadd_options(args: &[Vec<u8>]) -> Result<XaddOptions, RespValue> {
+    let mut nomkstream = false;
+    let mut trim = None;
+    let mut i = 1;
+    while i < args.len() {
+        if args[i].eq_ignore_ascii_case(b"NOMKSTREAM") {
+            nomkstream = true;
+            i += 1;
+        } else if args[i].eq_ignore_ascii_case(b"MAXLEN") || args[i].eq_ignore_ascii_case(b"MINID") {
+            let (parsed, next) = Trim::parse(args, i)?;
+            trim = Some(parsed);
+            i = next;
+        } else {
+            break;
+        }
+    }
+    Ok(XaddOptions { nomkstream, trim, id_index: i })
+}
 
-    // If timestamp is same as last, increment sequence
-    // If timestamp is greater, reset sequence to 0
-    // If timestamp is less, error
-    if timestamp < stream.last_id.timestamp {
-        return Err("ERR The ID specified in XADD is equal or smaller than the target stream top item".to_string());
+/// ID for a new entry of `stream` from an XADD ID argument: "*", "ms-*"
+/// or an explicit ID
+fn next_stream_id(stream: &Stream, id_arg: &[u8]) -> Result<StreamId, String> {
+    let last = &stream.last_id;
+    let exhausted = || "ERR The stream has exhausted the last possible ID, unable to add more items".to_string();
+
+    if id_arg == b"*" {
+        // A clock behind the last ID keeps its millisecond and counts on
+        let now = current_timestamp_ms();
+        if now > last.timestamp {
+            return Ok(StreamId::new(now, 0));
+        }
+        return match last.sequence.checked_add(1) {
+            Some(seq) => Ok(StreamId::new(last.timestamp, seq)),
+            None => match last.timestamp.checked_add(1) {
+                Some(ms) => Ok(StreamId::new(ms, 0)),
+                None => Err(exhausted()),
+            },
+        };
     }
 
-    let sequence = if timestamp == stream.last_id.timestamp {
-        stream.last_id.sequence + 1
+    let id_str = std::str::from_utf8(id_arg).map_err(|_| INVALID_ID.to_string())?;
+    let id = if let Some(ms) = id_str.strip_suffix("-*") {
+        // Partial auto-generation: timestamp provided, generate sequence
+        let ms: u64 = ms.parse().map_err(|_| INVALID_ID.to_string())?;
+        match ms.cmp(&last.timestamp) {
+            std::cmp::Ordering::Less => return Err(TOP_ITEM.to_string()),
+            std::cmp::Ordering::Equal if stream.entries_added > 0 || last.sequence > 0 => {
+                StreamId::new(ms, last.sequence.checked_add(1).ok_or_else(|| TOP_ITEM.to_string())?)
+            }
+            // 0-* on a fresh stream starts at 0-1
+            _ if ms == 0 => StreamId::new(0, 1),
+            _ => StreamId::new(ms, 0),
+        }
     } else {
-        0
+        parse_id_arg(id_arg).ok_or_else(|| INVALID_ID.to_string())?
     };
 
-    Ok(StreamId::new(timestamp, sequence))
+    if id == StreamId::new(0, 0) {
+        return Err("ERR The ID specified in XADD must be greater than 0-0".to_string());
+    }
+    if id <= *last {
+        return Err(TOP_ITEM.to_string());
+    }
+    Ok(id)
 }
 
-/// XADD key ID field value [field value ...]
+/// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] <*|ms-*|id> field value [field value ...]
 /// Add entry to stream
 pub async fn xadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 4 || (args.len() - 2) % 2 != 0 {
+    if args.len() < 4 {
         return RespValue::Error("ERR wrong number of arguments for 'xadd' command".to_string());
     }
 
@@ -49,10 +323,14 @@ pub async fn xadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let id_str = match std::str::from_utf8(&args[1]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid ID".to_string()),
+    let options = match parse_xadd_options(&args) {
+        Ok(options) => options,
+        Err(e) => return e,
     };
+    let field_args = args.get(options.id_index + 1..).unwrap_or_default();
+    if field_args.is_empty() || !field_args.len().is_multiple_of(2) {
+        return RespValue::Error("ERR wrong number of arguments for 'xadd' command".to_string());
+    }
 
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
@@ -67,52 +345,21 @@ pub async fn xadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
+        None if options.nomkstream => return RespValue::BulkString(None),
         None => Stream::new(),
     };
 
     // Parse or generate ID
-    let id = if id_str == "*" {
-        // Auto-generate ID
-        match generate_stream_id(&stream, None) {
-            Ok(id) => id,
-            Err(e) => return RespValue::Error(e),
-        }
-    } else if id_str.ends_with("-*") {
-        // Partial auto-generation: timestamp provided, generate sequence
-        let ts_str = &id_str[..id_str.len() - 2];
-        let timestamp: u64 = match ts_str.parse() {
-            Ok(t) => t,
-            Err(_) => return RespValue::Error("ERR invalid ID".to_string()),
-        };
-        match generate_stream_id(&stream, Some(timestamp)) {
-            Ok(id) => id,
-            Err(e) => return RespValue::Error(e),
-        }
-    } else {
-        // Explicit ID
-        match StreamId::from_string(id_str) {
-            Some(id) => {
-                // Validate ID is greater than last
-                if id <= stream.last_id {
-                    return RespValue::Error(
-                        "ERR The ID specified in XADD is equal or smaller than the target stream top item".to_string(),
-                    );
-                }
-                id
-            }
-            None => return RespValue::Error("ERR invalid ID".to_string()),
-        }
+    let id = match next_stream_id(&stream, &args[options.id_index]) {
+        Ok(id) => id,
+        Err(e) => return RespValue::Error(e),
     };
 
-    // Parse field-value pairs
-    let mut fields = HashMap::new();
-    let mut i = 2;
-    while i < args.len() {
-        let field = Bytes::from(args[i].clone());
-        let value = Bytes::from(args[i + 1].clone());
-        fields.insert(field, value);
-        i += 2;
-    }
+    // Field-value pairs, in the order given
+    let fields = field_args
+        .chunks(2)
+        .map(|pair| (Bytes::from(pair[0].clone()), Bytes::from(pair[1].clone())))
+        .collect();
 
     // Create entry
     let entry = StreamEntry {
@@ -120,12 +367,18 @@ pub async fn xadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         fields,
     };
 
-    // Add to stream
+    // Add to stream, then trim it
     stream.entries.insert(id.clone(), entry);
     stream.last_id = id.clone();
+    stream.entries_added += 1;
+    if let Some(trim) = &options.trim {
+        trim.apply(&mut stream);
+    }
 
-    // Store stream
-    db_instance.set(key, RedisValue::Stream(stream));
+    // Store stream, then wake clients blocked on it
+    db_instance.set(key.clone(), RedisValue::Stream(stream));
+    db_instance.notify(EventClass::STREAM, "xadd", &key);
+    db.key_waiters().signal(db_index, &key);
 
     RespValue::BulkString(Some(id.to_string().into_bytes()))
 }
@@ -147,7 +400,7 @@ pub async fn xlen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::Stream(stream)) => RespValue::Integer(stream.len() as i64),
         Some(_) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -183,7 +436,7 @@ pub async fn xrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let stream = match db_instance.get(key) {
+    let stream = match db_instance.lookup_read(key) {
         Some(RedisValue::Stream(s)) => s,
         Some(_) => {
             return RespValue::Error(
@@ -272,23 +525,58 @@ pub async fn xdel(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
 
         if let Some(id) = StreamId::from_string(id_str) {
             if stream.entries.remove(&id).is_some() {
+                if id > stream.max_deleted_id {
+                    stream.max_deleted_id = id;
+                }
                 deleted += 1;
             }
         }
     }
 
-    // Store back
-    if stream.is_empty() {
+    // Store back; consumer groups keep an empty stream alive
+    if stream.is_empty() && stream.groups.is_empty() {
         db_instance.delete(&key);
     } else {
-        db_instance.set(key, RedisValue::Stream(stream));
+        db_instance.set(key.clone(), RedisValue::Stream(stream));
+    }
+    if deleted > 0 {
+        db_instance.notify(EventClass::STREAM, "xdel", &key);
     }
 
     RespValue::Integer(deleted)
 }
 
+/// Where XREAD starts reading a stream
+enum ReadStart {
+    /// Entries after this ID
+    After(StreamId),
+    /// The last entry ("+")
+    LastEntry,
+}
+
+/// Wait for a write to one of the keys `wait` is registered on. Returns
+/// false once `deadline` passes; no deadline waits forever. A resync may
+/// go ahead meanwhile.
+pub(crate) async fn wait_for_keys(wait: &KeyWait<'_>, deadline: Option<Instant>) -> bool {
+    write_gate::idle(async {
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), wait.notified()).await.is_ok(),
+            None => {
+                wait.notified().await;
+                true
+            }
+        }
+    })
+    .await
+}
+
+/// Deadline of a BLOCK argument in milliseconds, 0 meaning forever
+pub(crate) fn block_deadline(block_ms: u64) -> Option<Instant> {
+    (block_ms > 0).then(|| Instant::now() + Duration::from_millis(block_ms))
+}
+
 /// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] ID [ID ...]
-/// Read entries from streams
+/// Read entries from streams, waiting for new ones with BLOCK
 pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
     if args.is_empty() {
         return RespValue::Error("ERR wrong number of arguments for 'xread' command".to_string());
@@ -297,6 +585,7 @@ pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     let mut count: Option<usize> = None;
     let mut block_ms: Option<u64> = None;
     let mut i = 0;
+    let mut has_streams = false;
 
     // Parse options
     while i < args.len() {
@@ -322,14 +611,15 @@ pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 if i >= args.len() {
                     return RespValue::Error("ERR syntax error".to_string());
                 }
-                block_ms = match std::str::from_utf8(&args[i]) {
-                    Ok(s) => s.parse().ok(),
-                    Err(_) => return RespValue::Error("ERR invalid block time".to_string()),
+                block_ms = match std::str::from_utf8(&args[i]).ok().and_then(|s| s.parse().ok()) {
+                    Some(ms) => Some(ms),
+                    None => return RespValue::Error("ERR timeout is not an integer or out of range".to_string()),
                 };
                 i += 1;
             }
             "STREAMS" => {
                 i += 1;
+                has_streams = true;
                 break;
             }
             _ => break,
@@ -337,18 +627,24 @@ pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     }
 
     // Check for STREAMS keyword
-    if i >= args.len() {
+    if !has_streams || i >= args.len() {
         return RespValue::Error("ERR syntax error".to_string());
     }
 
     // Parse stream keys and IDs
     let remaining = &args[i..];
-    if remaining.len() % 2 != 0 {
-        return RespValue::Error("ERR syntax error".to_string());
+    if !remaining.len().is_multiple_of(2) {
+        return RespValue::Error(
+            "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+                .to_string(),
+        );
     }
 
     let num_streams = remaining.len() / 2;
-    let keys = &remaining[..num_streams];
+    let keys: Vec<String> = remaining[..num_streams]
+        .iter()
+        .map(|k| String::from_utf8_lossy(k).to_string())
+        .collect();
     let ids = &remaining[num_streams..];
 
     let db_instance = match db.get_db(db_index) {
@@ -356,61 +652,89 @@ pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Read from streams (simplified - no actual blocking implementation)
-    let mut result = Vec::new();
-
-    for (key_bytes, id_bytes) in keys.iter().zip(ids.iter()) {
-        let key = match std::str::from_utf8(key_bytes) {
-            Ok(s) => s,
-            Err(_) => continue,
+    // "$" and "+" are resolved once, when the client starts blocking
+    let mut starts = Vec::with_capacity(num_streams);
+    for (key, id_bytes) in keys.iter().zip(ids) {
+        let stream = match db_instance.get(key) {
+            Some(RedisValue::Stream(s)) => Some(s),
+            Some(_) => {
+                return RespValue::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                )
+            }
+            None => None,
+        };
+        let last_id = stream.as_ref().map_or(StreamId::new(0, 0), |s| s.last_id.clone());
+
+        let start = match id_bytes.as_slice() {
+            b"$" => ReadStart::After(last_id),
+            // With no entry to return, wait for the next one like "$"
+            b"+" if stream.as_ref().is_some_and(|s| !s.is_empty()) => ReadStart::LastEntry,
+            b"+" => ReadStart::After(last_id),
+            _ => {
+                let id_str = String::from_utf8_lossy(id_bytes);
+                let id = StreamId::from_string(&id_str)
+                    .or_else(|| id_str.parse().ok().map(|ms| StreamId::new(ms, 0)));
+                match id {
+                    Some(id) => ReadStart::After(id),
+                    None => {
+                        return RespValue::Error(
+                            "ERR Invalid stream ID specified as stream command argument".to_string(),
+                        )
+                    }
+                }
+            }
         };
+        starts.push(start);
+    }
 
-        let id_str = match std::str::from_utf8(id_bytes) {
-            Ok(s) => s,
-            Err(_) => continue,
+    let deadline = block_ms.and_then(block_deadline);
+    loop {
+        // Register before reading, so an XADD in between still wakes us
+        let wait = block_ms.map(|_| db.key_waiters().register(db_index, &keys));
+
+        let db_instance = match db.get_db(db_index) {
+            Some(d) => d,
+            None => return RespValue::Error("ERR invalid database".to_string()),
         };
+        let result = read_streams(&db_instance, &keys, &starts, count);
+        if !result.is_empty() {
+            return RespValue::Array(Some(result));
+        }
+
+        match wait {
+            Some(wait) if wait_for_keys(&wait, deadline).await => continue,
+            _ => return RespValue::Array(None),
+        }
+    }
+}
+
+/// One XREAD pass: [key, entries] for every stream with something to read
+fn read_streams(db_instance: &DbInstance, keys: &[String], starts: &[ReadStart], count: Option<usize>) -> Vec<RespValue> {
+    let mut result = Vec::new();
 
+    for (key, start) in keys.iter().zip(starts) {
         let stream = match db_instance.get(key) {
             Some(RedisValue::Stream(s)) => s,
             _ => continue,
         };
 
-        // Parse start ID
-        let start_id = if id_str == "$" {
-            // $ means read from latest
-            stream.last_id.clone()
-        } else {
-            match StreamId::from_string(id_str) {
-                Some(id) => id,
-                None => continue,
-            }
+        let entries: Vec<RespValue> = match start {
+            ReadStart::LastEntry => stream
+                .entries
+                .iter()
+                .next_back()
+                .map(|(id, entry)| entry_to_resp(id, entry))
+                .into_iter()
+                .collect(),
+            ReadStart::After(start_id) => stream
+                .entries
+                .range((std::ops::Bound::Excluded(start_id), std::ops::Bound::Unbounded))
+                .take(count.unwrap_or(usize::MAX))
+                .map(|(id, entry)| entry_to_resp(id, entry))
+                .collect(),
         };
 
-        // Collect entries after start_id
-        let mut entries = Vec::new();
-        let mut collected = 0;
-
-        for (id, entry) in stream.entries.range((std::ops::Bound::Excluded(&start_id), std::ops::Bound::Unbounded)) {
-            if let Some(max) = count {
-                if collected >= max {
-                    break;
-                }
-            }
-
-            let mut entry_array = Vec::new();
-            entry_array.push(RespValue::BulkString(Some(id.to_string().into_bytes())));
-
-            let mut fields_array = Vec::new();
-            for (field, value) in &entry.fields {
-                fields_array.push(RespValue::BulkString(Some(field.to_vec())));
-                fields_array.push(RespValue::BulkString(Some(value.to_vec())));
-            }
-            entry_array.push(RespValue::Array(Some(fields_array)));
-
-            entries.push(RespValue::Array(Some(entry_array)));
-            collected += 1;
-        }
-
         if !entries.is_empty() {
             result.push(RespValue::Array(Some(vec![
                 RespValue::BulkString(Some(key.as_bytes().to_vec())),
@@ -419,12 +743,7 @@ pub async fn xread(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         }
     }
 
-    if result.is_empty() && block_ms.is_some() {
-        // Simplified: return null for blocking timeout
-        return RespValue::Null;
-    }
-
-    RespValue::Array(Some(result))
+    result
 }
 
 /// XREVRANGE key end start [COUNT count]
@@ -475,7 +794,7 @@ pub async fn xrevrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(&key) {
+    match db_instance.lookup_read(&key) {
         Some(RedisValue::Stream(stream)) => {
             // Collect entries in reverse order
             let entries: Vec<_> = stream
@@ -523,8 +842,8 @@ pub async fn xrevrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>)
     }
 }
 
-/// XTRIM key MAXLEN [~] count
-/// Trim stream to approximate maxlen
+/// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+/// Trim stream from its head
 pub async fn xtrim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
     if args.len() < 3 {
         return RespValue::Error("ERR wrong number of arguments for 'xtrim' command".to_string());
@@ -535,43 +854,13 @@ pub async fn xtrim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    // Check for MAXLEN strategy
-    let strategy = match std::str::from_utf8(&args[1]) {
-        Ok(s) => s.to_uppercase(),
-        Err(_) => return RespValue::Error("ERR invalid strategy".to_string()),
-    };
-
-    if strategy != "MAXLEN" {
-        return RespValue::Error("ERR unsupported trim strategy".to_string());
-    }
-
-    // Check for approximate trim (~)
-    let mut arg_idx = 2;
-    let _approximate = if args.len() > 3 {
-        if let Ok(s) = std::str::from_utf8(&args[2]) {
-            if s == "~" {
-                arg_idx = 3;
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    } else {
-        false
-    };
-
-    if arg_idx >= args.len() {
+    if !args[1].eq_ignore_ascii_case(b"MAXLEN") && !args[1].eq_ignore_ascii_case(b"MINID") {
         return RespValue::Error("ERR syntax error".to_string());
     }
-
-    let maxlen = match std::str::from_utf8(&args[arg_idx]) {
-        Ok(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR invalid maxlen value".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR invalid maxlen value".to_string()),
+    let trim = match Trim::parse(&args, 1) {
+        Ok((trim, next)) if next == args.len() => trim,
+        Ok(_) => return RespValue::Error("ERR syntax error".to_string()),
+        Err(e) => return e,
     };
 
     let db_instance = match db.get_db(db_index) {
@@ -581,23 +870,12 @@ pub async fn xtrim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
 
     match db_instance.get(&key) {
         Some(RedisValue::Stream(mut stream)) => {
-            let original_len = stream.entries.len();
-
-            // If stream is already smaller than maxlen, no trimming needed
-            if original_len <= maxlen {
-                return RespValue::Integer(0);
-            }
-
-            // Keep only the last maxlen entries
-            let to_remove = original_len - maxlen;
-            let ids_to_remove: Vec<_> = stream.entries.keys().take(to_remove).cloned().collect();
-
-            for id in ids_to_remove {
-                stream.entries.remove(&id);
+            let removed = trim.apply(&mut stream);
+            if removed > 0 {
+                db_instance.set(key.clone(), RedisValue::Stream(stream));
+                db_instance.notify(EventClass::STREAM, "xtrim", &key);
             }
-
-            db_instance.set(key, RedisValue::Stream(stream));
-            RespValue::Integer(to_remove as i64)
+            RespValue::Integer(removed as i64)
         }
         Some(_) => RespValue::Error(
             "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
@@ -606,6 +884,87 @@ pub async fn xtrim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     }
 }
 
+/// XSETID key last-id [ENTRIESADDED entries-added] [MAXDELETEDID max-deleted-id]
+/// Set the stream's last ID and counters
+pub async fn xsetid(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 2 {
+        return RespValue::Error("ERR wrong number of arguments for 'xsetid' command".to_string());
+    }
+
+    let key = String::from_utf8_lossy(&args[0]).to_string();
+    let last_id = match parse_id_arg(&args[1]) {
+        Some(id) => id,
+        None => return RespValue::Error(INVALID_ID.to_string()),
+    };
+
+    let mut entries_added = None;
+    let mut max_deleted_id = None;
+    let mut i = 2;
+    while i < args.len() {
+        let Some(value) = args.get(i + 1) else {
+            return RespValue::Error("ERR syntax error".to_string());
+        };
+        if args[i].eq_ignore_ascii_case(b"ENTRIESADDED") {
+            entries_added = match std::str::from_utf8(value).ok().and_then(|s| s.parse::<i64>().ok()) {
+                Some(n) if n >= 0 => Some(n as u64),
+                Some(_) => {
+                    return RespValue::Error("ERR entries_added must be positive".to_string())
+                }
+                None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+            };
+        } else if args[i].eq_ignore_ascii_case(b"MAXDELETEDID") {
+            match parse_id_arg(value) {
+                Some(id) if id > last_id => {
+                    return RespValue::Error(
+                        "ERR The ID specified in XSETID is smaller than the provided max_deleted_entry_id"
+                            .to_string(),
+                    )
+                }
+                Some(id) => max_deleted_id = Some(id),
+                None => return RespValue::Error(INVALID_ID.to_string()),
+            }
+        } else {
+            return RespValue::Error("ERR syntax error".to_string());
+        }
+        i += 2;
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let mut stream = match db_instance.get(&key) {
+        Some(RedisValue::Stream(s)) => s,
+        Some(_) => {
+            return RespValue::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            )
+        }
+        None => return RespValue::Error("ERR no such key".to_string()),
+    };
+
+    if entries_added.is_some_and(|n| n < stream.len() as u64) {
+        return RespValue::Error(
+            "ERR The entries_added specified in XSETID is smaller than the target stream length".to_string(),
+        );
+    }
+    // The last ID can't go below an entry still in the stream
+    if stream.entries.keys().next_back().is_some_and(|top| last_id < *top) {
+        return RespValue::Error("ERR The ID specified in XSETID is smaller than the target stream top item".to_string());
+    }
+
+    stream.last_id = last_id;
+    if let Some(n) = entries_added {
+        stream.entries_added = n;
+    }
+    if let Some(id) = max_deleted_id {
+        stream.max_deleted_id = id;
+    }
+    db_instance.set(key.clone(), RedisValue::Stream(stream));
+    db_instance.notify(EventClass::STREAM, "xsetid", &key);
+    RespValue::SimpleString("OK".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -703,4 +1062,195 @@ mod tests {
         let result = xlen(&db, 0, vec![b"mystream".to_vec()]).await;
         assert_eq!(result, RespValue::Integer(0));
     }
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_wakes_on_xadd() {
+        let db = Arc::new(Database::new(16));
+        xadd(&db, 0, args(&["s", "1-0", "f", "old"])).await;
+
+        // "$" is the last ID when blocking starts, so only the new entry shows
+        let reader = {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move { xread(&db, 0, args(&["BLOCK", "0", "STREAMS", "other", "s", "$", "$"])).await })
+        };
+        while db.key_waiters().waiting(0, "s") == 0 {
+            tokio::task::yield_now().await;
+        }
+        xadd(&db, 0, args(&["s", "2-0", "f", "new"])).await;
+
+        let reply = tokio::time::timeout(Duration::from_secs(5), reader).await.unwrap().unwrap();
+        let entry = RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"2-0".to_vec())),
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"f".to_vec())),
+                RespValue::BulkString(Some(b"new".to_vec())),
+            ])),
+        ]));
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"s".to_vec())),
+                RespValue::Array(Some(vec![entry])),
+            ]))]))
+        );
+        assert_eq!(db.key_waiters().waiting(0, "s"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_timeout_and_last_entry() {
+        let db = Arc::new(Database::new(16));
+
+        let reply = xread(&db, 0, args(&["BLOCK", "20", "STREAMS", "s", "$"])).await;
+        assert_eq!(reply, RespValue::Array(None));
+        let reply = xread(&db, 0, args(&["STREAMS", "s", "0"])).await;
+        assert_eq!(reply, RespValue::Array(None));
+
+        // "+" reads the last entry
+        xadd(&db, 0, args(&["s", "1-0", "f", "a"])).await;
+        xadd(&db, 0, args(&["s", "2-0", "f", "b"])).await;
+        let RespValue::Array(Some(streams)) = xread(&db, 0, args(&["STREAMS", "s", "+"])).await else {
+            panic!("expected entries");
+        };
+        let RespValue::Array(Some(stream)) = &streams[0] else {
+            panic!("unexpected stream");
+        };
+        let RespValue::Array(Some(entries)) = &stream[1] else {
+            panic!("unexpected entries");
+        };
+        assert_eq!(entries.len(), 1);
+        let last = StreamEntry {
+            id: StreamId::new(2, 0),
+            fields: [(Bytes::from("f"), Bytes::from("b"))].into_iter().collect(),
+        };
+        assert_eq!(entries[0], entry_to_resp(&last.id, &last));
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_block() {
+        let db = Arc::new(Database::new(16));
+        xadd(&db, 0, args(&["s", "1-0", "f", "a"])).await;
+        crate::commands::stream_group::xgroup(&db, 0, args(&["CREATE", "s", "g", "$"])).await;
+
+        let reader = {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move {
+                let cmd = ["GROUP", "g", "c", "BLOCK", "5000", "STREAMS", "s", ">"];
+                crate::commands::stream_group::xreadgroup(&db, 0, args(&cmd)).await
+            })
+        };
+        while db.key_waiters().waiting(0, "s") == 0 {
+            tokio::task::yield_now().await;
+        }
+        xadd(&db, 0, args(&["s", "2-0", "f", "b"])).await;
+
+        let reply = tokio::time::timeout(Duration::from_secs(5), reader).await.unwrap().unwrap();
+        assert!(matches!(reply, RespValue::Array(Some(ref streams)) if streams.len() == 1));
+
+        // Replaying must not block
+        let logged = propagated_commands(
+            &args(&["XREADGROUP", "GROUP", "BLOCK", "c", "BLOCK", "0", "STREAMS", "s", ">"]),
+            &reply,
+        );
+        assert_eq!(logged, vec![args(&["XREADGROUP", "GROUP", "BLOCK", "c", "STREAMS", "s", ">"])]);
+    }
+
+    fn stream(db: &Arc<Database>, key: &str) -> Stream {
+        match db.get_db(0).unwrap().get(key) {
+            Some(RedisValue::Stream(s)) => s,
+            other => panic!("not a stream: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_xadd_keeps_field_order() {
+        let db = Arc::new(Database::new(16));
+        xadd(&db, 0, args(&["s", "1-0", "z", "1", "a", "2", "m", "3"])).await;
+
+        let fields: Vec<_> = stream(&db, "s").entries[&StreamId::new(1, 0)]
+            .fields
+            .iter()
+            .map(|(f, _)| f.clone())
+            .collect();
+        assert_eq!(fields, vec![Bytes::from("z"), Bytes::from("a"), Bytes::from("m")]);
+    }
+
+    #[tokio::test]
+    async fn test_xadd_ids_and_options() {
+        let db = Arc::new(Database::new(16));
+
+        assert_eq!(xadd(&db, 0, args(&["s", "NOMKSTREAM", "*", "f", "v"])).await, RespValue::BulkString(None));
+        assert!(!db.get_db(0).unwrap().exists("s"));
+
+        let reply = xadd(&db, 0, args(&["s", "0-0", "f", "v"])).await;
+        assert_eq!(reply, RespValue::Error("ERR The ID specified in XADD must be greater than 0-0".to_string()));
+        assert_eq!(xadd(&db, 0, args(&["s", "0-*", "f", "v"])).await, RespValue::BulkString(Some(b"0-1".to_vec())));
+        assert_eq!(xadd(&db, 0, args(&["s", "5-*", "f", "v"])).await, RespValue::BulkString(Some(b"5-0".to_vec())));
+        assert_eq!(xadd(&db, 0, args(&["s", "5-*", "f", "v"])).await, RespValue::BulkString(Some(b"5-1".to_vec())));
+        assert_eq!(xadd(&db, 0, args(&["s", "4-*", "f", "v"])).await, RespValue::Error(TOP_ITEM.to_string()));
+
+        // Trimming applies after adding
+        xadd(&db, 0, args(&["s", "MAXLEN", "=", "2", "6-0", "f", "v"])).await;
+        let trimmed = stream(&db, "s");
+        assert_eq!(trimmed.len(), 2);
+        assert_eq!(trimmed.entries_added, 4);
+        assert_eq!(trimmed.max_deleted_id, StreamId::new(5, 0));
+
+        xadd(&db, 0, args(&["s", "MINID", "6", "7-0", "f", "v"])).await;
+
+        // Replicas get the generated ID, whatever options come before it
+        let logged = propagated_commands(
+            &args(&["XADD", "s", "MAXLEN", "~", "5", "LIMIT", "0", "*", "f", "v"]),
+            &RespValue::BulkString(Some(b"9-0".to_vec())),
+        );
+        assert_eq!(logged, vec![args(&["XADD", "s", "MAXLEN", "~", "5", "LIMIT", "0", "9-0", "f", "v"])]);
+        let ids: Vec<_> = stream(&db, "s").entries.keys().cloned().collect();
+        assert_eq!(ids, vec![StreamId::new(6, 0), StreamId::new(7, 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_xtrim_strategies() {
+        let db = Arc::new(Database::new(16));
+        for i in 1..=250 {
+            xadd(&db, 0, args(&["s", &format!("{}-0", i), "f", "v"])).await;
+        }
+
+        // Approximate trimming only removes whole nodes
+        assert_eq!(xtrim(&db, 0, args(&["s", "MAXLEN", "~", "120"])).await, RespValue::Integer(100));
+        assert_eq!(xtrim(&db, 0, args(&["s", "MAXLEN", "~", "0", "LIMIT", "50"])).await, RespValue::Integer(0));
+        assert_eq!(stream(&db, "s").len(), 150);
+
+        assert_eq!(xtrim(&db, 0, args(&["s", "MINID", "200"])).await, RespValue::Integer(99));
+        assert_eq!(xtrim(&db, 0, args(&["s", "MAXLEN", "10"])).await, RespValue::Integer(41));
+        assert_eq!(stream(&db, "s").max_deleted_id, StreamId::new(240, 0));
+
+        let reply = xtrim(&db, 0, args(&["s", "MAXLEN", "10", "LIMIT", "5"])).await;
+        assert_eq!(
+            reply,
+            RespValue::Error("ERR syntax error, LIMIT cannot be used without the special ~ option".to_string())
+        );
+        assert!(matches!(xtrim(&db, 0, args(&["s", "COUNT", "1"])).await, RespValue::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_xsetid() {
+        let db = Arc::new(Database::new(16));
+        let reply = xsetid(&db, 0, args(&["s", "5-0"])).await;
+        assert_eq!(reply, RespValue::Error("ERR no such key".to_string()));
+
+        xadd(&db, 0, args(&["s", "3-0", "f", "v"])).await;
+        assert!(matches!(xsetid(&db, 0, args(&["s", "2-0"])).await, RespValue::Error(_)));
+        assert!(matches!(xsetid(&db, 0, args(&["s", "5-0", "MAXDELETEDID", "6-0"])).await, RespValue::Error(_)));
+        assert!(matches!(xsetid(&db, 0, args(&["s", "5-0", "ENTRIESADDED", "0"])).await, RespValue::Error(_)));
+
+        let reply = xsetid(&db, 0, args(&["s", "10-5", "ENTRIESADDED", "7", "MAXDELETEDID", "9-0"])).await;
+        assert_eq!(reply, RespValue::SimpleString("OK".to_string()));
+        let updated = stream(&db, "s");
+        assert_eq!((updated.last_id.clone(), updated.entries_added), (StreamId::new(10, 5), 7));
+        assert_eq!(updated.max_deleted_id, StreamId::new(9, 0));
+        assert_eq!(xadd(&db, 0, args(&["s", "10-*", "f", "v"])).await, RespValue::BulkString(Some(b"10-6".to_vec())));
+    }
 }
@@ -0,0 +1,1276 @@
+// Stream consumer group commands
+// XGROUP, XREADGROUP, XACK, XPENDING, XCLAIM, XAUTOCLAIM and XINFO
+//
+// A group remembers the last entry it delivered and keeps every entry
+// delivered but not acknowledged in its pending entries list (PEL), along
+// with the consumer that owns it. Consumers re-read their own PEL with an
+// explicit ID, and entries left pending too long can be claimed by others.
+
+use super::stream::{block_deadline, current_timestamp_ms, entry_to_resp, wait_for_keys};
+use crate::protocol::RespValue;
+use crate::storage::db::{Database, DbInstance};
+use crate::storage::notify::EventClass;
+use crate::storage::types::{Consumer, ConsumerGroup, PendingEntry, RedisValue, Stream, StreamEntry, StreamId};
+use std::ops::Bound;
+use std::sync::Arc;
+
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+const INVALID_ID: &str = "ERR Invalid stream ID specified as stream command argument";
+const NO_KEY: &str = "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.";
+
+fn bulk(data: impl Into<Vec<u8>>) -> RespValue {
+    RespValue::BulkString(Some(data.into()))
+}
+
+fn arg_str(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_string()
+}
+
+fn wrong_args(cmd: &str) -> RespValue {
+    RespValue::Error(format!("ERR wrong number of arguments for '{}' command", cmd))
+}
+
+fn no_group(key: &str, group: &str) -> RespValue {
+    RespValue::Error(format!("NOGROUP No such key '{}' or consumer group '{}'", key, group))
+}
+
+fn parse_u64(arg: &[u8]) -> Option<u64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// Parse "ms-seq", or "ms" alone meaning "ms-<missing_seq>"
+fn parse_id(arg: &[u8], missing_seq: u64) -> Option<StreamId> {
+    let s = std::str::from_utf8(arg).ok()?;
+    match s.split_once('-') {
+        Some((ms, seq)) => Some(StreamId::new(ms.parse().ok()?, seq.parse().ok()?)),
+        None => Some(StreamId::new(s.parse().ok()?, missing_seq)),
+    }
+}
+
+/// Start of an ID interval: "-", an ID, or "(ID" for an exclusive bound
+fn parse_range_start(arg: &[u8]) -> Option<StreamId> {
+    match arg {
+        b"-" => Some(StreamId::new(0, 0)),
+        [b'(', id @ ..] => {
+            let id = parse_id(id, 0)?;
+            if id.sequence < u64::MAX {
+                Some(StreamId::new(id.timestamp, id.sequence + 1))
+            } else {
+                Some(StreamId::new(id.timestamp.checked_add(1)?, 0))
+            }
+        }
+        _ => parse_id(arg, 0),
+    }
+}
+
+/// End of an ID interval: "+", an ID, or "(ID" for an exclusive bound
+fn parse_range_end(arg: &[u8]) -> Option<StreamId> {
+    match arg {
+        b"+" => Some(StreamId::new(u64::MAX, u64::MAX)),
+        [b'(', id @ ..] => {
+            let id = parse_id(id, u64::MAX)?;
+            if id.sequence > 0 {
+                Some(StreamId::new(id.timestamp, id.sequence - 1))
+            } else {
+                Some(StreamId::new(id.timestamp.checked_sub(1)?, u64::MAX))
+            }
+        }
+        _ => parse_id(arg, u64::MAX),
+    }
+}
+
+/// The stream at `key`, None if there is no such key
+fn load_stream(db_instance: &DbInstance, key: &str) -> Result<Option<Stream>, RespValue> {
+    match db_instance.get(key) {
+        Some(RedisValue::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(RespValue::Error(WRONGTYPE.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Whether entries at or after `from` were deleted
+fn has_tombstones_after(stream: &Stream, from: &StreamId) -> bool {
+    !stream.is_empty() && stream.max_deleted_id != StreamId::new(0, 0) && stream.max_deleted_id >= *from
+}
+
+/// Number of entries added up to and including `id`, when it can be told
+/// without walking the stream
+fn estimate_entries_read(stream: &Stream, id: &StreamId) -> Option<u64> {
+    if stream.entries_added == 0 {
+        return Some(0);
+    }
+    if stream.is_empty() && *id <= stream.last_id {
+        return Some(stream.entries_added);
+    }
+    match id.cmp(&stream.last_id) {
+        std::cmp::Ordering::Equal => return Some(stream.entries_added),
+        std::cmp::Ordering::Greater => return None,
+        std::cmp::Ordering::Less => {}
+    }
+
+    // Without deletions past the first entry, everything before it is
+    // gone from the front
+    let first = stream.entries.keys().next()?;
+    if stream.max_deleted_id == StreamId::new(0, 0) || stream.max_deleted_id < *first {
+        let before_first = stream.entries_added - stream.len() as u64;
+        match id.cmp(first) {
+            std::cmp::Ordering::Less => return Some(before_first),
+            std::cmp::Ordering::Equal => return Some(before_first + 1),
+            std::cmp::Ordering::Greater => {}
+        }
+    }
+    None
+}
+
+/// Entries the group has yet to read, None if unknown
+fn group_lag(stream: &Stream, group: &ConsumerGroup) -> Option<u64> {
+    let read = match group.entries_read {
+        Some(read) if !has_tombstones_after(stream, &group.last_delivered_id) => read,
+        _ => estimate_entries_read(stream, &group.last_delivered_id)?,
+    };
+    Some(stream.entries_added.saturating_sub(read))
+}
+
+/// The consumer `name`, created if needed, marked as seen now
+fn touch_consumer<'a>(group: &'a mut ConsumerGroup, name: &str, now: u64) -> &'a mut Consumer {
+    let consumer = group
+        .consumers
+        .entry(name.to_string())
+        .or_insert_with(|| Consumer::new(now));
+    consumer.seen_time = now;
+    consumer
+}
+
+/// Give a pending entry to `consumer`, taking it from its previous owner
+fn assign_pending(group: &mut ConsumerGroup, id: &StreamId, consumer: &str, delivery_time: u64, delivery_count: u64) {
+    if let Some(previous) = group.pending.get(id) {
+        if previous.consumer != consumer {
+            if let Some(owner) = group.consumers.get_mut(&previous.consumer) {
+                owner.pending.remove(id);
+            }
+        }
+    }
+    group.pending.insert(
+        id.clone(),
+        PendingEntry {
+            consumer: consumer.to_string(),
+            delivery_time,
+            delivery_count,
+        },
+    );
+    if let Some(owner) = group.consumers.get_mut(consumer) {
+        owner.pending.insert(id.clone());
+    }
+}
+
+/// Drop an entry from the group's PEL and its consumer's
+fn remove_pending(group: &mut ConsumerGroup, id: &StreamId) -> bool {
+    match group.pending.remove(id) {
+        Some(entry) => {
+            if let Some(owner) = group.consumers.get_mut(&entry.consumer) {
+                owner.pending.remove(id);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// XGROUP CREATE|SETID|DESTROY|CREATECONSUMER|DELCONSUMER ...
+pub async fn xgroup(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.is_empty() {
+        return wrong_args("xgroup");
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let subcommand = arg_str(&args[0]).to_uppercase();
+    let (min_args, max_args) = match subcommand.as_str() {
+        "CREATE" => (4, 7),
+        "SETID" => (4, 6),
+        "DESTROY" => (3, 3),
+        "CREATECONSUMER" | "DELCONSUMER" => (4, 4),
+        _ => {
+            return RespValue::Error(format!(
+                "ERR unknown subcommand '{}'. Try XGROUP HELP.",
+                arg_str(&args[0])
+            ))
+        }
+    };
+    if args.len() < min_args || args.len() > max_args {
+        return wrong_args(&format!("xgroup|{}", subcommand.to_lowercase()));
+    }
+
+    let key = arg_str(&args[1]);
+    let group_name = arg_str(&args[2]);
+
+    // CREATE and SETID options
+    let mut mkstream = false;
+    let mut entries_read = None;
+    let mut i = 4;
+    while i < args.len() {
+        let option = arg_str(&args[i]).to_uppercase();
+        if option == "MKSTREAM" && subcommand == "CREATE" {
+            mkstream = true;
+            i += 1;
+        } else if option == "ENTRIESREAD" && i + 1 < args.len() {
+            entries_read = match std::str::from_utf8(&args[i + 1]).ok().and_then(|n| n.parse::<i64>().ok()) {
+                Some(-1) => None,
+                Some(n) if n >= 0 => Some(n as u64),
+                _ => return RespValue::Error("ERR value for ENTRIESREAD must be positive or -1".to_string()),
+            };
+            i += 2;
+        } else {
+            return RespValue::Error("ERR syntax error".to_string());
+        }
+    }
+
+    let mut stream = match load_stream(&db_instance, &key) {
+        Ok(Some(stream)) => stream,
+        Ok(None) if mkstream => Stream::new(),
+        Ok(None) => return RespValue::Error(NO_KEY.to_string()),
+        Err(e) => return e,
+    };
+
+    // ID argument of CREATE and SETID
+    let id = if matches!(subcommand.as_str(), "CREATE" | "SETID") {
+        if args[3] == b"$" {
+            Some(stream.last_id.clone())
+        } else {
+            match parse_id(&args[3], 0) {
+                Some(id) => Some(id),
+                None => return RespValue::Error(INVALID_ID.to_string()),
+            }
+        }
+    } else {
+        None
+    };
+
+    if subcommand == "CREATE" {
+        if stream.groups.contains_key(&group_name) {
+            return RespValue::Error("BUSYGROUP Consumer Group name already exists".to_string());
+        }
+        stream.groups.insert(group_name, ConsumerGroup::new(id.unwrap(), entries_read));
+        db_instance.set(key.clone(), RedisValue::Stream(stream));
+        db_instance.notify(EventClass::STREAM, "xgroup-create", &key);
+        return RespValue::SimpleString("OK".to_string());
+    }
+
+    let group = match stream.groups.get_mut(&group_name) {
+        Some(group) => group,
+        None if subcommand == "DESTROY" => return RespValue::Integer(0),
+        None => {
+            return RespValue::Error(format!(
+                "NOGROUP No such consumer group '{}' for key name '{}'",
+                group_name, key
+            ))
+        }
+    };
+
+    let (reply, event) = match subcommand.as_str() {
+        "SETID" => {
+            group.last_delivered_id = id.unwrap();
+            group.entries_read = entries_read;
+            (RespValue::SimpleString("OK".to_string()), "xgroup-setid")
+        }
+        "CREATECONSUMER" => {
+            let name = arg_str(&args[3]);
+            if group.consumers.contains_key(&name) {
+                return RespValue::Integer(0);
+            }
+            group.consumers.insert(name, Consumer::new(current_timestamp_ms()));
+            (RespValue::Integer(1), "xgroup-createconsumer")
+        }
+        "DELCONSUMER" => {
+            // Its pending entries are dropped with it
+            let pending = match group.consumers.remove(&arg_str(&args[3])) {
+                Some(consumer) => consumer.pending,
+                None => return RespValue::Integer(0),
+            };
+            for id in &pending {
+                group.pending.remove(id);
+            }
+            (RespValue::Integer(pending.len() as i64), "xgroup-delconsumer")
+        }
+        _ => {
+            stream.groups.remove(&group_name);
+            (RespValue::Integer(1), "xgroup-destroy")
+        }
+    };
+
+    db_instance.set(key.clone(), RedisValue::Stream(stream));
+    db_instance.notify(EventClass::STREAM, event, &key);
+    reply
+}
+
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK ms] [NOACK] STREAMS key [key ...] id [id ...]
+pub async fn xreadgroup(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 6 {
+        return wrong_args("xreadgroup");
+    }
+    if !args[0].eq_ignore_ascii_case(b"GROUP") {
+        return RespValue::Error("ERR syntax error".to_string());
+    }
+    let group_name = arg_str(&args[1]);
+    let consumer_name = arg_str(&args[2]);
+
+    let mut count = usize::MAX;
+    let mut noack = false;
+    let mut block_ms = None;
+    let mut i = 3;
+    loop {
+        let Some(arg) = args.get(i) else {
+            return RespValue::Error("ERR syntax error".to_string());
+        };
+        match arg_str(arg).to_uppercase().as_str() {
+            "COUNT" if i + 1 < args.len() => {
+                count = match parse_u64(&args[i + 1]) {
+                    Some(0) => usize::MAX,
+                    Some(n) => n as usize,
+                    None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+                };
+                i += 2;
+            }
+            "BLOCK" if i + 1 < args.len() => {
+                block_ms = match parse_u64(&args[i + 1]) {
+                    Some(ms) => Some(ms),
+                    None => return RespValue::Error("ERR timeout is not an integer or out of range".to_string()),
+                };
+                i += 2;
+            }
+            "NOACK" => {
+                noack = true;
+                i += 1;
+            }
+            "STREAMS" => {
+                i += 1;
+                break;
+            }
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+    }
+
+    let remaining = &args[i..];
+    if remaining.is_empty() || !remaining.len().is_multiple_of(2) {
+        return RespValue::Error(
+            "ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified."
+                .to_string(),
+        );
+    }
+    let (keys, ids) = remaining.split_at(remaining.len() / 2);
+
+    // Only reads of new entries wait; history is served right away
+    let blocking = block_ms.is_some() && ids.iter().all(|id| id == b">");
+    let key_names: Vec<String> = keys.iter().map(|k| arg_str(k)).collect();
+    let deadline = block_ms.and_then(block_deadline);
+    loop {
+        // Register before reading, so an XADD in between still wakes us
+        let wait = blocking.then(|| db.key_waiters().register(db_index, &key_names));
+        let read = GroupRead {
+            group: &group_name,
+            consumer: &consumer_name,
+            count,
+            noack,
+        };
+        let reply = read_group(db, db_index, &read, keys, ids);
+        match (reply, wait) {
+            (RespValue::Array(None), Some(wait)) if wait_for_keys(&wait, deadline).await => continue,
+            (reply, _) => return reply,
+        }
+    }
+}
+
+/// Group, consumer and options of an XREADGROUP
+struct GroupRead<'a> {
+    group: &'a str,
+    consumer: &'a str,
+    count: usize,
+    noack: bool,
+}
+
+/// One XREADGROUP pass over all the streams; a nil reply when none had
+/// anything to deliver
+fn read_group(db: &Arc<Database>, db_index: usize, read: &GroupRead, keys: &[Vec<u8>], ids: &[Vec<u8>]) -> RespValue {
+    let GroupRead { group: group_name, consumer: consumer_name, count, noack } = *read;
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    // Check every stream before touching any
+    let mut requests = Vec::with_capacity(keys.len());
+    for (key, id) in keys.iter().zip(ids) {
+        let key = arg_str(key);
+        let stream = match load_stream(&db_instance, &key) {
+            Ok(Some(stream)) if stream.groups.contains_key(group_name) => stream,
+            Ok(_) => {
+                return RespValue::Error(format!(
+                    "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    key, group_name
+                ))
+            }
+            Err(e) => return e,
+        };
+        let start = if id == b">" {
+            None
+        } else {
+            match parse_id(id, 0) {
+                Some(id) => Some(id),
+                None => return RespValue::Error(INVALID_ID.to_string()),
+            }
+        };
+        requests.push((key, stream, start));
+    }
+
+    let now = current_timestamp_ms();
+    let mut result = Vec::new();
+    for (key, mut stream, start) in requests {
+        let is_history = start.is_some();
+        let entries = match start {
+            None => read_new_entries(&mut stream, group_name, consumer_name, count, noack, now),
+            Some(start) => read_pending_entries(&mut stream, group_name, consumer_name, &start, count, now),
+        };
+        db_instance.set(key.clone(), RedisValue::Stream(stream));
+
+        // New entries only show up for streams that had some
+        if is_history || !entries.is_empty() {
+            result.push(RespValue::Array(Some(vec![bulk(key), RespValue::Array(Some(entries))])));
+        }
+    }
+
+    if result.is_empty() {
+        RespValue::Array(None)
+    } else {
+        RespValue::Array(Some(result))
+    }
+}
+
+/// Deliver entries the group has not seen yet to `consumer_name`
+fn read_new_entries(
+    stream: &mut Stream,
+    group_name: &str,
+    consumer_name: &str,
+    count: usize,
+    noack: bool,
+    now: u64,
+) -> Vec<RespValue> {
+    let last_delivered = stream.groups[group_name].last_delivered_id.clone();
+    let ids: Vec<StreamId> = stream
+        .entries
+        .range((Bound::Excluded(last_delivered), Bound::Unbounded))
+        .map(|(id, _)| id.clone())
+        .take(count)
+        .collect();
+
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in &ids {
+        // Keep counting reads while no deleted entry lies ahead
+        let entries_read = match stream.groups[group_name].entries_read {
+            Some(read) if !has_tombstones_after(stream, id) => Some(read + 1),
+            _ => estimate_entries_read(stream, id),
+        };
+        entries.push(entry_to_resp(id, &stream.entries[id]));
+
+        let group = stream.groups.get_mut(group_name).unwrap();
+        group.entries_read = entries_read;
+        group.last_delivered_id = id.clone();
+        touch_consumer(group, consumer_name, now);
+        if !noack {
+            assign_pending(group, id, consumer_name, now, 1);
+        }
+    }
+
+    let consumer = touch_consumer(stream.groups.get_mut(group_name).unwrap(), consumer_name, now);
+    if !entries.is_empty() {
+        consumer.active_time = Some(now);
+    }
+    entries
+}
+
+/// Re-deliver the consumer's own pending entries after `start`; entries
+/// deleted from the stream meanwhile come back as [id, nil]
+fn read_pending_entries(
+    stream: &mut Stream,
+    group_name: &str,
+    consumer_name: &str,
+    start: &StreamId,
+    count: usize,
+    now: u64,
+) -> Vec<RespValue> {
+    let Stream { entries: stream_entries, groups, .. } = stream;
+    let group = groups.get_mut(group_name).unwrap();
+    let ids: Vec<StreamId> = touch_consumer(group, consumer_name, now)
+        .pending
+        .range((Bound::Excluded(start.clone()), Bound::Unbounded))
+        .take(count)
+        .cloned()
+        .collect();
+
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in ids {
+        match stream_entries.get(&id) {
+            Some(entry) => {
+                entries.push(entry_to_resp(&id, entry));
+                if let Some(pending) = group.pending.get_mut(&id) {
+                    pending.delivery_time = now;
+                    pending.delivery_count += 1;
+                }
+            }
+            None => entries.push(RespValue::Array(Some(vec![bulk(id.to_string()), RespValue::Array(None)]))),
+        }
+    }
+    if !entries.is_empty() {
+        touch_consumer(group, consumer_name, now).active_time = Some(now);
+    }
+    entries
+}
+
+/// XACK key group id [id ...]
+pub async fn xack(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 3 {
+        return wrong_args("xack");
+    }
+
+    let mut ids = Vec::with_capacity(args.len() - 2);
+    for arg in &args[2..] {
+        match parse_id(arg, 0) {
+            Some(id) => ids.push(id),
+            None => return RespValue::Error(INVALID_ID.to_string()),
+        }
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let key = arg_str(&args[0]);
+    let mut stream = match load_stream(&db_instance, &key) {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return RespValue::Integer(0),
+        Err(e) => return e,
+    };
+    let Some(group) = stream.groups.get_mut(&arg_str(&args[1])) else {
+        return RespValue::Integer(0);
+    };
+
+    let acked = ids.iter().filter(|id| remove_pending(group, id)).count();
+    if acked > 0 {
+        db_instance.set(key, RedisValue::Stream(stream));
+    }
+    RespValue::Integer(acked as i64)
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+pub async fn xpending(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 2 {
+        return wrong_args("xpending");
+    }
+
+    // Extended form options
+    let mut min_idle = 0;
+    let mut rest = &args[2..];
+    if rest.first().is_some_and(|arg| arg.eq_ignore_ascii_case(b"IDLE")) {
+        min_idle = match rest.get(1).and_then(|arg| parse_u64(arg)) {
+            Some(ms) => ms,
+            None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+        };
+        rest = &rest[2..];
+    }
+    let extended = if rest.is_empty() && args.len() == 2 {
+        None
+    } else if rest.len() == 3 || rest.len() == 4 {
+        let start = match parse_range_start(&rest[0]) {
+            Some(id) => id,
+            None => return RespValue::Error(INVALID_ID.to_string()),
+        };
+        let end = match parse_range_end(&rest[1]) {
+            Some(id) => id,
+            None => return RespValue::Error(INVALID_ID.to_string()),
+        };
+        let count = match std::str::from_utf8(&rest[2]).ok().and_then(|n| n.parse::<i64>().ok()) {
+            Some(n) => n.max(0) as usize,
+            None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+        };
+        Some((start, end, count, rest.get(3).map(|c| arg_str(c))))
+    } else {
+        return RespValue::Error("ERR syntax error".to_string());
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let key = arg_str(&args[0]);
+    let group_name = arg_str(&args[1]);
+    let stream = match load_stream(&db_instance, &key) {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return no_group(&key, &group_name),
+        Err(e) => return e,
+    };
+    let Some(group) = stream.groups.get(&group_name) else {
+        return no_group(&key, &group_name);
+    };
+
+    let Some((start, end, count, consumer)) = extended else {
+        // Summary: count, smallest and greatest ID, pending per consumer
+        let (Some(first), Some(last)) = (group.pending.keys().next(), group.pending.keys().next_back()) else {
+            return RespValue::Array(Some(vec![
+                RespValue::Integer(0),
+                RespValue::BulkString(None),
+                RespValue::BulkString(None),
+                RespValue::Array(None),
+            ]));
+        };
+        let consumers = group
+            .consumers
+            .iter()
+            .filter(|(_, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| {
+                RespValue::Array(Some(vec![bulk(name.as_str()), bulk(consumer.pending.len().to_string())]))
+            })
+            .collect();
+        return RespValue::Array(Some(vec![
+            RespValue::Integer(group.pending.len() as i64),
+            bulk(first.to_string()),
+            bulk(last.to_string()),
+            RespValue::Array(Some(consumers)),
+        ]));
+    };
+
+    if start > end {
+        return RespValue::Array(Some(vec![]));
+    }
+    let now = current_timestamp_ms();
+    let entries = group
+        .pending
+        .range(start..=end)
+        .filter(|(_, entry)| consumer.as_ref().is_none_or(|name| entry.consumer == *name))
+        .filter(|(_, entry)| now.saturating_sub(entry.delivery_time) >= min_idle)
+        .take(count)
+        .map(|(id, entry)| {
+            RespValue::Array(Some(vec![
+                bulk(id.to_string()),
+                bulk(entry.consumer.as_str()),
+                RespValue::Integer(now.saturating_sub(entry.delivery_time) as i64),
+                RespValue::Integer(entry.delivery_count as i64),
+            ]))
+        })
+        .collect();
+    RespValue::Array(Some(entries))
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME ms]
+/// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID id]
+pub async fn xclaim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 5 {
+        return wrong_args("xclaim");
+    }
+    let min_idle = match parse_u64(&args[3]) {
+        Some(ms) => ms,
+        None => return RespValue::Error("ERR Invalid min-idle-time argument for XCLAIM".to_string()),
+    };
+
+    // IDs up to the first argument that is not one, then options
+    let mut ids = Vec::new();
+    let mut i = 4;
+    while let Some(id) = args.get(i).and_then(|arg| parse_id(arg, 0)) {
+        ids.push(id);
+        i += 1;
+    }
+
+    let now = current_timestamp_ms();
+    let mut delivery_time = now;
+    let mut retry_count = None;
+    let mut force = false;
+    let mut justid = false;
+    let mut last_id = None;
+    while i < args.len() {
+        let option = arg_str(&args[i]).to_uppercase();
+        let value = args.get(i + 1);
+        match option.as_str() {
+            "FORCE" => force = true,
+            "JUSTID" => justid = true,
+            "IDLE" | "TIME" | "RETRYCOUNT" => {
+                let Some(n) = value.and_then(|v| parse_u64(v)) else {
+                    return RespValue::Error(format!("ERR Invalid {} option argument for XCLAIM", option));
+                };
+                match option.as_str() {
+                    "IDLE" => delivery_time = now.saturating_sub(n),
+                    "TIME" => delivery_time = n,
+                    _ => retry_count = Some(n),
+                }
+                i += 1;
+            }
+            "LASTID" => {
+                match value.and_then(|v| parse_id(v, 0)) {
+                    Some(id) => last_id = Some(id),
+                    None => return RespValue::Error(INVALID_ID.to_string()),
+                }
+                i += 1;
+            }
+            _ => return RespValue::Error(format!("ERR Unrecognized XCLAIM option '{}'", arg_str(&args[i]))),
+        }
+        i += 1;
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let key = arg_str(&args[0]);
+    let group_name = arg_str(&args[1]);
+    let consumer_name = arg_str(&args[2]);
+    let mut stream = match load_stream(&db_instance, &key) {
+        Ok(Some(stream)) if stream.groups.contains_key(&group_name) => stream,
+        Ok(_) => return no_group(&key, &group_name),
+        Err(e) => return e,
+    };
+
+    let Stream { entries, groups, .. } = &mut stream;
+    let group = groups.get_mut(&group_name).unwrap();
+    if let Some(last_id) = last_id {
+        if last_id > group.last_delivered_id {
+            group.last_delivered_id = last_id;
+        }
+    }
+    touch_consumer(group, &consumer_name, now);
+
+    let mut claimed = Vec::new();
+    for id in ids {
+        let delivery_count = match group.pending.get(&id) {
+            // Entries deleted from the stream leave the PEL for good
+            Some(_) if !entries.contains_key(&id) => {
+                remove_pending(group, &id);
+                continue;
+            }
+            Some(pending) if now.saturating_sub(pending.delivery_time) < min_idle => continue,
+            Some(pending) => pending.delivery_count,
+            None if force && entries.contains_key(&id) => 0,
+            None => continue,
+        };
+        let delivery_count = retry_count.unwrap_or(if justid { delivery_count } else { delivery_count + 1 });
+        assign_pending(group, &id, &consumer_name, delivery_time, delivery_count);
+        claimed.push(if justid {
+            bulk(id.to_string())
+        } else {
+            entry_to_resp(&id, &entries[&id])
+        });
+    }
+    if !claimed.is_empty() {
+        touch_consumer(group, &consumer_name, now).active_time = Some(now);
+    }
+
+    db_instance.set(key, RedisValue::Stream(stream));
+    RespValue::Array(Some(claimed))
+}
+
+/// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+pub async fn xautoclaim(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 5 {
+        return wrong_args("xautoclaim");
+    }
+    let min_idle = match parse_u64(&args[3]) {
+        Some(ms) => ms,
+        None => return RespValue::Error("ERR Invalid min-idle-time argument for XAUTOCLAIM".to_string()),
+    };
+    let start = match parse_range_start(&args[4]) {
+        Some(id) => id,
+        None => return RespValue::Error(INVALID_ID.to_string()),
+    };
+
+    let mut count = 100;
+    let mut justid = false;
+    let mut i = 5;
+    while i < args.len() {
+        match arg_str(&args[i]).to_uppercase().as_str() {
+            "COUNT" if i + 1 < args.len() => {
+                count = match parse_u64(&args[i + 1]) {
+                    Some(n) if n > 0 => n as usize,
+                    _ => return RespValue::Error("ERR COUNT must be > 0".to_string()),
+                };
+                i += 2;
+            }
+            "JUSTID" => {
+                justid = true;
+                i += 1;
+            }
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let key = arg_str(&args[0]);
+    let group_name = arg_str(&args[1]);
+    let consumer_name = arg_str(&args[2]);
+    let mut stream = match load_stream(&db_instance, &key) {
+        Ok(Some(stream)) if stream.groups.contains_key(&group_name) => stream,
+        Ok(_) => return no_group(&key, &group_name),
+        Err(e) => return e,
+    };
+
+    let now = current_timestamp_ms();
+    let Stream { entries, groups, .. } = &mut stream;
+    let group = groups.get_mut(&group_name).unwrap();
+    touch_consumer(group, &consumer_name, now);
+
+    // Look at up to ten PEL entries per entry to claim
+    let attempts = count.saturating_mul(10);
+    let mut candidates = group
+        .pending
+        .range(start..)
+        .map(|(id, _)| id.clone())
+        .take(attempts.saturating_add(1))
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    let mut claimed = Vec::new();
+    let mut deleted = Vec::new();
+    for _ in 0..attempts {
+        if claimed.len() == count {
+            break;
+        }
+        let Some(id) = candidates.next() else {
+            break;
+        };
+        if !entries.contains_key(&id) {
+            remove_pending(group, &id);
+            deleted.push(bulk(id.to_string()));
+            continue;
+        }
+        let pending = &group.pending[&id];
+        if now.saturating_sub(pending.delivery_time) < min_idle {
+            continue;
+        }
+        let delivery_count = if justid { pending.delivery_count } else { pending.delivery_count + 1 };
+        assign_pending(group, &id, &consumer_name, now, delivery_count);
+        claimed.push(if justid {
+            bulk(id.to_string())
+        } else {
+            entry_to_resp(&id, &entries[&id])
+        });
+    }
+    if !claimed.is_empty() {
+        touch_consumer(group, &consumer_name, now).active_time = Some(now);
+    }
+    let cursor = candidates.next().unwrap_or_else(|| StreamId::new(0, 0));
+
+    db_instance.set(key, RedisValue::Stream(stream));
+    RespValue::Array(Some(vec![
+        bulk(cursor.to_string()),
+        RespValue::Array(Some(claimed)),
+        RespValue::Array(Some(deleted)),
+    ]))
+}
+
+/// XINFO STREAM key [FULL [COUNT count]] | GROUPS key | CONSUMERS key group
+pub async fn xinfo(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 2 {
+        return wrong_args("xinfo");
+    }
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+    let subcommand = arg_str(&args[0]).to_uppercase();
+    let stream = match load_stream(&db_instance, &arg_str(&args[1])) {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return RespValue::Error("ERR no such key".to_string()),
+        Err(e) => return e,
+    };
+    let now = current_timestamp_ms();
+
+    match subcommand.as_str() {
+        "STREAM" => {
+            let full = args.get(2).is_some_and(|arg| arg.eq_ignore_ascii_case(b"FULL"));
+            let count = match &args[2.min(args.len())..] {
+                [] => None,
+                [_] if full => Some(10),
+                [_, option, n] if full && option.eq_ignore_ascii_case(b"COUNT") => match parse_u64(n) {
+                    Some(0) => Some(usize::MAX),
+                    Some(n) => Some(n as usize),
+                    None => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+                },
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            };
+            xinfo_stream(&stream, count)
+        }
+        "GROUPS" if args.len() == 2 => {
+            let groups = stream
+                .groups
+                .iter()
+                .map(|(name, group)| {
+                    RespValue::Array(Some(vec![
+                        bulk("name"),
+                        bulk(name.as_str()),
+                        bulk("consumers"),
+                        RespValue::Integer(group.consumers.len() as i64),
+                        bulk("pending"),
+                        RespValue::Integer(group.pending.len() as i64),
+                        bulk("last-delivered-id"),
+                        bulk(group.last_delivered_id.to_string()),
+                        bulk("entries-read"),
+                        optional_integer(group.entries_read),
+                        bulk("lag"),
+                        optional_integer(group_lag(&stream, group)),
+                    ]))
+                })
+                .collect();
+            RespValue::Array(Some(groups))
+        }
+        "CONSUMERS" if args.len() == 3 => {
+            let key = arg_str(&args[1]);
+            let group_name = arg_str(&args[2]);
+            let Some(group) = stream.groups.get(&group_name) else {
+                return RespValue::Error(format!(
+                    "NOGROUP No such consumer group '{}' for key name '{}'",
+                    group_name, key
+                ));
+            };
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    RespValue::Array(Some(vec![
+                        bulk("name"),
+                        bulk(name.as_str()),
+                        bulk("pending"),
+                        RespValue::Integer(consumer.pending.len() as i64),
+                        bulk("idle"),
+                        RespValue::Integer(now.saturating_sub(consumer.seen_time) as i64),
+                        bulk("inactive"),
+                        RespValue::Integer(consumer.active_time.map_or(-1, |t| now.saturating_sub(t) as i64)),
+                    ]))
+                })
+                .collect();
+            RespValue::Array(Some(consumers))
+        }
+        "GROUPS" | "CONSUMERS" => wrong_args(&format!("xinfo|{}", subcommand.to_lowercase())),
+        _ => RespValue::Error(format!("ERR unknown subcommand '{}'. Try XINFO HELP.", arg_str(&args[0]))),
+    }
+}
+
+fn optional_integer(value: Option<u64>) -> RespValue {
+    match value {
+        Some(n) => RespValue::Integer(n as i64),
+        None => RespValue::BulkString(None),
+    }
+}
+
+/// XINFO STREAM reply; `full_count` is the number of entries to list
+/// with FULL, None for the summary form
+fn xinfo_stream(stream: &Stream, full_count: Option<usize>) -> RespValue {
+    let first_id = stream.entries.keys().next().cloned().unwrap_or_else(|| StreamId::new(0, 0));
+    // Entries are kept in a B-tree, one node per entry
+    let mut reply = vec![
+        bulk("length"),
+        RespValue::Integer(stream.len() as i64),
+        bulk("radix-tree-keys"),
+        RespValue::Integer(stream.len() as i64),
+        bulk("radix-tree-nodes"),
+        RespValue::Integer(stream.len() as i64),
+        bulk("last-generated-id"),
+        bulk(stream.last_id.to_string()),
+        bulk("max-deleted-entry-id"),
+        bulk(stream.max_deleted_id.to_string()),
+        bulk("entries-added"),
+        RespValue::Integer(stream.entries_added as i64),
+        bulk("recorded-first-entry-id"),
+        bulk(first_id.to_string()),
+    ];
+
+    let Some(count) = full_count else {
+        let entry_or_nil = |entry: Option<(&StreamId, &StreamEntry)>| match entry {
+            Some((id, entry)) => entry_to_resp(id, entry),
+            None => RespValue::BulkString(None),
+        };
+        reply.extend([
+            bulk("groups"),
+            RespValue::Integer(stream.groups.len() as i64),
+            bulk("first-entry"),
+            entry_or_nil(stream.entries.iter().next()),
+            bulk("last-entry"),
+            entry_or_nil(stream.entries.iter().next_back()),
+        ]);
+        return RespValue::Array(Some(reply));
+    };
+
+    let entries = stream.entries.iter().take(count).map(|(id, entry)| entry_to_resp(id, entry)).collect();
+    let groups = stream
+        .groups
+        .iter()
+        .map(|(name, group)| {
+            let pending = group
+                .pending
+                .iter()
+                .take(count)
+                .map(|(id, entry)| {
+                    RespValue::Array(Some(vec![
+                        bulk(id.to_string()),
+                        bulk(entry.consumer.as_str()),
+                        RespValue::Integer(entry.delivery_time as i64),
+                        RespValue::Integer(entry.delivery_count as i64),
+                    ]))
+                })
+                .collect();
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let pending = consumer
+                        .pending
+                        .iter()
+                        .take(count)
+                        .map(|id| {
+                            let entry = &group.pending[id];
+                            RespValue::Array(Some(vec![
+                                bulk(id.to_string()),
+                                RespValue::Integer(entry.delivery_time as i64),
+                                RespValue::Integer(entry.delivery_count as i64),
+                            ]))
+                        })
+                        .collect();
+                    RespValue::Array(Some(vec![
+                        bulk("name"),
+                        bulk(name.as_str()),
+                        bulk("seen-time"),
+                        RespValue::Integer(consumer.seen_time as i64),
+                        bulk("active-time"),
+                        RespValue::Integer(consumer.active_time.map_or(-1, |t| t as i64)),
+                        bulk("pel-count"),
+                        RespValue::Integer(consumer.pending.len() as i64),
+                        bulk("pending"),
+                        RespValue::Array(Some(pending)),
+                    ]))
+                })
+                .collect();
+            RespValue::Array(Some(vec![
+                bulk("name"),
+                bulk(name.as_str()),
+                bulk("last-delivered-id"),
+                bulk(group.last_delivered_id.to_string()),
+                bulk("entries-read"),
+                optional_integer(group.entries_read),
+                bulk("lag"),
+                optional_integer(group_lag(stream, group)),
+                bulk("pel-count"),
+                RespValue::Integer(group.pending.len() as i64),
+                bulk("pending"),
+                RespValue::Array(Some(pending)),
+                bulk("consumers"),
+                RespValue::Array(Some(consumers)),
+            ]))
+        })
+        .collect();
+    reply.extend([
+        bulk("entries"),
+        RespValue::Array(Some(entries)),
+        bulk("groups"),
+        RespValue::Array(Some(groups)),
+    ]);
+    RespValue::Array(Some(reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::stream::xadd;
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    async fn setup(db: &Arc<Database>) {
+        for id in ["1-0", "2-0", "3-0"] {
+            xadd(db, 0, args(&["s", id, "f", id])).await;
+        }
+        let reply = xgroup(db, 0, args(&["CREATE", "s", "g", "0"])).await;
+        assert_eq!(reply, RespValue::SimpleString("OK".to_string()));
+    }
+
+    fn ids(reply: &RespValue) -> Vec<String> {
+        let RespValue::Array(Some(entries)) = reply else {
+            panic!("expected an array, got {:?}", reply);
+        };
+        entries
+            .iter()
+            .map(|entry| match entry {
+                RespValue::BulkString(Some(id)) => String::from_utf8(id.clone()).unwrap(),
+                RespValue::Array(Some(parts)) => match &parts[0] {
+                    RespValue::BulkString(Some(id)) => String::from_utf8(id.clone()).unwrap(),
+                    other => panic!("unexpected entry {:?}", other),
+                },
+                other => panic!("unexpected entry {:?}", other),
+            })
+            .collect()
+    }
+
+    /// Entries of the single stream in an XREADGROUP reply
+    fn read_entries(reply: &RespValue) -> RespValue {
+        let RespValue::Array(Some(streams)) = reply else {
+            panic!("expected streams, got {:?}", reply);
+        };
+        let RespValue::Array(Some(stream)) = &streams[0] else {
+            panic!("unexpected stream {:?}", streams[0]);
+        };
+        stream[1].clone()
+    }
+
+    #[tokio::test]
+    async fn test_xgroup_create() {
+        let db = Arc::new(Database::new(16));
+
+        let reply = xgroup(&db, 0, args(&["CREATE", "s", "g", "$"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.starts_with("ERR The XGROUP subcommand requires")));
+        let reply = xgroup(&db, 0, args(&["CREATE", "s", "g", "$", "MKSTREAM"])).await;
+        assert_eq!(reply, RespValue::SimpleString("OK".to_string()));
+        let reply = xgroup(&db, 0, args(&["CREATE", "s", "g", "$"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.starts_with("BUSYGROUP")));
+
+        assert_eq!(xgroup(&db, 0, args(&["CREATECONSUMER", "s", "g", "c"])).await, RespValue::Integer(1));
+        assert_eq!(xgroup(&db, 0, args(&["CREATECONSUMER", "s", "g", "c"])).await, RespValue::Integer(0));
+        assert_eq!(xgroup(&db, 0, args(&["DESTROY", "s", "g"])).await, RespValue::Integer(1));
+        assert_eq!(xgroup(&db, 0, args(&["DESTROY", "s", "g"])).await, RespValue::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_and_xack() {
+        let db = Arc::new(Database::new(16));
+        setup(&db).await;
+
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "COUNT", "2", "STREAMS", "s", ">"])).await;
+        assert_eq!(ids(&read_entries(&reply)), vec!["1-0", "2-0"]);
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "g", "bob", "STREAMS", "s", ">"])).await;
+        assert_eq!(ids(&read_entries(&reply)), vec!["3-0"]);
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "g", "bob", "STREAMS", "s", ">"])).await;
+        assert_eq!(reply, RespValue::Array(None));
+
+        // Explicit IDs re-read the consumer's own history
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "STREAMS", "s", "0"])).await;
+        assert_eq!(ids(&read_entries(&reply)), vec!["1-0", "2-0"]);
+
+        assert_eq!(xack(&db, 0, args(&["s", "g", "1-0", "9-0"])).await, RespValue::Integer(1));
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "STREAMS", "s", "0"])).await;
+        assert_eq!(ids(&read_entries(&reply)), vec!["2-0"]);
+
+        let reply = xreadgroup(&db, 0, args(&["GROUP", "nope", "alice", "STREAMS", "s", ">"])).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.starts_with("NOGROUP")));
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_noack() {
+        let db = Arc::new(Database::new(16));
+        setup(&db).await;
+
+        xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "NOACK", "STREAMS", "s", ">"])).await;
+        let reply = xpending(&db, 0, args(&["s", "g"])).await;
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![
+                RespValue::Integer(0),
+                RespValue::BulkString(None),
+                RespValue::BulkString(None),
+                RespValue::Array(None),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_xpending() {
+        let db = Arc::new(Database::new(16));
+        setup(&db).await;
+        xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "COUNT", "2", "STREAMS", "s", ">"])).await;
+        xreadgroup(&db, 0, args(&["GROUP", "g", "bob", "STREAMS", "s", ">"])).await;
+
+        let reply = xpending(&db, 0, args(&["s", "g"])).await;
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![
+                RespValue::Integer(3),
+                bulk("1-0"),
+                bulk("3-0"),
+                RespValue::Array(Some(vec![
+                    RespValue::Array(Some(vec![bulk("alice"), bulk("2")])),
+                    RespValue::Array(Some(vec![bulk("bob"), bulk("1")])),
+                ])),
+            ]))
+        );
+
+        let reply = xpending(&db, 0, args(&["s", "g", "-", "+", "10", "alice"])).await;
+        assert_eq!(ids(&reply), vec!["1-0", "2-0"]);
+        let reply = xpending(&db, 0, args(&["s", "g", "(1-0", "+", "10"])).await;
+        assert_eq!(ids(&reply), vec!["2-0", "3-0"]);
+        let reply = xpending(&db, 0, args(&["s", "g", "IDLE", "60000", "-", "+", "10"])).await;
+        assert_eq!(ids(&reply), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_xclaim_and_xautoclaim() {
+        let db = Arc::new(Database::new(16));
+        setup(&db).await;
+        xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "STREAMS", "s", ">"])).await;
+
+        // Not idle long enough
+        let reply = xclaim(&db, 0, args(&["s", "g", "bob", "60000", "1-0"])).await;
+        assert_eq!(ids(&reply), Vec::<String>::new());
+
+        let reply = xclaim(&db, 0, args(&["s", "g", "bob", "0", "1-0", "JUSTID"])).await;
+        assert_eq!(ids(&reply), vec!["1-0"]);
+        let reply = xpending(&db, 0, args(&["s", "g", "-", "+", "10", "bob"])).await;
+        assert_eq!(ids(&reply), vec!["1-0"]);
+
+        // Entries deleted from the stream are reported and dropped
+        crate::commands::stream::xdel(&db, 0, args(&["s", "2-0"])).await;
+        let reply = xautoclaim(&db, 0, args(&["s", "g", "carol", "0", "0", "COUNT", "1"])).await;
+        let RespValue::Array(Some(parts)) = reply else {
+            panic!("unexpected reply");
+        };
+        assert_eq!(parts[0], bulk("2-0"));
+        assert_eq!(ids(&parts[1]), vec!["1-0"]);
+        assert_eq!(ids(&parts[2]), Vec::<String>::new());
+
+        let reply = xautoclaim(&db, 0, args(&["s", "g", "carol", "0", "2-0"])).await;
+        let RespValue::Array(Some(parts)) = reply else {
+            panic!("unexpected reply");
+        };
+        assert_eq!(parts[0], bulk("0-0"));
+        assert_eq!(ids(&parts[1]), vec!["3-0"]);
+        assert_eq!(ids(&parts[2]), vec!["2-0"]);
+    }
+
+    #[tokio::test]
+    async fn test_xinfo_groups_lag() {
+        let db = Arc::new(Database::new(16));
+        setup(&db).await;
+        xreadgroup(&db, 0, args(&["GROUP", "g", "alice", "COUNT", "1", "STREAMS", "s", ">"])).await;
+
+        let reply = xinfo(&db, 0, args(&["GROUPS", "s"])).await;
+        let RespValue::Array(Some(groups)) = reply else {
+            panic!("unexpected reply");
+        };
+        let RespValue::Array(Some(fields)) = &groups[0] else {
+            panic!("unexpected group");
+        };
+        assert_eq!(fields[1], bulk("g"));
+        assert_eq!(fields[7], bulk("1-0"));
+        assert_eq!(fields[9], RespValue::Integer(1));
+        assert_eq!(fields[11], RespValue::Integer(2));
+
+        let reply = xinfo(&db, 0, args(&["CONSUMERS", "s", "g"])).await;
+        let RespValue::Array(Some(consumers)) = reply else {
+            panic!("unexpected reply");
+        };
+        assert_eq!(consumers.len(), 1);
+
+        assert_eq!(
+            xinfo(&db, 0, args(&["STREAM", "nokey"])).await,
+            RespValue::Error("ERR no such key".to_string())
+        );
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::protocol::RespValue;
 use crate::storage::db::Database;
+use crate::storage::notify::EventClass;
 use crate::storage::types::RedisValue;
 use bytes::Bytes;
 use std::sync::Arc;
@@ -177,6 +178,7 @@ pub async fn set(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Res
 
     // Set the value
     db_instance.set(key.clone(), RedisValue::String(value));
+    db_instance.notify(EventClass::STRING, "set", &key);
 
     // Set expiration if specified
     if let Some(duration) = expiration {
@@ -187,6 +189,7 @@ pub async fn set(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Res
                 .as_millis() as u64;
             let expire_at_ms = now + duration.as_millis() as u64;
             db_instance.set_expiry(&key, expire_at_ms);
+            db_instance.notify(EventClass::GENERIC, "expire", &key);
         }
     } else if !keep_ttl {
         // Clear expiration if not KEEPTTL and no new expiration
@@ -220,7 +223,7 @@ pub async fn get(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Res
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::String(bytes)) => RespValue::BulkString(Some(bytes.to_vec())),
         Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
         None => RespValue::BulkString(None),
@@ -246,6 +249,7 @@ pub async fn del(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Res
         };
 
         if db_instance.delete(key) {
+            db_instance.notify(EventClass::GENERIC, "del", key);
             count += 1;
         }
     }
@@ -303,13 +307,15 @@ pub async fn append(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
             new_vec.extend_from_slice(&append_value);
             let new_bytes = Bytes::from(new_vec);
             let len = new_bytes.len();
-            db_instance.set(key, RedisValue::String(new_bytes));
+            db_instance.set(key.clone(), RedisValue::String(new_bytes));
+            db_instance.notify(EventClass::STRING, "append", &key);
             RespValue::Integer(len as i64)
         }
         Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
         None => {
             let len = append_value.len();
-            db_instance.set(key, RedisValue::String(append_value));
+            db_instance.set(key.clone(), RedisValue::String(append_value));
+            db_instance.notify(EventClass::STRING, "append", &key);
             RespValue::Integer(len as i64)
         }
     }
@@ -331,7 +337,7 @@ pub async fn strlen(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::String(bytes)) => RespValue::Integer(bytes.len() as i64),
         Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
         None => RespValue::Integer(0),
@@ -387,7 +393,8 @@ pub async fn incrby(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
     };
 
     let new_value = current_value + increment;
-    db_instance.set(key, RedisValue::String(Bytes::from(new_value.to_string())));
+    db_instance.set(key.clone(), RedisValue::String(Bytes::from(new_value.to_string())));
+    db_instance.notify(EventClass::STRING, "incrby", &key);
     RespValue::Integer(new_value)
 }
 
@@ -457,7 +464,8 @@ pub async fn incrbyfloat(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
         format!("{}", new_value)
     };
 
-    db_instance.set(key, RedisValue::String(Bytes::from(formatted.clone())));
+    db_instance.set(key.clone(), RedisValue::String(Bytes::from(formatted.clone())));
+    db_instance.notify(EventClass::STRING, "incrbyfloat", &key);
     RespValue::BulkString(Some(formatted.into_bytes()))
 }
 
@@ -494,6 +502,8 @@ pub async fn psetex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         .unwrap()
         .as_millis() as u64;
     db_instance.set_expiry(&key, now + milliseconds);
+    db_instance.notify(EventClass::STRING, "set", &key);
+    db_instance.notify(EventClass::GENERIC, "expire", &key);
 
     RespValue::SimpleString("OK".to_string())
 }
@@ -530,7 +540,7 @@ pub async fn getrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::String(bytes)) => {
             let len = bytes.len() as i64;
             let start_idx = normalize_index(start, len);
@@ -596,7 +606,8 @@ pub async fn setrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
     }
 
     let len = current.len();
-    db_instance.set(key, RedisValue::String(Bytes::from(current)));
+    db_instance.set(key.clone(), RedisValue::String(Bytes::from(current)));
+    db_instance.notify(EventClass::STRING, "setrange", &key);
     RespValue::Integer(len as i64)
 }
 
@@ -621,7 +632,7 @@ pub async fn mget(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
             }
         };
 
-        match db_instance.get(key) {
+        match db_instance.lookup_read(key) {
             Some(RedisValue::String(bytes)) => results.push(RespValue::BulkString(Some(bytes.to_vec()))),
             _ => results.push(RespValue::BulkString(None)),
         }
@@ -648,7 +659,8 @@ pub async fn mset(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         };
 
         let value = Bytes::from(chunk[1].clone());
-        db_instance.set(key, RedisValue::String(value));
+        db_instance.set(key.clone(), RedisValue::String(value));
+        db_instance.notify(EventClass::STRING, "set", &key);
     }
 
     RespValue::SimpleString("OK".to_string())
@@ -671,7 +683,7 @@ pub async fn getex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     };
 
     // Get the current value
-    let value = match db_instance.get(&key) {
+    let value = match db_instance.lookup_read(&key) {
         Some(RedisValue::String(bytes)) => bytes.to_vec(),
         Some(_) => return RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
         None => return RespValue::BulkString(None),
@@ -749,10 +761,14 @@ pub async fn getex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
                 if args.len() != 2 {
                     return RespValue::Error("ERR syntax error".to_string());
                 }
-                db_instance.persist(&key);
+                if db_instance.persist(&key) {
+                    db_instance.notify(EventClass::GENERIC, "persist", &key);
+                }
+                return RespValue::BulkString(Some(value));
             }
             _ => return RespValue::Error("ERR syntax error".to_string()),
         }
+        db_instance.notify(EventClass::GENERIC, "expire", &key);
     }
 
     RespValue::BulkString(Some(value))
@@ -774,10 +790,11 @@ pub async fn getdel(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
+    match db_instance.lookup_read(key) {
         Some(RedisValue::String(bytes)) => {
             let value = bytes.to_vec();
             db_instance.delete(key);
+            db_instance.notify(EventClass::GENERIC, "del", key);
             RespValue::BulkString(Some(value))
         }
         Some(_) => RespValue::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
@@ -818,6 +835,8 @@ pub async fn setex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         .unwrap()
         .as_millis() as u64;
     db_instance.set_expiry(&key, now + seconds * 1000);
+    db_instance.notify(EventClass::STRING, "set", &key);
+    db_instance.notify(EventClass::GENERIC, "expire", &key);
 
     RespValue::SimpleString("OK".to_string())
 }
@@ -843,7 +862,8 @@ pub async fn setnx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     if db_instance.exists(&key) {
         RespValue::Integer(0)
     } else {
-        db_instance.set(key, RedisValue::String(value));
+        db_instance.set(key.clone(), RedisValue::String(value));
+        db_instance.notify(EventClass::STRING, "set", &key);
         RespValue::Integer(1)
     }
 }
@@ -879,7 +899,8 @@ pub async fn msetnx(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         };
 
         let value = Bytes::from(chunk[1].clone());
-        db_instance.set(key, RedisValue::String(value));
+        db_instance.set(key.clone(), RedisValue::String(value));
+        db_instance.notify(EventClass::STRING, "set", &key);
     }
 
     RespValue::Integer(1)
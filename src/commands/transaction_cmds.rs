@@ -13,6 +13,10 @@ pub async fn exec(tx: &mut Transaction) -> RespValue {
     if !tx.in_multi {
         return RespValue::Error("ERR EXEC without MULTI".to_string());
     }
+    if tx.aborted {
+        tx.discard();
+        return RespValue::Error("EXECABORT Transaction discarded because of previous errors.".to_string());
+    }
 
     // Return marker that EXEC was called - actual execution happens in connection handler
     RespValue::SimpleString("__EXEC__".to_string())
@@ -66,6 +70,19 @@ mod tests {
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_exec_after_queueing_error() {
+        let mut tx = Transaction::new();
+        multi(&mut tx).await;
+        tx.flag_error();
+
+        match exec(&mut tx).await {
+            RespValue::Error(msg) => assert!(msg.starts_with("EXECABORT")),
+            other => panic!("Expected EXECABORT, got {:?}", other),
+        }
+        assert!(!tx.in_multi);
+    }
+
     #[tokio::test]
     async fn test_watch_unwatch() {
         let mut tx = Transaction::new();
@@ -1,15 +1,19 @@
 // Sorted Set (ZSet) command handlers
 
+use super::key_mgmt::ScanOptions;
 use crate::protocol::RespValue;
-use crate::storage::db::Database;
+use crate::replication::write_gate;
+use crate::storage::db::{Database, DbInstance};
+use crate::storage::notify::EventClass;
 use crate::storage::types::{RedisValue, ZSet};
 use bytes::Bytes;
-use ordered_float::OrderedFloat;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-/// ZADD key score member [score member ...]
+/// ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]
 pub async fn zadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 || args.len() % 2 == 0 {
+    if args.len() < 3 {
         return RespValue::Error("ERR wrong number of arguments for 'zadd' command".to_string());
     }
 
@@ -18,49 +22,112 @@ pub async fn zadd(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
+    let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) = (false, false, false, false, false, false);
+    let mut i = 1;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "GT" => gt = true,
+            "LT" => lt = true,
+            "CH" => ch = true,
+            "INCR" => incr = true,
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let pairs = &args[i..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return RespValue::Error("ERR syntax error".to_string());
+    }
+    if nx && xx {
+        return RespValue::Error(
+            "ERR XX and NX options at the same time are not compatible".to_string(),
+        );
+    }
+    if (gt && lt) || (nx && (gt || lt)) {
+        return RespValue::Error(
+            "ERR GT, LT, and/or NX options at the same time are not compatible".to_string(),
+        );
+    }
+    if incr && pairs.len() > 2 {
+        return RespValue::Error(
+            "ERR INCR option supports a single increment-element pair".to_string(),
+        );
+    }
+
+    // Validate every score before touching the set
+    let mut elements = Vec::with_capacity(pairs.len() / 2);
+    for chunk in pairs.chunks(2) {
+        match parse_score(&chunk[0]) {
+            Ok(score) => elements.push((score, Bytes::from(chunk[1].clone()))),
+            Err(e) => return RespValue::Error(e),
+        }
+    }
+
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    let apply = |zset: &mut ZSet| {
+        let mut added = 0;
+        let mut changed = 0;
+        let mut incr_result = None;
+        for (score, member) in elements {
+            let new_score = match zset.score(&member) {
+                Some(old) => {
+                    if nx {
+                        continue;
+                    }
+                    let new_score = if incr { old + score } else { score };
+                    // INCR takes a single pair, so nothing was changed yet
+                    if new_score.is_nan() {
+                        return Err("ERR resulting score is not a number (NaN)".to_string());
+                    }
+                    if (gt && new_score <= old) || (lt && new_score >= old) {
+                        continue;
+                    }
+                    if new_score != old {
+                        changed += 1;
+                    }
+                    new_score
+                }
+                None => {
+                    if xx {
+                        continue;
+                    }
+                    added += 1;
+                    score
+                }
+            };
+            zset.insert(member, new_score);
+            incr_result = Some(new_score);
         }
-        None => ZSet::new(),
+        Ok((added, changed, incr_result))
     };
 
-    let mut added = 0;
-    for chunk in args[1..].chunks(2) {
-        let score = match std::str::from_utf8(&chunk[0]) {
-            Ok(s) => match s.parse::<f64>() {
-                Ok(n) => n,
-                Err(_) => {
-                    return RespValue::Error("ERR value is not a valid float".to_string())
-                }
-            },
-            Err(_) => return RespValue::Error("ERR value is not a valid float".to_string()),
-        };
-
-        let member = Bytes::from(chunk[1].clone());
-
-        // Remove old score entry if member exists
-        if let Some(old_score) = zset.members.get(&member) {
-            zset.scores.remove(&(OrderedFloat(*old_score), member.clone()));
-        } else {
-            added += 1;
-        }
+    // Only XX can leave a new key without members, so it never creates one
+    let outcome = if xx {
+        with_zset_mut(&db_instance, &key, apply).unwrap_or(Ok(Ok((0, 0, None))))
+    } else {
+        with_zset_or_insert(&db_instance, &key, apply)
+    };
+    let (added, changed, incr_result) = match outcome.and_then(|outcome| outcome) {
+        Ok(outcome) => outcome,
+        Err(e) => return RespValue::Error(e),
+    };
 
-        // Add new score entry
-        zset.scores.insert((OrderedFloat(score), member.clone()), ());
-        zset.members.insert(member, score);
+    if added + changed > 0 {
+        db_instance.notify(EventClass::ZSET, if incr { "zincr" } else { "zadd" }, &key);
     }
 
-    db_instance.set(key, RedisValue::ZSet(zset));
-    RespValue::Integer(added)
+    if incr {
+        // An increment blocked by NX/XX/GT/LT replies nil
+        return RespValue::BulkString(incr_result.map(|score| score.to_string().into_bytes()));
+    }
+    RespValue::Integer(if ch { added + changed } else { added })
 }
 
 /// ZREM key member [member ...]
@@ -79,32 +146,20 @@ pub async fn zrem(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> Re
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => return RespValue::Integer(0),
-    };
+    let removed = with_zset_mut(&db_instance, &key, |zset| {
+        args[1..].iter().filter(|member| zset.remove(member).is_some()).count()
+    });
 
-    let mut removed = 0;
-    for member_bytes in &args[1..] {
-        let member = Bytes::from(member_bytes.clone());
-        if let Some(score) = zset.members.remove(&member) {
-            zset.scores.remove(&(OrderedFloat(score), member));
-            removed += 1;
+    match removed {
+        Some(Ok(removed)) => {
+            if removed > 0 {
+                finish_removal(&db_instance, &key, "zrem");
+            }
+            RespValue::Integer(removed as i64)
         }
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
     }
-
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
-    }
-
-    RespValue::Integer(removed)
 }
 
 /// ZSCORE key member
@@ -118,22 +173,15 @@ pub async fn zscore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let member = Bytes::from(args[1].clone());
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => match zset.members.get(&member) {
-            Some(score) => RespValue::BulkString(Some(score.to_string().into_bytes())),
-            None => RespValue::BulkString(None),
-        },
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::BulkString(None),
+    match read_zset(&db_instance, key, |zset| zset.score(&args[1])) {
+        Some(Ok(Some(score))) => RespValue::BulkString(Some(score.to_string().into_bytes())),
+        Some(Ok(None)) | None => RespValue::BulkString(None),
+        Some(Err(e)) => RespValue::Error(e),
     }
 }
 
@@ -153,11 +201,9 @@ pub async fn zcard(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => RespValue::Integer(zset.len() as i64),
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+    match read_zset(&db_instance, key, |zset| zset.len()) {
+        Some(Ok(len)) => RespValue::Integer(len as i64),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
@@ -173,13 +219,8 @@ pub async fn zcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let min = match parse_score_range(&args[1]) {
-        Ok(s) => s,
-        Err(e) => return RespValue::Error(e),
-    };
-
-    let max = match parse_score_range(&args[2]) {
-        Ok(s) => s,
+    let range = match ScoreRange::parse(&args[1], &args[2]) {
+        Ok(r) => r,
         Err(e) => return RespValue::Error(e),
     };
 
@@ -188,272 +229,69 @@ pub async fn zcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            let count = zset
-                .members
-                .values()
-                .filter(|&&score| score >= min && score <= max)
-                .count();
-            RespValue::Integer(count as i64)
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
+    let query = RangeQuery::new(RangeBy::Score(range));
+    match read_zset(&db_instance, key, |zset| query.count(zset)) {
+        Some(Ok(count)) => RespValue::Integer(count as i64),
+        Some(Err(e)) => RespValue::Error(e),
         None => RespValue::Integer(0),
     }
 }
 
-/// ZRANGE key start stop [WITHSCORES]
+/// ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
 pub async fn zrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 || args.len() > 4 {
-        return RespValue::Error("ERR wrong number of arguments for 'zrange' command".to_string());
-    }
-
-    let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
-
-    let start = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse::<i64>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-    };
-
-    let stop = match std::str::from_utf8(&args[2]) {
-        Ok(s) => match s.parse::<i64>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-    };
-
-    let with_scores = if args.len() == 4 {
-        match std::str::from_utf8(&args[3]) {
-            Ok(s) if s.to_uppercase() == "WITHSCORES" => true,
-            _ => return RespValue::Error("ERR syntax error".to_string()),
-        }
-    } else {
-        false
-    };
-
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
-
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            let len = zset.len() as i64;
-            if len == 0 {
-                return RespValue::Array(Some(vec![]));
-            }
-
-            // Normalize negative indices
-            let start = if start < 0 {
-                (len + start).max(0)
-            } else {
-                start.min(len - 1)
-            };
-            let stop = if stop < 0 {
-                (len + stop).max(0)
-            } else {
-                stop.min(len - 1)
-            };
-
-            if start > stop {
-                return RespValue::Array(Some(vec![]));
-            }
-
-            let mut result = Vec::new();
-            for (i, ((_, member), _)) in zset.scores.iter().enumerate() {
-                let idx = i as i64;
-                if idx >= start && idx <= stop {
-                    result.push(RespValue::BulkString(Some(member.to_vec())));
-                    if with_scores {
-                        if let Some(&score) = zset.members.get(member) {
-                            result.push(RespValue::BulkString(Some(score.to_string().into_bytes())));
-                        }
-                    }
-                }
-                if idx > stop {
-                    break;
-                }
-            }
-
-            RespValue::Array(Some(result))
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::Array(Some(vec![])),
-    }
+    range_command(db, db_index, "zrange", &args, None, true)
 }
 
 /// ZREVRANGE key start stop [WITHSCORES]
 pub async fn zrevrange(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 || args.len() > 4 {
-        return RespValue::Error(
-            "ERR wrong number of arguments for 'zrevrange' command".to_string(),
-        );
-    }
-
-    let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
-
-    let start = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse::<i64>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-    };
-
-    let stop = match std::str::from_utf8(&args[2]) {
-        Ok(s) => match s.parse::<i64>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-    };
-
-    let with_scores = if args.len() == 4 {
-        match std::str::from_utf8(&args[3]) {
-            Ok(s) if s.to_uppercase() == "WITHSCORES" => true,
-            _ => return RespValue::Error("ERR syntax error".to_string()),
-        }
-    } else {
-        false
-    };
-
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
-
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            let len = zset.len() as i64;
-            if len == 0 {
-                return RespValue::Array(Some(vec![]));
-            }
-
-            // Normalize negative indices
-            let start = if start < 0 {
-                (len + start).max(0)
-            } else {
-                start.min(len - 1)
-            };
-            let stop = if stop < 0 {
-                (len + stop).max(0)
-            } else {
-                stop.min(len - 1)
-            };
-
-            if start > stop {
-                return RespValue::Array(Some(vec![]));
-            }
-
-            let mut result = Vec::new();
-            for (i, ((_, member), _)) in zset.scores.iter().rev().enumerate() {
-                let idx = i as i64;
-                if idx >= start && idx <= stop {
-                    result.push(RespValue::BulkString(Some(member.to_vec())));
-                    if with_scores {
-                        if let Some(&score) = zset.members.get(member) {
-                            result.push(RespValue::BulkString(Some(score.to_string().into_bytes())));
-                        }
-                    }
-                }
-                if idx > stop {
-                    break;
-                }
-            }
-
-            RespValue::Array(Some(result))
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::Array(Some(vec![])),
-    }
+    range_command(db, db_index, "zrevrange", &args, Some((RangeKind::Rank, true)), true)
 }
 
 /// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
 pub async fn zrangebyscore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
+    range_command(db, db_index, "zrangebyscore", &args, Some((RangeKind::Score, false)), true)
+}
+
+/// ZRANGESTORE dst src min max [BYSCORE|BYLEX] [REV] [LIMIT offset count]
+pub async fn zrangestore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
         return RespValue::Error(
-            "ERR wrong number of arguments for 'zrangebyscore' command".to_string(),
+            "ERR wrong number of arguments for 'zrangestore' command".to_string(),
         );
     }
 
-    let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
-
-    let min = match parse_score_range(&args[1]) {
-        Ok(s) => s,
-        Err(e) => return RespValue::Error(e),
+    let (dest, src) = match (std::str::from_utf8(&args[0]), std::str::from_utf8(&args[1])) {
+        (Ok(d), Ok(s)) => (d.to_string(), s),
+        _ => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let max = match parse_score_range(&args[2]) {
-        Ok(s) => s,
+    let query = match RangeQuery::parse(&args[2..], None, false) {
+        Ok(q) => q,
         Err(e) => return RespValue::Error(e),
     };
 
-    let mut with_scores = false;
-    let mut _limit_offset = 0i64;
-    let mut _limit_count = -1i64;
-
-    // Parse optional arguments
-    let mut i = 3;
-    while i < args.len() {
-        match std::str::from_utf8(&args[i]) {
-            Ok(s) if s.to_uppercase() == "WITHSCORES" => {
-                with_scores = true;
-                i += 1;
-            }
-            Ok(s) if s.to_uppercase() == "LIMIT" => {
-                if i + 2 >= args.len() {
-                    return RespValue::Error("ERR syntax error".to_string());
-                }
-                // LIMIT not fully implemented yet
-                i += 3;
-            }
-            _ => return RespValue::Error("ERR syntax error".to_string()),
-        }
-    }
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            let mut result = Vec::new();
-            for ((score, member), _) in zset.scores.iter() {
-                let s = score.into_inner();
-                if s >= min && s <= max {
-                    result.push(RespValue::BulkString(Some(member.to_vec())));
-                    if with_scores {
-                        result.push(RespValue::BulkString(Some(s.to_string().into_bytes())));
-                    }
-                }
-            }
-            RespValue::Array(Some(result))
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::Array(Some(vec![])),
+    let selected = db_instance.with_value(src, |value| match value {
+        RedisValue::ZSet(zset) => Ok(query.select(zset)),
+        _ => Err(WRONGTYPE.to_string()),
+    });
+    let selected = match selected {
+        Some(Ok(selected)) => selected,
+        Some(Err(e)) => return RespValue::Error(e),
+        None => Vec::new(),
+    };
+
+    let mut result = db_instance.new_zset();
+    for (member, score) in selected {
+        result.insert(member, score);
     }
+    let count = result.len() as i64;
+    store_result(&db_instance, dest, result, "zrangestore");
+    RespValue::Integer(count)
 }
 
 /// ZRANK key member
@@ -467,32 +305,15 @@ pub async fn zrank(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let member = Bytes::from(args[1].clone());
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            // Check if member exists
-            if !zset.members.contains_key(&member) {
-                return RespValue::BulkString(None);
-            }
-
-            // Find rank by iterating through sorted scores
-            for (rank, ((_, m), _)) in zset.scores.iter().enumerate() {
-                if m == &member {
-                    return RespValue::Integer(rank as i64);
-                }
-            }
-            RespValue::BulkString(None)
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::BulkString(None),
+    match read_zset(&db_instance, key, |zset| zset.rank(&args[1])) {
+        Some(Ok(Some(rank))) => RespValue::Integer(rank as i64),
+        Some(Ok(None)) | None => RespValue::BulkString(None),
+        Some(Err(e)) => RespValue::Error(e),
     }
 }
 
@@ -509,32 +330,17 @@ pub async fn zrevrank(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let member = Bytes::from(args[1].clone());
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    match db_instance.get(key) {
-        Some(RedisValue::ZSet(zset)) => {
-            // Check if member exists
-            if !zset.members.contains_key(&member) {
-                return RespValue::BulkString(None);
-            }
-
-            // Find rank by iterating through sorted scores in reverse
-            for (rank, ((_, m), _)) in zset.scores.iter().rev().enumerate() {
-                if m == &member {
-                    return RespValue::Integer(rank as i64);
-                }
-            }
-            RespValue::BulkString(None)
-        }
-        Some(_) => RespValue::Error(
-            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-        ),
-        None => RespValue::BulkString(None),
+    // Reverse rank counts from the highest score
+    let rank = read_zset(&db_instance, key, |zset| zset.rank(&args[1]).map(|rank| zset.len() - 1 - rank));
+    match rank {
+        Some(Ok(Some(rank))) => RespValue::Integer(rank as i64),
+        Some(Ok(None)) | None => RespValue::BulkString(None),
+        Some(Err(e)) => RespValue::Error(e),
     }
 }
 
@@ -564,29 +370,17 @@ pub async fn zincrby(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => ZSet::new(),
+    let new_score = with_zset_or_insert(&db_instance, &key, |zset| {
+        // Get current score or default to 0.0
+        let new_score = zset.score(&member).unwrap_or(0.0) + increment;
+        zset.insert(member, new_score);
+        new_score
+    });
+    let new_score = match new_score {
+        Ok(score) => score,
+        Err(e) => return RespValue::Error(e),
     };
-
-    // Get current score or default to 0.0
-    let current_score = zset.members.get(&member).copied().unwrap_or(0.0);
-    let new_score = current_score + increment;
-
-    // Remove old entry and add new one
-    if let Some(&old_score) = zset.members.get(&member) {
-        zset.scores.remove(&(OrderedFloat(old_score), member.clone()));
-    }
-
-    zset.members.insert(member.clone(), new_score);
-    zset.scores.insert((OrderedFloat(new_score), member), ());
-
-    db_instance.set(key, RedisValue::ZSet(zset));
+    db_instance.notify(EventClass::ZSET, "zincr", &key);
 
     // Format the score for response
     let formatted = if new_score.fract() == 0.0 && new_score.abs() < 1e10 {
@@ -631,35 +425,20 @@ pub async fn zpopmin(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
+    let popped = match with_zset_mut(&db_instance, &key, |zset| pop_entries(zset, count, false)) {
+        Some(Ok(popped)) => popped,
+        Some(Err(e)) => return RespValue::Error(e),
         None => return RespValue::Array(Some(vec![])),
     };
 
-    let mut result = Vec::new();
-    let mut popped = 0;
-
-    // Pop minimum elements
-    while popped < count && !zset.scores.is_empty() {
-        if let Some(((score, member), _)) = zset.scores.iter().next().map(|((s, m), v)| ((*s, m.clone()), v)) {
-            zset.scores.remove(&(score, member.clone()));
-            zset.members.remove(&member);
-
-            result.push(RespValue::BulkString(Some(member.to_vec())));
-            result.push(RespValue::BulkString(Some(format!("{}", score.0).into_bytes())));
-            popped += 1;
-        }
+    let mut result = Vec::with_capacity(popped.len() * 2);
+    for (member, score) in &popped {
+        result.push(RespValue::BulkString(Some(member.to_vec())));
+        result.push(RespValue::BulkString(Some(format!("{}", score).into_bytes())));
     }
 
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
+    if !popped.is_empty() {
+        finish_removal(&db_instance, &key, "zpopmin");
     }
 
     RespValue::Array(Some(result))
@@ -699,35 +478,20 @@ pub async fn zpopmax(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
+    let popped = match with_zset_mut(&db_instance, &key, |zset| pop_entries(zset, count, true)) {
+        Some(Ok(popped)) => popped,
+        Some(Err(e)) => return RespValue::Error(e),
         None => return RespValue::Array(Some(vec![])),
     };
 
-    let mut result = Vec::new();
-    let mut popped = 0;
-
-    // Pop maximum elements (iterate in reverse)
-    while popped < count && !zset.scores.is_empty() {
-        if let Some(((score, member), _)) = zset.scores.iter().next_back().map(|((s, m), v)| ((*s, m.clone()), v)) {
-            zset.scores.remove(&(score, member.clone()));
-            zset.members.remove(&member);
-
-            result.push(RespValue::BulkString(Some(member.to_vec())));
-            result.push(RespValue::BulkString(Some(format!("{}", score.0).into_bytes())));
-            popped += 1;
-        }
+    let mut result = Vec::with_capacity(popped.len() * 2);
+    for (member, score) in &popped {
+        result.push(RespValue::BulkString(Some(member.to_vec())));
+        result.push(RespValue::BulkString(Some(format!("{}", score).into_bytes())));
     }
 
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
+    if !popped.is_empty() {
+        finish_removal(&db_instance, &key, "zpopmax");
     }
 
     RespValue::Array(Some(result))
@@ -779,63 +543,34 @@ pub async fn zremrangebyrank(
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => return RespValue::Integer(0),
-    };
-
-    let len = zset.scores.len() as i64;
-    if len == 0 {
-        return RespValue::Integer(0);
-    }
-
-    // Handle negative indices
-    let start_idx = if start < 0 {
-        (len + start).max(0) as usize
-    } else {
-        start.min(len) as usize
-    };
-
-    let stop_idx = if stop < 0 {
-        (len + stop).max(-1) as usize
-    } else {
-        stop.min(len - 1) as usize
-    };
-
-    if start_idx > stop_idx {
-        return RespValue::Integer(0);
-    }
+    let removed = with_zset_mut(&db_instance, &key, |zset| {
+        let Some((start_idx, stop_idx)) = rank_bounds(start, stop, zset.len()) else {
+            return 0;
+        };
 
-    // Collect members to remove
-    let members_to_remove: Vec<_> = zset
-        .scores
-        .iter()
-        .skip(start_idx)
-        .take(stop_idx - start_idx + 1)
-        .map(|((score, member), _)| (*score, member.clone()))
-        .collect();
+        // Collect members to remove
+        let members_to_remove: Vec<Bytes> = zset
+            .range_by_rank(start_idx, stop_idx + 1)
+            .map(|(member, _)| Bytes::copy_from_slice(member))
+            .collect();
 
-    let removed = members_to_remove.len() as i64;
+        for member in &members_to_remove {
+            zset.remove(member);
+        }
+        members_to_remove.len()
+    });
 
-    // Remove them
-    for (score, member) in members_to_remove {
-        zset.scores.remove(&(score, member.clone()));
-        zset.members.remove(&member);
+    match removed {
+        Some(Ok(removed)) => {
+            if removed > 0 {
+                finish_removal(&db_instance, &key, "zremrangebyrank");
+            }
+            RespValue::Integer(removed as i64)
+        }
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
     }
-
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
-    }
-
-    RespValue::Integer(removed)
-}
+}
 
 /// ZREMRANGEBYSCORE key min max
 pub async fn zremrangebyscore(
@@ -854,13 +589,8 @@ pub async fn zremrangebyscore(
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let min_score = match parse_score_range(&args[1]) {
-        Ok(s) => s,
-        Err(e) => return RespValue::Error(e),
-    };
-
-    let max_score = match parse_score_range(&args[2]) {
-        Ok(s) => s,
+    let range = match ScoreRange::parse(&args[1], &args[2]) {
+        Ok(r) => r,
         Err(e) => return RespValue::Error(e),
     };
 
@@ -869,46 +599,38 @@ pub async fn zremrangebyscore(
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    let query = RangeQuery::new(RangeBy::Score(range));
+    let removed = with_zset_mut(&db_instance, &key, |zset| {
+        let to_remove = query.select(zset);
+        for (member, _) in &to_remove {
+            zset.remove(member);
         }
-        None => return RespValue::Integer(0),
-    };
+        to_remove.len()
+    });
 
-    // Collect members in score range
-    let members_to_remove: Vec<_> = zset
-        .scores
-        .iter()
-        .filter(|((score, _), _)| score.0 >= min_score && score.0 <= max_score)
-        .map(|((score, member), _)| (*score, member.clone()))
-        .collect();
-
-    let removed = members_to_remove.len() as i64;
-
-    // Remove them
-    for (score, member) in members_to_remove {
-        zset.scores.remove(&(score, member.clone()));
-        zset.members.remove(&member);
-    }
-
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
+    match removed {
+        Some(Ok(removed)) => {
+            if removed > 0 {
+                finish_removal(&db_instance, &key, "zremrangebyscore");
+            }
+            RespValue::Integer(removed as i64)
+        }
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
     }
-
-    RespValue::Integer(removed)
 }
 
 /// ZREVRANGEBYSCORE key max min [WITHSCORES] [LIMIT offset count]
 /// Return members in reverse score order
 pub async fn zrevrangebyscore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zrevrangebyscore' command".to_string());
+    range_command(db, db_index, "zrevrangebyscore", &args, Some((RangeKind::Score, true)), true)
+}
+
+/// ZLEXCOUNT key min max
+/// Count members between lexicographical range
+pub async fn zlexcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() != 3 {
+        return RespValue::Error("ERR wrong number of arguments for 'zlexcount' command".to_string());
     }
 
     let key = match std::str::from_utf8(&args[0]) {
@@ -916,134 +638,84 @@ pub async fn zrevrangebyscore(db: &Arc<Database>, db_index: usize, args: Vec<Vec
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let max_score = match parse_score_range(&args[1]) {
-        Ok(s) => s,
-        Err(e) => return RespValue::Error(e),
-    };
-
-    let min_score = match parse_score_range(&args[2]) {
-        Ok(s) => s,
+    let range = match LexRange::parse(&args[1], &args[2]) {
+        Ok(r) => r,
         Err(e) => return RespValue::Error(e),
     };
 
-    let mut withscores = false;
-    let mut limit_offset = 0;
-    let mut limit_count = None;
-    let mut i = 3;
-
-    while i < args.len() {
-        if let Ok(s) = std::str::from_utf8(&args[i]) {
-            match s.to_uppercase().as_str() {
-                "WITHSCORES" => withscores = true,
-                "LIMIT" => {
-                    if i + 2 >= args.len() {
-                        return RespValue::Error("ERR syntax error".to_string());
-                    }
-                    i += 1;
-                    limit_offset = match std::str::from_utf8(&args[i]) {
-                        Ok(s) => match s.parse::<usize>() {
-                            Ok(n) => n,
-                            Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                        },
-                        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                    };
-                    i += 1;
-                    limit_count = match std::str::from_utf8(&args[i]) {
-                        Ok(s) => match s.parse::<i64>() {
-                            Ok(n) if n >= 0 => Some(n as usize),
-                            _ => return RespValue::Error("ERR value is out of range".to_string()),
-                        },
-                        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                    };
-                }
-                _ => return RespValue::Error("ERR syntax error".to_string()),
-            }
-        }
-        i += 1;
-    }
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => return RespValue::Array(Some(vec![])),
-    };
-
-    // Collect matching members (reversed order)
-    let results: Vec<_> = zset
-        .scores
-        .iter()
-        .filter(|((score, _), _)| score.0 >= min_score && score.0 <= max_score)
-        .rev() // Reverse order
-        .skip(limit_offset)
-        .take(limit_count.unwrap_or(usize::MAX))
-        .collect();
-
-    let mut output = Vec::new();
-    for ((score, member), _) in results {
-        output.push(RespValue::BulkString(Some(member.to_vec())));
-        if withscores {
-            output.push(RespValue::BulkString(Some(format!("{}", score.0).into_bytes())));
-        }
+    let query = RangeQuery::new(RangeBy::Lex(range));
+    match read_zset(&db_instance, key, |zset| query.count(zset)) {
+        Some(Ok(count)) => RespValue::Integer(count as i64),
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
     }
+}
 
-    RespValue::Array(Some(output))
+/// ZRANGEBYLEX key min max [LIMIT offset count]
+/// Return members between lexicographical range
+pub async fn zrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    range_command(db, db_index, "zrangebylex", &args, Some((RangeKind::Lex, false)), false)
 }
 
-/// ZLEXCOUNT key min max
-/// Count members between lexicographical range
-pub async fn zlexcount(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+/// ZREVRANGEBYLEX key max min [LIMIT offset count]
+/// Return members in reverse lexicographical range
+pub async fn zrevrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    range_command(db, db_index, "zrevrangebylex", &args, Some((RangeKind::Lex, true)), false)
+}
+
+/// ZREMRANGEBYLEX key min max
+/// Remove members between lexicographical range
+pub async fn zremrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
     if args.len() != 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zlexcount' command".to_string());
+        return RespValue::Error("ERR wrong number of arguments for 'zremrangebylex' command".to_string());
     }
 
     let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s,
+        Ok(s) => s.to_string(),
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let min_lex = &args[1];
-    let max_lex = &args[2];
+    let range = match LexRange::parse(&args[1], &args[2]) {
+        Ok(r) => r,
+        Err(e) => return RespValue::Error(e),
+    };
 
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    let query = RangeQuery::new(RangeBy::Lex(range));
+    let removed = with_zset_mut(&db_instance, &key, |zset| {
+        let to_remove = query.select(zset);
+        for (member, _) in &to_remove {
+            zset.remove(member);
         }
-        None => return RespValue::Integer(0),
-    };
-
-    let count = zset
-        .scores
-        .iter()
-        .filter(|((_, member), _)| {
-            lex_match(member, min_lex, max_lex)
-        })
-        .count();
+        to_remove.len()
+    });
 
-    RespValue::Integer(count as i64)
+    match removed {
+        Some(Ok(removed)) => {
+            if removed > 0 {
+                finish_removal(&db_instance, &key, "zremrangebylex");
+            }
+            RespValue::Integer(removed as i64)
+        }
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Integer(0),
+    }
 }
 
-/// ZRANGEBYLEX key min max [LIMIT offset count]
-/// Return members between lexicographical range
-pub async fn zrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zrangebylex' command".to_string());
+/// ZSCAN key cursor [MATCH pattern] [COUNT count]
+/// Incrementally iterate sorted set elements
+pub async fn zscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 2 {
+        return RespValue::Error("ERR wrong number of arguments for 'zscan' command".to_string());
     }
 
     let key = match std::str::from_utf8(&args[0]) {
@@ -1051,191 +723,349 @@ pub async fn zrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let min_lex = &args[1];
-    let max_lex = &args[2];
-
-    let mut limit_offset = 0;
-    let mut limit_count = None;
-
-    if args.len() > 3 {
-        if let Ok(s) = std::str::from_utf8(&args[3]) {
-            if s.to_uppercase() == "LIMIT" {
-                if args.len() < 6 {
-                    return RespValue::Error("ERR syntax error".to_string());
-                }
-                limit_offset = match std::str::from_utf8(&args[4]) {
-                    Ok(s) => match s.parse::<usize>() {
-                        Ok(n) => n,
-                        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                    },
-                    Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                };
-                limit_count = match std::str::from_utf8(&args[5]) {
-                    Ok(s) => match s.parse::<i64>() {
-                        Ok(n) if n >= 0 => Some(n as usize),
-                        _ => return RespValue::Error("ERR value is out of range".to_string()),
-                    },
-                    Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                };
-            }
-        }
-    }
+    let options = match ScanOptions::parse(&args[1..], &[]) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
 
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+    let page = read_zset(&db_instance, key, |zset| {
+        // Walk members by hash rather than rank: a rank cursor shifts whenever
+        // an element is added or rescored ahead of it
+        let (entries, next_cursor) = zset.scan(options.cursor, options.count);
+
+        let mut results = Vec::new();
+        for (member, score) in entries.into_iter().filter(|(member, _)| options.matches(member)) {
+            results.push(RespValue::BulkString(Some(member.to_vec())));
+            results.push(RespValue::BulkString(Some(format!("{}", score).into_bytes())));
         }
-        None => return RespValue::Array(Some(vec![])),
-    };
+        (next_cursor, results)
+    });
 
-    let results: Vec<RespValue> = zset
-        .scores
-        .iter()
-        .filter(|((_, member), _)| lex_match(member, min_lex, max_lex))
-        .skip(limit_offset)
-        .take(limit_count.unwrap_or(usize::MAX))
-        .map(|((_, member), _)| RespValue::BulkString(Some(member.to_vec())))
-        .collect();
+    match page {
+        Some(Ok((next_cursor, results))) => ScanOptions::reply(next_cursor, results),
+        Some(Err(e)) => RespValue::Error(e),
+        None => ScanOptions::reply(0, vec![]),
+    }
+}
 
-    RespValue::Array(Some(results))
+/// Parse a member score; NaN is not a valid score
+fn parse_score(bytes: &[u8]) -> Result<f64, String> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| "ERR value is not a valid float".to_string())
 }
 
-/// ZREVRANGEBYLEX key max min [LIMIT offset count]
-/// Return members in reverse lexicographical range
-pub async fn zrevrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zrevrangebylex' command".to_string());
+fn parse_integer(bytes: &[u8]) -> Result<i64, String> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".to_string())
+}
+
+/// One end of a score range: `1.5`, `(1.5` when exclusive, or `-inf`/`+inf`
+#[derive(Debug, Clone, Copy)]
+struct ScoreBound {
+    value: f64,
+    exclusive: bool,
+}
+
+impl ScoreBound {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let (exclusive, value) = match bytes.strip_prefix(b"(") {
+            Some(rest) => (true, rest),
+            None => (false, bytes),
+        };
+        std::str::from_utf8(value)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|value| !value.is_nan())
+            .map(|value| ScoreBound { value, exclusive })
+            .ok_or_else(|| "ERR min or max is not a float".to_string())
     }
+}
 
-    let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
+/// Score interval of ZRANGEBYSCORE, ZCOUNT and friends
+#[derive(Debug, Clone, Copy)]
+struct ScoreRange {
+    min: ScoreBound,
+    max: ScoreBound,
+}
 
-    let max_lex = &args[1];
-    let min_lex = &args[2];
+impl ScoreRange {
+    fn parse(min: &[u8], max: &[u8]) -> Result<Self, String> {
+        Ok(ScoreRange {
+            min: ScoreBound::parse(min)?,
+            max: ScoreBound::parse(max)?,
+        })
+    }
+}
 
-    let mut limit_offset = 0;
-    let mut limit_count = None;
+/// One end of a lexicographical range: `[a` inclusive, `(a` exclusive, or
+/// `-`/`+` for the smallest and greatest possible member
+#[derive(Debug, Clone)]
+enum LexBound {
+    NegInf,
+    PosInf,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
 
-    if args.len() > 3 {
-        if let Ok(s) = std::str::from_utf8(&args[3]) {
-            if s.to_uppercase() == "LIMIT" {
-                if args.len() < 6 {
-                    return RespValue::Error("ERR syntax error".to_string());
-                }
-                limit_offset = match std::str::from_utf8(&args[4]) {
-                    Ok(s) => match s.parse::<usize>() {
-                        Ok(n) => n,
-                        Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                    },
-                    Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                };
-                limit_count = match std::str::from_utf8(&args[5]) {
-                    Ok(s) => match s.parse::<i64>() {
-                        Ok(n) if n >= 0 => Some(n as usize),
-                        _ => return RespValue::Error("ERR value is out of range".to_string()),
-                    },
-                    Err(_) => return RespValue::Error("ERR value is not an integer".to_string()),
-                };
-            }
+impl LexBound {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        match bytes {
+            b"-" => Ok(LexBound::NegInf),
+            b"+" => Ok(LexBound::PosInf),
+            [b'[', rest @ ..] => Ok(LexBound::Inclusive(rest.to_vec())),
+            [b'(', rest @ ..] => Ok(LexBound::Exclusive(rest.to_vec())),
+            _ => Err("ERR min or max not valid string range item".to_string()),
         }
     }
+}
 
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
+/// Member interval of ZRANGEBYLEX, ZLEXCOUNT and friends
+#[derive(Debug, Clone)]
+struct LexRange {
+    min: LexBound,
+    max: LexBound,
+}
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+impl LexRange {
+    fn parse(min: &[u8], max: &[u8]) -> Result<Self, String> {
+        Ok(LexRange {
+            min: LexBound::parse(min)?,
+            max: LexBound::parse(max)?,
+        })
+    }
+
+    fn above_min(&self, member: &[u8]) -> bool {
+        match &self.min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(min) => member >= min.as_slice(),
+            LexBound::Exclusive(min) => member > min.as_slice(),
         }
-        None => return RespValue::Array(Some(vec![])),
-    };
+    }
 
-    let results: Vec<RespValue> = zset
-        .scores
-        .iter()
-        .filter(|((_, member), _)| lex_match(member, min_lex, max_lex))
-        .rev()
-        .skip(limit_offset)
-        .take(limit_count.unwrap_or(usize::MAX))
-        .map(|((_, member), _)| RespValue::BulkString(Some(member.to_vec())))
-        .collect();
+    fn below_max(&self, member: &[u8]) -> bool {
+        match &self.max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(max) => member <= max.as_slice(),
+            LexBound::Exclusive(max) => member < max.as_slice(),
+        }
+    }
+}
 
-    RespValue::Array(Some(results))
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
 }
 
-/// ZREMRANGEBYLEX key min max
-/// Remove members between lexicographical range
-pub async fn zremrangebylex(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() != 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zremrangebylex' command".to_string());
+/// What a ZRANGE-family query selects
+#[derive(Debug, Clone)]
+enum RangeBy {
+    /// Start and stop indexes, negative ones counting from the end
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// A parsed ZRANGE-family query
+#[derive(Debug, Clone)]
+struct RangeQuery {
+    by: RangeBy,
+    rev: bool,
+    /// LIMIT offset and count; a negative count means no limit
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+impl RangeQuery {
+    fn new(by: RangeBy) -> Self {
+        RangeQuery {
+            by,
+            rev: false,
+            limit: None,
+            with_scores: false,
+        }
     }
 
-    let key = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s.to_string(),
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
+    /// Parse `start stop [options]`. `fixed` is the kind and direction of the
+    /// older commands (ZREVRANGE, ZRANGEBYSCORE, ...), which only take
+    /// WITHSCORES and LIMIT; without it the unified ZRANGE options apply.
+    fn parse(
+        args: &[Vec<u8>],
+        fixed: Option<(RangeKind, bool)>,
+        allow_withscores: bool,
+    ) -> Result<Self, String> {
+        let (mut kind, mut rev) = fixed.unwrap_or((RangeKind::Rank, false));
+        let mut limit = None;
+        let mut with_scores = false;
+
+        let mut i = 2;
+        while i < args.len() {
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                "WITHSCORES" if allow_withscores => with_scores = true,
+                "LIMIT" if i + 2 < args.len() => {
+                    limit = Some((parse_integer(&args[i + 1])?, parse_integer(&args[i + 2])?));
+                    i += 2;
+                }
+                "BYSCORE" if fixed.is_none() => kind = RangeKind::Score,
+                "BYLEX" if fixed.is_none() => kind = RangeKind::Lex,
+                "REV" if fixed.is_none() => rev = true,
+                _ => return Err("ERR syntax error".to_string()),
+            }
+            i += 1;
+        }
 
-    let min_lex = &args[1];
-    let max_lex = &args[2];
+        if limit.is_some() && kind == RangeKind::Rank {
+            return Err(
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_string(),
+            );
+        }
+        if with_scores && kind == RangeKind::Lex {
+            return Err(
+                "ERR syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+            );
+        }
 
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
+        // Reversed score and lex ranges are given from the high end
+        let (low, high) = if rev && kind != RangeKind::Rank {
+            (&args[1], &args[0])
+        } else {
+            (&args[0], &args[1])
+        };
+        let by = match kind {
+            RangeKind::Rank => RangeBy::Rank(parse_integer(low)?, parse_integer(high)?),
+            RangeKind::Score => RangeBy::Score(ScoreRange::parse(low, high)?),
+            RangeKind::Lex => RangeBy::Lex(LexRange::parse(low, high)?),
+        };
 
-    let mut zset = match db_instance.get(&key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
+        Ok(RangeQuery {
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+
+    /// The selected members and their scores, in reply order
+    fn select(&self, zset: &ZSet) -> Vec<(Bytes, f64)> {
+        let (start, end) = self.ranks(zset);
+
+        // LIMIT counts from the end the reply starts at
+        let (offset, count) = self.limit.unwrap_or((0, -1));
+        if offset < 0 || start >= end {
+            return Vec::new();
         }
-        None => return RespValue::Integer(0),
-    };
+        let offset = offset as usize;
+        let count = usize::try_from(count).unwrap_or(usize::MAX);
+
+        let entries = |(member, score): (&[u8], f64)| (Bytes::copy_from_slice(member), score);
+        if self.rev {
+            let end = end.saturating_sub(offset).max(start);
+            let start = end.saturating_sub(count).max(start);
+            zset.range_by_rank(start, end).rev().map(entries).collect()
+        } else {
+            let start = start.saturating_add(offset).min(end);
+            let end = start.saturating_add(count).min(end);
+            zset.range_by_rank(start, end).map(entries).collect()
+        }
+    }
 
-    let to_remove: Vec<_> = zset
-        .scores
-        .iter()
-        .filter(|((_, member), _)| lex_match(member, min_lex, max_lex))
-        .map(|((score, member), _)| (*score, member.clone()))
-        .collect();
+    /// Number of members in the range, ignoring LIMIT, in O(log n)
+    fn count(&self, zset: &ZSet) -> usize {
+        let (start, end) = self.ranks(zset);
+        end.saturating_sub(start)
+    }
 
-    let removed = to_remove.len();
+    /// Ascending ranks start..end of the range, found in O(log n); empty
+    /// when start is not below end
+    fn ranks(&self, zset: &ZSet) -> (usize, usize) {
+        match &self.by {
+            RangeBy::Rank(start, stop) => {
+                let Some((start, stop)) = rank_bounds(*start, *stop, zset.len()) else {
+                    return (0, 0);
+                };
+                if self.rev {
+                    (zset.len() - 1 - stop, zset.len() - start)
+                } else {
+                    (start, stop + 1)
+                }
+            }
+            RangeBy::Score(range) => (
+                zset.count_below(range.min.value, range.min.exclusive),
+                zset.count_below(range.max.value, !range.max.exclusive),
+            ),
+            // Lex ranges assume all members share one score, as in Redis,
+            // so members are in order and can be binary searched by rank
+            RangeBy::Lex(range) => (
+                partition_rank(zset, |member| !range.above_min(member)),
+                partition_rank(zset, |member| range.below_max(member)),
+            ),
+        }
+    }
+}
 
-    for (score, member) in to_remove {
-        zset.scores.remove(&(score, member.clone()));
-        zset.members.remove(&member);
+/// First rank whose member fails `pred`, for a `pred` that holds for a
+/// prefix of the set
+fn partition_rank(zset: &ZSet, pred: impl Fn(&[u8]) -> bool) -> usize {
+    let (mut low, mut high) = (0, zset.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match zset.get_by_rank(mid) {
+            Some((member, _)) if pred(member) => low = mid + 1,
+            _ => high = mid,
+        }
     }
+    low
+}
 
-    if zset.is_empty() {
-        db_instance.delete(&key);
-    } else {
-        db_instance.set(key, RedisValue::ZSet(zset));
+/// Clamp ZRANGE-style start/stop indexes to `len`; None if nothing is selected
+fn rank_bounds(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+    if start > stop || start >= len {
+        return None;
     }
+    Some((start as usize, stop as usize))
+}
 
-    RespValue::Integer(removed as i64)
+/// Members, each followed by its score when `with_scores` is set
+fn range_reply(entries: Vec<(Bytes, f64)>, with_scores: bool) -> RespValue {
+    let mut reply = Vec::with_capacity(entries.len() * if with_scores { 2 } else { 1 });
+    for (member, score) in entries {
+        reply.push(RespValue::BulkString(Some(member.to_vec())));
+        if with_scores {
+            reply.push(RespValue::BulkString(Some(score.to_string().into_bytes())));
+        }
+    }
+    RespValue::Array(Some(reply))
 }
 
-/// ZSCAN key cursor [MATCH pattern] [COUNT count]
-/// Incrementally iterate sorted set elements
-pub async fn zscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 2 {
-        return RespValue::Error("ERR wrong number of arguments for 'zscan' command".to_string());
+/// Shared body of ZRANGE and the older range commands: `args` is
+/// `key start stop [options]`
+fn range_command(
+    db: &Arc<Database>,
+    db_index: usize,
+    name: &str,
+    args: &[Vec<u8>],
+    fixed: Option<(RangeKind, bool)>,
+    allow_withscores: bool,
+) -> RespValue {
+    if args.len() < 3 {
+        return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", name));
     }
 
     let key = match std::str::from_utf8(&args[0]) {
@@ -1243,134 +1073,73 @@ pub async fn zscan(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let cursor = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR invalid cursor".to_string()),
+    let query = match RangeQuery::parse(&args[1..], fixed, allow_withscores) {
+        Ok(q) => q,
+        Err(e) => return RespValue::Error(e),
     };
 
-    let mut count = 10; // Default count
-    let mut i = 2;
-
-    while i < args.len() {
-        if let Ok(s) = std::str::from_utf8(&args[i]) {
-            match s.to_uppercase().as_str() {
-                "COUNT" => {
-                    i += 1;
-                    if i < args.len() {
-                        if let Ok(c_str) = std::str::from_utf8(&args[i]) {
-                            count = c_str.parse::<usize>().unwrap_or(10);
-                        }
-                    }
-                }
-                "MATCH" => {
-                    // Pattern matching not implemented in this basic version
-                    i += 1;
-                }
-                _ => {}
-            }
-        }
-        i += 1;
-    }
-
     let db_instance = match db.get_db(db_index) {
         Some(d) => d,
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => {
-            return RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(b"0".to_vec())),
-                RespValue::Array(Some(vec![])),
-            ]));
-        }
-    };
-
-    let members: Vec<_> = zset.scores.iter().collect();
-    let start = cursor;
-    let end = (start + count).min(members.len());
-    let next_cursor = if end >= members.len() { 0 } else { end };
-
-    let mut results = Vec::new();
-    for i in start..end {
-        if let Some(((score, member), _)) = members.get(i) {
-            results.push(RespValue::BulkString(Some(member.to_vec())));
-            results.push(RespValue::BulkString(Some(format!("{}", score.0).into_bytes())));
-        }
+    match read_zset(&db_instance, key, |zset| query.select(zset)) {
+        Some(Ok(entries)) => range_reply(entries, query.with_scores),
+        Some(Err(e)) => RespValue::Error(e),
+        None => RespValue::Array(Some(vec![])),
     }
-
-    RespValue::Array(Some(vec![
-        RespValue::BulkString(Some(next_cursor.to_string().into_bytes())),
-        RespValue::Array(Some(results)),
-    ]))
 }
 
-// Helper function for lexicographical matching
-fn lex_match(member: &Bytes, min: &[u8], max: &[u8]) -> bool {
-    let min_inclusive = min.first() == Some(&b'[');
-    let max_inclusive = max.first() == Some(&b'[');
-
-    let min_val = if min == b"-" {
-        return true; // -inf
-    } else if min == b"+" {
-        return false; // +inf
-    } else if min_inclusive || min.first() == Some(&b'(') {
-        &min[1..]
-    } else {
-        min
-    };
-
-    let max_val = if max == b"+" {
-        return true; // +inf
-    } else if max == b"-" {
-        return false; // -inf
-    } else if max_inclusive || max.first() == Some(&b'(') {
-        &max[1..]
-    } else {
-        max
-    };
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
 
-    let min_check = if min == b"-" {
-        true
-    } else if min_inclusive {
-        member.as_ref() >= min_val
-    } else {
-        member.as_ref() > min_val
-    };
+/// Run `f` on the sorted set at `key` without cloning it. None if the key
+/// does not exist, a WRONGTYPE error if it holds another type
+fn read_zset<R>(db_instance: &DbInstance, key: &str, f: impl FnOnce(&ZSet) -> R) -> Option<Result<R, String>> {
+    db_instance.lookup_read_with(key, |value| match value {
+        RedisValue::ZSet(zset) => Ok(f(zset)),
+        _ => Err(WRONGTYPE.to_string()),
+    })
+}
 
-    let max_check = if max == b"+" {
-        true
-    } else if max_inclusive {
-        member.as_ref() <= max_val
-    } else {
-        member.as_ref() < max_val
-    };
+/// `read_zset` for writes: the set is changed in place while its shard is
+/// locked, so concurrent writers of the key cannot lose each other's edits
+fn with_zset_mut<R>(db_instance: &DbInstance, key: &str, f: impl FnOnce(&mut ZSet) -> R) -> Option<Result<R, String>> {
+    db_instance.with_value_mut(key, |value| match value {
+        RedisValue::ZSet(zset) => Ok(f(zset)),
+        _ => Err(WRONGTYPE.to_string()),
+    })
+}
 
-    min_check && max_check
+/// `with_zset_mut`, first storing an empty set if the key does not exist
+fn with_zset_or_insert<R>(db_instance: &DbInstance, key: &str, f: impl FnOnce(&mut ZSet) -> R) -> Result<R, String> {
+    db_instance.with_value_or_insert(
+        key,
+        || RedisValue::ZSet(db_instance.new_zset()),
+        |value| match value {
+            RedisValue::ZSet(zset) => Ok(f(zset)),
+            _ => Err(WRONGTYPE.to_string()),
+        },
+    )
 }
 
-// Helper function to parse score range (handles -inf, +inf)
-fn parse_score_range(bytes: &[u8]) -> Result<f64, String> {
-    let s = match std::str::from_utf8(bytes) {
-        Ok(s) => s,
-        Err(_) => return Err("ERR min or max is not a float".to_string()),
-    };
+/// Publish `event` after members were removed from the set at `key` in
+/// place, deleting the key once it is empty
+fn finish_removal(db_instance: &DbInstance, key: &str, event: &str) {
+    let emptied = db_instance.delete_if(key, |value| matches!(value, RedisValue::ZSet(zset) if zset.is_empty()));
+    db_instance.notify(EventClass::ZSET, event, key);
+    if emptied {
+        db_instance.notify(EventClass::GENERIC, "del", key);
+    }
+}
 
-    match s {
-        "-inf" => Ok(f64::NEG_INFINITY),
-        "+inf" | "inf" => Ok(f64::INFINITY),
-        _ => s
-            .parse::<f64>()
-            .map_err(|_| "ERR min or max is not a float".to_string()),
+/// Store the result of ZRANGESTORE and friends at `dest`; an empty result
+/// deletes the key instead
+fn store_result(db_instance: &DbInstance, dest: String, result: ZSet, event: &str) {
+    if !result.is_empty() {
+        db_instance.set(dest.clone(), RedisValue::ZSet(result));
+        db_instance.notify(EventClass::ZSET, event, &dest);
+    } else if db_instance.delete(&dest) {
+        db_instance.notify(EventClass::GENERIC, "del", &dest);
     }
 }
 
@@ -1481,6 +1250,281 @@ mod tests {
         let result = zrevrank(&db, 0, vec![b"myzset".to_vec(), b"b".to_vec()]).await;
         assert_eq!(result, RespValue::Integer(1));
     }
+
+    fn args(parts: &[&str]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    fn bulks(parts: &[&str]) -> RespValue {
+        RespValue::Array(Some(
+            parts.iter().map(|p| RespValue::BulkString(Some(p.as_bytes().to_vec()))).collect(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_zadd_options() {
+        let db = Arc::new(Database::new(16));
+        zadd(&db, 0, args(&["z", "1", "a", "2", "b"])).await;
+
+        // XX never adds, NX never updates
+        assert_eq!(zadd(&db, 0, args(&["z", "XX", "5", "c"])).await, RespValue::Integer(0));
+        assert_eq!(zadd(&db, 0, args(&["z", "NX", "5", "a", "3", "c"])).await, RespValue::Integer(1));
+        assert_eq!(zscore(&db, 0, args(&["z", "a"])).await, RespValue::BulkString(Some(b"1".to_vec())));
+
+        // GT only raises, CH counts updates too
+        assert_eq!(
+            zadd(&db, 0, args(&["z", "GT", "CH", "0", "a", "4", "b", "1", "d"])).await,
+            RespValue::Integer(2)
+        );
+        assert_eq!(zscore(&db, 0, args(&["z", "a"])).await, RespValue::BulkString(Some(b"1".to_vec())));
+        assert_eq!(zscore(&db, 0, args(&["z", "b"])).await, RespValue::BulkString(Some(b"4".to_vec())));
+
+        assert_eq!(
+            zadd(&db, 0, args(&["z", "INCR", "2.5", "a"])).await,
+            RespValue::BulkString(Some(b"3.5".to_vec()))
+        );
+        assert_eq!(
+            zadd(&db, 0, args(&["z", "LT", "INCR", "1", "a"])).await,
+            RespValue::BulkString(None)
+        );
+
+        for (bad, error) in [
+            (&["z", "NX", "XX", "1", "a"][..], "ERR XX and NX options at the same time are not compatible"),
+            (&["z", "NX", "GT", "1", "a"][..], "ERR GT, LT, and/or NX options at the same time are not compatible"),
+            (&["z", "INCR", "1", "a", "2", "b"][..], "ERR INCR option supports a single increment-element pair"),
+            (&["z", "1", "a", "2"][..], "ERR syntax error"),
+            (&["z", "nan", "a"][..], "ERR value is not a valid float"),
+        ] {
+            assert_eq!(zadd(&db, 0, args(bad)).await, RespValue::Error(error.to_string()));
+        }
+
+        // XX against a missing key creates nothing
+        zadd(&db, 0, args(&["missing", "XX", "1", "a"])).await;
+        assert_eq!(zcard(&db, 0, args(&["missing"])).await, RespValue::Integer(0));
+        assert!(db.get_db(0).unwrap().get("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unified_zrange() {
+        let db = Arc::new(Database::new(16));
+        zadd(&db, 0, args(&["z", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e"])).await;
+
+        assert_eq!(zrange(&db, 0, args(&["z", "-2", "-1"])).await, bulks(&["d", "e"]));
+        assert_eq!(zrange(&db, 0, args(&["z", "0", "1", "REV"])).await, bulks(&["e", "d"]));
+        assert_eq!(
+            zrange(&db, 0, args(&["z", "(1", "3", "BYSCORE", "WITHSCORES"])).await,
+            bulks(&["b", "2", "c", "3"])
+        );
+        assert_eq!(
+            zrange(&db, 0, args(&["z", "+inf", "(2", "BYSCORE", "REV", "LIMIT", "1", "2"])).await,
+            bulks(&["d", "c"])
+        );
+        assert_eq!(
+            zrange(&db, 0, args(&["z", "-inf", "+inf", "BYSCORE", "LIMIT", "3", "-1"])).await,
+            bulks(&["d", "e"])
+        );
+        assert_eq!(zrange(&db, 0, args(&["z", "[b", "(d", "BYLEX"])).await, bulks(&["b", "c"]));
+        assert_eq!(zrange(&db, 0, args(&["z", "+", "[d", "BYLEX", "REV"])).await, bulks(&["e", "d"]));
+        assert_eq!(zrangebylex(&db, 0, args(&["z", "-", "[b"])).await, bulks(&["a", "b"]));
+        assert_eq!(zcount(&db, 0, args(&["z", "(1", "(5"])).await, RespValue::Integer(3));
+        assert_eq!(zrevrangebyscore(&db, 0, args(&["z", "(3", "-inf"])).await, bulks(&["b", "a"]));
+
+        for (bad, error) in [
+            (&["z", "0", "1", "LIMIT", "0", "1"][..], "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"),
+            (&["z", "-", "+", "BYLEX", "WITHSCORES"][..], "ERR syntax error, WITHSCORES not supported in combination with BYLEX"),
+            (&["z", "a", "b", "BYLEX"][..], "ERR min or max not valid string range item"),
+            (&["z", "x", "1", "BYSCORE"][..], "ERR min or max is not a float"),
+        ] {
+            assert_eq!(zrange(&db, 0, args(bad)).await, RespValue::Error(error.to_string()));
+        }
+
+        assert_eq!(
+            zrangestore(&db, 0, args(&["dst", "z", "5", "3", "BYSCORE", "REV"])).await,
+            RespValue::Integer(3)
+        );
+        assert_eq!(zrange(&db, 0, args(&["dst", "0", "-1"])).await, bulks(&["c", "d", "e"]));
+        assert_eq!(
+            zrangestore(&db, 0, args(&["dst", "z", "10", "20"])).await,
+            RespValue::Integer(0)
+        );
+        assert!(db.get_db(0).unwrap().get("dst").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_zunion_zinter_weights_aggregate() {
+        let db = Arc::new(Database::new(16));
+        zadd(&db, 0, args(&["z1", "1", "a", "2", "b", "3", "c"])).await;
+        zadd(&db, 0, args(&["z2", "10", "b", "20", "c", "30", "d"])).await;
+        crate::commands::set::sadd(&db, 0, args(&["s", "c", "d"])).await;
+
+        assert_eq!(
+            zunion(&db, 0, args(&["2", "z1", "z2", "WEIGHTS", "2", "1", "WITHSCORES"])).await,
+            bulks(&["a", "2", "b", "14", "c", "26", "d", "30"])
+        );
+        assert_eq!(
+            zinter(&db, 0, args(&["2", "z1", "z2", "AGGREGATE", "MAX", "WITHSCORES"])).await,
+            bulks(&["b", "10", "c", "20"])
+        );
+        // Plain sets count with score 1
+        assert_eq!(
+            zinter(&db, 0, args(&["3", "z1", "z2", "s", "AGGREGATE", "MIN", "WITHSCORES"])).await,
+            bulks(&["c", "1"])
+        );
+        assert_eq!(zintercard(&db, 0, args(&["2", "z1", "z2"])).await, RespValue::Integer(2));
+        assert_eq!(zintercard(&db, 0, args(&["2", "z1", "z2", "LIMIT", "1"])).await, RespValue::Integer(1));
+        assert_eq!(
+            zintercard(&db, 0, args(&["2", "z1", "z2", "LIMIT", "-1"])).await,
+            RespValue::Error("ERR LIMIT can't be negative".to_string())
+        );
+
+        assert_eq!(
+            zunionstore(&db, 0, args(&["out", "2", "z1", "z2", "AGGREGATE", "MIN"])).await,
+            RespValue::Integer(4)
+        );
+        assert_eq!(zscore(&db, 0, args(&["out", "c"])).await, RespValue::BulkString(Some(b"3".to_vec())));
+        assert_eq!(
+            zinterstore(&db, 0, args(&["out", "2", "z1", "missing"])).await,
+            RespValue::Integer(0)
+        );
+        assert!(db.get_db(0).unwrap().get("out").is_none());
+
+        for (bad, error) in [
+            (&["2", "z1", "z2", "WEIGHTS", "1"][..], "ERR syntax error"),
+            (&["2", "z1", "z2", "WEIGHTS", "1", "x"][..], "ERR weight value is not a float"),
+            (&["2", "z1", "z2", "AGGREGATE", "AVG"][..], "ERR syntax error"),
+            (&["0", "z1"][..], "ERR at least 1 input key is needed for ZUNION"),
+        ] {
+            assert_eq!(zunion(&db, 0, args(bad)).await, RespValue::Error(error.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zrandmember() {
+        let db = Arc::new(Database::new(16));
+        zadd(&db, 0, args(&["z", "1", "a", "2", "b", "3", "c"])).await;
+
+        let RespValue::Array(Some(distinct)) = zrandmember(&db, 0, args(&["z", "10"])).await else {
+            panic!("expected an array");
+        };
+        let mut distinct: Vec<_> = distinct.into_iter().collect();
+        distinct.sort_by_key(|m| format!("{:?}", m));
+        assert_eq!(RespValue::Array(Some(distinct)), bulks(&["a", "b", "c"]));
+
+        let RespValue::Array(Some(repeated)) =
+            zrandmember(&db, 0, args(&["z", "-7", "WITHSCORES"])).await
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(repeated.len(), 14);
+
+        assert!(matches!(zrandmember(&db, 0, args(&["z"])).await, RespValue::BulkString(Some(_))));
+        assert_eq!(zrandmember(&db, 0, args(&["missing"])).await, RespValue::BulkString(None));
+        assert_eq!(zrandmember(&db, 0, args(&["missing", "2"])).await, RespValue::Array(Some(vec![])));
+    }
+
+    #[tokio::test]
+    async fn test_zmpop() {
+        let db = Arc::new(Database::new(16));
+        zadd(&db, 0, args(&["z", "1", "a", "2", "b", "3", "c"])).await;
+
+        let popped = zmpop(&db, 0, args(&["2", "empty", "z", "MAX", "COUNT", "2"])).await;
+        assert_eq!(
+            popped,
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"z".to_vec())),
+                RespValue::Array(Some(vec![bulks(&["c", "3"]), bulks(&["b", "2"])])),
+            ]))
+        );
+        let command = args(&["ZMPOP", "2", "empty", "z", "MAX", "COUNT", "2"]);
+        assert_eq!(propagated_commands(&command, &popped), vec![args(&["ZPOPMAX", "z", "2"])]);
+
+        zmpop(&db, 0, args(&["1", "z", "MIN"])).await;
+        assert!(db.get_db(0).unwrap().get("z").is_none());
+        assert_eq!(zmpop(&db, 0, args(&["1", "z", "MIN"])).await, RespValue::Array(None));
+        let nothing = propagated_commands(&args(&["ZMPOP", "1", "z", "MIN"]), &RespValue::Array(None));
+        assert!(nothing.is_empty());
+
+        assert_eq!(
+            zmpop(&db, 0, args(&["1", "z", "MIN", "COUNT", "0"])).await,
+            RespValue::Error("ERR count should be greater than 0".to_string())
+        );
+        assert_eq!(
+            bzmpop(&db, 0, args(&["0.05", "1", "z", "MIN"])).await,
+            RespValue::Array(None)
+        );
+
+        let waiter = {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move { bzmpop(&db, 0, args(&["0", "1", "z", "MIN"])).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+        zadd(&db, 0, args(&["z", "7", "x"])).await;
+        assert_eq!(
+            waiter.await.unwrap(),
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"z".to_vec())),
+                RespValue::Array(Some(vec![bulks(&["x", "7"])])),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zscan_survives_rescoring() {
+        let db = Arc::new(Database::new(16));
+        for i in 0..100 {
+            zadd(&db, 0, args(&["z", &i.to_string(), &format!("m{}", i)])).await;
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = b"0".to_vec();
+        for call in 0.. {
+            let reply = zscan(&db, 0, vec![b"z".to_vec(), cursor, b"COUNT".to_vec(), b"10".to_vec()]).await;
+            let RespValue::Array(Some(reply)) = reply else { panic!("unexpected ZSCAN reply") };
+            let RespValue::BulkString(Some(next)) = &reply[0] else { panic!("unexpected cursor") };
+            let RespValue::Array(Some(entries)) = &reply[1] else { panic!("unexpected entries") };
+            seen.extend(entries.iter().step_by(2).cloned());
+            cursor = next.clone();
+            // Moving members to the front would shift a rank cursor
+            zadd(&db, 0, args(&["z", "-1", &format!("new{}", call)])).await;
+            if cursor == b"0" {
+                break;
+            }
+        }
+
+        for i in 0..100 {
+            let member = RespValue::BulkString(Some(format!("m{}", i).into_bytes()));
+            assert_eq!(seen.iter().filter(|m| **m == member).count(), 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_writes_and_counts() {
+        let db = Arc::new(Database::new(16));
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let db = Arc::clone(&db);
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        zincrby(&db, 0, args(&["z", "1", "m"])).await;
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert_eq!(zscore(&db, 0, args(&["z", "m"])).await, RespValue::BulkString(Some(b"800".to_vec())));
+
+        for (score, member) in [("1", "a"), ("2", "b"), ("3", "c")] {
+            zadd(&db, 0, args(&["lex", "0", member])).await;
+            zadd(&db, 0, args(&["s", score, member])).await;
+        }
+        assert_eq!(zcount(&db, 0, args(&["s", "(1", "+inf"])).await, RespValue::Integer(2));
+        assert_eq!(zcount(&db, 0, args(&["s", "3", "1"])).await, RespValue::Integer(0));
+        assert_eq!(zlexcount(&db, 0, args(&["lex", "[b", "+"])).await, RespValue::Integer(2));
+        assert_eq!(zremrangebyrank(&db, 0, args(&["s", "0", "-1"])).await, RespValue::Integer(3));
+        assert!(!db.get_db(0).unwrap().exists("s"));
+    }
 }
 
 /// ZMSCORE key member [member ...]
@@ -1500,31 +1544,19 @@ pub async fn zmscore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) ->
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    let zset = match db_instance.get(key) {
-        Some(RedisValue::ZSet(z)) => z,
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => {
-            // If key doesn't exist, return array of nulls
-            let nulls = vec![RespValue::BulkString(None); args.len() - 1];
-            return RespValue::Array(Some(nulls));
-        }
-    };
+    let scores = read_zset(&db_instance, key, |zset| {
+        args[1..]
+            .iter()
+            .map(|member| RespValue::BulkString(zset.score(member).map(|score| format!("{}", score).into_bytes())))
+            .collect()
+    });
 
-    let mut results = Vec::new();
-    for member_bytes in &args[1..] {
-        let member = Bytes::from(member_bytes.clone());
-        if let Some(&score) = zset.members.get(&member) {
-            results.push(RespValue::BulkString(Some(format!("{}", score).into_bytes())));
-        } else {
-            results.push(RespValue::BulkString(None));
-        }
+    match scores {
+        Some(Ok(results)) => RespValue::Array(Some(results)),
+        Some(Err(e)) => RespValue::Error(e),
+        // If key doesn't exist, return array of nulls
+        None => RespValue::Array(Some(vec![RespValue::BulkString(None); args.len() - 1])),
     }
-
-    RespValue::Array(Some(results))
 }
 
 /// ZDIFF numkeys key [key ...] [WITHSCORES]
@@ -1570,14 +1602,14 @@ pub async fn zdiff(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let mut result_zset = match db_instance.get(first_key) {
+    let mut result_zset = match db_instance.lookup_read(first_key) {
         Some(RedisValue::ZSet(z)) => z.clone(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => ZSet::new(),
+        None => db_instance.new_zset(),
     };
 
     // Remove members from subsequent sets
@@ -1587,22 +1619,18 @@ pub async fn zdiff(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
             Err(_) => continue,
         };
 
-        if let Some(RedisValue::ZSet(zset)) = db_instance.get(key) {
-            for member in zset.members.keys() {
-                if let Some(&score) = result_zset.members.get(member) {
-                    result_zset.scores.remove(&(OrderedFloat(score), member.clone()));
-                    result_zset.members.remove(member);
-                }
+        if let Some(RedisValue::ZSet(zset)) = db_instance.lookup_read(key) {
+            for (member, _) in zset.iter() {
+                result_zset.remove(member);
             }
         }
     }
 
     // Build response
     let mut response = Vec::new();
-    for ((_, member), _) in result_zset.scores.iter() {
+    for (member, score) in result_zset.iter() {
         response.push(RespValue::BulkString(Some(member.to_vec())));
         if with_scores {
-            let score = result_zset.members.get(member).unwrap();
             response.push(RespValue::BulkString(Some(format!("{}", score).into_bytes())));
         }
     }
@@ -1610,173 +1638,11 @@ pub async fn zdiff(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> R
     RespValue::Array(Some(response))
 }
 
-/// ZDIFFSTORE destination numkeys key [key ...]
-/// Store difference between first and successive sets
-pub async fn zdiffstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zdiffstore' command".to_string());
-    }
-
-    let dest = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s.to_string(),
-        Err(_) => return RespValue::Error("ERR invalid destination key".to_string()),
-    };
-
-    let numkeys: usize = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-    };
-
-    if numkeys < 1 {
-        return RespValue::Error("ERR at least 1 input key is needed for ZDIFFSTORE".to_string());
-    }
-
-    if args.len() < numkeys + 2 {
-        return RespValue::Error("ERR syntax error".to_string());
-    }
-
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
-
-    // Get first set
-    let first_key = match std::str::from_utf8(&args[2]) {
-        Ok(s) => s,
-        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
-    };
-
-    let mut result_zset = match db_instance.get(first_key) {
-        Some(RedisValue::ZSet(z)) => z.clone(),
-        Some(_) => {
-            return RespValue::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            )
-        }
-        None => ZSet::new(),
-    };
-
-    // Remove members from subsequent sets
-    for i in 3..(numkeys + 2) {
-        if i >= args.len() {
-            break;
-        }
-        let key = match std::str::from_utf8(&args[i]) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
-
-        if let Some(RedisValue::ZSet(zset)) = db_instance.get(key) {
-            for member in zset.members.keys() {
-                if let Some(&score) = result_zset.members.get(member) {
-                    result_zset.scores.remove(&(OrderedFloat(score), member.clone()));
-                    result_zset.members.remove(member);
-                }
-            }
-        }
-    }
-
-    let count = result_zset.len() as i64;
-
-    // Store result
-    if result_zset.is_empty() {
-        db_instance.delete(&dest);
-    } else {
-        db_instance.set(dest, RedisValue::ZSet(result_zset));
-    }
-
-    RespValue::Integer(count)
-}
-
-/// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]
-/// Compute union and store result
-pub async fn zunionstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
-    if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zunionstore' command".to_string());
-    }
-
-    let dest = match std::str::from_utf8(&args[0]) {
-        Ok(s) => s.to_string(),
-        Err(_) => return RespValue::Error("ERR invalid destination key".to_string()),
-    };
-
-    let numkeys: usize = match std::str::from_utf8(&args[1]) {
-        Ok(s) => match s.parse() {
-            Ok(n) => n,
-            Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-        },
-        Err(_) => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
-    };
-
-    if numkeys < 1 {
-        return RespValue::Error("ERR at least 1 input key is needed for ZUNIONSTORE".to_string());
-    }
-
-    if args.len() < numkeys + 2 {
-        return RespValue::Error("ERR syntax error".to_string());
-    }
-
-    let db_instance = match db.get_db(db_index) {
-        Some(d) => d,
-        None => return RespValue::Error("ERR invalid database".to_string()),
-    };
-
-    // Parse weights and aggregate (simplified - default weights=1, aggregate=SUM)
-    let weights = vec![1.0; numkeys];
-    let aggregate = "SUM"; // Default
-
-    // Collect all members with scores from all sets
-    let mut result_members: std::collections::HashMap<Bytes, Vec<f64>> = std::collections::HashMap::new();
-
-    for i in 0..numkeys {
-        let key = match std::str::from_utf8(&args[2 + i]) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
-
-        if let Some(RedisValue::ZSet(zset)) = db_instance.get(key) {
-            for (member, &score) in &zset.members {
-                result_members
-                    .entry(member.clone())
-                    .or_default()
-                    .push(score * weights[i]);
-            }
-        }
-    }
-
-    // Create result zset with aggregated scores
-    let mut result_zset = ZSet::new();
-    for (member, scores) in result_members {
-        let final_score = match aggregate {
-            "MIN" => scores.iter().cloned().fold(f64::INFINITY, f64::min),
-            "MAX" => scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-            _ => scores.iter().sum(), // SUM
-        };
-
-        result_zset.members.insert(member.clone(), final_score);
-        result_zset.scores.insert((OrderedFloat(final_score), member), ());
-    }
-
-    let count = result_zset.len() as i64;
-
-    // Store result
-    if result_zset.is_empty() {
-        db_instance.delete(&dest);
-    } else {
-        db_instance.set(dest, RedisValue::ZSet(result_zset));
-    }
-
-    RespValue::Integer(count)
-}
-
-/// ZINTERSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]
-/// Compute intersection and store result
-pub async fn zinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+/// ZDIFFSTORE destination numkeys key [key ...]
+/// Store difference between first and successive sets
+pub async fn zdiffstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
     if args.len() < 3 {
-        return RespValue::Error("ERR wrong number of arguments for 'zinterstore' command".to_string());
+        return RespValue::Error("ERR wrong number of arguments for 'zdiffstore' command".to_string());
     }
 
     let dest = match std::str::from_utf8(&args[0]) {
@@ -1793,7 +1659,7 @@ pub async fn zinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
     };
 
     if numkeys < 1 {
-        return RespValue::Error("ERR at least 1 input key is needed for ZINTERSTORE".to_string());
+        return RespValue::Error("ERR at least 1 input key is needed for ZDIFFSTORE".to_string());
     }
 
     if args.len() < numkeys + 2 {
@@ -1805,89 +1671,354 @@ pub async fn zinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>
         None => return RespValue::Error("ERR invalid database".to_string()),
     };
 
-    // Parse weights and aggregate (simplified - default weights=1, aggregate=SUM)
-    let weights = vec![1.0; numkeys];
-    let aggregate = "SUM"; // Default
-
-    // Get first set as base
+    // Get first set
     let first_key = match std::str::from_utf8(&args[2]) {
         Ok(s) => s,
         Err(_) => return RespValue::Error("ERR invalid key".to_string()),
     };
 
-    let first_zset = match db_instance.get(first_key) {
-        Some(RedisValue::ZSet(z)) => z,
+    let mut result_zset = match db_instance.get(first_key) {
+        Some(RedisValue::ZSet(z)) => z.clone(),
         Some(_) => {
             return RespValue::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             )
         }
-        None => {
-            // If first set is empty, intersection is empty
-            db_instance.delete(&dest);
-            return RespValue::Integer(0);
+        None => db_instance.new_zset(),
+    };
+
+    // Remove members from subsequent sets
+    for i in 3..(numkeys + 2) {
+        if i >= args.len() {
+            break;
+        }
+        let key = match std::str::from_utf8(&args[i]) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+
+        if let Some(RedisValue::ZSet(zset)) = db_instance.get(key) {
+            for (member, _) in zset.iter() {
+                result_zset.remove(member);
+            }
         }
+    }
+
+    let count = result_zset.len() as i64;
+
+    // Store result
+    store_result(&db_instance, dest, result_zset, "zdiffstore");
+
+    RespValue::Integer(count)
+}
+
+/// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]
+/// Compute union and store result
+pub async fn zunionstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    set_operation_store(db, db_index, "ZUNIONSTORE", &args, false)
+}
+
+/// ZINTERSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]
+/// Compute intersection and store result
+pub async fn zinterstore(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    set_operation_store(db, db_index, "ZINTERSTORE", &args, true)
+}
+
+/// ZUNION numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]
+pub async fn zunion(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    set_operation_reply(db, db_index, "ZUNION", &args, false)
+}
+
+/// ZINTER numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]
+pub async fn zinter(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    set_operation_reply(db, db_index, "ZINTER", &args, true)
+}
+
+/// ZINTERCARD numkeys key [key ...] [LIMIT limit]
+/// Size of the intersection, counting at most `limit` members (0 = all)
+pub async fn zintercard(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 2 {
+        return RespValue::Error(
+            "ERR wrong number of arguments for 'zintercard' command".to_string(),
+        );
+    }
+
+    let keys = match parse_numkeys(&args) {
+        Ok(keys) => keys,
+        Err(e) => return RespValue::Error(e),
     };
 
-    // Collect all members that exist in ALL sets
-    let mut result_members: std::collections::HashMap<Bytes, Vec<f64>> = std::collections::HashMap::new();
+    let mut limit = 0;
+    let rest = &args[1 + keys.len()..];
+    match rest {
+        [] => {}
+        [option, value] if option.eq_ignore_ascii_case(b"LIMIT") => match parse_integer(value) {
+            Ok(n) if n >= 0 => limit = n as usize,
+            Ok(_) => return RespValue::Error("ERR LIMIT can't be negative".to_string()),
+            Err(e) => return RespValue::Error(e),
+        },
+        _ => return RespValue::Error("ERR syntax error".to_string()),
+    }
 
-    for (member, &score) in &first_zset.members {
-        let mut scores_in_all = vec![score * weights[0]];
-        let mut exists_in_all = true;
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
 
-        // Check if member exists in all other sets
-        for i in 1..numkeys {
-            let key = match std::str::from_utf8(&args[2 + i]) {
-                Ok(s) => s,
-                Err(_) => {
-                    exists_in_all = false;
-                    break;
-                }
-            };
+    let sources = match load_sources(&db_instance, &keys) {
+        Ok(sources) => sources,
+        Err(e) => return RespValue::Error(e),
+    };
 
-            if let Some(RedisValue::ZSet(zset)) = db_instance.get(key) {
-                if let Some(&s) = zset.members.get(member) {
-                    scores_in_all.push(s * weights[i]);
-                } else {
-                    exists_in_all = false;
-                    break;
-                }
-            } else {
-                // Set doesn't exist or wrong type - no intersection
-                exists_in_all = false;
+    let Some(smallest) = (0..sources.len()).min_by_key(|&i| sources[i].len()) else {
+        return RespValue::Integer(0);
+    };
+    let mut count = 0;
+    for member in sources[smallest].keys() {
+        if sources.iter().all(|source| source.contains_key(member)) {
+            count += 1;
+            if count == limit {
                 break;
             }
         }
+    }
+
+    RespValue::Integer(count as i64)
+}
+
+/// How ZUNION and ZINTER combine the scores of a member found in several sets
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
 
-        if exists_in_all {
-            result_members.insert(member.clone(), scores_in_all);
+impl Aggregate {
+    fn combine(self, a: f64, b: f64) -> f64 {
+        match self {
+            // inf + -inf is 0, not NaN
+            Aggregate::Sum => zero_if_nan(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
         }
     }
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+/// `numkeys key [key ...] [WEIGHTS ...] [AGGREGATE ...] [WITHSCORES]` of
+/// ZUNION, ZINTER and their STORE variants
+#[derive(Debug)]
+struct SetOperation {
+    keys: Vec<String>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
 
-    // Create result zset with aggregated scores
-    let mut result_zset = ZSet::new();
-    for (member, scores) in result_members {
-        let final_score = match aggregate {
-            "MIN" => scores.iter().cloned().fold(f64::INFINITY, f64::min),
-            "MAX" => scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-            _ => scores.iter().sum(), // SUM
+impl SetOperation {
+    fn parse(args: &[Vec<u8>], name: &str, allow_withscores: bool) -> Result<Self, String> {
+        if parse_integer(&args[0]) == Ok(0) {
+            return Err(format!("ERR at least 1 input key is needed for {}", name));
+        }
+        let keys = parse_numkeys(args)?;
+        let mut operation = SetOperation {
+            weights: vec![1.0; keys.len()],
+            keys,
+            aggregate: Aggregate::Sum,
+            with_scores: false,
         };
 
-        result_zset.members.insert(member.clone(), final_score);
-        result_zset.scores.insert((OrderedFloat(final_score), member), ());
+        let mut i = 1 + operation.keys.len();
+        while i < args.len() {
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                "WEIGHTS" if i + operation.keys.len() < args.len() => {
+                    for weight in operation.weights.iter_mut() {
+                        i += 1;
+                        *weight = std::str::from_utf8(&args[i])
+                            .ok()
+                            .and_then(|s| s.parse::<f64>().ok())
+                            .filter(|w| !w.is_nan())
+                            .ok_or_else(|| "ERR weight value is not a float".to_string())?;
+                    }
+                }
+                "AGGREGATE" if i + 1 < args.len() => {
+                    i += 1;
+                    operation.aggregate = match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                        "SUM" => Aggregate::Sum,
+                        "MIN" => Aggregate::Min,
+                        "MAX" => Aggregate::Max,
+                        _ => return Err("ERR syntax error".to_string()),
+                    };
+                }
+                "WITHSCORES" if allow_withscores => operation.with_scores = true,
+                _ => return Err("ERR syntax error".to_string()),
+            }
+            i += 1;
+        }
+
+        Ok(operation)
     }
 
-    let count = result_zset.len() as i64;
+    /// Combine `sources` (one per key) into the union or intersection,
+    /// added to the empty `result`
+    fn apply(&self, sources: &[HashMap<Bytes, f64>], intersect: bool, mut result: ZSet) -> ZSet {
+        let weighted = |score: f64, weight: f64| zero_if_nan(score * weight);
 
-    // Store result
-    if result_zset.is_empty() {
-        db_instance.delete(&dest);
-    } else {
-        db_instance.set(dest, RedisValue::ZSet(result_zset));
+        if intersect {
+            // Walk the smallest set and look the members up in the others
+            let Some(smallest) = (0..sources.len()).min_by_key(|&i| sources[i].len()) else {
+                return result;
+            };
+            'members: for member in sources[smallest].keys() {
+                let mut total = None;
+                for (source, &weight) in sources.iter().zip(&self.weights) {
+                    let Some(&score) = source.get(member) else {
+                        continue 'members;
+                    };
+                    let score = weighted(score, weight);
+                    total = Some(total.map_or(score, |t| self.aggregate.combine(t, score)));
+                }
+                if let Some(total) = total {
+                    result.insert(member.clone(), total);
+                }
+            }
+        } else {
+            let mut totals: HashMap<Bytes, f64> = HashMap::new();
+            for (source, &weight) in sources.iter().zip(&self.weights) {
+                for (member, &score) in source {
+                    let score = weighted(score, weight);
+                    totals
+                        .entry(member.clone())
+                        .and_modify(|t| *t = self.aggregate.combine(*t, score))
+                        .or_insert(score);
+                }
+            }
+            for (member, total) in totals {
+                result.insert(member, total);
+            }
+        }
+
+        result
+    }
+}
+
+/// Parse `numkeys key [key ...]` at the start of `args`
+pub(crate) fn parse_numkeys(args: &[Vec<u8>]) -> Result<Vec<String>, String> {
+    let numkeys = parse_integer(&args[0])?;
+    if numkeys <= 0 {
+        return Err("ERR numkeys should be greater than 0".to_string());
+    }
+    let numkeys = numkeys as usize;
+    if args.len() < numkeys + 1 {
+        return Err("ERR Number of keys can't be greater than number of args".to_string());
     }
+    args[1..=numkeys]
+        .iter()
+        .map(|key| {
+            std::str::from_utf8(key)
+                .map(str::to_string)
+                .map_err(|_| "ERR invalid key".to_string())
+        })
+        .collect()
+}
 
-    RespValue::Integer(count)
+/// Members and scores of each key; missing keys are empty and plain sets
+/// count every member with score 1
+fn load_sources(db_instance: &DbInstance, keys: &[String]) -> Result<Vec<HashMap<Bytes, f64>>, String> {
+    keys.iter()
+        .map(|key| match db_instance.get(key) {
+            Some(RedisValue::ZSet(zset)) => {
+                Ok(zset.iter().map(|(m, score)| (Bytes::copy_from_slice(m), score)).collect())
+            }
+            Some(RedisValue::Set(set)) => Ok(set.iter().map(|m| (m, 1.0)).collect()),
+            Some(_) => Err(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+            None => Ok(HashMap::new()),
+        })
+        .collect()
+}
+
+fn set_operation_reply(
+    db: &Arc<Database>,
+    db_index: usize,
+    name: &str,
+    args: &[Vec<u8>],
+    intersect: bool,
+) -> RespValue {
+    if args.len() < 2 {
+        return RespValue::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            name.to_lowercase()
+        ));
+    }
+
+    let operation = match SetOperation::parse(args, name, true) {
+        Ok(op) => op,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    match load_sources(&db_instance, &operation.keys) {
+        Ok(sources) => {
+            let result = operation.apply(&sources, intersect, db_instance.new_zset());
+            let entries = RangeQuery::new(RangeBy::Rank(0, -1)).select(&result);
+            range_reply(entries, operation.with_scores)
+        }
+        Err(e) => RespValue::Error(e),
+    }
+}
+
+fn set_operation_store(
+    db: &Arc<Database>,
+    db_index: usize,
+    name: &str,
+    args: &[Vec<u8>],
+    intersect: bool,
+) -> RespValue {
+    if args.len() < 3 {
+        return RespValue::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            name.to_lowercase()
+        ));
+    }
+
+    let dest = match std::str::from_utf8(&args[0]) {
+        Ok(s) => s.to_string(),
+        Err(_) => return RespValue::Error("ERR invalid destination key".to_string()),
+    };
+
+    let operation = match SetOperation::parse(&args[1..], name, false) {
+        Ok(op) => op,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    match load_sources(&db_instance, &operation.keys) {
+        Ok(sources) => {
+            let result = operation.apply(&sources, intersect, db_instance.new_zset());
+            let count = result.len() as i64;
+            store_result(&db_instance, dest, result, &name.to_lowercase());
+            RespValue::Integer(count)
+        }
+        Err(e) => RespValue::Error(e),
+    }
 }
 
 /// BZPOPMIN key [key ...] timeout
@@ -1937,34 +2068,16 @@ pub async fn bzpopmin(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
                 Err(_) => continue,
             };
 
-            if let Some(RedisValue::ZSet(mut zset)) = db_instance.get(key) {
-                if !zset.is_empty() {
-                    // Find minimum score element (first in BTreeMap)
-                    let min_entry = zset.scores.iter().next();
-
-                    if let Some(((score_key, member), _)) = min_entry {
-                        let member_clone = member.clone();
-                        let score_clone = score_key.into_inner();
-
-                        // Remove from both maps
-                        zset.scores.remove(&(*score_key, member_clone.clone()));
-                        zset.members.remove(&member_clone);
-
-                        // Store back
-                        if zset.is_empty() {
-                            db_instance.delete(key);
-                        } else {
-                            db_instance.set(key.to_string(), RedisValue::ZSet(zset));
-                        }
-
-                        // Return [key, member, score]
-                        return RespValue::Array(Some(vec![
-                            RespValue::BulkString(Some(key.as_bytes().to_vec())),
-                            RespValue::BulkString(Some(member_clone.to_vec())),
-                            RespValue::BulkString(Some(score_clone.to_string().into_bytes())),
-                        ]));
-                    }
-                }
+            // Keys holding another type are skipped
+            if let Some(Ok(Some((member, score)))) = with_zset_mut(&db_instance, key, |zset| zset.pop_first()) {
+                finish_removal(&db_instance, key, "zpopmin");
+
+                // Return [key, member, score]
+                return RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(key.as_bytes().to_vec())),
+                    RespValue::BulkString(Some(member.to_vec())),
+                    RespValue::BulkString(Some(score.to_string().into_bytes())),
+                ]));
             }
         }
 
@@ -1973,8 +2086,8 @@ pub async fn bzpopmin(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
             return RespValue::Null;
         }
 
-        // Sleep before next poll
-        tokio::time::sleep(poll_interval).await;
+        // Sleep before next poll, without holding up a resync
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
     }
 }
 
@@ -2025,34 +2138,16 @@ pub async fn bzpopmax(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
                 Err(_) => continue,
             };
 
-            if let Some(RedisValue::ZSet(mut zset)) = db_instance.get(key) {
-                if !zset.is_empty() {
-                    // Find maximum score element (last in BTreeMap)
-                    let max_entry = zset.scores.iter().next_back();
-
-                    if let Some(((score_key, member), _)) = max_entry {
-                        let member_clone = member.clone();
-                        let score_clone = score_key.into_inner();
-
-                        // Remove from both maps
-                        zset.scores.remove(&(*score_key, member_clone.clone()));
-                        zset.members.remove(&member_clone);
-
-                        // Store back
-                        if zset.is_empty() {
-                            db_instance.delete(key);
-                        } else {
-                            db_instance.set(key.to_string(), RedisValue::ZSet(zset));
-                        }
-
-                        // Return [key, member, score]
-                        return RespValue::Array(Some(vec![
-                            RespValue::BulkString(Some(key.as_bytes().to_vec())),
-                            RespValue::BulkString(Some(member_clone.to_vec())),
-                            RespValue::BulkString(Some(score_clone.to_string().into_bytes())),
-                        ]));
-                    }
-                }
+            // Keys holding another type are skipped
+            if let Some(Ok(Some((member, score)))) = with_zset_mut(&db_instance, key, |zset| zset.pop_last()) {
+                finish_removal(&db_instance, key, "zpopmax");
+
+                // Return [key, member, score]
+                return RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(key.as_bytes().to_vec())),
+                    RespValue::BulkString(Some(member.to_vec())),
+                    RespValue::BulkString(Some(score.to_string().into_bytes())),
+                ]));
             }
         }
 
@@ -2061,7 +2156,246 @@ pub async fn bzpopmax(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -
             return RespValue::Null;
         }
 
-        // Sleep before next poll
-        tokio::time::sleep(poll_interval).await;
+        // Sleep before next poll, without holding up a resync
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
+    }
+}
+
+/// ZRANDMEMBER key [count [WITHSCORES]]
+/// Random members: distinct ones for a positive count, possibly repeated
+/// ones for a negative count
+pub async fn zrandmember(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.is_empty() || args.len() > 3 {
+        return RespValue::Error(
+            "ERR wrong number of arguments for 'zrandmember' command".to_string(),
+        );
+    }
+
+    let key = match std::str::from_utf8(&args[0]) {
+        Ok(s) => s,
+        Err(_) => return RespValue::Error("ERR invalid key".to_string()),
+    };
+
+    let count = match args.get(1).map(|c| parse_integer(c)) {
+        Some(Ok(n)) => Some(n),
+        Some(Err(e)) => return RespValue::Error(e),
+        None => None,
+    };
+    let with_scores = match args.get(2) {
+        Some(option) if option.eq_ignore_ascii_case(b"WITHSCORES") => true,
+        Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+        None => false,
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let reply = read_zset(&db_instance, key, |zset| {
+        let entries: Vec<(&[u8], f64)> = zset.iter().collect();
+        let mut rng = rand::thread_rng();
+
+        let Some(count) = count else {
+            return match entries.choose(&mut rng) {
+                Some((member, _)) => RespValue::BulkString(Some(member.to_vec())),
+                None => RespValue::BulkString(None),
+            };
+        };
+
+        let picked: Vec<(Bytes, f64)> = if count >= 0 {
+            let amount = (count as usize).min(entries.len());
+            rand::seq::index::sample(&mut rng, entries.len(), amount)
+                .into_iter()
+                .map(|i| (Bytes::copy_from_slice(entries[i].0), entries[i].1))
+                .collect()
+        } else {
+            (0..count.unsigned_abs())
+                .filter_map(|_| entries.choose(&mut rng))
+                .map(|(member, score)| (Bytes::copy_from_slice(member), *score))
+                .collect()
+        };
+
+        range_reply(picked, with_scores)
+    });
+
+    match reply {
+        Some(Ok(reply)) => reply,
+        Some(Err(e)) => RespValue::Error(e),
+        None if count.is_some() => RespValue::Array(Some(vec![])),
+        None => RespValue::BulkString(None),
+    }
+}
+
+/// ZMPOP numkeys key [key ...] MIN|MAX [COUNT count]
+/// Pop from the first non-empty sorted set
+pub async fn zmpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 3 {
+        return RespValue::Error("ERR wrong number of arguments for 'zmpop' command".to_string());
+    }
+
+    let query = match MultiPop::parse(&args) {
+        Ok(q) => q,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    match query.pop(&db_instance) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => RespValue::Array(None),
+        Err(e) => RespValue::Error(e),
+    }
+}
+
+/// BZMPOP timeout numkeys key [key ...] MIN|MAX [COUNT count]
+/// Blocking version of ZMPOP; a timeout of 0 waits indefinitely
+pub async fn bzmpop(db: &Arc<Database>, db_index: usize, args: Vec<Vec<u8>>) -> RespValue {
+    if args.len() < 4 {
+        return RespValue::Error("ERR wrong number of arguments for 'bzmpop' command".to_string());
+    }
+
+    let timeout_secs = match std::str::from_utf8(&args[0]).ok().and_then(|s| s.parse::<f64>().ok()) {
+        Some(t) if t < 0.0 => return RespValue::Error("ERR timeout is negative".to_string()),
+        Some(t) if t.is_finite() => t,
+        _ => return RespValue::Error("ERR timeout is not a float or out of range".to_string()),
+    };
+
+    let query = match MultiPop::parse(&args[1..]) {
+        Ok(q) => q,
+        Err(e) => return RespValue::Error(e),
+    };
+
+    let db_instance = match db.get_db(db_index) {
+        Some(d) => d,
+        None => return RespValue::Error("ERR invalid database".to_string()),
+    };
+
+    let deadline = (timeout_secs > 0.0)
+        .then(|| std::time::Instant::now() + std::time::Duration::from_secs_f64(timeout_secs));
+    let poll_interval = std::time::Duration::from_millis(10);
+
+    loop {
+        match query.pop(&db_instance) {
+            Ok(Some(reply)) => return reply,
+            Ok(None) => {}
+            Err(e) => return RespValue::Error(e),
+        }
+
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return RespValue::Array(None);
+        }
+
+        write_gate::idle(tokio::time::sleep(poll_interval)).await;
+    }
+}
+
+/// `numkeys key [key ...] MIN|MAX [COUNT count]` of ZMPOP and BZMPOP
+#[derive(Debug)]
+struct MultiPop {
+    keys: Vec<String>,
+    max: bool,
+    count: usize,
+}
+
+impl MultiPop {
+    fn parse(args: &[Vec<u8>]) -> Result<Self, String> {
+        let keys = parse_numkeys(args)?;
+        let Some((side, options)) = args[1 + keys.len()..].split_first() else {
+            return Err("ERR syntax error".to_string());
+        };
+        let max = match String::from_utf8_lossy(side).to_uppercase().as_str() {
+            "MIN" => false,
+            "MAX" => true,
+            _ => return Err("ERR syntax error".to_string()),
+        };
+        let count = match options {
+            [] => 1,
+            [option, value] if option.eq_ignore_ascii_case(b"COUNT") => match parse_integer(value)? {
+                n if n > 0 => n as usize,
+                _ => return Err("ERR count should be greater than 0".to_string()),
+            },
+            _ => return Err("ERR syntax error".to_string()),
+        };
+        Ok(MultiPop { keys, max, count })
+    }
+
+    /// Pop from the first non-empty key, replying `[key, [[member, score], ...]]`;
+    /// None when every key is empty
+    fn pop(&self, db_instance: &DbInstance) -> Result<Option<RespValue>, String> {
+        for key in &self.keys {
+            let popped = match with_zset_mut(db_instance, key, |zset| pop_entries(zset, self.count, self.max)) {
+                Some(popped) => popped?,
+                None => continue,
+            };
+            if popped.is_empty() {
+                continue;
+            }
+            finish_removal(db_instance, key, if self.max { "zpopmax" } else { "zpopmin" });
+
+            let entries = popped
+                .into_iter()
+                .map(|(member, score)| {
+                    RespValue::Array(Some(vec![
+                        RespValue::BulkString(Some(member.to_vec())),
+                        RespValue::BulkString(Some(score.to_string().into_bytes())),
+                    ]))
+                })
+                .collect();
+            return Ok(Some(RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(key.as_bytes().to_vec())),
+                RespValue::Array(Some(entries)),
+            ]))));
+        }
+        Ok(None)
+    }
+}
+
+/// Remove up to `count` members from the low end, or the high end with `max`
+fn pop_entries(zset: &mut ZSet, count: usize, max: bool) -> Vec<(Bytes, f64)> {
+    let mut popped = Vec::new();
+    while popped.len() < count {
+        let entry = if max { zset.pop_last() } else { zset.pop_first() };
+        let Some(entry) = entry else {
+            break;
+        };
+        popped.push(entry);
     }
+    popped
+}
+
+/// The commands to append to the AOF and send to replicas for a successful
+/// sorted set write. ZMPOP and BZMPOP become the ZPOPMIN/ZPOPMAX of what
+/// they actually popped, so a replay never blocks or picks another key.
+pub fn propagated_commands(args: &[Vec<u8>], response: &RespValue) -> Vec<Vec<Vec<u8>>> {
+    let numkeys_at = if args[0].eq_ignore_ascii_case(b"ZMPOP") {
+        1
+    } else if args[0].eq_ignore_ascii_case(b"BZMPOP") {
+        2
+    } else {
+        return vec![args.to_vec()];
+    };
+
+    let RespValue::Array(Some(reply)) = response else {
+        return Vec::new();
+    };
+    let (Some(RespValue::BulkString(Some(key))), Some(RespValue::Array(Some(popped)))) =
+        (reply.first(), reply.get(1))
+    else {
+        return Vec::new();
+    };
+
+    let numkeys = args
+        .get(numkeys_at)
+        .and_then(|n| std::str::from_utf8(n).ok())
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0);
+    let pop = match args.get(numkeys_at + 1 + numkeys) {
+        Some(side) if side.eq_ignore_ascii_case(b"MAX") => b"ZPOPMAX".to_vec(),
+        _ => b"ZPOPMIN".to_vec(),
+    };
+    vec![vec![pop, key.clone(), popped.len().to_string().into_bytes()]]
 }
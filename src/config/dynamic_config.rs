@@ -8,6 +8,7 @@ use anyhow::{Result, bail};
 
 use super::static_config::{StaticConfig, ConfigValue};
 use super::parser::format_config;
+use crate::storage::notify::EventClass;
 
 /// Dynamic configuration that can be changed at runtime
 pub struct DynamicConfig {
@@ -180,6 +181,68 @@ impl DynamicConfig {
                     bail!("slowlog-max-len must be non-negative");
                 }
             }
+            "hash-max-listpack-entries" | "hash-max-listpack-value" | "set-max-intset-entries"
+            | "set-max-listpack-entries" | "set-max-listpack-value"
+            | "zset-max-listpack-entries" | "zset-max-listpack-value" | "list-compress-depth" => {
+                let _: usize = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid {} value", key))?;
+            }
+            "list-max-listpack-size" => {
+                let size: i64 = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid list-max-listpack-size value"))?;
+                if size < -5 {
+                    bail!("list-max-listpack-size must be at least -5");
+                }
+            }
+            "notify-keyspace-events" if EventClass::parse(value).is_none() => {
+                bail!("Invalid event class character. Use 'Ag$lshzxetKEmn'.");
+            }
+            "repl-diskless-sync" => {
+                let valid_values = ["yes", "no"];
+                if !valid_values.contains(&value) {
+                    bail!("Invalid repl-diskless-sync. Valid values: {}", valid_values.join(", "));
+                }
+            }
+            "repl-diskless-sync-delay" => {
+                let delay: i64 = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid repl-diskless-sync-delay value"))?;
+                if delay < 0 {
+                    bail!("repl-diskless-sync-delay must be non-negative");
+                }
+            }
+            "replica-priority" => {
+                let priority: i64 = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid replica-priority value"))?;
+                if priority < 0 {
+                    bail!("replica-priority must be non-negative");
+                }
+            }
+            "repl-diskless-load" => {
+                let valid_values = ["disabled", "on-empty-db", "swapdb"];
+                if !valid_values.contains(&value) {
+                    bail!("Invalid repl-diskless-load. Valid values: {}", valid_values.join(", "));
+                }
+            }
+            "cluster-node-timeout" => {
+                let timeout: i64 = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid cluster-node-timeout value"))?;
+                if timeout < 1 {
+                    bail!("cluster-node-timeout must be positive");
+                }
+            }
+            "cluster-replica-validity-factor" => {
+                let factor: i64 = value.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid cluster-replica-validity-factor value"))?;
+                if factor < 0 {
+                    bail!("cluster-replica-validity-factor must be non-negative");
+                }
+            }
+            "cluster-replica-no-failover" => {
+                let valid_values = ["yes", "no"];
+                if !valid_values.contains(&value) {
+                    bail!("Invalid cluster-replica-no-failover. Valid values: {}", valid_values.join(", "));
+                }
+            }
             _ => {
                 // Allow unknown keys for forward compatibility
             }
@@ -233,6 +296,18 @@ mod tests {
         let result = config.set("maxmemory-policy".to_string(), "invalid".to_string());
         assert!(result.is_err());
 
+        // Invalid diskless replication settings
+        assert!(config.set("repl-diskless-load".to_string(), "sometimes".to_string()).is_err());
+        assert!(config.set("repl-diskless-sync-delay".to_string(), "-1".to_string()).is_err());
+        assert!(config.set("repl-diskless-load".to_string(), "swapdb".to_string()).is_ok());
+        assert!(config.set("replica-priority".to_string(), "-5".to_string()).is_err());
+        assert!(config.set("replica-priority".to_string(), "0".to_string()).is_ok());
+
+        // Invalid cluster failover settings
+        assert!(config.set("cluster-replica-validity-factor".to_string(), "-1".to_string()).is_err());
+        assert!(config.set("cluster-replica-no-failover".to_string(), "maybe".to_string()).is_err());
+        assert!(config.set("cluster-replica-no-failover".to_string(), "yes".to_string()).is_ok());
+
         // Valid value
         let result = config.set("maxmemory-policy".to_string(), "allkeys-lru".to_string());
         assert!(result.is_ok());
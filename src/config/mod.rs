@@ -9,6 +9,7 @@ pub use static_config::{StaticConfig, ConfigValue};
 pub use dynamic_config::DynamicConfig;
 pub use parser::ConfigParser;
 
+use crate::storage::encoding::{self, EncodingLimits};
 use std::sync::Arc;
 use anyhow::Result;
 
@@ -20,6 +21,8 @@ pub struct ConfigManager {
     dynamic_config: Arc<DynamicConfig>,
     /// Path to the configuration file
     config_file: Option<String>,
+    /// This server's collection encoding limits, kept in step with `set`
+    encoding: Arc<EncodingLimits>,
 }
 
 impl ConfigManager {
@@ -29,7 +32,9 @@ impl ConfigManager {
             static_config: StaticConfig::default(),
             dynamic_config: Arc::new(DynamicConfig::new()),
             config_file: None,
+            encoding: Arc::new(EncodingLimits::new()),
         }
+        .with_encoding_from_values()
     }
 
     /// Load configuration from a file
@@ -41,7 +46,25 @@ impl ConfigManager {
             static_config,
             dynamic_config,
             config_file: Some(path.to_string()),
-        })
+            encoding: Arc::new(EncodingLimits::new()),
+        }
+        .with_encoding_from_values())
+    }
+
+    /// Load the encoding limits from the current values
+    fn with_encoding_from_values(self) -> Self {
+        for name in encoding::names() {
+            if let Some(value) = self.get(name) {
+                self.encoding.apply(name, &value);
+            }
+        }
+        self
+    }
+
+    /// Override a static value, e.g. the port the server was started on
+    pub fn with_static(mut self, key: &str, value: ConfigValue) -> Self {
+        self.static_config.set(key, value);
+        self
     }
 
     /// Get a configuration value (checks dynamic config first, then static)
@@ -52,7 +75,14 @@ impl ConfigManager {
 
     /// Set a configuration value at runtime
     pub fn set(&self, key: String, value: String) -> Result<()> {
-        self.dynamic_config.set(key, value)
+        self.dynamic_config.set(key.clone(), value.clone())?;
+        self.encoding.apply(&key, &value);
+        Ok(())
+    }
+
+    /// The encoding limits new collections of this server are built with
+    pub fn encoding_limits(&self) -> Arc<EncodingLimits> {
+        Arc::clone(&self.encoding)
     }
 
     /// Rewrite the configuration file with current dynamic values
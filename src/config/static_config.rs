@@ -92,6 +92,8 @@ impl StaticConfig {
         values.insert("replica-read-only".to_string(), ConfigValue::Bool(true));
         values.insert("repl-diskless-sync".to_string(), ConfigValue::Bool(false));
         values.insert("repl-diskless-sync-delay".to_string(), ConfigValue::Int(5));
+        values.insert("repl-diskless-load".to_string(), ConfigValue::String("disabled".to_string()));
+        values.insert("replica-priority".to_string(), ConfigValue::Int(100));
 
         // Security
         values.insert("requirepass".to_string(), ConfigValue::String("".to_string()));
@@ -114,12 +116,16 @@ impl StaticConfig {
         values.insert("slowlog-max-len".to_string(), ConfigValue::Int(128));
 
         // Advanced config
-        values.insert("hash-max-ziplist-entries".to_string(), ConfigValue::Int(512));
-        values.insert("hash-max-ziplist-value".to_string(), ConfigValue::Int(64));
-        values.insert("list-max-ziplist-size".to_string(), ConfigValue::Int(-2));
+        values.insert("hash-max-listpack-entries".to_string(), ConfigValue::Int(128));
+        values.insert("hash-max-listpack-value".to_string(), ConfigValue::Int(64));
+        values.insert("list-max-listpack-size".to_string(), ConfigValue::Int(-2));
+        values.insert("list-compress-depth".to_string(), ConfigValue::Int(0));
         values.insert("set-max-intset-entries".to_string(), ConfigValue::Int(512));
-        values.insert("zset-max-ziplist-entries".to_string(), ConfigValue::Int(128));
-        values.insert("zset-max-ziplist-value".to_string(), ConfigValue::Int(64));
+        values.insert("set-max-listpack-entries".to_string(), ConfigValue::Int(128));
+        values.insert("set-max-listpack-value".to_string(), ConfigValue::Int(64));
+        values.insert("zset-max-listpack-entries".to_string(), ConfigValue::Int(128));
+        values.insert("zset-max-listpack-value".to_string(), ConfigValue::Int(64));
+        values.insert("notify-keyspace-events".to_string(), ConfigValue::String("".to_string()));
 
         // Cluster
         values.insert("cluster-enabled".to_string(), ConfigValue::Bool(false));
@@ -150,6 +156,11 @@ impl StaticConfig {
         self.values.get(key)
     }
 
+    /// Override a value (used for settings given on the command line)
+    pub fn set(&mut self, key: &str, value: ConfigValue) {
+        self.values.insert(key.to_string(), value);
+    }
+
     /// Get all configuration values
     pub fn get_all(&self) -> &HashMap<String, ConfigValue> {
         &self.values
@@ -12,6 +12,8 @@ pub mod replication;
 pub mod scripting;
 pub mod config;
 pub mod acl;
+pub mod sentinel;
+pub mod proxy;
 
 // Re-export commonly used types
 pub use server::{RedisServer, ServerConfig};
@@ -1,7 +1,45 @@
+use redis_rust::proxy::{ProxyConfig, ProxyServer};
+use redis_rust::sentinel::{SentinelConfig, SentinelServer};
 use redis_rust::server::{RedisServer, ServerConfig};
 use tracing::info;
 use tracing_subscriber;
 
+/// Command line options
+///
+///   redis-rust [--port N]
+///   redis-rust --sentinel [sentinel.conf] [--port N]
+///   redis-rust --cluster-proxy host:port [--cluster-proxy host:port ...] [--port N]
+struct Args {
+    sentinel: bool,
+    /// Seed nodes of the cluster to proxy
+    proxy_seeds: Vec<String>,
+    config_file: Option<String>,
+    port: Option<u16>,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = Args { sentinel: false, proxy_seeds: Vec::new(), config_file: None, port: None };
+    let mut iter = std::env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--sentinel" => args.sentinel = true,
+            "--cluster-proxy" => {
+                let seed = iter.next().ok_or_else(|| anyhow::anyhow!("--cluster-proxy needs a host:port"))?;
+                args.proxy_seeds.push(seed);
+            }
+            "--port" => {
+                let value = iter.next().ok_or_else(|| anyhow::anyhow!("--port needs a value"))?;
+                args.port = Some(value.parse()?);
+            }
+            _ if !arg.starts_with("--") && args.config_file.is_none() => args.config_file = Some(arg),
+            _ => anyhow::bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    Ok(args)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize logging
@@ -11,11 +49,49 @@ async fn main() -> anyhow::Result<()> {
         .with_level(true)
         .init();
 
+    let args = parse_args()?;
+
+    if args.sentinel {
+        info!("Redis-Rust sentinel starting...");
+
+        let mut config = match &args.config_file {
+            Some(path) => SentinelConfig::from_file(path)?,
+            None => SentinelConfig::default(),
+        };
+        if let Some(port) = args.port {
+            config.port = port;
+        }
+
+        let sentinel = SentinelServer::new(config);
+        sentinel.run().await?;
+        return Ok(());
+    }
+
+    if !args.proxy_seeds.is_empty() {
+        info!("Redis-Rust cluster proxy starting...");
+
+        let mut config = ProxyConfig::default().with_seeds(args.proxy_seeds);
+        if let Some(port) = args.port {
+            config = config.with_port(port);
+        }
+
+        let proxy = ProxyServer::new(config);
+        proxy.run().await?;
+        return Ok(());
+    }
+
+    if args.config_file.is_some() {
+        anyhow::bail!("A configuration file is only supported together with --sentinel");
+    }
+
     info!("Redis-Rust server starting...");
 
     // Create server configuration
     // Cluster mode disabled for replication testing
-    let config = ServerConfig::default();
+    let mut config = ServerConfig::default();
+    if let Some(port) = args.port {
+        config = config.with_port(port);
+    }
 
     info!("Server will bind to {}", config.addr());
     info!("AOF enabled: {}", config.aof_enabled);
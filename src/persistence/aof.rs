@@ -1,11 +1,12 @@
 // AOF (Append-Only File) persistence
 
-use crate::protocol::{RespSerializer, RespValue};
+use crate::protocol::{RespParser, RespSerializer, RespValue};
 use crate::storage::db::Database;
+use bytes::BytesMut;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
@@ -132,49 +133,37 @@ impl AofReader {
 
         info!("Loading AOF from {:?}", self.path);
 
-        let file = File::open(&self.path).await?;
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+        // Commands are RESP arrays whose arguments may hold any bytes
+        // (DUMP payloads, binary values), so the file is parsed as bytes
+        let mut buffer = BytesMut::from(&tokio::fs::read(&self.path).await?[..]);
 
         let mut current_db = 0;
         let mut commands_loaded = 0;
-        let mut buffer = String::new();
 
-        while let Some(line) = lines.next_line().await? {
-            buffer.push_str(&line);
-            buffer.push('\n');
-
-            // Try to parse complete RESP value
-            if let Ok(Some(value)) = self.try_parse_resp(&buffer) {
-                // Process the command
-                if let Err(e) = self.replay_command(db, &mut current_db, &value).await {
-                    error!("Error replaying command: {}", e);
+        while !buffer.is_empty() {
+            let len = match RespParser::check_complete(&buffer) {
+                Ok(Some(len)) => len,
+                // A write cut short by a crash
+                Ok(None) => {
+                    warn!("AOF ends with a truncated command, ignoring {} bytes", buffer.len());
+                    break;
+                }
+                Err(e) => {
+                    error!("Invalid AOF entry after {} commands: {}", commands_loaded, e);
+                    break;
                 }
-                commands_loaded += 1;
-                buffer.clear();
+            };
+            let value = RespParser::parse(&buffer.split_to(len))?;
+            if let Err(e) = self.replay_command(db, &mut current_db, &value).await {
+                error!("Error replaying command: {}", e);
             }
+            commands_loaded += 1;
         }
 
         info!("AOF loaded {} commands", commands_loaded);
         Ok(commands_loaded)
     }
 
-    /// Try to parse a complete RESP value from buffer
-    fn try_parse_resp(&self, buffer: &str) -> anyhow::Result<Option<RespValue>> {
-        use crate::protocol::RespParser;
-        use bytes::BytesMut;
-
-        let bytes = BytesMut::from(buffer.as_bytes());
-        match RespParser::check_complete(&bytes) {
-            Ok(Some(_)) => {
-                let value = RespParser::parse(&bytes)?;
-                Ok(Some(value))
-            }
-            Ok(None) => Ok(None),
-            Err(_) => Ok(None),
-        }
-    }
-
     /// Replay a single command into the database
     async fn replay_command(
         &self,
@@ -248,7 +237,7 @@ impl AofReader {
         let cmd = String::from_utf8_lossy(&args[0]).to_uppercase();
 
         // Import command handlers
-        use crate::commands::{expiration, hash, list, set, string, zset};
+        use crate::commands::{bitmap, expiration, geo, hash, key_mgmt, list, set, stream, stream_group, string, zset};
 
         // Execute the command (simplified - just call handlers directly)
         let _ = match cmd.as_str() {
@@ -263,6 +252,9 @@ impl AofReader {
             "SETRANGE" => string::setrange(db, db_index, args[1..].to_vec()).await,
             "MSET" => string::mset(db, db_index, args[1..].to_vec()).await,
 
+            // Key management commands
+            "RESTORE" => key_mgmt::restore(db, db_index, args[1..].to_vec()).await,
+
             // List commands
             "LPUSH" => list::lpush(db, db_index, args[1..].to_vec()).await,
             "RPUSH" => list::rpush(db, db_index, args[1..].to_vec()).await,
@@ -270,10 +262,15 @@ impl AofReader {
             "RPOP" => list::rpop(db, db_index, args[1..].to_vec()).await,
             "LSET" => list::lset(db, db_index, args[1..].to_vec()).await,
             "LTRIM" => list::ltrim(db, db_index, args[1..].to_vec()).await,
+            "LINSERT" => list::linsert(db, db_index, args[1..].to_vec()).await,
 
             // Hash commands
             "HSET" => hash::hset(db, db_index, args[1..].to_vec()).await,
             "HDEL" => hash::hdel(db, db_index, args[1..].to_vec()).await,
+            "HPEXPIREAT" => hash::hpexpireat(db, db_index, args[1..].to_vec()).await,
+            "HPERSIST" => hash::hpersist(db, db_index, args[1..].to_vec()).await,
+            "HGETDEL" => hash::hgetdel(db, db_index, args[1..].to_vec()).await,
+            "HSETEX" => hash::hsetex(db, db_index, args[1..].to_vec()).await,
 
             // Set commands
             "SADD" => set::sadd(db, db_index, args[1..].to_vec()).await,
@@ -283,6 +280,30 @@ impl AofReader {
             // ZSet commands
             "ZADD" => zset::zadd(db, db_index, args[1..].to_vec()).await,
             "ZREM" => zset::zrem(db, db_index, args[1..].to_vec()).await,
+            "ZPOPMIN" => zset::zpopmin(db, db_index, args[1..].to_vec()).await,
+            "ZPOPMAX" => zset::zpopmax(db, db_index, args[1..].to_vec()).await,
+            "ZRANGESTORE" => zset::zrangestore(db, db_index, args[1..].to_vec()).await,
+            "ZUNIONSTORE" => zset::zunionstore(db, db_index, args[1..].to_vec()).await,
+            "ZINTERSTORE" => zset::zinterstore(db, db_index, args[1..].to_vec()).await,
+
+            // Bitmap commands
+            "BITFIELD" => bitmap::bitfield(db, db_index, args[1..].to_vec()).await,
+
+            // Geo commands
+            "GEOADD" => geo::geoadd(db, db_index, args[1..].to_vec()).await,
+            "GEOSEARCHSTORE" => geo::geosearchstore(db, db_index, args[1..].to_vec()).await,
+            "GEORADIUS" => geo::georadius(db, db_index, args[1..].to_vec()).await,
+            "GEORADIUSBYMEMBER" => geo::georadiusbymember(db, db_index, args[1..].to_vec()).await,
+
+            // Stream commands
+            "XADD" => stream::xadd(db, db_index, args[1..].to_vec()).await,
+            "XDEL" => stream::xdel(db, db_index, args[1..].to_vec()).await,
+            "XTRIM" => stream::xtrim(db, db_index, args[1..].to_vec()).await,
+            "XSETID" => stream::xsetid(db, db_index, args[1..].to_vec()).await,
+            "XGROUP" => stream_group::xgroup(db, db_index, args[1..].to_vec()).await,
+            "XREADGROUP" => stream_group::xreadgroup(db, db_index, args[1..].to_vec()).await,
+            "XACK" => stream_group::xack(db, db_index, args[1..].to_vec()).await,
+            "XCLAIM" => stream_group::xclaim(db, db_index, args[1..].to_vec()).await,
 
             // Expiration commands
             "EXPIRE" => expiration::expire(db, db_index, args[1..].to_vec()).await,
@@ -370,6 +391,19 @@ impl AofManager {
                         // Generate commands to recreate this key
                         self.write_value_to_aof(&temp_writer, db_index, &key, &value).await?;
 
+                        // Hash fields with a TTL get an HPEXPIREAT each
+                        for (field, expire_at) in db_instance.hash_field_expiries(&key) {
+                            let args = vec![
+                                b"HPEXPIREAT".to_vec(),
+                                key.as_bytes().to_vec(),
+                                expire_at.to_string().into_bytes(),
+                                b"FIELDS".to_vec(),
+                                b"1".to_vec(),
+                                field.to_vec(),
+                            ];
+                            temp_writer.append_command(db_index, &args).await?;
+                        }
+
                         // If key has expiration, write PEXPIREAT command
                         let ttl_ms = db_instance.get_ttl_ms(&key);
                         if ttl_ms > 0 {
@@ -400,7 +434,7 @@ impl AofManager {
         key: &str,
         value: &crate::storage::types::RedisValue,
     ) -> anyhow::Result<()> {
-        use crate::storage::types::RedisValue;
+        use crate::storage::types::{RedisValue, StreamId};
 
         match value {
             RedisValue::String(data) => {
@@ -443,8 +477,8 @@ impl AofManager {
                 }
             }
             RedisValue::ZSet(zset) => {
-                if !zset.members.is_empty() {
-                    for (member, score) in zset.members.iter() {
+                if !zset.is_empty() {
+                    for (member, score) in zset.iter() {
                         let args = vec![
                             b"ZADD".to_vec(),
                             key.as_bytes().to_vec(),
@@ -469,6 +503,82 @@ impl AofManager {
                     }
                     writer.append_command(db_index, &args).await?;
                 }
+
+                // An empty stream still needs creating; XSETID then restores
+                // the last ID and counters either way
+                if stream.is_empty() {
+                    let id = if stream.last_id == StreamId::new(0, 0) {
+                        StreamId::new(0, 1)
+                    } else {
+                        stream.last_id.clone()
+                    };
+                    let args = vec![
+                        b"XADD".to_vec(),
+                        key.as_bytes().to_vec(),
+                        b"MAXLEN".to_vec(),
+                        b"0".to_vec(),
+                        id.to_string().into_bytes(),
+                        b"x".to_vec(),
+                        b"y".to_vec(),
+                    ];
+                    writer.append_command(db_index, &args).await?;
+                }
+                let args = vec![
+                    b"XSETID".to_vec(),
+                    key.as_bytes().to_vec(),
+                    stream.last_id.to_string().into_bytes(),
+                    b"ENTRIESADDED".to_vec(),
+                    stream.entries_added.to_string().into_bytes(),
+                    b"MAXDELETEDID".to_vec(),
+                    stream.max_deleted_id.to_string().into_bytes(),
+                ];
+                writer.append_command(db_index, &args).await?;
+
+                // Consumer groups, their consumers, then each pending
+                // entry claimed back by its owner
+                for (name, group) in &stream.groups {
+                    let entries_read = group.entries_read.map_or("-1".to_string(), |n| n.to_string());
+                    let args = vec![
+                        b"XGROUP".to_vec(),
+                        b"CREATE".to_vec(),
+                        key.as_bytes().to_vec(),
+                        name.as_bytes().to_vec(),
+                        group.last_delivered_id.to_string().into_bytes(),
+                        b"MKSTREAM".to_vec(),
+                        b"ENTRIESREAD".to_vec(),
+                        entries_read.into_bytes(),
+                    ];
+                    writer.append_command(db_index, &args).await?;
+
+                    for consumer in group.consumers.keys() {
+                        let args = vec![
+                            b"XGROUP".to_vec(),
+                            b"CREATECONSUMER".to_vec(),
+                            key.as_bytes().to_vec(),
+                            name.as_bytes().to_vec(),
+                            consumer.as_bytes().to_vec(),
+                        ];
+                        writer.append_command(db_index, &args).await?;
+                    }
+
+                    for (id, pending) in &group.pending {
+                        let args = vec![
+                            b"XCLAIM".to_vec(),
+                            key.as_bytes().to_vec(),
+                            name.as_bytes().to_vec(),
+                            pending.consumer.as_bytes().to_vec(),
+                            b"0".to_vec(),
+                            id.to_string().into_bytes(),
+                            b"TIME".to_vec(),
+                            pending.delivery_time.to_string().into_bytes(),
+                            b"RETRYCOUNT".to_vec(),
+                            pending.delivery_count.to_string().into_bytes(),
+                            b"FORCE".to_vec(),
+                            b"JUSTID".to_vec(),
+                        ];
+                        writer.append_command(db_index, &args).await?;
+                    }
+                }
             }
         }
 
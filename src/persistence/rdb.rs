@@ -2,10 +2,14 @@
 // Binary format for snapshots
 
 use crate::storage::db::{Database, DbInstance};
-use crate::storage::types::RedisValue;
+use crate::storage::quicklist::QuickList;
+use crate::storage::types::{
+    Consumer, ConsumerGroup, HashValue, PendingEntry, RedisValue, SetValue, Stream, StreamEntry,
+    StreamId,
+};
 use anyhow::{Context, Result};
 use bytes::Bytes;
-use std::collections::{HashMap, HashSet, LinkedList};
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
@@ -21,6 +25,9 @@ const OPCODE_LIST: u8 = 1;
 const OPCODE_SET: u8 = 2;
 const OPCODE_HASH: u8 = 3;
 const OPCODE_ZSET: u8 = 4;
+const OPCODE_STREAM: u8 = 5;
+/// A hash followed by the expiration times of its fields
+const OPCODE_HASH_WITH_TTLS: u8 = 6;
 const OPCODE_EXPIRY: u8 = 253;
 const OPCODE_DB_SELECT: u8 = 254;
 const OPCODE_EOF: u8 = 255;
@@ -32,7 +39,19 @@ impl RdbSerializer {
     pub async fn save(db: &Arc<Database>, path: impl AsRef<Path>) -> Result<()> {
         let file = File::create(path).context("Failed to create RDB file")?;
         let mut writer = BufWriter::new(file);
+        Self::write_snapshot(db, &mut writer)
+    }
+
+    /// Serialize a snapshot of the database into memory
+    pub fn to_bytes(db: &Database) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        Self::write_snapshot(db, &mut buf)?;
+        Ok(buf)
+    }
 
+    /// Serialize a snapshot of the database into any writer
+    /// (a file, an in-memory buffer, or a replica socket)
+    pub fn write_snapshot<W: Write>(db: &Database, writer: &mut W) -> Result<()> {
         // Write magic string and version
         writer.write_all(RDB_MAGIC)?;
         writer.write_all(&RDB_VERSION.to_le_bytes())?;
@@ -58,7 +77,7 @@ impl RdbSerializer {
 
             // Write each key-value pair
             for key in keys {
-                Self::save_key_value(&mut writer, db_instance, &key)?;
+                Self::save_key_value(writer, &db_instance, &key)?;
             }
         }
 
@@ -69,8 +88,8 @@ impl RdbSerializer {
         Ok(())
     }
 
-    fn save_key_value(
-        writer: &mut BufWriter<File>,
+    fn save_key_value<W: Write>(
+        writer: &mut W,
         db_instance: &DbInstance,
         key: &str,
     ) -> Result<()> {
@@ -81,108 +100,180 @@ impl RdbSerializer {
             return Ok(());
         }
 
-        // Write expiration if present
-        if ttl_ms > 0 {
-            writer.write_all(&[OPCODE_EXPIRY])?;
-            let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
-            writer.write_all(&expire_at_ms.to_le_bytes())?;
-        }
-
         // Get value
         let value = match db_instance.get(key) {
             Some(v) => v,
             None => return Ok(()), // Already expired
         };
 
-        // Write key
-        Self::write_string(writer, key.as_bytes())?;
+        // Write expiration if present
+        if ttl_ms > 0 {
+            writer.write_all(&[OPCODE_EXPIRY])?;
+            let expire_at_ms = crate::storage::db::current_timestamp_ms() + ttl_ms as u64;
+            writer.write_all(&expire_at_ms.to_le_bytes())?;
+        }
 
-        // Write value based on type
+        // Write type opcode, then key, then value (the order the loader expects)
         match value {
             RedisValue::String(bytes) => {
                 writer.write_all(&[OPCODE_STRING])?;
+                Self::write_string(writer, key.as_bytes())?;
                 Self::write_bytes(writer, &bytes)?;
             }
             RedisValue::List(list) => {
                 writer.write_all(&[OPCODE_LIST])?;
+                Self::write_string(writer, key.as_bytes())?;
                 Self::write_list(writer, &list)?;
             }
             RedisValue::Set(set) => {
                 writer.write_all(&[OPCODE_SET])?;
+                Self::write_string(writer, key.as_bytes())?;
                 Self::write_set(writer, &set)?;
             }
             RedisValue::Hash(hash) => {
-                writer.write_all(&[OPCODE_HASH])?;
-                Self::write_hash(writer, &hash)?;
+                let field_ttls = db_instance.hash_field_expiries(key);
+                if field_ttls.is_empty() {
+                    writer.write_all(&[OPCODE_HASH])?;
+                    Self::write_string(writer, key.as_bytes())?;
+                    Self::write_hash(writer, &hash)?;
+                } else {
+                    writer.write_all(&[OPCODE_HASH_WITH_TTLS])?;
+                    Self::write_string(writer, key.as_bytes())?;
+                    Self::write_hash(writer, &hash)?;
+                    writer.write_all(&(field_ttls.len() as u32).to_le_bytes())?;
+                    for (field, expire_at_ms) in &field_ttls {
+                        Self::write_bytes(writer, field)?;
+                        Self::write_u64(writer, *expire_at_ms)?;
+                    }
+                }
             }
             RedisValue::ZSet(zset) => {
                 writer.write_all(&[OPCODE_ZSET])?;
+                Self::write_string(writer, key.as_bytes())?;
                 Self::write_zset(writer, &zset)?;
             }
-            RedisValue::Stream(_stream) => {
-                // Skip stream serialization for RDB (not yet fully supported)
-                // In a full implementation, we would add OPCODE_STREAM and write_stream
-                return Ok(());
+            RedisValue::Stream(stream) => {
+                writer.write_all(&[OPCODE_STREAM])?;
+                Self::write_string(writer, key.as_bytes())?;
+                Self::write_stream(writer, &stream)?;
             }
         }
 
         Ok(())
     }
 
-    fn write_string(writer: &mut BufWriter<File>, s: &[u8]) -> Result<()> {
+    fn write_string<W: Write>(writer: &mut W, s: &[u8]) -> Result<()> {
         let len = s.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
         writer.write_all(s)?;
         Ok(())
     }
 
-    fn write_bytes(writer: &mut BufWriter<File>, bytes: &Bytes) -> Result<()> {
+    fn write_bytes<W: Write>(writer: &mut W, bytes: &Bytes) -> Result<()> {
         let len = bytes.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
         writer.write_all(bytes)?;
         Ok(())
     }
 
-    fn write_list(writer: &mut BufWriter<File>, list: &LinkedList<Bytes>) -> Result<()> {
+    fn write_list<W: Write>(writer: &mut W, list: &QuickList) -> Result<()> {
         let len = list.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
-        for item in list {
-            Self::write_bytes(writer, item)?;
+        for item in list.iter() {
+            Self::write_string(writer, &item)?;
         }
         Ok(())
     }
 
-    fn write_set(writer: &mut BufWriter<File>, set: &HashSet<Bytes>) -> Result<()> {
+    fn write_set<W: Write>(writer: &mut W, set: &SetValue) -> Result<()> {
         let len = set.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
-        for item in set {
-            Self::write_bytes(writer, item)?;
+        for item in set.iter() {
+            Self::write_bytes(writer, &item)?;
         }
         Ok(())
     }
 
-    fn write_hash(writer: &mut BufWriter<File>, hash: &HashMap<Bytes, Bytes>) -> Result<()> {
+    fn write_hash<W: Write>(writer: &mut W, hash: &HashValue) -> Result<()> {
         let len = hash.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
-        for (key, value) in hash {
-            Self::write_bytes(writer, key)?;
+        for (key, value) in hash.iter() {
+            Self::write_string(writer, key)?;
+            Self::write_string(writer, value)?;
+        }
+        Ok(())
+    }
+
+    /// Field-value pairs in order, laid out like a hash
+    fn write_pairs<W: Write>(writer: &mut W, pairs: &[(Bytes, Bytes)]) -> Result<()> {
+        writer.write_all(&(pairs.len() as u32).to_le_bytes())?;
+        for (field, value) in pairs {
+            Self::write_bytes(writer, field)?;
             Self::write_bytes(writer, value)?;
         }
         Ok(())
     }
 
-    fn write_zset(
-        writer: &mut BufWriter<File>,
+    fn write_zset<W: Write>(
+        writer: &mut W,
         zset: &crate::storage::types::ZSet,
     ) -> Result<()> {
         let len = zset.len() as u32;
         writer.write_all(&len.to_le_bytes())?;
-        for (member, score) in &zset.members {
-            Self::write_bytes(writer, member)?;
+        for (member, score) in zset.iter() {
+            Self::write_string(writer, member)?;
             writer.write_all(&score.to_le_bytes())?;
         }
         Ok(())
     }
+
+    fn write_u64<W: Write>(writer: &mut W, n: u64) -> Result<()> {
+        writer.write_all(&n.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_id<W: Write>(writer: &mut W, id: &StreamId) -> Result<()> {
+        Self::write_u64(writer, id.timestamp)?;
+        Self::write_u64(writer, id.sequence)
+    }
+
+    /// Entries, then the stream's counters, then every consumer group
+    /// with its PEL and consumers
+    fn write_stream<W: Write>(writer: &mut W, stream: &Stream) -> Result<()> {
+        writer.write_all(&(stream.entries.len() as u32).to_le_bytes())?;
+        for (id, entry) in &stream.entries {
+            Self::write_id(writer, id)?;
+            Self::write_pairs(writer, &entry.fields)?;
+        }
+        Self::write_id(writer, &stream.last_id)?;
+        Self::write_u64(writer, stream.entries_added)?;
+        Self::write_id(writer, &stream.max_deleted_id)?;
+
+        writer.write_all(&(stream.groups.len() as u32).to_le_bytes())?;
+        for (name, group) in &stream.groups {
+            Self::write_string(writer, name.as_bytes())?;
+            Self::write_id(writer, &group.last_delivered_id)?;
+            // u64::MAX stands for an unknown entries-read count
+            Self::write_u64(writer, group.entries_read.unwrap_or(u64::MAX))?;
+
+            writer.write_all(&(group.pending.len() as u32).to_le_bytes())?;
+            for (id, entry) in &group.pending {
+                Self::write_id(writer, id)?;
+                Self::write_string(writer, entry.consumer.as_bytes())?;
+                Self::write_u64(writer, entry.delivery_time)?;
+                Self::write_u64(writer, entry.delivery_count)?;
+            }
+
+            // A consumer's pending IDs are rebuilt from the group's PEL
+            writer.write_all(&(group.consumers.len() as u32).to_le_bytes())?;
+            for (name, consumer) in &group.consumers {
+                Self::write_string(writer, name.as_bytes())?;
+                Self::write_u64(writer, consumer.seen_time)?;
+                Self::write_u64(writer, consumer.active_time.unwrap_or(u64::MAX))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct RdbDeserializer;
@@ -192,7 +283,11 @@ impl RdbDeserializer {
     pub async fn load(db: &Arc<Database>, path: impl AsRef<Path>) -> Result<()> {
         let file = File::open(path).context("Failed to open RDB file")?;
         let mut reader = BufReader::new(file);
+        Self::read_snapshot(db, &mut reader)
+    }
 
+    /// Load a snapshot from any reader (a file or a replication stream)
+    pub fn read_snapshot<R: Read>(db: &Database, reader: &mut R) -> Result<()> {
         // Read and verify magic string
         let mut magic = [0u8; 5];
         reader.read_exact(&mut magic)?;
@@ -232,42 +327,58 @@ impl RdbDeserializer {
                 }
                 _ => {
                     // Read key
-                    let key = Self::read_string(&mut reader)?;
+                    let key = Self::read_string(reader)?;
+                    let db_instance = db
+                        .get_db(current_db)
+                        .context("Invalid database index in RDB")?;
+
+                    let mut field_ttls = Vec::new();
 
                     // Read value based on preceding opcode (which we stored)
                     let value = match opcode[0] {
                         OPCODE_STRING => {
-                            let bytes = Self::read_bytes(&mut reader)?;
+                            let bytes = Self::read_bytes(reader)?;
                             RedisValue::String(bytes)
                         }
                         OPCODE_LIST => {
-                            let list = Self::read_list(&mut reader)?;
+                            let list = Self::read_list(reader, db_instance.new_list())?;
                             RedisValue::List(list)
                         }
                         OPCODE_SET => {
-                            let set = Self::read_set(&mut reader)?;
+                            let set = Self::read_set(reader, db_instance.new_set())?;
                             RedisValue::Set(set)
                         }
                         OPCODE_HASH => {
-                            let hash = Self::read_hash(&mut reader)?;
+                            let hash = Self::read_hash(reader, db_instance.new_hash())?;
+                            RedisValue::Hash(hash)
+                        }
+                        OPCODE_HASH_WITH_TTLS => {
+                            let hash = Self::read_hash(reader, db_instance.new_hash())?;
+                            for _ in 0..Self::read_u32(reader)? {
+                                let field = Self::read_bytes(reader)?;
+                                field_ttls.push((field, Self::read_u64(reader)?));
+                            }
                             RedisValue::Hash(hash)
                         }
                         OPCODE_ZSET => {
-                            let zset = Self::read_zset(&mut reader)?;
+                            let zset = Self::read_zset(reader, db_instance.new_zset())?;
                             RedisValue::ZSet(zset)
                         }
+                        OPCODE_STREAM => {
+                            let stream = Self::read_stream(reader)?;
+                            RedisValue::Stream(stream)
+                        }
                         _ => anyhow::bail!("Unknown value type opcode: {}", opcode[0]),
                     };
 
                     // Store in database
-                    let db_instance = db
-                        .get_db(current_db)
-                        .context("Invalid database index in RDB")?;
-
                     if let Some(expire_at_ms) = expiry_ms.take() {
-                        db_instance.set_with_expiry(key, value, expire_at_ms);
+                        db_instance.set_with_expiry(key.clone(), value, expire_at_ms);
                     } else {
-                        db_instance.set(key, value);
+                        db_instance.set(key.clone(), value);
+                    }
+                    if !field_ttls.is_empty() {
+                        db_instance.set_hash_field_expiries(&key, field_ttls);
                     }
                 }
             }
@@ -276,7 +387,7 @@ impl RdbDeserializer {
         Ok(())
     }
 
-    fn read_string(reader: &mut BufReader<File>) -> Result<String> {
+    fn read_string<R: Read>(reader: &mut R) -> Result<String> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
@@ -287,7 +398,7 @@ impl RdbDeserializer {
         String::from_utf8(buf).context("Invalid UTF-8 in key")
     }
 
-    fn read_bytes(reader: &mut BufReader<File>) -> Result<Bytes> {
+    fn read_bytes<R: Read>(reader: &mut R) -> Result<Bytes> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
@@ -297,36 +408,33 @@ impl RdbDeserializer {
         Ok(Bytes::from(buf))
     }
 
-    fn read_list(reader: &mut BufReader<File>) -> Result<LinkedList<Bytes>> {
+    fn read_list<R: Read>(reader: &mut R, mut list: QuickList) -> Result<QuickList> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
 
-        let mut list = LinkedList::new();
         for _ in 0..len {
-            list.push_back(Self::read_bytes(reader)?);
+            list.push_back(&Self::read_bytes(reader)?);
         }
         Ok(list)
     }
 
-    fn read_set(reader: &mut BufReader<File>) -> Result<HashSet<Bytes>> {
+    fn read_set<R: Read>(reader: &mut R, mut set: SetValue) -> Result<SetValue> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
 
-        let mut set = HashSet::new();
         for _ in 0..len {
             set.insert(Self::read_bytes(reader)?);
         }
         Ok(set)
     }
 
-    fn read_hash(reader: &mut BufReader<File>) -> Result<HashMap<Bytes, Bytes>> {
+    fn read_hash<R: Read>(reader: &mut R, mut hash: HashValue) -> Result<HashValue> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
 
-        let mut hash = HashMap::new();
         for _ in 0..len {
             let key = Self::read_bytes(reader)?;
             let value = Self::read_bytes(reader)?;
@@ -335,26 +443,84 @@ impl RdbDeserializer {
         Ok(hash)
     }
 
-    fn read_zset(reader: &mut BufReader<File>) -> Result<crate::storage::types::ZSet> {
+    fn read_zset<R: Read>(reader: &mut R, mut zset: crate::storage::types::ZSet) -> Result<crate::storage::types::ZSet> {
         let mut len_bytes = [0u8; 4];
         reader.read_exact(&mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
 
-        let mut zset = crate::storage::types::ZSet::new();
         for _ in 0..len {
             let member = Self::read_bytes(reader)?;
             let mut score_bytes = [0u8; 8];
             reader.read_exact(&mut score_bytes)?;
             let score = f64::from_le_bytes(score_bytes);
 
-            zset.members.insert(member.clone(), score);
-            zset.scores.insert(
-                (ordered_float::OrderedFloat(score), member),
-                (),
-            );
+            zset.insert(member, score);
         }
         Ok(zset)
     }
+
+    fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_id<R: Read>(reader: &mut R) -> Result<StreamId> {
+        let timestamp = Self::read_u64(reader)?;
+        let sequence = Self::read_u64(reader)?;
+        Ok(StreamId::new(timestamp, sequence))
+    }
+
+    fn read_stream<R: Read>(reader: &mut R) -> Result<Stream> {
+        let mut stream = Stream::new();
+        for _ in 0..Self::read_u32(reader)? {
+            let id = Self::read_id(reader)?;
+            let mut fields = Vec::new();
+            for _ in 0..Self::read_u32(reader)? {
+                fields.push((Self::read_bytes(reader)?, Self::read_bytes(reader)?));
+            }
+            stream.entries.insert(id.clone(), StreamEntry { id, fields });
+        }
+        stream.last_id = Self::read_id(reader)?;
+        stream.entries_added = Self::read_u64(reader)?;
+        stream.max_deleted_id = Self::read_id(reader)?;
+
+        for _ in 0..Self::read_u32(reader)? {
+            let name = Self::read_string(reader)?;
+            let last_delivered_id = Self::read_id(reader)?;
+            let entries_read = Some(Self::read_u64(reader)?).filter(|&n| n != u64::MAX);
+            let mut group = ConsumerGroup::new(last_delivered_id, entries_read);
+
+            for _ in 0..Self::read_u32(reader)? {
+                let id = Self::read_id(reader)?;
+                let consumer = Self::read_string(reader)?;
+                let delivery_time = Self::read_u64(reader)?;
+                let delivery_count = Self::read_u64(reader)?;
+                group.pending.insert(id, PendingEntry { consumer, delivery_time, delivery_count });
+            }
+
+            for _ in 0..Self::read_u32(reader)? {
+                let name = Self::read_string(reader)?;
+                let seen_time = Self::read_u64(reader)?;
+                let active_time = Some(Self::read_u64(reader)?).filter(|&t| t != u64::MAX);
+                let pending: BTreeSet<StreamId> = group
+                    .pending
+                    .iter()
+                    .filter(|(_, entry)| entry.consumer == name)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                group.consumers.insert(name, Consumer { seen_time, active_time, pending });
+            }
+            stream.groups.insert(name, group);
+        }
+        Ok(stream)
+    }
 }
 
 #[cfg(test)]
@@ -373,9 +539,7 @@ mod tests {
             RedisValue::String(Bytes::from("test_value")),
         );
 
-        let mut list = LinkedList::new();
-        list.push_back(Bytes::from("item1"));
-        list.push_back(Bytes::from("item2"));
+        let list: QuickList = ["item1", "item2"].into_iter().collect();
         db_instance.set("list_key".to_string(), RedisValue::List(list));
 
         // Save to temporary file
@@ -399,4 +563,69 @@ mod tests {
             _ => panic!("Wrong type"),
         }
     }
+
+    #[test]
+    fn test_rdb_stream_with_groups() {
+        let db = Database::new(16);
+        let db_instance = db.get_db(0).unwrap();
+
+        let mut stream = Stream::new();
+        for seq in 1..=3 {
+            let id = StreamId::new(100, seq);
+            let fields = vec![(Bytes::from("f"), Bytes::from(seq.to_string()))];
+            stream.entries.insert(id.clone(), StreamEntry { id: id.clone(), fields });
+            stream.last_id = id;
+            stream.entries_added += 1;
+        }
+        let mut group = ConsumerGroup::new(StreamId::new(100, 2), Some(2));
+        let mut consumer = Consumer::new(5000);
+        consumer.active_time = Some(6000);
+        consumer.pending.insert(StreamId::new(100, 1));
+        group.consumers.insert("alice".to_string(), consumer);
+        group.consumers.insert("bob".to_string(), Consumer::new(7000));
+        group.pending.insert(
+            StreamId::new(100, 1),
+            PendingEntry { consumer: "alice".to_string(), delivery_time: 6000, delivery_count: 2 },
+        );
+        stream.groups.insert("g".to_string(), group.clone());
+        stream.groups.insert("empty".to_string(), ConsumerGroup::new(StreamId::new(0, 0), None));
+        db_instance.set("s".to_string(), RedisValue::Stream(stream));
+
+        let bytes = RdbSerializer::to_bytes(&db).unwrap();
+        let db2 = Database::new(16);
+        RdbDeserializer::read_snapshot(&db2, &mut bytes.as_slice()).unwrap();
+
+        match db2.get_db(0).unwrap().get("s") {
+            Some(RedisValue::Stream(loaded)) => {
+                assert_eq!(loaded.len(), 3);
+                assert_eq!(loaded.last_id, StreamId::new(100, 3));
+                assert_eq!(loaded.entries_added, 3);
+                assert_eq!(loaded.groups["g"], group);
+                assert_eq!(loaded.groups["empty"].entries_read, None);
+            }
+            other => panic!("Wrong value: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rdb_hash_field_ttls() {
+        let db = Database::new(16);
+        let db_instance = db.get_db(0).unwrap();
+
+        let mut hash = HashValue::new();
+        hash.insert(Bytes::from("a"), Bytes::from("1"));
+        hash.insert(Bytes::from("b"), Bytes::from("2"));
+        db_instance.set("h".to_string(), RedisValue::Hash(hash));
+        let expire_at = crate::storage::db::current_timestamp_ms() + 60_000;
+        db_instance.set_hash_field_expiry("h", Bytes::from("a"), expire_at);
+
+        let bytes = RdbSerializer::to_bytes(&db).unwrap();
+        let db2 = Database::new(16);
+        RdbDeserializer::read_snapshot(&db2, &mut bytes.as_slice()).unwrap();
+
+        let loaded = db2.get_db(0).unwrap();
+        assert!(matches!(loaded.get("h"), Some(RedisValue::Hash(h)) if h.len() == 2));
+        assert_eq!(loaded.hash_field_expiry("h", b"a"), Some(expire_at));
+        assert_eq!(loaded.hash_field_expiry("h", b"b"), None);
+    }
 }
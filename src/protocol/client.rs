@@ -0,0 +1,114 @@
+// Minimal RESP client
+// Used by server-side components that talk to other instances
+// (sentinel monitoring, cluster links)
+
+use super::{RespParser, RespSerializer, RespValue};
+use bytes::BytesMut;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// A single connection to another RESP server
+pub struct RespClient {
+    stream: TcpStream,
+    buffer: BytesMut,
+    /// Reply timeout, None waiting forever
+    timeout: Option<Duration>,
+}
+
+impl RespClient {
+    /// Connect to `addr`, failing if it takes longer than `timeout`.
+    /// The same timeout applies to every reply read afterwards.
+    pub async fn connect(addr: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow::anyhow!("connect to {} timed out", addr))??;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            buffer: BytesMut::with_capacity(4096),
+            timeout: Some(timeout),
+        })
+    }
+
+    /// Change the timeout of later reply reads, None waiting forever
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Whether an idle connection can carry another request: the peer has
+    /// neither closed it nor sent anything it was not asked for
+    pub fn is_idle_open(&self) -> bool {
+        self.buffer.is_empty()
+            && matches!(self.stream.try_read(&mut [0u8; 1]), Err(e) if e.kind() == std::io::ErrorKind::WouldBlock)
+    }
+
+    /// Send a command and wait for its reply
+    pub async fn command<A: AsRef<[u8]>>(&mut self, args: &[A]) -> anyhow::Result<RespValue> {
+        self.send(args).await?;
+        self.read_reply().await
+    }
+
+    /// Send a command without waiting for the reply
+    pub async fn send<A: AsRef<[u8]>>(&mut self, args: &[A]) -> anyhow::Result<()> {
+        let frame = RespValue::Array(Some(
+            args.iter()
+                .map(|arg| RespValue::BulkString(Some(arg.as_ref().to_vec())))
+                .collect(),
+        ));
+        self.stream.write_all(&RespSerializer::serialize(&frame)).await?;
+        Ok(())
+    }
+
+    /// Read the next frame, honouring the client timeout. After an error
+    /// the connection is out of step with its replies and must be dropped.
+    pub async fn read_reply(&mut self) -> anyhow::Result<RespValue> {
+        let Some(timeout) = self.timeout else {
+            return self.read_frame().await;
+        };
+        tokio::time::timeout(timeout, self.read_frame())
+            .await
+            .map_err(|_| anyhow::anyhow!("timed out waiting for reply"))?
+    }
+
+    /// Read the next frame with no timeout (for subscriptions)
+    pub async fn read_frame(&mut self) -> anyhow::Result<RespValue> {
+        loop {
+            if let Some(len) = RespParser::check_complete(&self.buffer)? {
+                let frame_data = self.buffer.split_to(len);
+                return Ok(RespParser::parse(&frame_data)?);
+            }
+
+            let n = self.stream.read_buf(&mut self.buffer).await?;
+            if n == 0 {
+                return Err(anyhow::anyhow!("Connection closed"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_command_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"*1\r\n$4\r\nPING\r\n");
+            // Reply arrives in two pieces
+            socket.write_all(b"+PO").await.unwrap();
+            socket.write_all(b"NG\r\n").await.unwrap();
+        });
+
+        let mut client = RespClient::connect(&addr, Duration::from_secs(1)).await.unwrap();
+        let reply = client.command(&["PING"]).await.unwrap();
+        assert_eq!(reply, RespValue::SimpleString("PONG".to_string()));
+    }
+}
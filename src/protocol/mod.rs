@@ -4,9 +4,11 @@
 use std::io::Cursor;
 use thiserror::Error;
 
+pub mod client;
 pub mod parser;
 pub mod serializer;
 
+pub use client::RespClient;
 pub use parser::RespParser;
 pub use serializer::RespSerializer;
 
@@ -29,6 +31,10 @@ pub enum RespValue {
     Boolean(bool),
     /// Double (RESP3)
     Double(f64),
+    /// Map (RESP3): %1\r\n+key\r\n:1\r\n
+    Map(Vec<(RespValue, RespValue)>),
+    /// Out-of-band push (RESP3): >2\r\n$10\r\ninvalidate\r\n...
+    Push(Vec<RespValue>),
 }
 
 impl RespValue {
@@ -28,6 +28,11 @@ impl RespParser {
             b':' => Self::parse_integer(cursor),
             b'$' => Self::parse_bulk_string(cursor),
             b'*' => Self::parse_array(cursor),
+            b'>' => match Self::parse_array(cursor)? {
+                RespValue::Array(Some(items)) => Ok(RespValue::Push(items)),
+                _ => Err(RespError::InvalidArrayLength),
+            },
+            b'%' => Self::parse_map(cursor),
             b'_' => {
                 // RESP3 null
                 let _ = read_line(cursor)?;
@@ -139,6 +144,24 @@ impl RespParser {
         Ok(RespValue::Array(Some(arr)))
     }
 
+    /// Parse map: %1\r\n+key\r\n:1\r\n
+    fn parse_map(cursor: &mut Cursor<&[u8]>) -> Result<RespValue> {
+        let line = read_line(cursor)?;
+        let len = parse_integer(line)?;
+        if len < 0 {
+            return Err(RespError::InvalidArrayLength);
+        }
+
+        let mut pairs = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let key = Self::parse_value(cursor)?;
+            let value = Self::parse_value(cursor)?;
+            pairs.push((key, value));
+        }
+
+        Ok(RespValue::Map(pairs))
+    }
+
     /// Check if buffer contains a complete RESP value
     pub fn check_complete(buf: &BytesMut) -> Result<Option<usize>> {
         let mut cursor = Cursor::new(&buf[..]);
@@ -289,6 +312,28 @@ mod tests {
         assert_eq!(result, RespValue::Double(-0.5));
     }
 
+    #[test]
+    fn test_resp3_map_and_push() {
+        let result = RespParser::parse(b"%1\r\n+mode\r\n$10\r\nstandalone\r\n").unwrap();
+        assert_eq!(
+            result,
+            RespValue::Map(vec![(
+                RespValue::SimpleString("mode".to_string()),
+                RespValue::BulkString(Some(b"standalone".to_vec()))
+            )])
+        );
+
+        let result = RespParser::parse(b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nkey\r\n").unwrap();
+        assert_eq!(
+            result,
+            RespValue::Push(vec![
+                RespValue::BulkString(Some(b"invalidate".to_vec())),
+                RespValue::Array(Some(vec![RespValue::BulkString(Some(b"key".to_vec()))]))
+            ])
+        );
+        assert!(matches!(RespParser::parse(b"%2\r\n+a\r\n:1\r\n"), Err(RespError::Incomplete)));
+    }
+
     #[test]
     fn test_check_complete() {
         let mut buf = BytesMut::from(&b"+OK\r\n"[..]);
@@ -69,6 +69,23 @@ impl RespSerializer {
                 buf.put_slice(d.to_string().as_bytes());
                 buf.put_slice(b"\r\n");
             }
+            RespValue::Map(pairs) => {
+                buf.put_u8(b'%');
+                buf.put_slice(pairs.len().to_string().as_bytes());
+                buf.put_slice(b"\r\n");
+                for (key, value) in pairs {
+                    Self::write_value(buf, key);
+                    Self::write_value(buf, value);
+                }
+            }
+            RespValue::Push(items) => {
+                buf.put_u8(b'>');
+                buf.put_slice(items.len().to_string().as_bytes());
+                buf.put_slice(b"\r\n");
+                for item in items {
+                    Self::write_value(buf, item);
+                }
+            }
         }
     }
 
@@ -206,6 +223,21 @@ mod tests {
         assert_eq!(result, b",3.14159\r\n");
     }
 
+    #[test]
+    fn test_serialize_resp3_map_and_push() {
+        let result = RespSerializer::serialize(&RespValue::Map(vec![(
+            RespValue::BulkString(Some(b"proto".to_vec())),
+            RespValue::Integer(3),
+        )]));
+        assert_eq!(result, b"%1\r\n$5\r\nproto\r\n:3\r\n");
+
+        let result = RespSerializer::serialize(&RespValue::Push(vec![
+            RespValue::BulkString(Some(b"invalidate".to_vec())),
+            RespValue::Null,
+        ]));
+        assert_eq!(result, b">2\r\n$10\r\ninvalidate\r\n_\r\n");
+    }
+
     #[test]
     fn test_convenience_methods() {
         assert_eq!(RespSerializer::ok(), b"+OK\r\n");
@@ -233,6 +265,8 @@ mod tests {
             RespValue::Null,
             RespValue::Boolean(true),
             RespValue::Double(3.14),
+            RespValue::Map(vec![(RespValue::SimpleString("id".to_string()), RespValue::Integer(7))]),
+            RespValue::Push(vec![RespValue::BulkString(Some(b"invalidate".to_vec())), RespValue::Array(Some(vec![]))]),
         ];
 
         for value in values {
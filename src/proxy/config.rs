@@ -0,0 +1,67 @@
+// Cluster proxy configuration
+
+use std::time::Duration;
+
+/// Default cluster proxy port
+pub const DEFAULT_PROXY_PORT: u16 = 7777;
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    /// Address to bind to
+    pub bind: String,
+    /// Port to listen on
+    pub port: u16,
+    /// Cluster nodes (host:port) asked for the slot map
+    pub seeds: Vec<String>,
+    /// Idle connections kept open per cluster node
+    pub pool_size: usize,
+    /// Connect and reply timeout towards cluster nodes
+    pub node_timeout: Duration,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: DEFAULT_PROXY_PORT,
+            seeds: Vec::new(),
+            pool_size: 16,
+            node_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ProxyConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn with_bind(mut self, bind: String) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    pub fn with_seeds(mut self, seeds: Vec<String>) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size;
+        self
+    }
+
+    pub fn with_node_timeout(mut self, node_timeout: Duration) -> Self {
+        self.node_timeout = node_timeout;
+        self
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.bind, self.port)
+    }
+}
@@ -0,0 +1,18 @@
+// Cluster proxy - lets cluster-unaware clients talk to a Redis Cluster
+//
+// The proxy serves no data. It learns the slot map from CLUSTER SLOTS,
+// forwards every command to the node owning its keys over pooled
+// connections, follows MOVED and ASK on the client's behalf, and splits
+// multi-key commands whose keys live in different slots.
+
+pub mod config;
+pub mod pool;
+pub mod router;
+pub mod server;
+pub mod topology;
+
+pub use config::ProxyConfig;
+pub use pool::ConnectionPool;
+pub use router::Router;
+pub use server::ProxyServer;
+pub use topology::Topology;
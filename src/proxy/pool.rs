@@ -0,0 +1,149 @@
+// Pooled connections to cluster nodes
+
+use crate::protocol::{RespClient, RespValue};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Why a call failed, which decides whether it may be sent again
+#[derive(Debug)]
+pub enum CallError {
+    /// The request never reached the node: connecting or writing failed
+    NotSent(anyhow::Error),
+    /// The request was written but its replies were not all read, so the
+    /// node may have run it
+    NoReply(anyhow::Error),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::NotSent(e) => write!(f, "{}", e),
+            CallError::NoReply(e) => write!(f, "no reply: {}", e),
+        }
+    }
+}
+
+/// Idle connections per node address. A connection is checked out for
+/// one request (possibly several pipelined commands) and only returned
+/// when every reply was read, so a broken or out of step link is dropped.
+pub struct ConnectionPool {
+    idle: Mutex<HashMap<String, Vec<RespClient>>>,
+    /// Idle connections kept per node
+    max_idle: usize,
+    timeout: Duration,
+}
+
+impl ConnectionPool {
+    pub fn new(max_idle: usize, timeout: Duration) -> Self {
+        Self {
+            idle: Mutex::new(HashMap::new()),
+            max_idle,
+            timeout,
+        }
+    }
+
+    /// Send `commands` to `addr` in one pipeline and return their replies
+    pub async fn call(&self, addr: &str, commands: &[Vec<Vec<u8>>]) -> Result<Vec<RespValue>, CallError> {
+        let mut client = match self.checkout(addr) {
+            Some(client) => client,
+            None => RespClient::connect(addr, self.timeout).await.map_err(CallError::NotSent)?,
+        };
+
+        let replies = exchange(&mut client, commands).await?;
+        self.checkin(addr, client);
+        Ok(replies)
+    }
+
+    /// Send a pipeline ending in a blocking command over a connection of
+    /// its own, waiting for the replies as long as the command may block
+    /// (`block`, zero meaning forever) plus the node timeout
+    pub async fn call_blocking(
+        &self,
+        addr: &str,
+        commands: &[Vec<Vec<u8>>],
+        block: Duration,
+    ) -> Result<Vec<RespValue>, CallError> {
+        let mut client = RespClient::connect(addr, self.timeout).await.map_err(CallError::NotSent)?;
+        client.set_timeout((!block.is_zero()).then(|| block + self.timeout));
+        exchange(&mut client, commands).await
+    }
+
+    /// Number of idle connections to `addr`
+    pub fn idle_count(&self, addr: &str) -> usize {
+        self.idle.lock().unwrap().get(addr).map_or(0, |clients| clients.len())
+    }
+
+    /// An idle connection the node has not closed in the meantime
+    fn checkout(&self, addr: &str) -> Option<RespClient> {
+        let mut idle = self.idle.lock().unwrap();
+        let clients = idle.get_mut(addr)?;
+        std::iter::from_fn(|| clients.pop()).find(RespClient::is_idle_open)
+    }
+
+    fn checkin(&self, addr: &str, client: RespClient) {
+        let mut idle = self.idle.lock().unwrap();
+        let clients = idle.entry(addr.to_string()).or_default();
+        if clients.len() < self.max_idle {
+            clients.push(client);
+        }
+    }
+}
+
+/// Write `commands` and read one reply per command
+async fn exchange(client: &mut RespClient, commands: &[Vec<Vec<u8>>]) -> Result<Vec<RespValue>, CallError> {
+    for command in commands {
+        client.send(command).await.map_err(CallError::NotSent)?;
+    }
+    let mut replies = Vec::with_capacity(commands.len());
+    for _ in commands {
+        replies.push(client.read_reply().await.map_err(CallError::NoReply)?);
+    }
+    Ok(replies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_reply_timeout_is_not_retryable_and_not_pooled() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // A node that reads the request and never answers
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            while socket.read(&mut buf).await.unwrap_or(0) > 0 {}
+        });
+
+        let pool = ConnectionPool::new(4, Duration::from_millis(100));
+        let command = vec![b"INCR".to_vec(), b"counter".to_vec()];
+        let result = pool.call(&addr, std::slice::from_ref(&command)).await;
+        assert!(matches!(result, Err(CallError::NoReply(_))));
+        assert_eq!(pool.idle_count(&addr), 0);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_call_outlives_node_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            assert!(socket.read(&mut buf).await.unwrap() > 0);
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            socket.write_all(b"*-1\r\n").await.unwrap();
+        });
+
+        let pool = ConnectionPool::new(4, Duration::from_millis(100));
+        let command = vec![b"BLPOP".to_vec(), b"list".to_vec(), b"0".to_vec()];
+        let replies = pool.call_blocking(&addr, &[command], Duration::ZERO).await.unwrap();
+        assert_eq!(replies, vec![RespValue::Array(None)]);
+        // The dedicated connection does not join the pool
+        assert_eq!(pool.idle_count(&addr), 0);
+    }
+}
@@ -0,0 +1,239 @@
+// Command routing for the cluster proxy
+
+use super::pool::{CallError, ConnectionPool};
+use super::topology::Topology;
+use crate::cluster::redirection::{parse_ask_error, parse_moved_error};
+use crate::cluster::{command_keys, key_hash_slot};
+use crate::protocol::RespValue;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Redirections followed for one command before giving up
+const MAX_REDIRECTS: usize = 16;
+
+/// Commands that need per-connection state on a node, which pooled
+/// connections cannot provide
+const UNSUPPORTED: &[&str] = &[
+    "MULTI", "EXEC", "DISCARD", "WATCH", "UNWATCH", "SUBSCRIBE", "UNSUBSCRIBE", "PSUBSCRIBE",
+    "PUNSUBSCRIBE", "SSUBSCRIBE", "SUNSUBSCRIBE", "MONITOR", "ASKING", "READONLY", "READWRITE",
+];
+
+/// Routes commands to the node serving their keys
+#[derive(Clone)]
+pub struct Router {
+    topology: Arc<Topology>,
+    pool: Arc<ConnectionPool>,
+}
+
+impl Router {
+    pub fn new(topology: Arc<Topology>, pool: Arc<ConnectionPool>) -> Self {
+        Self { topology, pool }
+    }
+
+    /// Run one client command against the cluster
+    pub async fn execute(&self, args: Vec<Vec<u8>>) -> RespValue {
+        let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match name.as_str() {
+            "PING" => match args.get(1) {
+                Some(message) => RespValue::BulkString(Some(message.clone())),
+                None => RespValue::SimpleString("PONG".to_string()),
+            },
+            "SELECT" => match args.get(1).map(|db| db.as_slice()) {
+                Some(b"0") => RespValue::SimpleString("OK".to_string()),
+                _ => RespValue::Error("ERR SELECT is not allowed in cluster mode".to_string()),
+            },
+            _ if UNSUPPORTED.contains(&name.as_str()) => RespValue::Error(format!(
+                "ERR '{}' is not supported by the cluster proxy",
+                name.to_lowercase()
+            )),
+            "MGET" | "MSET" | "DEL" | "EXISTS" | "UNLINK" | "TOUCH" if args.len() > 1 => {
+                self.split(&name, args).await
+            }
+            _ => {
+                let slot = command_keys(&name, &args).first().map(|key| key_hash_slot(key));
+                self.send(slot, args).await
+            }
+        }
+    }
+
+    /// Send a command to the node serving `slot` (any node when None),
+    /// following MOVED, ASK and TRYAGAIN. A command is only sent again when
+    /// it never reached a node or was redirected, never after its reply
+    /// went missing, since the node may have run it.
+    pub async fn send(&self, slot: Option<u16>, args: Vec<Vec<u8>>) -> RespValue {
+        let mut addr = self.route(slot);
+        let mut asking = false;
+        let mut retried_link = false;
+        let block = block_timeout(&args);
+
+        for _ in 0..MAX_REDIRECTS {
+            let Some(target) = addr.clone() else {
+                return RespValue::Error("CLUSTERDOWN Hash slot not served".to_string());
+            };
+
+            let mut commands = Vec::with_capacity(2);
+            if asking {
+                commands.push(vec![b"ASKING".to_vec()]);
+            }
+            commands.push(args.clone());
+            let reply = match block {
+                Some(block) => self.pool.call_blocking(&target, &commands, block).await,
+                None => self.pool.call(&target, &commands).await,
+            }
+            .map(|mut replies| replies.pop());
+
+            match reply {
+                Ok(Some(RespValue::Error(msg))) => {
+                    if let Some((moved_slot, to)) = parse_moved_error(&msg) {
+                        // Our map is stale: reload it, trusting the MOVED
+                        // reply over nodes that may not know the move yet
+                        self.topology.refresh(&self.pool).await;
+                        self.topology.set_slot(moved_slot, &to);
+                        addr = Some(to);
+                        asking = false;
+                    } else if let Some((_, to)) = parse_ask_error(&msg) {
+                        addr = Some(to);
+                        asking = true;
+                    } else if msg.starts_with("TRYAGAIN") {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    } else {
+                        return RespValue::Error(msg);
+                    }
+                }
+                Ok(Some(reply)) => return reply,
+                Ok(None) => return RespValue::Error("ERR empty reply from cluster node".to_string()),
+                Err(CallError::NoReply(e)) => {
+                    return RespValue::Error(format!("ERR no reply from cluster node {}: {}", target, e));
+                }
+                Err(CallError::NotSent(e)) => {
+                    // The node may have failed over: retry once on whatever
+                    // the refreshed map points to
+                    if retried_link || !self.topology.refresh(&self.pool).await {
+                        return RespValue::Error(format!("ERR cluster node {} unreachable: {}", target, e));
+                    }
+                    retried_link = true;
+                    addr = self.route(slot);
+                    asking = false;
+                }
+            }
+        }
+
+        RespValue::Error("ERR too many cluster redirections".to_string())
+    }
+
+    /// Reload the slot map from the cluster
+    pub async fn refresh_topology(&self) -> bool {
+        self.topology.refresh(&self.pool).await
+    }
+
+    fn route(&self, slot: Option<u16>) -> Option<String> {
+        slot.and_then(|slot| self.topology.node_for_slot(slot))
+            .or_else(|| self.topology.any_node())
+    }
+
+    /// Run a multi-key command as one sub-command per slot and merge the
+    /// replies as if a single node had answered
+    async fn split(&self, name: &str, args: Vec<Vec<u8>>) -> RespValue {
+        // MSET takes key/value pairs, the others plain keys
+        let step = if name == "MSET" { 2 } else { 1 };
+        if !(args.len() - 1).is_multiple_of(step) {
+            return RespValue::Error(format!("ERR wrong number of arguments for '{}' command", name.to_lowercase()));
+        }
+
+        // Argument groups per slot, remembering each key's position
+        let mut groups: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+        for (position, index) in (1..args.len()).step_by(step).enumerate() {
+            groups.entry(key_hash_slot(&args[index])).or_default().push(position);
+        }
+
+        let mut tasks = JoinSet::new();
+        for (slot, positions) in groups {
+            let mut command = vec![args[0].clone()];
+            for &position in &positions {
+                let index = 1 + position * step;
+                command.extend_from_slice(&args[index..index + step]);
+            }
+            let router = self.clone();
+            tasks.spawn(async move { (positions, router.send(Some(slot), command).await) });
+        }
+
+        let mut values = vec![RespValue::BulkString(None); (args.len() - 1) / step];
+        let mut total = 0;
+        while let Some(joined) = tasks.join_next().await {
+            let (positions, reply) = match joined {
+                Ok(result) => result,
+                Err(e) => return RespValue::Error(format!("ERR proxy task failed: {}", e)),
+            };
+            match reply {
+                RespValue::Error(msg) => return RespValue::Error(msg),
+                RespValue::Integer(n) => total += n,
+                RespValue::Array(Some(items)) => {
+                    for (position, item) in positions.into_iter().zip(items) {
+                        values[position] = item;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match name {
+            "MGET" => RespValue::Array(Some(values)),
+            "MSET" => RespValue::SimpleString("OK".to_string()),
+            _ => RespValue::Integer(total),
+        }
+    }
+}
+
+/// How long a blocking command may wait on its node, zero meaning forever
+/// as in the commands themselves; None for commands that reply right away
+fn block_timeout(args: &[Vec<u8>]) -> Option<Duration> {
+    let seconds = |arg: Option<&Vec<u8>>| -> Option<Duration> {
+        let secs: f64 = std::str::from_utf8(arg?).ok()?.parse().ok()?;
+        Duration::try_from_secs_f64(secs).ok()
+    };
+    let millis = |arg: Option<&Vec<u8>>| -> Option<Duration> {
+        std::str::from_utf8(arg?).ok()?.parse().ok().map(Duration::from_millis)
+    };
+
+    match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+        "BLPOP" | "BRPOP" | "BRPOPLPUSH" | "BLMOVE" | "BZPOPMIN" | "BZPOPMAX" => seconds(args.last()),
+        "BLMPOP" | "BZMPOP" => seconds(args.get(1)),
+        "WAIT" => millis(args.get(2)),
+        // BLOCK comes before STREAMS, and after the group and consumer names
+        name @ ("XREAD" | "XREADGROUP") => {
+            let from = if name == "XREAD" { 1 } else { 4 };
+            let streams = args.iter().position(|arg| arg.eq_ignore_ascii_case(b"STREAMS"))?;
+            let block = (from..streams).find(|&i| args[i].eq_ignore_ascii_case(b"BLOCK"))?;
+            millis(args.get(block + 1))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<Vec<u8>> {
+        line.split_whitespace().map(|a| a.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_block_timeout() {
+        assert_eq!(block_timeout(&args("GET key")), None);
+        assert_eq!(block_timeout(&args("BLPOP a b 1.5")), Some(Duration::from_millis(1500)));
+        assert_eq!(block_timeout(&args("BRPOP a 0")), Some(Duration::ZERO));
+        assert_eq!(block_timeout(&args("BLMPOP 2 1 a LEFT")), Some(Duration::from_secs(2)));
+        assert_eq!(block_timeout(&args("XREAD COUNT 1 BLOCK 250 STREAMS s $")), Some(Duration::from_millis(250)));
+        assert_eq!(block_timeout(&args("XREAD STREAMS BLOCK 0")), None);
+        assert_eq!(
+            block_timeout(&args("XREADGROUP GROUP g BLOCK STREAMS s >")),
+            None,
+            "a consumer named BLOCK does not block"
+        );
+        // A timeout the node will reject anyway goes out like any command
+        assert_eq!(block_timeout(&args("BLPOP a -1")), None);
+    }
+}
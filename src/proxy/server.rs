@@ -0,0 +1,94 @@
+// Cluster proxy server
+// Accepts plain RESP connections and hands every command to the router
+
+use super::config::ProxyConfig;
+use super::pool::ConnectionPool;
+use super::router::Router;
+use super::topology::Topology;
+use crate::protocol::{RespParser, RespSerializer, RespValue};
+use bytes::BytesMut;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info, warn};
+
+pub struct ProxyServer {
+    config: ProxyConfig,
+    topology: Arc<Topology>,
+    router: Router,
+}
+
+impl ProxyServer {
+    pub fn new(config: ProxyConfig) -> Self {
+        let topology = Arc::new(Topology::new(config.seeds.clone()));
+        let pool = Arc::new(ConnectionPool::new(config.pool_size, config.node_timeout));
+        let router = Router::new(Arc::clone(&topology), pool);
+        Self { config, topology, router }
+    }
+
+    pub fn topology(&self) -> &Arc<Topology> {
+        &self.topology
+    }
+
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let addr = self.config.addr();
+        let listener = TcpListener::bind(&addr).await?;
+        info!("Cluster proxy listening on {}", addr);
+
+        // Commands refresh the map on MOVED, so starting without one is fine
+        if !self.router.refresh_topology().await {
+            warn!("No cluster node answered CLUSTER SLOTS yet, seeds: {:?}", self.config.seeds);
+        }
+
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let router = self.router.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_client(router, socket).await {
+                    error!("Proxy client {} error: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_client(router: Router, mut socket: TcpStream) -> anyhow::Result<()> {
+    socket.set_nodelay(true)?;
+    let mut buffer = BytesMut::with_capacity(4096);
+
+    loop {
+        while let Some(len) = RespParser::check_complete(&buffer)? {
+            let frame = RespParser::parse(&buffer.split_to(len))?;
+            let reply = match command_args(frame) {
+                Some(args) if !args.is_empty() => {
+                    if args[0].eq_ignore_ascii_case(b"QUIT") {
+                        let ok = RespValue::SimpleString("OK".to_string());
+                        socket.write_all(&RespSerializer::serialize(&ok)).await?;
+                        return Ok(());
+                    }
+                    router.execute(args).await
+                }
+                _ => RespValue::Error("ERR invalid command format".to_string()),
+            };
+            socket.write_all(&RespSerializer::serialize(&reply)).await?;
+        }
+
+        if socket.read_buf(&mut buffer).await? == 0 {
+            return Ok(());
+        }
+    }
+}
+
+fn command_args(frame: RespValue) -> Option<Vec<Vec<u8>>> {
+    match frame {
+        RespValue::Array(Some(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                RespValue::BulkString(Some(data)) => Some(data),
+                RespValue::SimpleString(s) => Some(s.into_bytes()),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
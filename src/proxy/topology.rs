@@ -0,0 +1,146 @@
+// Slot map of the proxied cluster, learned from CLUSTER SLOTS
+
+use super::pool::ConnectionPool;
+use crate::cluster::CLUSTER_SLOTS;
+use crate::protocol::RespValue;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use tracing::{debug, warn};
+
+pub struct Topology {
+    /// Nodes to ask for the slot map when none of the known ones answers
+    seeds: Vec<String>,
+    /// Address of the master serving each slot
+    slots: RwLock<Vec<Option<String>>>,
+    /// Set while a refresh is running, so concurrent MOVED replies don't
+    /// all ask the cluster for the same map
+    refreshing: AtomicBool,
+}
+
+impl Topology {
+    pub fn new(seeds: Vec<String>) -> Self {
+        Self {
+            seeds,
+            slots: RwLock::new(vec![None; CLUSTER_SLOTS as usize]),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    /// Address of the node serving `slot`, if known
+    pub fn node_for_slot(&self, slot: u16) -> Option<String> {
+        self.slots.read().unwrap()[slot as usize].clone()
+    }
+
+    /// Record the owner a MOVED redirection pointed to
+    pub fn set_slot(&self, slot: u16, addr: &str) {
+        self.slots.write().unwrap()[slot as usize] = Some(addr.to_string());
+    }
+
+    /// Every known node address followed by the seeds, without duplicates
+    pub fn nodes(&self) -> Vec<String> {
+        let mut nodes: Vec<String> = Vec::new();
+        let slots = self.slots.read().unwrap();
+        for addr in slots.iter().flatten().chain(self.seeds.iter()) {
+            if !nodes.contains(addr) {
+                nodes.push(addr.clone());
+            }
+        }
+        nodes
+    }
+
+    /// Some node to send keyless commands to
+    pub fn any_node(&self) -> Option<String> {
+        self.nodes().into_iter().next()
+    }
+
+    /// Replace the slot map with a CLUSTER SLOTS reply. Slots missing from
+    /// the reply are no longer served by anyone.
+    pub fn apply_slots_reply(&self, reply: &RespValue) -> bool {
+        let Some(ranges) = parse_cluster_slots(reply) else {
+            return false;
+        };
+        let mut slots = vec![None; CLUSTER_SLOTS as usize];
+        for (start, end, addr) in ranges {
+            for slot in start..=end.min(CLUSTER_SLOTS - 1) {
+                slots[slot as usize] = Some(addr.clone());
+            }
+        }
+        *self.slots.write().unwrap() = slots;
+        true
+    }
+
+    /// Ask the known nodes for the slot map until one answers. Returns
+    /// false if another refresh was already running or nobody answered.
+    pub async fn refresh(&self, pool: &ConnectionPool) -> bool {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let command = vec![b"CLUSTER".to_vec(), b"SLOTS".to_vec()];
+        let mut refreshed = false;
+        for addr in self.nodes() {
+            match pool.call(&addr, std::slice::from_ref(&command)).await {
+                Ok(replies) if self.apply_slots_reply(&replies[0]) => {
+                    debug!("Cluster slot map refreshed from {}", addr);
+                    refreshed = true;
+                    break;
+                }
+                Ok(replies) => warn!("Unexpected CLUSTER SLOTS reply from {}: {:?}", addr, replies[0]),
+                Err(e) => warn!("Could not fetch the slot map from {}: {}", addr, e),
+            }
+        }
+
+        self.refreshing.store(false, Ordering::SeqCst);
+        refreshed
+    }
+}
+
+/// (start, end, master address) for every entry of a CLUSTER SLOTS reply
+pub fn parse_cluster_slots(reply: &RespValue) -> Option<Vec<(u16, u16, String)>> {
+    let mut ranges = Vec::new();
+    for entry in reply.as_array()? {
+        let entry = entry.as_array()?;
+        let start = entry.first()?.as_integer()? as u16;
+        let end = entry.get(1)?.as_integer()? as u16;
+        let master = entry.get(2)?.as_array()?;
+        let ip = String::from_utf8_lossy(master.first()?.as_bulk_string()?).to_string();
+        let port = master.get(1)?.as_integer()?;
+        ranges.push((start, end, format!("{}:{}", ip, port)));
+    }
+    Some(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slots_entry(start: i64, end: i64, port: i64) -> RespValue {
+        RespValue::Array(Some(vec![
+            RespValue::Integer(start),
+            RespValue::Integer(end),
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(b"127.0.0.1".to_vec())),
+                RespValue::Integer(port),
+                RespValue::BulkString(Some(b"id".to_vec())),
+            ])),
+        ]))
+    }
+
+    #[test]
+    fn test_apply_slots_reply() {
+        let topology = Topology::new(vec!["127.0.0.1:7000".to_string()]);
+        assert_eq!(topology.node_for_slot(0), None);
+
+        let reply = RespValue::Array(Some(vec![slots_entry(0, 8191, 7000), slots_entry(8192, 16383, 7001)]));
+        assert!(topology.apply_slots_reply(&reply));
+        assert_eq!(topology.node_for_slot(100).as_deref(), Some("127.0.0.1:7000"));
+        assert_eq!(topology.node_for_slot(16383).as_deref(), Some("127.0.0.1:7001"));
+        assert_eq!(topology.nodes(), vec!["127.0.0.1:7000", "127.0.0.1:7001"]);
+
+        topology.set_slot(100, "127.0.0.1:7001");
+        assert_eq!(topology.node_for_slot(100).as_deref(), Some("127.0.0.1:7001"));
+
+        assert!(!topology.apply_slots_reply(&RespValue::Error("ERR".to_string())));
+        assert_eq!(topology.node_for_slot(100).as_deref(), Some("127.0.0.1:7001"));
+    }
+}
@@ -2,18 +2,25 @@
 
 use crate::protocol::RespValue;
 use dashmap::DashMap;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 
 /// Channel for broadcasting messages
 type Channel = broadcast::Sender<Vec<u8>>;
 
+/// Pattern channel; messages carry the name of the channel they were published to
+type PatternChannel = broadcast::Sender<(String, Vec<u8>)>;
+
 /// Pub/Sub manager for handling subscriptions and publishing
 pub struct PubSub {
     /// Channels for exact name subscriptions
     channels: DashMap<String, Channel>,
     /// Pattern-based channels (for PSUBSCRIBE)
-    patterns: DashMap<String, Channel>,
+    patterns: DashMap<String, PatternChannel>,
+    /// Shard channels (for SSUBSCRIBE), a namespace of their own
+    shard_channels: DashMap<String, Channel>,
 }
 
 impl PubSub {
@@ -21,6 +28,7 @@ impl PubSub {
         Self {
             channels: DashMap::new(),
             patterns: DashMap::new(),
+            shard_channels: DashMap::new(),
         }
     }
 
@@ -38,7 +46,7 @@ impl PubSub {
         for entry in self.patterns.iter() {
             if Self::match_pattern(channel, entry.key()) {
                 subscriber_count += entry.value().receiver_count();
-                let _ = entry.value().send(message.clone());
+                let _ = entry.value().send((channel.to_string(), message.clone()));
             }
         }
 
@@ -54,8 +62,30 @@ impl PubSub {
         entry.subscribe()
     }
 
+    /// Publish a message to a shard channel. Pattern subscribers never
+    /// see shard messages.
+    pub fn spublish(&self, channel: &str, message: Vec<u8>) -> usize {
+        match self.shard_channels.get(channel) {
+            Some(ch) => {
+                let count = ch.receiver_count();
+                let _ = ch.send(message);
+                count
+            }
+            None => 0,
+        }
+    }
+
+    /// Get or create a shard channel for SSUBSCRIBE
+    pub fn get_or_create_shard_channel(&self, channel_name: &str) -> broadcast::Receiver<Vec<u8>> {
+        let entry = self.shard_channels.entry(channel_name.to_string()).or_insert_with(|| {
+            let (tx, _) = broadcast::channel(1024);
+            tx
+        });
+        entry.subscribe()
+    }
+
     /// Get or create a pattern channel for PSUBSCRIBE
-    pub fn get_or_create_pattern(&self, pattern: &str) -> broadcast::Receiver<Vec<u8>> {
+    pub fn get_or_create_pattern(&self, pattern: &str) -> broadcast::Receiver<(String, Vec<u8>)> {
         let entry = self.patterns.entry(pattern.to_string()).or_insert_with(|| {
             let (tx, _) = broadcast::channel(1024);
             tx
@@ -83,6 +113,32 @@ impl PubSub {
         }
     }
 
+    /// Remove a shard channel if it has no subscribers
+    pub fn cleanup_shard_channel(&self, channel_name: &str) {
+        if let Some(entry) = self.shard_channels.get(channel_name) {
+            if entry.receiver_count() == 0 {
+                drop(entry);
+                self.shard_channels.remove(channel_name);
+            }
+        }
+    }
+
+    /// Drop the shard channels for which `lost` returns true, whatever
+    /// their subscribers. Their forwarders see the channel close and
+    /// unsubscribe their clients. Returns the dropped channels.
+    pub fn drop_shard_channels(&self, lost: impl Fn(&str) -> bool) -> Vec<String> {
+        let dropped: Vec<String> = self
+            .shard_channels
+            .iter()
+            .filter(|entry| lost(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for channel in &dropped {
+            self.shard_channels.remove(channel);
+        }
+        dropped
+    }
+
     /// Get list of active channels (with at least one subscriber)
     pub fn active_channels(&self) -> Vec<String> {
         self.channels
@@ -92,6 +148,14 @@ impl PubSub {
             .collect()
     }
 
+    /// Get number of patterns with at least one subscriber
+    pub fn active_patterns(&self) -> usize {
+        self.patterns
+            .iter()
+            .filter(|entry| entry.value().receiver_count() > 0)
+            .count()
+    }
+
     /// Get count of subscribers for a channel
     pub fn channel_subscribers(&self, channel: &str) -> usize {
         self.channels
@@ -100,9 +164,26 @@ impl PubSub {
             .unwrap_or(0)
     }
 
+    /// Get list of active shard channels (with at least one subscriber)
+    pub fn active_shard_channels(&self) -> Vec<String> {
+        self.shard_channels
+            .iter()
+            .filter(|entry| entry.value().receiver_count() > 0)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Get count of subscribers for a shard channel
+    pub fn shard_channel_subscribers(&self, channel: &str) -> usize {
+        self.shard_channels
+            .get(channel)
+            .map(|ch| ch.receiver_count())
+            .unwrap_or(0)
+    }
+
     /// Simple pattern matching for PSUBSCRIBE
     /// Supports * (match any) and ? (match one character)
-    fn match_pattern(channel: &str, pattern: &str) -> bool {
+    pub fn match_pattern(channel: &str, pattern: &str) -> bool {
         if pattern == "*" {
             return true;
         }
@@ -167,6 +248,7 @@ pub async fn publish(pubsub: &Arc<PubSub>, args: Vec<Vec<u8>>) -> RespValue {
 pub struct SubscriptionState {
     pub channels: Vec<String>,
     pub patterns: Vec<String>,
+    pub shard_channels: Vec<String>,
 }
 
 impl SubscriptionState {
@@ -174,11 +256,12 @@ impl SubscriptionState {
         Self {
             channels: Vec::new(),
             patterns: Vec::new(),
+            shard_channels: Vec::new(),
         }
     }
 
     pub fn is_subscribed(&self) -> bool {
-        !self.channels.is_empty() || !self.patterns.is_empty()
+        !self.channels.is_empty() || !self.patterns.is_empty() || !self.shard_channels.is_empty()
     }
 
     pub fn add_channel(&mut self, channel: String) {
@@ -210,6 +293,21 @@ impl SubscriptionState {
             false
         }
     }
+
+    pub fn add_shard_channel(&mut self, channel: String) {
+        if !self.shard_channels.contains(&channel) {
+            self.shard_channels.push(channel);
+        }
+    }
+
+    pub fn remove_shard_channel(&mut self, channel: &str) -> bool {
+        if let Some(pos) = self.shard_channels.iter().position(|c| c == channel) {
+            self.shard_channels.remove(pos);
+            true
+        } else {
+            false
+        }
+    }
 }
 
 impl Default for SubscriptionState {
@@ -218,6 +316,161 @@ impl Default for SubscriptionState {
     }
 }
 
+/// Forwards messages from a connection's subscriptions into its push queue.
+///
+/// Each subscribed channel or pattern gets a task that turns broadcast
+/// messages into `message`/`pmessage`/`smessage` frames. Tasks are aborted
+/// when the subscription goes away or the subscriber is dropped.
+///
+/// A shard channel can also be dropped by the server when its slot moves
+/// to another node; its forwarder then pushes `sunsubscribe <channel>`
+/// without a count, for the connection to drop the subscription and
+/// complete the frame.
+pub struct Subscriber {
+    pubsub: Arc<PubSub>,
+    push: mpsc::UnboundedSender<RespValue>,
+    channel_tasks: HashMap<String, JoinHandle<()>>,
+    pattern_tasks: HashMap<String, JoinHandle<()>>,
+    shard_tasks: HashMap<String, JoinHandle<()>>,
+}
+
+impl Subscriber {
+    pub fn new(pubsub: Arc<PubSub>, push: mpsc::UnboundedSender<RespValue>) -> Self {
+        Self {
+            pubsub,
+            push,
+            channel_tasks: HashMap::new(),
+            pattern_tasks: HashMap::new(),
+            shard_tasks: HashMap::new(),
+        }
+    }
+
+    /// Start or stop forwarders so they match the subscription state
+    pub fn sync(&mut self, state: &SubscriptionState) {
+        self.channel_tasks.retain(|channel, task| {
+            let keep = state.channels.contains(channel);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+        self.pattern_tasks.retain(|pattern, task| {
+            let keep = state.patterns.contains(pattern);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+
+        for channel in &state.channels {
+            if self.channel_tasks.contains_key(channel) {
+                continue;
+            }
+            let mut rx = self.pubsub.get_or_create_channel(channel);
+            let push = self.push.clone();
+            let name = channel.clone();
+            let task = tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(message) => {
+                            let frame = RespValue::Array(Some(vec![
+                                RespValue::BulkString(Some(b"message".to_vec())),
+                                RespValue::BulkString(Some(name.as_bytes().to_vec())),
+                                RespValue::BulkString(Some(message)),
+                            ]));
+                            if push.send(frame).is_err() {
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+            self.channel_tasks.insert(channel.clone(), task);
+        }
+
+        for pattern in &state.patterns {
+            if self.pattern_tasks.contains_key(pattern) {
+                continue;
+            }
+            let mut rx = self.pubsub.get_or_create_pattern(pattern);
+            let push = self.push.clone();
+            let name = pattern.clone();
+            let task = tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok((channel, message)) => {
+                            let frame = RespValue::Array(Some(vec![
+                                RespValue::BulkString(Some(b"pmessage".to_vec())),
+                                RespValue::BulkString(Some(name.as_bytes().to_vec())),
+                                RespValue::BulkString(Some(channel.into_bytes())),
+                                RespValue::BulkString(Some(message)),
+                            ]));
+                            if push.send(frame).is_err() {
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+            self.pattern_tasks.insert(pattern.clone(), task);
+        }
+
+        self.shard_tasks.retain(|channel, task| {
+            let keep = state.shard_channels.contains(channel);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+        for channel in &state.shard_channels {
+            if self.shard_tasks.contains_key(channel) {
+                continue;
+            }
+            let mut rx = self.pubsub.get_or_create_shard_channel(channel);
+            let push = self.push.clone();
+            let name = channel.clone();
+            let task = tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(message) => {
+                            let frame = RespValue::Array(Some(vec![
+                                RespValue::BulkString(Some(b"smessage".to_vec())),
+                                RespValue::BulkString(Some(name.as_bytes().to_vec())),
+                                RespValue::BulkString(Some(message)),
+                            ]));
+                            if push.send(frame).is_err() {
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            let _ = push.send(RespValue::Array(Some(vec![
+                                RespValue::BulkString(Some(b"sunsubscribe".to_vec())),
+                                RespValue::BulkString(Some(name.into_bytes())),
+                            ])));
+                            break;
+                        }
+                    }
+                }
+            });
+            self.shard_tasks.insert(channel.clone(), task);
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let tasks = self.channel_tasks.values().chain(self.pattern_tasks.values());
+        for task in tasks.chain(self.shard_tasks.values()) {
+            task.abort();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,6 +508,75 @@ mod tests {
         assert_eq!(msg, b"hello".to_vec());
     }
 
+    #[tokio::test]
+    async fn test_pattern_message_carries_channel() {
+        let pubsub = PubSub::new();
+        let mut rx = pubsub.get_or_create_pattern("news.*");
+
+        assert_eq!(pubsub.publish("news.tech", b"hi".to_vec()), 1);
+        assert_eq!(pubsub.active_patterns(), 1);
+
+        let (channel, msg) = rx.recv().await.unwrap();
+        assert_eq!(channel, "news.tech");
+        assert_eq!(msg, b"hi".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_forwards_messages() {
+        let pubsub = Arc::new(PubSub::new());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(pubsub.clone(), tx);
+
+        let mut state = SubscriptionState::new();
+        state.add_channel("news".to_string());
+        state.add_pattern("n*".to_string());
+        subscriber.sync(&state);
+
+        assert_eq!(pubsub.publish("news", b"hi".to_vec()), 2);
+
+        let mut kinds = Vec::new();
+        for _ in 0..2 {
+            let frame = rx.recv().await.unwrap();
+            let items = frame.as_array().unwrap().to_vec();
+            kinds.push(items[0].as_bulk_string().unwrap().to_vec());
+            assert_eq!(items.last().unwrap().as_bulk_string(), Some(&b"hi"[..]));
+        }
+        kinds.sort();
+        assert_eq!(kinds, vec![b"message".to_vec(), b"pmessage".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_shard_channels() {
+        let pubsub = Arc::new(PubSub::new());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(pubsub.clone(), tx);
+
+        let mut state = SubscriptionState::new();
+        state.add_pattern("*".to_string());
+        state.add_shard_channel("orders".to_string());
+        subscriber.sync(&state);
+        assert!(state.is_subscribed());
+
+        // Shard messages skip pattern subscribers and plain channels
+        assert_eq!(pubsub.publish("orders", b"plain".to_vec()), 1);
+        let _ = rx.recv().await.unwrap();
+        assert_eq!(pubsub.spublish("orders", b"hi".to_vec()), 1);
+        let frame = rx.recv().await.unwrap();
+        let items = frame.as_array().unwrap().to_vec();
+        assert_eq!(items[0].as_bulk_string(), Some(&b"smessage"[..]));
+        assert_eq!(items[2].as_bulk_string(), Some(&b"hi"[..]));
+        assert_eq!(pubsub.active_shard_channels(), vec!["orders".to_string()]);
+        assert_eq!(pubsub.shard_channel_subscribers("orders"), 1);
+
+        // Dropping the channel unsubscribes the client
+        assert_eq!(pubsub.drop_shard_channels(|channel| channel == "orders"), vec!["orders".to_string()]);
+        let frame = rx.recv().await.unwrap();
+        let items = frame.as_array().unwrap().to_vec();
+        assert_eq!(items[0].as_bulk_string(), Some(&b"sunsubscribe"[..]));
+        assert_eq!(items[1].as_bulk_string(), Some(&b"orders"[..]));
+        assert_eq!(pubsub.spublish("orders", b"gone".to_vec()), 0);
+    }
+
     #[test]
     fn test_subscription_state() {
         let mut state = SubscriptionState::new();
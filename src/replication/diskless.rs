@@ -0,0 +1,546 @@
+// Diskless replication - stream RDB snapshots straight over replica sockets
+//
+// Master side: replicas that advertise `capa eof` are queued in a
+// `DisklessSyncScheduler`. The first replica of a batch arms a timer of
+// `repl-diskless-sync-delay` seconds; when it fires, the dataset is copied
+// at one replication offset (see `SnapshotSource`), and that copy is
+// serialized and fanned out to every replica that joined in the meantime.
+// The replication stream from that offset is held for each of them until
+// their payload has been sent.
+// The payload is framed as `$EOF:<40 byte mark>\r\n<rdb><40 byte mark>`
+// since its length is not known up front.
+//
+// Replica side: the payload (sized or EOF-marked) is forwarded chunk by
+// chunk to a channel that the RDB parser consumes, so nothing touches disk.
+
+use super::{CommandPropagator, ReplicationInfo};
+use crate::persistence::rdb::RdbSerializer;
+use crate::storage::db::Database;
+use bytes::{Buf, Bytes, BytesMut};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info};
+
+/// Length of the random delimiter used by the EOF-marked payload format
+pub const EOF_MARK_SIZE: usize = 40;
+
+/// Snapshot bytes are handed to replica sockets in chunks of this size
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Generate a random 40 character EOF delimiter
+pub fn generate_eof_mark() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..EOF_MARK_SIZE)
+        .map(|_| format!("{:x}", rng.gen::<u8>() % 16))
+        .collect()
+}
+
+/// How a replica loads the snapshot received during a full resync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisklessLoad {
+    /// Write the payload to a temporary RDB file, then load it
+    Disabled,
+    /// Parse from the socket only when the current dataset is empty
+    OnEmptyDb,
+    /// Parse from the socket into a staging database and swap it in
+    Swapdb,
+}
+
+impl DisklessLoad {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "disabled" => Some(DisklessLoad::Disabled),
+            "on-empty-db" => Some(DisklessLoad::OnEmptyDb),
+            "swapdb" => Some(DisklessLoad::Swapdb),
+            _ => None,
+        }
+    }
+}
+
+/// A piece of a snapshot on its way to a replica
+#[derive(Debug, Clone)]
+pub enum SnapshotChunk {
+    /// Replication offset the snapshot was taken at, sent before its data
+    Offset(u64),
+    Data(Bytes),
+    /// The snapshot was written completely
+    Done,
+}
+
+/// `std::io::Write` sink that fans snapshot bytes out to waiting replicas.
+/// Runs on a blocking thread, so it can use `blocking_send`.
+struct ChunkFanout {
+    buf: Vec<u8>,
+    targets: Vec<mpsc::Sender<SnapshotChunk>>,
+}
+
+impl ChunkFanout {
+    fn new(targets: Vec<mpsc::Sender<SnapshotChunk>>) -> Self {
+        Self {
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            targets,
+        }
+    }
+
+    fn send(&mut self, chunk: SnapshotChunk) -> std::io::Result<()> {
+        // Replicas whose connection went away are simply dropped
+        self.targets.retain(|tx| tx.blocking_send(chunk.clone()).is_ok());
+        if self.targets.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "all replicas disconnected",
+            ));
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.send(SnapshotChunk::Done)
+    }
+}
+
+impl Write for ChunkFanout {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE)));
+        self.send(SnapshotChunk::Data(chunk))
+    }
+}
+
+/// Where full resync snapshots are taken from
+#[derive(Clone)]
+pub struct SnapshotSource {
+    db: Arc<Database>,
+    repl_info: Arc<ReplicationInfo>,
+    propagator: Arc<CommandPropagator>,
+}
+
+impl SnapshotSource {
+    pub fn new(db: Arc<Database>, repl_info: Arc<ReplicationInfo>, propagator: Arc<CommandPropagator>) -> Self {
+        Self {
+            db,
+            repl_info,
+            propagator,
+        }
+    }
+
+    /// Pin the dataset with writes paused and hold the replication stream
+    /// from that point for `replicas` (client ids), then copy it with writes
+    /// going on again. Returns the copy and the offset it was pinned at.
+    pub async fn capture(&self, replicas: &[u64]) -> anyhow::Result<(Database, u64)> {
+        let (pinned, offset) = {
+            let _paused = self.repl_info.write_gate().pause().await;
+            self.propagator.buffer_replicas(replicas);
+            (self.db.pin_snapshot(), self.repl_info.master_offset())
+        };
+        let copy = tokio::task::spawn_blocking(move || pinned.copy()).await?;
+        Ok((copy, offset))
+    }
+}
+
+/// A replica waiting for a snapshot: its client id and where its chunks go
+type PendingReplica = (u64, mpsc::Sender<SnapshotChunk>);
+
+/// Batches diskless full syncs so that replicas arriving within the
+/// configured delay share a single snapshot
+pub struct DisklessSyncScheduler {
+    /// Replicas waiting for the next snapshot
+    pending: Arc<Mutex<Vec<PendingReplica>>>,
+}
+
+impl DisklessSyncScheduler {
+    pub fn new() -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Queue replica `id` for the next diskless snapshot.
+    /// The returned receiver yields the snapshot's `Offset`, the RDB payload
+    /// and `Done`; it is closed early if the snapshot failed.
+    pub fn register(&self, source: SnapshotSource, id: u64, delay: Duration) -> mpsc::Receiver<SnapshotChunk> {
+        let (tx, rx) = mpsc::channel(16);
+
+        let mut pending = self.pending.lock().unwrap();
+        let first = pending.is_empty();
+        pending.push((id, tx));
+
+        if first {
+            let pending = Arc::clone(&self.pending);
+            tokio::spawn(async move {
+                // Give other replicas a chance to join this snapshot
+                tokio::time::sleep(delay).await;
+                let targets = std::mem::take(&mut *pending.lock().unwrap());
+                info!("Starting diskless snapshot for {} replica(s)", targets.len());
+
+                let ids: Vec<u64> = targets.iter().map(|(id, _)| *id).collect();
+                let (copy, offset) = match source.capture(&ids).await {
+                    Ok(captured) => captured,
+                    Err(e) => {
+                        error!("Diskless snapshot failed: {}", e);
+                        return;
+                    }
+                };
+                let mut senders = Vec::with_capacity(targets.len());
+                for (_, tx) in targets {
+                    if tx.send(SnapshotChunk::Offset(offset)).await.is_ok() {
+                        senders.push(tx);
+                    }
+                }
+
+                let result = tokio::task::spawn_blocking(move || {
+                    let mut out = ChunkFanout::new(senders);
+                    RdbSerializer::write_snapshot(&copy, &mut out)?;
+                    out.finish()?;
+                    Ok::<_, anyhow::Error>(())
+                })
+                .await;
+
+                match result {
+                    Ok(Ok(())) => info!("Diskless snapshot transferred"),
+                    Ok(Err(e)) => error!("Diskless snapshot failed: {}", e),
+                    Err(e) => error!("Diskless snapshot task panicked: {}", e),
+                }
+            });
+        }
+
+        rx
+    }
+
+    /// Number of replicas waiting for the next snapshot
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+impl Default for DisklessSyncScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Master side: wait for a registered replica's snapshot to be taken,
+/// returning the replication offset it was taken at
+pub async fn snapshot_offset(chunks: &mut mpsc::Receiver<SnapshotChunk>) -> anyhow::Result<u64> {
+    match chunks.recv().await {
+        Some(SnapshotChunk::Offset(offset)) => Ok(offset),
+        _ => Err(anyhow::anyhow!("Snapshot aborted before it was taken")),
+    }
+}
+
+/// Master side: stream an EOF-marked payload to a replica
+pub async fn send_eof_payload<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut chunks: mpsc::Receiver<SnapshotChunk>,
+) -> anyhow::Result<u64> {
+    let mark = generate_eof_mark();
+    writer.write_all(format!("$EOF:{}\r\n", mark).as_bytes()).await?;
+
+    let mut sent = 0u64;
+    loop {
+        match chunks.recv().await {
+            Some(SnapshotChunk::Data(data)) => {
+                writer.write_all(&data).await?;
+                sent += data.len() as u64;
+            }
+            Some(SnapshotChunk::Done) => break,
+            Some(SnapshotChunk::Offset(_)) | None => {
+                return Err(anyhow::anyhow!("Snapshot aborted before completion"))
+            }
+        }
+    }
+
+    writer.write_all(mark.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(sent)
+}
+
+/// Master side: send an RDB payload whose size is known up front
+pub async fn send_sized_payload<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    rdb: &[u8],
+) -> anyhow::Result<()> {
+    writer.write_all(format!("${}\r\n", rdb.len()).as_bytes()).await?;
+    writer.write_all(rdb).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Framing of a full sync payload as announced by the master
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadHeader {
+    /// `$<len>\r\n` followed by exactly `len` bytes
+    Sized(usize),
+    /// `$EOF:<mark>\r\n` followed by data terminated by `mark`
+    EofMarked(Vec<u8>),
+}
+
+/// Parse the payload header at the start of `buf`.
+/// Returns the header and the number of bytes it occupies,
+/// or `None` if the header line is not complete yet.
+pub fn parse_payload_header(buf: &[u8]) -> anyhow::Result<Option<(PayloadHeader, usize)>> {
+    let pos = match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let line = &buf[..pos];
+
+    if !line.starts_with(b"$") {
+        return Err(anyhow::anyhow!(
+            "Bad protocol from master: expected payload, got {:?}",
+            String::from_utf8_lossy(line)
+        ));
+    }
+
+    let header = if let Some(mark) = line.strip_prefix(b"$EOF:") {
+        if mark.len() != EOF_MARK_SIZE {
+            return Err(anyhow::anyhow!("Invalid EOF mark length {}", mark.len()));
+        }
+        PayloadHeader::EofMarked(mark.to_vec())
+    } else {
+        let len: usize = std::str::from_utf8(&line[1..])?.parse()?;
+        PayloadHeader::Sized(len)
+    };
+
+    Ok(Some((header, pos + 2)))
+}
+
+/// Replica side: forward the payload described by `header` from
+/// `buffer` + `stream` into `out`. Bytes following the payload (the start
+/// of the command stream) are left in `buffer`.
+pub async fn receive_payload<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    buffer: &mut BytesMut,
+    header: &PayloadHeader,
+    out: mpsc::Sender<Bytes>,
+) -> anyhow::Result<u64> {
+    let mut received = 0u64;
+
+    match header {
+        PayloadHeader::Sized(len) => {
+            let mut remaining = *len;
+            while remaining > 0 {
+                if buffer.is_empty() && stream.read_buf(buffer).await? == 0 {
+                    return Err(anyhow::anyhow!("Connection closed while reading RDB payload"));
+                }
+                let take = buffer.len().min(remaining);
+                let chunk = buffer.split_to(take).freeze();
+                remaining -= take;
+                received += take as u64;
+                out.send(chunk)
+                    .await
+                    .map_err(|_| anyhow::anyhow!("RDB loader stopped early"))?;
+            }
+        }
+        PayloadHeader::EofMarked(mark) => {
+            loop {
+                // The mark may already be fully buffered
+                if buffer.len() >= EOF_MARK_SIZE {
+                    if let Some(pos) = find_subsequence(buffer, mark) {
+                        let chunk = buffer.split_to(pos).freeze();
+                        buffer.advance(EOF_MARK_SIZE);
+                        received += chunk.len() as u64;
+                        if !chunk.is_empty() {
+                            out.send(chunk)
+                                .await
+                                .map_err(|_| anyhow::anyhow!("RDB loader stopped early"))?;
+                        }
+                        break;
+                    }
+
+                    // Forward everything except a tail that could be the
+                    // beginning of a mark split across reads
+                    let safe = buffer.len() - (EOF_MARK_SIZE - 1);
+                    let chunk = buffer.split_to(safe).freeze();
+                    received += chunk.len() as u64;
+                    out.send(chunk)
+                        .await
+                        .map_err(|_| anyhow::anyhow!("RDB loader stopped early"))?;
+                }
+
+                if stream.read_buf(buffer).await? == 0 {
+                    return Err(anyhow::anyhow!("Connection closed while reading RDB payload"));
+                }
+            }
+        }
+    }
+
+    debug!("Received {} bytes of RDB payload", received);
+    Ok(received)
+}
+
+fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Blocking `std::io::Read` adapter over a channel of payload chunks,
+/// letting the synchronous RDB parser consume bytes as they arrive
+pub struct ChannelReader {
+    rx: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl ChannelReader {
+    pub fn new(rx: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            rx,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current[..n]);
+        self.current.advance(n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::rdb::RdbDeserializer;
+    use crate::replication::ReplicationBacklog;
+    use crate::storage::RedisValue;
+
+    fn source(db: &Arc<Database>) -> SnapshotSource {
+        let propagator = CommandPropagator::new(Arc::new(ReplicationBacklog::new()));
+        SnapshotSource::new(Arc::clone(db), Arc::new(ReplicationInfo::new()), Arc::new(propagator))
+    }
+
+    #[test]
+    fn test_generate_eof_mark() {
+        let mark = generate_eof_mark();
+        assert_eq!(mark.len(), EOF_MARK_SIZE);
+        assert!(mark.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(mark, generate_eof_mark());
+    }
+
+    #[test]
+    fn test_parse_payload_header() {
+        assert_eq!(parse_payload_header(b"$12").unwrap(), None);
+
+        let (header, used) = parse_payload_header(b"$12\r\nREDIS").unwrap().unwrap();
+        assert_eq!(header, PayloadHeader::Sized(12));
+        assert_eq!(used, 5);
+
+        let mark = generate_eof_mark();
+        let line = format!("$EOF:{}\r\n", mark);
+        let (header, used) = parse_payload_header(line.as_bytes()).unwrap().unwrap();
+        assert_eq!(header, PayloadHeader::EofMarked(mark.into_bytes()));
+        assert_eq!(used, line.len());
+
+        assert!(parse_payload_header(b"+OK\r\n").is_err());
+        assert!(parse_payload_header(b"$EOF:short\r\n").is_err());
+    }
+
+    #[test]
+    fn test_diskless_load_parse() {
+        assert_eq!(DisklessLoad::parse("disabled"), Some(DisklessLoad::Disabled));
+        assert_eq!(DisklessLoad::parse("on-empty-db"), Some(DisklessLoad::OnEmptyDb));
+        assert_eq!(DisklessLoad::parse("SWAPDB"), Some(DisklessLoad::Swapdb));
+        assert_eq!(DisklessLoad::parse("maybe"), None);
+    }
+
+    #[tokio::test]
+    async fn test_eof_payload_roundtrip() {
+        let db = Arc::new(Database::new(16));
+        let db0 = db.get_db(0).unwrap();
+        for i in 0..2000 {
+            db0.set(format!("key:{}", i), RedisValue::String(Bytes::from(format!("value:{}", i))));
+        }
+
+        // Master streams the snapshot into one end of a pipe
+        let scheduler = DisklessSyncScheduler::new();
+        let mut chunks = scheduler.register(source(&db), 1, Duration::from_millis(0));
+        let (mut master_end, mut replica_end) = tokio::io::duplex(4096);
+        let sender = tokio::spawn(async move {
+            assert_eq!(snapshot_offset(&mut chunks).await.unwrap(), 0);
+            send_eof_payload(&mut master_end, chunks).await.unwrap();
+            // The command stream follows the payload directly
+            master_end.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+        });
+
+        // Replica parses the other end into a staging database
+        let mut buffer = BytesMut::new();
+        let header = loop {
+            if let Some((header, used)) = parse_payload_header(&buffer).unwrap() {
+                buffer.advance(used);
+                break header;
+            }
+            replica_end.read_buf(&mut buffer).await.unwrap();
+        };
+
+        let staging = Arc::new(Database::new(16));
+        let (tx, rx) = mpsc::channel(16);
+        let loader_db = Arc::clone(&staging);
+        let loader = tokio::task::spawn_blocking(move || {
+            RdbDeserializer::read_snapshot(&loader_db, &mut ChannelReader::new(rx))
+        });
+
+        receive_payload(&mut replica_end, &mut buffer, &header, tx).await.unwrap();
+        loader.await.unwrap().unwrap();
+        sender.await.unwrap();
+
+        assert_eq!(staging.db_size(0).await, 2000);
+
+        // Whatever followed the mark is preserved for the command stream
+        while buffer.len() < 14 {
+            replica_end.read_buf(&mut buffer).await.unwrap();
+        }
+        assert_eq!(&buffer[..], b"*1\r\n$4\r\nPING\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_batches_replicas() {
+        let db = Arc::new(Database::new(16));
+        db.get_db(0)
+            .unwrap()
+            .set("k".to_string(), RedisValue::String(Bytes::from("v")));
+
+        let scheduler = DisklessSyncScheduler::new();
+        let mut first = scheduler.register(source(&db), 1, Duration::from_millis(100));
+        let mut second = scheduler.register(source(&db), 2, Duration::from_millis(100));
+        assert_eq!(scheduler.pending_count(), 2);
+
+        let mut payloads = Vec::new();
+        for rx in [&mut first, &mut second] {
+            let mut data = Vec::new();
+            while let Some(chunk) = rx.recv().await {
+                match chunk {
+                    SnapshotChunk::Offset(offset) => assert_eq!(offset, 0),
+                    SnapshotChunk::Data(d) => data.extend_from_slice(&d),
+                    SnapshotChunk::Done => break,
+                }
+            }
+            payloads.push(data);
+        }
+
+        assert_eq!(scheduler.pending_count(), 0);
+        assert_eq!(payloads[0], payloads[1]);
+        assert_eq!(payloads[0], RdbSerializer::to_bytes(&db).unwrap());
+    }
+}
@@ -5,9 +5,13 @@ pub mod backlog;
 pub mod sync;
 pub mod propagation;
 pub mod replica_client;
+pub mod diskless;
+pub mod write_gate;
 
-pub use replication_info::{ReplicationInfo, ReplicationRole};
+pub use replication_info::{ReplicationInfo, ReplicationRole, ReplicaState};
 pub use backlog::ReplicationBacklog;
 pub use sync::{SyncHandler, ReplicationOffset};
 pub use propagation::CommandPropagator;
-pub use replica_client::ReplicaClient;
+pub use replica_client::{start_replication, ReplicaClient};
+pub use diskless::{DisklessLoad, DisklessSyncScheduler, SnapshotSource};
+pub use write_gate::WriteGate;
@@ -2,7 +2,8 @@
 
 use crate::protocol::{RespSerializer, RespValue};
 use crate::replication::ReplicationBacklog;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 use tokio::sync::RwLock;
@@ -14,6 +15,9 @@ pub struct CommandPropagator {
     replicas: Arc<RwLock<Vec<ReplicaConnection>>>,
     /// Replication backlog for partial resync
     backlog: Arc<ReplicationBacklog>,
+    /// Stream held for replicas (by client id) that are still receiving
+    /// their snapshot or backlog tail
+    buffers: Mutex<HashMap<u64, Vec<u8>>>,
 }
 
 /// Represents an active replica connection
@@ -29,20 +33,56 @@ impl CommandPropagator {
         Self {
             replicas: Arc::new(RwLock::new(Vec::new())),
             backlog,
+            buffers: Mutex::new(HashMap::new()),
         }
     }
 
-    /// Add a replica connection for command propagation
-    pub async fn add_replica(&self, stream: TcpStream, ip: String, port: u16, offset: u64) {
-        let replica = ReplicaConnection {
+    /// Start holding the stream for replicas `ids` until `add_replica`.
+    /// Called with writes paused, at the offset their sync data ends.
+    pub fn buffer_replicas(&self, ids: &[u64]) {
+        let mut buffers = self.buffers.lock().unwrap();
+        for &id in ids {
+            buffers.insert(id, Vec::new());
+        }
+    }
+
+    /// Drop the stream held for a replica whose sync did not complete
+    pub fn discard_buffer(&self, id: u64) {
+        self.buffers.lock().unwrap().remove(&id);
+    }
+
+    /// Add a replica connection for command propagation, first sending it
+    /// the stream held since its sync data was taken
+    pub async fn add_replica(&self, id: u64, mut stream: TcpStream, ip: String, port: u16, offset: u64) {
+        // Catch up without holding up propagation, then send the rest with
+        // it held so nothing falls between the buffer and the live stream
+        loop {
+            let held = match self.buffers.lock().unwrap().get_mut(&id) {
+                Some(buffer) => std::mem::take(buffer),
+                None => break,
+            };
+            if held.is_empty() {
+                break;
+            }
+            if let Err(e) = stream.write_all(&held).await {
+                error!("Failed to catch up replica {}:{}: {}", ip, port, e);
+                self.discard_buffer(id);
+                return;
+            }
+        }
+
+        let mut replicas = self.replicas.write().await;
+        let held = self.buffers.lock().unwrap().remove(&id).unwrap_or_default();
+        if let Err(e) = stream.write_all(&held).await {
+            error!("Failed to catch up replica {}:{}: {}", ip, port, e);
+            return;
+        }
+        replicas.push(ReplicaConnection {
             stream: Arc::new(RwLock::new(stream)),
             ip: ip.clone(),
             port,
             offset,
-        };
-
-        let mut replicas = self.replicas.write().await;
-        replicas.push(replica);
+        });
         debug!("Added replica {}:{} for command propagation", ip, port);
     }
 
@@ -53,15 +93,23 @@ impl CommandPropagator {
         debug!("Removed replica {}:{} from propagation", ip, port);
     }
 
-    /// Propagate a write command to all replicas
-    pub async fn propagate(&self, db_index: usize, cmd_args: &[Vec<u8>], offset: u64) {
+    /// Propagate a write command to all replicas.
+    /// Returns the number of bytes added to the replication stream, which
+    /// is what the master offset advances by.
+    pub async fn propagate(&self, db_index: usize, cmd_args: &[Vec<u8>], offset: u64) -> u64 {
         // Add to backlog for partial resync
         let cmd_resp = Self::encode_command(db_index, cmd_args);
+        let len = cmd_resp.len() as u64;
         self.backlog.add(offset, cmd_resp.clone());
 
         // Propagate to all connected replicas
         let replicas = self.replicas.read().await;
 
+        // Replicas still syncing get it once they are added
+        for buffer in self.buffers.lock().unwrap().values_mut() {
+            buffer.extend_from_slice(&cmd_resp);
+        }
+
         for replica in replicas.iter() {
             let stream = replica.stream.clone();
             let cmd_data = cmd_resp.clone();
@@ -75,6 +123,8 @@ impl CommandPropagator {
                 }
             });
         }
+
+        len
     }
 
     /// Send command data to a specific replica
@@ -188,7 +238,7 @@ mod tests {
 
         // Should start with SELECT command
         assert!(encoded.starts_with(b"*2\r\n"));
-        assert!(encoded.contains(&b"SELECT"[..]));
+        assert!(encoded.windows(6).any(|w| w == b"SELECT"));
     }
 
     #[tokio::test]
@@ -198,4 +248,30 @@ mod tests {
 
         assert_eq!(propagator.replica_count().await, 0);
     }
+
+    #[tokio::test]
+    async fn test_stream_held_while_replica_syncs() {
+        use tokio::io::AsyncReadExt;
+
+        let backlog = Arc::new(ReplicationBacklog::new());
+        let propagator = CommandPropagator::new(backlog);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut replica_end = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (master_end, _) = listener.accept().await.unwrap();
+
+        // Written while the replica loads its snapshot
+        propagator.buffer_replicas(&[7]);
+        let first = vec![b"SET".to_vec(), b"a".to_vec(), b"1".to_vec()];
+        let offset = propagator.propagate(0, &first, 0).await;
+
+        propagator.add_replica(7, master_end, "127.0.0.1".to_string(), 0, 0).await;
+        let second = vec![b"SET".to_vec(), b"b".to_vec(), b"2".to_vec()];
+        propagator.propagate(0, &second, offset).await;
+
+        let expected = [CommandPropagator::encode_command(0, &first), CommandPropagator::encode_command(0, &second)].concat();
+        let mut received = vec![0; expected.len()];
+        replica_end.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, expected);
+        assert!(propagator.buffers.lock().unwrap().is_empty());
+    }
 }
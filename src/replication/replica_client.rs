@@ -1,13 +1,16 @@
 // Replica client - Connects to master and handles replication
 
+use crate::config::Config;
 use crate::persistence::rdb::RdbDeserializer;
 use crate::protocol::{RespParser, RespSerializer, RespValue};
-use crate::replication::{ReplicationInfo, ReplicationBacklog};
+use crate::replication::diskless::{parse_payload_header, receive_payload, ChannelReader, DisklessLoad};
+use crate::replication::{ReplicationInfo, ReplicationBacklog, ReplicaState};
 use crate::storage::db::Database;
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use std::sync::{atomic::AtomicU64, atomic::Ordering, Arc, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
 /// Replica client that connects to master
@@ -21,6 +24,10 @@ pub struct ReplicaClient {
     db_index: Arc<Mutex<usize>>,
     /// Replica offset
     replica_offset: Arc<AtomicU64>,
+    /// How the full sync payload is loaded (repl-diskless-load)
+    diskless_load: DisklessLoad,
+    /// Port we accept clients on, announced via REPLCONF listening-port
+    listening_port: u16,
 }
 
 impl ReplicaClient {
@@ -39,9 +46,23 @@ impl ReplicaClient {
             backlog,
             db_index: Arc::new(Mutex::new(0)),
             replica_offset: Arc::new(AtomicU64::new(0)),
+            diskless_load: DisklessLoad::Disabled,
+            listening_port: 6379,
         }
     }
 
+    /// Set the port announced to the master
+    pub fn with_listening_port(mut self, port: u16) -> Self {
+        self.listening_port = port;
+        self
+    }
+
+    /// Set how the full sync payload is loaded
+    pub fn with_diskless_load(mut self, mode: DisklessLoad) -> Self {
+        self.diskless_load = mode;
+        self
+    }
+
     /// Start replication connection to master
     pub async fn start(&self) -> anyhow::Result<()> {
         info!(
@@ -49,26 +70,52 @@ impl ReplicaClient {
             self.master_host, self.master_port
         );
 
+        let result = self.sync_with_master().await;
+        self.repl_info.update_replica_state(ReplicaState::Disconnected);
+        result
+    }
+
+    /// Keep replicating, reconnecting after `retry` whenever the link drops.
+    /// Runs until the task is aborted by a later REPLICAOF.
+    pub async fn run(&self, retry: std::time::Duration) {
+        loop {
+            match self.start().await {
+                Ok(()) => warn!("Lost connection to master"),
+                Err(e) => error!("Replication connection failed: {}", e),
+            }
+            tokio::time::sleep(retry).await;
+        }
+    }
+
+    async fn sync_with_master(&self) -> anyhow::Result<()> {
         // Connect to master
+        self.repl_info.update_replica_state(ReplicaState::Connecting);
         let mut stream = TcpStream::connect(format!("{}:{}", self.master_host, self.master_port))
             .await?;
 
         info!("Connected to master");
 
+        // Shared across phases: the master sends the RDB payload and the
+        // command stream right behind the PSYNC reply, so a single read can
+        // span several of them
+        let mut buffer = BytesMut::with_capacity(4096);
+
         // Perform handshake
-        self.handshake(&mut stream).await?;
+        self.handshake(&mut stream, &mut buffer).await?;
 
         // Receive sync data (RDB or command stream)
-        self.receive_sync(&mut stream).await?;
+        self.repl_info.update_replica_state(ReplicaState::WaitingFullSync);
+        self.receive_sync(&mut stream, &mut buffer).await?;
+        self.repl_info.update_replica_state(ReplicaState::Connected);
 
         // Process command stream
-        self.process_command_stream(&mut stream).await?;
+        self.process_command_stream(&mut stream, buffer).await?;
 
         Ok(())
     }
 
     /// Perform handshake with master
-    async fn handshake(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn handshake(&self, stream: &mut TcpStream, buffer: &mut BytesMut) -> anyhow::Result<()> {
         // Step 1: Send PING
         debug!("Sending PING to master");
         let ping_cmd = RespValue::Array(Some(vec![RespValue::BulkString(Some(
@@ -79,7 +126,7 @@ impl ReplicaClient {
         stream.flush().await?;
 
         // Read PONG response
-        let response = self.read_response(stream).await?;
+        let response = self.read_response(stream, buffer).await?;
         debug!("Received PING response: {:?}", response);
 
         // Step 2: Send REPLCONF listening-port
@@ -87,14 +134,14 @@ impl ReplicaClient {
         let replconf_cmd = RespValue::Array(Some(vec![
             RespValue::BulkString(Some(b"REPLCONF".to_vec())),
             RespValue::BulkString(Some(b"listening-port".to_vec())),
-            RespValue::BulkString(Some(self.master_port.to_string().into_bytes())),
+            RespValue::BulkString(Some(self.listening_port.to_string().into_bytes())),
         ]));
         let replconf_data = RespSerializer::serialize(&replconf_cmd);
         stream.write_all(&replconf_data).await?;
         stream.flush().await?;
 
         // Read OK response
-        let response = self.read_response(stream).await?;
+        let response = self.read_response(stream, buffer).await?;
         debug!("Received REPLCONF response: {:?}", response);
 
         // Step 3: Send REPLCONF capa (capabilities)
@@ -102,6 +149,8 @@ impl ReplicaClient {
         let capa_cmd = RespValue::Array(Some(vec![
             RespValue::BulkString(Some(b"REPLCONF".to_vec())),
             RespValue::BulkString(Some(b"capa".to_vec())),
+            RespValue::BulkString(Some(b"eof".to_vec())),
+            RespValue::BulkString(Some(b"capa".to_vec())),
             RespValue::BulkString(Some(b"psync2".to_vec())),
         ]));
         let capa_data = RespSerializer::serialize(&capa_cmd);
@@ -109,7 +158,7 @@ impl ReplicaClient {
         stream.flush().await?;
 
         // Read OK response
-        let response = self.read_response(stream).await?;
+        let response = self.read_response(stream, buffer).await?;
         debug!("Received CAPA response: {:?}", response);
 
         // Step 4: Send PSYNC
@@ -129,9 +178,9 @@ impl ReplicaClient {
     }
 
     /// Receive sync data from master (RDB or continuation)
-    async fn receive_sync(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn receive_sync(&self, stream: &mut TcpStream, buffer: &mut BytesMut) -> anyhow::Result<()> {
         // Read PSYNC response
-        let response = self.read_response(stream).await?;
+        let response = self.read_response(stream, buffer).await?;
         debug!("Received PSYNC response: {:?}", response);
 
         match response {
@@ -142,10 +191,15 @@ impl ReplicaClient {
                 if parts.len() >= 3 {
                     let repl_id = parts[1].to_string();
                     info!("Master replication ID: {}", repl_id);
+                    if let Ok(offset) = parts[2].parse::<u64>() {
+                        self.replica_offset.store(offset, Ordering::SeqCst);
+                        self.repl_info.set_offset(offset);
+                    }
                 }
 
+                self.repl_info.update_replica_state(ReplicaState::ReceivingRdb);
                 // Receive RDB data
-                self.receive_rdb(stream).await?;
+                self.receive_rdb(stream, buffer).await?;
             }
             RespValue::SimpleString(s) if s.starts_with("CONTINUE") => {
                 info!("Partial resync possible");
@@ -160,77 +214,86 @@ impl ReplicaClient {
     }
 
     /// Receive RDB data from master
-    async fn receive_rdb(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn receive_rdb(&self, stream: &mut TcpStream, buffer: &mut BytesMut) -> anyhow::Result<()> {
         info!("Receiving RDB data from master");
 
-        // Read RDB as bulk string
-        // Format: $<length>\r\n<data>
-        let mut buffer = BytesMut::with_capacity(4096);
-
-        // Read until we have the length prefix
-        loop {
-            let n = stream.read_buf(&mut buffer).await?;
-            if n == 0 {
+        // Read until we have the payload header ($<len> or $EOF:<mark>)
+        let header = loop {
+            if let Some((header, used)) = parse_payload_header(buffer)? {
+                buffer.advance(used);
+                break header;
+            }
+            if stream.read_buf(buffer).await? == 0 {
                 return Err(anyhow::anyhow!("Connection closed while reading RDB"));
             }
+        };
+        info!("RDB payload: {:?}", header);
 
-            // Try to parse the bulk string length
-            if buffer.starts_with(b"$") {
-                if let Some(pos) = buffer.windows(2).position(|w| w == b"\r\n") {
-                    let len_str = std::str::from_utf8(&buffer[1..pos])?;
-                    let rdb_len: usize = len_str.parse()?;
-
-                    info!("RDB size: {} bytes", rdb_len);
-
-                    // Skip the length prefix
-                    buffer.advance(pos + 2);
-
-                    // Read the RDB data
-                    let mut rdb_data = vec![0u8; rdb_len];
-                    let mut read_total = 0;
-
-                    // Copy what we already have
-                    let available = buffer.len().min(rdb_len);
-                    rdb_data[..available].copy_from_slice(&buffer[..available]);
-                    read_total += available;
-                    buffer.advance(available);
-
-                    // Read the rest
-                    while read_total < rdb_len {
-                        let n = stream.read(&mut rdb_data[read_total..]).await?;
-                        if n == 0 {
-                            return Err(anyhow::anyhow!("Connection closed while reading RDB data"));
-                        }
-                        read_total += n;
-                    }
+        let load_from_socket = match self.diskless_load {
+            DisklessLoad::Disabled => false,
+            DisklessLoad::OnEmptyDb => self.db.is_empty(),
+            DisklessLoad::Swapdb => true,
+        };
 
-                    info!("Received complete RDB data: {} bytes", read_total);
+        if load_from_socket {
+            // Parse straight from the socket. With swapdb the old dataset keeps
+            // serving reads until the new one is completely loaded.
+            let target = if self.diskless_load == DisklessLoad::Swapdb {
+                Arc::new(self.db.new_like())
+            } else {
+                Arc::clone(&self.db)
+            };
+
+            let (tx, rx) = mpsc::channel(16);
+            let loader_db = Arc::clone(&target);
+            let loader = tokio::task::spawn_blocking(move || {
+                RdbDeserializer::read_snapshot(&loader_db, &mut ChannelReader::new(rx))
+            });
+
+            let received = receive_payload(stream, buffer, &header, tx).await;
+            let loaded = loader.await?;
+            received?;
+            loaded?;
+
+            if self.diskless_load == DisklessLoad::Swapdb {
+                self.db.swap_with(&target);
+            }
+            info!("RDB loaded from socket");
+        } else {
+            // Save RDB to temporary file and load it
+            let (tx, mut rx) = mpsc::channel::<Bytes>(16);
+            let collector = tokio::spawn(async move {
+                let mut rdb_data = Vec::new();
+                while let Some(chunk) = rx.recv().await {
+                    rdb_data.extend_from_slice(&chunk);
+                }
+                rdb_data
+            });
+            let read_total = receive_payload(stream, buffer, &header, tx).await?;
+            let rdb_data = collector.await?;
 
-                    // Save RDB to temporary file and load it
-                    let temp_path = "/tmp/replica_sync.rdb";
-                    tokio::fs::write(temp_path, &rdb_data).await?;
+            info!("Received complete RDB data: {} bytes", read_total);
 
-                    info!("Loading RDB into database");
-                    RdbDeserializer::load(&self.db, temp_path).await?;
+            let temp_path = "/tmp/replica_sync.rdb";
+            tokio::fs::write(temp_path, &rdb_data).await?;
 
-                    info!("RDB loaded successfully");
+            info!("Loading RDB into database");
+            self.db.flush_all().await;
+            RdbDeserializer::load(&self.db, temp_path).await?;
 
-                    // Clean up temp file
-                    let _ = tokio::fs::remove_file(temp_path).await;
+            info!("RDB loaded successfully");
 
-                    break;
-                }
-            }
+            // Clean up temp file
+            let _ = tokio::fs::remove_file(temp_path).await;
         }
 
         Ok(())
     }
 
     /// Process command stream from master
-    async fn process_command_stream(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn process_command_stream(&self, stream: &mut TcpStream, mut buffer: BytesMut) -> anyhow::Result<()> {
         info!("Processing command stream from master");
 
-        let mut buffer = BytesMut::with_capacity(4096);
         let mut last_ack = std::time::Instant::now();
         let ack_interval = std::time::Duration::from_secs(1); // Send ACK every second
 
@@ -270,6 +333,7 @@ impl ReplicaClient {
 
                 // Update replica offset by bytes consumed
                 self.replica_offset.fetch_add(len as u64, Ordering::SeqCst);
+                self.repl_info.increment_offset(len as u64);
 
                 match RespParser::parse(&frame_data) {
                     Ok(frame) => {
@@ -351,35 +415,173 @@ impl ReplicaClient {
         // This avoids circular dependencies and is more efficient
         debug!("Received command to apply: {}", cmd);
 
-        // TODO: Implement direct command application
-        // For now, we just log that we received it
-        // Full implementation would apply each command type directly to the database
+        let db_index = *self.db_index.lock().unwrap();
+        let db = &self.db;
+        let args = cmd_args[1..].to_vec();
+
+        use crate::commands::{bitmap, expiration, geo, hash, list, set, stream, stream_group, string, zset};
+
+        let result = match cmd.as_str() {
+            // String commands
+            "SET" => string::set(db, db_index, args).await,
+            "SETEX" => string::setex(db, db_index, args).await,
+            "PSETEX" => string::psetex(db, db_index, args).await,
+            "SETNX" => string::setnx(db, db_index, args).await,
+            "GETDEL" => string::getdel(db, db_index, args).await,
+            "DEL" => string::del(db, db_index, args).await,
+            "APPEND" => string::append(db, db_index, args).await,
+            "INCR" => string::incr(db, db_index, args).await,
+            "DECR" => string::decr(db, db_index, args).await,
+            "INCRBY" => string::incrby(db, db_index, args).await,
+            "DECRBY" => string::decrby(db, db_index, args).await,
+            "INCRBYFLOAT" => string::incrbyfloat(db, db_index, args).await,
+            "SETRANGE" => string::setrange(db, db_index, args).await,
+            "MSET" => string::mset(db, db_index, args).await,
+            "MSETNX" => string::msetnx(db, db_index, args).await,
+
+            // List commands
+            "LPUSH" => list::lpush(db, db_index, args).await,
+            "RPUSH" => list::rpush(db, db_index, args).await,
+            "LPOP" => list::lpop(db, db_index, args).await,
+            "RPOP" => list::rpop(db, db_index, args).await,
+            "LSET" => list::lset(db, db_index, args).await,
+            "LTRIM" => list::ltrim(db, db_index, args).await,
+            "LINSERT" => list::linsert(db, db_index, args).await,
+
+            // Hash commands
+            "HSET" => hash::hset(db, db_index, args).await,
+            "HDEL" => hash::hdel(db, db_index, args).await,
+            "HPEXPIREAT" => hash::hpexpireat(db, db_index, args).await,
+            "HPERSIST" => hash::hpersist(db, db_index, args).await,
+            "HGETDEL" => hash::hgetdel(db, db_index, args).await,
+            "HSETEX" => hash::hsetex(db, db_index, args).await,
+
+            // Set commands
+            "SADD" => set::sadd(db, db_index, args).await,
+            "SREM" => set::srem(db, db_index, args).await,
+            "SPOP" => set::spop(db, db_index, args).await,
+
+            // ZSet commands
+            "ZADD" => zset::zadd(db, db_index, args).await,
+            "ZREM" => zset::zrem(db, db_index, args).await,
+            "ZPOPMIN" => zset::zpopmin(db, db_index, args).await,
+            "ZPOPMAX" => zset::zpopmax(db, db_index, args).await,
+            "ZRANGESTORE" => zset::zrangestore(db, db_index, args).await,
+            "ZUNIONSTORE" => zset::zunionstore(db, db_index, args).await,
+            "ZINTERSTORE" => zset::zinterstore(db, db_index, args).await,
+
+            // Bitmap commands
+            "BITFIELD" => bitmap::bitfield(db, db_index, args).await,
+
+            // Geo commands
+            "GEOADD" => geo::geoadd(db, db_index, args).await,
+            "GEOSEARCHSTORE" => geo::geosearchstore(db, db_index, args).await,
+            "GEORADIUS" => geo::georadius(db, db_index, args).await,
+            "GEORADIUSBYMEMBER" => geo::georadiusbymember(db, db_index, args).await,
+
+            // Stream commands
+            "XADD" => stream::xadd(db, db_index, args).await,
+            "XDEL" => stream::xdel(db, db_index, args).await,
+            "XTRIM" => stream::xtrim(db, db_index, args).await,
+            "XSETID" => stream::xsetid(db, db_index, args).await,
+            "XGROUP" => stream_group::xgroup(db, db_index, args).await,
+            "XREADGROUP" => stream_group::xreadgroup(db, db_index, args).await,
+            "XACK" => stream_group::xack(db, db_index, args).await,
+            "XCLAIM" => stream_group::xclaim(db, db_index, args).await,
+
+            // Expiration commands
+            "EXPIRE" => expiration::expire(db, db_index, args).await,
+            "EXPIREAT" => expiration::expireat(db, db_index, args).await,
+            "PEXPIRE" => expiration::pexpire(db, db_index, args).await,
+            "PEXPIREAT" => expiration::pexpireat(db, db_index, args).await,
+            "PERSIST" => expiration::persist(db, db_index, args).await,
+
+            "FLUSHDB" => {
+                if let Some(instance) = db.get_db(db_index) {
+                    instance.clear();
+                }
+                RespValue::SimpleString("OK".to_string())
+            }
+            "FLUSHALL" => {
+                for index in 0..db.num_dbs() {
+                    if let Some(instance) = db.get_db(index) {
+                        instance.clear();
+                    }
+                }
+                RespValue::SimpleString("OK".to_string())
+            }
+
+            _ => {
+                debug!("Skipping unsupported replicated command: {}", cmd);
+                return Ok(());
+            }
+        };
+
+        if let RespValue::Error(e) = result {
+            warn!("Replicated {} failed: {}", cmd, e);
+        }
 
         Ok(())
     }
 
     /// Read a RESP response from stream
-    async fn read_response(&self, stream: &mut TcpStream) -> anyhow::Result<RespValue> {
-        let mut buffer = BytesMut::with_capacity(4096);
-
+    async fn read_response(&self, stream: &mut TcpStream, buffer: &mut BytesMut) -> anyhow::Result<RespValue> {
         loop {
-            let n = stream.read_buf(&mut buffer).await?;
-            if n == 0 {
-                return Err(anyhow::anyhow!("Connection closed"));
+            if let Ok(Some(len)) = RespParser::check_complete(buffer) {
+                let frame_data = buffer.split_to(len);
+                return Ok(RespParser::parse(&frame_data)?);
             }
 
-            match RespParser::check_complete(&buffer) {
-                Ok(Some(len)) => {
-                    let frame_data = buffer.split_to(len);
-                    return Ok(RespParser::parse(&frame_data)?);
-                }
-                Ok(None) => continue,
-                Err(_) => continue,
+            let n = stream.read_buf(buffer).await?;
+            if n == 0 {
+                return Err(anyhow::anyhow!("Connection closed"));
             }
         }
     }
 }
 
+/// Become a replica of `host:port` and keep the replication link running
+/// in the background until the next role change replaces it.
+/// Shared by REPLICAOF, CLUSTER REPLICATE and cluster failover.
+pub fn start_replication(
+    host: &str,
+    port: u16,
+    repl_info: &Arc<ReplicationInfo>,
+    backlog: &Arc<ReplicationBacklog>,
+    db: &Arc<Database>,
+    config: &Arc<Config>,
+) {
+    repl_info.set_replica(host.to_string(), port);
+
+    let diskless_load = config
+        .get("repl-diskless-load")
+        .and_then(|v| DisklessLoad::parse(&v))
+        .unwrap_or(DisklessLoad::Disabled);
+
+    // Start replication connection in background
+    let listening_port = config
+        .get_int("port")
+        .and_then(|p| u16::try_from(p).ok())
+        .unwrap_or(6379);
+
+    // Start replication connection in background; it keeps retrying until
+    // the next REPLICAOF replaces it
+    let replica_client = ReplicaClient::new(
+        host.to_string(),
+        port,
+        Arc::clone(db),
+        Arc::clone(repl_info),
+        Arc::clone(backlog),
+    )
+    .with_diskless_load(diskless_load)
+    .with_listening_port(listening_port);
+
+    let task = tokio::spawn(async move {
+        replica_client.run(std::time::Duration::from_secs(1)).await;
+    });
+    repl_info.set_link_task(task.abort_handle());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -401,4 +603,31 @@ mod tests {
         assert_eq!(client.master_host, "127.0.0.1");
         assert_eq!(client.master_port, 6379);
     }
+
+    #[tokio::test]
+    async fn test_apply_command_stream() {
+        let db = Arc::new(Database::new(16));
+        let client = ReplicaClient::new(
+            "127.0.0.1".to_string(),
+            6379,
+            Arc::clone(&db),
+            Arc::new(ReplicationInfo::new()),
+            Arc::new(ReplicationBacklog::new()),
+        );
+        let command = |args: &[&str]| {
+            RespValue::Array(Some(
+                args.iter().map(|a| RespValue::BulkString(Some(a.as_bytes().to_vec()))).collect(),
+            ))
+        };
+
+        client.apply_command(command(&["SELECT", "2"])).await.unwrap();
+        client.apply_command(command(&["SET", "key", "value"])).await.unwrap();
+        client.apply_command(command(&["RPUSH", "list", "a", "b"])).await.unwrap();
+        assert!(db.get_db(2).unwrap().exists("key"));
+        assert!(db.get_db(2).unwrap().exists("list"));
+        assert!(!db.get_db(0).unwrap().exists("key"));
+
+        client.apply_command(command(&["DEL", "key"])).await.unwrap();
+        assert!(!db.get_db(2).unwrap().exists("key"));
+    }
 }
@@ -1,7 +1,9 @@
 // Replication information and state management
 
+use super::write_gate::WriteGate;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::task::AbortHandle;
 
 
 /// Server role in replication
@@ -66,6 +68,10 @@ pub struct ReplicationInfo {
     master_offset: Arc<AtomicU64>,
     /// Connected replicas (only for master)
     replicas: Arc<RwLock<Vec<ReplicaInfo>>>,
+    /// Task running the link to our master (only for replica)
+    link_task: Arc<Mutex<Option<AbortHandle>>>,
+    /// Held by writes until they are propagated (only for master)
+    write_gate: WriteGate,
 }
 
 impl ReplicationInfo {
@@ -76,6 +82,8 @@ impl ReplicationInfo {
             replication_id: Arc::new(RwLock::new(Self::generate_replication_id())),
             master_offset: Arc::new(AtomicU64::new(0)),
             replicas: Arc::new(RwLock::new(Vec::new())),
+            link_task: Arc::new(Mutex::new(None)),
+            write_gate: WriteGate::new(),
         }
     }
 
@@ -105,6 +113,7 @@ impl ReplicationInfo {
 
     /// Set as master
     pub fn set_master(&self) {
+        self.stop_link();
         let mut role = self.role.write().unwrap();
         *role = ReplicationRole::Master;
 
@@ -115,6 +124,7 @@ impl ReplicationInfo {
 
     /// Set as replica
     pub fn set_replica(&self, master_host: String, master_port: u16) {
+        self.stop_link();
         let mut role = self.role.write().unwrap();
         *role = ReplicationRole::Replica {
             master_host,
@@ -123,6 +133,29 @@ impl ReplicationInfo {
         };
     }
 
+    /// Remember the task replicating from our master so that a later
+    /// REPLICAOF can stop it
+    pub fn set_link_task(&self, task: AbortHandle) {
+        if let Some(old) = self.link_task.lock().unwrap().replace(task) {
+            old.abort();
+        }
+    }
+
+    /// Stop replicating from the current master, if any
+    fn stop_link(&self) {
+        if let Some(task) = self.link_task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    /// Whether the link to our master is up
+    pub fn master_link_up(&self) -> bool {
+        matches!(
+            *self.role.read().unwrap(),
+            ReplicationRole::Replica { state: ReplicaState::Connected, .. }
+        )
+    }
+
     /// Update replica state
     pub fn update_replica_state(&self, new_state: ReplicaState) {
         let mut role = self.role.write().unwrap();
@@ -140,6 +173,11 @@ impl ReplicationInfo {
         self.replication_id.read().unwrap().clone()
     }
 
+    /// Gate that keeps writes and the master offset in step
+    pub fn write_gate(&self) -> &WriteGate {
+        &self.write_gate
+    }
+
     /// Get master replication offset
     pub fn master_offset(&self) -> u64 {
         self.master_offset.load(Ordering::SeqCst)
@@ -155,9 +193,10 @@ impl ReplicationInfo {
         self.master_offset.store(offset, Ordering::SeqCst);
     }
 
-    /// Add a replica
+    /// Add a replica, replacing an older entry for the same address
     pub fn add_replica(&self, replica: ReplicaInfo) {
         let mut replicas = self.replicas.write().unwrap();
+        replicas.retain(|r| r.ip != replica.ip || r.port != replica.port);
         replicas.push(replica);
     }
 
@@ -64,9 +64,9 @@ impl SyncHandler {
         RespValue::SimpleString(format!("CONTINUE {}", repl_id))
     }
 
-    /// Generate PSYNC response for full resync
-    pub fn generate_fullresync_response(repl_id: &str) -> RespValue {
-        RespValue::SimpleString(format!("FULLRESYNC {} 0", repl_id))
+    /// Generate PSYNC response for full resync from `offset`
+    pub fn generate_fullresync_response(repl_id: &str, offset: u64) -> RespValue {
+        RespValue::SimpleString(format!("FULLRESYNC {} {}", repl_id, offset))
     }
 
     /// Get commands from backlog for partial resync
@@ -0,0 +1,108 @@
+// Write gate - lets a resync capture the dataset and the replication
+// offset at the same point
+//
+// A write command holds the gate shared from the moment it runs until it
+// has been propagated, so outside the gate the master offset always matches
+// the data. Starting a resync takes the gate exclusively just long enough
+// to read the offset and pin the dataset (see `storage::snapshot`). Blocking commands give
+// their share back while they wait (see `idle`), so a BLPOP with no timeout
+// cannot hold a resync, and every write queued behind it, back.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+tokio::task_local! {
+    /// The share of the gate held by the write command running on this task
+    static HELD: RefCell<Share>;
+}
+
+/// A write command's hold on the gate, released while the command waits
+struct Share {
+    lock: Arc<RwLock<()>>,
+    guard: Option<OwnedRwLockReadGuard<()>>,
+}
+
+#[derive(Clone, Default)]
+pub struct WriteGate {
+    lock: Arc<RwLock<()>>,
+}
+
+impl WriteGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run a write command, holding the gate until it completes
+    pub async fn write<F: Future>(&self, command: F) -> F::Output {
+        let guard = Arc::clone(&self.lock).read_owned().await;
+        let share = Share {
+            lock: Arc::clone(&self.lock),
+            guard: Some(guard),
+        };
+        HELD.scope(RefCell::new(share), command).await
+    }
+
+    /// Wait for running writes to finish and hold new ones until the
+    /// returned guard is dropped
+    pub async fn pause(&self) -> OwnedRwLockWriteGuard<()> {
+        Arc::clone(&self.lock).write_owned().await
+    }
+}
+
+/// Await `wait` (a blocking command's sleep) without holding the gate,
+/// taking it back before the command goes on
+pub async fn idle<F: Future>(wait: F) -> F::Output {
+    let lock = HELD
+        .try_with(|share| {
+            let mut share = share.borrow_mut();
+            share.guard = None;
+            Arc::clone(&share.lock)
+        })
+        .ok();
+    let output = wait.await;
+    if let Some(lock) = lock {
+        let guard = lock.read_owned().await;
+        HELD.with(|share| share.borrow_mut().guard = Some(guard));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pause_waits_for_writes() {
+        let gate = WriteGate::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let writer = tokio::spawn({
+            let gate = gate.clone();
+            async move { gate.write(async move { rx.await.unwrap() }).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The write in progress holds the pause back
+        assert!(tokio::time::timeout(Duration::from_millis(50), gate.pause()).await.is_err());
+        tx.send(()).unwrap();
+        writer.await.unwrap();
+        let _paused = gate.pause().await;
+    }
+
+    #[tokio::test]
+    async fn test_idle_writes_do_not_hold_pause() {
+        let gate = WriteGate::new();
+        let writer = tokio::spawn({
+            let gate = gate.clone();
+            async move { gate.write(idle(tokio::time::sleep(Duration::from_secs(60)))).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // A blocked command gives its share back while it waits
+        let paused = tokio::time::timeout(Duration::from_millis(500), gate.pause()).await;
+        assert!(paused.is_ok());
+        writer.abort();
+    }
+}
@@ -0,0 +1,193 @@
+// Sentinel configuration
+// Parses the sentinel.conf format:
+//
+//   port 26379
+//   sentinel monitor mymaster 127.0.0.1 6379 2
+//   sentinel down-after-milliseconds mymaster 5000
+//   sentinel failover-timeout mymaster 60000
+//   sentinel parallel-syncs mymaster 1
+
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+
+/// Default sentinel port
+pub const DEFAULT_SENTINEL_PORT: u16 = 26379;
+
+/// A master the sentinel is asked to monitor
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterConfig {
+    pub name: String,
+    pub ip: String,
+    pub port: u16,
+    /// Sentinels that must agree the master is down before failing over
+    pub quorum: usize,
+    /// Time without a valid PING reply before the master is subjectively down
+    pub down_after: Duration,
+    /// Upper bound for the whole failover, also used to space out retries
+    pub failover_timeout: Duration,
+    /// Replicas reconfigured at the same time after a failover
+    pub parallel_syncs: usize,
+}
+
+impl MasterConfig {
+    pub fn new(name: String, ip: String, port: u16, quorum: usize) -> Self {
+        Self {
+            name,
+            ip,
+            port,
+            quorum,
+            down_after: Duration::from_millis(30_000),
+            failover_timeout: Duration::from_millis(180_000),
+            parallel_syncs: 1,
+        }
+    }
+
+    /// Apply a per-master option (`sentinel <option> <name> <value>` /
+    /// `SENTINEL SET <name> <option> <value>`)
+    pub fn set_option(&mut self, option: &str, value: &str) -> Result<()> {
+        match option.to_lowercase().as_str() {
+            "down-after-milliseconds" => self.down_after = Duration::from_millis(parse_positive(option, value)?),
+            "failover-timeout" => self.failover_timeout = Duration::from_millis(parse_positive(option, value)?),
+            "parallel-syncs" => self.parallel_syncs = parse_positive(option, value)? as usize,
+            "quorum" => self.quorum = parse_positive(option, value)? as usize,
+            _ => bail!("Unknown sentinel option '{}'", option),
+        }
+        Ok(())
+    }
+}
+
+fn parse_positive(option: &str, value: &str) -> Result<u64> {
+    match value.parse::<u64>() {
+        Ok(v) if v > 0 => Ok(v),
+        _ => bail!("Invalid value '{}' for {}", value, option),
+    }
+}
+
+/// Sentinel process configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentinelConfig {
+    pub bind: String,
+    pub port: u16,
+    /// Address other sentinels use to reach us (defaults to `bind`)
+    pub announce_ip: Option<String>,
+    pub masters: Vec<MasterConfig>,
+}
+
+impl Default for SentinelConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: DEFAULT_SENTINEL_PORT,
+            announce_ip: None,
+            masters: Vec::new(),
+        }
+    }
+}
+
+impl SentinelConfig {
+    /// Load configuration from a file
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sentinel config {}", path))?;
+        Self::parse(&content)
+    }
+
+    /// Parse configuration file content
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (line_num, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            config
+                .apply(&parts)
+                .with_context(|| format!("line {}: {}", line_num + 1, line))?;
+        }
+
+        Ok(config)
+    }
+
+    fn apply(&mut self, parts: &[&str]) -> Result<()> {
+        match (parts[0].to_lowercase().as_str(), parts.len()) {
+            ("port", 2) => self.port = parts[1].parse().context("invalid port")?,
+            ("bind", 2) => self.bind = parts[1].to_string(),
+            ("sentinel", _) if parts.len() >= 3 => self.apply_sentinel(&parts[1..])?,
+            // Other server directives do not apply to a sentinel
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_sentinel(&mut self, parts: &[&str]) -> Result<()> {
+        match (parts[0].to_lowercase().as_str(), parts.len()) {
+            ("monitor", 5) => {
+                let name = parts[1].to_string();
+                if self.masters.iter().any(|m| m.name == name) {
+                    bail!("Duplicated master name '{}'", name);
+                }
+                let port = parts[3].parse().context("invalid master port")?;
+                let quorum = parse_positive("quorum", parts[4])? as usize;
+                self.masters.push(MasterConfig::new(name, parts[2].to_string(), port, quorum));
+            }
+            ("announce-ip", 2) => self.announce_ip = Some(parts[1].to_string()),
+            (option, 3) => {
+                let master = self
+                    .masters
+                    .iter_mut()
+                    .find(|m| m.name == parts[1])
+                    .ok_or_else(|| anyhow::anyhow!("No such master with specified name '{}'", parts[1]))?;
+                master.set_option(option, parts[2])?;
+            }
+            _ => bail!("Unrecognized sentinel configuration statement"),
+        }
+        Ok(())
+    }
+
+    /// Address announced in hello messages
+    pub fn announce_ip(&self) -> String {
+        self.announce_ip.clone().unwrap_or_else(|| self.bind.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sentinel_config() {
+        let config = SentinelConfig::parse(
+            "# comment\n\
+             port 26380\n\
+             sentinel monitor mymaster 127.0.0.1 6379 2\n\
+             sentinel down-after-milliseconds mymaster 1000\n\
+             sentinel failover-timeout mymaster 5000\n\
+             sentinel parallel-syncs mymaster 2\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 26380);
+        assert_eq!(config.masters.len(), 1);
+        let master = &config.masters[0];
+        assert_eq!(master.name, "mymaster");
+        assert_eq!(master.port, 6379);
+        assert_eq!(master.quorum, 2);
+        assert_eq!(master.down_after, Duration::from_millis(1000));
+        assert_eq!(master.failover_timeout, Duration::from_millis(5000));
+        assert_eq!(master.parallel_syncs, 2);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // Options must follow the monitor line
+        assert!(SentinelConfig::parse("sentinel down-after-milliseconds mymaster 1000").is_err());
+        assert!(SentinelConfig::parse("sentinel monitor m 127.0.0.1 6379 0").is_err());
+        assert!(SentinelConfig::parse(
+            "sentinel monitor m 127.0.0.1 6379 1\nsentinel monitor m 127.0.0.1 6380 1"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,15 @@
+// Sentinel - high availability for master/replica setups
+//
+// A sentinel process serves no data. It monitors masters and their
+// replicas, agrees with other sentinels (discovered through the hello
+// channel on the monitored instances) that a master is down, elects a
+// leader among them and lets the leader promote a replica.
+
+pub mod config;
+pub mod monitor;
+pub mod server;
+pub mod state;
+
+pub use config::{MasterConfig, SentinelConfig};
+pub use server::SentinelServer;
+pub use state::SentinelState;
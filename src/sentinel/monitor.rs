@@ -0,0 +1,729 @@
+// Sentinel monitoring loop
+// One task per monitored master: pings and INFOs the master and its
+// replicas, exchanges hello messages with other sentinels, decides SDOWN /
+// ODOWN and drives the failover state machine
+
+use super::state::{Failover, FailoverState, MasterInstance, ReplicaInstance, SentinelState, HELLO_CHANNEL};
+use crate::protocol::{RespClient, RespValue};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Resolution of the monitoring loop
+const TICK: Duration = Duration::from_millis(100);
+/// PING every instance this often
+const PING_PERIOD: Duration = Duration::from_secs(1);
+/// INFO period when nothing is going on
+const INFO_PERIOD: Duration = Duration::from_secs(10);
+/// INFO period while the master is down or being failed over
+const INFO_PERIOD_FAST: Duration = Duration::from_secs(1);
+/// Publish hello messages this often
+const HELLO_PERIOD: Duration = Duration::from_secs(2);
+/// Ask other sentinels about a down master this often
+const ASK_PERIOD: Duration = Duration::from_secs(1);
+/// How long a peer's is-master-down reply stays valid
+const ASK_VALIDITY: Duration = Duration::from_secs(5);
+/// Connect / reply timeout for links to instances
+const LINK_TIMEOUT: Duration = Duration::from_millis(500);
+/// Longest we wait to be elected before giving up on an attempt
+const ELECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait this long before fixing a replica pointing at the wrong master
+const RECONF_GRACE: Duration = Duration::from_secs(5);
+
+/// Start monitoring the master named `name`. The task exits once the
+/// master is removed with SENTINEL REMOVE.
+pub fn spawn_monitor(state: Arc<SentinelState>, name: String) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        MasterMonitor::new(state, name).run().await;
+    })
+}
+
+/// Fields of an INFO reply that sentinels care about
+#[derive(Debug, Default, PartialEq)]
+pub struct InfoReport {
+    pub role: String,
+    pub master_host: Option<String>,
+    pub master_port: Option<u16>,
+    pub master_link_up: bool,
+    pub repl_offset: u64,
+    pub priority: Option<u64>,
+    /// Replicas listed by a master
+    pub replicas: Vec<(String, u16)>,
+}
+
+impl InfoReport {
+    pub fn parse(info: &str) -> Self {
+        let mut report = InfoReport::default();
+        for line in info.lines() {
+            let (key, value) = match line.trim().split_once(':') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            match key {
+                "role" => report.role = value.to_string(),
+                "master_host" => report.master_host = Some(value.to_string()),
+                "master_port" => report.master_port = value.parse().ok(),
+                "master_link_status" => report.master_link_up = value == "up",
+                "slave_repl_offset" => report.repl_offset = value.parse().unwrap_or(0),
+                "slave_priority" | "replica_priority" => report.priority = value.parse().ok(),
+                _ if key.starts_with("slave") && key[5..].chars().all(|c| c.is_ascii_digit()) => {
+                    let mut ip = None;
+                    let mut port = None;
+                    for field in value.split(',') {
+                        match field.split_once('=') {
+                            Some(("ip", v)) => ip = Some(v.to_string()),
+                            Some(("port", v)) => port = v.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(ip), Some(port)) = (ip, port) {
+                        report.replicas.push((ip, port));
+                    }
+                }
+                _ => {}
+            }
+        }
+        report
+    }
+}
+
+/// Send one command over a short-lived link
+async fn send_command(addr: &str, args: &[&str]) -> anyhow::Result<RespValue> {
+    let mut client = RespClient::connect(addr, LINK_TIMEOUT).await?;
+    client.command(args).await
+}
+
+/// An instance answers PING validly when it replies PONG, or is busy
+/// loading or waiting for its master
+async fn ping(addr: &str) -> bool {
+    match send_command(addr, &["PING"]).await {
+        Ok(RespValue::SimpleString(s)) => s == "PONG",
+        Ok(RespValue::Error(e)) => e.starts_with("LOADING") || e.starts_with("MASTERDOWN"),
+        _ => false,
+    }
+}
+
+async fn fetch_info(addr: &str) -> Option<InfoReport> {
+    match send_command(addr, &["INFO", "replication"]).await {
+        Ok(RespValue::BulkString(Some(data))) => Some(InfoReport::parse(&String::from_utf8_lossy(&data))),
+        _ => None,
+    }
+}
+
+/// Subscribe to the hello channel of an instance and feed every message to
+/// the state until the link drops
+async fn hello_subscriber(state: Arc<SentinelState>, addr: String) {
+    if let Ok(mut client) = RespClient::connect(&addr, LINK_TIMEOUT).await {
+        if client.command(&["SUBSCRIBE", HELLO_CHANNEL]).await.is_ok() {
+            while let Ok(frame) = client.read_frame().await {
+                if let Some(items) = frame.as_array() {
+                    if items.len() == 3 && items[0].as_bulk_string() == Some(&b"message"[..]) {
+                        if let Some(message) = items[2].as_bulk_string() {
+                            state.process_hello(&String::from_utf8_lossy(message));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // Back off before the monitor reconnects
+    tokio::time::sleep(PING_PERIOD).await;
+}
+
+fn due(last: Option<Instant>, period: Duration, now: Instant) -> bool {
+    last.is_none_or(|t| now.duration_since(t) >= period)
+}
+
+struct MasterMonitor {
+    state: Arc<SentinelState>,
+    name: String,
+    last_ping: Option<Instant>,
+    last_info: Option<Instant>,
+    last_hello: Option<Instant>,
+    last_ask: Option<Instant>,
+    hello_links: HashMap<String, JoinHandle<()>>,
+    /// Reconfiguration of the other replicas after a promotion; it runs on
+    /// its own so the monitor keeps ticking meanwhile
+    reconf: Option<JoinHandle<()>>,
+}
+
+impl MasterMonitor {
+    fn new(state: Arc<SentinelState>, name: String) -> Self {
+        Self {
+            state,
+            name,
+            last_ping: None,
+            last_info: None,
+            last_hello: None,
+            last_ask: None,
+            hello_links: HashMap::new(),
+            reconf: None,
+        }
+    }
+
+    async fn run(mut self) {
+        let mut ticker = tokio::time::interval(TICK);
+        loop {
+            ticker.tick().await;
+            let master = match self.state.master(&self.name) {
+                Some(master) => master,
+                None => break,
+            };
+            let now = Instant::now();
+
+            self.periodic_io(&master, now);
+            self.maintain_hello_links(&master);
+            self.check_sdown(now);
+            self.check_odown(now);
+            self.fix_replicas(now);
+            self.failover_step(now).await;
+        }
+
+        for (_, link) in self.hello_links.drain() {
+            link.abort();
+        }
+        if let Some(reconf) = self.reconf.take() {
+            reconf.abort();
+        }
+    }
+
+    /// Spawn PING / INFO / hello traffic that is due
+    fn periodic_io(&mut self, master: &MasterInstance, now: Instant) {
+        let mut instances = vec![master.addr()];
+        instances.extend(master.replicas.keys().cloned());
+
+        if due(self.last_ping, PING_PERIOD, now) {
+            self.last_ping = Some(now);
+            for addr in &instances {
+                let state = self.state.clone();
+                let name = self.name.clone();
+                let addr = addr.clone();
+                let is_master = addr == master.addr();
+                tokio::spawn(async move {
+                    if !ping(&addr).await {
+                        return;
+                    }
+                    let now = Instant::now();
+                    state.with_master(&name, |m| {
+                        if is_master && m.addr() == addr {
+                            m.last_ok_ping = now;
+                        } else if let Some(replica) = m.replicas.get_mut(&addr) {
+                            replica.last_ok_ping = now;
+                        }
+                    });
+                });
+            }
+        }
+
+        let info_period = if master.sdown
+            || master.failover.is_some()
+            || master.replicas.values().any(|r| r.info_refreshed.is_none())
+        {
+            INFO_PERIOD_FAST
+        } else {
+            INFO_PERIOD
+        };
+        if due(self.last_info, info_period, now) {
+            self.last_info = Some(now);
+            for addr in &instances {
+                let state = self.state.clone();
+                let name = self.name.clone();
+                let addr = addr.clone();
+                tokio::spawn(async move {
+                    if let Some(report) = fetch_info(&addr).await {
+                        apply_info(&state, &name, &addr, report);
+                    }
+                });
+            }
+        }
+
+        if due(self.last_hello, HELLO_PERIOD, now) {
+            self.last_hello = Some(now);
+            let hello = self.state.hello_for(master);
+            for addr in instances {
+                let hello = hello.clone();
+                tokio::spawn(async move {
+                    let _ = send_command(&addr, &["PUBLISH", HELLO_CHANNEL, &hello]).await;
+                });
+            }
+        }
+
+        if master.sdown && due(self.last_ask, ASK_PERIOD, now) {
+            self.last_ask = Some(now);
+            self.ask_peers(master);
+        }
+    }
+
+    /// Ask every other sentinel whether it sees the master down. While we
+    /// are trying to fail over, the question also asks for their vote.
+    fn ask_peers(&self, master: &MasterInstance) {
+        let run_id = match &master.failover {
+            Some(f) if f.state == FailoverState::WaitStart && !f.forced => self.state.my_id.clone(),
+            _ => "*".to_string(),
+        };
+        let epoch = self.state.current_epoch().to_string();
+        let port = master.config.port.to_string();
+
+        for peer in master.sentinels.values() {
+            let state = self.state.clone();
+            let name = self.name.clone();
+            let peer_id = peer.run_id.clone();
+            let peer_addr = peer.addr();
+            let args = [
+                "SENTINEL".to_string(),
+                "is-master-down-by-addr".to_string(),
+                master.config.ip.clone(),
+                port.clone(),
+                epoch.clone(),
+                run_id.clone(),
+            ];
+            tokio::spawn(async move {
+                let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+                let reply = match send_command(&peer_addr, &args).await {
+                    Ok(RespValue::Array(Some(items))) if items.len() == 3 => items,
+                    _ => return,
+                };
+                let down = reply[0].as_integer() == Some(1);
+                let leader = reply[1]
+                    .as_bulk_string()
+                    .map(|l| String::from_utf8_lossy(l).to_string())
+                    .filter(|l| l != "*");
+                let leader_epoch = reply[2].as_integer().unwrap_or(0) as u64;
+
+                state.with_master(&name, |m| {
+                    if let Some(peer) = m.sentinels.get_mut(&peer_id) {
+                        peer.master_down = down;
+                        peer.master_down_reply = Some(Instant::now());
+                        if let Some(leader) = leader {
+                            debug!("{} voted for {} in epoch {}", peer_id, leader, leader_epoch);
+                            peer.leader = Some(leader);
+                            peer.leader_epoch = leader_epoch;
+                        }
+                    }
+                });
+            });
+        }
+    }
+
+    /// Keep a hello subscription open on the master and every replica
+    fn maintain_hello_links(&mut self, master: &MasterInstance) {
+        let mut wanted = vec![master.addr()];
+        wanted.extend(master.replicas.keys().cloned());
+
+        self.hello_links.retain(|addr, link| {
+            let keep = wanted.contains(addr) && !link.is_finished();
+            if !keep {
+                link.abort();
+            }
+            keep
+        });
+        for addr in wanted {
+            if !self.hello_links.contains_key(&addr) {
+                let link = tokio::spawn(hello_subscriber(self.state.clone(), addr.clone()));
+                self.hello_links.insert(addr, link);
+            }
+        }
+    }
+
+    /// Subjectively down: no valid PING reply for down-after-milliseconds
+    fn check_sdown(&self, now: Instant) {
+        let mut events = Vec::new();
+        self.state.with_master(&self.name, |m| {
+            let down_after = m.config.down_after;
+            let down = now.duration_since(m.last_ok_ping) > down_after;
+            if down != m.sdown {
+                m.sdown = down;
+                let kind = if down { "+sdown" } else { "-sdown" };
+                events.push((kind, format!("master {} {} {}", m.config.name, m.config.ip, m.config.port)));
+            }
+
+            let master_desc = format!("@ {} {} {}", m.config.name, m.config.ip, m.config.port);
+            for replica in m.replicas.values_mut() {
+                let down = now.duration_since(replica.last_ok_ping) > down_after;
+                if down != replica.sdown {
+                    replica.sdown = down;
+                    let kind = if down { "+sdown" } else { "-sdown" };
+                    events.push((
+                        kind,
+                        format!("slave {} {} {} {}", replica.addr(), replica.ip, replica.port, master_desc),
+                    ));
+                }
+            }
+        });
+        for (kind, message) in events {
+            self.state.event(kind, &message);
+        }
+    }
+
+    /// Objectively down: enough sentinels agree the master is down
+    fn check_odown(&self, now: Instant) {
+        let mut event = None;
+        self.state.with_master(&self.name, |m| {
+            let votes = m.down_votes(now, ASK_VALIDITY);
+            let odown = m.sdown && votes >= m.config.quorum;
+            if odown != m.odown {
+                m.odown = odown;
+                event = Some(if odown {
+                    ("+odown", format!("master {} {} {} #quorum {}/{}", m.config.name, m.config.ip, m.config.port, votes, m.config.quorum))
+                } else {
+                    ("-odown", format!("master {} {} {}", m.config.name, m.config.ip, m.config.port))
+                });
+            }
+        });
+        if let Some((kind, message)) = event {
+            self.state.event(kind, &message);
+        }
+    }
+
+    /// Point replicas that report the wrong master (e.g. an old master that
+    /// came back) at the current one
+    fn fix_replicas(&self, now: Instant) {
+        let mut reconf = Vec::new();
+        self.state.with_master(&self.name, |m| {
+            if m.sdown || m.failover.is_some() {
+                return;
+            }
+            let (master_ip, master_port) = (m.config.ip.clone(), m.config.port);
+            for replica in m.replicas.values_mut() {
+                if replica.sdown || replica.info_refreshed.is_none() {
+                    continue;
+                }
+                if !due(replica.last_reconf, RECONF_GRACE, now) {
+                    continue;
+                }
+                let kind = if replica.role == "master" {
+                    "+convert-to-slave"
+                } else if replica.master_host.as_deref() != Some(master_ip.as_str())
+                    || replica.master_port != Some(master_port)
+                {
+                    "+fix-slave-config"
+                } else {
+                    continue;
+                };
+                replica.last_reconf = Some(now);
+                reconf.push((kind, replica.addr(), master_ip.clone(), master_port));
+            }
+        });
+
+        for (kind, addr, ip, port) in reconf {
+            self.state.event(kind, &format!("slave {} @ {} {} {}", addr, self.name, ip, port));
+            tokio::spawn(async move {
+                let _ = send_command(&addr, &["REPLICAOF", &ip, &port.to_string()]).await;
+            });
+        }
+    }
+
+    /// Advance the failover state machine by at most one step
+    async fn failover_step(&mut self, now: Instant) {
+        let master = match self.state.master(&self.name) {
+            Some(master) => master,
+            None => return,
+        };
+        let my_id = self.state.my_id.clone();
+
+        let failover = match master.failover.clone() {
+            Some(failover) => failover,
+            None => {
+                let retry_allowed = master
+                    .failover_start
+                    .is_none_or(|t| now >= t && now.duration_since(t) >= master.config.failover_timeout * 2);
+                if master.force_failover || (master.odown && retry_allowed) {
+                    self.start_failover(master.force_failover, now);
+                }
+                return;
+            }
+        };
+
+        let desc = format!("master {} {} {}", master.config.name, master.config.ip, master.config.port);
+        match failover.state {
+            FailoverState::WaitStart => {
+                if master.election_winner(failover.epoch).as_deref() == Some(my_id.as_str()) {
+                    self.state.event("+elected-leader", &desc);
+                    self.set_failover_state(FailoverState::SelectReplica, now);
+                } else if now.duration_since(failover.started) > master.config.failover_timeout.min(ELECTION_TIMEOUT) {
+                    self.abort_failover("-failover-abort-not-elected", &desc);
+                }
+            }
+            FailoverState::SelectReplica => {
+                let replica = match master.select_replica() {
+                    Some(replica) => replica,
+                    None => {
+                        self.abort_failover("-failover-abort-no-good-slave", &desc);
+                        return;
+                    }
+                };
+                let addr = replica.addr();
+                self.state.event(
+                    "+selected-slave",
+                    &format!("slave {} {} {} @ {} {} {}", addr, replica.ip, replica.port, master.config.name, master.config.ip, master.config.port),
+                );
+                let _ = send_command(&addr, &["REPLICAOF", "NO", "ONE"]).await;
+                self.state.event("+failover-state-send-slaveof-noone", &format!("slave {} @ {}", addr, master.config.name));
+                self.set_failover_state(FailoverState::WaitPromotion { promoted: addr }, now);
+            }
+            FailoverState::WaitPromotion { promoted } => {
+                let is_master = master.replicas.get(&promoted).is_some_and(|r| r.role == "master");
+                if is_master {
+                    self.state.event("+promoted-slave", &format!("slave {} @ {}", promoted, master.config.name));
+                    self.set_failover_state(FailoverState::ReconfReplicas { promoted }, now);
+                } else if now.duration_since(failover.state_changed) > master.config.failover_timeout {
+                    self.abort_failover("-failover-abort-slave-timeout", &desc);
+                }
+            }
+            FailoverState::ReconfReplicas { promoted } => {
+                // The promoted replica may have been dropped meanwhile (SENTINEL
+                // RESET); give up and let a later failover pick another one
+                let new_master = match master.replicas.get(&promoted) {
+                    Some(replica) => replica.clone(),
+                    None => {
+                        self.abort_failover("-failover-abort-no-good-slave", &desc);
+                        return;
+                    }
+                };
+                match &self.reconf {
+                    None => {
+                        let reconf = reconfigure_replicas(self.state.clone(), master, new_master, failover.started);
+                        self.reconf = Some(tokio::spawn(reconf));
+                        return;
+                    }
+                    Some(reconf) if !reconf.is_finished() => return,
+                    Some(_) => self.reconf = None,
+                }
+
+                self.state.with_master(&self.name, |m| {
+                    m.switch_to(new_master.ip.clone(), new_master.port, failover.epoch);
+                });
+                self.state.event(
+                    "+switch-master",
+                    &format!("{} {} {} {} {}", master.config.name, master.config.ip, master.config.port, new_master.ip, new_master.port),
+                );
+                self.state.event("+failover-end", &format!("master {} {} {}", master.config.name, new_master.ip, new_master.port));
+                // Tell everyone about the new configuration right away
+                self.last_hello = None;
+                self.last_info = None;
+            }
+        }
+    }
+
+    fn start_failover(&mut self, forced: bool, now: Instant) {
+        let epoch = self.state.bump_epoch();
+        let my_id = self.state.my_id.clone();
+        let desc = self.state.with_master(&self.name, |m| {
+            m.force_failover = false;
+            m.failover = Some(Failover {
+                epoch,
+                state: if forced { FailoverState::SelectReplica } else { FailoverState::WaitStart },
+                started: now,
+                state_changed: now,
+                forced,
+            });
+            // Spread retries of competing sentinels
+            let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..1000));
+            m.failover_start = Some(now + jitter);
+            m.vote_leader(epoch, &my_id, &my_id);
+            format!("master {} {} {}", m.config.name, m.config.ip, m.config.port)
+        });
+
+        if let Some(desc) = desc {
+            self.state.event("+new-epoch", &epoch.to_string());
+            self.state.event("+try-failover", &desc);
+            // Ask for votes on the next tick
+            self.last_ask = None;
+        }
+    }
+
+    fn set_failover_state(&self, new_state: FailoverState, now: Instant) {
+        self.state.with_master(&self.name, |m| {
+            if let Some(failover) = m.failover.as_mut() {
+                failover.state = new_state;
+                failover.state_changed = now;
+            }
+        });
+    }
+
+    fn abort_failover(&mut self, kind: &str, desc: &str) {
+        if let Some(reconf) = self.reconf.take() {
+            reconf.abort();
+        }
+        self.state.with_master(&self.name, |m| m.failover = None);
+        self.state.event(kind, desc);
+    }
+}
+
+/// Send REPLICAOF <promoted> to the other replicas of `master`,
+/// `parallel-syncs` at a time, waiting for each batch to report its link up
+/// (bounded by the failover timeout). The replicas of a batch are contacted
+/// concurrently and every exchange is bounded by `LINK_TIMEOUT`, so a replica
+/// that hangs holds up neither the others nor the batch for longer.
+async fn reconfigure_replicas(
+    state: Arc<SentinelState>,
+    master: MasterInstance,
+    new_master: ReplicaInstance,
+    started: Instant,
+) {
+    let promoted = new_master.addr();
+    let new_port = new_master.port.to_string();
+    let others: Vec<String> = master
+        .replicas
+        .values()
+        .filter(|r| r.addr() != promoted && !r.sdown)
+        .map(|r| r.addr())
+        .collect();
+
+    for batch in others.chunks(master.config.parallel_syncs.max(1)) {
+        let sent = on_each(batch, |addr| {
+            let args = ["REPLICAOF".to_string(), new_master.ip.clone(), new_port.clone()];
+            async move {
+                let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+                send_command(&addr, &args).await.is_ok()
+            }
+        })
+        .await;
+        for (addr, _) in batch.iter().zip(sent).filter(|(_, sent)| *sent) {
+            state.event("+slave-reconf-sent", &format!("slave {} @ {}", addr, master.config.name));
+        }
+
+        while started.elapsed() < master.config.failover_timeout {
+            let reports = on_each(batch, |addr| async move { fetch_info(&addr).await }).await;
+            let synced = reports
+                .iter()
+                .flatten()
+                .filter(|report| report.master_link_up && report.master_port == Some(new_master.port))
+                .count();
+            if synced == batch.len() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+/// Run `f` for every address at once, returning the results in order (the
+/// default for a run that panicked)
+async fn on_each<T, F>(addrs: &[String], f: impl Fn(String) -> F) -> Vec<T>
+where
+    T: Default + Send + 'static,
+    F: std::future::Future<Output = T> + Send + 'static,
+{
+    let tasks: Vec<_> = addrs.iter().map(|addr| tokio::spawn(f(addr.clone()))).collect();
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.unwrap_or_default());
+    }
+    results
+}
+
+/// Update what we know about an instance from its INFO reply
+fn apply_info(state: &SentinelState, name: &str, addr: &str, report: InfoReport) {
+    let mut discovered = Vec::new();
+    state.with_master(name, |m| {
+        if m.addr() == addr {
+            m.role = report.role.clone();
+            if report.role == "master" {
+                for (ip, port) in &report.replicas {
+                    let replica_addr = format!("{}:{}", ip, port);
+                    if replica_addr != m.addr() && !m.replicas.contains_key(&replica_addr) {
+                        m.replicas.insert(
+                            replica_addr.clone(),
+                            super::state::ReplicaInstance::new(ip.clone(), *port),
+                        );
+                        discovered.push(format!("slave {} {} {} @ {} {} {}", replica_addr, ip, port, m.config.name, m.config.ip, m.config.port));
+                    }
+                }
+            }
+        } else if let Some(replica) = m.replicas.get_mut(addr) {
+            replica.role = report.role.clone();
+            replica.master_host = report.master_host.clone();
+            replica.master_port = report.master_port;
+            replica.master_link_up = report.master_link_up;
+            replica.repl_offset = report.repl_offset;
+            if let Some(priority) = report.priority {
+                replica.priority = priority;
+            }
+            replica.info_refreshed = Some(Instant::now());
+        }
+    });
+    for message in discovered {
+        state.event("+slave", &message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sentinel::config::{MasterConfig, SentinelConfig};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_hung_replica_does_not_hold_up_reconfiguration() {
+        // One replica reads REPLICAOF and never answers, the other answers
+        let hung = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hung_port = hung.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = hung.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            while socket.read(&mut buf).await.unwrap_or(0) > 0 {}
+        });
+        let healthy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy_port = healthy.local_addr().unwrap().port();
+        let (received, reconfigured) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = healthy.accept().await.unwrap();
+            let mut buf = [0u8; 128];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket.write_all(b"+OK\r\n").await.unwrap();
+            let _ = received.send(String::from_utf8_lossy(&buf[..n]).contains("REPLICAOF"));
+        });
+
+        let mut config = MasterConfig::new("mymaster".to_string(), "127.0.0.1".to_string(), 6379, 1);
+        config.parallel_syncs = 2;
+        config.failover_timeout = Duration::ZERO;
+        let mut master = MasterInstance::new(config);
+        for port in [hung_port, healthy_port] {
+            let replica = ReplicaInstance::new("127.0.0.1".to_string(), port);
+            master.replicas.insert(replica.addr(), replica);
+        }
+        let promoted = ReplicaInstance::new("127.0.0.1".to_string(), 6380);
+        let state = Arc::new(SentinelState::new(&SentinelConfig::default()));
+
+        let reconf = tokio::spawn(reconfigure_replicas(state, master, promoted, Instant::now()));
+        // Well before the hung replica's link times out
+        assert!(tokio::time::timeout(LINK_TIMEOUT / 2, reconfigured).await.unwrap().unwrap());
+        tokio::time::timeout(LINK_TIMEOUT * 3, reconf).await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_parse_master_info() {
+        let report = InfoReport::parse(
+            "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+             slave0:ip=127.0.0.1,port=7001,state=online,offset=10\r\n\
+             slave1:ip=127.0.0.1,port=7002,state=online,offset=10\r\n\
+             master_repl_offset:10\r\n",
+        );
+        assert_eq!(report.role, "master");
+        assert_eq!(
+            report.replicas,
+            vec![("127.0.0.1".to_string(), 7001), ("127.0.0.1".to_string(), 7002)]
+        );
+    }
+
+    #[test]
+    fn test_parse_replica_info() {
+        let report = InfoReport::parse(
+            "role:slave\r\nmaster_host:127.0.0.1\r\nmaster_port:6379\r\n\
+             master_link_status:up\r\nslave_repl_offset:42\r\nslave_priority:10\r\n",
+        );
+        assert_eq!(report.role, "slave");
+        assert_eq!(report.master_host.as_deref(), Some("127.0.0.1"));
+        assert_eq!(report.master_port, Some(6379));
+        assert!(report.master_link_up);
+        assert_eq!(report.repl_offset, 42);
+        assert_eq!(report.priority, Some(10));
+        assert!(report.replicas.is_empty());
+    }
+}
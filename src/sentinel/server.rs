@@ -0,0 +1,543 @@
+// Sentinel server
+// Accepts client connections and answers the SENTINEL command family
+
+use super::config::{MasterConfig, SentinelConfig};
+use super::monitor::spawn_monitor;
+use super::state::{MasterInstance, SentinelState};
+use crate::commands::pubsub_cmds;
+use crate::protocol::{RespParser, RespSerializer, RespValue};
+use crate::pubsub::{PubSub, Subscriber, SubscriptionState};
+use bytes::BytesMut;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Hello messages older than this no longer count a sentinel as reachable
+const PEER_VALIDITY: Duration = Duration::from_secs(10);
+
+pub struct SentinelServer {
+    config: SentinelConfig,
+    state: Arc<SentinelState>,
+}
+
+impl SentinelServer {
+    pub fn new(config: SentinelConfig) -> Self {
+        let state = Arc::new(SentinelState::new(&config));
+        Self { config, state }
+    }
+
+    pub fn state(&self) -> Arc<SentinelState> {
+        Arc::clone(&self.state)
+    }
+
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let addr = format!("{}:{}", self.config.bind, self.config.port);
+        let listener = TcpListener::bind(&addr).await?;
+        info!("Sentinel {} listening on {}", self.state.my_id, addr);
+
+        for name in self.state.master_names() {
+            if let Some(master) = self.state.master(&name) {
+                self.state.event(
+                    "+monitor",
+                    &format!("master {} {} {} quorum {}", name, master.config.ip, master.config.port, master.config.quorum),
+                );
+            }
+            spawn_monitor(self.state.clone(), name);
+        }
+
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let state = self.state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_client(state, socket).await {
+                    error!("Sentinel client {} error: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_client(state: Arc<SentinelState>, mut socket: TcpStream) -> anyhow::Result<()> {
+    let mut buffer = BytesMut::with_capacity(4096);
+    let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+    let mut subscriber = Subscriber::new(state.events.clone(), push_tx);
+    let mut subscriptions = SubscriptionState::new();
+
+    loop {
+        if let Some(len) = RespParser::check_complete(&buffer)? {
+            let frame = RespParser::parse(&buffer.split_to(len))?;
+            let args = match command_args(frame) {
+                Some(args) if !args.is_empty() => args,
+                _ => {
+                    let reply = RespValue::Error("ERR invalid command format".to_string());
+                    socket.write_all(&RespSerializer::serialize(&reply)).await?;
+                    continue;
+                }
+            };
+
+            let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+            let replies = match name.as_str() {
+                "SUBSCRIBE" | "UNSUBSCRIBE" | "PSUBSCRIBE" | "PUNSUBSCRIBE" => {
+                    let replies = subscription_command(&state.events, &mut subscriptions, &name, args[1..].to_vec()).await;
+                    subscriber.sync(&subscriptions);
+                    replies
+                }
+                _ => vec![sentinel_command(&state, &name, &args[1..])],
+            };
+            for reply in replies {
+                socket.write_all(&RespSerializer::serialize(&reply)).await?;
+            }
+            continue;
+        }
+
+        tokio::select! {
+            n = socket.read_buf(&mut buffer) => {
+                if n? == 0 {
+                    return Ok(());
+                }
+            }
+            Some(push) = push_rx.recv() => {
+                socket.write_all(&RespSerializer::serialize(&push)).await?;
+            }
+        }
+    }
+}
+
+fn command_args(frame: RespValue) -> Option<Vec<Vec<u8>>> {
+    match frame {
+        RespValue::Array(Some(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                RespValue::BulkString(Some(data)) => Some(data),
+                RespValue::SimpleString(s) => Some(s.into_bytes()),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+async fn subscription_command(
+    events: &Arc<PubSub>,
+    subscriptions: &mut SubscriptionState,
+    name: &str,
+    args: Vec<Vec<u8>>,
+) -> Vec<RespValue> {
+    match name {
+        "SUBSCRIBE" => pubsub_cmds::subscribe(events, subscriptions, args).await,
+        "UNSUBSCRIBE" => pubsub_cmds::unsubscribe(events, subscriptions, args).await,
+        "PSUBSCRIBE" => pubsub_cmds::psubscribe(events, subscriptions, args).await,
+        _ => pubsub_cmds::punsubscribe(events, subscriptions, args).await,
+    }
+}
+
+fn bulk(s: impl Into<String>) -> RespValue {
+    RespValue::BulkString(Some(s.into().into_bytes()))
+}
+
+fn arg_str(args: &[Vec<u8>], index: usize) -> String {
+    args.get(index)
+        .map(|a| String::from_utf8_lossy(a).to_string())
+        .unwrap_or_default()
+}
+
+fn wrong_args(command: &str) -> RespValue {
+    RespValue::Error(format!("ERR wrong number of arguments for '{}' command", command))
+}
+
+fn no_such_master() -> RespValue {
+    RespValue::Error("ERR No such master with that name".to_string())
+}
+
+/// Execute a non-subscription command
+fn sentinel_command(state: &Arc<SentinelState>, name: &str, args: &[Vec<u8>]) -> RespValue {
+    match name {
+        "PING" => RespValue::SimpleString("PONG".to_string()),
+        "ROLE" => RespValue::Array(Some(vec![
+            bulk("sentinel"),
+            RespValue::Array(Some(state.master_names().into_iter().map(bulk).collect())),
+        ])),
+        "INFO" => info(state),
+        "SENTINEL" => {
+            if args.is_empty() {
+                return wrong_args("sentinel");
+            }
+            sentinel_subcommand(state, &arg_str(args, 0).to_lowercase(), &args[1..])
+        }
+        _ => RespValue::Error(format!("ERR unknown command '{}'", name.to_lowercase())),
+    }
+}
+
+fn sentinel_subcommand(state: &Arc<SentinelState>, sub: &str, args: &[Vec<u8>]) -> RespValue {
+    match sub {
+        "myid" => bulk(state.my_id.clone()),
+        "masters" => RespValue::Array(Some(
+            state
+                .master_names()
+                .iter()
+                .filter_map(|name| state.master(name))
+                .map(|m| master_fields(&m))
+                .collect(),
+        )),
+        "master" => match state.master(&arg_str(args, 0)) {
+            Some(m) if args.len() == 1 => master_fields(&m),
+            _ if args.len() != 1 => wrong_args("sentinel master"),
+            _ => no_such_master(),
+        },
+        "replicas" | "slaves" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel replicas");
+            }
+            match state.master(&arg_str(args, 0)) {
+                Some(m) => {
+                    let mut replicas: Vec<_> = m.replicas.values().collect();
+                    replicas.sort_by_key(|r| r.addr());
+                    RespValue::Array(Some(replicas.into_iter().map(replica_fields).collect()))
+                }
+                None => no_such_master(),
+            }
+        }
+        "sentinels" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel sentinels");
+            }
+            match state.master(&arg_str(args, 0)) {
+                Some(m) => {
+                    let now = Instant::now();
+                    let mut peers: Vec<_> = m.sentinels.values().collect();
+                    peers.sort_by_key(|p| p.run_id.clone());
+                    RespValue::Array(Some(
+                        peers
+                            .into_iter()
+                            .map(|p| {
+                                fields(vec![
+                                    ("name", p.run_id.clone()),
+                                    ("ip", p.ip.clone()),
+                                    ("port", p.port.to_string()),
+                                    ("runid", p.run_id.clone()),
+                                    ("flags", "sentinel".to_string()),
+                                    ("last-hello-message", now.duration_since(p.last_hello).as_millis().to_string()),
+                                    ("voted-leader", p.leader.clone().unwrap_or_else(|| "?".to_string())),
+                                    ("voted-leader-epoch", p.leader_epoch.to_string()),
+                                ])
+                            })
+                            .collect(),
+                    ))
+                }
+                None => no_such_master(),
+            }
+        }
+        "get-master-addr-by-name" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel get-master-addr-by-name");
+            }
+            match state.master(&arg_str(args, 0)) {
+                Some(m) => RespValue::Array(Some(vec![bulk(m.config.ip.clone()), bulk(m.config.port.to_string())])),
+                None => RespValue::Array(None),
+            }
+        }
+        "is-master-down-by-addr" => is_master_down_by_addr(state, args),
+        "monitor" => {
+            if args.len() != 4 {
+                return wrong_args("sentinel monitor");
+            }
+            let port = match arg_str(args, 2).parse::<u16>() {
+                Ok(port) if port > 0 => port,
+                _ => return RespValue::Error("ERR Invalid port".to_string()),
+            };
+            let quorum = match arg_str(args, 3).parse::<usize>() {
+                Ok(q) if q > 0 => q,
+                _ => return RespValue::Error("ERR Quorum must be 1 or greater.".to_string()),
+            };
+            let name = arg_str(args, 0);
+            let config = MasterConfig::new(name.clone(), arg_str(args, 1), port, quorum);
+            match state.add_master(config) {
+                Ok(()) => {
+                    spawn_monitor(state.clone(), name);
+                    RespValue::SimpleString("OK".to_string())
+                }
+                Err(e) => RespValue::Error(e),
+            }
+        }
+        "remove" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel remove");
+            }
+            if state.remove_master(&arg_str(args, 0)) {
+                RespValue::SimpleString("OK".to_string())
+            } else {
+                no_such_master()
+            }
+        }
+        "set" => {
+            if args.len() < 3 || args.len().is_multiple_of(2) {
+                return wrong_args("sentinel set");
+            }
+            let result = state.with_master(&arg_str(args, 0), |m| {
+                for pair in args[1..].chunks(2) {
+                    let option = String::from_utf8_lossy(&pair[0]).to_string();
+                    let value = String::from_utf8_lossy(&pair[1]).to_string();
+                    m.config.set_option(&option, &value)?;
+                }
+                Ok::<_, anyhow::Error>(())
+            });
+            match result {
+                Some(Ok(())) => RespValue::SimpleString("OK".to_string()),
+                Some(Err(e)) => RespValue::Error(format!("ERR {}", e)),
+                None => no_such_master(),
+            }
+        }
+        "reset" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel reset");
+            }
+            let pattern = arg_str(args, 0);
+            let mut count = 0;
+            for name in state.master_names() {
+                if PubSub::match_pattern(&name, &pattern) {
+                    state.with_master(&name, |m| *m = MasterInstance::new(m.config.clone()));
+                    state.event("+reset-master", &format!("master {}", name));
+                    count += 1;
+                }
+            }
+            RespValue::Integer(count)
+        }
+        "failover" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel failover");
+            }
+            let result = state.with_master(&arg_str(args, 0), |m| {
+                if m.failover.is_some() {
+                    return Err("INPROG Failover already in progress");
+                }
+                if m.select_replica().is_none() {
+                    return Err("NOGOODSLAVE No suitable replica to promote");
+                }
+                m.force_failover = true;
+                Ok(())
+            });
+            match result {
+                Some(Ok(())) => RespValue::SimpleString("OK".to_string()),
+                Some(Err(e)) => RespValue::Error(e.to_string()),
+                None => no_such_master(),
+            }
+        }
+        "ckquorum" => {
+            if args.len() != 1 {
+                return wrong_args("sentinel ckquorum");
+            }
+            match state.master(&arg_str(args, 0)) {
+                Some(m) => {
+                    let now = Instant::now();
+                    let voters = m.sentinels.len() + 1;
+                    let usable = 1 + m
+                        .sentinels
+                        .values()
+                        .filter(|p| now.duration_since(p.last_hello) < PEER_VALIDITY)
+                        .count();
+                    if usable >= m.config.quorum && usable > voters / 2 {
+                        RespValue::SimpleString(format!(
+                            "OK {} usable Sentinels. Quorum and failover authorization can be reached",
+                            usable
+                        ))
+                    } else {
+                        RespValue::Error(format!(
+                            "NOQUORUM {} usable Sentinels. Not enough available Sentinels to reach the specified quorum or failover authorization",
+                            usable
+                        ))
+                    }
+                }
+                None => no_such_master(),
+            }
+        }
+        _ => RespValue::Error(format!("ERR Unknown sentinel subcommand '{}'", sub)),
+    }
+}
+
+/// SENTINEL is-master-down-by-addr <ip> <port> <current-epoch> <runid>
+///
+/// Reports whether we see the master at ip:port subjectively down. A run id
+/// other than `*` is a request for our vote in `current-epoch`.
+fn is_master_down_by_addr(state: &Arc<SentinelState>, args: &[Vec<u8>]) -> RespValue {
+    if args.len() != 4 {
+        return wrong_args("sentinel is-master-down-by-addr");
+    }
+    let ip = arg_str(args, 0);
+    let (port, epoch) = match (arg_str(args, 1).parse::<u16>(), arg_str(args, 2).parse::<u64>()) {
+        (Ok(port), Ok(epoch)) => (port, epoch),
+        _ => return RespValue::Error("ERR value is not an integer or out of range".to_string()),
+    };
+    let run_id = arg_str(args, 3);
+
+    let name = state.master_by_addr(&ip, port);
+    if run_id != "*" {
+        state.observe_epoch(epoch);
+    }
+    let my_id = state.my_id.clone();
+    let reply = name.and_then(|name| {
+        state.with_master(&name, |m| {
+            let (leader, leader_epoch) = if run_id != "*" {
+                m.vote_leader(epoch, &run_id, &my_id)
+            } else {
+                (None, 0)
+            };
+            (m.sdown, leader, leader_epoch)
+        })
+    });
+
+    let (down, leader, leader_epoch) = reply.unwrap_or((false, None, 0));
+    RespValue::Array(Some(vec![
+        RespValue::Integer(i64::from(down)),
+        bulk(leader.unwrap_or_else(|| "*".to_string())),
+        RespValue::Integer(leader_epoch as i64),
+    ]))
+}
+
+fn fields(pairs: Vec<(&str, String)>) -> RespValue {
+    RespValue::Array(Some(
+        pairs
+            .into_iter()
+            .flat_map(|(k, v)| [bulk(k), bulk(v)])
+            .collect(),
+    ))
+}
+
+fn master_fields(m: &MasterInstance) -> RespValue {
+    fields(vec![
+        ("name", m.config.name.clone()),
+        ("ip", m.config.ip.clone()),
+        ("port", m.config.port.to_string()),
+        ("flags", m.flags()),
+        ("role-reported", m.role.clone()),
+        ("num-slaves", m.replicas.len().to_string()),
+        ("num-other-sentinels", m.sentinels.len().to_string()),
+        ("quorum", m.config.quorum.to_string()),
+        ("config-epoch", m.config_epoch.to_string()),
+        ("down-after-milliseconds", m.config.down_after.as_millis().to_string()),
+        ("failover-timeout", m.config.failover_timeout.as_millis().to_string()),
+        ("parallel-syncs", m.config.parallel_syncs.to_string()),
+    ])
+}
+
+fn replica_fields(r: &super::state::ReplicaInstance) -> RespValue {
+    let flags = if r.sdown { "slave,s_down" } else { "slave" };
+    fields(vec![
+        ("name", r.addr()),
+        ("ip", r.ip.clone()),
+        ("port", r.port.to_string()),
+        ("flags", flags.to_string()),
+        ("role-reported", r.role.clone()),
+        ("master-link-status", if r.master_link_up { "ok" } else { "err" }.to_string()),
+        ("master-host", r.master_host.clone().unwrap_or_else(|| "?".to_string())),
+        ("master-port", r.master_port.map(|p| p.to_string()).unwrap_or_else(|| "0".to_string())),
+        ("slave-priority", r.priority.to_string()),
+        ("slave-repl-offset", r.repl_offset.to_string()),
+    ])
+}
+
+fn info(state: &Arc<SentinelState>) -> RespValue {
+    let mut lines = vec![
+        "# Server".to_string(),
+        "redis_version:7.0.0-rust".to_string(),
+        "redis_mode:sentinel".to_string(),
+        format!("run_id:{}", state.my_id),
+        format!("tcp_port:{}", state.port),
+        String::new(),
+        "# Sentinel".to_string(),
+    ];
+    let names = state.master_names();
+    lines.push(format!("sentinel_masters:{}", names.len()));
+    lines.push(format!("sentinel_current_epoch:{}", state.current_epoch()));
+    for (i, name) in names.iter().enumerate() {
+        if let Some(m) = state.master(name) {
+            let status = if m.odown { "odown" } else if m.sdown { "sdown" } else { "ok" };
+            lines.push(format!(
+                "master{}:name={},status={},address={},slaves={},sentinels={}",
+                i,
+                name,
+                status,
+                m.addr(),
+                m.replicas.len(),
+                m.sentinels.len() + 1
+            ));
+        }
+    }
+    lines.push(String::new());
+    RespValue::BulkString(Some(lines.join("\r\n").into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> Arc<SentinelState> {
+        let config = SentinelConfig::parse("sentinel monitor mymaster 127.0.0.1 6379 2").unwrap();
+        Arc::new(SentinelState::new(&config))
+    }
+
+    fn cmd(state: &Arc<SentinelState>, parts: &[&str]) -> RespValue {
+        let args: Vec<Vec<u8>> = parts[1..].iter().map(|p| p.as_bytes().to_vec()).collect();
+        sentinel_command(state, &parts[0].to_uppercase(), &args)
+    }
+
+    #[test]
+    fn test_get_master_addr_by_name() {
+        let state = state();
+        assert_eq!(
+            cmd(&state, &["SENTINEL", "get-master-addr-by-name", "mymaster"]),
+            RespValue::Array(Some(vec![bulk("127.0.0.1"), bulk("6379")]))
+        );
+        assert_eq!(
+            cmd(&state, &["SENTINEL", "get-master-addr-by-name", "other"]),
+            RespValue::Array(None)
+        );
+    }
+
+    #[test]
+    fn test_is_master_down_by_addr_votes() {
+        let state = state();
+        let reply = cmd(&state, &["SENTINEL", "is-master-down-by-addr", "127.0.0.1", "6379", "3", "abc"]);
+        assert_eq!(
+            reply,
+            RespValue::Array(Some(vec![RespValue::Integer(0), bulk("abc"), RespValue::Integer(3)]))
+        );
+        assert_eq!(state.current_epoch(), 3);
+
+        // Same epoch: the earlier vote stands
+        let reply = cmd(&state, &["SENTINEL", "is-master-down-by-addr", "127.0.0.1", "6379", "3", "xyz"]);
+        assert_eq!(reply.as_array().unwrap()[1], bulk("abc"));
+
+        // Plain query does not vote
+        let reply = cmd(&state, &["SENTINEL", "is-master-down-by-addr", "127.0.0.1", "6379", "0", "*"]);
+        assert_eq!(reply.as_array().unwrap()[1], bulk("*"));
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let state = state();
+        assert_eq!(
+            cmd(&state, &["SENTINEL", "set", "mymaster", "down-after-milliseconds", "1000"]),
+            RespValue::SimpleString("OK".to_string())
+        );
+        assert_eq!(state.master("mymaster").unwrap().config.down_after, Duration::from_millis(1000));
+        assert!(matches!(
+            cmd(&state, &["SENTINEL", "set", "mymaster", "bogus", "1"]),
+            RespValue::Error(_)
+        ));
+
+        assert_eq!(cmd(&state, &["SENTINEL", "remove", "mymaster"]), RespValue::SimpleString("OK".to_string()));
+        assert!(matches!(cmd(&state, &["SENTINEL", "master", "mymaster"]), RespValue::Error(_)));
+    }
+
+    #[test]
+    fn test_failover_requires_replica() {
+        let state = state();
+        assert_eq!(
+            cmd(&state, &["SENTINEL", "failover", "mymaster"]),
+            RespValue::Error("NOGOODSLAVE No suitable replica to promote".to_string())
+        );
+    }
+}
@@ -0,0 +1,589 @@
+// Sentinel state
+// What a sentinel knows about the masters it monitors, their replicas and
+// the other sentinels watching them
+
+use super::config::{MasterConfig, SentinelConfig};
+use crate::pubsub::PubSub;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Channel used by sentinels to announce themselves on monitored instances
+pub const HELLO_CHANNEL: &str = "__sentinel__:hello";
+
+/// A replica discovered through the master's INFO
+#[derive(Debug, Clone)]
+pub struct ReplicaInstance {
+    pub ip: String,
+    pub port: u16,
+    pub last_ok_ping: Instant,
+    pub sdown: bool,
+    /// Role reported by the replica's own INFO
+    pub role: String,
+    pub master_host: Option<String>,
+    pub master_port: Option<u16>,
+    pub master_link_up: bool,
+    pub priority: u64,
+    pub repl_offset: u64,
+    /// When this replica last reported its INFO
+    pub info_refreshed: Option<Instant>,
+    /// Last time we sent REPLICAOF to it
+    pub last_reconf: Option<Instant>,
+}
+
+impl ReplicaInstance {
+    pub fn new(ip: String, port: u16) -> Self {
+        Self {
+            ip,
+            port,
+            last_ok_ping: Instant::now(),
+            sdown: false,
+            role: "slave".to_string(),
+            master_host: None,
+            master_port: None,
+            master_link_up: false,
+            priority: 100,
+            repl_offset: 0,
+            info_refreshed: None,
+            last_reconf: None,
+        }
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+}
+
+/// Another sentinel monitoring the same master
+#[derive(Debug, Clone)]
+pub struct SentinelPeer {
+    pub run_id: String,
+    pub ip: String,
+    pub port: u16,
+    pub last_hello: Instant,
+    /// Its last answer to is-master-down-by-addr
+    pub master_down: bool,
+    pub master_down_reply: Option<Instant>,
+    /// The leader it voted for, and in which epoch
+    pub leader: Option<String>,
+    pub leader_epoch: u64,
+}
+
+impl SentinelPeer {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+}
+
+/// Steps of a failover led by this sentinel
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailoverState {
+    /// Waiting to be elected leader for the failover epoch
+    WaitStart,
+    /// Elected; pick the replica to promote
+    SelectReplica,
+    /// REPLICAOF NO ONE sent; waiting for the replica to report role:master
+    WaitPromotion { promoted: String },
+    /// Promotion confirmed; point the remaining replicas at the new master
+    ReconfReplicas { promoted: String },
+}
+
+/// A failover in progress
+#[derive(Debug, Clone)]
+pub struct Failover {
+    pub epoch: u64,
+    pub state: FailoverState,
+    pub started: Instant,
+    pub state_changed: Instant,
+    /// Started by SENTINEL FAILOVER, without agreement from other sentinels
+    pub forced: bool,
+}
+
+/// A monitored master
+#[derive(Debug, Clone)]
+pub struct MasterInstance {
+    pub config: MasterConfig,
+    pub config_epoch: u64,
+    pub last_ok_ping: Instant,
+    pub sdown: bool,
+    pub odown: bool,
+    /// Role reported by the master's INFO
+    pub role: String,
+    pub replicas: HashMap<String, ReplicaInstance>,
+    /// Other sentinels, keyed by run id
+    pub sentinels: HashMap<String, SentinelPeer>,
+    /// Who we voted for as failover leader, and in which epoch
+    pub leader: Option<String>,
+    pub leader_epoch: u64,
+    pub failover: Option<Failover>,
+    /// Last failover attempt (ours or one we voted for); new attempts are
+    /// spaced by twice the failover timeout
+    pub failover_start: Option<Instant>,
+    /// Set by SENTINEL FAILOVER
+    pub force_failover: bool,
+}
+
+impl MasterInstance {
+    pub fn new(config: MasterConfig) -> Self {
+        Self {
+            config,
+            config_epoch: 0,
+            last_ok_ping: Instant::now(),
+            sdown: false,
+            odown: false,
+            role: "master".to_string(),
+            replicas: HashMap::new(),
+            sentinels: HashMap::new(),
+            leader: None,
+            leader_epoch: 0,
+            failover: None,
+            failover_start: None,
+            force_failover: false,
+        }
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.config.ip, self.config.port)
+    }
+
+    /// Flags as shown by SENTINEL MASTERS
+    pub fn flags(&self) -> String {
+        let mut flags = vec!["master"];
+        if self.sdown {
+            flags.push("s_down");
+        }
+        if self.odown {
+            flags.push("o_down");
+        }
+        if self.failover.is_some() {
+            flags.push("failover_in_progress");
+        }
+        flags.join(",")
+    }
+
+    /// Number of sentinels (including us) that currently agree the master
+    /// is down
+    pub fn down_votes(&self, now: Instant, validity: Duration) -> usize {
+        let peers = self
+            .sentinels
+            .values()
+            .filter(|p| p.master_down && p.master_down_reply.is_some_and(|t| now.duration_since(t) < validity))
+            .count();
+        peers + usize::from(self.sdown)
+    }
+
+    /// Record a vote for `run_id` as failover leader in `epoch`. We vote at
+    /// most once per epoch; the reply is whoever we voted for last.
+    pub fn vote_leader(&mut self, epoch: u64, run_id: &str, my_id: &str) -> (Option<String>, u64) {
+        if epoch > self.leader_epoch {
+            self.leader = Some(run_id.to_string());
+            self.leader_epoch = epoch;
+            if run_id != my_id {
+                // Someone else is trying; do not start our own attempt soon
+                self.failover_start = Some(Instant::now());
+            }
+            info!(
+                "+vote-for-leader {} {} (master {})",
+                run_id, epoch, self.config.name
+            );
+        }
+        (self.leader.clone(), self.leader_epoch)
+    }
+
+    /// The run id holding a majority of votes for `epoch`, if any
+    pub fn election_winner(&self, epoch: u64) -> Option<String> {
+        let mut votes: HashMap<&str, usize> = HashMap::new();
+        if self.leader_epoch == epoch {
+            if let Some(leader) = &self.leader {
+                *votes.entry(leader.as_str()).or_default() += 1;
+            }
+        }
+        for peer in self.sentinels.values() {
+            if peer.leader_epoch == epoch {
+                if let Some(leader) = &peer.leader {
+                    *votes.entry(leader.as_str()).or_default() += 1;
+                }
+            }
+        }
+
+        let voters = self.sentinels.len() + 1;
+        let needed = self.config.quorum.max(voters / 2 + 1);
+        votes
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .filter(|(_, count)| *count >= needed)
+            .map(|(leader, _)| leader.to_string())
+    }
+
+    /// Pick the replica to promote: skip replicas that are down, disconnected
+    /// from the master for long, or have priority 0; then prefer the lowest
+    /// priority, the largest replication offset and the lowest address
+    pub fn select_replica(&self) -> Option<ReplicaInstance> {
+        let mut candidates: Vec<&ReplicaInstance> = self
+            .replicas
+            .values()
+            .filter(|r| !r.sdown && r.priority != 0 && r.info_refreshed.is_some())
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| b.repl_offset.cmp(&a.repl_offset))
+                .then_with(|| a.addr().cmp(&b.addr()))
+        });
+        candidates.first().map(|r| (*r).clone())
+    }
+
+    /// Point this entry at a new master address. The old master is kept as
+    /// a replica so that it gets reconfigured if it comes back.
+    pub fn switch_to(&mut self, ip: String, port: u16, config_epoch: u64) {
+        let old = (self.config.ip.clone(), self.config.port);
+        self.config.ip = ip;
+        self.config.port = port;
+        self.config_epoch = config_epoch;
+
+        let now = Instant::now();
+        self.replicas.remove(&self.addr());
+        let mut old_master = ReplicaInstance::new(old.0, old.1);
+        old_master.last_reconf = Some(now);
+        self.replicas.insert(old_master.addr(), old_master);
+        for replica in self.replicas.values_mut() {
+            replica.last_ok_ping = now;
+            replica.sdown = false;
+            replica.info_refreshed = None;
+            replica.last_reconf = Some(now);
+        }
+
+        self.last_ok_ping = now;
+        self.sdown = false;
+        self.odown = false;
+        self.role = "master".to_string();
+        self.failover = None;
+        for peer in self.sentinels.values_mut() {
+            peer.master_down = false;
+        }
+    }
+}
+
+/// A parsed hello message:
+/// `ip,port,runid,current_epoch,master_name,master_ip,master_port,master_config_epoch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub ip: String,
+    pub port: u16,
+    pub run_id: String,
+    pub current_epoch: u64,
+    pub master_name: String,
+    pub master_ip: String,
+    pub master_port: u16,
+    pub master_config_epoch: u64,
+}
+
+impl Hello {
+    pub fn parse(message: &str) -> Option<Self> {
+        let parts: Vec<&str> = message.split(',').collect();
+        if parts.len() != 8 {
+            return None;
+        }
+        Some(Self {
+            ip: parts[0].to_string(),
+            port: parts[1].parse().ok()?,
+            run_id: parts[2].to_string(),
+            current_epoch: parts[3].parse().ok()?,
+            master_name: parts[4].to_string(),
+            master_ip: parts[5].to_string(),
+            master_port: parts[6].parse().ok()?,
+            master_config_epoch: parts[7].parse().ok()?,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{}",
+            self.ip,
+            self.port,
+            self.run_id,
+            self.current_epoch,
+            self.master_name,
+            self.master_ip,
+            self.master_port,
+            self.master_config_epoch
+        )
+    }
+}
+
+/// Shared state of a sentinel process
+pub struct SentinelState {
+    pub my_id: String,
+    pub announce_ip: String,
+    pub port: u16,
+    current_epoch: AtomicU64,
+    masters: RwLock<HashMap<String, MasterInstance>>,
+    /// Event channels (+sdown, +odown, +switch-master, ...) for subscribers
+    pub events: Arc<PubSub>,
+}
+
+impl SentinelState {
+    pub fn new(config: &SentinelConfig) -> Self {
+        let masters = config
+            .masters
+            .iter()
+            .map(|m| (m.name.clone(), MasterInstance::new(m.clone())))
+            .collect();
+        Self {
+            my_id: generate_run_id(),
+            announce_ip: config.announce_ip(),
+            port: config.port,
+            current_epoch: AtomicU64::new(0),
+            masters: RwLock::new(masters),
+            events: Arc::new(PubSub::new()),
+        }
+    }
+
+    pub fn current_epoch(&self) -> u64 {
+        self.current_epoch.load(Ordering::SeqCst)
+    }
+
+    /// Start a new epoch and return it
+    pub fn bump_epoch(&self) -> u64 {
+        self.current_epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Adopt a larger epoch seen from another sentinel
+    pub fn observe_epoch(&self, epoch: u64) {
+        let previous = self.current_epoch.fetch_max(epoch, Ordering::SeqCst);
+        if epoch > previous {
+            self.event("+new-epoch", &epoch.to_string());
+        }
+    }
+
+    pub fn master_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.masters.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Snapshot of a master entry
+    pub fn master(&self, name: &str) -> Option<MasterInstance> {
+        self.masters.read().unwrap().get(name).cloned()
+    }
+
+    /// Run `f` on a master entry under the write lock
+    pub fn with_master<R>(&self, name: &str, f: impl FnOnce(&mut MasterInstance) -> R) -> Option<R> {
+        self.masters.write().unwrap().get_mut(name).map(f)
+    }
+
+    /// Find the master currently at `ip:port`
+    pub fn master_by_addr(&self, ip: &str, port: u16) -> Option<String> {
+        self.masters
+            .read()
+            .unwrap()
+            .values()
+            .find(|m| m.config.ip == ip && m.config.port == port)
+            .map(|m| m.config.name.clone())
+    }
+
+    pub fn add_master(&self, config: MasterConfig) -> Result<(), String> {
+        let mut masters = self.masters.write().unwrap();
+        if masters.contains_key(&config.name) {
+            return Err("ERR Duplicated master name".to_string());
+        }
+        self.event("+monitor", &format!("master {} {} {} quorum {}", config.name, config.ip, config.port, config.quorum));
+        masters.insert(config.name.clone(), MasterInstance::new(config));
+        Ok(())
+    }
+
+    pub fn remove_master(&self, name: &str) -> bool {
+        let removed = self.masters.write().unwrap().remove(name).is_some();
+        if removed {
+            self.event("-monitor", &format!("master {}", name));
+        }
+        removed
+    }
+
+    /// Handle a hello message received on a monitored instance
+    pub fn process_hello(&self, message: &str) {
+        let hello = match Hello::parse(message) {
+            Some(hello) => hello,
+            None => return,
+        };
+        if hello.run_id == self.my_id {
+            return;
+        }
+        self.observe_epoch(hello.current_epoch);
+
+        let mut switched = None;
+        self.with_master(&hello.master_name, |master| {
+            let now = Instant::now();
+            let peer = master
+                .sentinels
+                .entry(hello.run_id.clone())
+                .or_insert_with(|| SentinelPeer {
+                    run_id: hello.run_id.clone(),
+                    ip: hello.ip.clone(),
+                    port: hello.port,
+                    last_hello: now,
+                    master_down: false,
+                    master_down_reply: None,
+                    leader: None,
+                    leader_epoch: 0,
+                });
+            peer.ip = hello.ip.clone();
+            peer.port = hello.port;
+            peer.last_hello = now;
+
+            // A newer configuration for this master wins
+            if hello.master_config_epoch > master.config_epoch {
+                let old_addr = master.addr();
+                if hello.master_ip != master.config.ip || hello.master_port != master.config.port {
+                    master.switch_to(hello.master_ip.clone(), hello.master_port, hello.master_config_epoch);
+                    switched = Some(old_addr);
+                } else {
+                    master.config_epoch = hello.master_config_epoch;
+                }
+            }
+        });
+
+        if let Some(old_addr) = switched {
+            self.event(
+                "+switch-master",
+                &format!(
+                    "{} {} {} {}",
+                    hello.master_name,
+                    old_addr.replace(':', " "),
+                    hello.master_ip,
+                    hello.master_port
+                ),
+            );
+        }
+    }
+
+    /// Hello message announcing our view of `master`
+    pub fn hello_for(&self, master: &MasterInstance) -> String {
+        Hello {
+            ip: self.announce_ip.clone(),
+            port: self.port,
+            run_id: self.my_id.clone(),
+            current_epoch: self.current_epoch(),
+            master_name: master.config.name.clone(),
+            master_ip: master.config.ip.clone(),
+            master_port: master.config.port,
+            master_config_epoch: master.config_epoch,
+        }
+        .encode()
+    }
+
+    /// Log an event and publish it on the channel named after its type
+    pub fn event(&self, kind: &str, message: &str) {
+        info!("{} {}", kind, message);
+        self.events.publish(kind, message.as_bytes().to_vec());
+    }
+}
+
+/// Generate a 40 character run id
+fn generate_run_id() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..40).map(|_| format!("{:x}", rng.gen::<u8>() % 16)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master() -> MasterInstance {
+        MasterInstance::new(MasterConfig::new("mymaster".to_string(), "127.0.0.1".to_string(), 6379, 2))
+    }
+
+    fn replica(port: u16, priority: u64, offset: u64) -> ReplicaInstance {
+        let mut replica = ReplicaInstance::new("127.0.0.1".to_string(), port);
+        replica.priority = priority;
+        replica.repl_offset = offset;
+        replica.info_refreshed = Some(Instant::now());
+        replica
+    }
+
+    fn peer(run_id: &str) -> SentinelPeer {
+        SentinelPeer {
+            run_id: run_id.to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 26379,
+            last_hello: Instant::now(),
+            master_down: false,
+            master_down_reply: None,
+            leader: None,
+            leader_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_hello_roundtrip() {
+        let text = "127.0.0.1,26379,abc,3,mymaster,127.0.0.1,6380,2";
+        let hello = Hello::parse(text).unwrap();
+        assert_eq!(hello.master_port, 6380);
+        assert_eq!(hello.master_config_epoch, 2);
+        assert_eq!(hello.encode(), text);
+        assert!(Hello::parse("a,b,c").is_none());
+    }
+
+    #[test]
+    fn test_select_replica() {
+        let mut m = master();
+        for r in [replica(7001, 100, 10), replica(7002, 100, 50), replica(7003, 0, 99), replica(7004, 10, 1)] {
+            m.replicas.insert(r.addr(), r);
+        }
+        // Lowest non-zero priority wins
+        assert_eq!(m.select_replica().unwrap().port, 7004);
+
+        // Then the largest offset
+        m.replicas.get_mut("127.0.0.1:7004").unwrap().sdown = true;
+        assert_eq!(m.select_replica().unwrap().port, 7002);
+
+        m.replicas.retain(|_, r| r.port == 7003 || r.port == 7004);
+        assert!(m.select_replica().is_none());
+    }
+
+    #[test]
+    fn test_vote_once_per_epoch() {
+        let mut m = master();
+        assert_eq!(m.vote_leader(1, "a", "me"), (Some("a".to_string()), 1));
+        // Already voted in epoch 1
+        assert_eq!(m.vote_leader(1, "b", "me"), (Some("a".to_string()), 1));
+        assert_eq!(m.vote_leader(2, "b", "me"), (Some("b".to_string()), 2));
+    }
+
+    #[test]
+    fn test_election_winner_needs_majority() {
+        let mut m = master();
+        m.sentinels.insert("a".to_string(), peer("a"));
+        m.sentinels.insert("b".to_string(), peer("b"));
+        m.vote_leader(1, "me", "me");
+        assert_eq!(m.election_winner(1), None);
+
+        let a = m.sentinels.get_mut("a").unwrap();
+        a.leader = Some("me".to_string());
+        a.leader_epoch = 1;
+        assert_eq!(m.election_winner(1), Some("me".to_string()));
+        // Votes from another epoch do not count
+        assert_eq!(m.election_winner(2), None);
+    }
+
+    #[test]
+    fn test_process_hello_switches_master() {
+        let config = SentinelConfig::parse("sentinel monitor mymaster 127.0.0.1 6379 2").unwrap();
+        let state = SentinelState::new(&config);
+
+        state.process_hello("127.0.0.1,26380,other,5,mymaster,127.0.0.1,6380,5");
+        let m = state.master("mymaster").unwrap();
+        assert_eq!(m.config.port, 6380);
+        assert_eq!(m.config_epoch, 5);
+        assert!(m.sentinels.contains_key("other"));
+        assert!(m.replicas.contains_key("127.0.0.1:6379"));
+        assert_eq!(state.current_epoch(), 5);
+
+        // Older configuration is ignored
+        state.process_hello("127.0.0.1,26380,other,5,mymaster,127.0.0.1,6379,4");
+        assert_eq!(state.master("mymaster").unwrap().config.port, 6380);
+    }
+}
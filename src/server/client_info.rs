@@ -1,13 +1,32 @@
 // Client connection tracking and management
 
+use super::tracking::Tracking;
 use dashmap::DashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
 
 /// Global client ID counter
 static CLIENT_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
 
+/// Buffer sizes of a connection, as of its last command
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientMemory {
+    /// Bytes received but not yet parsed
+    pub qbuf: usize,
+    /// Free space in the query buffer
+    pub qbuf_free: usize,
+    /// Bytes in the output buffer
+    pub obl: usize,
+    /// Pushed frames waiting to be written
+    pub oll: usize,
+    /// Memory used by pending output
+    pub omem: usize,
+    /// Memory used by the connection's buffers
+    pub tot_mem: usize,
+}
+
 /// Client connection information
 #[derive(Debug, Clone)]
 pub struct ClientInfo {
@@ -15,10 +34,17 @@ pub struct ClientInfo {
     pub id: u64,
     /// Client address (IP:port)
     pub addr: String,
+    /// Local address the client connected to
+    pub laddr: String,
     /// File descriptor (socket ID)
     pub fd: u64,
     /// Client name (set by CLIENT SETNAME)
     pub name: Option<String>,
+    /// Authenticated user
+    pub user: String,
+    /// Client library, from CLIENT SETINFO
+    pub lib_name: Option<String>,
+    pub lib_ver: Option<String>,
     /// Connection age in seconds
     pub age: u64,
     /// Idle time in seconds
@@ -27,10 +53,24 @@ pub struct ClientInfo {
     pub db: usize,
     /// Flags (N=normal, M=master, S=slave, etc.)
     pub flags: String,
+    /// CLIENT NO-EVICT
+    pub no_evict: bool,
+    /// CLIENT NO-TOUCH
+    pub no_touch: bool,
+    /// Protocol version chosen with HELLO
+    pub resp: u8,
     /// Number of subscriptions
     pub sub: usize,
     /// Number of pattern subscriptions
     pub psub: usize,
+    /// Number of shard channel subscriptions
+    pub ssub: usize,
+    /// Commands queued in MULTI, -1 outside a transaction
+    pub multi: i64,
+    /// Buffer sizes
+    pub memory: ClientMemory,
+    /// CLIENT TRACKING redirect (see CLIENT GETREDIR), filled in when listed
+    pub redir: i64,
     /// Last command executed
     pub cmd: String,
     /// Connection creation timestamp
@@ -51,14 +91,25 @@ impl ClientInfo {
         Self {
             id,
             addr,
+            laddr: String::new(),
             fd,
             name: None,
+            user: "default".to_string(),
+            lib_name: None,
+            lib_ver: None,
             age: 0,
             idle: 0,
             db: 0,
             flags: "N".to_string(), // Normal client
+            no_evict: false,
+            no_touch: false,
+            resp: 2,
             sub: 0,
             psub: 0,
+            ssub: 0,
+            multi: -1,
+            memory: ClientMemory::default(),
+            redir: -1,
             cmd: "".to_string(),
             created_at: now,
             last_activity: now,
@@ -85,29 +136,150 @@ impl ClientInfo {
         self.db = db_index;
     }
 
+    /// Whether the client is subscribed to anything
+    pub fn is_pubsub(&self) -> bool {
+        self.sub + self.psub + self.ssub > 0
+    }
+
+    /// CLIENT LIST flags: the connection's role plus its current modes
+    fn list_flags(&self) -> String {
+        let mut flags: String = self.flags.chars().filter(|&c| c != 'N').collect();
+        if self.is_pubsub() {
+            flags.push('P');
+        }
+        if self.multi >= 0 {
+            flags.push('x');
+        }
+        if self.redir >= 0 {
+            flags.push('t');
+        }
+        if self.no_evict {
+            flags.push('e');
+        }
+        if self.no_touch {
+            flags.push('T');
+        }
+        if flags.is_empty() {
+            flags.push('N');
+        }
+        flags
+    }
+
     /// Format as CLIENT LIST entry
     pub fn to_list_entry(&self) -> String {
         format!(
-            "id={} addr={} fd={} name={} age={} idle={} flags={} db={} sub={} psub={} multi=-1 qbuf=0 qbuf-free=0 obl=0 oll=0 omem=0 events=r cmd={}",
+            "id={} addr={} laddr={} fd={} name={} age={} idle={} flags={} db={} sub={} psub={} ssub={} multi={} qbuf={} qbuf-free={} obl={} oll={} omem={} tot-mem={} events=r cmd={} user={} redir={} resp={} lib-name={} lib-ver={}",
             self.id,
             self.addr,
+            self.laddr,
             self.fd,
             self.name.as_deref().unwrap_or(""),
             self.age,
             self.idle,
-            self.flags,
+            self.list_flags(),
             self.db,
             self.sub,
             self.psub,
-            self.cmd
+            self.ssub,
+            self.multi,
+            self.memory.qbuf,
+            self.memory.qbuf_free,
+            self.memory.obl,
+            self.memory.oll,
+            self.memory.omem,
+            self.memory.tot_mem,
+            self.cmd.to_lowercase(),
+            self.user,
+            self.redir,
+            self.resp,
+            self.lib_name.as_deref().unwrap_or(""),
+            self.lib_ver.as_deref().unwrap_or("")
         )
     }
 }
 
+/// Client types of CLIENT KILL TYPE and CLIENT LIST TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientType {
+    Normal,
+    Master,
+    Replica,
+    PubSub,
+}
+
+impl ClientType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "normal" => Some(Self::Normal),
+            "master" => Some(Self::Master),
+            "replica" | "slave" => Some(Self::Replica),
+            "pubsub" => Some(Self::PubSub),
+            _ => None,
+        }
+    }
+
+    fn of(client: &ClientInfo) -> Self {
+        if client.flags.contains('M') {
+            Self::Master
+        } else if client.flags.contains('S') {
+            Self::Replica
+        } else if client.is_pubsub() {
+            Self::PubSub
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Filters of CLIENT KILL; a client must match all that are set
+#[derive(Debug, Clone)]
+pub struct KillFilter {
+    pub id: Option<u64>,
+    pub addr: Option<String>,
+    pub laddr: Option<String>,
+    pub user: Option<String>,
+    pub kind: Option<ClientType>,
+    /// Spare the client sending the command (the default)
+    pub skip_me: bool,
+    /// Only clients connected longer than this many seconds
+    pub max_age: Option<u64>,
+}
+
+impl Default for KillFilter {
+    fn default() -> Self {
+        Self { id: None, addr: None, laddr: None, user: None, kind: None, skip_me: true, max_age: None }
+    }
+}
+
+impl KillFilter {
+    fn matches(&self, client: &ClientInfo, caller: u64) -> bool {
+        (!self.skip_me || client.id != caller)
+            && self.id.is_none_or(|id| client.id == id)
+            && self.addr.as_ref().is_none_or(|addr| client.addr == *addr)
+            && self.laddr.as_ref().is_none_or(|laddr| client.laddr == *laddr)
+            && self.user.as_ref().is_none_or(|user| client.user == *user)
+            && self.kind.is_none_or(|kind| ClientType::of(client) == kind)
+            && self.max_age.is_none_or(|max_age| client.age > max_age)
+    }
+}
+
+/// An active CLIENT PAUSE
+#[derive(Debug, Clone, Copy)]
+struct ClientPause {
+    until: Instant,
+    /// ALL rather than WRITE
+    all: bool,
+}
+
 /// Client registry for managing all active connections
 #[derive(Clone)]
 pub struct ClientRegistry {
     clients: Arc<DashMap<u64, ClientInfo>>,
+    /// Signalled to make a connection close, by CLIENT KILL
+    kill_signals: Arc<DashMap<u64, Arc<Notify>>>,
+    /// CLIENT TRACKING state and the clients' push queues
+    tracking: Arc<Tracking>,
+    pause: Arc<Mutex<Option<ClientPause>>>,
 }
 
 impl ClientRegistry {
@@ -115,6 +287,9 @@ impl ClientRegistry {
     pub fn new() -> Self {
         Self {
             clients: Arc::new(DashMap::new()),
+            kill_signals: Arc::new(DashMap::new()),
+            tracking: Arc::new(Tracking::new()),
+            pause: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -123,12 +298,24 @@ impl ClientRegistry {
         let client = ClientInfo::new(addr, fd);
         let id = client.id;
         self.clients.insert(id, client);
+        self.kill_signals.insert(id, Arc::new(Notify::new()));
         id
     }
 
     /// Unregister a client connection
     pub fn unregister(&self, id: u64) {
         self.clients.remove(&id);
+        self.kill_signals.remove(&id);
+        self.tracking.remove_client(id);
+    }
+
+    pub fn tracking(&self) -> &Arc<Tracking> {
+        &self.tracking
+    }
+
+    /// Notified when the client is killed; the connection closes on it
+    pub fn kill_signal(&self, id: u64) -> Arc<Notify> {
+        Arc::clone(self.kill_signals.entry(id).or_insert_with(|| Arc::new(Notify::new())).value())
     }
 
     /// Get client info by ID
@@ -148,6 +335,63 @@ impl ClientRegistry {
         self.clients.get(&id).and_then(|entry| entry.name.clone())
     }
 
+    /// Record the local address a client connected to
+    pub fn set_local_addr(&self, id: u64, laddr: String) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.laddr = laddr;
+        }
+    }
+
+    /// CLIENT SETINFO lib-name / lib-ver
+    pub fn set_lib_info(&self, id: u64, lib_name: Option<String>, lib_ver: Option<String>) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            if lib_name.is_some() {
+                entry.lib_name = lib_name;
+            }
+            if lib_ver.is_some() {
+                entry.lib_ver = lib_ver;
+            }
+        }
+    }
+
+    /// CLIENT NO-EVICT on|off
+    pub fn set_no_evict(&self, id: u64, on: bool) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.no_evict = on;
+        }
+    }
+
+    /// CLIENT NO-TOUCH on|off
+    pub fn set_no_touch(&self, id: u64, on: bool) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.no_touch = on;
+        }
+    }
+
+    /// Protocol version switched to with HELLO
+    pub fn set_resp(&self, id: u64, resp: u8) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.resp = resp;
+        }
+    }
+
+    /// Update subscription counters shown by CLIENT LIST
+    pub fn set_subscriptions(&self, id: u64, channels: usize, patterns: usize, shard_channels: usize) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.sub = channels;
+            entry.psub = patterns;
+            entry.ssub = shard_channels;
+        }
+    }
+
+    /// Update the buffer sizes and MULTI queue length shown by CLIENT LIST
+    pub fn update_stats(&self, id: u64, memory: ClientMemory, multi: i64) {
+        if let Some(mut entry) = self.clients.get_mut(&id) {
+            entry.memory = memory;
+            entry.multi = multi;
+        }
+    }
+
     /// Mark client activity
     pub fn mark_activity(&self, id: u64, cmd: String, db_index: usize) {
         if let Some(mut entry) = self.clients.get_mut(&id) {
@@ -155,13 +399,39 @@ impl ClientRegistry {
         }
     }
 
+    /// A client's CLIENT LIST line, with its current timing and tracking
+    fn entry(&self, client: &ClientInfo) -> String {
+        let mut client = client.clone();
+        client.update_timing();
+        client.redir = self.tracking.redirect(client.id);
+        client.to_list_entry()
+    }
+
+    /// CLIENT INFO: the CLIENT LIST line of one client
+    pub fn info(&self, id: u64) -> Option<String> {
+        let client = self.get(id)?;
+        Some(format!("{}\n", self.entry(&client)))
+    }
+
     /// Get all clients as formatted list
     pub fn list(&self) -> String {
+        self.list_filtered(None, &[])
+    }
+
+    /// CLIENT LIST [TYPE type] [ID id ...]
+    pub fn list_filtered(&self, kind: Option<ClientType>, ids: &[u64]) -> String {
+        let mut clients: Vec<ClientInfo> = self
+            .clients
+            .iter()
+            .filter(|entry| kind.is_none_or(|kind| ClientType::of(entry.value()) == kind))
+            .filter(|entry| ids.is_empty() || ids.contains(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        clients.sort_by_key(|client| client.id);
+
         let mut result = String::new();
-        for entry in self.clients.iter() {
-            let mut client = entry.value().clone();
-            client.update_timing();
-            result.push_str(&client.to_list_entry());
+        for client in &clients {
+            result.push_str(&self.entry(client));
             result.push('\n');
         }
         result
@@ -174,24 +444,64 @@ impl ClientRegistry {
 
     /// Kill client by ID
     pub fn kill(&self, id: u64) -> bool {
-        self.clients.remove(&id).is_some()
+        let killed = self.clients.remove(&id).is_some();
+        if let Some((_, signal)) = self.kill_signals.remove(&id) {
+            signal.notify_one();
+        }
+        killed
     }
 
     /// Kill client by address
     pub fn kill_by_addr(&self, addr: &str) -> usize {
-        let mut killed = 0;
+        self.kill_matching(
+            &KillFilter { addr: Some(addr.to_string()), skip_me: false, ..Default::default() },
+            0,
+        )
+    }
+
+    /// Kill every client matching `filter`, returning how many there were.
+    /// `caller` is the client sending CLIENT KILL, for SKIPME.
+    pub fn kill_matching(&self, filter: &KillFilter, caller: u64) -> usize {
         let to_remove: Vec<u64> = self
             .clients
             .iter()
-            .filter(|entry| entry.value().addr == addr)
+            .filter(|entry| {
+                let mut client = entry.value().clone();
+                client.update_timing();
+                filter.matches(&client, caller)
+            })
             .map(|entry| *entry.key())
             .collect();
 
-        for id in to_remove {
-            self.clients.remove(&id);
-            killed += 1;
+        to_remove.into_iter().filter(|&id| self.kill(id)).count()
+    }
+
+    /// CLIENT PAUSE: hold writes, or with `all` every command, for
+    /// `duration`. Overlapping pauses last until the later end, in the
+    /// stricter mode.
+    pub fn pause(&self, duration: Duration, all: bool) {
+        let until = Instant::now() + duration;
+        let mut pause = self.pause.lock().unwrap();
+        *pause = Some(match *pause {
+            Some(current) if current.until > Instant::now() => ClientPause {
+                until: current.until.max(until),
+                all: current.all || all,
+            },
+            _ => ClientPause { until, all },
+        });
+    }
+
+    /// CLIENT UNPAUSE
+    pub fn unpause(&self) {
+        *self.pause.lock().unwrap() = None;
+    }
+
+    /// Whether a command has to wait for CLIENT PAUSE to end
+    pub fn is_paused(&self, write: bool) -> bool {
+        match *self.pause.lock().unwrap() {
+            Some(pause) => pause.until > Instant::now() && (write || pause.all),
+            None => false,
         }
-        killed
     }
 }
 
@@ -239,6 +549,33 @@ mod tests {
         assert!(list_entry.contains("db=0"));
     }
 
+    #[test]
+    fn test_client_list_stats_and_flags() {
+        let registry = ClientRegistry::new();
+        let id = registry.register("127.0.0.1:1111".to_string(), 1);
+        registry.set_local_addr(id, "127.0.0.1:6379".to_string());
+        registry.mark_activity(id, "CLIENT|LIST".to_string(), 0);
+        registry.set_no_evict(id, true);
+        registry.set_lib_info(id, Some("redis-py".to_string()), Some("5.0".to_string()));
+        let memory = ClientMemory { qbuf: 26, qbuf_free: 4070, omem: 0, tot_mem: 8192, ..Default::default() };
+        registry.update_stats(id, memory, 2);
+
+        let info = registry.info(id).unwrap();
+        assert!(info.ends_with('\n'));
+        for field in [
+            "laddr=127.0.0.1:6379",
+            "flags=xe ",
+            "multi=2",
+            "qbuf=26 qbuf-free=4070",
+            "omem=0 tot-mem=8192",
+            "cmd=client|list",
+            "user=default redir=-1 resp=2",
+            "lib-name=redis-py lib-ver=5.0",
+        ] {
+            assert!(info.contains(field), "{} missing from {}", field, info);
+        }
+    }
+
     #[test]
     fn test_client_activity_tracking() {
         let registry = ClientRegistry::new();
@@ -250,4 +587,43 @@ mod tests {
         assert_eq!(client.cmd, "GET");
         assert_eq!(client.db, 2);
     }
+
+    #[tokio::test]
+    async fn test_kill_filters_signal_connections() {
+        let registry = ClientRegistry::new();
+        let me = registry.register("127.0.0.1:1111".to_string(), 1);
+        let other = registry.register("127.0.0.1:2222".to_string(), 2);
+        let subscriber = registry.register("127.0.0.1:3333".to_string(), 3);
+        registry.set_subscriptions(subscriber, 1, 0, 0);
+        let signal = registry.kill_signal(subscriber);
+
+        let pubsub = KillFilter { kind: Some(ClientType::PubSub), ..Default::default() };
+        assert_eq!(registry.kill_matching(&pubsub, me), 1);
+        tokio::time::timeout(Duration::from_secs(1), signal.notified()).await.unwrap();
+
+        // SKIPME spares the caller unless turned off
+        let everyone = KillFilter::default();
+        assert_eq!(registry.kill_matching(&KillFilter { max_age: Some(60), ..everyone.clone() }, me), 0);
+        assert_eq!(registry.kill_matching(&everyone, me), 1);
+        assert!(registry.get(other).is_none());
+        assert_eq!(registry.kill_matching(&KillFilter { skip_me: false, ..everyone }, me), 1);
+        assert_eq!(registry.count(), 0);
+    }
+
+    #[test]
+    fn test_client_pause() {
+        let registry = ClientRegistry::new();
+        assert!(!registry.is_paused(true));
+
+        registry.pause(Duration::from_secs(10), false);
+        assert!(registry.is_paused(true));
+        assert!(!registry.is_paused(false));
+
+        // A shorter ALL pause keeps the longer end and widens the mode
+        registry.pause(Duration::from_millis(1), true);
+        assert!(registry.is_paused(false));
+
+        registry.unpause();
+        assert!(!registry.is_paused(true));
+    }
 }
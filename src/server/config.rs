@@ -31,6 +31,8 @@ pub struct ServerConfig {
     pub cluster_enabled: bool,
     /// Cluster nodes configuration file
     pub cluster_config_file: String,
+    /// Milliseconds a node may be unreachable before it is flagged PFAIL
+    pub cluster_node_timeout: u64,
 }
 
 impl Default for ServerConfig {
@@ -49,6 +51,7 @@ impl Default for ServerConfig {
             rdb_filename: "dump.rdb".to_string(),
             cluster_enabled: false,
             cluster_config_file: "nodes.conf".to_string(),
+            cluster_node_timeout: 15000,
         }
     }
 }
@@ -86,4 +89,9 @@ impl ServerConfig {
         self.cluster_config_file = file;
         self
     }
+
+    pub fn with_cluster_node_timeout(mut self, timeout_ms: u64) -> Self {
+        self.cluster_node_timeout = timeout_ms;
+        self
+    }
 }
@@ -1,24 +1,29 @@
 // Connection handler
 
-use crate::cluster::{ClusterState, MigrationManager};
+use crate::cluster::{ClusterBus, ClusterState, MigrationManager};
 use crate::commands::dispatcher::CommandDispatcher;
 use crate::config::Config;
 use crate::persistence::aof::AofManager;
+use crate::persistence::rdb::RdbSerializer;
 use crate::protocol::{RespParser, RespSerializer, RespValue};
-use crate::pubsub::PubSub;
-use crate::replication::{ReplicationInfo, ReplicationBacklog, CommandPropagator};
+use crate::pubsub::{PubSub, Subscriber, SubscriptionState};
+use crate::replication::{ReplicationInfo, ReplicationBacklog, CommandPropagator, DisklessSyncScheduler, SnapshotSource, SyncHandler};
+use crate::replication::diskless;
 use crate::scripting::ScriptCache;
-use crate::server::client_info::ClientRegistry;
+use crate::server::client_info::{ClientMemory, ClientRegistry};
 use crate::server::config::ServerConfig;
+use crate::server::context::ServerContext;
 use crate::server::slowlog::SlowLog;
+use crate::server::tracking::{self, INVALIDATE_CHANNEL};
 use crate::storage::db::Database;
 use crate::transaction::Transaction;
 use bytes::BytesMut;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tokio::net::TcpStream;
-use tracing::{debug, error};
+use tokio::sync::{mpsc, Notify};
+use tracing::{debug, error, info};
 
 pub struct Connection {
     stream: BufWriter<TcpStream>,
@@ -37,34 +42,88 @@ pub struct Connection {
     slowlog: Arc<SlowLog>,
     cluster: Arc<ClusterState>,
     migration: Arc<MigrationManager>,
+    cluster_bus: Arc<ClusterBus>,
+    diskless_sync: Arc<DisklessSyncScheduler>,
     /// Current selected database (0-15)
     db_index: usize,
     /// Transaction state
     transaction: Transaction,
     /// ASKING flag for cluster redirection
     asking: bool,
+    /// READONLY: read the slots of our master while we are a cluster replica
+    readonly: bool,
+    /// Port announced by a replica via REPLCONF listening-port
+    replica_listening_port: Option<u16>,
+    /// Replica announced REPLCONF capa eof (accepts diskless payloads)
+    replica_capa_eof: bool,
+    /// Pending PSYNC outcome: Some(None) for a full resync,
+    /// Some(Some(offset)) for a partial resync from offset
+    pending_sync: Option<Option<u64>>,
+    /// Set once this connection has become a replication link
+    replica_handoff: bool,
+    /// Channels and patterns this client is subscribed to
+    subscriptions: SubscriptionState,
+    /// Forwards published messages into the push queue
+    subscriber: Subscriber,
+    /// Out-of-band frames (pub/sub messages, invalidations) waiting to be written
+    push_rx: mpsc::UnboundedReceiver<RespValue>,
+    /// HELLO 3 switched the connection to RESP3
+    resp3: bool,
+    /// Notified by CLIENT KILL
+    killed: Arc<Notify>,
+    /// CLIENT REPLY OFF
+    replies_off: bool,
+    /// Replies still to drop for CLIENT REPLY SKIP
+    skip_replies: u8,
+    /// Size of the next socket read
+    read_size: usize,
 }
 
+/// Commands that may wait before replying; replies pipelined ahead of
+/// them are flushed first
+const BLOCKING_COMMANDS: [&str; 11] = [
+    "BLMOVE", "BLMPOP", "BLPOP", "BRPOP", "BRPOPLPUSH", "BZMPOP", "BZPOPMAX", "BZPOPMIN", "WAIT", "XREAD",
+    "XREADGROUP",
+];
+
+/// First socket read size; reads grow up to MAX_READ while they keep filling
+/// the space offered and shrink back after short reads
+const MIN_READ: usize = 4 * 1024;
+const MAX_READ: usize = 1024 * 1024;
+
+/// Replies gathered before the writer has to go to the socket mid-pipeline
+const REPLY_BUFFER: usize = 16 * 1024;
+
+/// Commands listed with their subcommand in CLIENT LIST, as in client|list
+const CONTAINER_COMMANDS: [&str; 11] = [
+    "ACL", "CLIENT", "CLUSTER", "COMMAND", "CONFIG", "MEMORY", "OBJECT", "PUBSUB", "SCRIPT", "SLOWLOG", "XGROUP",
+];
+
 impl Connection {
-    pub fn new(
-        socket: TcpStream,
-        client_id: u64,
-        db: Arc<Database>,
-        pubsub: Arc<PubSub>,
-        config: Arc<ServerConfig>,
-        app_config: Arc<Config>,
-        aof: Arc<AofManager>,
-        script_cache: Arc<ScriptCache>,
-        repl_info: Arc<ReplicationInfo>,
-        repl_backlog: Arc<ReplicationBacklog>,
-        propagator: Arc<CommandPropagator>,
-        client_registry: Arc<ClientRegistry>,
-        slowlog: Arc<SlowLog>,
-        cluster: Arc<ClusterState>,
-        migration: Arc<MigrationManager>,
-    ) -> Self {
+    pub fn new(socket: TcpStream, client_id: u64, ctx: ServerContext) -> Self {
+        let ServerContext {
+            db,
+            pubsub,
+            config,
+            app_config,
+            aof,
+            script_cache,
+            repl_info,
+            repl_backlog,
+            propagator,
+            client_registry,
+            slowlog,
+            cluster,
+            migration,
+            cluster_bus,
+            diskless_sync,
+        } = ctx;
+        let (push_tx, push_rx) = mpsc::unbounded_channel();
+        client_registry.tracking().register_client(client_id, push_tx.clone());
+        let killed = client_registry.kill_signal(client_id);
+        let subscriber = Subscriber::new(pubsub.clone(), push_tx);
         Self {
-            stream: BufWriter::new(socket),
+            stream: BufWriter::with_capacity(REPLY_BUFFER, socket),
             buffer: BytesMut::with_capacity(4096),
             client_id,
             db,
@@ -80,25 +139,82 @@ impl Connection {
             slowlog,
             cluster,
             migration,
+            cluster_bus,
+            diskless_sync,
             db_index: 0,
             transaction: Transaction::new(),
             asking: false,
+            readonly: false,
+            replica_listening_port: None,
+            replica_capa_eof: false,
+            pending_sync: None,
+            replica_handoff: false,
+            subscriptions: SubscriptionState::new(),
+            subscriber,
+            push_rx,
+            resp3: false,
+            killed,
+            replies_off: false,
+            skip_replies: 0,
+            read_size: MIN_READ,
         }
     }
 
     /// Main processing loop for this connection
+    ///
+    /// Every complete frame already buffered is handled before the replies
+    /// are flushed, so a pipeline costs one write rather than one per command.
     pub async fn process(&mut self) -> anyhow::Result<()> {
         loop {
             // Try to parse a complete frame from buffer
             match self.parse_frame()? {
                 Some(frame) => {
                     debug!("Received frame: {:?}", frame);
-                    let response = self.handle_frame(frame).await;
-                    self.write_response(response).await?;
+                    let response = tracking::scope(self.client_id, self.handle_frame(frame)).await;
+                    if !self.transaction.in_multi {
+                        self.client_registry.tracking().end_command(self.client_id);
+                    }
+                    // Invalidations caused by the command go out before its reply
+                    while let Ok(push) = self.push_rx.try_recv() {
+                        if let Some(push) = self.complete_push(push) {
+                            self.write_response(push).await?;
+                        }
+                    }
+
+                    // After a successful PSYNC this connection turns into a
+                    // replication link and is handed over to the propagator;
+                    // the sync sends the PSYNC reply itself
+                    if let Some(sync) = self.pending_sync.take() {
+                        self.update_stats();
+                        self.send_sync_payload(sync, response).await?;
+                        self.replica_handoff = true;
+                        return Ok(());
+                    }
+
+                    if self.skip_replies > 0 {
+                        self.skip_replies -= 1;
+                    } else if !self.replies_off {
+                        self.write_response(response).await?;
+                    }
+                    self.update_stats();
                 }
                 None => {
-                    // Need more data
-                    if self.read_frame().await? == 0 {
+                    // The pipeline is drained: send its replies in one write
+                    self.stream.flush().await?;
+
+                    // Need more data; deliver pushed messages while waiting
+                    let n = tokio::select! {
+                        // CLIENT KILL closes the connection
+                        _ = self.killed.notified() => return Ok(()),
+                        n = Self::read_frame(self.stream.get_mut(), &mut self.buffer, &mut self.read_size) => n?,
+                        Some(push) = self.push_rx.recv() => {
+                            if let Some(push) = self.complete_push(push) {
+                                self.write_response(push).await?;
+                            }
+                            continue;
+                        }
+                    };
+                    if n == 0 {
                         // Connection closed by client
                         if self.buffer.is_empty() {
                             return Ok(());
@@ -127,13 +243,18 @@ impl Connection {
         }
     }
 
-    /// Read data from socket into buffer
-    async fn read_frame(&mut self) -> anyhow::Result<usize> {
-        let stream = self.stream.get_mut();
-        let mut read_buf = [0u8; 4096];
-        let n = stream.read(&mut read_buf).await?;
-        if n > 0 {
-            self.buffer.extend_from_slice(&read_buf[..n]);
+    /// Read data from socket straight into the buffer
+    async fn read_frame(stream: &mut TcpStream, buffer: &mut BytesMut, read_size: &mut usize) -> anyhow::Result<usize> {
+        // Give back the space a large pipeline or argument left behind
+        if buffer.is_empty() && buffer.capacity() > MAX_READ {
+            *buffer = BytesMut::with_capacity(MIN_READ);
+        }
+        buffer.reserve(*read_size);
+        let n = stream.read_buf(buffer).await?;
+        if n >= *read_size {
+            *read_size = (*read_size * 2).min(MAX_READ);
+        } else if n < *read_size / 4 {
+            *read_size = (*read_size / 2).max(MIN_READ);
         }
         Ok(n)
     }
@@ -168,10 +289,16 @@ impl Connection {
         }
 
         // Track client activity
-        let cmd_name = std::str::from_utf8(&cmd_args[0])
+        let mut cmd_name = std::str::from_utf8(&cmd_args[0])
             .unwrap_or("unknown")
             .to_uppercase();
-        self.client_registry.mark_activity(self.client_id, cmd_name.clone(), self.db_index);
+        let activity = match cmd_args.get(1) {
+            Some(sub) if CONTAINER_COMMANDS.contains(&cmd_name.as_str()) => {
+                format!("{}|{}", cmd_name, String::from_utf8_lossy(sub))
+            }
+            _ => cmd_name.clone(),
+        };
+        self.client_registry.mark_activity(self.client_id, activity, self.db_index);
 
         // Handle ASKING command (sets asking flag for next command)
         if cmd_name == "ASKING" {
@@ -179,9 +306,109 @@ impl Connection {
             return RespValue::SimpleString("OK".to_string());
         }
 
-        // Handle CLUSTER commands directly (need access to cluster state)
+        // RESTORE-ASKING is RESTORE with an implicit ASKING, as sent by MIGRATE
+        if cmd_name == "RESTORE-ASKING" {
+            self.asking = true;
+            cmd_args[0] = b"RESTORE".to_vec();
+            cmd_name = "RESTORE".to_string();
+        }
+
+        if cmd_name == "READONLY" || cmd_name == "READWRITE" {
+            if !self.cluster.enabled {
+                return RespValue::Error("ERR This instance has cluster support disabled".to_string());
+            }
+            self.readonly = cmd_name == "READONLY";
+            return RespValue::SimpleString("OK".to_string());
+        }
+
+        if cmd_name == "HELLO" {
+            return self.handle_hello(&cmd_args[1..]);
+        }
+
+        // CLIENT REPLY decides which of the following replies are written
+        if cmd_name == "CLIENT"
+            && !self.transaction.in_multi
+            && cmd_args.len() == 3
+            && cmd_args[1].eq_ignore_ascii_case(b"REPLY")
+        {
+            match String::from_utf8_lossy(&cmd_args[2]).to_uppercase().as_str() {
+                "ON" => {
+                    self.replies_off = false;
+                    self.skip_replies = 0;
+                    return RespValue::SimpleString("OK".to_string());
+                }
+                "OFF" => {
+                    self.replies_off = true;
+                    return RespValue::SimpleString("OK".to_string());
+                }
+                // Neither this reply nor the next one is written
+                "SKIP" => {
+                    self.skip_replies = 2;
+                    return RespValue::SimpleString("OK".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        // Subscription commands reply with one frame per channel
+        if matches!(
+            cmd_name.as_str(),
+            "SUBSCRIBE" | "UNSUBSCRIBE" | "PSUBSCRIBE" | "PUNSUBSCRIBE" | "SSUBSCRIBE" | "SUNSUBSCRIBE"
+        ) {
+            // Shard channels are served by the nodes of their slot's shard
+            if self.cluster.enabled {
+                if let Some(redirection_error) = self.check_cluster_redirection(&cmd_name, &cmd_args) {
+                    return redirection_error;
+                }
+            }
+            return self.handle_subscription(&cmd_name, cmd_args[1..].to_vec()).await;
+        }
+
+        // A subscribed RESP2 client may only manage subscriptions; RESP3
+        // tells pushes and replies apart
+        if self.subscriptions.is_subscribed() && !self.resp3 {
+            match cmd_name.as_str() {
+                "PING" => {
+                    let payload = cmd_args.get(1).cloned().unwrap_or_default();
+                    return RespValue::Array(Some(vec![
+                        RespValue::BulkString(Some(b"pong".to_vec())),
+                        RespValue::BulkString(Some(payload)),
+                    ]));
+                }
+                "QUIT" | "RESET" => {}
+                _ => {
+                    return RespValue::Error(format!(
+                        "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                        cmd_name.to_lowercase()
+                    ));
+                }
+            }
+        }
+
+        // Remember what a replica tells us about itself during the handshake
+        if cmd_name == "REPLCONF" {
+            self.record_replconf(&cmd_args[1..]);
+        }
+
+        // PSYNC needs to stream the dataset after its reply
+        if cmd_name == "PSYNC" {
+            return self.handle_psync(&cmd_args[1..]).await;
+        }
+
+        // Handle CLUSTER commands directly (need access to cluster state).
+        // They hold the write gate, as the keys of dropped slots are logged
         if cmd_name == "CLUSTER" {
-            return self.handle_cluster_command(&cmd_args[1..]);
+            let gate = self.repl_info.write_gate().clone();
+            return gate.write(self.run_cluster_command(&cmd_args[1..])).await;
+        }
+
+        // Hold writes while a replica takes over from us, so it can catch
+        // up; afterwards the redirection below sends them to the new master
+        if self.cluster.enabled && self.should_log_to_aof(&cmd_args) {
+            while self.cluster_bus.writes_paused() {
+                let _ = self.stream.flush().await;
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
         }
 
         // Check cluster redirection before executing command (skip for CLUSTER commands)
@@ -190,13 +417,30 @@ impl Connection {
             if let Some(redirection_error) = self.check_cluster_redirection(&cmd_name, &cmd_args) {
                 // Reset ASKING flag after using it
                 self.asking = false;
+                // A transaction must not run with some of its commands missing
+                if cmd_name == "EXEC" {
+                    self.transaction.discard();
+                } else {
+                    self.transaction.flag_error();
+                }
                 return redirection_error;
             }
         }
 
-        // Reset ASKING flag after command (whether redirected or not)
+        // Reset ASKING flag after command (whether redirected or not); it
+        // covers a whole transaction up to EXEC
         let asking_was_set = self.asking;
-        self.asking = false;
+        if !self.transaction.in_multi || matches!(cmd_name.as_str(), "EXEC" | "DISCARD") {
+            self.asking = false;
+        }
+
+        // Inside MULTI everything but the transaction commands waits for EXEC
+        if self.transaction.in_multi
+            && !matches!(cmd_name.as_str(), "EXEC" | "DISCARD" | "MULTI" | "WATCH" | "UNWATCH")
+        {
+            self.transaction.queue_command(cmd_args);
+            return RespValue::SimpleString("QUEUED".to_string());
+        }
 
         // Convert command args to strings for slow log
         let cmd_strings: Vec<String> = cmd_args
@@ -207,42 +451,56 @@ impl Connection {
         // Determine if command should be logged to AOF
         let should_log_aof = self.should_log_to_aof(&cmd_args);
 
+        let writes = should_log_aof
+            || crate::cluster::keys::is_write_command(&cmd_name)
+            || (cmd_name == "EXEC" && self.transaction.commands.iter().any(|c| self.should_log_to_aof(c)));
+
+        // CLIENT PAUSE holds writes, or with ALL every command but CLIENT
+        if cmd_name != "CLIENT" {
+            while self.client_registry.is_paused(writes) {
+                let _ = self.stream.flush().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+
+        // Replies batched so far must not wait behind a blocking command
+        if BLOCKING_COMMANDS.contains(&cmd_name.as_str()) {
+            let _ = self.stream.flush().await;
+        }
+
+        // A write holds the replication write gate until it is propagated,
+        // so a resync never sees its data without its offset or vice versa
+        if writes {
+            let gate = self.repl_info.write_gate().clone();
+            gate.write(self.run_command(cmd_args, should_log_aof, cmd_strings, start)).await
+        } else {
+            self.run_command(cmd_args, should_log_aof, cmd_strings, start).await
+        }
+    }
+
+    /// Execute a command that passed the checks of handle_frame, log it if
+    /// it wrote, and record it in the slow log
+    async fn run_command(
+        &mut self,
+        cmd_args: Vec<Vec<u8>>,
+        should_log_aof: bool,
+        cmd_strings: Vec<String>,
+        start: Instant,
+    ) -> RespValue {
+        let cmd_name = String::from_utf8_lossy(&cmd_args[0]).to_uppercase();
+
         // Dispatch command
-        let dispatcher = CommandDispatcher::new();
-        let response = dispatcher.dispatch(
-            &mut self.db_index,
-            &self.db,
-            &self.pubsub,
-            &self.aof,
-            &self.script_cache,
-            &self.repl_info,
-            &self.repl_backlog,
-            &self.propagator,
-            &self.client_registry,
-            self.client_id,
-            &self.slowlog,
-            &self.app_config,
-            &mut self.transaction,
-            cmd_args.clone(),
-        ).await;
+        let response = self.dispatch(cmd_args.clone()).await;
+        self.forward_shard_message(&cmd_args, &response);
+        self.track_reads(&cmd_name, &cmd_args, &response);
 
         // Log to AOF if command modifies data and succeeded
         if should_log_aof && self.is_success_response(&response) {
-            if let Err(e) = self.aof.append(self.db_index, &cmd_args).await {
-                error!("Failed to append to AOF: {}", e);
-            }
-
-            // Propagate to replicas if we're a master
-            if self.repl_info.is_master() {
-                let offset = self.repl_info.master_offset();
-                self.propagator.propagate(self.db_index, &cmd_args, offset).await;
-                self.repl_info.increment_offset(1);
-            }
+            self.log_write(&cmd_args, &response).await;
         }
 
-        // Handle MULTI mode - queue commands instead of executing
+        // EXEC runs the queued commands
         if self.transaction.in_multi {
-            // Check if this is EXEC command
             if let RespValue::SimpleString(ref s) = response {
                 if s == "__EXEC__" {
                     // Execute all queued commands
@@ -250,36 +508,14 @@ impl Connection {
                     let mut results = Vec::new();
 
                     for queued_cmd in commands {
-                        let dispatcher = CommandDispatcher::new();
-                        let result = dispatcher.dispatch(
-                            &mut self.db_index,
-                            &self.db,
-                            &self.pubsub,
-                            &self.aof,
-                            &self.script_cache,
-                            &self.repl_info,
-                            &self.repl_backlog,
-                            &self.propagator,
-                            &self.client_registry,
-                            self.client_id,
-                            &self.slowlog,
-                            &self.app_config,
-                            &mut self.transaction,
-                            queued_cmd.clone(),
-                        ).await;
+                        let result = self.dispatch(queued_cmd.clone()).await;
+                        self.forward_shard_message(&queued_cmd, &result);
+                        let queued_name = String::from_utf8_lossy(&queued_cmd[0]).to_uppercase();
+                        self.track_reads(&queued_name, &queued_cmd, &result);
 
                         // Log each executed command to AOF
                         if self.should_log_to_aof(&queued_cmd) && self.is_success_response(&result) {
-                            if let Err(e) = self.aof.append(self.db_index, &queued_cmd).await {
-                                error!("Failed to append to AOF: {}", e);
-                            }
-
-                            // Propagate to replicas if we're a master
-                            if self.repl_info.is_master() {
-                                let offset = self.repl_info.master_offset();
-                                self.propagator.propagate(self.db_index, &queued_cmd, offset).await;
-                                self.repl_info.increment_offset(1);
-                            }
+                            self.log_write(&queued_cmd, &result).await;
                         }
 
                         results.push(result);
@@ -289,10 +525,6 @@ impl Connection {
                     return RespValue::Array(Some(results));
                 }
             }
-
-            // Queue the command
-            self.transaction.queue_command(cmd_args);
-            return RespValue::SimpleString("QUEUED".to_string());
         }
 
         // Log to slow log if needed
@@ -307,6 +539,39 @@ impl Connection {
         response
     }
 
+    /// Run one command through the dispatcher
+    async fn dispatch(&mut self, cmd_args: Vec<Vec<u8>>) -> RespValue {
+        // MIGRATE is logged as the removal of the keys it actually moved
+        if cmd_args[0].eq_ignore_ascii_case(b"MIGRATE") {
+            let (response, deleted) =
+                crate::commands::key_mgmt::migrate_keys(&self.db, self.db_index, cmd_args[1..].to_vec()).await;
+            if !deleted.is_empty() {
+                let mut del = vec![b"DEL".to_vec()];
+                del.extend(deleted.into_iter().map(String::into_bytes));
+                self.log_write(&del, &response).await;
+            }
+            return response;
+        }
+
+        let dispatcher = CommandDispatcher::new();
+        dispatcher.dispatch(
+            &mut self.db_index,
+            &self.db,
+            &self.pubsub,
+            &self.aof,
+            &self.script_cache,
+            &self.repl_info,
+            &self.repl_backlog,
+            &self.propagator,
+            &self.client_registry,
+            self.client_id,
+            &self.slowlog,
+            &self.app_config,
+            &mut self.transaction,
+            cmd_args,
+        ).await
+    }
+
     /// Check if a command should be logged to AOF
     fn should_log_to_aof(&self, args: &[Vec<u8>]) -> bool {
         if args.is_empty() {
@@ -315,19 +580,37 @@ impl Connection {
 
         let cmd = String::from_utf8_lossy(&args[0]).to_uppercase();
 
+        // GEORADIUS only writes with a STORE option
+        if matches!(cmd.as_str(), "GEORADIUS" | "GEORADIUSBYMEMBER") {
+            return crate::commands::geo::has_store_option(args);
+        }
+        // BITFIELD only writes with SET or INCRBY
+        if cmd == "BITFIELD" {
+            return crate::commands::bitmap::bitfield_writes(args);
+        }
+
         // Only log write commands, not read commands
         matches!(cmd.as_str(),
             // String write commands
             "SET" | "DEL" | "APPEND" | "INCR" | "DECR" | "INCRBY" | "DECRBY" |
             "SETRANGE" | "MSET" |
+            // Key management write commands
+            "RESTORE" |
             // List write commands
-            "LPUSH" | "RPUSH" | "LPOP" | "RPOP" | "LSET" | "LTRIM" |
+            "LPUSH" | "RPUSH" | "LPOP" | "RPOP" | "LSET" | "LTRIM" | "LINSERT" | "LMPOP" | "BLMPOP" |
             // Hash write commands
-            "HSET" | "HDEL" |
+            "HSET" | "HDEL" | "HEXPIRE" | "HPEXPIRE" | "HEXPIREAT" | "HPEXPIREAT" | "HPERSIST" |
+            "HGETDEL" | "HGETEX" | "HSETEX" |
             // Set write commands
             "SADD" | "SREM" | "SPOP" |
             // ZSet write commands
-            "ZADD" | "ZREM" |
+            "ZADD" | "ZREM" | "ZPOPMIN" | "ZPOPMAX" | "ZRANGESTORE" | "ZUNIONSTORE" | "ZINTERSTORE" |
+            "ZMPOP" | "BZMPOP" |
+            // Geo write commands
+            "GEOADD" | "GEOSEARCHSTORE" |
+            // Stream write commands
+            "XADD" | "XDEL" | "XTRIM" | "XSETID" | "XGROUP" | "XREADGROUP" | "XACK" |
+            "XCLAIM" | "XAUTOCLAIM" |
             // Expiration commands
             "EXPIRE" | "EXPIREAT" | "PEXPIRE" | "PEXPIREAT" | "PERSIST" |
             // Database commands
@@ -335,6 +618,42 @@ impl Connection {
         )
     }
 
+    /// Append a successful write to the AOF and propagate it to replicas,
+    /// in the form that replays to the same result
+    async fn log_write(&mut self, args: &[Vec<u8>], response: &RespValue) {
+        self.log_write_in(self.db_index, args, response).await;
+    }
+
+    /// `log_write` for a write to database `db_index`
+    async fn log_write_in(&mut self, db_index: usize, args: &[Vec<u8>], response: &RespValue) {
+        let commands = match args[0].first().map(|c| c.to_ascii_uppercase()) {
+            Some(b'X') => crate::commands::stream::propagated_commands(args, response),
+            Some(b'H') => crate::commands::hash::propagated_commands(args),
+            Some(b'L') => crate::commands::list::propagated_commands(args, response),
+            Some(b'B') if args[0].eq_ignore_ascii_case(b"BLMPOP") => {
+                crate::commands::list::propagated_commands(args, response)
+            }
+            Some(b'Z' | b'B') => crate::commands::zset::propagated_commands(args, response),
+            Some(b'R') if args[0].eq_ignore_ascii_case(b"RESTORE") => {
+                vec![crate::commands::key_mgmt::propagated_restore(args)]
+            }
+            _ => vec![args.to_vec()],
+        };
+
+        for command in commands {
+            if let Err(e) = self.aof.append(db_index, &command).await {
+                error!("Failed to append to AOF: {}", e);
+            }
+
+            // Propagate to replicas if we're a master
+            if self.repl_info.is_master() {
+                let offset = self.repl_info.master_offset();
+                let bytes = self.propagator.propagate(db_index, &command, offset).await;
+                self.repl_info.increment_offset(bytes);
+            }
+        }
+    }
+
     /// Check if response indicates success
     fn is_success_response(&self, response: &RespValue) -> bool {
         !matches!(response, RespValue::Error(_))
@@ -342,12 +661,324 @@ impl Connection {
 
     /// Write response to client
     async fn write_response(&mut self, response: RespValue) -> anyhow::Result<()> {
+        // Buffered until the pipeline is drained; process() flushes
         let data = RespSerializer::serialize(&response);
         self.stream.write_all(&data).await?;
+        Ok(())
+    }
+
+    /// Run a (P|S)(UN)SUBSCRIBE command, writing every confirmation but
+    /// the last directly and returning the last as the command's reply
+    async fn handle_subscription(&mut self, cmd_name: &str, args: Vec<Vec<u8>>) -> RespValue {
+        use crate::commands::pubsub_cmds;
+
+        let mut responses = match cmd_name {
+            "SUBSCRIBE" => pubsub_cmds::subscribe(&self.pubsub, &mut self.subscriptions, args).await,
+            "UNSUBSCRIBE" => pubsub_cmds::unsubscribe(&self.pubsub, &mut self.subscriptions, args).await,
+            "PSUBSCRIBE" => pubsub_cmds::psubscribe(&self.pubsub, &mut self.subscriptions, args).await,
+            "SSUBSCRIBE" => pubsub_cmds::ssubscribe(&self.pubsub, &mut self.subscriptions, args).await,
+            "SUNSUBSCRIBE" => pubsub_cmds::sunsubscribe(&self.pubsub, &mut self.subscriptions, args).await,
+            _ => pubsub_cmds::punsubscribe(&self.pubsub, &mut self.subscriptions, args).await,
+        };
+        self.sync_subscriptions();
+
+        let last = match responses.pop() {
+            Some(last) => last,
+            // Unsubscribing with no active subscriptions still gets a reply
+            None => {
+                let count = if cmd_name == "SUNSUBSCRIBE" {
+                    self.subscriptions.shard_channels.len()
+                } else {
+                    self.subscriptions.channels.len() + self.subscriptions.patterns.len()
+                };
+                return RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(cmd_name.to_lowercase().into_bytes())),
+                    RespValue::BulkString(None),
+                    RespValue::Integer(count as i64),
+                ]));
+            }
+        };
+        for response in responses {
+            if let Err(e) = self.write_response(response).await {
+                error!("Failed to write subscription reply: {}", e);
+            }
+        }
+        last
+    }
+
+    /// Start or stop message forwarders and update CLIENT LIST after the
+    /// subscriptions changed
+    fn sync_subscriptions(&mut self) {
+        self.subscriber.sync(&self.subscriptions);
+        self.client_registry.set_subscriptions(
+            self.client_id,
+            self.subscriptions.channels.len(),
+            self.subscriptions.patterns.len(),
+            self.subscriptions.shard_channels.len(),
+        );
+    }
+
+    /// HELLO [protover [AUTH username password] [SETNAME clientname]]
+    fn handle_hello(&mut self, args: &[Vec<u8>]) -> RespValue {
+        let mut resp3 = self.resp3;
+        if let Some(version) = args.first() {
+            match std::str::from_utf8(version).ok().and_then(|v| v.parse::<i64>().ok()) {
+                Some(2) => resp3 = false,
+                Some(3) => resp3 = true,
+                Some(_) => return RespValue::Error("NOPROTO unsupported protocol version".to_string()),
+                None => return RespValue::Error("ERR Protocol version is not an integer or out of range".to_string()),
+            }
+        }
+
+        let mut name = None;
+        let mut i = 1;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            // Every connection is the default user, which takes any password
+            if option == "AUTH" && i + 2 < args.len() {
+                i += 3;
+            } else if option == "SETNAME" && i + 1 < args.len() {
+                name = Some(String::from_utf8_lossy(&args[i + 1]).to_string());
+                i += 2;
+            } else {
+                return RespValue::Error(format!(
+                    "ERR Syntax error in HELLO option '{}'",
+                    String::from_utf8_lossy(&args[i])
+                ));
+            }
+        }
+
+        self.resp3 = resp3;
+        self.client_registry.set_resp(self.client_id, if resp3 { 3 } else { 2 });
+        if let Some(name) = name {
+            self.client_registry.set_name(self.client_id, name);
+        }
+
+        let bulk = |s: &str| RespValue::BulkString(Some(s.as_bytes().to_vec()));
+        let fields = vec![
+            ("server", bulk("redis")),
+            ("version", bulk("7.0.0-rust")),
+            ("proto", RespValue::Integer(if resp3 { 3 } else { 2 })),
+            ("id", RespValue::Integer(self.client_id as i64)),
+            ("mode", bulk(if self.cluster.enabled { "cluster" } else { "standalone" })),
+            ("role", bulk(if self.repl_info.is_master() { "master" } else { "replica" })),
+            ("modules", RespValue::Array(Some(Vec::new()))),
+        ];
+        if resp3 {
+            RespValue::Map(fields.into_iter().map(|(k, v)| (bulk(k), v)).collect())
+        } else {
+            RespValue::Array(Some(fields.into_iter().flat_map(|(k, v)| [bulk(k), v]).collect()))
+        }
+    }
+
+    /// Publish this connection's buffer sizes for CLIENT LIST
+    fn update_stats(&self) {
+        let obl = self.stream.buffer().len();
+        let memory = ClientMemory {
+            qbuf: self.buffer.len(),
+            qbuf_free: self.buffer.capacity() - self.buffer.len(),
+            obl,
+            oll: self.push_rx.len(),
+            omem: obl,
+            tot_mem: self.buffer.capacity() + obl,
+        };
+        let multi = if self.transaction.in_multi {
+            self.transaction.commands.len() as i64
+        } else {
+            -1
+        };
+        self.client_registry.update_stats(self.client_id, memory, multi);
+    }
+
+    /// Remember the keys a read command used for CLIENT TRACKING
+    fn track_reads(&self, cmd_name: &str, args: &[Vec<u8>], response: &RespValue) {
+        let tracking = self.client_registry.tracking();
+        if matches!(response, RespValue::Error(_))
+            || crate::cluster::keys::is_write_command(cmd_name)
+            || !tracking.is_tracking(self.client_id)
+        {
+            return;
+        }
+        let keys = crate::cluster::keys::command_keys(cmd_name, args);
+        tracking.record_reads(self.client_id, &keys);
+    }
+
+    /// Prepare a pushed frame for the client. A bare `sunsubscribe
+    /// <channel>` means the channel's slot moved away: drop the
+    /// subscription and add the count, or skip the frame if the client
+    /// already unsubscribed.
+    fn complete_push(&mut self, push: RespValue) -> Option<RespValue> {
+        let channel = match push.as_array() {
+            Some([kind, channel]) if kind.as_bulk_string() == Some(&b"sunsubscribe"[..]) => {
+                String::from_utf8_lossy(channel.as_bulk_string()?).to_string()
+            }
+            _ => return self.push_frame(push),
+        };
+        if !self.subscriptions.remove_shard_channel(&channel) {
+            return None;
+        }
+        self.sync_subscriptions();
+        self.push_frame(RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"sunsubscribe".to_vec())),
+            RespValue::BulkString(Some(channel.into_bytes())),
+            RespValue::Integer(self.subscriptions.shard_channels.len() as i64),
+        ])))
+    }
+
+    /// Frame a push for the connection's protocol. RESP3 clients get push
+    /// frames; RESP2 clients get pub/sub messages as arrays, and tracking
+    /// invalidations only as messages on __redis__:invalidate.
+    fn push_frame(&self, push: RespValue) -> Option<RespValue> {
+        match push {
+            RespValue::Push(items) if self.resp3 => Some(RespValue::Push(items)),
+            RespValue::Push(mut items) => {
+                let subscribed = self.subscriptions.channels.iter().any(|c| c == INVALIDATE_CHANNEL);
+                if !subscribed || items.first().and_then(|kind| kind.as_bulk_string()) != Some(&b"invalidate"[..]) {
+                    return None;
+                }
+                let keys = match items.pop() {
+                    Some(RespValue::Null) | None => RespValue::BulkString(None),
+                    Some(keys) => keys,
+                };
+                Some(RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(b"message".to_vec())),
+                    RespValue::BulkString(Some(INVALIDATE_CHANNEL.as_bytes().to_vec())),
+                    keys,
+                ])))
+            }
+            RespValue::Array(Some(items)) if self.resp3 => Some(RespValue::Push(items)),
+            other => Some(other),
+        }
+    }
+
+    /// Hand an SPUBLISH executed here to the other nodes of the shard
+    fn forward_shard_message(&self, args: &[Vec<u8>], reply: &RespValue) {
+        if self.cluster.enabled
+            && args.len() == 3
+            && args[0].eq_ignore_ascii_case(b"SPUBLISH")
+            && matches!(reply, RespValue::Integer(_))
+        {
+            self.cluster_bus.publish_shard(&args[1], &args[2]);
+        }
+    }
+
+    /// Track REPLCONF listening-port / capa sent by a replica
+    fn record_replconf(&mut self, args: &[Vec<u8>]) {
+        for pair in args.chunks(2) {
+            if pair.len() != 2 {
+                break;
+            }
+            let option = String::from_utf8_lossy(&pair[0]).to_lowercase();
+            let value = String::from_utf8_lossy(&pair[1]).to_lowercase();
+            match option.as_str() {
+                "listening-port" => self.replica_listening_port = value.parse().ok(),
+                "capa" if value == "eof" => self.replica_capa_eof = true,
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle PSYNC: reply with FULLRESYNC/CONTINUE and schedule the payload
+    async fn handle_psync(&mut self, args: &[Vec<u8>]) -> RespValue {
+        use crate::commands::replication_cmds::psync;
+
+        let response = psync(&self.repl_info, &self.repl_backlog, args.to_vec()).await;
+        if let RespValue::SimpleString(ref s) = response {
+            if s.starts_with("FULLRESYNC") {
+                self.pending_sync = Some(None);
+            } else if s.starts_with("CONTINUE") {
+                let offset = std::str::from_utf8(&args[1])
+                    .ok()
+                    .and_then(|o| o.parse().ok())
+                    .unwrap_or(0);
+                self.pending_sync = Some(Some(offset));
+            }
+        }
+        response
+    }
+
+    /// Send the PSYNC reply and then the RDB payload (full resync, whose
+    /// reply is remade with the snapshot's offset) or the backlog tail
+    /// (partial resync). Either way the replication stream is held from
+    /// where that data ends until the replica is handed over.
+    async fn send_sync_payload(&mut self, sync: Option<u64>, reply: RespValue) -> anyhow::Result<()> {
+        if let Some(offset) = sync {
+            let tail = {
+                let _paused = self.repl_info.write_gate().pause().await;
+                self.propagator.buffer_replicas(&[self.client_id]);
+                SyncHandler::new(Arc::clone(&self.repl_backlog)).get_partial_sync_data(offset)
+            };
+            // The backlog may have moved on since PSYNC looked; the replica
+            // then reconnects for a full resync
+            let tail = tail.ok_or_else(|| anyhow::anyhow!("Backlog no longer holds offset {}", offset))?;
+            self.write_response(reply).await?;
+            for data in tail {
+                self.stream.write_all(&data).await?;
+            }
+            self.stream.flush().await?;
+            return Ok(());
+        }
+
+        // FULLRESYNC announces the offset the snapshot is taken at, so it
+        // waits for the snapshot
+        let source = SnapshotSource::new(
+            Arc::clone(&self.db),
+            Arc::clone(&self.repl_info),
+            Arc::clone(&self.propagator),
+        );
+        let diskless = self.replica_capa_eof
+            && self.app_config.get_bool("repl-diskless-sync").unwrap_or(false);
+
+        if diskless {
+            let delay = self.app_config.get_int("repl-diskless-sync-delay").unwrap_or(5).max(0);
+            info!("Full resync: streaming snapshot to replica (delay {}s)", delay);
+            let mut chunks = self
+                .diskless_sync
+                .register(source, self.client_id, Duration::from_secs(delay as u64));
+            let offset = diskless::snapshot_offset(&mut chunks).await?;
+            self.write_fullresync(offset).await?;
+            let sent = diskless::send_eof_payload(&mut self.stream, chunks).await?;
+            info!("Diskless full resync sent {} bytes", sent);
+        } else {
+            let (snapshot, offset) = source.capture(&[self.client_id]).await?;
+            self.write_fullresync(offset).await?;
+            info!("Full resync: saving {} for replica", self.config.rdb_filename);
+            RdbSerializer::save(&Arc::new(snapshot), &self.config.rdb_filename).await?;
+            let rdb = tokio::fs::read(&self.config.rdb_filename).await?;
+            diskless::send_sized_payload(&mut self.stream, &rdb).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reply FULLRESYNC with the offset the replica's snapshot was taken at
+    async fn write_fullresync(&mut self, offset: u64) -> anyhow::Result<()> {
+        let reply = SyncHandler::generate_fullresync_response(&self.repl_info.replication_id(), offset);
+        self.write_response(reply).await?;
         self.stream.flush().await?;
         Ok(())
     }
 
+    /// If this connection became a replication link, the replica's
+    /// address (peer ip, announced listening port)
+    pub fn replica_handoff(&self) -> Option<(String, u16)> {
+        if !self.replica_handoff {
+            return None;
+        }
+        let ip = self
+            .stream
+            .get_ref()
+            .peer_addr()
+            .map(|a| a.ip().to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        Some((ip, self.replica_listening_port.unwrap_or(0)))
+    }
+
+    /// Consume the connection, returning the underlying socket
+    pub fn into_stream(self) -> TcpStream {
+        self.stream.into_inner()
+    }
+
     pub fn current_db(&self) -> usize {
         self.db_index
     }
@@ -363,68 +994,76 @@ impl Connection {
     /// Check if command needs cluster redirection
     /// Returns Some(error) if redirection is needed, None if command can execute locally
     fn check_cluster_redirection(&self, cmd_name: &str, cmd_args: &[Vec<u8>]) -> Option<RespValue> {
-        use crate::cluster::{key_hash_slot, check_slot_ownership};
-
-        // Skip commands that don't access keys
-        let keyless_commands = [
-            "PING", "ECHO", "SELECT", "FLUSHDB", "FLUSHALL", "DBSIZE",
-            "INFO", "TIME", "LASTSAVE", "SAVE", "BGSAVE",
-            "SHUTDOWN", "CLIENT", "CONFIG", "SLOWLOG", "ROLE",
-            "MULTI", "EXEC", "DISCARD", "WATCH", "UNWATCH"
-        ];
+        use crate::cluster::{check_multi_key_slot, command_keys, is_write_command, key_hash_slot, served_by_my_master};
 
-        if keyless_commands.contains(&cmd_name) {
+        // EXEC runs the whole transaction on this node, so every queued
+        // command counts; the rest only name their own keys
+        let (keys, write) = if cmd_name == "EXEC" {
+            let mut keys = Vec::new();
+            let mut write = false;
+            for queued in &self.transaction.commands {
+                let name = String::from_utf8_lossy(&queued[0]).to_uppercase();
+                keys.extend(command_keys(&name, queued));
+                write |= is_write_command(&name);
+            }
+            (keys, write)
+        } else {
+            (command_keys(cmd_name, cmd_args), is_write_command(cmd_name))
+        };
+        if keys.is_empty() {
             return None;
         }
 
-        // Extract first key based on command
-        let key_index = match cmd_name {
-            // Commands where key is at position 1
-            "GET" | "SET" | "DEL" | "EXISTS" | "EXPIRE" | "TTL" | "TYPE" |
-            "APPEND" | "STRLEN" | "INCR" | "DECR" | "LPUSH" | "RPUSH" |
-            "LPOP" | "RPOP" | "LLEN" | "HSET" | "HGET" | "HDEL" | "HLEN" |
-            "SADD" | "SREM" | "SMEMBERS" | "SCARD" | "ZADD" | "ZREM" |
-            "ZCARD" | "ZSCORE" | "GETEX" | "GETDEL" | "SETEX" | "SETNX" |
-            "INCRBY" | "DECRBY" | "INCRBYFLOAT" | "PSETEX" => 0,
-
-            // Multi-key commands (we'll check if they're in same slot)
-            "MGET" | "MSET" | "MSETNX" => {
-                // For multi-key commands, use check_multi_key_slot
-                if cmd_args.len() > 1 {
-                    use crate::cluster::check_multi_key_slot;
-                    let keys: Vec<&[u8]> = cmd_args.iter().skip(1).step_by(if cmd_name == "MGET" { 1 } else { 2 }).map(|k| k.as_slice()).collect();
-
-                    match check_multi_key_slot(&keys) {
-                        Ok(slot) => {
-                            // All keys in same slot, check if we own it
-                            return check_slot_ownership(&self.cluster, &cmd_args[1], self.asking);
-                        }
-                        Err(msg) => {
-                            // Keys in different slots - CROSSSLOT error
-                            return Some(RespValue::Error(msg));
-                        }
-                    }
-                }
+        if let Err(msg) = check_multi_key_slot(&keys) {
+            return Some(RespValue::Error(msg));
+        }
+
+        // Shard channels live on every node of the shard, replicas
+        // included, and are not migrated with the slot's keys: only MOVED
+        if matches!(cmd_name, "SPUBLISH" | "SSUBSCRIBE" | "SUNSUBSCRIBE") {
+            if served_by_my_master(&self.cluster, key_hash_slot(keys[0])) {
                 return None;
             }
+            return crate::cluster::check_slot_ownership(&self.cluster, &self.migration, keys[0], false);
+        }
 
-            // Commands we don't handle yet
-            _ => return None,
-        };
-
-        // Get the key
-        if cmd_args.len() <= key_index + 1 {
-            return None; // Not enough arguments
+        // A replica serves reads of its master's slots to READONLY clients
+        if self.readonly && !write && served_by_my_master(&self.cluster, key_hash_slot(keys[0])) {
+            return None;
         }
 
-        let key = &cmd_args[key_index + 1];
+        self.check_keys_redirection(&keys)
+    }
 
-        // Check slot ownership and return redirection if needed
-        check_slot_ownership(&self.cluster, key, self.asking)
+    /// MOVED / ASK / TRYAGAIN for keys of a single slot, looking them up
+    /// in the selected database while the slot is migrating
+    fn check_keys_redirection(&self, keys: &[&[u8]]) -> Option<RespValue> {
+        let instance = self.db.get_db(self.db_index);
+        crate::cluster::check_keys_redirection(&self.cluster, &self.migration, keys, self.asking, |key| {
+            let key = String::from_utf8_lossy(key);
+            instance.as_ref().is_some_and(|db| db.exists(&key))
+        })
     }
 
     /// Handle CLUSTER commands with access to cluster state
-    fn handle_cluster_command(&self, args: &[Vec<u8>]) -> RespValue {
+    /// Run a CLUSTER subcommand, logging the keys of any slots it dropped
+    /// as deletions so the AOF and replicas lose them too
+    async fn run_cluster_command(&mut self, args: &[Vec<u8>]) -> RespValue {
+        let mut dropped = Vec::new();
+        let response = self.handle_cluster_command(args, &mut dropped);
+        for (db_index, keys) in dropped.into_iter().enumerate().filter(|(_, keys)| !keys.is_empty()) {
+            let mut del = vec![b"DEL".to_vec()];
+            del.extend(keys.into_iter().map(String::into_bytes));
+            self.log_write_in(db_index, &del, &response).await;
+        }
+        response
+    }
+
+    fn handle_cluster_command(
+        &self,
+        args: &[Vec<u8>],
+        dropped: &mut crate::commands::cluster::DroppedKeys,
+    ) -> RespValue {
         use crate::commands::cluster::*;
         use crate::cluster::migration::*;
 
@@ -437,7 +1076,7 @@ impl Connection {
             Err(_) => return RespValue::Error("ERR invalid subcommand".to_string()),
         };
 
-        match subcommand.as_str() {
+        let reply = match subcommand.as_str() {
             "KEYSLOT" => {
                 if args.len() != 2 {
                     return RespValue::Error("ERR wrong number of arguments for 'cluster keyslot'".to_string());
@@ -468,7 +1107,14 @@ impl Connection {
                         _ => return RespValue::Error("ERR Invalid slot number".to_string()),
                     }
                 }
-                cluster_delslots(&self.cluster, slots)
+                let (reply, keys) = cluster_delslots(&self.cluster, &self.db, slots);
+                *dropped = keys;
+                reply
+            }
+            "FLUSHSLOTS" => {
+                let (reply, keys) = cluster_flushslots(&self.cluster, &self.db);
+                *dropped = keys;
+                reply
             }
             "SETSLOT" => {
                 if args.len() < 3 {
@@ -523,7 +1169,7 @@ impl Connection {
                     Some(c) => c,
                     _ => return RespValue::Error("ERR Invalid count".to_string()),
                 };
-                crate::commands::cluster::cluster_getkeysinslot(&self.cluster, slot, count)
+                cluster_getkeysinslot(&self.cluster, &self.db, self.db_index, slot, count)
             }
             "COUNTKEYSINSLOT" => {
                 if args.len() != 2 {
@@ -533,10 +1179,105 @@ impl Connection {
                     Some(s) if s < 16384 => s,
                     _ => return RespValue::Error("ERR Invalid slot number".to_string()),
                 };
-                crate::commands::cluster::cluster_countkeysinslot(&self.cluster, slot)
+                cluster_countkeysinslot(&self.cluster, &self.db, self.db_index, slot)
+            }
+            "ADDSLOTSRANGE" | "DELSLOTSRANGE" => {
+                if args.len() < 3 || args.len().is_multiple_of(2) {
+                    return RespValue::Error(format!(
+                        "ERR wrong number of arguments for 'cluster {}'",
+                        subcommand.to_lowercase()
+                    ));
+                }
+                let mut ranges = Vec::new();
+                for pair in args[1..].chunks(2) {
+                    let mut bounds = [0u16; 2];
+                    for (bound, arg) in bounds.iter_mut().zip(pair) {
+                        match std::str::from_utf8(arg).ok().and_then(|s| s.parse::<u16>().ok()) {
+                            Some(slot) if slot < 16384 => *bound = slot,
+                            _ => return RespValue::Error("ERR Invalid or out of range slot".to_string()),
+                        }
+                    }
+                    ranges.push((bounds[0], bounds[1]));
+                }
+                if subcommand == "ADDSLOTSRANGE" {
+                    cluster_addslotsrange(&self.cluster, ranges)
+                } else {
+                    let (reply, keys) = cluster_delslotsrange(&self.cluster, &self.db, ranges);
+                    *dropped = keys;
+                    reply
+                }
+            }
+            "MEET" => {
+                if args.len() != 3 {
+                    return RespValue::Error("ERR wrong number of arguments for 'cluster meet'".to_string());
+                }
+                cluster_meet(
+                    &self.cluster_bus,
+                    &String::from_utf8_lossy(&args[1]),
+                    &String::from_utf8_lossy(&args[2]),
+                )
+            }
+            "FORGET" => {
+                if args.len() != 2 {
+                    return RespValue::Error("ERR wrong number of arguments for 'cluster forget'".to_string());
+                }
+                cluster_forget(&self.cluster_bus, String::from_utf8_lossy(&args[1]).to_string())
             }
+            "REPLICATE" => {
+                if args.len() != 2 {
+                    return RespValue::Error("ERR wrong number of arguments for 'cluster replicate'".to_string());
+                }
+                cluster_replicate(
+                    &self.cluster,
+                    &self.db,
+                    &self.repl_info,
+                    &self.repl_backlog,
+                    &self.app_config,
+                    String::from_utf8_lossy(&args[1]).to_string(),
+                )
+            }
+            "RESET" => {
+                let hard = match args.get(1).map(|a| String::from_utf8_lossy(a).to_uppercase()) {
+                    None => false,
+                    Some(mode) if mode == "SOFT" && args.len() == 2 => false,
+                    Some(mode) if mode == "HARD" && args.len() == 2 => true,
+                    Some(_) => return RespValue::Error("ERR syntax error".to_string()),
+                };
+                cluster_reset(&self.cluster_bus, &self.db, &self.repl_info, &self.migration, hard)
+            }
+            "SHARDS" => cluster_shards(&self.cluster, &self.repl_info),
+            "FAILOVER" => {
+                if args.len() > 2 {
+                    return RespValue::Error("ERR syntax error".to_string());
+                }
+                let mode = args.get(1).map(|a| String::from_utf8_lossy(a).to_string());
+                cluster_failover(&self.cluster_bus, mode.as_deref())
+            }
+            "LINKS" => cluster_links(&self.cluster_bus),
+            "COUNT-FAILURE-REPORTS" => {
+                if args.len() != 2 {
+                    return RespValue::Error(
+                        "ERR wrong number of arguments for 'cluster count-failure-reports'".to_string(),
+                    );
+                }
+                cluster_count_failure_reports(&self.cluster_bus, String::from_utf8_lossy(&args[1]).to_string())
+            }
+            "SET-CONFIG-EPOCH" => {
+                if args.len() != 2 {
+                    return RespValue::Error(
+                        "ERR wrong number of arguments for 'cluster set-config-epoch'".to_string(),
+                    );
+                }
+                cluster_set_config_epoch(&self.cluster, &String::from_utf8_lossy(&args[1]))
+            }
+            "BUMPEPOCH" => cluster_bumpepoch(&self.cluster),
+            "SAVECONFIG" => cluster_saveconfig(&self.cluster_bus),
             _ => RespValue::Error(format!("ERR Unknown CLUSTER subcommand '{}'", subcommand)),
-        }
+        };
+
+        // Persist whatever the command changed before replying
+        self.cluster_bus.flush_config();
+        reply
     }
 }
 
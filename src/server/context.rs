@@ -0,0 +1,34 @@
+// Shared server state handed to every connection
+
+use super::client_info::ClientRegistry;
+use super::config::ServerConfig;
+use super::slowlog::SlowLog;
+use crate::cluster::{ClusterBus, ClusterState, MigrationManager};
+use crate::config::Config;
+use crate::persistence::aof::AofManager;
+use crate::pubsub::PubSub;
+use crate::replication::{CommandPropagator, DisklessSyncScheduler, ReplicationBacklog, ReplicationInfo};
+use crate::scripting::ScriptCache;
+use crate::storage::db::Database;
+use std::sync::Arc;
+
+/// Handles to the state every connection of one server shares; cloning
+/// copies the handles, not the state
+#[derive(Clone)]
+pub struct ServerContext {
+    pub db: Arc<Database>,
+    pub pubsub: Arc<PubSub>,
+    pub config: Arc<ServerConfig>,
+    pub app_config: Arc<Config>,
+    pub aof: Arc<AofManager>,
+    pub script_cache: Arc<ScriptCache>,
+    pub repl_info: Arc<ReplicationInfo>,
+    pub repl_backlog: Arc<ReplicationBacklog>,
+    pub propagator: Arc<CommandPropagator>,
+    pub client_registry: Arc<ClientRegistry>,
+    pub slowlog: Arc<SlowLog>,
+    pub cluster: Arc<ClusterState>,
+    pub migration: Arc<MigrationManager>,
+    pub cluster_bus: Arc<ClusterBus>,
+    pub diskless_sync: Arc<DisklessSyncScheduler>,
+}
@@ -3,35 +3,29 @@
 use super::client_info::ClientRegistry;
 use super::config::ServerConfig;
 use super::connection::Connection;
+use super::context::ServerContext;
 use super::slowlog::SlowLog;
-use crate::cluster::{ClusterState, MigrationManager, load_cluster_config};
-use crate::config::Config;
+use crate::cluster::{ClusterBus, ClusterState, MigrationManager, load_cluster_config};
+use crate::config::{Config, ConfigValue};
 use crate::persistence::aof::{AofManager, AofReader};
 use crate::persistence::rdb::RdbDeserializer;
 use crate::pubsub::PubSub;
-use crate::replication::{ReplicationInfo, ReplicationBacklog, CommandPropagator};
+use crate::replication::{ReplicationInfo, ReplicationBacklog, CommandPropagator, DisklessSyncScheduler};
+use crate::replication::replication_info::ReplicaInfo;
 use crate::scripting::ScriptCache;
 use crate::storage::db::Database;
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Semaphore;
 use tracing::{error, info, warn};
 
+/// How often hash fields whose TTL passed are removed in the background
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct RedisServer {
-    config: Arc<ServerConfig>,
-    app_config: Arc<Config>,
-    db: Arc<Database>,
-    pubsub: Arc<PubSub>,
-    aof: Arc<AofManager>,
-    script_cache: Arc<ScriptCache>,
-    repl_info: Arc<ReplicationInfo>,
-    repl_backlog: Arc<ReplicationBacklog>,
-    propagator: Arc<CommandPropagator>,
-    client_registry: Arc<ClientRegistry>,
-    slowlog: Arc<SlowLog>,
-    cluster: Arc<ClusterState>,
-    migration: Arc<MigrationManager>,
+    ctx: ServerContext,
     /// Limit max concurrent connections
     limit_connections: Arc<Semaphore>,
 }
@@ -39,7 +33,21 @@ pub struct RedisServer {
 impl RedisServer {
     pub async fn new(config: ServerConfig) -> anyhow::Result<Self> {
         let max_connections = config.max_clients;
-        let db = Arc::new(Database::new(config.databases));
+        let app_config = Arc::new(
+            Config::new()
+                .with_static("bind", ConfigValue::String(config.bind.clone()))
+                .with_static("port", ConfigValue::Int(config.port as i64))
+                .with_static("cluster-enabled", ConfigValue::Bool(config.cluster_enabled))
+                .with_static("cluster-config-file", ConfigValue::String(config.cluster_config_file.clone()))
+                .with_static("cluster-node-timeout", ConfigValue::Int(config.cluster_node_timeout as i64)),
+        );
+        // In cluster mode keys are also indexed by hash slot for resharding
+        let db = if config.cluster_enabled {
+            Database::with_slot_index(config.databases)
+        } else {
+            Database::new(config.databases)
+        };
+        let db = Arc::new(db.with_encoding(app_config.encoding_limits()));
 
         // Load persistence data (RDB first, then AOF)
         if config.rdb_enabled && std::path::Path::new(&config.rdb_filename).exists() {
@@ -68,6 +76,7 @@ impl RedisServer {
             }
         }
 
+        let repl_info = Arc::new(ReplicationInfo::new());
         let repl_backlog = Arc::new(ReplicationBacklog::new());
         let propagator = Arc::new(CommandPropagator::new(Arc::clone(&repl_backlog)));
 
@@ -84,31 +93,63 @@ impl RedisServer {
             }
         }
 
+        let pubsub = Arc::new(PubSub::new());
+        db.keyspace_events().attach(Arc::clone(&pubsub));
+        if let Some(value) = app_config.get("notify-keyspace-events") {
+            if !db.keyspace_events().configure(&value) {
+                warn!("Ignoring notify-keyspace-events '{}': unknown or unsupported event class", value);
+            }
+        }
+        let client_registry = Arc::new(ClientRegistry::new());
+        db.keyspace_events().attach_tracking(Arc::clone(client_registry.tracking()));
+        let cluster_bus = Arc::new(ClusterBus::new(
+            Arc::clone(&cluster),
+            Arc::clone(&migration),
+            Arc::clone(&app_config),
+        )
+        .with_replication(Arc::clone(&db), Arc::clone(&repl_info), Arc::clone(&repl_backlog))
+        .with_pubsub(Arc::clone(&pubsub)));
+
         Ok(Self {
-            db,
-            pubsub: Arc::new(PubSub::new()),
-            aof: Arc::new(aof),
-            app_config: Arc::new(Config::new()),
-            script_cache: Arc::new(ScriptCache::new()),
-            repl_info: Arc::new(ReplicationInfo::new()),
-            repl_backlog,
-            propagator,
-            client_registry: Arc::new(ClientRegistry::new()),
-            slowlog: Arc::new(SlowLog::new()),
-            cluster,
-            migration,
-            config: Arc::new(config),
+            ctx: ServerContext {
+                db,
+                pubsub,
+                aof: Arc::new(aof),
+                app_config,
+                script_cache: Arc::new(ScriptCache::new()),
+                repl_info,
+                repl_backlog,
+                propagator,
+                client_registry,
+                slowlog: Arc::new(SlowLog::new()),
+                cluster,
+                migration,
+                cluster_bus,
+                diskless_sync: Arc::new(DisklessSyncScheduler::new()),
+                config: Arc::new(config),
+            },
             limit_connections: Arc::new(Semaphore::new(max_connections)),
         })
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
-        let listener = TcpListener::bind(self.config.addr()).await?;
+        let listener = TcpListener::bind(self.ctx.config.addr()).await?;
+        self.serve(listener).await
+    }
+
+    /// Accept clients on a listener bound by the caller, e.g. to port 0
+    pub async fn serve(&self, listener: TcpListener) -> anyhow::Result<()> {
         info!(
             "Redis-Rust server listening on {}",
-            self.config.addr()
+            listener.local_addr()?
         );
 
+        if self.ctx.cluster.enabled {
+            self.ctx.cluster_bus.start(&self.ctx.config.bind, self.ctx.config.port).await?;
+        }
+
+        self.spawn_active_expire();
+
         loop {
             // Wait for permit to accept new connection
             let permit = self
@@ -130,95 +171,87 @@ impl RedisServer {
 
             // Get socket file descriptor (for client tracking)
             let fd = socket.as_raw_fd() as u64;
-            let client_id = self.client_registry.register(addr.to_string(), fd);
-
-            let db = self.db.clone();
-            let pubsub = self.pubsub.clone();
-            let config = self.config.clone();
-            let app_config = self.app_config.clone();
-            let aof = self.aof.clone();
-            let script_cache = self.script_cache.clone();
-            let repl_info = self.repl_info.clone();
-            let repl_backlog = self.repl_backlog.clone();
-            let propagator = self.propagator.clone();
-            let client_registry = self.client_registry.clone();
-            let client_registry_for_cleanup = client_registry.clone();
-            let slowlog = self.slowlog.clone();
-            let cluster = self.cluster.clone();
-            let migration = self.migration.clone();
+            let client_id = self.ctx.client_registry.register(addr.to_string(), fd);
+            if let Ok(laddr) = socket.local_addr() {
+                self.ctx.client_registry.set_local_addr(client_id, laddr.to_string());
+            }
+
+            let ctx = self.ctx.clone();
+            let client_registry = self.ctx.client_registry.clone();
 
             // Spawn a new task to handle this connection
             tokio::spawn(async move {
-                if let Err(e) = Self::handle_connection(
-                    socket,
-                    client_id,
-                    db,
-                    pubsub,
-                    config,
-                    app_config,
-                    aof,
-                    script_cache,
-                    repl_info,
-                    repl_backlog,
-                    propagator,
-                    client_registry,
-                    slowlog,
-                    cluster,
-                    migration,
-                ).await {
+                if let Err(e) = Self::handle_connection(socket, client_id, ctx).await {
                     error!("Connection error: {}", e);
                 }
                 // Unregister client when connection closes
-                client_registry_for_cleanup.unregister(client_id);
+                client_registry.unregister(client_id);
                 // Drop permit when connection closes
                 drop(permit);
             });
         }
     }
 
-    async fn handle_connection(
-        socket: TcpStream,
-        client_id: u64,
-        db: Arc<Database>,
-        pubsub: Arc<PubSub>,
-        config: Arc<ServerConfig>,
-        app_config: Arc<Config>,
-        aof: Arc<AofManager>,
-        script_cache: Arc<ScriptCache>,
-        repl_info: Arc<ReplicationInfo>,
-        repl_backlog: Arc<ReplicationBacklog>,
-        propagator: Arc<CommandPropagator>,
-        client_registry: Arc<ClientRegistry>,
-        slowlog: Arc<SlowLog>,
-        cluster: Arc<ClusterState>,
-        migration: Arc<MigrationManager>,
-    ) -> anyhow::Result<()> {
-        let mut connection = Connection::new(
-            socket,
-            client_id,
-            db,
-            pubsub,
-            config,
-            app_config,
-            aof,
-            script_cache,
-            repl_info,
-            repl_backlog,
-            propagator,
-            client_registry,
-            slowlog,
-            cluster,
-            migration,
-        );
-        connection.process().await
+    /// Periodically remove expired hash fields, until the server's
+    /// database is dropped
+    fn spawn_active_expire(&self) {
+        let db = Arc::downgrade(&self.ctx.db);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                match db.upgrade() {
+                    Some(db) => {
+                        db.active_expire_hash_fields();
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+
+    async fn handle_connection(socket: TcpStream, client_id: u64, ctx: ServerContext) -> anyhow::Result<()> {
+        let link_repl_info = Arc::clone(&ctx.repl_info);
+        let link_propagator = Arc::clone(&ctx.propagator);
+        let mut connection = Connection::new(socket, client_id, ctx);
+        let processed = connection.process().await;
+
+        // A replica finished its sync: keep the socket for propagation
+        let Some((ip, port)) = connection.replica_handoff() else {
+            // Nothing to hold the stream for if a sync broke off
+            link_propagator.discard_buffer(client_id);
+            return processed;
+        };
+        let offset = link_repl_info.master_offset();
+        info!("Replica {}:{} synchronized at offset {}", ip, port, offset);
+        link_repl_info.add_replica(ReplicaInfo {
+            id: client_id.to_string(),
+            ip: ip.clone(),
+            port,
+            offset,
+            last_interaction: std::time::Instant::now(),
+        });
+        link_propagator
+            .add_replica(client_id, connection.into_stream(), ip, port, offset)
+            .await;
+
+        Ok(())
     }
 
     pub fn db(&self) -> &Arc<Database> {
-        &self.db
+        &self.ctx.db
     }
 
     pub fn aof(&self) -> &Arc<AofManager> {
-        &self.aof
+        &self.ctx.aof
+    }
+
+    pub fn cluster(&self) -> &Arc<ClusterState> {
+        &self.ctx.cluster
+    }
+
+    pub fn cluster_bus(&self) -> &Arc<ClusterBus> {
+        &self.ctx.cluster_bus
     }
 }
 
@@ -229,7 +262,7 @@ mod tests {
     #[tokio::test]
     async fn test_server_creation() {
         let config = ServerConfig::default();
-        let server = RedisServer::new(config);
-        assert_eq!(server.config.port, 6379);
+        let server = RedisServer::new(config).await.unwrap();
+        assert_eq!(server.ctx.config.port, 6379);
     }
 }
@@ -2,12 +2,15 @@
 
 pub mod listener;
 pub mod connection;
+pub mod context;
 pub mod config;
 pub mod client_info;
 pub mod slowlog;
+pub mod tracking;
 
 pub use listener::RedisServer;
 pub use connection::Connection;
+pub use context::ServerContext;
 pub use config::ServerConfig;
 pub use client_info::{ClientInfo, ClientRegistry};
 pub use slowlog::{SlowLog, SlowLogEntry};
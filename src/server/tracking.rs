@@ -0,0 +1,386 @@
+// Client-side caching (CLIENT TRACKING)
+//
+// In the default mode the server remembers which keys each tracking client
+// read, and the first write to one of them sends that client an
+// `invalidate` push and forgets the entry: the client has to read the key
+// again to hear about the next change. BCAST clients instead hear about
+// every key matching one of their prefixes, without anything being
+// remembered per key. Pushes go to the client itself over RESP3, or to the
+// REDIRECT client, which RESP2 clients subscribe to __redis__:invalidate on.
+
+use crate::protocol::RespValue;
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+
+/// Channel RESP2 clients receive redirected invalidations on
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+tokio::task_local! {
+    /// The client whose command is running, so NOLOOP can skip it
+    static CURRENT_CLIENT: u64;
+}
+
+/// Run a client's command, attributing the writes it makes to the client
+pub async fn scope<F: Future>(client_id: u64, command: F) -> F::Output {
+    CURRENT_CLIENT.scope(client_id, command).await
+}
+
+/// Options of CLIENT TRACKING ON
+#[derive(Debug, Clone, Default)]
+pub struct TrackingOptions {
+    pub redirect: Option<u64>,
+    pub bcast: bool,
+    pub prefixes: Vec<Vec<u8>>,
+    pub optin: bool,
+    pub optout: bool,
+    pub noloop: bool,
+}
+
+struct TrackedClient {
+    options: TrackingOptions,
+    /// CLIENT CACHING yes/no, applying to the next command only
+    caching: Option<bool>,
+    /// Whether `caching` was set by the command that just ran
+    caching_fresh: bool,
+}
+
+pub struct Tracking {
+    /// Clients with tracking enabled
+    clients: DashMap<u64, TrackedClient>,
+    /// Push queues of all connected clients, which may be redirect targets
+    queues: DashMap<u64, mpsc::UnboundedSender<RespValue>>,
+    /// Keys read by default-mode clients, with the clients that read them
+    keys: DashMap<String, HashSet<u64>>,
+    /// Number of tracking clients; writes skip invalidation while it is 0
+    active: AtomicUsize,
+}
+
+impl Tracking {
+    pub fn new() -> Self {
+        Self {
+            clients: DashMap::new(),
+            queues: DashMap::new(),
+            keys: DashMap::new(),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Deliver this client's pushes through `queue`
+    pub fn register_client(&self, id: u64, queue: mpsc::UnboundedSender<RespValue>) {
+        self.queues.insert(id, queue);
+    }
+
+    /// Forget a disconnected client. Its entries in the key table are
+    /// dropped lazily, when the keys are invalidated.
+    pub fn remove_client(&self, id: u64) {
+        self.queues.remove(&id);
+        self.disable(id);
+    }
+
+    /// CLIENT TRACKING ON. Enabling it again keeps the mode and adds
+    /// prefixes; changing BCAST, OPTIN or OPTOUT needs a TRACKING OFF first.
+    pub fn enable(&self, id: u64, options: TrackingOptions) -> Result<(), String> {
+        if let Some(redirect) = options.redirect {
+            if !self.queues.contains_key(&redirect) {
+                return Err("ERR The client ID you want redirect to does not exist".to_string());
+            }
+        }
+
+        let mut prefixes = self
+            .clients
+            .get(&id)
+            .map(|client| {
+                let current = &client.options;
+                if current.bcast != options.bcast
+                    || current.optin != options.optin
+                    || current.optout != options.optout
+                {
+                    return Err("ERR You can't switch BCAST, OPTIN or OPTOUT mode before disabling tracking for this client, and then re-enabling it with a different mode.".to_string());
+                }
+                Ok(current.prefixes.clone())
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        // A key must match at most one prefix of a client
+        for prefix in &options.prefixes {
+            if let Some(other) = prefixes
+                .iter()
+                .find(|other| *other != prefix && (other.starts_with(prefix) || prefix.starts_with(other)))
+            {
+                return Err(format!(
+                    "ERR Prefix '{}' overlaps with an existing prefix '{}'. Prefixes for a single client must not overlap.",
+                    String::from_utf8_lossy(prefix),
+                    String::from_utf8_lossy(other)
+                ));
+            }
+            if !prefixes.contains(prefix) {
+                prefixes.push(prefix.clone());
+            }
+        }
+
+        let options = TrackingOptions { prefixes, ..options };
+        let client = TrackedClient { options, caching: None, caching_fresh: false };
+        if self.clients.insert(id, client).is_none() {
+            self.active.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// CLIENT TRACKING OFF
+    pub fn disable(&self, id: u64) {
+        if self.clients.remove(&id).is_some() {
+            self.active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_tracking(&self, id: u64) -> bool {
+        self.clients.contains_key(&id)
+    }
+
+    /// CLIENT GETREDIR: the redirect target, 0 without one, -1 when not tracking
+    pub fn redirect(&self, id: u64) -> i64 {
+        match self.clients.get(&id) {
+            Some(client) => client.options.redirect.map_or(0, |target| target as i64),
+            None => -1,
+        }
+    }
+
+    /// CLIENT CACHING yes|no, for the client's next command
+    pub fn set_caching(&self, id: u64, yes: bool) -> Result<(), String> {
+        let mut client = match self.clients.get_mut(&id) {
+            Some(client) if client.options.optin || client.options.optout => client,
+            _ => {
+                return Err("ERR CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled".to_string())
+            }
+        };
+        if yes && !client.options.optin {
+            return Err("ERR CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.".to_string());
+        }
+        if !yes && !client.options.optout {
+            return Err("ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.".to_string());
+        }
+        client.caching = Some(yes);
+        client.caching_fresh = true;
+        Ok(())
+    }
+
+    /// Called after each command outside MULTI: CLIENT CACHING only
+    /// covers the command following it
+    pub fn end_command(&self, id: u64) {
+        if let Some(mut client) = self.clients.get_mut(&id) {
+            if client.caching_fresh {
+                client.caching_fresh = false;
+            } else {
+                client.caching = None;
+            }
+        }
+    }
+
+    /// Remember that a client read `keys`, if its mode tracks this read
+    pub fn record_reads(&self, id: u64, keys: &[&[u8]]) {
+        let tracked = match self.clients.get(&id) {
+            Some(client) if !client.options.bcast => {
+                if client.options.optin {
+                    client.caching == Some(true)
+                } else if client.options.optout {
+                    client.caching != Some(false)
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        };
+        if !tracked {
+            return;
+        }
+        for key in keys {
+            self.keys
+                .entry(String::from_utf8_lossy(key).to_string())
+                .or_default()
+                .insert(id);
+        }
+    }
+
+    /// `key` was modified: tell the clients that may have cached it
+    pub fn invalidate(&self, key: &str) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let mut targets: Vec<u64> = self
+            .keys
+            .remove(key)
+            .map(|(_, readers)| readers.into_iter().collect())
+            .unwrap_or_default();
+        for client in self.clients.iter() {
+            let options = &client.options;
+            if options.bcast
+                && (options.prefixes.is_empty()
+                    || options.prefixes.iter().any(|prefix| key.as_bytes().starts_with(prefix)))
+            {
+                targets.push(*client.key());
+            }
+        }
+
+        let writer = CURRENT_CLIENT.try_with(|id| *id).ok();
+        let keys = RespValue::Array(Some(vec![RespValue::BulkString(Some(key.as_bytes().to_vec()))]));
+        for id in targets {
+            self.send(id, writer, keys.clone());
+        }
+    }
+
+    /// The whole keyspace was flushed: every tracking client drops its cache
+    pub fn invalidate_all(&self) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        self.keys.clear();
+        let ids: Vec<u64> = self.clients.iter().map(|client| *client.key()).collect();
+        for id in ids {
+            self.send(id, None, RespValue::Null);
+        }
+    }
+
+    /// Push an invalidation to a client or its redirect target
+    fn send(&self, id: u64, writer: Option<u64>, keys: RespValue) {
+        let redirect = match self.clients.get(&id) {
+            Some(client) if client.options.noloop && writer == Some(id) => return,
+            Some(client) => client.options.redirect,
+            // Stopped tracking since reading the key
+            None => return,
+        };
+
+        let target = redirect.unwrap_or(id);
+        let delivered = self.queues.get(&target).is_some_and(|queue| {
+            queue
+                .send(RespValue::Push(vec![RespValue::BulkString(Some(b"invalidate".to_vec())), keys]))
+                .is_ok()
+        });
+        if !delivered && redirect.is_some() {
+            if let Some(queue) = self.queues.get(&id) {
+                let _ = queue.send(RespValue::Push(vec![
+                    RespValue::BulkString(Some(b"tracking-redir-broken".to_vec())),
+                    RespValue::Integer(target as i64),
+                ]));
+            }
+        }
+    }
+}
+
+impl Default for Tracking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(tracking: &Tracking, id: u64) -> mpsc::UnboundedReceiver<RespValue> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tracking.register_client(id, tx);
+        rx
+    }
+
+    fn invalidated(rx: &mut mpsc::UnboundedReceiver<RespValue>) -> Option<RespValue> {
+        match rx.try_recv().ok()? {
+            RespValue::Push(mut frame) if frame[0].as_bulk_string() == Some(&b"invalidate"[..]) => frame.pop(),
+            other => panic!("unexpected push {:?}", other),
+        }
+    }
+
+    fn keys(names: &[&str]) -> RespValue {
+        RespValue::Array(Some(names.iter().map(|k| RespValue::BulkString(Some(k.as_bytes().to_vec()))).collect()))
+    }
+
+    #[test]
+    fn test_default_mode_invalidates_once() {
+        let tracking = Tracking::new();
+        let mut rx = client(&tracking, 1);
+        tracking.enable(1, TrackingOptions::default()).unwrap();
+
+        tracking.record_reads(1, &[b"a"]);
+        tracking.invalidate("b");
+        assert_eq!(invalidated(&mut rx), None);
+        tracking.invalidate("a");
+        assert_eq!(invalidated(&mut rx), Some(keys(&["a"])));
+
+        // Forgotten until read again
+        tracking.invalidate("a");
+        assert_eq!(invalidated(&mut rx), None);
+
+        tracking.record_reads(1, &[b"a"]);
+        tracking.invalidate_all();
+        assert_eq!(invalidated(&mut rx), Some(RespValue::Null));
+    }
+
+    #[test]
+    fn test_bcast_prefixes_and_redirect() {
+        let tracking = Tracking::new();
+        let _rx = client(&tracking, 1);
+        let mut redirect_rx = client(&tracking, 2);
+
+        let options = TrackingOptions {
+            redirect: Some(2),
+            bcast: true,
+            prefixes: vec![b"user:".to_vec()],
+            ..Default::default()
+        };
+        tracking.enable(1, options.clone()).unwrap();
+        assert_eq!(tracking.redirect(1), 2);
+
+        tracking.invalidate("user:1");
+        tracking.invalidate("order:1");
+        assert_eq!(invalidated(&mut redirect_rx), Some(keys(&["user:1"])));
+        assert_eq!(invalidated(&mut redirect_rx), None);
+
+        let overlapping = TrackingOptions { prefixes: vec![b"user:admin".to_vec()], ..options };
+        assert!(tracking.enable(1, overlapping).unwrap_err().contains("overlaps"));
+        assert!(tracking.enable(1, TrackingOptions::default()).is_err());
+        assert!(tracking
+            .enable(3, TrackingOptions { redirect: Some(9), ..Default::default() })
+            .is_err());
+    }
+
+    #[test]
+    fn test_optin_caching_covers_next_command() {
+        let tracking = Tracking::new();
+        let mut rx = client(&tracking, 1);
+        tracking.enable(1, TrackingOptions { optin: true, ..Default::default() }).unwrap();
+        assert!(tracking.set_caching(1, false).is_err());
+
+        tracking.record_reads(1, &[b"skipped"]);
+        tracking.end_command(1);
+        tracking.set_caching(1, true).unwrap();
+        tracking.end_command(1);
+        tracking.record_reads(1, &[b"cached"]);
+        tracking.end_command(1);
+        tracking.record_reads(1, &[b"after"]);
+
+        for key in ["skipped", "cached", "after"] {
+            tracking.invalidate(key);
+        }
+        assert_eq!(invalidated(&mut rx), Some(keys(&["cached"])));
+        assert_eq!(invalidated(&mut rx), None);
+    }
+
+    #[tokio::test]
+    async fn test_noloop_skips_own_writes() {
+        let tracking = Tracking::new();
+        let mut rx = client(&tracking, 1);
+        tracking.enable(1, TrackingOptions { noloop: true, ..Default::default() }).unwrap();
+
+        tracking.record_reads(1, &[b"k"]);
+        scope(1, async { tracking.invalidate("k") }).await;
+        assert_eq!(invalidated(&mut rx), None);
+
+        tracking.record_reads(1, &[b"k"]);
+        scope(2, async { tracking.invalidate("k") }).await;
+        assert_eq!(invalidated(&mut rx), Some(keys(&["k"])));
+    }
+}
@@ -1,9 +1,25 @@
 // Database implementation
 
-use super::types::RedisValue;
+use super::encoding::{self, EncodingLimits};
+use super::field_expires::FieldExpires;
+use super::key_waiters::KeyWaiters;
+use super::notify::{EventClass, KeyspaceEvents};
+use super::scan_index::ScanIndex;
+use super::slot_index::SlotIndex;
+use super::snapshot::{Capture, KeyState, PinnedSnapshot};
+use super::quicklist::QuickList;
+use super::types::{HashValue, RedisValue, SetValue, ZSet};
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Most hashes one active expire tick visits in a database
+const ACTIVE_EXPIRE_HASHES: usize = 200;
+/// Time one active expire tick may spend in a database
+const ACTIVE_EXPIRE_BUDGET: Duration = Duration::from_millis(25);
 
 /// Get current timestamp in milliseconds
 pub fn current_timestamp_ms() -> u64 {
@@ -16,9 +32,27 @@ pub fn current_timestamp_ms() -> u64 {
 /// A single database instance
 pub struct DbInstance {
     /// Main key-value storage
-    data: DashMap<String, RedisValue>,
+    data: DashMap<Arc<str>, RedisValue>,
     /// Expiration timestamps in milliseconds (key -> expiration_time_ms)
     expires: DashMap<String, u64>,
+    /// Expiration timestamps of hash fields, for hashes that have any
+    field_expires: DashMap<String, FieldExpires>,
+    /// (expiration time, key) for every field TTL set, earliest first, so
+    /// active expiry finds due hashes without a scan. Entries of TTLs that
+    /// were since changed or removed stay until their time and are skipped.
+    field_expiry_queue: Mutex<BTreeSet<(u64, String)>>,
+    /// Keys ordered by hash, which SCAN cursors walk
+    scan_index: ScanIndex,
+    /// Keys grouped by hash slot, only kept in cluster mode
+    slot_index: Option<SlotIndex>,
+    /// Where keyspace notifications go, shared by every database of a server
+    events: Arc<KeyspaceEvents>,
+    /// Number of this database, for the notification channel names
+    index: usize,
+    /// Encoding limits of the server, handed to every collection created here
+    encoding: Arc<EncodingLimits>,
+    /// Snapshots being copied, which keys are saved for before changing
+    captures: RwLock<Vec<Arc<Capture>>>,
 }
 
 impl DbInstance {
@@ -26,9 +60,136 @@ impl DbInstance {
         Self {
             data: DashMap::new(),
             expires: DashMap::new(),
+            field_expires: DashMap::new(),
+            field_expiry_queue: Mutex::new(BTreeSet::new()),
+            scan_index: ScanIndex::new(),
+            slot_index: None,
+            events: Arc::new(KeyspaceEvents::new()),
+            index: 0,
+            encoding: encoding::defaults(),
+            captures: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// A database that also indexes its keys by hash slot
+    pub fn with_slot_index() -> Self {
+        Self {
+            slot_index: Some(SlotIndex::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Publish keyspace notifications through `events` as database `index`
+    pub fn with_notifications(self, events: Arc<KeyspaceEvents>, index: usize) -> Self {
+        Self { events, index, ..self }
+    }
+
+    /// Build collections according to the server's `encoding` limits
+    pub fn with_encoding(self, encoding: Arc<EncodingLimits>) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// An empty list using this database's encoding limits
+    pub fn new_list(&self) -> QuickList {
+        QuickList::with_limits(Arc::clone(&self.encoding))
+    }
+
+    /// An empty hash using this database's encoding limits
+    pub fn new_hash(&self) -> HashValue {
+        HashValue::with_limits(Arc::clone(&self.encoding))
+    }
+
+    /// An empty set using this database's encoding limits
+    pub fn new_set(&self) -> SetValue {
+        SetValue::with_limits(Arc::clone(&self.encoding))
+    }
+
+    /// An empty sorted set using this database's encoding limits
+    pub fn new_zset(&self) -> ZSet {
+        ZSet::with_limits(Arc::clone(&self.encoding))
+    }
+
+    /// Publish a keyspace notification for `key`, if its class is enabled
+    pub fn notify(&self, class: EventClass, event: &str, key: &str) {
+        self.events.notify(self.index, class, event, key);
+    }
+
+    /// Before `key` is changed, save it for the snapshots being copied that
+    /// have not seen it yet. Must be called before any of the key's shards
+    /// is locked; see `copy_pinned`.
+    fn preserve(&self, key: &str) {
+        // Held throughout, so a capture that ended gets nothing saved late
+        let captures = self.captures.read().unwrap();
+        for capture in captures.iter() {
+            if capture.saved.contains_key(key) {
+                continue;
+            }
+            if let Entry::Vacant(slot) = capture.saved.entry(key.to_string()) {
+                slot.insert(self.key_state(key));
+            }
         }
     }
 
+    /// The value of `key` with its expiration and field TTLs, expired or not
+    fn key_state(&self, key: &str) -> Option<KeyState> {
+        let value = self.data.get(key)?.value().clone();
+        Some(KeyState {
+            value,
+            expire_at: self.expires.get(key).map(|at| *at),
+            field_expires: self.field_expires.get(key).map(|fields| fields.clone()),
+        })
+    }
+
+    /// Insert or overwrite a key. The key indexes are updated while the
+    /// key's shard is locked so they never disagree with `data`.
+    fn insert(&self, key: String, value: RedisValue) {
+        self.preserve(&key);
+        // Field TTLs only outlive the write for fields still in the hash
+        if let Some(mut fields) = self.field_expires.get_mut(&key) {
+            match &value {
+                RedisValue::Hash(hash) => fields.retain(|field| hash.contains_key(field)),
+                _ => fields.retain(|_| false),
+            }
+        }
+        self.field_expires.remove_if(&key, |_, fields| fields.is_empty());
+
+        let created = match self.data.entry(Arc::from(key)) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                None
+            }
+            Entry::Vacant(entry) => {
+                self.scan_index.add(entry.key());
+                if let Some(index) = &self.slot_index {
+                    index.add(entry.key());
+                }
+                let key = Arc::clone(entry.key());
+                entry.insert(value);
+                Some(key)
+            }
+        };
+        // Published once the shard is unlocked
+        if let Some(key) = created {
+            self.notify(EventClass::NEW, "new", &key);
+        }
+    }
+
+    /// Remove a key if `pred` holds for its value, keeping the key indexes in step
+    fn remove_if(&self, key: &str, pred: impl FnOnce(&RedisValue) -> bool) -> bool {
+        self.preserve(key);
+        let removed = self.data.remove_if(key, |key, value| {
+            let remove = pred(value);
+            if remove {
+                self.scan_index.remove(key);
+                if let Some(index) = &self.slot_index {
+                    index.remove(key);
+                }
+            }
+            remove
+        });
+        removed.is_some()
+    }
+
     /// Check if key is expired and remove it if so
     fn check_expired(&self, key: &str) -> bool {
         if let Some(expire_entry) = self.expires.get(key) {
@@ -36,14 +197,121 @@ impl DbInstance {
             if current_timestamp_ms() >= expire_time {
                 // Key has expired, remove it
                 drop(expire_entry); // Drop the reference before removal
-                self.data.remove(key);
+                self.remove_if(key, |_| true);
                 self.expires.remove(key);
+                self.field_expires.remove(key);
+                self.notify(EventClass::EXPIRED, "expired", key);
                 return true;
             }
         }
+        self.expire_hash_fields(key, current_timestamp_ms())
+    }
+
+    /// Remove the fields of the hash at `key` whose TTL has passed,
+    /// deleting the key once no field is left. Returns true if the key
+    /// was deleted.
+    fn expire_hash_fields(&self, key: &str, now: u64) -> bool {
+        let due = |fields: &FieldExpires| fields.next_expiry().is_some_and(|at| at <= now);
+        if !self.field_expires.get(key).is_some_and(|fields| due(&fields)) {
+            return false;
+        }
+        self.preserve(key);
+        let expired = match self.field_expires.get_mut(key) {
+            Some(mut fields) if due(&fields) => fields.pop_expired(now),
+            _ => return false,
+        };
+        self.field_expires.remove_if(key, |_, fields| fields.is_empty());
+
+        let emptied = match self.data.get_mut(key) {
+            Some(mut value) => match value.value_mut() {
+                RedisValue::Hash(hash) => {
+                    for field in &expired {
+                        hash.remove(field);
+                    }
+                    hash.is_empty()
+                }
+                _ => false,
+            },
+            None => false,
+        };
+        self.notify(EventClass::HASH, "hexpired", key);
+        if emptied && self.remove_if(key, |v| matches!(v, RedisValue::Hash(h) if h.is_empty())) {
+            self.expires.remove(key);
+            self.field_expires.remove(key);
+            self.notify(EventClass::GENERIC, "del", key);
+            return true;
+        }
         false
     }
 
+    /// Expiration time of a hash field in milliseconds, if it has one
+    pub fn hash_field_expiry(&self, key: &str, field: &[u8]) -> Option<u64> {
+        self.field_expires.get(key).and_then(|fields| fields.get(field))
+    }
+
+    /// Set the expiration time of a field of the hash at `key`
+    pub fn set_hash_field_expiry(&self, key: &str, field: Bytes, expire_at_ms: u64) {
+        self.preserve(key);
+        self.field_expires
+            .entry(key.to_string())
+            .or_default()
+            .set(field, expire_at_ms);
+        self.field_expiry_queue
+            .lock()
+            .unwrap()
+            .insert((expire_at_ms, key.to_string()));
+    }
+
+    /// Remove the expiration of a hash field (returns true if it had one)
+    pub fn persist_hash_field(&self, key: &str, field: &[u8]) -> bool {
+        self.preserve(key);
+        let removed = self
+            .field_expires
+            .get_mut(key)
+            .is_some_and(|mut fields| fields.remove(field).is_some());
+        self.field_expires.remove_if(key, |_, fields| fields.is_empty());
+        removed
+    }
+
+    /// Every field of the hash at `key` that has an expiration time
+    pub fn hash_field_expiries(&self, key: &str) -> Vec<(Bytes, u64)> {
+        self.field_expires.get(key).map_or_else(Vec::new, |fields| {
+            fields.iter().map(|(field, at)| (field.clone(), at)).collect()
+        })
+    }
+
+    /// Replace the field expiration times of the hash at `key`, e.g. after
+    /// renaming or copying it
+    pub fn set_hash_field_expiries(&self, key: &str, expiries: Vec<(Bytes, u64)>) {
+        self.preserve(key);
+        self.field_expires.remove(key);
+        for (field, expire_at_ms) in expiries {
+            self.set_hash_field_expiry(key, field, expire_at_ms);
+        }
+    }
+
+    /// Actively expire hash fields whose TTL has passed, so fields of hashes
+    /// nobody reads do not linger. Takes due hashes from the expiry queue,
+    /// at most ACTIVE_EXPIRE_HASHES of them or ACTIVE_EXPIRE_BUDGET worth;
+    /// the rest wait for the next tick. Returns the number of hashes visited.
+    pub fn active_expire_hash_fields(&self) -> usize {
+        let started = Instant::now();
+        let now = current_timestamp_ms();
+        let mut visited = 0;
+        while visited < ACTIVE_EXPIRE_HASHES && started.elapsed() < ACTIVE_EXPIRE_BUDGET {
+            let key = {
+                let mut queue = self.field_expiry_queue.lock().unwrap();
+                match queue.first() {
+                    Some((at, _)) if *at <= now => queue.pop_first().unwrap().1,
+                    _ => break,
+                }
+            };
+            self.expire_hash_fields(&key, now);
+            visited += 1;
+        }
+        visited
+    }
+
     pub fn get(&self, key: &str) -> Option<RedisValue> {
         if self.check_expired(key) {
             return None;
@@ -51,18 +319,86 @@ impl DbInstance {
         self.data.get(key).map(|v| v.value().clone())
     }
 
+    /// `get` for commands that only read the key, publishing a keymiss
+    /// notification when it does not exist
+    pub fn lookup_read(&self, key: &str) -> Option<RedisValue> {
+        let value = self.get(key);
+        if value.is_none() {
+            self.notify(EventClass::KEY_MISS, "keymiss", key);
+        }
+        value
+    }
+
+    /// Run `f` on the value at `key` without cloning it. The key's shard
+    /// stays locked while `f` runs, so `f` must not touch this database.
+    pub fn with_value<R>(&self, key: &str, f: impl FnOnce(&RedisValue) -> R) -> Option<R> {
+        if self.check_expired(key) {
+            return None;
+        }
+        self.data.get(key).map(|v| f(v.value()))
+    }
+
+    /// `with_value` for commands that only read the key, publishing a
+    /// keymiss notification when it does not exist
+    pub fn lookup_read_with<R>(&self, key: &str, f: impl FnOnce(&RedisValue) -> R) -> Option<R> {
+        let result = self.with_value(key, f);
+        if result.is_none() {
+            self.notify(EventClass::KEY_MISS, "keymiss", key);
+        }
+        result
+    }
+
+    /// Modify the value at `key` in place under its shard lock, so
+    /// concurrent writers of the key cannot interleave. `f` must not touch
+    /// this database.
+    pub fn with_value_mut<R>(&self, key: &str, f: impl FnOnce(&mut RedisValue) -> R) -> Option<R> {
+        if self.check_expired(key) {
+            return None;
+        }
+        self.preserve(key);
+        self.data.get_mut(key).map(|mut v| f(v.value_mut()))
+    }
+
+    /// `with_value_mut`, first storing `create()` if the key does not exist
+    pub fn with_value_or_insert<R>(
+        &self,
+        key: &str,
+        create: impl FnOnce() -> RedisValue,
+        f: impl FnOnce(&mut RedisValue) -> R,
+    ) -> R {
+        self.check_expired(key);
+        self.preserve(key);
+        let (result, created) = match self.data.entry(Arc::from(key)) {
+            Entry::Occupied(mut entry) => (f(entry.get_mut()), false),
+            Entry::Vacant(entry) => {
+                self.scan_index.add(entry.key());
+                if let Some(index) = &self.slot_index {
+                    index.add(entry.key());
+                }
+                let mut value = entry.insert(create());
+                (f(value.value_mut()), true)
+            }
+        };
+        // Published once the shard is unlocked
+        if created {
+            self.notify(EventClass::NEW, "new", key);
+        }
+        result
+    }
+
     pub fn set(&self, key: String, value: RedisValue) {
-        self.data.insert(key, value);
+        self.insert(key, value);
     }
 
     /// Set key with expiration time in milliseconds
     pub fn set_with_expiry(&self, key: String, value: RedisValue, expire_at_ms: u64) {
-        self.data.insert(key.clone(), value);
+        self.insert(key.clone(), value);
         self.expires.insert(key, expire_at_ms);
     }
 
     /// Set expiration for an existing key (returns true if key exists)
     pub fn set_expiry(&self, key: &str, expire_at_ms: u64) -> bool {
+        self.preserve(key);
         if self.data.contains_key(key) {
             self.expires.insert(key.to_string(), expire_at_ms);
             true
@@ -95,12 +431,27 @@ impl DbInstance {
 
     /// Remove expiration from key (returns true if expiration was removed)
     pub fn persist(&self, key: &str) -> bool {
+        self.preserve(key);
         self.expires.remove(key).is_some()
     }
 
     pub fn delete(&self, key: &str) -> bool {
+        self.preserve(key);
         self.expires.remove(key);
-        self.data.remove(key).is_some()
+        self.field_expires.remove(key);
+        self.remove_if(key, |_| true)
+    }
+
+    /// Delete a key only if `pred` holds for its current value, atomically
+    /// with respect to writers of that key
+    pub fn delete_if(&self, key: &str, pred: impl FnOnce(&RedisValue) -> bool) -> bool {
+        if self.remove_if(key, pred) {
+            self.expires.remove(key);
+            self.field_expires.remove(key);
+            true
+        } else {
+            false
+        }
     }
 
     pub fn exists(&self, key: &str) -> bool {
@@ -119,28 +470,120 @@ impl DbInstance {
     }
 
     pub fn clear(&self) {
+        if !self.captures.read().unwrap().is_empty() {
+            let keys: Vec<String> = self.data.iter().map(|entry| entry.key().to_string()).collect();
+            for key in &keys {
+                self.preserve(key);
+            }
+        }
         self.data.clear();
         self.expires.clear();
+        self.field_expires.clear();
+        self.field_expiry_queue.lock().unwrap().clear();
+        self.scan_index.clear();
+        if let Some(index) = &self.slot_index {
+            index.clear();
+        }
     }
 
-    pub fn keys(&self, pattern: &str) -> Vec<String> {
-        if pattern == "*" {
-            // Return all keys (excluding expired ones)
-            self.data
-                .iter()
-                .filter(|entry| !self.check_expired(entry.key()))
-                .map(|entry| entry.key().clone())
-                .collect()
-        } else {
-            // Simple pattern matching (only supports * wildcard)
-            self.data
-                .iter()
-                .filter(|entry| {
-                    !self.check_expired(entry.key()) && Self::match_pattern(entry.key(), pattern)
-                })
-                .map(|entry| entry.key().clone())
-                .collect()
+    /// One SCAN step: live keys at or past `cursor` in scan order, roughly
+    /// `count` of them, and the cursor to continue from (0 when done)
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<String>, u64) {
+        let (keys, next) = self.scan_index.scan(cursor, count);
+        // Expire after the index lock is released; expiry removes from it
+        let keys = keys.into_iter().filter(|key| !self.check_expired(key)).collect();
+        (keys, next)
+    }
+
+    /// Type of the value at `key` without cloning it
+    pub fn type_name(&self, key: &str) -> Option<&'static str> {
+        if self.check_expired(key) {
+            return None;
         }
+        self.data.get(key).map(|value| value.type_name())
+    }
+
+    /// Number of keys hashing to `slot`, zero unless the slot index is kept
+    pub fn count_keys_in_slot(&self, slot: u16) -> usize {
+        self.slot_index.as_ref().map_or(0, |index| index.count(slot))
+    }
+
+    /// Up to `count` keys hashing to `slot`
+    pub fn get_keys_in_slot(&self, slot: u16, count: usize) -> Vec<String> {
+        self.slot_index
+            .as_ref()
+            .map_or_else(Vec::new, |index| index.keys(slot, count))
+    }
+
+    /// Delete every key hashing to `slot`, returning the keys removed
+    pub fn delete_slot(&self, slot: u16) -> Vec<String> {
+        self.get_keys_in_slot(slot, usize::MAX)
+            .into_iter()
+            .filter(|key| self.delete(key))
+            .collect()
+    }
+
+    /// Start saving keys before they change, for a snapshot pinned now
+    pub(super) fn start_capture(&self) -> Arc<Capture> {
+        let capture = Arc::new(Capture::default());
+        self.captures.write().unwrap().push(Arc::clone(&capture));
+        capture
+    }
+
+    /// Stop saving keys for `capture`; waits for saves in progress
+    pub(super) fn end_capture(&self, capture: &Arc<Capture>) {
+        self.captures.write().unwrap().retain(|c| !Arc::ptr_eq(c, capture));
+    }
+
+    /// Copy every key that was live when `capture` started, with its
+    /// expiration and field TTLs, into `target`
+    pub(super) fn copy_pinned(&self, capture: &Arc<Capture>, target: &DbInstance) {
+        let now = current_timestamp_ms();
+        let keys: Vec<String> = self.data.iter().map(|entry| entry.key().to_string()).collect();
+        for key in keys {
+            // While the key's slot in `saved` is held, a writer can neither
+            // save the key nor, therefore, change it
+            let (key, state) = match capture.saved.entry(key) {
+                Entry::Occupied(_) => continue,
+                Entry::Vacant(slot) => {
+                    let state = self.key_state(slot.key());
+                    (slot.into_key(), state)
+                }
+            };
+            if let Some(state) = state {
+                target.restore_state(key, state, now);
+            }
+        }
+
+        // Every live key has been copied, so later writes need no saving;
+        // keys written before that are copied as they were at the pin
+        self.end_capture(capture);
+        for entry in capture.saved.iter() {
+            if let Some(state) = entry.value() {
+                target.restore_state(entry.key().clone(), state.clone(), now);
+            }
+        }
+    }
+
+    /// Store a key copied by a snapshot unless it expired by `now`
+    fn restore_state(&self, key: String, state: KeyState, now: u64) {
+        match state.expire_at {
+            Some(at) if at <= now => return,
+            Some(at) => self.set_with_expiry(key.clone(), state.value, at),
+            None => self.set(key.clone(), state.value),
+        }
+        if let Some(fields) = state.field_expires {
+            self.field_expires.insert(key, fields);
+        }
+    }
+
+    pub fn keys(&self, pattern: &str) -> Vec<String> {
+        // Collect first: expiring a key while iterating would lock its shard twice
+        let keys: Vec<String> = self.data.iter().map(|entry| entry.key().to_string()).collect();
+        // Simple pattern matching (only supports * wildcard), excluding expired keys
+        keys.into_iter()
+            .filter(|key| Self::match_pattern(key, pattern) && !self.check_expired(key))
+            .collect()
     }
 
     fn match_pattern(key: &str, pattern: &str) -> bool {
@@ -195,32 +638,156 @@ impl Default for DbInstance {
 
 /// Main database with multiple instances (typically 16)
 pub struct Database {
-    databases: Vec<Arc<DbInstance>>,
+    /// Each slot can be swapped wholesale (e.g. after a diskless replica load)
+    databases: Vec<RwLock<Arc<DbInstance>>>,
+    /// Whether the instances keep a hash slot index (cluster mode)
+    slot_indexed: bool,
+    /// Clients blocked until keys are written (XREAD BLOCK)
+    key_waiters: KeyWaiters,
+    /// Keyspace notification settings, shared with every instance
+    events: Arc<KeyspaceEvents>,
+    /// Collection encoding limits, shared with every instance
+    encoding: Arc<EncodingLimits>,
 }
 
 impl Database {
     pub fn new(num_dbs: usize) -> Self {
+        Self::build(num_dbs, false, Arc::new(KeyspaceEvents::new()), encoding::defaults())
+    }
+
+    /// Databases that index their keys by hash slot, for cluster mode
+    pub fn with_slot_index(num_dbs: usize) -> Self {
+        Self::build(num_dbs, true, Arc::new(KeyspaceEvents::new()), encoding::defaults())
+    }
+
+    /// The same databases, creating collections according to `encoding`
+    /// (the server's configured limits)
+    pub fn with_encoding(self, encoding: Arc<EncodingLimits>) -> Self {
+        Self::build(self.num_dbs(), self.slot_indexed, self.events, encoding)
+    }
+
+    fn build(num_dbs: usize, slot_indexed: bool, events: Arc<KeyspaceEvents>, encoding: Arc<EncodingLimits>) -> Self {
         let mut databases = Vec::with_capacity(num_dbs);
-        for _ in 0..num_dbs {
-            databases.push(Arc::new(DbInstance::new()));
+        for index in 0..num_dbs {
+            let db = if slot_indexed {
+                DbInstance::with_slot_index()
+            } else {
+                DbInstance::new()
+            };
+            let db = db
+                .with_notifications(Arc::clone(&events), index)
+                .with_encoding(Arc::clone(&encoding));
+            databases.push(RwLock::new(Arc::new(db)));
         }
-        Self { databases }
+        Self {
+            databases,
+            slot_indexed,
+            key_waiters: KeyWaiters::new(),
+            events,
+            encoding,
+        }
+    }
+
+    /// An empty database of the same shape, e.g. to stage a replica load
+    /// before `swap_with`. It shares the notification settings, so the
+    /// swapped-in instances keep publishing.
+    pub fn new_like(&self) -> Self {
+        Self::build(
+            self.num_dbs(),
+            self.slot_indexed,
+            Arc::clone(&self.events),
+            Arc::clone(&self.encoding),
+        )
+    }
+
+    /// Pin every database as it is now, for a snapshot that is copied
+    /// (see `PinnedSnapshot::copy`) while writes go on. Pinning itself is
+    /// cheap; the copy costs as much memory as the data, and its keyspace
+    /// notifications go nowhere.
+    pub fn pin_snapshot(&self) -> PinnedSnapshot {
+        let target = Self::build(
+            self.num_dbs(),
+            false,
+            Arc::new(KeyspaceEvents::new()),
+            Arc::clone(&self.encoding),
+        );
+        let databases = (0..self.num_dbs()).filter_map(|i| self.get_db(i)).collect();
+        PinnedSnapshot::new(databases, target)
+    }
+
+    /// A copy of every database as it is now
+    pub fn snapshot(&self) -> Self {
+        self.pin_snapshot().copy()
+    }
+
+    pub fn get_db(&self, index: usize) -> Option<Arc<DbInstance>> {
+        self.databases
+            .get(index)
+            .map(|slot| Arc::clone(&slot.read().unwrap()))
+    }
+
+    /// Clients blocked on keys, to wake after writing them
+    pub fn key_waiters(&self) -> &KeyWaiters {
+        &self.key_waiters
+    }
+
+    /// Keyspace notification settings of every database
+    pub fn keyspace_events(&self) -> &KeyspaceEvents {
+        &self.events
+    }
+
+    /// Number of logical databases
+    pub fn num_dbs(&self) -> usize {
+        self.databases.len()
+    }
+
+    /// Check whether every database is empty
+    pub fn is_empty(&self) -> bool {
+        self.databases.iter().all(|slot| slot.read().unwrap().is_empty())
     }
 
-    pub fn get_db(&self, index: usize) -> Option<&Arc<DbInstance>> {
-        self.databases.get(index)
+    /// Atomically replace every database with the contents of `other`.
+    /// Used by replicas to swap in a dataset that was loaded into a
+    /// staging `Database` while the old one kept serving reads.
+    pub fn swap_with(&self, other: &Database) {
+        // Take every lock before swapping so readers never observe a mix
+        let mut mine: Vec<_> = self.databases.iter().map(|s| s.write().unwrap()).collect();
+        let mut theirs: Vec<_> = other.databases.iter().map(|s| s.write().unwrap()).collect();
+        for (a, b) in mine.iter_mut().zip(theirs.iter_mut()) {
+            std::mem::swap(&mut **a, &mut **b);
+        }
     }
 
     pub async fn flush_db(&self, index: usize) {
         if let Some(db) = self.get_db(index) {
             db.clear();
         }
+        self.events.flushed();
     }
 
     pub async fn flush_all(&self) {
         for db in &self.databases {
-            db.clear();
+            db.read().unwrap().clear();
         }
+        self.events.flushed();
+    }
+
+    /// Expire hash fields in every database, returning the number of
+    /// hashes visited
+    pub fn active_expire_hash_fields(&self) -> usize {
+        self.databases
+            .iter()
+            .map(|db| db.read().unwrap().active_expire_hash_fields())
+            .sum()
+    }
+
+    /// Delete the keys of a hash slot from every database, returning the
+    /// keys removed from each
+    pub fn delete_slot(&self, slot: u16) -> Vec<Vec<String>> {
+        self.databases
+            .iter()
+            .map(|db| db.read().unwrap().delete_slot(slot))
+            .collect()
     }
 
     pub async fn db_size(&self, index: usize) -> usize {
@@ -299,4 +866,159 @@ mod tests {
         db.flush_db(0).await;
         assert_eq!(db.db_size(0).await, 0);
     }
+
+    #[test]
+    fn test_slot_index() {
+        use crate::cluster::key_hash_slot;
+
+        let db = DbInstance::with_slot_index();
+        let slot = key_hash_slot(b"{tag}");
+        db.set("{tag}:a".to_string(), RedisValue::String(Bytes::from("1")));
+        db.set("{tag}:a".to_string(), RedisValue::String(Bytes::from("2")));
+        db.set_with_expiry("{tag}:b".to_string(), RedisValue::String(Bytes::from("3")), 1);
+        db.set("{tag}:c".to_string(), RedisValue::String(Bytes::from("4")));
+        db.set("other".to_string(), RedisValue::String(Bytes::from("5")));
+        assert_eq!(db.count_keys_in_slot(slot), 3);
+
+        // Expired and deleted keys leave the index
+        assert!(!db.exists("{tag}:b"));
+        assert!(db.delete("{tag}:c"));
+        assert_eq!(db.count_keys_in_slot(slot), 1);
+        assert_eq!(db.get_keys_in_slot(slot, 10), vec!["{tag}:a"]);
+
+        db.set("{tag}:d".to_string(), RedisValue::String(Bytes::from("6")));
+        assert_eq!(db.delete_slot(slot), vec!["{tag}:a", "{tag}:d"]);
+        assert_eq!(db.count_keys_in_slot(slot), 0);
+        assert!(db.exists("other"));
+
+        // Without an index nothing is counted
+        let plain = DbInstance::new();
+        plain.set("{tag}:a".to_string(), RedisValue::String(Bytes::from("1")));
+        assert_eq!(plain.count_keys_in_slot(slot), 0);
+    }
+
+    #[test]
+    fn test_hash_field_expiry() {
+        use crate::storage::types::HashValue;
+
+        let db = Database::new(16);
+        let instance = db.get_db(0).unwrap();
+        let hash: HashValue = [("a", "1"), ("b", "2")]
+            .into_iter()
+            .map(|(f, v)| (Bytes::from(f), Bytes::from(v)))
+            .collect();
+        instance.set("h".to_string(), RedisValue::Hash(hash.clone()));
+        instance.set("other".to_string(), RedisValue::String(Bytes::from("x")));
+
+        // Active expiry removes due fields without the hash being read
+        instance.set_hash_field_expiry("h", Bytes::from("a"), 1);
+        instance.set_hash_field_expiry("h", Bytes::from("b"), u64::MAX);
+        assert_eq!(db.active_expire_hash_fields(), 1);
+        assert_eq!(instance.hash_field_expiries("h"), vec![(Bytes::from("b"), u64::MAX)]);
+
+        // Overwriting the key with another type drops its field TTLs
+        instance.set("h".to_string(), RedisValue::String(Bytes::from("s")));
+        assert!(instance.hash_field_expiries("h").is_empty());
+
+        // The last field expiring deletes the key, also while listing keys
+        instance.set("h".to_string(), RedisValue::Hash(hash));
+        instance.set_hash_field_expiry("h", Bytes::from("a"), 1);
+        instance.set_hash_field_expiry("h", Bytes::from("b"), 1);
+        assert_eq!(instance.keys("*"), vec!["other".to_string()]);
+        assert!(!instance.exists("h"));
+    }
+
+    #[test]
+    fn test_active_expire_hash_fields_is_bounded() {
+        use crate::storage::types::HashValue;
+
+        let db = DbInstance::new();
+        let hash: HashValue = [(Bytes::from("f"), Bytes::from("v"))].into_iter().collect();
+        for i in 0..ACTIVE_EXPIRE_HASHES + 50 {
+            let key = format!("h{}", i);
+            db.set(key.clone(), RedisValue::Hash(hash.clone()));
+            db.set_hash_field_expiry(&key, Bytes::from("f"), 1);
+        }
+        // Not yet due, and a TTL moved later leaves a stale entry behind
+        db.set("later".to_string(), RedisValue::Hash(hash));
+        db.set_hash_field_expiry("later", Bytes::from("f"), 2);
+        db.set_hash_field_expiry("later", Bytes::from("f"), u64::MAX);
+
+        assert_eq!(db.active_expire_hash_fields(), ACTIVE_EXPIRE_HASHES);
+        assert_eq!(db.active_expire_hash_fields(), 51);
+        assert_eq!(db.active_expire_hash_fields(), 0);
+        assert_eq!(db.keys("*"), vec!["later".to_string()]);
+        assert_eq!(db.hash_field_expiry("later", b"f"), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_values_edited_in_place() {
+        let db = DbInstance::with_slot_index();
+        let created = db.with_value_or_insert(
+            "n",
+            || RedisValue::String(Bytes::from("1")),
+            |value| matches!(value, RedisValue::String(_)),
+        );
+        assert!(created);
+        assert_eq!(db.count_keys_in_slot(crate::cluster::key_hash_slot(b"n")), 1);
+        assert_eq!(db.scan(0, 10).0, vec!["n".to_string()]);
+
+        db.with_value_mut("n", |value| *value = RedisValue::String(Bytes::from("2")));
+        assert_eq!(db.with_value("n", |value| matches!(value, RedisValue::String(s) if s == "2")), Some(true));
+        assert_eq!(db.with_value_mut("missing", |_| ()), None);
+
+        db.set_with_expiry("gone".to_string(), RedisValue::String(Bytes::from("x")), 1);
+        assert_eq!(db.lookup_read_with("gone", |_| ()), None);
+    }
+
+    #[test]
+    fn test_snapshot_is_a_detached_copy() {
+        let db = Database::new(16);
+        let db0 = db.get_db(0).unwrap();
+        db0.set("a".to_string(), RedisValue::String(Bytes::from("1")));
+        let later = current_timestamp_ms() + 60_000;
+        db0.set_with_expiry("ttl".to_string(), RedisValue::String(Bytes::from("x")), later);
+        db0.set_with_expiry("gone".to_string(), RedisValue::String(Bytes::from("x")), 1);
+        db0.set("h".to_string(), RedisValue::Hash(db0.new_hash()));
+        db0.set_hash_field_expiry("h", Bytes::from("f"), u64::MAX);
+        db.get_db(3).unwrap().set("b".to_string(), RedisValue::String(Bytes::from("2")));
+
+        let copy = db.snapshot();
+        db0.set("a".to_string(), RedisValue::String(Bytes::from("changed")));
+        db0.delete("ttl");
+
+        let copy0 = copy.get_db(0).unwrap();
+        assert!(matches!(copy0.get("a"), Some(RedisValue::String(s)) if s == "1"));
+        assert!(copy0.get_ttl_ms("ttl") > 0);
+        assert!(!copy0.exists("gone"));
+        assert_eq!(copy0.hash_field_expiry("h", b"f"), Some(u64::MAX));
+        assert!(copy.get_db(3).unwrap().exists("b"));
+    }
+
+    #[test]
+    fn test_pinned_snapshot_ignores_writes_after_the_pin() {
+        let db = Database::new(2);
+        let db0 = db.get_db(0).unwrap();
+        db0.set("changed".to_string(), RedisValue::String(Bytes::from("1")));
+        db0.set("deleted".to_string(), RedisValue::String(Bytes::from("1")));
+        db0.set("mutated".to_string(), RedisValue::String(Bytes::from("1")));
+        db0.set("untouched".to_string(), RedisValue::String(Bytes::from("1")));
+
+        let pinned = db.pin_snapshot();
+        db0.set("changed".to_string(), RedisValue::String(Bytes::from("2")));
+        db0.delete("deleted");
+        db0.with_value_mut("mutated", |value| *value = RedisValue::String(Bytes::from("2")));
+        db0.set("created".to_string(), RedisValue::String(Bytes::from("2")));
+        let copy = pinned.copy();
+        // Writes after the copy no longer pay for saving keys
+        db0.set("untouched".to_string(), RedisValue::String(Bytes::from("2")));
+        assert!(db0.captures.read().unwrap().is_empty());
+
+        let copy0 = copy.get_db(0).unwrap();
+        for key in ["changed", "deleted", "mutated", "untouched"] {
+            assert!(matches!(copy0.get(key), Some(RedisValue::String(s)) if s == "1"), "{}", key);
+        }
+        assert!(!copy0.exists("created"));
+        assert!(matches!(db0.get("changed"), Some(RedisValue::String(s)) if s == "2"));
+    }
 }
@@ -0,0 +1,160 @@
+// Thresholds for the compact encodings of small collections
+//
+// Like Redis's hash-max-listpack-* family these are server-wide: each
+// server keeps one set of limits in its configuration, and every
+// collection it creates holds a handle to them and checks them when it
+// grows. CONFIG SET therefore takes effect for values converted after the
+// change. Collections only ever convert to the general encoding, never
+// back.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+#[derive(Debug)]
+pub struct Limit {
+    value: AtomicI64,
+}
+
+impl Limit {
+    fn new(value: i64) -> Self {
+        Self { value: AtomicI64::new(value) }
+    }
+
+    pub fn get(&self) -> usize {
+        self.value.load(Ordering::Relaxed).max(0) as usize
+    }
+
+    pub fn get_signed(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A configuration parameter that is an encoding limit
+struct Param {
+    name: &'static str,
+    /// Name accepted for compatibility with older configs
+    alias: Option<&'static str>,
+    /// Smallest accepted value; only the list node size goes below zero
+    min: i64,
+    default: i64,
+}
+
+const PARAMS: [Param; 9] = [
+    Param { name: "hash-max-listpack-entries", alias: Some("hash-max-ziplist-entries"), min: 0, default: 128 },
+    Param { name: "hash-max-listpack-value", alias: Some("hash-max-ziplist-value"), min: 0, default: 64 },
+    Param { name: "set-max-intset-entries", alias: None, min: 0, default: 512 },
+    Param { name: "set-max-listpack-entries", alias: None, min: 0, default: 128 },
+    Param { name: "set-max-listpack-value", alias: None, min: 0, default: 64 },
+    Param { name: "zset-max-listpack-entries", alias: Some("zset-max-ziplist-entries"), min: 0, default: 128 },
+    Param { name: "zset-max-listpack-value", alias: Some("zset-max-ziplist-value"), min: 0, default: 64 },
+    Param { name: "list-max-listpack-size", alias: Some("list-max-ziplist-size"), min: -5, default: -2 },
+    Param { name: "list-compress-depth", alias: None, min: 0, default: 0 },
+];
+
+/// The encoding limits of one server
+#[derive(Debug)]
+pub struct EncodingLimits {
+    pub hash_max_listpack_entries: Limit,
+    pub hash_max_listpack_value: Limit,
+    pub set_max_intset_entries: Limit,
+    pub set_max_listpack_entries: Limit,
+    pub set_max_listpack_value: Limit,
+    pub zset_max_listpack_entries: Limit,
+    pub zset_max_listpack_value: Limit,
+    /// Entries per quicklist node when positive; -1 to -5 cap each node at
+    /// 4, 8, 16, 32 or 64 KB instead
+    pub list_max_listpack_size: Limit,
+    /// Quicklist nodes left uncompressed at each end; 0 disables compression
+    pub list_compress_depth: Limit,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        let [hash_entries, hash_value, intset, set_entries, set_value, zset_entries, zset_value, list_size, compress] =
+            PARAMS.map(|param| Limit::new(param.default));
+        Self {
+            hash_max_listpack_entries: hash_entries,
+            hash_max_listpack_value: hash_value,
+            set_max_intset_entries: intset,
+            set_max_listpack_entries: set_entries,
+            set_max_listpack_value: set_value,
+            zset_max_listpack_entries: zset_entries,
+            zset_max_listpack_value: zset_value,
+            list_max_listpack_size: list_size,
+            list_compress_depth: compress,
+        }
+    }
+}
+
+impl EncodingLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The limits in the order of PARAMS
+    fn limits(&self) -> [&Limit; 9] {
+        [
+            &self.hash_max_listpack_entries,
+            &self.hash_max_listpack_value,
+            &self.set_max_intset_entries,
+            &self.set_max_listpack_entries,
+            &self.set_max_listpack_value,
+            &self.zset_max_listpack_entries,
+            &self.zset_max_listpack_value,
+            &self.list_max_listpack_size,
+            &self.list_compress_depth,
+        ]
+    }
+
+    /// Apply a configuration parameter if it is one of the encoding limits,
+    /// returning false for other parameters and values out of its range
+    pub fn apply(&self, name: &str, value: &str) -> bool {
+        match (find(name), value.parse::<i64>()) {
+            (Some(i), Ok(value)) if value >= PARAMS[i].min => {
+                self.limits()[i].value.store(value, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Limits of collections built outside a server (tests, tools, values
+/// not yet stored); never changed
+pub fn defaults() -> Arc<EncodingLimits> {
+    static DEFAULTS: OnceLock<Arc<EncodingLimits>> = OnceLock::new();
+    Arc::clone(DEFAULTS.get_or_init(|| Arc::new(EncodingLimits::new())))
+}
+
+fn find(name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    PARAMS
+        .iter()
+        .position(|param| param.name == name || param.alias == Some(name.as_str()))
+}
+
+/// The current name of an encoding parameter, mapping old ziplist names
+pub fn canonical_name(name: &str) -> Option<&'static str> {
+    find(name).map(|i| PARAMS[i].name)
+}
+
+/// Names of every encoding limit
+pub fn names() -> impl Iterator<Item = &'static str> {
+    PARAMS.iter().map(|param| param.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_are_per_instance() {
+        let server = EncodingLimits::new();
+        assert!(server.apply("zset-max-ziplist-entries", "4"));
+        assert!(!server.apply("list-max-listpack-size", "-6"));
+        assert!(!server.apply("maxmemory", "1"));
+        assert_eq!(server.zset_max_listpack_entries.get(), 4);
+        assert_eq!(EncodingLimits::new().zset_max_listpack_entries.get(), 128);
+        assert_eq!(defaults().list_max_listpack_size.get_signed(), -2);
+    }
+}
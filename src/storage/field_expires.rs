@@ -0,0 +1,96 @@
+// Expiration times of individual hash fields (HEXPIRE and friends)
+
+use bytes::Bytes;
+use std::collections::{BTreeSet, HashMap};
+
+/// Expiration times of the fields of one hash, also ordered by time so the
+/// next field to expire is found without scanning
+#[derive(Debug, Clone, Default)]
+pub struct FieldExpires {
+    by_field: HashMap<Bytes, u64>,
+    by_time: BTreeSet<(u64, Bytes)>,
+}
+
+impl FieldExpires {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expiration time of `field` in milliseconds, if it has one
+    pub fn get(&self, field: &[u8]) -> Option<u64> {
+        self.by_field.get(field).copied()
+    }
+
+    pub fn set(&mut self, field: Bytes, expire_at_ms: u64) {
+        if let Some(old) = self.by_field.insert(field.clone(), expire_at_ms) {
+            self.by_time.remove(&(old, field.clone()));
+        }
+        self.by_time.insert((expire_at_ms, field));
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<u64> {
+        let (field, expire_at_ms) = self.by_field.remove_entry(field)?;
+        self.by_time.remove(&(expire_at_ms, field));
+        Some(expire_at_ms)
+    }
+
+    /// Keep only the fields for which `keep` holds
+    pub fn retain(&mut self, mut keep: impl FnMut(&Bytes) -> bool) {
+        let dropped: Vec<Bytes> = self.by_field.keys().filter(|f| !keep(f)).cloned().collect();
+        for field in dropped {
+            self.remove(&field);
+        }
+    }
+
+    /// The earliest expiration time
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.by_time.first().map(|(at, _)| *at)
+    }
+
+    /// Remove and return the fields that expire at or before `now`
+    pub fn pop_expired(&mut self, now: u64) -> Vec<Bytes> {
+        let mut expired = Vec::new();
+        while self.by_time.first().is_some_and(|(at, _)| *at <= now) {
+            let (_, field) = self.by_time.pop_first().unwrap();
+            self.by_field.remove(&field);
+            expired.push(field);
+        }
+        expired
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, u64)> {
+        self.by_field.iter().map(|(field, at)| (field, *at))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_field.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_field.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_expires_order() {
+        let mut expires = FieldExpires::new();
+        expires.set(Bytes::from("a"), 300);
+        expires.set(Bytes::from("b"), 100);
+        expires.set(Bytes::from("c"), 200);
+        // Re-setting moves the field in time order
+        expires.set(Bytes::from("b"), 400);
+        assert_eq!(expires.next_expiry(), Some(200));
+
+        assert_eq!(expires.pop_expired(300), vec![Bytes::from("c"), Bytes::from("a")]);
+        assert_eq!(expires.get(b"a"), None);
+        assert_eq!(expires.len(), 1);
+
+        expires.retain(|f| f != "b");
+        assert!(expires.is_empty());
+        assert_eq!(expires.next_expiry(), None);
+    }
+}
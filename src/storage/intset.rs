@@ -0,0 +1,141 @@
+// Intset: small sets of integers as a sorted array
+//
+// Members are stored in ascending order at the narrowest width (2, 4 or 8
+// bytes) that fits all of them, so lookups are a binary search and a set
+// of small numbers costs two bytes per member. Adding a member that does
+// not fit widens every element first.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intset {
+    /// Bytes per element
+    width: usize,
+    data: Vec<u8>,
+}
+
+impl Default for Intset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn width_for(value: i64) -> usize {
+    if i16::try_from(value).is_ok() {
+        2
+    } else if i32::try_from(value).is_ok() {
+        4
+    } else {
+        8
+    }
+}
+
+impl Intset {
+    pub fn new() -> Self {
+        Self { width: 2, data: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Bytes used by the elements
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<i64> {
+        let bytes = self.data.get(index * self.width..(index + 1) * self.width)?;
+        Some(match self.width {
+            2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            _ => i64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+
+    fn encode(value: i64, width: usize) -> Vec<u8> {
+        match width {
+            2 => (value as i16).to_le_bytes().to_vec(),
+            4 => (value as i32).to_le_bytes().to_vec(),
+            _ => value.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Index of `value`, or where it would be inserted
+    fn search(&self, value: i64) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.get(mid).unwrap().cmp(&value) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.search(value).is_ok()
+    }
+
+    /// Add `value`, returning false if it was already present
+    pub fn insert(&mut self, value: i64) -> bool {
+        let width = width_for(value);
+        if width > self.width {
+            let widened = self.iter().flat_map(|v| Self::encode(v, width)).collect();
+            self.data = widened;
+            self.width = width;
+        }
+        match self.search(value) {
+            Ok(_) => false,
+            Err(index) => {
+                let at = index * self.width;
+                self.data.splice(at..at, Self::encode(value, self.width));
+                true
+            }
+        }
+    }
+
+    /// Remove `value`, returning whether it was present
+    pub fn remove(&mut self, value: i64) -> bool {
+        match self.search(value) {
+            Ok(index) => {
+                self.data.drain(index * self.width..(index + 1) * self.width);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Members in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.len()).map(move |i| self.get(i).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intset_widens_and_stays_sorted() {
+        let mut set = Intset::new();
+        assert!(set.insert(5));
+        assert!(set.insert(-3));
+        assert!(!set.insert(5));
+        assert_eq!(set.encoded_len(), 4);
+
+        assert!(set.insert(100_000));
+        assert!(set.insert(i64::MIN));
+        assert_eq!(set.encoded_len(), 32);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![i64::MIN, -3, 5, 100_000]);
+
+        assert!(set.contains(-3));
+        assert!(set.remove(-3));
+        assert!(!set.remove(-3));
+        assert_eq!(set.len(), 3);
+    }
+}
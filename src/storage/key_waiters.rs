@@ -0,0 +1,111 @@
+// Clients blocked on keys, woken when one of the keys is written
+
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Blocked clients by the (database, key) pairs they wait on. A write
+/// signals the key, waking every client registered under it; each client
+/// then checks for itself whether it can be served.
+#[derive(Default)]
+pub struct KeyWaiters {
+    next_id: AtomicU64,
+    /// Only keys with at least one waiter have an entry
+    keys: DashMap<(usize, String), HashMap<u64, Arc<Notify>>>,
+}
+
+impl KeyWaiters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a client waiting on `keys` of database `db_index`. Signals
+    /// sent after this call are not lost, even while the client is not
+    /// awaiting yet.
+    pub fn register(&self, db_index: usize, keys: &[String]) -> KeyWait<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let notify = Arc::new(Notify::new());
+        for key in keys {
+            self.keys
+                .entry((db_index, key.clone()))
+                .or_default()
+                .insert(id, Arc::clone(&notify));
+        }
+        KeyWait {
+            waiters: self,
+            id,
+            db_index,
+            keys: keys.to_vec(),
+            notify,
+        }
+    }
+
+    /// Wake every client waiting on `key`
+    pub fn signal(&self, db_index: usize, key: &str) {
+        if let Some(waiters) = self.keys.get(&(db_index, key.to_string())) {
+            for notify in waiters.values() {
+                notify.notify_one();
+            }
+        }
+    }
+
+    /// Number of clients waiting on `key`
+    pub fn waiting(&self, db_index: usize, key: &str) -> usize {
+        self.keys.get(&(db_index, key.to_string())).map_or(0, |w| w.len())
+    }
+}
+
+/// A client's registration; dropping it unregisters the client
+pub struct KeyWait<'a> {
+    waiters: &'a KeyWaiters,
+    id: u64,
+    db_index: usize,
+    keys: Vec<String>,
+    notify: Arc<Notify>,
+}
+
+impl KeyWait<'_> {
+    /// Wait until one of the keys is signaled
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+}
+
+impl Drop for KeyWait<'_> {
+    fn drop(&mut self) {
+        for key in self.keys.drain(..) {
+            self.waiters.keys.remove_if_mut(&(self.db_index, key), |_, waiters| {
+                waiters.remove(&self.id);
+                waiters.is_empty()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_signal_wakes_registered_client() {
+        let waiters = KeyWaiters::new();
+        let wait = waiters.register(0, &["a".to_string(), "b".to_string()]);
+        assert_eq!(waiters.waiting(0, "b"), 1);
+
+        // A signal before awaiting is kept
+        waiters.signal(0, "b");
+        tokio::time::timeout(Duration::from_secs(1), wait.notified()).await.unwrap();
+
+        // Other databases and keys do not wake it
+        waiters.signal(1, "a");
+        waiters.signal(0, "c");
+        assert!(tokio::time::timeout(Duration::from_millis(50), wait.notified()).await.is_err());
+
+        drop(wait);
+        assert_eq!(waiters.waiting(0, "a"), 0);
+        assert_eq!(waiters.waiting(0, "b"), 0);
+    }
+}
@@ -0,0 +1,298 @@
+// Listpack: a run of byte strings packed into one buffer
+//
+// Small hashes, sets and sorted sets keep their elements here instead of in
+// a hash table, saving the per-entry allocations and table overhead. Each
+// entry is laid out as
+//
+//   <length varint> <bytes> <backlen>
+//
+// where backlen is the size of the first two parts, written so it can be
+// read from its last byte backwards. That lets iteration run from either
+// end. Every update rewrites the tail of the buffer, which is cheap at the
+// sizes the encoding thresholds allow.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listpack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+/// Append `value` as a little-endian base-128 varint
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Bytes `write_varint` uses for `value`
+fn varint_len(mut value: usize) -> usize {
+    let mut size = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        size += 1;
+    }
+    size
+}
+
+/// Decode the varint at `pos`, returning it and its size
+fn read_varint(buf: &[u8], pos: usize) -> (usize, usize) {
+    let mut value = 0;
+    let mut size = 0;
+    loop {
+        let byte = buf[pos + size];
+        value |= ((byte & 0x7f) as usize) << (7 * size);
+        size += 1;
+        if byte & 0x80 == 0 {
+            return (value, size);
+        }
+    }
+}
+
+/// Append `value` so that it decodes reading backwards from its last byte
+fn write_backlen(out: &mut Vec<u8>, value: usize) {
+    let start = out.len();
+    write_varint(out, value);
+    out[start..].reverse();
+}
+
+/// Decode the backlen ending just before `end`, returning it and its size
+fn read_backlen(buf: &[u8], end: usize) -> (usize, usize) {
+    let mut value = 0;
+    let mut size = 0;
+    loop {
+        let byte = buf[end - 1 - size];
+        value |= ((byte & 0x7f) as usize) << (7 * size);
+        size += 1;
+        if byte & 0x80 == 0 {
+            return (value, size);
+        }
+    }
+}
+
+fn encode_entry(out: &mut Vec<u8>, entry: &[u8]) {
+    let start = out.len();
+    write_varint(out, entry.len());
+    out.extend_from_slice(entry);
+    let size = out.len() - start;
+    write_backlen(out, size);
+}
+
+impl Listpack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes used by the encoded entries
+    pub fn encoded_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The encoded entries, e.g. for compressing the whole listpack
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Rebuild a listpack of `len` entries from `as_bytes` output
+    pub fn from_bytes(buf: Vec<u8>, len: usize) -> Self {
+        Self { buf, len }
+    }
+
+    /// Bytes `entry` will take once encoded
+    pub fn entry_size(entry: &[u8]) -> usize {
+        let size = varint_len(entry.len()) + entry.len();
+        size + varint_len(size)
+    }
+
+    /// Data bytes of the entry starting at `pos`, and where the next starts
+    fn entry_at(&self, pos: usize) -> (Range<usize>, usize) {
+        let (len, size) = read_varint(&self.buf, pos);
+        let data = pos + size..pos + size + len;
+        let next = data.end + varint_len(size + len);
+        (data, next)
+    }
+
+    /// Data bytes of the entry ending at `end`, and where it starts
+    fn entry_before(&self, end: usize) -> (Range<usize>, usize) {
+        let (size, backlen_size) = read_backlen(&self.buf, end);
+        let start = end - backlen_size - size;
+        let (len, header) = read_varint(&self.buf, start);
+        (start + header..start + header + len, start)
+    }
+
+    /// The entry starting at byte `pos` and where the next one starts
+    pub fn next_entry(&self, pos: usize) -> (&[u8], usize) {
+        let (data, next) = self.entry_at(pos);
+        (&self.buf[data], next)
+    }
+
+    /// The entry ending at byte `end` and where it starts
+    pub fn prev_entry(&self, end: usize) -> (&[u8], usize) {
+        let (data, start) = self.entry_before(end);
+        (&self.buf[data], start)
+    }
+
+    /// Byte offset of entry `index` (the buffer end for `len`), walking
+    /// from whichever end is closer
+    pub fn offset(&self, index: usize) -> usize {
+        if index <= self.len / 2 {
+            let mut pos = 0;
+            for _ in 0..index {
+                pos = self.entry_at(pos).1;
+            }
+            pos
+        } else {
+            let mut pos = self.buf.len();
+            for _ in index..self.len {
+                pos = self.entry_before(pos).1;
+            }
+            pos
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.len {
+            return None;
+        }
+        let (data, _) = self.entry_at(self.offset(index));
+        Some(&self.buf[data])
+    }
+
+    /// Replace `count` entries starting at `index` with `entries`
+    pub fn splice(&mut self, index: usize, count: usize, entries: &[&[u8]]) {
+        let start = self.offset(index);
+        let mut end = start;
+        for _ in 0..count {
+            end = self.entry_at(end).1;
+        }
+        let mut encoded = Vec::new();
+        for entry in entries {
+            encode_entry(&mut encoded, entry);
+        }
+        self.buf.splice(start..end, encoded);
+        self.len = self.len - count + entries.len();
+    }
+
+    pub fn push(&mut self, entry: &[u8]) {
+        encode_entry(&mut self.buf, entry);
+        self.len += 1;
+    }
+
+    pub fn insert(&mut self, index: usize, entry: &[u8]) {
+        self.splice(index, 0, &[entry]);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.splice(index, 1, &[]);
+    }
+
+    pub fn replace(&mut self, index: usize, entry: &[u8]) {
+        self.splice(index, 1, &[entry]);
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.range(0, self.len)
+    }
+
+    /// Entries `start..end`, double-ended
+    pub fn range(&self, start: usize, end: usize) -> Iter<'_> {
+        let end = end.min(self.len);
+        let start = start.min(end);
+        Iter {
+            listpack: self,
+            front: self.offset(start),
+            back: if end == self.len { self.buf.len() } else { self.offset(end) },
+            remaining: end - start,
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a [u8]> for Listpack {
+    fn from_iter<I: IntoIterator<Item = &'a [u8]>>(iter: I) -> Self {
+        let mut listpack = Listpack::new();
+        for entry in iter {
+            listpack.push(entry);
+        }
+        listpack
+    }
+}
+
+/// Iterator over a run of entries, from either end
+pub struct Iter<'a> {
+    listpack: &'a Listpack,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (data, next) = self.listpack.entry_at(self.front);
+        self.front = next;
+        self.remaining -= 1;
+        Some(&self.listpack.buf[data])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (data, start) = self.listpack.entry_before(self.back);
+        self.back = start;
+        self.remaining -= 1;
+        Some(&self.listpack.buf[data])
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listpack_edits_and_iterates_both_ways() {
+        let mut lp = Listpack::new();
+        let long = vec![b'x'; 300];
+        lp.push(b"a");
+        lp.push(&long);
+        lp.push(b"");
+        lp.insert(1, b"b");
+        assert_eq!(lp.len(), 4);
+        assert_eq!(lp.iter().collect::<Vec<_>>(), vec![&b"a"[..], b"b", &long, b""]);
+        assert_eq!(lp.iter().rev().collect::<Vec<_>>(), vec![&b""[..], &long, b"b", b"a"]);
+
+        lp.replace(2, b"c");
+        lp.remove(0);
+        assert_eq!(lp.get(0), Some(&b"b"[..]));
+        assert_eq!(lp.get(1), Some(&b"c"[..]));
+        assert_eq!(lp.get(3), None);
+        assert_eq!(lp.range(1, 3).rev().collect::<Vec<_>>(), vec![&b""[..], b"c"]);
+
+        lp.splice(0, 3, &[]);
+        assert!(lp.is_empty());
+        assert_eq!(lp.encoded_len(), 0);
+    }
+}
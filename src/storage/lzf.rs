@@ -0,0 +1,130 @@
+// LZF compression for interior quicklist nodes
+//
+// The format of liblzf, which Redis also uses for quicklist nodes: a stream
+// of literal runs (control byte < 32, then up to 32 bytes) and back
+// references (3-bit length, 13-bit offset, with an extra length byte for
+// long matches). It is fast rather than tight, which suits nodes that are
+// recompressed whenever they are edited.
+
+const HASH_LOG: u32 = 13;
+const MAX_LITERAL: usize = 32;
+/// Furthest back a reference can point
+const MAX_OFFSET: usize = 1 << 13;
+/// Longest match one reference can copy
+const MAX_MATCH: usize = (1 << 8) + (1 << 3);
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+fn flush_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERAL) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Compress `input`, or None when that would not make it smaller
+pub fn compress(input: &[u8]) -> Option<Vec<u8>> {
+    // Position + 1 of the last occurrence of each 3-byte hash
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut out = Vec::with_capacity(input.len());
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + 2 < input.len() {
+        let h = hash(&input[i..]);
+        let candidate = table[h];
+        table[h] = i + 1;
+
+        if candidate > 0 {
+            let r = candidate - 1;
+            let offset = i - r - 1;
+            if offset < MAX_OFFSET && input[r..r + 3] == input[i..i + 3] {
+                let max = (input.len() - i).min(MAX_MATCH);
+                let mut len = 3;
+                while len < max && input[r + len] == input[i + len] {
+                    len += 1;
+                }
+
+                flush_literals(&mut out, &input[literal_start..i]);
+                let code = len - 2;
+                if code < 7 {
+                    out.push((code << 5) as u8 | (offset >> 8) as u8);
+                } else {
+                    out.push((7 << 5) | (offset >> 8) as u8);
+                    out.push((code - 7) as u8);
+                }
+                out.push(offset as u8);
+
+                for p in i + 1..(i + len).min(input.len().saturating_sub(2)) {
+                    table[hash(&input[p..])] = p + 1;
+                }
+                i += len;
+                literal_start = i;
+                if out.len() >= input.len() {
+                    return None;
+                }
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    flush_literals(&mut out, &input[literal_start..]);
+    (out.len() < input.len()).then_some(out)
+}
+
+/// Expand `compress` output back to its `len` original bytes
+pub fn decompress(input: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < MAX_LITERAL {
+            out.extend_from_slice(&input[i..i + ctrl + 1]);
+            i += ctrl + 1;
+        } else {
+            let mut match_len = ctrl >> 5;
+            if match_len == 7 {
+                match_len += input[i] as usize;
+                i += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + input[i] as usize + 1;
+            i += 1;
+            // Byte by byte: a reference may overlap the bytes it produces
+            let start = out.len() - offset;
+            for k in 0..match_len + 2 {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lzf_round_trip() {
+        let repetitive: Vec<u8> = b"element:".iter().cycle().take(5000).copied().collect();
+        let packed = compress(&repetitive).unwrap();
+        assert!(packed.len() < repetitive.len() / 10);
+        assert_eq!(decompress(&packed, repetitive.len()), repetitive);
+
+        let mut mixed = Vec::new();
+        for i in 0..2000u32 {
+            mixed.extend_from_slice(format!("value-{}-", i * 7919 % 1000).as_bytes());
+        }
+        let packed = compress(&mixed).unwrap();
+        assert_eq!(decompress(&packed, mixed.len()), mixed);
+
+        // Incompressible input is left alone
+        let noise: Vec<u8> = (0..256u32).map(|i| (i * 167 % 256) as u8).collect();
+        assert_eq!(compress(&noise), None);
+        assert_eq!(compress(b""), None);
+    }
+}
@@ -1,6 +1,18 @@
 // Storage module - Database and data structures
 
 pub mod db;
+pub mod encoding;
+pub mod field_expires;
+pub mod intset;
+pub mod key_waiters;
+pub mod listpack;
+pub mod lzf;
+pub mod notify;
+pub mod quicklist;
+pub mod scan_index;
+pub mod skiplist;
+pub mod slot_index;
+pub mod snapshot;
 pub mod types;
 pub mod memory;
 
@@ -0,0 +1,228 @@
+// Keyspace notifications
+//
+// With notify-keyspace-events enabled, every write publishes the event name
+// on `__keyspace@<db>__:<key>` (K) and the key name on
+// `__keyevent@<db>__:<event>` (E), so clients can follow changes through
+// ordinary SUBSCRIBE and PSUBSCRIBE. The class flags pick which kinds of
+// events are published; both stay off by default, and checking them is a
+// single atomic load so writes pay nothing for it.
+
+use crate::pubsub::PubSub;
+use crate::server::tracking::Tracking;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+
+bitflags::bitflags! {
+    /// The classes of notify-keyspace-events, one flag per character
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EventClass: u32 {
+        /// K: publish on __keyspace@<db>__ channels
+        const KEYSPACE = 1 << 0;
+        /// E: publish on __keyevent@<db>__ channels
+        const KEYEVENT = 1 << 1;
+        /// g: generic commands such as DEL, EXPIRE and RENAME
+        const GENERIC = 1 << 2;
+        /// $: string commands
+        const STRING = 1 << 3;
+        /// l: list commands
+        const LIST = 1 << 4;
+        /// s: set commands
+        const SET = 1 << 5;
+        /// h: hash commands
+        const HASH = 1 << 6;
+        /// z: sorted set commands
+        const ZSET = 1 << 7;
+        /// x: keys removed because their TTL passed
+        const EXPIRED = 1 << 8;
+        /// e: keys evicted under maxmemory. Accepted for compatibility;
+        /// this server never evicts, so these events never fire.
+        const EVICTED = 1 << 9;
+        /// t: stream commands
+        const STREAM = 1 << 10;
+        /// m: reads of keys that do not exist
+        const KEY_MISS = 1 << 11;
+        /// n: keys added to the database
+        const NEW = 1 << 12;
+        /// A: alias for g$lshzxet
+        const ALL = Self::GENERIC.bits()
+            | Self::STRING.bits()
+            | Self::LIST.bits()
+            | Self::SET.bits()
+            | Self::HASH.bits()
+            | Self::ZSET.bits()
+            | Self::EXPIRED.bits()
+            | Self::EVICTED.bits()
+            | Self::STREAM.bits();
+    }
+}
+
+const CLASS_CHARS: [(char, EventClass); 13] = [
+    ('g', EventClass::GENERIC),
+    ('$', EventClass::STRING),
+    ('l', EventClass::LIST),
+    ('s', EventClass::SET),
+    ('h', EventClass::HASH),
+    ('z', EventClass::ZSET),
+    ('x', EventClass::EXPIRED),
+    ('e', EventClass::EVICTED),
+    ('t', EventClass::STREAM),
+    ('K', EventClass::KEYSPACE),
+    ('E', EventClass::KEYEVENT),
+    ('m', EventClass::KEY_MISS),
+    ('n', EventClass::NEW),
+];
+
+impl EventClass {
+    /// Parse a notify-keyspace-events value such as "KEA" or "Elg"
+    pub fn parse(value: &str) -> Option<Self> {
+        value.chars().try_fold(Self::empty(), |flags, c| {
+            let class = if c == 'A' {
+                Self::ALL
+            } else {
+                CLASS_CHARS.iter().find(|(ch, _)| *ch == c)?.1
+            };
+            Some(flags | class)
+        })
+    }
+
+    /// The canonical spelling of these flags, using A where it applies
+    pub fn to_config_string(self) -> String {
+        let mut out = String::new();
+        let mut rest = self;
+        if self.contains(Self::ALL) {
+            out.push('A');
+            rest -= Self::ALL;
+        }
+        for (c, class) in CLASS_CHARS {
+            if rest.contains(class) {
+                out.push(c);
+            }
+        }
+        out
+    }
+}
+
+/// The notification settings of a server and where events are published
+#[derive(Default)]
+pub struct KeyspaceEvents {
+    flags: AtomicU32,
+    /// Set once the server's Pub/Sub exists; nothing is published before
+    pubsub: OnceLock<Arc<PubSub>>,
+    /// Set once client tracking exists; writes invalidate cached keys
+    tracking: OnceLock<Arc<Tracking>>,
+}
+
+impl KeyspaceEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publish through `pubsub` from now on
+    pub fn attach(&self, pubsub: Arc<PubSub>) {
+        let _ = self.pubsub.set(pubsub);
+    }
+
+    /// Invalidate keys of tracking clients from now on
+    pub fn attach_tracking(&self, tracking: Arc<Tracking>) {
+        let _ = self.tracking.set(tracking);
+    }
+
+    pub fn flags(&self) -> EventClass {
+        EventClass::from_bits_truncate(self.flags.load(Ordering::Relaxed))
+    }
+
+    pub fn set_flags(&self, flags: EventClass) {
+        self.flags.store(flags.bits(), Ordering::Relaxed);
+    }
+
+    /// Apply a notify-keyspace-events value, returning false if it does not parse
+    pub fn configure(&self, value: &str) -> bool {
+        match EventClass::parse(value) {
+            Some(flags) => {
+                self.set_flags(flags);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Publish `event` on `key` of database `db_index` if its class is
+    /// enabled. Every event but keymiss and new also means the key changed,
+    /// so clients caching it are told.
+    pub fn notify(&self, db_index: usize, class: EventClass, event: &str, key: &str) {
+        if !class.intersects(EventClass::KEY_MISS | EventClass::NEW) {
+            if let Some(tracking) = self.tracking.get() {
+                tracking.invalidate(key);
+            }
+        }
+
+        let flags = self.flags();
+        if !flags.intersects(class) {
+            return;
+        }
+        let Some(pubsub) = self.pubsub.get() else {
+            return;
+        };
+        if flags.contains(EventClass::KEYSPACE) {
+            let channel = format!("__keyspace@{}__:{}", db_index, key);
+            pubsub.publish(&channel, event.as_bytes().to_vec());
+        }
+        if flags.contains(EventClass::KEYEVENT) {
+            let channel = format!("__keyevent@{}__:{}", db_index, event);
+            pubsub.publish(&channel, key.as_bytes().to_vec());
+        }
+    }
+
+    /// A database was flushed: clients drop everything they cached
+    pub fn flushed(&self) {
+        if let Some(tracking) = self.tracking.get() {
+            tracking.invalidate_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_class_parse() {
+        assert_eq!(EventClass::parse(""), Some(EventClass::empty()));
+        assert_eq!(
+            EventClass::parse("Elg"),
+            Some(EventClass::KEYEVENT | EventClass::LIST | EventClass::GENERIC)
+        );
+        let all = EventClass::parse("KEA").unwrap();
+        assert!(all.contains(EventClass::STREAM | EventClass::EXPIRED));
+        assert!(!all.contains(EventClass::KEY_MISS));
+        assert_eq!(all.to_config_string(), "AKE");
+        assert_eq!(EventClass::parse("nKx").unwrap().to_config_string(), "xKn");
+        assert_eq!(EventClass::parse("KEq"), None);
+        // e is accepted, and part of A, though nothing is ever evicted
+        assert!(all.contains(EventClass::EVICTED));
+        assert_eq!(EventClass::parse("KEe").unwrap().to_config_string(), "eKE");
+    }
+
+    #[tokio::test]
+    async fn test_notify_publishes_enabled_classes() {
+        let pubsub = Arc::new(PubSub::new());
+        let events = KeyspaceEvents::new();
+        events.attach(Arc::clone(&pubsub));
+        let mut keyspace = pubsub.get_or_create_channel("__keyspace@0__:mykey");
+        let mut keyevent = pubsub.get_or_create_channel("__keyevent@0__:lpush");
+
+        // Off by default
+        events.notify(0, EventClass::LIST, "lpush", "mykey");
+        assert!(keyspace.try_recv().is_err());
+
+        assert!(events.configure("KEl"));
+        events.notify(0, EventClass::LIST, "lpush", "mykey");
+        events.notify(0, EventClass::STRING, "set", "mykey");
+        assert_eq!(keyspace.try_recv().unwrap(), b"lpush".to_vec());
+        assert_eq!(keyevent.try_recv().unwrap(), b"mykey".to_vec());
+        assert!(keyspace.try_recv().is_err());
+
+        assert!(!events.configure("Kw"));
+        assert_eq!(events.flags(), EventClass::KEYSPACE | EventClass::KEYEVENT | EventClass::LIST);
+    }
+}
@@ -0,0 +1,579 @@
+// Quicklist: lists as a deque of listpack nodes
+//
+// Each node packs a run of consecutive elements into one listpack, bounded
+// by list-max-listpack-size (an entry count, or a byte size when negative),
+// so pushes and pops at either end touch one small buffer and indexing
+// skips whole nodes by their element counts. With list-compress-depth set,
+// nodes further than that many from both ends are kept LZF-compressed and
+// expanded only while they are read or edited: long queues are mostly
+// worked at the ends, so their middle costs a fraction of the memory.
+
+use super::encoding::{self, EncodingLimits};
+use super::listpack::Listpack;
+use super::lzf;
+use bytes::Bytes;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Nodes smaller than this are not worth compressing
+const MIN_COMPRESS_BYTES: usize = 48;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Plain(Listpack),
+    /// LZF-compressed listpack bytes
+    Compressed { data: Vec<u8>, raw_len: usize, count: usize },
+}
+
+impl Node {
+    fn single(entry: &[u8]) -> Self {
+        let mut listpack = Listpack::new();
+        listpack.push(entry);
+        Node::Plain(listpack)
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Plain(listpack) => listpack.len(),
+            Node::Compressed { count, .. } => *count,
+        }
+    }
+
+    /// Size of the node's listpack when expanded
+    fn encoded_len(&self) -> usize {
+        match self {
+            Node::Plain(listpack) => listpack.encoded_len(),
+            Node::Compressed { raw_len, .. } => *raw_len,
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        matches!(self, Node::Compressed { .. })
+    }
+
+    /// The node's entries, expanding a compressed node into a copy
+    fn listpack(&self) -> Cow<'_, Listpack> {
+        match self {
+            Node::Plain(listpack) => Cow::Borrowed(listpack),
+            Node::Compressed { data, raw_len, count } => {
+                Cow::Owned(Listpack::from_bytes(lzf::decompress(data, *raw_len), *count))
+            }
+        }
+    }
+
+    /// Expand the node in place for editing
+    fn expand(&mut self) -> &mut Listpack {
+        if self.is_compressed() {
+            *self = Node::Plain(self.listpack().into_owned());
+        }
+        match self {
+            Node::Plain(listpack) => listpack,
+            Node::Compressed { .. } => unreachable!("node was just expanded"),
+        }
+    }
+
+    fn compress(&mut self) {
+        let Node::Plain(listpack) = self else {
+            return;
+        };
+        if listpack.encoded_len() < MIN_COMPRESS_BYTES {
+            return;
+        }
+        if let Some(data) = lzf::compress(listpack.as_bytes()) {
+            let (raw_len, count) = (listpack.encoded_len(), listpack.len());
+            *self = Node::Compressed { data, raw_len, count };
+        }
+    }
+}
+
+/// A list of byte strings stored as a deque of listpack nodes
+#[derive(Debug, Clone)]
+pub struct QuickList {
+    nodes: VecDeque<Node>,
+    len: usize,
+    limits: Arc<EncodingLimits>,
+}
+
+impl Default for QuickList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuickList {
+    pub fn new() -> Self {
+        Self::with_limits(encoding::defaults())
+    }
+
+    /// An empty list whose nodes are sized and compressed according to `limits`
+    pub fn with_limits(limits: Arc<EncodingLimits>) -> Self {
+        Self {
+            nodes: VecDeque::new(),
+            len: 0,
+            limits,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Nodes currently held compressed
+    pub fn compressed_nodes(&self) -> usize {
+        self.nodes.iter().filter(|node| node.is_compressed()).count()
+    }
+
+    /// "listpack" while the list fits in one node, "quicklist" after
+    pub fn encoding(&self) -> &'static str {
+        if self.nodes.len() <= 1 {
+            "listpack"
+        } else {
+            "quicklist"
+        }
+    }
+
+    /// Whether node `i` can take `entry` within list-max-listpack-size
+    fn fits(&self, i: usize, entry: &[u8]) -> bool {
+        let node = &self.nodes[i];
+        match self.limits.list_max_listpack_size.get_signed() {
+            entries if entries >= 0 => node.len() < (entries as usize).max(1),
+            size => node.encoded_len() + Listpack::entry_size(entry) <= 4096 << (-size - 1),
+        }
+    }
+
+    /// Node holding element `index` and the element's position in it. For
+    /// `len` this is the end of the last node. Walks from the nearer end.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        if index < self.len / 2 {
+            let mut index = index;
+            for (i, node) in self.nodes.iter().enumerate() {
+                if index < node.len() {
+                    return (i, index);
+                }
+                index -= node.len();
+            }
+        } else {
+            let mut from_end = self.len - index;
+            for (i, node) in self.nodes.iter().enumerate().rev() {
+                if from_end <= node.len() {
+                    return (i, node.len() - from_end);
+                }
+                from_end -= node.len();
+            }
+        }
+        (0, 0)
+    }
+
+    /// Bring nodes to the state list-compress-depth asks for: compressed
+    /// unless within `depth` of an end. Only the `touched` nodes and those
+    /// near the ends can have changed position relative to the ends.
+    fn settle(&mut self, touched: Range<usize>) {
+        let depth = self.limits.list_compress_depth.get();
+        let n = self.nodes.len();
+        let near_ends = (0..=depth.min(n)).chain(n.saturating_sub(depth + 1)..n);
+        for i in touched.chain(near_ends) {
+            let Some(node) = self.nodes.get_mut(i) else {
+                continue;
+            };
+            let interior = depth > 0 && i >= depth && i + depth < n;
+            if interior {
+                node.compress();
+            } else if node.is_compressed() {
+                node.expand();
+            }
+        }
+    }
+
+    /// Insert `entry` at `offset` in node `i`, going to a neighbour or a new
+    /// node when that one is full
+    fn insert_at(&mut self, i: usize, offset: usize, entry: &[u8]) {
+        let count = self.nodes[i].len();
+        let touched = if self.fits(i, entry) {
+            self.nodes[i].expand().insert(offset, entry);
+            i..i + 1
+        } else if offset == 0 && i > 0 && self.fits(i - 1, entry) {
+            self.nodes[i - 1].expand().push(entry);
+            i - 1..i
+        } else if offset == count && i + 1 < self.nodes.len() && self.fits(i + 1, entry) {
+            self.nodes[i + 1].expand().insert(0, entry);
+            i + 1..i + 2
+        } else if offset == 0 {
+            self.nodes.insert(i, Node::single(entry));
+            i..i + 1
+        } else if offset == count {
+            self.nodes.insert(i + 1, Node::single(entry));
+            i + 1..i + 2
+        } else {
+            // Split the full node around the insertion point
+            let listpack = self.nodes[i].expand();
+            let tail: Listpack = listpack.range(offset, count).collect();
+            listpack.splice(offset, count - offset, &[]);
+            self.nodes.insert(i + 1, Node::Plain(tail));
+            if self.fits(i, entry) {
+                self.nodes[i].expand().push(entry);
+            } else {
+                self.nodes.insert(i + 1, Node::single(entry));
+            }
+            i..i + 3
+        };
+        self.len += 1;
+        self.settle(touched);
+    }
+
+    pub fn push_front(&mut self, entry: &[u8]) {
+        if self.nodes.is_empty() {
+            self.nodes.push_back(Node::single(entry));
+            self.len = 1;
+        } else {
+            self.insert_at(0, 0, entry);
+        }
+    }
+
+    pub fn push_back(&mut self, entry: &[u8]) {
+        if self.nodes.is_empty() {
+            self.push_front(entry);
+        } else {
+            let last = self.nodes.len() - 1;
+            let count = self.nodes[last].len();
+            self.insert_at(last, count, entry);
+        }
+    }
+
+    /// Insert `entry` so that it ends up at `index` (at most `len`)
+    pub fn insert(&mut self, index: usize, entry: &[u8]) {
+        if index >= self.len {
+            self.push_back(entry);
+        } else {
+            let (i, offset) = self.locate(index);
+            self.insert_at(i, offset, entry);
+        }
+    }
+
+    /// Remove and return entry `offset` of node `i`
+    fn take(&mut self, i: usize, offset: usize) -> Bytes {
+        let listpack = self.nodes[i].expand();
+        let entry = Bytes::copy_from_slice(listpack.get(offset).unwrap_or_default());
+        listpack.remove(offset);
+        if listpack.is_empty() {
+            self.nodes.remove(i);
+        }
+        self.len -= 1;
+        self.settle(i..i + 1);
+        entry
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.take(0, 0))
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        if self.is_empty() {
+            return None;
+        }
+        let last = self.nodes.len() - 1;
+        let offset = self.nodes[last].len() - 1;
+        Some(self.take(last, offset))
+    }
+
+    /// Remove and return the element at `index`
+    pub fn remove(&mut self, index: usize) -> Option<Bytes> {
+        if index >= self.len {
+            return None;
+        }
+        let (i, offset) = self.locate(index);
+        Some(self.take(i, offset))
+    }
+
+    pub fn get(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        if index >= self.len {
+            return None;
+        }
+        let (i, offset) = self.locate(index);
+        match &self.nodes[i] {
+            Node::Plain(listpack) => listpack.get(offset).map(Cow::Borrowed),
+            node => node.listpack().get(offset).map(|entry| Cow::Owned(entry.to_vec())),
+        }
+    }
+
+    /// Replace the element at `index`, returning false if it is out of range
+    pub fn set(&mut self, index: usize, entry: &[u8]) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        let (i, offset) = self.locate(index);
+        self.nodes[i].expand().replace(offset, entry);
+        self.settle(i..i + 1);
+        true
+    }
+
+    /// Remove `count` elements starting at `start`, dropping whole nodes
+    /// where the range covers them
+    pub fn remove_range(&mut self, start: usize, count: usize) {
+        let count = count.min(self.len.saturating_sub(start));
+        if count == 0 {
+            return;
+        }
+        let (mut i, mut offset) = self.locate(start);
+        let mut left = count;
+        while left > 0 {
+            let node_len = self.nodes[i].len();
+            let taken = left.min(node_len - offset);
+            if taken == node_len {
+                self.nodes.remove(i);
+            } else {
+                self.nodes[i].expand().splice(offset, taken, &[]);
+                i += 1;
+            }
+            left -= taken;
+            offset = 0;
+        }
+        self.len -= count;
+        self.settle(i.saturating_sub(1)..i + 1);
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.range(0, self.len)
+    }
+
+    /// Elements `start..end`, double-ended
+    pub fn range(&self, start: usize, end: usize) -> Iter<'_> {
+        let end = end.min(self.len);
+        let start = start.min(end);
+        let (front_node, front_index) = self.locate(start);
+        let (back_node, back_index) = self.locate(end);
+        Iter {
+            list: self,
+            front: End { node: front_node, index: front_index, view: None },
+            back: End { node: back_node, index: back_index, view: None },
+            remaining: end - start,
+        }
+    }
+}
+
+impl PartialEq for QuickList {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: AsRef<[u8]>> Extend<T> for QuickList {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for entry in iter {
+            self.push_back(entry.as_ref());
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> FromIterator<T> for QuickList {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = QuickList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+/// One end of an `Iter`: the node it is in and, once the node has been
+/// read, its entries and the byte position within them
+struct End<'a> {
+    node: usize,
+    index: usize,
+    view: Option<(Cow<'a, Listpack>, usize)>,
+}
+
+impl<'a> End<'a> {
+    fn view(&mut self, list: &'a QuickList) -> (&Cow<'a, Listpack>, &mut usize) {
+        let (node, index) = (self.node, self.index);
+        let (view, pos) = self.view.get_or_insert_with(|| {
+            let view = list.nodes[node].listpack();
+            let pos = view.offset(index);
+            (view, pos)
+        });
+        (view, pos)
+    }
+}
+
+/// Borrow an entry of a plain node; copy it out of an expanded one
+fn entry_of<'a>(view: &Cow<'a, Listpack>, entry: impl Fn(&Listpack) -> (&[u8], usize)) -> (Cow<'a, [u8]>, usize) {
+    match view {
+        Cow::Borrowed(listpack) => {
+            let listpack: &'a Listpack = listpack;
+            let (data, pos) = entry(listpack);
+            (Cow::Borrowed(data), pos)
+        }
+        Cow::Owned(listpack) => {
+            let (data, pos) = entry(listpack);
+            (Cow::Owned(data.to_vec()), pos)
+        }
+    }
+}
+
+/// Iterator over a run of elements, from either end
+pub struct Iter<'a> {
+    list: &'a QuickList,
+    front: End<'a>,
+    back: End<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Cow<'a, [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let (view, pos) = self.front.view(self.list);
+            if *pos < view.encoded_len() {
+                let (entry, next) = entry_of(view, |listpack| listpack.next_entry(*pos));
+                *pos = next;
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.front = End { node: self.front.node + 1, index: 0, view: None };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let (view, pos) = self.back.view(self.list);
+            if *pos > 0 {
+                let (entry, start) = entry_of(view, |listpack| listpack.prev_entry(*pos));
+                *pos = start;
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            let node = self.back.node - 1;
+            self.back = End { node, index: self.list.nodes[node].len(), view: None };
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(list: &QuickList) -> Vec<Vec<u8>> {
+        list.iter().map(|entry| entry.into_owned()).collect()
+    }
+
+    #[test]
+    fn test_quicklist_matches_deque() {
+        let mut list = QuickList::new();
+        let mut model: VecDeque<Vec<u8>> = VecDeque::new();
+
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        for step in 0..20_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let value = format!("v{}", seed % 1000).into_bytes();
+            match seed % 8 {
+                0 | 1 => {
+                    list.push_front(&value);
+                    model.push_front(value);
+                }
+                2 | 3 => {
+                    list.push_back(&value);
+                    model.push_back(value);
+                }
+                4 => assert_eq!(list.pop_front().map(|b| b.to_vec()), model.pop_front()),
+                5 => assert_eq!(list.pop_back().map(|b| b.to_vec()), model.pop_back()),
+                6 => {
+                    let index = (seed as usize / 8) % (model.len() + 1);
+                    list.insert(index, &value);
+                    model.insert(index, value);
+                }
+                _ if !model.is_empty() => {
+                    let index = (seed as usize / 8) % model.len();
+                    assert!(list.set(index, &value));
+                    model[index] = value;
+                    assert_eq!(list.remove(index / 2).map(|b| b.to_vec()), model.remove(index / 2));
+                }
+                _ => {}
+            }
+            if step % 1000 == 0 {
+                assert_eq!(entries(&list), Vec::from(model.clone()));
+            }
+        }
+
+        assert_eq!(list.len(), model.len());
+        assert!(list.node_count() > 1);
+        let expected = Vec::from(model.clone());
+        assert_eq!(entries(&list), expected);
+        let mut backward: Vec<Vec<u8>> = list.iter().rev().map(|e| e.into_owned()).collect();
+        backward.reverse();
+        assert_eq!(backward, expected);
+        for i in (0..expected.len()).step_by(37) {
+            assert_eq!(list.get(i).as_deref(), Some(expected[i].as_slice()));
+        }
+
+        let middle: Vec<Vec<u8>> = list.range(100, 300).rev().map(|e| e.into_owned()).collect();
+        let expected_middle: Vec<Vec<u8>> = expected[100..300].iter().rev().cloned().collect();
+        assert_eq!(middle, expected_middle);
+
+        list.remove_range(50, 500);
+        model.drain(50..550);
+        assert_eq!(entries(&list), Vec::from(model));
+    }
+
+    #[test]
+    fn test_quicklist_compresses_interior_nodes() {
+        let limits = Arc::new(EncodingLimits::new());
+        assert!(limits.apply("list-compress-depth", "1"));
+        let mut list = QuickList::with_limits(limits);
+        list.extend((0..5000).map(|i| format!("element:{:06}", i)));
+        let n = list.node_count();
+        assert_eq!(list.compressed_nodes(), n - 2);
+        assert!(!list.nodes[0].is_compressed() && !list.nodes[n - 1].is_compressed());
+        let stored: usize = list.nodes.iter().map(|node| match node {
+            Node::Compressed { data, .. } => data.len(),
+            Node::Plain(listpack) => listpack.encoded_len(),
+        }).sum();
+        assert!(stored < list.nodes.iter().map(Node::encoded_len).sum::<usize>() / 2);
+
+        // Reads see through compression; edits keep the interior compressed
+        assert_eq!(list.get(2500).as_deref(), Some(&b"element:002500"[..]));
+        assert_eq!(list.iter().nth(1234).as_deref(), Some(&b"element:001234"[..]));
+        assert_eq!(list.iter().rev().nth(10).as_deref(), Some(&b"element:004989"[..]));
+        list.insert(2500, b"inserted");
+        assert_eq!(list.get(2500).as_deref(), Some(&b"inserted"[..]));
+        assert_eq!(list.get(2501).as_deref(), Some(&b"element:002500"[..]));
+        assert_eq!(list.compressed_nodes(), list.node_count() - 2);
+
+        // Popping the head node empty exposes its neighbour, which expands
+        let head = list.nodes[0].len();
+        for _ in 0..head {
+            list.pop_front();
+        }
+        assert!(!list.nodes[0].is_compressed());
+        assert_eq!(list.compressed_nodes(), list.node_count() - 2);
+        list.remove_range(0, 4000);
+        assert_eq!(list.compressed_nodes(), list.node_count().saturating_sub(2));
+        assert_eq!(list.iter().next_back().as_deref(), Some(&b"element:004999"[..]));
+    }
+}
@@ -0,0 +1,261 @@
+// Hash-ordered key index behind SCAN cursors
+//
+// Keys are kept sorted by a fixed 64-bit hash and the cursor handed to the
+// client is the hash to resume from. Writes between calls only add or drop
+// entries around the cursor, so a key present for the whole iteration is
+// returned exactly once, and each call costs O(log n + COUNT). The index is
+// split by the top bits of the hash so writers to different keys rarely
+// contend, while the shards still cover consecutive ranges of the cursor.
+// Entries share the key's allocation with the database, so the index costs
+// a hash and a pointer per key rather than a second copy of it.
+// Large hashes, sets and sorted sets keep the same ordering of their
+// elements for HSCAN, SSCAN and ZSCAN.
+
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::Hasher;
+use std::sync::{Arc, RwLock};
+
+const SHARD_BITS: u32 = 6;
+const SHARDS: usize = 1 << SHARD_BITS;
+
+/// Position of a key or collection element in scan order. Fixed keys make
+/// it stable for the life of the process, which is all a cursor needs.
+pub fn scan_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Cursor that resumes after `hash`; 0 once the hash space is exhausted
+fn cursor_after(hash: u64) -> u64 {
+    hash.checked_add(1).unwrap_or(0)
+}
+
+/// Keys of one index shard, ordered by `scan_hash`
+type Shard = BTreeSet<(u64, Arc<str>)>;
+
+/// Keys of a database ordered by `scan_hash`
+pub struct ScanIndex {
+    shards: Vec<RwLock<Shard>>,
+}
+
+impl Default for ScanIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanIndex {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::new(BTreeSet::new())).collect(),
+        }
+    }
+
+    fn shard(hash: u64) -> usize {
+        (hash >> (64 - SHARD_BITS)) as usize
+    }
+
+    pub fn add(&self, key: &Arc<str>) {
+        let hash = scan_hash(key.as_bytes());
+        self.shards[Self::shard(hash)].write().unwrap().insert((hash, Arc::clone(key)));
+    }
+
+    pub fn remove(&self, key: &str) {
+        let hash = scan_hash(key.as_bytes());
+        let mut shard = self.shards[Self::shard(hash)].write().unwrap();
+        let found = shard
+            .range((hash, Arc::from(""))..)
+            .take_while(|(h, _)| *h == hash)
+            .find(|(_, k)| &**k == key)
+            .cloned();
+        if let Some(entry) = found {
+            shard.remove(&entry);
+        }
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+    }
+
+    /// At least `count` keys (fewer at the end) whose hash is at or past
+    /// `cursor`, and the cursor to continue from. Keys sharing the last
+    /// hash are all returned so the next call can start past it.
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<String>, u64) {
+        let count = count.max(1);
+        let mut keys = Vec::new();
+        let mut last = None;
+        for shard in &self.shards[Self::shard(cursor)..] {
+            let shard = shard.read().unwrap();
+            for (hash, key) in shard.range((cursor, Arc::from(""))..) {
+                if let Some(last) = last.filter(|last| keys.len() >= count && last != hash) {
+                    return (keys, cursor_after(last));
+                }
+                keys.push(key.to_string());
+                last = Some(*hash);
+            }
+        }
+        (keys, 0)
+    }
+}
+
+/// Elements of one large collection ordered by `scan_hash`, kept next to
+/// its table so a scan step costs O(log n + COUNT)
+#[derive(Debug, Clone, Default)]
+pub struct ScanOrder {
+    elements: BTreeSet<(u64, Bytes)>,
+}
+
+impl ScanOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an element that is not in the order yet
+    pub fn insert(&mut self, element: Bytes) {
+        self.elements.insert((scan_hash(&element), element));
+    }
+
+    pub fn remove(&mut self, element: &[u8]) {
+        let hash = scan_hash(element);
+        let found = self
+            .elements
+            .range((hash, Bytes::new())..)
+            .take_while(|(h, _)| *h == hash)
+            .find(|(_, e)| e == element)
+            .cloned();
+        if let Some(entry) = found {
+            self.elements.remove(&entry);
+        }
+    }
+
+    /// At least `count` elements (fewer at the end) whose hash is at or
+    /// past `cursor`, and the cursor to continue from, as `ScanIndex::scan`
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<&Bytes>, u64) {
+        let count = count.max(1);
+        let mut step = Vec::new();
+        let mut last = None;
+        for (hash, element) in self.elements.range((cursor, Bytes::new())..) {
+            if let Some(last) = last.filter(|last| step.len() >= count && last != hash) {
+                return (step, cursor_after(last));
+            }
+            step.push(element);
+            last = Some(*hash);
+        }
+        (step, 0)
+    }
+}
+
+/// One step of SCAN over the elements of a collection: up to `count`
+/// elements at or past `cursor` in hash order (plus any sharing the last
+/// hash), and the cursor to continue from. Selecting the step is linear in
+/// the collection size but needs no state between calls, so it suits the
+/// compact encodings, whose size the encoding limits bound. It visits
+/// elements in the same order as `ScanOrder`, so a cursor carries over
+/// when a collection converts mid-iteration.
+pub fn scan_elements<T>(
+    elements: impl Iterator<Item = T>,
+    hash: impl Fn(&T) -> u64,
+    cursor: u64,
+    count: usize,
+) -> (Vec<T>, u64) {
+    let mut pending: Vec<(u64, T)> = elements
+        .map(|element| (hash(&element), element))
+        .filter(|(h, _)| *h >= cursor)
+        .collect();
+    let count = count.max(1);
+    if pending.len() <= count {
+        pending.sort_unstable_by_key(|(h, _)| *h);
+        return (pending.into_iter().map(|(_, element)| element).collect(), 0);
+    }
+
+    let (_, &mut (last, _), _) = pending.select_nth_unstable_by_key(count - 1, |(h, _)| *h);
+    let mut step: Vec<(u64, T)> = pending.into_iter().filter(|(h, _)| *h <= last).collect();
+    step.sort_unstable_by_key(|(h, _)| *h);
+    (step.into_iter().map(|(_, element)| element).collect(), cursor_after(last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_scan_survives_writes() {
+        let index = ScanIndex::new();
+        for i in 0..1000 {
+            index.add(&Arc::from(format!("stable:{}", i)));
+        }
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut calls = 0;
+        loop {
+            let (keys, next) = index.scan(cursor, 10);
+            assert!(keys.len() >= 10 || next == 0);
+            seen.extend(keys);
+            // Churn between calls: new keys arrive and old ones go
+            index.add(&Arc::from(format!("new:{}", calls)));
+            index.remove(&format!("new:{}", calls / 2));
+            calls += 1;
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        assert!(calls < 150);
+        for i in 0..1000 {
+            assert!(seen.contains(&format!("stable:{}", i)));
+        }
+    }
+
+    #[test]
+    fn test_scan_elements_steps() {
+        let members: Vec<String> = (0..100).map(|i| format!("m{}", i)).collect();
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (step, next) = scan_elements(members.iter(), |m| scan_hash(m.as_bytes()), cursor, 7);
+            assert!(step.len() >= 7 || next == 0);
+            seen.extend(step);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        // Every element exactly once
+        seen.sort();
+        let mut expected: Vec<_> = members.iter().collect();
+        expected.sort();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn test_scan_order_matches_scan_elements() {
+        let members: Vec<Bytes> = (0..100).map(|i| Bytes::from(format!("m{}", i))).collect();
+        let mut order = ScanOrder::new();
+        for member in &members {
+            order.insert(member.clone());
+        }
+        order.insert(Bytes::from("gone"));
+        order.remove(b"gone");
+        order.remove(b"never-added");
+
+        let mut cursor = 0;
+        loop {
+            let (step, next) = order.scan(cursor, 7);
+            let (expected, expected_next) = scan_elements(members.iter(), |m| scan_hash(m), cursor, 7);
+            assert_eq!(step, expected);
+            assert_eq!(next, expected_next);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// Skiplist with spans: the ordered half of a sorted set
+//
+// Same layout as Redis's zskiplist. Every forward link records how many
+// level-0 steps it skips, so the rank of an element and the element at a
+// rank are both found in O(log n) by summing spans along the search path.
+// Nodes live in an arena and link to each other by index.
+
+use bytes::Bytes;
+use ordered_float::OrderedFloat;
+use std::cmp::Ordering;
+
+const MAX_LEVEL: usize = 32;
+/// Index of the header node, which holds no element
+const HEAD: usize = 0;
+/// Null link
+const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    forward: usize,
+    /// Level-0 steps from this node to `forward`
+    span: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    score: f64,
+    member: Bytes,
+    backward: usize,
+    levels: Vec<Level>,
+}
+
+/// (score, member) pairs in ascending order, with rank queries
+#[derive(Debug, Clone)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    /// Arena slots of removed nodes, reused by later inserts
+    free: Vec<usize>,
+    tail: usize,
+    level: usize,
+    len: usize,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SkipList {
+    pub fn new() -> Self {
+        let head = Node {
+            score: 0.0,
+            member: Bytes::new(),
+            backward: NIL,
+            levels: vec![Level { forward: NIL, span: 0 }; MAX_LEVEL],
+        };
+        SkipList {
+            nodes: vec![head],
+            free: Vec::new(),
+            tail: NIL,
+            level: 1,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Order of node `x` relative to (score, member)
+    fn cmp_node(&self, x: usize, score: f64, member: &[u8]) -> Ordering {
+        let node = &self.nodes[x];
+        (OrderedFloat(node.score), node.member.as_ref()).cmp(&(OrderedFloat(score), member))
+    }
+
+    fn forward(&self, x: usize, level: usize) -> usize {
+        self.nodes[x].levels[level].forward
+    }
+
+    /// Level of a new node: each extra level with probability 1/4
+    fn random_level() -> usize {
+        let bits: u64 = rand::random();
+        (1 + bits.trailing_zeros() as usize / 2).min(MAX_LEVEL)
+    }
+
+    /// Insert a pair that is not in the list yet
+    pub fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            loop {
+                let next = self.forward(x, i);
+                if next == NIL || self.cmp_node(next, score, &member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            score,
+            member,
+            backward: if update[0] == HEAD { NIL } else { update[0] },
+            levels: vec![Level { forward: NIL, span: 0 }; level],
+        };
+        let x = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for (i, (&prev, &prev_rank)) in update.iter().zip(&rank).enumerate().take(level) {
+            let steps = rank[0] - prev_rank;
+            self.nodes[x].levels[i] = Level {
+                forward: self.nodes[prev].levels[i].forward,
+                span: self.nodes[prev].levels[i].span - steps,
+            };
+            self.nodes[prev].levels[i] = Level { forward: x, span: steps + 1 };
+        }
+        // Links above the new node now skip one more element
+        for (i, &prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[prev].levels[i].span += 1;
+        }
+
+        match self.forward(x, 0) {
+            NIL => self.tail = x,
+            next => self.nodes[next].backward = x,
+        }
+        self.len += 1;
+    }
+
+    /// Remove a pair, returning whether it was present
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.forward(x, i);
+                if next == NIL || self.cmp_node(next, score, member) != Ordering::Less {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let x = self.forward(x, 0);
+        if x == NIL || self.cmp_node(x, score, member) != Ordering::Equal {
+            return false;
+        }
+
+        for (i, &prev) in update.iter().enumerate().take(self.level) {
+            if self.nodes[prev].levels[i].forward == x {
+                let removed = self.nodes[x].levels[i];
+                self.nodes[prev].levels[i] = Level {
+                    forward: removed.forward,
+                    span: self.nodes[prev].levels[i].span + removed.span - 1,
+                };
+            } else {
+                self.nodes[prev].levels[i].span -= 1;
+            }
+        }
+
+        let backward = self.nodes[x].backward;
+        match self.forward(x, 0) {
+            NIL => self.tail = backward,
+            next => self.nodes[next].backward = backward,
+        }
+        while self.level > 1 && self.forward(HEAD, self.level - 1) == NIL {
+            self.level -= 1;
+        }
+
+        // Drop the member now rather than when the slot is reused
+        self.nodes[x].member = Bytes::new();
+        self.nodes[x].levels = Vec::new();
+        self.free.push(x);
+        self.len -= 1;
+        true
+    }
+
+    /// 0-based position of a pair in ascending order
+    pub fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.forward(x, i);
+                if next == NIL || self.cmp_node(next, score, member) == Ordering::Greater {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEAD && self.cmp_node(x, score, member) == Ordering::Equal {
+                return Some(traversed - 1);
+            }
+        }
+        None
+    }
+
+    /// Arena index of the element at 0-based `rank`
+    fn node_at(&self, rank: usize) -> usize {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.forward(x, i);
+                if next == NIL || traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return x;
+            }
+        }
+        NIL
+    }
+
+    /// The element at 0-based `rank`
+    pub fn get(&self, rank: usize) -> Option<(&Bytes, f64)> {
+        if rank >= self.len {
+            return None;
+        }
+        let node = &self.nodes[self.node_at(rank)];
+        Some((&node.member, node.score))
+    }
+
+    /// Number of elements scoring below `score`, or at most `score` when
+    /// `inclusive`; this is also the rank of the first element past them
+    pub fn count_below(&self, score: f64, inclusive: bool) -> usize {
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let next = self.forward(x, i);
+                if next == NIL {
+                    break;
+                }
+                let next_score = self.nodes[next].score;
+                if next_score > score || (next_score == score && !inclusive) {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+        }
+        traversed
+    }
+
+    /// Elements with ranks in `start..end`, in ascending order
+    pub fn range(&self, start: usize, end: usize) -> Iter<'_> {
+        let end = end.min(self.len);
+        if start >= end {
+            return Iter { list: self, front: NIL, back: NIL, remaining: 0 };
+        }
+        Iter {
+            list: self,
+            front: self.node_at(start),
+            back: if end == self.len { self.tail } else { self.node_at(end - 1) },
+            remaining: end - start,
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            list: self,
+            front: self.forward(HEAD, 0),
+            back: self.tail,
+            remaining: self.len,
+        }
+    }
+
+    pub fn first(&self) -> Option<(&Bytes, f64)> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<(&Bytes, f64)> {
+        self.iter().next_back()
+    }
+}
+
+/// Double-ended iterator over a run of consecutive elements
+pub struct Iter<'a> {
+    list: &'a SkipList,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Bytes, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.list.nodes[self.front];
+        self.front = node.levels[0].forward;
+        self.remaining -= 1;
+        Some((&node.member, node.score))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.list.nodes[self.back];
+        self.back = node.backward;
+        self.remaining -= 1;
+        Some((&node.member, node.score))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_skiplist_matches_btree() {
+        let mut list = SkipList::new();
+        let mut model = BTreeSet::new();
+
+        // Deterministic pseudo-random inserts and removals with duplicate scores
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..5000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let score = (seed % 50) as f64;
+            let member = Bytes::from(format!("m{}", seed % 400));
+            let key = (OrderedFloat(score), member.clone());
+            if seed.is_multiple_of(3) {
+                assert_eq!(list.remove(score, &member), model.remove(&key));
+            } else if !model.contains(&key) {
+                list.insert(score, member);
+                model.insert(key);
+            }
+        }
+
+        assert_eq!(list.len(), model.len());
+        let expected: Vec<_> = model.iter().map(|(s, m)| (m.clone(), s.0)).collect();
+        let forward: Vec<_> = list.iter().map(|(m, s)| (m.clone(), s)).collect();
+        assert_eq!(forward, expected);
+        let mut backward: Vec<_> = list.iter().rev().map(|(m, s)| (m.clone(), s)).collect();
+        backward.reverse();
+        assert_eq!(backward, expected);
+
+        for (i, (member, score)) in expected.iter().enumerate() {
+            assert_eq!(list.rank(*score, member), Some(i));
+            assert_eq!(list.get(i), Some((member, *score)));
+        }
+        assert_eq!(list.rank(1000.0, b"missing"), None);
+
+        let below = expected.iter().filter(|(_, s)| *s < 20.0).count();
+        let at_most = expected.iter().filter(|(_, s)| *s <= 20.0).count();
+        assert_eq!(list.count_below(20.0, false), below);
+        assert_eq!(list.count_below(20.0, true), at_most);
+
+        let window: Vec<_> = list.range(10, 20).rev().map(|(m, s)| (m.clone(), s)).collect();
+        let expected_window: Vec<_> = expected[10..20].iter().rev().cloned().collect();
+        assert_eq!(window, expected_window);
+        assert_eq!(list.range(5, 5).count(), 0);
+    }
+}
@@ -0,0 +1,76 @@
+// Hash slot -> keys index, kept by databases running in cluster mode
+
+use crate::cluster::key_hash_slot;
+use dashmap::DashMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Keys of a database grouped by the hash slot they map to. Lets
+/// CLUSTER COUNTKEYSINSLOT answer in O(1) and lets resharding enumerate
+/// or drop the keys of one slot without scanning the whole keyspace. Keys
+/// share their allocation with the database.
+#[derive(Default)]
+pub struct SlotIndex {
+    /// Only slots holding at least one key have an entry
+    slots: DashMap<u16, BTreeSet<Arc<str>>>,
+}
+
+impl SlotIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, key: &Arc<str>) {
+        let slot = key_hash_slot(key.as_bytes());
+        self.slots.entry(slot).or_default().insert(Arc::clone(key));
+    }
+
+    pub fn remove(&self, key: &str) {
+        let slot = key_hash_slot(key.as_bytes());
+        self.slots.remove_if_mut(&slot, |_, keys| {
+            keys.remove(key);
+            keys.is_empty()
+        });
+    }
+
+    /// Number of keys in a slot
+    pub fn count(&self, slot: u16) -> usize {
+        self.slots.get(&slot).map_or(0, |keys| keys.len())
+    }
+
+    /// Up to `count` keys of a slot, in lexicographic order
+    pub fn keys(&self, slot: u16, count: usize) -> Vec<String> {
+        self.slots
+            .get(&slot)
+            .map_or_else(Vec::new, |keys| keys.iter().take(count).map(|key| key.to_string()).collect())
+    }
+
+    pub fn clear(&self) {
+        self.slots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_index_tracks_keys() {
+        let index = SlotIndex::new();
+        let slot = key_hash_slot(b"{user}");
+        for key in ["{user}:2", "{user}:1", "{user}:1", "other"] {
+            index.add(&Arc::from(key));
+        }
+
+        assert_eq!(index.count(slot), 2);
+        assert_eq!(index.keys(slot, 10), vec!["{user}:1", "{user}:2"]);
+        assert_eq!(index.keys(slot, 1), vec!["{user}:1"]);
+
+        index.remove("{user}:1");
+        index.remove("{user}:2");
+        assert_eq!(index.count(slot), 0);
+        assert!(index.keys(slot, 10).is_empty());
+        assert!(index.slots.get(&slot).is_none());
+        assert_eq!(index.count(key_hash_slot(b"other")), 1);
+    }
+}
@@ -0,0 +1,73 @@
+// Point-in-time snapshots taken while writes go on
+//
+// Pinning a snapshot only starts a `Capture` in every database. From then
+// on the first write to a key saves the key as it was, before changing it.
+// The copy made afterwards takes each key's saved state if it has one and
+// its live state otherwise, so it shows the databases as they were at the
+// pin however long it runs. While a copy runs, every write costs one more
+// map lookup, plus a clone the first time it changes a key.
+
+use super::db::{Database, DbInstance};
+use super::field_expires::FieldExpires;
+use super::types::RedisValue;
+use dashmap::DashMap;
+use std::sync::Arc;
+
+/// A key as a snapshot sees it
+#[derive(Clone)]
+pub(super) struct KeyState {
+    pub value: RedisValue,
+    pub expire_at: Option<u64>,
+    pub field_expires: Option<FieldExpires>,
+}
+
+/// The keys of one database written since a snapshot was pinned, each with
+/// its state at the pin (None if it did not exist yet)
+#[derive(Default)]
+pub(super) struct Capture {
+    pub saved: DashMap<String, Option<KeyState>>,
+}
+
+/// The databases of a server pinned at one moment; `copy` copies them as
+/// they were then
+pub struct PinnedSnapshot {
+    pins: Pins,
+    /// Empty databases of the same shape, filled by `copy`
+    target: Database,
+}
+
+impl PinnedSnapshot {
+    pub(super) fn new(databases: Vec<Arc<DbInstance>>, target: Database) -> Self {
+        let pins = databases
+            .into_iter()
+            .map(|db| {
+                let capture = db.start_capture();
+                (db, capture)
+            })
+            .collect();
+        Self { pins: Pins(pins), target }
+    }
+
+    /// Copy the pinned databases. This takes as long as copying all the
+    /// data, so run it off the async runtime.
+    pub fn copy(self) -> Database {
+        let PinnedSnapshot { pins, target } = self;
+        for (index, (db, capture)) in pins.0.iter().enumerate() {
+            if let Some(to) = target.get_db(index) {
+                db.copy_pinned(capture, &to);
+            }
+        }
+        target
+    }
+}
+
+/// Captures in progress, ended when the snapshot is copied or dropped
+struct Pins(Vec<(Arc<DbInstance>, Arc<Capture>)>);
+
+impl Drop for Pins {
+    fn drop(&mut self) {
+        for (db, capture) in &self.0 {
+            db.end_capture(capture);
+        }
+    }
+}
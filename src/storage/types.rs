@@ -1,7 +1,15 @@
 // Redis value types
 
+use super::encoding::{self, EncodingLimits};
+use super::intset::Intset;
+use super::listpack::{self, Listpack};
+use super::quicklist::QuickList;
+use super::scan_index::{scan_elements, scan_hash, ScanOrder};
+use super::skiplist::{self, SkipList};
 use bytes::Bytes;
-use std::collections::{BTreeMap, HashMap, HashSet, LinkedList};
+use ordered_float::OrderedFloat;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
 
 /// Sorted Set member with score
 #[derive(Debug, Clone, PartialEq)]
@@ -29,30 +37,726 @@ impl Ord for ZSetMember {
     }
 }
 
-/// Sorted Set implementation using BTreeMap for range queries
-/// and HashMap for O(1) member->score lookups
+/// Sorted set: (member, score) pairs ordered by score, then member.
+///
+/// Small sets are a listpack of alternating member and score entries kept
+/// in order. Past zset-max-listpack-entries/value they become a skiplist
+/// with spans for ordered and rank queries, plus a HashMap for O(1)
+/// member->score lookups and a scan order for ZSCAN.
 #[derive(Debug, Clone)]
 pub struct ZSet {
-    // BTreeMap: (score, member) -> () for ordered access
-    pub scores: BTreeMap<(ordered_float::OrderedFloat<f64>, Bytes), ()>,
-    // HashMap: member -> score for quick score lookups
-    pub members: HashMap<Bytes, f64>,
+    encoding: ZSetEncoding,
+    limits: Arc<EncodingLimits>,
+}
+
+#[derive(Debug, Clone)]
+enum ZSetEncoding {
+    Listpack(Listpack),
+    Skiplist {
+        // (score, member) in order; only changed through insert/remove
+        scores: SkipList,
+        // member -> score for quick score lookups
+        members: HashMap<Bytes, f64>,
+        // members by scan hash, for ZSCAN
+        order: ScanOrder,
+    },
+}
+
+fn decode_score(entry: &[u8]) -> f64 {
+    f64::from_le_bytes(entry.try_into().expect("listpack score entry"))
+}
+
+impl Default for ZSet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ZSet {
     pub fn new() -> Self {
+        Self::with_limits(encoding::defaults())
+    }
+
+    /// An empty sorted set that converts according to `limits`
+    pub fn with_limits(limits: Arc<EncodingLimits>) -> Self {
+        Self {
+            encoding: ZSetEncoding::Listpack(Listpack::new()),
+            limits,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.encoding {
+            ZSetEncoding::Listpack(lp) => lp.len() / 2,
+            ZSetEncoding::Skiplist { members, .. } => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// "listpack" or "skiplist", as OBJECT ENCODING reports it
+    pub fn encoding(&self) -> &'static str {
+        match &self.encoding {
+            ZSetEncoding::Listpack(_) => "listpack",
+            ZSetEncoding::Skiplist { .. } => "skiplist",
+        }
+    }
+
+    /// Rank and score of `member` in a listpack
+    fn listpack_find(lp: &Listpack, member: &[u8]) -> Option<(usize, f64)> {
+        let mut entries = lp.iter();
+        let mut rank = 0;
+        while let (Some(m), Some(s)) = (entries.next(), entries.next()) {
+            if m == member {
+                return Some((rank, decode_score(s)));
+            }
+            rank += 1;
+        }
+        None
+    }
+
+    /// Move a listpack into the skiplist encoding
+    fn convert(&mut self) {
+        if let ZSetEncoding::Listpack(lp) = &self.encoding {
+            let mut scores = SkipList::new();
+            let mut members = HashMap::with_capacity(lp.len() / 2);
+            let mut order = ScanOrder::new();
+            for (member, score) in self.iter() {
+                let member = Bytes::copy_from_slice(member);
+                scores.insert(score, member.clone());
+                order.insert(member.clone());
+                members.insert(member, score);
+            }
+            debug_assert_eq!(members.len(), lp.len() / 2);
+            self.encoding = ZSetEncoding::Skiplist { scores, members, order };
+        }
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        match &self.encoding {
+            ZSetEncoding::Listpack(lp) => Self::listpack_find(lp, member).map(|(_, score)| score),
+            ZSetEncoding::Skiplist { members, .. } => members.get(member).copied(),
+        }
+    }
+
+    /// Set the score of `member`, returning its previous score
+    pub fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        if let ZSetEncoding::Listpack(lp) = &self.encoding {
+            let grows = Self::listpack_find(lp, &member).is_none();
+            if (grows && lp.len() / 2 >= self.limits.zset_max_listpack_entries.get())
+                || member.len() > self.limits.zset_max_listpack_value.get()
+            {
+                self.convert();
+            }
+        }
+
+        match &mut self.encoding {
+            ZSetEncoding::Listpack(lp) => {
+                let old = Self::listpack_find(lp, &member);
+                if let Some((rank, old_score)) = old {
+                    if old_score == score {
+                        return Some(old_score);
+                    }
+                    lp.splice(rank * 2, 2, &[]);
+                }
+                let key = (OrderedFloat(score), member.as_ref());
+                let mut entries = lp.iter();
+                let mut rank = 0;
+                while let (Some(m), Some(s)) = (entries.next(), entries.next()) {
+                    if (OrderedFloat(decode_score(s)), m) > key {
+                        break;
+                    }
+                    rank += 1;
+                }
+                lp.splice(rank * 2, 0, &[&member, &score.to_le_bytes()]);
+                old.map(|(_, old_score)| old_score)
+            }
+            ZSetEncoding::Skiplist { scores, members, order } => {
+                let old = members.insert(member.clone(), score);
+                match old {
+                    Some(old) if old == score => return Some(old),
+                    Some(old) => {
+                        scores.remove(old, &member);
+                    }
+                    None => order.insert(member.clone()),
+                }
+                scores.insert(score, member);
+                old
+            }
+        }
+    }
+
+    /// Remove `member`, returning its score
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        match &mut self.encoding {
+            ZSetEncoding::Listpack(lp) => {
+                let (rank, score) = Self::listpack_find(lp, member)?;
+                lp.splice(rank * 2, 2, &[]);
+                Some(score)
+            }
+            ZSetEncoding::Skiplist { scores, members, order } => {
+                let score = members.remove(member)?;
+                scores.remove(score, member);
+                order.remove(member);
+                Some(score)
+            }
+        }
+    }
+
+    /// 0-based position of `member` in ascending score order
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        match &self.encoding {
+            ZSetEncoding::Listpack(lp) => Self::listpack_find(lp, member).map(|(rank, _)| rank),
+            ZSetEncoding::Skiplist { scores, members, .. } => scores.rank(*members.get(member)?, member),
+        }
+    }
+
+    /// Number of members scoring below `score` (at most `score` when
+    /// `inclusive`), i.e. the rank where such a score range ends
+    pub fn count_below(&self, score: f64, inclusive: bool) -> usize {
+        match &self.encoding {
+            ZSetEncoding::Listpack(_) => self
+                .iter()
+                .take_while(|(_, s)| *s < score || (inclusive && *s == score))
+                .count(),
+            ZSetEncoding::Skiplist { scores, .. } => scores.count_below(score, inclusive),
+        }
+    }
+
+    /// The member at 0-based `rank` and its score
+    pub fn get_by_rank(&self, rank: usize) -> Option<(&[u8], f64)> {
+        self.range_by_rank(rank, rank + 1).next()
+    }
+
+    /// Members and scores in ascending order
+    pub fn iter(&self) -> ZSetIter<'_> {
+        self.range_by_rank(0, self.len())
+    }
+
+    /// Members with ranks in `start..end`, in ascending order
+    pub fn range_by_rank(&self, start: usize, end: usize) -> ZSetIter<'_> {
+        match &self.encoding {
+            ZSetEncoding::Listpack(lp) => {
+                ZSetIter::Listpack(lp.range(start.saturating_mul(2), end.saturating_mul(2)))
+            }
+            ZSetEncoding::Skiplist { scores, .. } => ZSetIter::Skiplist(scores.range(start, end)),
+        }
+    }
+
+    /// One ZSCAN step: members and scores at or past `cursor` in scan
+    /// order, and the cursor to continue from
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<(&[u8], f64)>, u64) {
+        match &self.encoding {
+            ZSetEncoding::Listpack(_) => scan_elements(self.iter(), |(member, _)| scan_hash(member), cursor, count),
+            ZSetEncoding::Skiplist { members, order, .. } => {
+                let (step, next) = order.scan(cursor, count);
+                (step.into_iter().map(|member| (member.as_ref(), members[member])).collect(), next)
+            }
+        }
+    }
+
+    /// Remove and return the member with the lowest score
+    pub fn pop_first(&mut self) -> Option<(Bytes, f64)> {
+        let (member, score) = self.iter().next().map(|(m, s)| (Bytes::copy_from_slice(m), s))?;
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// Remove and return the member with the highest score
+    pub fn pop_last(&mut self) -> Option<(Bytes, f64)> {
+        let (member, score) = self.iter().next_back().map(|(m, s)| (Bytes::copy_from_slice(m), s))?;
+        self.remove(&member);
+        Some((member, score))
+    }
+}
+
+impl PartialEq for ZSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Extend<(Bytes, f64)> for ZSet {
+    fn extend<I: IntoIterator<Item = (Bytes, f64)>>(&mut self, iter: I) {
+        for (member, score) in iter {
+            self.insert(member, score);
+        }
+    }
+}
+
+impl FromIterator<(Bytes, f64)> for ZSet {
+    fn from_iter<I: IntoIterator<Item = (Bytes, f64)>>(iter: I) -> Self {
+        let mut zset = ZSet::new();
+        zset.extend(iter);
+        zset
+    }
+}
+
+/// Double-ended iterator over a run of consecutive sorted set members
+pub enum ZSetIter<'a> {
+    Listpack(listpack::Iter<'a>),
+    Skiplist(skiplist::Iter<'a>),
+}
+
+impl<'a> Iterator for ZSetIter<'a> {
+    type Item = (&'a [u8], f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ZSetIter::Listpack(entries) => {
+                let member = entries.next()?;
+                Some((member, decode_score(entries.next()?)))
+            }
+            ZSetIter::Skiplist(iter) => iter.next().map(|(m, s)| (m.as_ref(), s)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            ZSetIter::Listpack(entries) => entries.len() / 2,
+            ZSetIter::Skiplist(iter) => iter.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for ZSetIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            ZSetIter::Listpack(entries) => {
+                let score = decode_score(entries.next_back()?);
+                Some((entries.next_back()?, score))
+            }
+            ZSetIter::Skiplist(iter) => iter.next_back().map(|(m, s)| (m.as_ref(), s)),
+        }
+    }
+}
+
+impl ExactSizeIterator for ZSetIter<'_> {}
+
+/// A borrowed hash field and its value
+pub type HashEntry<'a> = (&'a [u8], &'a [u8]);
+
+/// Hash: field/value pairs in a listpack while small, a HashMap and its
+/// scan order once it outgrows hash-max-listpack-entries/value
+#[derive(Debug, Clone)]
+pub struct HashValue {
+    encoding: HashEncoding,
+    limits: Arc<EncodingLimits>,
+}
+
+#[derive(Debug, Clone)]
+enum HashEncoding {
+    /// Alternating field and value entries
+    Listpack(Listpack),
+    Table(HashMap<Bytes, Bytes>, ScanOrder),
+}
+
+impl Default for HashValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashValue {
+    pub fn new() -> Self {
+        Self::with_limits(encoding::defaults())
+    }
+
+    /// An empty hash that converts according to `limits`
+    pub fn with_limits(limits: Arc<EncodingLimits>) -> Self {
+        Self {
+            encoding: HashEncoding::Listpack(Listpack::new()),
+            limits,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.encoding {
+            HashEncoding::Listpack(lp) => lp.len() / 2,
+            HashEncoding::Table(map, _) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// "listpack" or "hashtable", as OBJECT ENCODING reports it
+    pub fn encoding(&self) -> &'static str {
+        match &self.encoding {
+            HashEncoding::Listpack(_) => "listpack",
+            HashEncoding::Table(..) => "hashtable",
+        }
+    }
+
+    /// Entry index of `field` in a listpack
+    fn listpack_find(lp: &Listpack, field: &[u8]) -> Option<usize> {
+        lp.iter().step_by(2).position(|f| f == field).map(|pair| pair * 2)
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&[u8]> {
+        match &self.encoding {
+            HashEncoding::Listpack(lp) => {
+                let mut entries = lp.iter();
+                while let (Some(f), Some(v)) = (entries.next(), entries.next()) {
+                    if f == field {
+                        return Some(v);
+                    }
+                }
+                None
+            }
+            HashEncoding::Table(map, _) => map.get(field).map(|v| v.as_ref()),
+        }
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        match &self.encoding {
+            HashEncoding::Listpack(lp) => Self::listpack_find(lp, field).is_some(),
+            HashEncoding::Table(map, _) => map.contains_key(field),
+        }
+    }
+
+    /// Set `field` to `value`, returning true if the field is new
+    pub fn insert(&mut self, field: Bytes, value: Bytes) -> bool {
+        if let HashEncoding::Listpack(lp) = &mut self.encoding {
+            let limit = self.limits.hash_max_listpack_value.get();
+            if field.len() <= limit && value.len() <= limit {
+                match Self::listpack_find(lp, &field) {
+                    Some(index) => {
+                        lp.replace(index + 1, &value);
+                        return false;
+                    }
+                    None if lp.len() / 2 < self.limits.hash_max_listpack_entries.get() => {
+                        lp.push(&field);
+                        lp.push(&value);
+                        return true;
+                    }
+                    None => {}
+                }
+            }
+            let map: HashMap<Bytes, Bytes> =
+                self.iter().map(|(f, v)| (Bytes::copy_from_slice(f), Bytes::copy_from_slice(v))).collect();
+            let mut order = ScanOrder::new();
+            for field in map.keys() {
+                order.insert(field.clone());
+            }
+            self.encoding = HashEncoding::Table(map, order);
+        }
+        match &mut self.encoding {
+            HashEncoding::Table(map, order) => {
+                let new = map.insert(field.clone(), value).is_none();
+                if new {
+                    order.insert(field);
+                }
+                new
+            }
+            HashEncoding::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+
+    /// Remove `field`, returning whether it was present
+    pub fn remove(&mut self, field: &[u8]) -> bool {
+        match &mut self.encoding {
+            HashEncoding::Listpack(lp) => match Self::listpack_find(lp, field) {
+                Some(index) => {
+                    lp.splice(index, 2, &[]);
+                    true
+                }
+                None => false,
+            },
+            HashEncoding::Table(map, order) => {
+                let removed = map.remove(field).is_some();
+                if removed {
+                    order.remove(field);
+                }
+                removed
+            }
+        }
+    }
+
+    /// Fields and values; listpacks keep insertion order
+    pub fn iter(&self) -> HashIter<'_> {
+        match &self.encoding {
+            HashEncoding::Listpack(lp) => HashIter::Listpack(lp.iter()),
+            HashEncoding::Table(map, _) => HashIter::Table(map.iter()),
+        }
+    }
+
+    /// One HSCAN step: fields and values at or past `cursor` in scan
+    /// order, and the cursor to continue from
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<HashEntry<'_>>, u64) {
+        match &self.encoding {
+            HashEncoding::Listpack(_) => scan_elements(self.iter(), |(field, _)| scan_hash(field), cursor, count),
+            HashEncoding::Table(map, order) => {
+                let (step, next) = order.scan(cursor, count);
+                (step.into_iter().map(|field| (field.as_ref(), map[field].as_ref())).collect(), next)
+            }
+        }
+    }
+}
+
+impl PartialEq for HashValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(f, v)| other.get(f) == Some(v))
+    }
+}
+
+impl Extend<(Bytes, Bytes)> for HashValue {
+    fn extend<I: IntoIterator<Item = (Bytes, Bytes)>>(&mut self, iter: I) {
+        for (field, value) in iter {
+            self.insert(field, value);
+        }
+    }
+}
+
+impl FromIterator<(Bytes, Bytes)> for HashValue {
+    fn from_iter<I: IntoIterator<Item = (Bytes, Bytes)>>(iter: I) -> Self {
+        let mut hash = HashValue::new();
+        hash.extend(iter);
+        hash
+    }
+}
+
+pub enum HashIter<'a> {
+    Listpack(listpack::Iter<'a>),
+    Table(std::collections::hash_map::Iter<'a, Bytes, Bytes>),
+}
+
+impl<'a> Iterator for HashIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            HashIter::Listpack(entries) => Some((entries.next()?, entries.next()?)),
+            HashIter::Table(iter) => iter.next().map(|(f, v)| (f.as_ref(), v.as_ref())),
+        }
+    }
+}
+
+/// Set: an intset while every member is an integer, a listpack while small,
+/// and a HashSet with its scan order past set-max-intset-entries /
+/// set-max-listpack-*
+#[derive(Debug, Clone)]
+pub struct SetValue {
+    encoding: SetEncoding,
+    limits: Arc<EncodingLimits>,
+}
+
+#[derive(Debug, Clone)]
+enum SetEncoding {
+    Intset(Intset),
+    Listpack(Listpack),
+    Table(HashSet<Bytes>, ScanOrder),
+}
+
+/// `member` as an integer, if it is one written canonically (no sign or
+/// leading zeros that would be lost by storing it as a number)
+fn canonical_int(member: &[u8]) -> Option<i64> {
+    let value: i64 = std::str::from_utf8(member).ok()?.parse().ok()?;
+    (value.to_string().as_bytes() == member).then_some(value)
+}
+
+impl Default for SetValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetValue {
+    pub fn new() -> Self {
+        Self::with_limits(encoding::defaults())
+    }
+
+    /// An empty set that converts according to `limits`
+    pub fn with_limits(limits: Arc<EncodingLimits>) -> Self {
         Self {
-            scores: BTreeMap::new(),
-            members: HashMap::new(),
+            encoding: SetEncoding::Intset(Intset::new()),
+            limits,
         }
     }
 
     pub fn len(&self) -> usize {
-        self.members.len()
+        match &self.encoding {
+            SetEncoding::Intset(ints) => ints.len(),
+            SetEncoding::Listpack(lp) => lp.len(),
+            SetEncoding::Table(set, _) => set.len(),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.members.is_empty()
+        self.len() == 0
+    }
+
+    /// "intset", "listpack" or "hashtable", as OBJECT ENCODING reports it
+    pub fn encoding(&self) -> &'static str {
+        match &self.encoding {
+            SetEncoding::Intset(_) => "intset",
+            SetEncoding::Listpack(_) => "listpack",
+            SetEncoding::Table(..) => "hashtable",
+        }
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match &self.encoding {
+            SetEncoding::Intset(ints) => canonical_int(member).is_some_and(|v| ints.contains(v)),
+            SetEncoding::Listpack(lp) => lp.iter().any(|m| m == member),
+            SetEncoding::Table(set, _) => set.contains(member),
+        }
+    }
+
+    /// Re-encode as a listpack if `extra` more members of up to
+    /// `longest` bytes would fit one, else as a hash table
+    fn convert(&mut self, extra: usize, longest: usize) {
+        let max_value = self.limits.set_max_listpack_value.get();
+        let fits = self.len() + extra <= self.limits.set_max_listpack_entries.get()
+            && longest <= max_value
+            && self.iter().all(|m| m.len() <= max_value);
+        self.encoding = if fits && !matches!(self.encoding, SetEncoding::Listpack(_)) {
+            let mut lp = Listpack::new();
+            for member in self.iter() {
+                lp.push(&member);
+            }
+            SetEncoding::Listpack(lp)
+        } else {
+            self.table()
+        };
+    }
+
+    /// The members re-encoded as a hash table
+    fn table(&self) -> SetEncoding {
+        let set: HashSet<Bytes> = self.iter().collect();
+        let mut order = ScanOrder::new();
+        for member in &set {
+            order.insert(member.clone());
+        }
+        SetEncoding::Table(set, order)
+    }
+
+    /// Add `member`, returning false if it was already present
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        if self.contains(&member) {
+            return false;
+        }
+        match &self.encoding {
+            SetEncoding::Intset(ints) => {
+                if canonical_int(&member).is_none() {
+                    self.convert(1, member.len());
+                } else if ints.len() >= self.limits.set_max_intset_entries.get() {
+                    self.encoding = self.table();
+                }
+            }
+            SetEncoding::Listpack(lp) => {
+                if lp.len() >= self.limits.set_max_listpack_entries.get()
+                    || member.len() > self.limits.set_max_listpack_value.get()
+                {
+                    self.encoding = self.table();
+                }
+            }
+            SetEncoding::Table(..) => {}
+        }
+        match &mut self.encoding {
+            SetEncoding::Intset(ints) => ints.insert(canonical_int(&member).unwrap()),
+            SetEncoding::Listpack(lp) => {
+                lp.push(&member);
+                true
+            }
+            SetEncoding::Table(set, order) => {
+                // Already known to be absent
+                order.insert(member.clone());
+                set.insert(member)
+            }
+        }
+    }
+
+    /// Remove `member`, returning whether it was present
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match &mut self.encoding {
+            SetEncoding::Intset(ints) => canonical_int(member).is_some_and(|v| ints.remove(v)),
+            SetEncoding::Listpack(lp) => match lp.iter().position(|m| m == member) {
+                Some(index) => {
+                    lp.remove(index);
+                    true
+                }
+                None => false,
+            },
+            SetEncoding::Table(set, order) => {
+                let removed = set.remove(member);
+                if removed {
+                    order.remove(member);
+                }
+                removed
+            }
+        }
+    }
+
+    /// Members; intsets yield them in ascending order
+    pub fn iter(&self) -> SetIter<'_> {
+        match &self.encoding {
+            SetEncoding::Intset(ints) => SetIter::Intset(ints, 0),
+            SetEncoding::Listpack(lp) => SetIter::Listpack(lp.iter()),
+            SetEncoding::Table(set, _) => SetIter::Table(set.iter()),
+        }
+    }
+
+    /// One SSCAN step: members at or past `cursor` in scan order, and the
+    /// cursor to continue from
+    pub fn scan(&self, cursor: u64, count: usize) -> (Vec<Bytes>, u64) {
+        match &self.encoding {
+            SetEncoding::Table(_, order) => {
+                let (step, next) = order.scan(cursor, count);
+                (step.into_iter().cloned().collect(), next)
+            }
+            _ => scan_elements(self.iter(), |member| scan_hash(member), cursor, count),
+        }
+    }
+}
+
+impl PartialEq for SetValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|m| other.contains(&m))
+    }
+}
+
+impl Extend<Bytes> for SetValue {
+    fn extend<I: IntoIterator<Item = Bytes>>(&mut self, iter: I) {
+        for member in iter {
+            self.insert(member);
+        }
+    }
+}
+
+impl FromIterator<Bytes> for SetValue {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut set = SetValue::new();
+        set.extend(iter);
+        set
+    }
+}
+
+/// Members of a set as owned bytes; cheap clones for hash tables, decoded
+/// for the compact encodings
+pub enum SetIter<'a> {
+    Intset(&'a Intset, usize),
+    Listpack(listpack::Iter<'a>),
+    Table(std::collections::hash_set::Iter<'a, Bytes>),
+}
+
+impl Iterator for SetIter<'_> {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SetIter::Intset(ints, index) => {
+                let value = ints.get(*index)?;
+                *index += 1;
+                Some(Bytes::from(value.to_string()))
+            }
+            SetIter::Listpack(entries) => entries.next().map(Bytes::copy_from_slice),
+            SetIter::Table(iter) => iter.next().cloned(),
+        }
     }
 }
 
@@ -87,7 +791,62 @@ impl StreamId {
 #[derive(Debug, Clone)]
 pub struct StreamEntry {
     pub id: StreamId,
-    pub fields: HashMap<Bytes, Bytes>,
+    /// Field-value pairs in the order they were added
+    pub fields: Vec<(Bytes, Bytes)>,
+}
+
+/// Entry of a consumer group's pending entries list (PEL): delivered to
+/// a consumer but not acknowledged yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// Last delivery time (milliseconds)
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+/// A consumer of a group
+#[derive(Debug, Clone, PartialEq)]
+pub struct Consumer {
+    /// Last time the consumer sent a command (milliseconds)
+    pub seen_time: u64,
+    /// Last time the consumer got entries, None if it never did
+    pub active_time: Option<u64>,
+    /// Entries pending for this consumer, also found in the group's PEL
+    pub pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    pub fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+/// Consumer group of a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerGroup {
+    /// Last entry delivered to any consumer of the group
+    pub last_delivered_id: StreamId,
+    /// Entries the group has read, None when it cannot be known (after
+    /// SETID to an arbitrary ID, or deletions in the range)
+    pub entries_read: Option<u64>,
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<String, Consumer>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_delivered_id: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_delivered_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
 }
 
 /// Stream data structure
@@ -95,6 +854,11 @@ pub struct StreamEntry {
 pub struct Stream {
     pub entries: BTreeMap<StreamId, StreamEntry>,
     pub last_id: StreamId,
+    /// Entries ever added, including deleted ones
+    pub entries_added: u64,
+    /// Greatest ID deleted by XDEL or XTRIM
+    pub max_deleted_id: StreamId,
+    pub groups: BTreeMap<String, ConsumerGroup>,
 }
 
 impl Stream {
@@ -102,6 +866,9 @@ impl Stream {
         Self {
             entries: BTreeMap::new(),
             last_id: StreamId::new(0, 0),
+            entries_added: 0,
+            max_deleted_id: StreamId::new(0, 0),
+            groups: BTreeMap::new(),
         }
     }
 
@@ -117,15 +884,15 @@ impl Stream {
 #[derive(Debug, Clone)]
 pub enum RedisValue {
     String(Bytes),
-    List(LinkedList<Bytes>),
-    Set(HashSet<Bytes>),
-    Hash(HashMap<Bytes, Bytes>),
+    List(QuickList),
+    Set(SetValue),
+    Hash(HashValue),
     ZSet(ZSet),
     Stream(Stream),
 }
 
 impl RedisValue {
-    pub fn type_name(&self) -> &str {
+    pub fn type_name(&self) -> &'static str {
         match self {
             RedisValue::String(_) => "string",
             RedisValue::List(_) => "list",
@@ -136,6 +903,18 @@ impl RedisValue {
         }
     }
 
+    /// Internal representation, as OBJECT ENCODING reports it
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            RedisValue::String(_) => "raw",
+            RedisValue::List(list) => list.encoding(),
+            RedisValue::Set(set) => set.encoding(),
+            RedisValue::Hash(hash) => hash.encoding(),
+            RedisValue::ZSet(zset) => zset.encoding(),
+            RedisValue::Stream(_) => "stream",
+        }
+    }
+
     pub fn as_string(&self) -> Option<&Bytes> {
         match self {
             RedisValue::String(s) => Some(s),
@@ -163,12 +942,10 @@ impl PartialEq for RedisValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (RedisValue::String(a), RedisValue::String(b)) => a == b,
-            (RedisValue::List(a), RedisValue::List(b)) => {
-                a.iter().eq(b.iter())
-            }
+            (RedisValue::List(a), RedisValue::List(b)) => a == b,
             (RedisValue::Set(a), RedisValue::Set(b)) => a == b,
             (RedisValue::Hash(a), RedisValue::Hash(b)) => a == b,
-            (RedisValue::ZSet(a), RedisValue::ZSet(b)) => a.members == b.members,
+            (RedisValue::ZSet(a), RedisValue::ZSet(b)) => a == b,
             _ => false,
         }
     }
@@ -15,6 +15,8 @@ pub struct Transaction {
     pub watched_keys: Vec<String>,
     /// Whether we're in MULTI mode
     pub in_multi: bool,
+    /// A command was rejected while queueing, so EXEC must abort
+    pub aborted: bool,
 }
 
 impl Transaction {
@@ -23,15 +25,24 @@ impl Transaction {
             commands: Vec::new(),
             watched_keys: Vec::new(),
             in_multi: false,
+            aborted: false,
         }
     }
 
     /// Start a transaction
     pub fn start_multi(&mut self) {
         self.in_multi = true;
+        self.aborted = false;
         self.commands.clear();
     }
 
+    /// Remember that a command failed to queue
+    pub fn flag_error(&mut self) {
+        if self.in_multi {
+            self.aborted = true;
+        }
+    }
+
     /// Queue a command for execution
     pub fn queue_command(&mut self, args: Vec<Vec<u8>>) {
         self.commands.push(args);
@@ -40,12 +51,14 @@ impl Transaction {
     /// Discard the transaction
     pub fn discard(&mut self) {
         self.in_multi = false;
+        self.aborted = false;
         self.commands.clear();
     }
 
     /// Execute all queued commands
     pub fn exec(&mut self) -> Vec<Vec<Vec<u8>>> {
         self.in_multi = false;
+        self.aborted = false;
         std::mem::take(&mut self.commands)
     }
 
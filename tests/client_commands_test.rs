@@ -0,0 +1,107 @@
+// CLIENT Command Integration Test
+//
+// Drives the CLIENT suite against a running server: KILL with filters
+// closes the matching connections, REPLY OFF/SKIP drops replies, PAUSE
+// WRITE holds writes but not reads, and INFO reports the per-connection
+// fields that CLIENT LIST prints.
+
+pub mod common;
+
+use common::{bulk, connect, ok, start_server};
+use redis_rust::protocol::{RespClient, RespValue};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+async fn client_id(client: &mut RespClient) -> String {
+    match client.command(&["CLIENT", "ID"]).await.unwrap() {
+        RespValue::Integer(id) => id.to_string(),
+        other => panic!("unexpected CLIENT ID reply {:?}", other),
+    }
+}
+
+/// The CLIENT INFO line of a connection, split into its fields
+async fn client_info(client: &mut RespClient) -> Vec<(String, String)> {
+    match client.command(&["CLIENT", "INFO"]).await.unwrap() {
+        RespValue::BulkString(Some(line)) => String::from_utf8_lossy(&line)
+            .trim_end()
+            .split(' ')
+            .filter_map(|field| field.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        other => panic!("unexpected CLIENT INFO reply {:?}", other),
+    }
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+    fields
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or_else(|| panic!("CLIENT INFO has no {} field", name))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_commands() {
+    let dir = TempDir::new().unwrap();
+    let port = start_server(&dir).await.port;
+    let mut admin = connect(port).await;
+
+    // INFO reports the local address, buffers and the last command
+    let mut client = connect(port).await;
+    assert_eq!(client.command(&["CLIENT", "SETINFO", "lib-name", "mylib"]).await.unwrap(), ok());
+    assert!(matches!(
+        client.command(&["CLIENT", "SETINFO", "lib-name", "has space"]).await.unwrap(),
+        RespValue::Error(_)
+    ));
+    let info = client_info(&mut client).await;
+    assert_eq!(field(&info, "laddr"), format!("127.0.0.1:{}", port));
+    assert_eq!(field(&info, "cmd"), "client|info");
+    assert_eq!(field(&info, "lib-name"), "mylib");
+    assert_eq!(field(&info, "multi"), "-1");
+    assert_eq!(field(&info, "resp"), "2");
+    assert!(field(&info, "tot-mem").parse::<u64>().is_ok());
+    assert!(field(&info, "qbuf").parse::<u64>().is_ok());
+
+    // REPLY OFF drops everything until ON, SKIP only the next reply
+    assert_eq!(client.command(&["SET", "k", "1"]).await.unwrap(), ok());
+    client.send(&["CLIENT", "REPLY", "OFF"]).await.unwrap();
+    client.send(&["INCR", "k"]).await.unwrap();
+    assert_eq!(client.command(&["CLIENT", "REPLY", "ON"]).await.unwrap(), ok());
+    client.send(&["CLIENT", "REPLY", "SKIP"]).await.unwrap();
+    client.send(&["INCR", "k"]).await.unwrap();
+    assert_eq!(client.command(&["GET", "k"]).await.unwrap(), bulk("3"));
+
+    // PAUSE WRITE holds SET until the pause ends but lets GET through
+    assert_eq!(admin.command(&["CLIENT", "PAUSE", "300", "WRITE"]).await.unwrap(), ok());
+    assert_eq!(client.command(&["GET", "k"]).await.unwrap(), bulk("3"));
+    let start = Instant::now();
+    client.send(&["SET", "k", "paused"]).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(100), client.read_frame()).await.is_err());
+    assert_eq!(admin.command(&["CLIENT", "UNPAUSE"]).await.unwrap(), ok());
+    assert_eq!(client.read_reply().await.unwrap(), ok());
+    assert!(start.elapsed() < Duration::from_millis(300));
+
+    // KILL with filters counts and closes the matching connections
+    let mut victim = connect(port).await;
+    let victim_id = client_id(&mut victim).await;
+    assert_eq!(
+        admin.command(&["CLIENT", "KILL", "ID", &victim_id, "SKIPME", "yes"]).await.unwrap(),
+        RespValue::Integer(1)
+    );
+    let closed = tokio::time::timeout(Duration::from_secs(2), victim.read_frame()).await.unwrap();
+    assert!(closed.is_err());
+    assert_eq!(
+        admin.command(&["CLIENT", "KILL", "ID", &victim_id]).await.unwrap(),
+        RespValue::Integer(0)
+    );
+    assert!(matches!(
+        admin.command(&["CLIENT", "KILL", "USER", "nobody"]).await.unwrap(),
+        RespValue::Error(_)
+    ));
+
+    // TYPE filters the listing
+    match admin.command(&["CLIENT", "LIST", "TYPE", "pubsub"]).await.unwrap() {
+        RespValue::BulkString(Some(list)) => assert!(list.is_empty()),
+        other => panic!("unexpected CLIENT LIST reply {:?}", other),
+    }
+}
@@ -0,0 +1,89 @@
+// Client-Side Caching Integration Test
+//
+// Exercises CLIENT TRACKING against a running server: a RESP3 client gets
+// invalidate pushes for keys it read, a RESP2 client receives them through
+// a redirect connection subscribed to __redis__:invalidate, and BCAST
+// clients hear about every key under their prefixes.
+
+pub mod common;
+
+use common::{bulk, connect, ok, start_server};
+use redis_rust::protocol::{RespClient, RespValue};
+use tempfile::TempDir;
+
+fn invalidate(keys: &[&str]) -> RespValue {
+    RespValue::Push(vec![bulk("invalidate"), RespValue::Array(Some(keys.iter().map(|k| bulk(k)).collect()))])
+}
+
+async fn client_id(client: &mut RespClient) -> String {
+    match client.command(&["CLIENT", "ID"]).await.unwrap() {
+        RespValue::Integer(id) => id.to_string(),
+        other => panic!("unexpected CLIENT ID reply {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_tracking_invalidations() {
+    let dir = TempDir::new().unwrap();
+    let port = start_server(&dir).await.port;
+    let mut writer = connect(port).await;
+
+    // RESP3: HELLO returns a map and invalidations arrive as pushes
+    let mut cached = connect(port).await;
+    match cached.command(&["HELLO", "3"]).await.unwrap() {
+        RespValue::Map(fields) => assert!(fields.contains(&(bulk("proto"), RespValue::Integer(3)))),
+        other => panic!("expected a map, got {:?}", other),
+    }
+    assert!(matches!(cached.command(&["HELLO", "4"]).await.unwrap(), RespValue::Error(e) if e.starts_with("NOPROTO")));
+    assert_eq!(cached.command(&["CLIENT", "TRACKING", "ON"]).await.unwrap(), ok());
+    assert_eq!(cached.command(&["CLIENT", "GETREDIR"]).await.unwrap(), RespValue::Integer(0));
+
+    writer.command(&["SET", "k", "v1"]).await.unwrap();
+    assert_eq!(cached.command(&["GET", "k"]).await.unwrap(), bulk("v1"));
+    writer.command(&["SET", "k", "v2"]).await.unwrap();
+    assert_eq!(cached.read_reply().await.unwrap(), invalidate(&["k"]));
+
+    // Own writes are reported too, before the reply
+    assert_eq!(cached.command(&["GET", "k"]).await.unwrap(), bulk("v2"));
+    cached.send(&["DEL", "k"]).await.unwrap();
+    assert_eq!(cached.read_reply().await.unwrap(), invalidate(&["k"]));
+    assert_eq!(cached.read_reply().await.unwrap(), RespValue::Integer(1));
+
+    // RESP2: redirect to a connection subscribed to __redis__:invalidate
+    let mut redirect = connect(port).await;
+    let redirect_id = client_id(&mut redirect).await;
+    redirect.command(&["SUBSCRIBE", "__redis__:invalidate"]).await.unwrap();
+    let mut resp2 = connect(port).await;
+    assert_eq!(
+        resp2.command(&["CLIENT", "TRACKING", "ON", "REDIRECT", &redirect_id, "BCAST", "PREFIX", "user:"]).await.unwrap(),
+        ok()
+    );
+    writer.command(&["SET", "other", "x"]).await.unwrap();
+    writer.command(&["HSET", "user:1", "name", "ann"]).await.unwrap();
+    assert_eq!(
+        redirect.read_reply().await.unwrap(),
+        RespValue::Array(Some(vec![
+            bulk("message"),
+            bulk("__redis__:invalidate"),
+            RespValue::Array(Some(vec![bulk("user:1")])),
+        ]))
+    );
+
+    // FLUSHALL invalidates everything at once
+    writer.command(&["FLUSHALL"]).await.unwrap();
+    assert_eq!(
+        redirect.read_reply().await.unwrap(),
+        RespValue::Array(Some(vec![bulk("message"), bulk("__redis__:invalidate"), RespValue::BulkString(None)]))
+    );
+    assert_eq!(cached.read_reply().await.unwrap(), RespValue::Push(vec![bulk("invalidate"), RespValue::Null]));
+
+    // OPTIN only tracks reads right after CLIENT CACHING yes
+    assert_eq!(cached.command(&["CLIENT", "TRACKING", "OFF"]).await.unwrap(), ok());
+    assert_eq!(cached.command(&["CLIENT", "TRACKING", "ON", "OPTIN", "NOLOOP"]).await.unwrap(), ok());
+    cached.command(&["GET", "a"]).await.unwrap();
+    assert_eq!(cached.command(&["CLIENT", "CACHING", "yes"]).await.unwrap(), ok());
+    cached.command(&["GET", "b"]).await.unwrap();
+    writer.command(&["MSET", "a", "1", "b", "2"]).await.unwrap();
+    assert_eq!(cached.read_reply().await.unwrap(), invalidate(&["b"]));
+    assert_eq!(cached.command(&["PING"]).await.unwrap(), RespValue::SimpleString("PONG".to_string()));
+}
@@ -0,0 +1,117 @@
+// Cluster Bus Integration Test
+//
+// Runs three cluster nodes in-process, each on its own runtime so that a
+// node can be killed by shutting its runtime down. Checks that nodes
+// discover each other through gossip, learn each other's slots, and agree
+// that a stopped master has failed.
+
+use redis_rust::cluster::{ClusterBus, ClusterState};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const PORTS: [u16; 3] = [17101, 17102, 17103];
+const SLOT_RANGES: [(u16, u16); 3] = [(0, 5460), (5461, 10922), (10923, 16383)];
+
+struct Node {
+    runtime: Runtime,
+    cluster: Arc<ClusterState>,
+    bus: Arc<ClusterBus>,
+}
+
+fn start_node(port: u16, dir: &TempDir) -> Node {
+    let runtime = Runtime::new().unwrap();
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+
+    let server = runtime.block_on(RedisServer::new(config)).unwrap();
+    let cluster = Arc::clone(server.cluster());
+    let bus = Arc::clone(server.cluster_bus());
+    runtime.spawn(async move {
+        let _ = server.run().await;
+    });
+
+    Node { runtime, cluster, bus }
+}
+
+fn wait_until(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+fn converged(nodes: &[Node]) -> bool {
+    nodes.iter().all(|node| {
+        let all = node.cluster.get_all_nodes();
+        all.len() == nodes.len()
+            && all.iter().all(|n| !n.in_handshake())
+            && node.cluster.slot_map.len() == 16384
+    })
+}
+
+#[test]
+fn test_gossip_convergence_and_failure_detection() {
+    let dir = TempDir::new().unwrap();
+    let nodes: Vec<Node> = PORTS.iter().map(|&port| start_node(port, &dir)).collect();
+    std::thread::sleep(Duration::from_millis(200));
+
+    for (node, (start, end)) in nodes.iter().zip(SLOT_RANGES) {
+        node.cluster.assign_slots_to_node(&node.cluster.my_id(), (start..=end).collect());
+    }
+
+    // Only the first node is introduced to the others; the second and
+    // third find each other through gossip
+    for &port in &PORTS[1..] {
+        assert!(nodes[0].bus.meet(format!("127.0.0.1:{}", port).parse().unwrap()));
+    }
+    assert!(wait_until(Duration::from_secs(10), || converged(&nodes)), "cluster did not converge");
+
+    for node in &nodes {
+        for (other, (start, end)) in nodes.iter().zip(SLOT_RANGES) {
+            assert_eq!(node.cluster.get_slot_node(start), Some(other.cluster.my_id()));
+            assert_eq!(node.cluster.get_slot_node(end), Some(other.cluster.my_id()));
+        }
+        assert!(node.cluster.state_ok());
+    }
+
+    // Every master ends up with a distinct config epoch
+    assert!(wait_until(Duration::from_secs(5), || {
+        let mut epochs: Vec<u64> = nodes.iter().map(|n| n.cluster.my_config_epoch()).collect();
+        epochs.sort_unstable();
+        epochs.dedup();
+        epochs.len() == nodes.len()
+    }));
+
+    // Stop the third master: the two remaining masters are a majority
+    let mut nodes = nodes;
+    let victim = nodes.pop().unwrap();
+    let victim_id = victim.cluster.my_id();
+    victim.runtime.shutdown_background();
+
+    let failed = wait_until(Duration::from_secs(10), || {
+        nodes
+            .iter()
+            .all(|n| n.cluster.get_node(&victim_id).is_some_and(|v| v.is_failed()))
+    });
+    assert!(failed, "stopped master was not flagged FAIL");
+    for node in &nodes {
+        assert!(!node.cluster.state_ok());
+        // The survivors still see each other
+        for other in &nodes {
+            let peer = node.cluster.get_node(&other.cluster.my_id()).unwrap();
+            assert!(!peer.is_failed() && !peer.is_pfail());
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// Cluster Commands Integration Test
+//
+// Runs two cluster nodes in-process and drives them through CLUSTER
+// MEET / ADDSLOTSRANGE / REPLICATE / FORGET / RESET over the wire, checking
+// that nodes.conf follows every change.
+
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const MASTER_PORT: u16 = 17111;
+const REPLICA_PORT: u16 = 17112;
+
+async fn start_node(port: u16, dir: &TempDir) -> PathBuf {
+    let nodes_conf = dir.path().join(format!("nodes-{}.conf", port));
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(nodes_conf.to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = RedisServer::new(config).await.unwrap();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    nodes_conf
+}
+
+async fn connect(port: u16) -> redis::aio::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    client.get_async_connection().await.unwrap()
+}
+
+async fn cluster<T: redis::FromRedisValue>(conn: &mut redis::aio::Connection, args: &[&str]) -> redis::RedisResult<T> {
+    redis::cmd("CLUSTER").arg(args).query_async(conn).await
+}
+
+async fn wait_until<F, Fut>(mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_cluster_membership_commands() {
+    let dir = TempDir::new().unwrap();
+    let master_conf = start_node(MASTER_PORT, &dir).await;
+    let replica_conf = start_node(REPLICA_PORT, &dir).await;
+
+    let mut master = connect(MASTER_PORT).await;
+    let mut replica = connect(REPLICA_PORT).await;
+    let master_id: String = cluster(&mut master, &["MYID"]).await.unwrap();
+    let replica_id: String = cluster(&mut replica, &["MYID"]).await.unwrap();
+
+    let _: () = cluster(&mut master, &["ADDSLOTSRANGE", "0", "8191", "8192", "16383"]).await.unwrap();
+    let err = cluster::<()>(&mut master, &["ADDSLOTSRANGE", "10", "5"]).await.unwrap_err();
+    assert!(err.to_string().contains("greater than end slot number"));
+    let err = cluster::<()>(&mut master, &["MEET", "not-an-ip", "1"]).await.unwrap_err();
+    assert!(err.to_string().contains("Invalid node address specified"));
+
+    // Slot changes reach nodes.conf before the reply
+    let conf = std::fs::read_to_string(&master_conf).unwrap();
+    let first = conf.lines().next().unwrap();
+    assert!(first.starts_with(&master_id));
+    assert!(first.contains("myself,master"));
+    assert!(first.ends_with("0-16383"));
+    assert!(conf.contains("vars currentEpoch"));
+
+    let _: () = cluster(&mut master, &["MEET", "127.0.0.1", &REPLICA_PORT.to_string()]).await.unwrap();
+    let met = wait_until(|| async {
+        let nodes: String = cluster(&mut connect(REPLICA_PORT).await, &["NODES"]).await.unwrap();
+        nodes.contains(&master_id) && !nodes.contains("handshake")
+    })
+    .await;
+    assert!(met, "nodes did not meet");
+
+    let _: () = cluster(&mut replica, &["REPLICATE", &master_id]).await.unwrap();
+    let nodes: String = cluster(&mut replica, &["NODES"]).await.unwrap();
+    let me = nodes.lines().find(|l| l.starts_with(&replica_id)).unwrap();
+    assert!(me.contains("myself,slave"));
+    assert!(me.contains(&master_id));
+    let conf = std::fs::read_to_string(&replica_conf).unwrap();
+    assert!(conf.lines().any(|l| l.starts_with(&replica_id) && l.contains("slave") && l.contains(&master_id)));
+
+    // The master learns about its new replica through gossip
+    let seen = wait_until(|| async {
+        let nodes: String = cluster(&mut connect(MASTER_PORT).await, &["NODES"]).await.unwrap();
+        nodes
+            .lines()
+            .any(|l| l.starts_with(&replica_id) && l.contains("slave") && l.contains(&master_id))
+    })
+    .await;
+    assert!(seen, "master did not learn about its replica");
+
+    let err = cluster::<()>(&mut replica, &["FORGET", &master_id]).await.unwrap_err();
+    assert!(err.to_string().contains("Can't forget my master"));
+    let links: Vec<redis::Value> = cluster(&mut master, &["LINKS"]).await.unwrap();
+    assert!(!links.is_empty());
+    let reports: i64 = cluster(&mut master, &["COUNT-FAILURE-REPORTS", &replica_id]).await.unwrap();
+    assert_eq!(reports, 0);
+
+    // Forgetting the replica removes it, and gossip does not bring it back
+    let _: () = cluster(&mut master, &["FORGET", &replica_id]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let nodes: String = cluster(&mut master, &["NODES"]).await.unwrap();
+    assert!(!nodes.contains(&replica_id));
+    assert!(!std::fs::read_to_string(&master_conf).unwrap().contains(&replica_id));
+
+    // A master with keys can't be reset
+    let _: () = redis::cmd("SET").arg("{a}key").arg("v").query_async(&mut master).await.unwrap();
+    let err = cluster::<()>(&mut master, &["RESET", "HARD"]).await.unwrap_err();
+    assert!(err.to_string().contains("containing keys"));
+
+    // A replica drops its data and takes a new identity on a hard reset
+    let _: () = cluster(&mut replica, &["RESET", "HARD"]).await.unwrap();
+    let new_id: String = cluster(&mut replica, &["MYID"]).await.unwrap();
+    assert_ne!(new_id, replica_id);
+    let info: String = cluster(&mut replica, &["INFO"]).await.unwrap();
+    assert!(info.contains("cluster_known_nodes:1"));
+    assert!(info.contains("cluster_current_epoch:0"));
+    let conf = std::fs::read_to_string(&replica_conf).unwrap();
+    assert!(conf.starts_with(&new_id));
+    assert!(!conf.contains(&master_id));
+
+    let _: () = cluster(&mut replica, &["SET-CONFIG-EPOCH", "7"]).await.unwrap();
+    let bumped: String = cluster(&mut replica, &["BUMPEPOCH"]).await.unwrap();
+    assert_eq!(bumped, "STILL 7");
+    let _: () = cluster(&mut replica, &["SAVECONFIG"]).await.unwrap();
+    assert!(std::fs::read_to_string(&replica_conf).unwrap().contains("vars currentEpoch 7"));
+}
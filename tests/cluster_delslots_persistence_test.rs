@@ -0,0 +1,71 @@
+// CLUSTER DELSLOTS Persistence Integration Test
+//
+// Drops slots from a cluster node with the AOF on, then replays its AOF in
+// a fresh server: the keys of the dropped slots must stay gone, so a restart
+// (or a replica) never brings back keys the node already let go of.
+
+pub mod common;
+
+use common::{bulk, command, ok, start_server_with};
+use redis_rust::persistence::aof::AofSyncPolicy;
+use redis_rust::protocol::RespValue;
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::time::Duration;
+use tempfile::TempDir;
+
+// The cluster bus listens on the client port + 10000, so the node needs a
+// port that leaves room for it
+const NODE_PORT: u16 = 17181;
+
+async fn start_node(dir: &TempDir, aof: &str) {
+    let mut config = ServerConfig::default()
+        .with_port(NODE_PORT)
+        .with_cluster_enabled(true)
+        .with_cluster_config_file(dir.path().join("nodes.conf").to_string_lossy().to_string());
+    config.aof_enabled = true;
+    config.aof_filename = aof.to_string();
+    config.aof_sync_policy = AofSyncPolicy::Always;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join("dump.rdb").to_string_lossy().to_string();
+
+    let server = RedisServer::new(config).await.unwrap();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+/// GET `key` on a server that loaded `aof` and nothing else
+async fn get_after_replay(dir: &TempDir, aof: &str, key: &str) -> RespValue {
+    let aof = aof.to_string();
+    let server = start_server_with(dir, |config| {
+        config.aof_enabled = true;
+        config.aof_filename = aof;
+    })
+    .await;
+    let reply = command(server.port, &["GET", key]).await;
+    server.stop();
+    reply
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dropped_slots_stay_dropped_after_replay() {
+    let dir = TempDir::new().unwrap();
+    let aof = dir.path().join("node.aof").to_string_lossy().to_string();
+    start_node(&dir, &aof).await;
+
+    let a = redis_rust::cluster::key_hash_slot(b"{a}").to_string();
+    let b = redis_rust::cluster::key_hash_slot(b"{b}").to_string();
+    assert_eq!(command(NODE_PORT, &["CLUSTER", "ADDSLOTS", &a, &b]).await, ok());
+    for key in ["{a}:1", "{a}:2", "{b}:1"] {
+        assert_eq!(command(NODE_PORT, &["SET", key, "v"]).await, ok());
+    }
+
+    assert_eq!(command(NODE_PORT, &["CLUSTER", "DELSLOTS", &a]).await, ok());
+    assert_eq!(get_after_replay(&dir, &aof, "{a}:1").await, RespValue::BulkString(None));
+    assert_eq!(get_after_replay(&dir, &aof, "{a}:2").await, RespValue::BulkString(None));
+    assert_eq!(get_after_replay(&dir, &aof, "{b}:1").await, bulk("v"));
+
+    assert_eq!(command(NODE_PORT, &["CLUSTER", "FLUSHSLOTS"]).await, ok());
+    assert_eq!(get_after_replay(&dir, &aof, "{b}:1").await, RespValue::BulkString(None));
+}
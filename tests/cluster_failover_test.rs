@@ -0,0 +1,189 @@
+// Cluster Failover Integration Test
+//
+// Runs cluster nodes in-process, each on its own runtime so that a master
+// can be killed by shutting its runtime down. Checks that a replica wins
+// the election when its master fails, and that CLUSTER FAILOVER swaps a
+// replica with its live master without losing writes.
+
+use redis_rust::cluster::{key_hash_slot, ClusterBus, ClusterState};
+use redis_rust::server::{RedisServer, ServerConfig};
+use redis_rust::storage::db::Database;
+use redis_rust::storage::types::RedisValue;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+struct Node {
+    port: u16,
+    runtime: Runtime,
+    cluster: Arc<ClusterState>,
+    bus: Arc<ClusterBus>,
+    db: Arc<Database>,
+}
+
+impl Node {
+    fn id(&self) -> String {
+        self.cluster.my_id()
+    }
+
+    fn connection(&self) -> redis::Connection {
+        let client = redis::Client::open(format!("redis://127.0.0.1:{}", self.port)).unwrap();
+        client.get_connection().unwrap()
+    }
+
+    fn has_key(&self, key: &str) -> bool {
+        self.db.get_db(0).unwrap().exists(key)
+    }
+}
+
+fn start_node(port: u16, dir: &TempDir) -> Node {
+    let runtime = Runtime::new().unwrap();
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = runtime.block_on(RedisServer::new(config)).unwrap();
+    let cluster = Arc::clone(server.cluster());
+    let bus = Arc::clone(server.cluster_bus());
+    let db = Arc::clone(server.db());
+    runtime.spawn(async move {
+        let _ = server.run().await;
+    });
+
+    Node { port, runtime, cluster, bus, db }
+}
+
+fn wait_until(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+/// Start the nodes, give the masters their slot ranges and make the last
+/// node a replica of the first master
+fn start_cluster(ports: &[u16], ranges: &[(u16, u16)], dir: &TempDir) -> Vec<Node> {
+    let nodes: Vec<Node> = ports.iter().map(|&port| start_node(port, dir)).collect();
+    std::thread::sleep(Duration::from_millis(200));
+
+    for (node, &(start, end)) in nodes.iter().zip(ranges) {
+        node.cluster.assign_slots_to_node(&node.id(), (start..=end).collect());
+    }
+    for node in &nodes[1..] {
+        assert!(nodes[0].bus.meet(format!("127.0.0.1:{}", node.port).parse().unwrap()));
+    }
+    let converged = wait_until(Duration::from_secs(10), || {
+        nodes.iter().all(|node| {
+            let all = node.cluster.get_all_nodes();
+            all.len() == nodes.len() && all.iter().all(|n| !n.in_handshake())
+        })
+    });
+    assert!(converged, "cluster did not converge");
+
+    let replica = nodes.last().unwrap();
+    let _: () = redis::cmd("CLUSTER")
+        .arg("REPLICATE")
+        .arg(nodes[0].id())
+        .query(&mut replica.connection())
+        .unwrap();
+    let known = wait_until(Duration::from_secs(10), || {
+        nodes.iter().all(|node| {
+            node.cluster
+                .get_node(&replica.id())
+                .is_some_and(|n| n.master_id == Some(nodes[0].id()))
+        })
+    });
+    assert!(known, "the replica was not announced");
+    nodes
+}
+
+/// A key served by the first master
+fn key_in(range: (u16, u16)) -> String {
+    (0..)
+        .map(|i| format!("key:{}", i))
+        .find(|key| (range.0..=range.1).contains(&key_hash_slot(key.as_bytes())))
+        .unwrap()
+}
+
+#[test]
+fn test_automatic_failover() {
+    let dir = TempDir::new().unwrap();
+    let ranges = [(0, 5460), (5461, 10922), (10923, 16383)];
+    let mut nodes = start_cluster(&[17121, 17122, 17123, 17124], &ranges, &dir);
+    let replica = nodes.pop().unwrap();
+
+    let key = key_in(ranges[0]);
+    let _: () = redis::cmd("SET").arg(&key).arg("before").query(&mut nodes[0].connection()).unwrap();
+    assert!(wait_until(Duration::from_secs(10), || replica.has_key(&key)), "replica did not sync");
+
+    // Kill the first master: its replica must take over its slots
+    let master = nodes.remove(0);
+    let master_id = master.id();
+    master.runtime.shutdown_background();
+
+    let promoted = wait_until(Duration::from_secs(20), || {
+        replica.cluster.is_myself_master()
+            && nodes.iter().all(|n| n.cluster.get_slot_node(0) == Some(replica.id()))
+    });
+    assert!(promoted, "replica was not promoted");
+    assert!(replica.cluster.myself().unwrap().config_epoch > 0);
+    assert!(replica.cluster.get_node(&master_id).unwrap().slots.is_empty());
+
+    // The new master serves the data it replicated
+    let value: String = redis::cmd("GET").arg(&key).query(&mut replica.connection()).unwrap();
+    assert_eq!(value, "before");
+    let _: () = redis::cmd("SET").arg(&key).arg("after").query(&mut replica.connection()).unwrap();
+}
+
+#[test]
+fn test_manual_failover() {
+    let dir = TempDir::new().unwrap();
+    let ranges = [(0, 8191), (8192, 16383)];
+    let nodes = start_cluster(&[17131, 17132, 17133], &ranges, &dir);
+    let (master, replica) = (&nodes[0], &nodes[2]);
+
+    let key = key_in(ranges[0]);
+    let _: () = redis::cmd("SET").arg(&key).arg("v1").query(&mut master.connection()).unwrap();
+    assert!(wait_until(Duration::from_secs(10), || replica.has_key(&key)), "replica did not sync");
+
+    let mut conn = master.connection();
+    let err = redis::cmd("CLUSTER").arg("FAILOVER").query::<()>(&mut conn).unwrap_err();
+    assert!(err.to_string().contains("send CLUSTER FAILOVER to a replica"));
+    let err = redis::cmd("CLUSTER").arg("FAILOVER").arg("SOON").query::<()>(&mut replica.connection()).unwrap_err();
+    assert!(err.to_string().contains("syntax error"));
+
+    let _: () = redis::cmd("CLUSTER").arg("FAILOVER").query(&mut replica.connection()).unwrap();
+
+    // The roles swap: the old master follows its former replica
+    let swapped = wait_until(Duration::from_secs(10), || {
+        replica.cluster.is_myself_master()
+            && master.cluster.myself().unwrap().master_id == Some(replica.id())
+            && nodes[1].cluster.get_slot_node(0) == Some(replica.id())
+    });
+    assert!(swapped, "manual failover did not complete");
+
+    let value: String = redis::cmd("GET").arg(&key).query(&mut replica.connection()).unwrap();
+    assert_eq!(value, "v1");
+
+    // Writes to the new master reach the old one
+    let _: () = redis::cmd("SET").arg(&key).arg("v2").query(&mut replica.connection()).unwrap();
+    let replicated = wait_until(Duration::from_secs(10), || {
+        master
+            .db
+            .get_db(0)
+            .unwrap()
+            .get(&key)
+            .is_some_and(|v| matches!(v, RedisValue::String(s) if s == "v2"))
+    });
+    assert!(replicated, "old master does not replicate from the new one");
+}
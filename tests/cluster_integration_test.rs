@@ -42,7 +42,7 @@ fn test_cluster_state_operations() {
     assert!(!cluster.owns_slot(101));
 
     // Test node ID generation
-    let node_id = cluster.my_id.clone();
+    let node_id = cluster.my_id();
     assert_eq!(node_id.len(), 40, "Node ID should be 40 characters");
 
     // Verify it's hexadecimal
@@ -0,0 +1,172 @@
+// Cluster Proxy Integration Test
+//
+// Puts the proxy in front of a three-node in-process cluster and talks to
+// it with a client that knows nothing about MOVED or ASK. Multi-key
+// commands spanning nodes must behave as on a single instance, and moving
+// a slot behind the proxy's back must go unnoticed by the client.
+
+use redis_rust::cluster::{key_hash_slot, ClusterBus, ClusterState};
+use redis_rust::proxy::{ProxyConfig, ProxyServer};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const NODE_PORTS: [u16; 3] = [17161, 17162, 17163];
+const PROXY_PORT: u16 = 17164;
+
+struct Node {
+    _runtime: Runtime,
+    cluster: Arc<ClusterState>,
+    bus: Arc<ClusterBus>,
+}
+
+fn start_node(port: u16, dir: &TempDir) -> Node {
+    let runtime = Runtime::new().unwrap();
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = runtime.block_on(RedisServer::new(config)).unwrap();
+    let cluster = Arc::clone(server.cluster());
+    let bus = Arc::clone(server.cluster_bus());
+    runtime.spawn(async move {
+        let _ = server.run().await;
+    });
+
+    Node { _runtime: runtime, cluster, bus }
+}
+
+fn connect(port: u16) -> redis::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    client.get_connection().unwrap()
+}
+
+fn wait_until(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+fn setslot(port: u16, slot: u16, action: &str, node_id: &str) {
+    let _: () = redis::cmd("CLUSTER")
+        .arg("SETSLOT")
+        .arg(slot)
+        .arg(action)
+        .arg(node_id)
+        .query(&mut connect(port))
+        .unwrap();
+}
+
+/// Index of the node serving `key`
+fn owner(nodes: &[Node], key: &str) -> usize {
+    let slot = key_hash_slot(key.as_bytes());
+    let id = nodes[0].cluster.get_slot_node(slot).unwrap();
+    nodes.iter().position(|n| n.cluster.my_id() == id).unwrap()
+}
+
+#[test]
+fn test_proxy_routes_for_plain_clients() {
+    let dir = TempDir::new().unwrap();
+    let nodes: Vec<Node> = NODE_PORTS.iter().map(|&port| start_node(port, &dir)).collect();
+    std::thread::sleep(Duration::from_millis(200));
+
+    let ranges = [(0, 5460), (5461, 10922), (10923, 16383)];
+    for (node, &(start, end)) in nodes.iter().zip(&ranges) {
+        node.cluster.assign_slots_to_node(&node.cluster.my_id(), (start..=end).collect());
+    }
+    for &port in &NODE_PORTS[1..] {
+        assert!(nodes[0].bus.meet(format!("127.0.0.1:{}", port).parse().unwrap()));
+    }
+    let converged = wait_until(Duration::from_secs(10), || {
+        nodes.iter().all(|node| {
+            node.cluster.slot_map.len() == 16384
+                && node.cluster.get_all_nodes().iter().all(|n| !n.in_handshake() && n.addr.is_some())
+        })
+    });
+    assert!(converged, "cluster did not converge");
+
+    let proxy_runtime = Runtime::new().unwrap();
+    let proxy = Arc::new(ProxyServer::new(
+        ProxyConfig::default()
+            .with_port(PROXY_PORT)
+            .with_seeds(vec![format!("127.0.0.1:{}", NODE_PORTS[0])]),
+    ));
+    let running = Arc::clone(&proxy);
+    proxy_runtime.spawn(async move {
+        let _ = running.run().await;
+    });
+    std::thread::sleep(Duration::from_millis(200));
+    let mut conn = connect(PROXY_PORT);
+
+    // Single-key commands land on their owner, wherever it is
+    let keys: Vec<String> = (0..30).map(|i| format!("key:{}", i)).collect();
+    for key in &keys {
+        let _: () = redis::cmd("SET").arg(key).arg(key).query(&mut conn).unwrap();
+    }
+    for node in 0..nodes.len() {
+        assert!(keys.iter().any(|key| owner(&nodes, key) == node), "keys do not span all nodes");
+    }
+    let _: () = redis::cmd("HSET").arg("hash").arg("f").arg("v").query(&mut conn).unwrap();
+    let hash: Vec<String> = redis::cmd("HGETALL").arg("hash").query(&mut conn).unwrap();
+    assert_eq!(hash, vec!["f", "v"]);
+
+    // Multi-key commands are split per slot and merged in order
+    let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).arg("missing").query(&mut conn).unwrap();
+    let mut expected: Vec<Option<String>> = keys.iter().cloned().map(Some).collect();
+    expected.push(None);
+    assert_eq!(values, expected);
+
+    let _: () = redis::cmd("MSET").arg("a").arg("1").arg("b").arg("2").arg("c").arg("3").query(&mut conn).unwrap();
+    let values: Vec<String> = redis::cmd("MGET").arg("c").arg("b").arg("a").query(&mut conn).unwrap();
+    assert_eq!(values, vec!["3", "2", "1"]);
+    let exists: i64 = redis::cmd("EXISTS").arg("a").arg("b").arg("missing").arg(&keys).query(&mut conn).unwrap();
+    assert_eq!(exists, 2 + keys.len() as i64);
+    let deleted: i64 = redis::cmd("DEL").arg("a").arg("b").arg("c").arg("missing").query(&mut conn).unwrap();
+    assert_eq!(deleted, 3);
+
+    let err = redis::cmd("MULTI").query::<()>(&mut conn).unwrap_err();
+    assert!(err.to_string().contains("not supported"));
+
+    // Move the slot of one key to another node behind the proxy's back
+    let key = &keys[0];
+    let slot = key_hash_slot(key.as_bytes());
+    let from = owner(&nodes, key);
+    let to = (from + 1) % nodes.len();
+    let (from_id, to_id) = (nodes[from].cluster.my_id(), nodes[to].cluster.my_id());
+    setslot(NODE_PORTS[to], slot, "IMPORTING", &from_id);
+    setslot(NODE_PORTS[from], slot, "MIGRATING", &to_id);
+    let reply: String = redis::cmd("MIGRATE")
+        .arg("127.0.0.1")
+        .arg(NODE_PORTS[to])
+        .arg(key)
+        .arg(0)
+        .arg(5000)
+        .query(&mut connect(NODE_PORTS[from]))
+        .unwrap();
+    assert_eq!(reply, "OK");
+
+    // Half-way through the source answers ASK, which the proxy follows
+    let value: String = redis::cmd("GET").arg(key).query(&mut conn).unwrap();
+    assert_eq!(&value, key);
+
+    setslot(NODE_PORTS[to], slot, "NODE", &to_id);
+    setslot(NODE_PORTS[from], slot, "NODE", &to_id);
+
+    // Afterwards MOVED makes the proxy reload its map
+    let value: String = redis::cmd("GET").arg(key).query(&mut conn).unwrap();
+    assert_eq!(&value, key);
+    let to_addr = format!("127.0.0.1:{}", NODE_PORTS[to]);
+    assert_eq!(proxy.topology().node_for_slot(slot), Some(to_addr));
+}
@@ -0,0 +1,205 @@
+// Cluster Resharding Integration Test
+//
+// Moves a hash slot between two in-process nodes with CLUSTER SETSLOT,
+// GETKEYSINSLOT and MIGRATE while a client keeps writing to it, following
+// MOVED and ASK redirections like a cluster client would. Every write must
+// be readable from the new owner afterwards.
+
+use redis::{ErrorKind, FromRedisValue};
+use redis_rust::cluster::{key_hash_slot, ClusterBus, ClusterState};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const SOURCE_PORT: u16 = 17141;
+const TARGET_PORT: u16 = 17142;
+
+struct Node {
+    _runtime: Runtime,
+    cluster: Arc<ClusterState>,
+    bus: Arc<ClusterBus>,
+}
+
+fn start_node(port: u16, dir: &TempDir) -> Node {
+    let runtime = Runtime::new().unwrap();
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = runtime.block_on(RedisServer::new(config)).unwrap();
+    let cluster = Arc::clone(server.cluster());
+    let bus = Arc::clone(server.cluster_bus());
+    runtime.spawn(async move {
+        let _ = server.run().await;
+    });
+
+    Node { _runtime: runtime, cluster, bus }
+}
+
+fn connect(port: u16) -> redis::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    client.get_connection().unwrap()
+}
+
+fn wait_until(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+/// Port of the node a MOVED / ASK error points to
+fn redirect_port(err: &redis::RedisError) -> u16 {
+    let (addr, _slot) = err.redirect_node().unwrap();
+    addr.rsplit(':').next().unwrap().parse().unwrap()
+}
+
+/// Minimal cluster client: one connection per node, follows MOVED and
+/// ASK, retries on TRYAGAIN
+#[derive(Default)]
+struct ClusterClient {
+    connections: HashMap<u16, redis::Connection>,
+}
+
+impl ClusterClient {
+    fn query<T: FromRedisValue>(&mut self, mut port: u16, cmd: &redis::Cmd) -> T {
+        let mut asking = false;
+        for _ in 0..100 {
+            let conn = self.connections.entry(port).or_insert_with(|| connect(port));
+            let result = if asking {
+                redis::pipe()
+                    .cmd("ASKING")
+                    .ignore()
+                    .add_command(cmd.clone())
+                    .query::<(T,)>(conn)
+                    .map(|(value,)| value)
+            } else {
+                cmd.query(conn)
+            };
+            match result {
+                Ok(value) => return value,
+                Err(e) => match e.kind() {
+                    ErrorKind::Moved | ErrorKind::Ask => {
+                        asking = e.kind() == ErrorKind::Ask;
+                        port = redirect_port(&e);
+                    }
+                    ErrorKind::TryAgain => std::thread::sleep(Duration::from_millis(10)),
+                    _ => panic!("command failed: {}", e),
+                },
+            }
+        }
+        panic!("too many redirections");
+    }
+}
+
+fn setslot(port: u16, slot: u16, action: &str, node_id: &str) {
+    let _: () = redis::cmd("CLUSTER")
+        .arg("SETSLOT")
+        .arg(slot)
+        .arg(action)
+        .arg(node_id)
+        .query(&mut connect(port))
+        .unwrap();
+}
+
+#[test]
+fn test_reshard_slot_under_writes() {
+    let dir = TempDir::new().unwrap();
+    let source = start_node(SOURCE_PORT, &dir);
+    let target = start_node(TARGET_PORT, &dir);
+    std::thread::sleep(Duration::from_millis(200));
+
+    source.cluster.assign_slots_to_node(&source.cluster.my_id(), (0..16384).collect());
+    assert!(source.bus.meet(format!("127.0.0.1:{}", TARGET_PORT).parse().unwrap()));
+    let converged = wait_until(Duration::from_secs(10), || {
+        target.cluster.slot_map.len() == 16384 && target.cluster.get_all_nodes().iter().all(|n| !n.in_handshake())
+    });
+    assert!(converged, "nodes did not meet");
+    let (source_id, target_id) = (source.cluster.my_id(), target.cluster.my_id());
+
+    // Keep writing new keys of one slot
+    let slot = key_hash_slot(b"{user1}");
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            let mut client = ClusterClient::default();
+            let mut written = 0;
+            while !stop.load(Ordering::SeqCst) {
+                let key = format!("{{user1}}:{}", written);
+                let _: () = client.query(SOURCE_PORT, redis::cmd("SET").arg(&key).arg(written));
+                written += 1;
+            }
+            written
+        })
+    };
+    std::thread::sleep(Duration::from_millis(200));
+
+    setslot(TARGET_PORT, slot, "IMPORTING", &source_id);
+    setslot(SOURCE_PORT, slot, "MIGRATING", &target_id);
+
+    let mut source_conn = connect(SOURCE_PORT);
+    let mut batches = 0;
+    loop {
+        let keys: Vec<String> = redis::cmd("CLUSTER")
+            .arg("GETKEYSINSLOT")
+            .arg(slot)
+            .arg(1000)
+            .query(&mut source_conn)
+            .unwrap();
+        if keys.is_empty() {
+            break;
+        }
+        let reply: String = redis::cmd("MIGRATE")
+            .arg("127.0.0.1")
+            .arg(TARGET_PORT)
+            .arg("")
+            .arg(0)
+            .arg(5000)
+            .arg("REPLACE")
+            .arg("KEYS")
+            .arg(&keys)
+            .query(&mut source_conn)
+            .unwrap();
+        assert!(reply == "OK" || reply == "NOKEY");
+        batches += 1;
+        assert!(batches < 100, "source keeps receiving keys of a migrating slot");
+    }
+
+    let left: i64 = redis::cmd("CLUSTER").arg("COUNTKEYSINSLOT").arg(slot).query(&mut source_conn).unwrap();
+    assert_eq!(left, 0);
+    setslot(TARGET_PORT, slot, "NODE", &target_id);
+    setslot(SOURCE_PORT, slot, "NODE", &target_id);
+    std::thread::sleep(Duration::from_millis(200));
+    stop.store(true, Ordering::SeqCst);
+    let written = writer.join().unwrap();
+    assert!(written > 0);
+    let moved: i64 = redis::cmd("CLUSTER").arg("COUNTKEYSINSLOT").arg(slot).query(&mut connect(TARGET_PORT)).unwrap();
+    assert_eq!(moved, written);
+
+    // The source redirects for good, and nothing was lost on the way
+    let err = redis::cmd("GET").arg("{user1}:0").query::<String>(&mut source_conn).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Moved);
+    assert_eq!(redirect_port(&err), TARGET_PORT);
+    let mut client = ClusterClient::default();
+    for i in 0..written {
+        let value: i64 = client.query(SOURCE_PORT, redis::cmd("GET").arg(format!("{{user1}}:{}", i)));
+        assert_eq!(value, i);
+    }
+    assert!(target.cluster.my_config_epoch() > source.cluster.my_config_epoch());
+    let agreed = wait_until(Duration::from_secs(5), || source.cluster.get_slot_node(slot) == Some(target_id.clone()));
+    assert!(agreed);
+}
@@ -0,0 +1,136 @@
+// Cluster Routing Integration Test
+//
+// Runs a master and its replica in-process and checks how commands are
+// routed by their keys: MOVED from the replica, CROSSSLOT for keys of
+// different slots (including movable keys and whole transactions), and
+// replica reads after READONLY.
+
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const MASTER_PORT: u16 = 17151;
+const REPLICA_PORT: u16 = 17152;
+
+async fn start_node(port: u16, dir: &TempDir) {
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = RedisServer::new(config).await.unwrap();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+async fn connect(port: u16) -> redis::aio::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    client.get_async_connection().await.unwrap()
+}
+
+async fn query<T: redis::FromRedisValue>(conn: &mut redis::aio::Connection, args: &[&str]) -> redis::RedisResult<T> {
+    redis::cmd(args[0]).arg(&args[1..]).query_async(conn).await
+}
+
+/// The error code of a failed command, e.g. "MOVED" or "CROSSSLOT"
+async fn error_code(conn: &mut redis::aio::Connection, args: &[&str]) -> String {
+    let err = query::<redis::Value>(conn, args).await.unwrap_err();
+    err.code().unwrap_or_default().to_string()
+}
+
+async fn wait_until<F, Fut>(mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cluster_key_routing() {
+    let dir = TempDir::new().unwrap();
+    start_node(MASTER_PORT, &dir).await;
+    start_node(REPLICA_PORT, &dir).await;
+
+    let mut master = connect(MASTER_PORT).await;
+    let mut replica = connect(REPLICA_PORT).await;
+    let _: () = query(&mut master, &["CLUSTER", "ADDSLOTSRANGE", "0", "16383"]).await.unwrap();
+    let _: () = query(&mut replica, &["CLUSTER", "MEET", "127.0.0.1", &MASTER_PORT.to_string()])
+        .await
+        .unwrap();
+    let master_id: String = query(&mut master, &["CLUSTER", "MYID"]).await.unwrap();
+    let known = wait_until(|| async {
+        let nodes: String = query(&mut connect(REPLICA_PORT).await, &["CLUSTER", "NODES"]).await.unwrap();
+        nodes.lines().any(|line| line.starts_with(&master_id) && line.contains("0-16383"))
+    })
+    .await;
+    assert!(known, "replica did not learn the master's slots");
+    let _: () = query(&mut replica, &["CLUSTER", "REPLICATE", &master_id]).await.unwrap();
+
+    // Commands beyond plain GET/SET are routed by their keys
+    let _: () = query(&mut master, &["HSET", "h", "f", "v"]).await.unwrap();
+    assert_eq!(error_code(&mut replica, &["HGETALL", "h"]).await, "MOVED");
+    assert_eq!(error_code(&mut replica, &["ZRANGE", "z", "0", "-1"]).await, "MOVED");
+    assert_eq!(error_code(&mut replica, &["EVAL", "return 1", "1", "k"]).await, "MOVED");
+
+    // Keys of one command, or of one transaction, must share a slot
+    for args in [
+        &["SUNIONSTORE", "{a}d", "{a}s", "{b}s"][..],
+        &["RENAME", "{a}x", "{b}x"],
+        &["EVAL", "return 1", "2", "{a}k", "{b}k"],
+        &["ZUNIONSTORE", "{a}d", "2", "{a}z", "{b}z"],
+        &["XREAD", "STREAMS", "{a}s", "{b}s", "0", "0"],
+    ] {
+        assert_eq!(error_code(&mut master, args).await, "CROSSSLOT", "{:?}", args);
+    }
+    let _: i64 = query(&mut master, &["SUNIONSTORE", "{a}d", "{a}s", "{a}t"]).await.unwrap();
+
+    let (n,): (i64,) = redis::pipe()
+        .atomic()
+        .set("{t}a", 1)
+        .ignore()
+        .incr("{t}a", 1)
+        .query_async(&mut master)
+        .await
+        .unwrap();
+    assert_eq!(n, 2);
+
+    let _: () = query(&mut master, &["MULTI"]).await.unwrap();
+    let _: String = query(&mut master, &["SET", "{a}k", "1"]).await.unwrap();
+    let _: String = query(&mut master, &["SET", "{b}k", "1"]).await.unwrap();
+    assert_eq!(error_code(&mut master, &["EXEC"]).await, "CROSSSLOT");
+    let exists: i64 = query(&mut master, &["EXISTS", "{a}k"]).await.unwrap();
+    assert_eq!(exists, 0);
+
+    let _: () = query(&mut replica, &["MULTI"]).await.unwrap();
+    assert_eq!(error_code(&mut replica, &["SET", "k", "1"]).await, "MOVED");
+    assert_eq!(error_code(&mut replica, &["EXEC"]).await, "EXECABORT");
+
+    // READONLY lets the replica answer reads, never writes
+    let _: () = query(&mut replica, &["READONLY"]).await.unwrap();
+    let synced = wait_until(|| async {
+        let mut conn = connect(REPLICA_PORT).await;
+        let _: () = query(&mut conn, &["READONLY"]).await.unwrap();
+        query::<Option<String>>(&mut conn, &["HGET", "h", "f"]).await == Ok(Some("v".to_string()))
+    })
+    .await;
+    assert!(synced, "replica did not serve the read");
+    let value: String = query(&mut replica, &["HGET", "h", "f"]).await.unwrap();
+    assert_eq!(value, "v");
+    assert_eq!(error_code(&mut replica, &["HSET", "h", "f", "w"]).await, "MOVED");
+
+    let _: () = query(&mut replica, &["READWRITE"]).await.unwrap();
+    assert_eq!(error_code(&mut replica, &["HGET", "h", "f"]).await, "MOVED");
+}
@@ -0,0 +1,148 @@
+// Cluster Sharded Pub/Sub Integration Test
+//
+// Runs two masters and a replica of the first in-process. Shard channels
+// hash to slots like keys: subscribing or publishing on the wrong shard
+// gets MOVED, a message published on the master reaches subscribers on
+// its replica too, and moving the channel's slot away unsubscribes its
+// subscribers.
+
+use redis_rust::cluster::key_hash_slot;
+use redis_rust::protocol::{RespClient, RespValue};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const MASTER_PORT: u16 = 17171;
+const OTHER_PORT: u16 = 17172;
+const REPLICA_PORT: u16 = 17173;
+
+async fn start_node(port: u16, dir: &TempDir) {
+    let mut config = ServerConfig::default()
+        .with_port(port)
+        .with_cluster_enabled(true)
+        .with_cluster_node_timeout(1000)
+        .with_cluster_config_file(dir.path().join(format!("nodes-{}.conf", port)).to_string_lossy().to_string());
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+
+    let server = RedisServer::new(config).await.unwrap();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+async fn connect(port: u16) -> RespClient {
+    RespClient::connect(&format!("127.0.0.1:{}", port), Duration::from_secs(5)).await.unwrap()
+}
+
+async fn command(port: u16, args: &[&str]) -> RespValue {
+    connect(port).await.command(args).await.unwrap()
+}
+
+async fn text(port: u16, args: &[&str]) -> String {
+    match command(port, args).await {
+        RespValue::BulkString(Some(data)) => String::from_utf8(data).unwrap(),
+        RespValue::SimpleString(s) => s,
+        other => panic!("unexpected reply to {:?}: {:?}", args, other),
+    }
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.as_bytes().to_vec()))
+}
+
+/// The error code of a reply, e.g. "MOVED" or "CROSSSLOT"
+fn error_code(reply: &RespValue) -> &str {
+    match reply {
+        RespValue::Error(msg) => msg.split_whitespace().next().unwrap_or(""),
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+async fn wait_until<F, Fut>(mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sharded_pubsub() {
+    let dir = TempDir::new().unwrap();
+    for port in [MASTER_PORT, OTHER_PORT, REPLICA_PORT] {
+        start_node(port, &dir).await;
+    }
+
+    text(MASTER_PORT, &["CLUSTER", "ADDSLOTSRANGE", "0", "8191"]).await;
+    text(OTHER_PORT, &["CLUSTER", "ADDSLOTSRANGE", "8192", "16383"]).await;
+    for port in [OTHER_PORT, REPLICA_PORT] {
+        text(port, &["CLUSTER", "MEET", "127.0.0.1", &MASTER_PORT.to_string()]).await;
+    }
+    let master_id = text(MASTER_PORT, &["CLUSTER", "MYID"]).await;
+    let other_id = text(OTHER_PORT, &["CLUSTER", "MYID"]).await;
+    let converged = wait_until(|| async {
+        let mut ok = true;
+        for port in [MASTER_PORT, OTHER_PORT, REPLICA_PORT] {
+            ok &= text(port, &["CLUSTER", "INFO"]).await.contains("cluster_slots_assigned:16384");
+        }
+        ok
+    })
+    .await;
+    assert!(converged, "cluster did not converge");
+    text(REPLICA_PORT, &["CLUSTER", "REPLICATE", &master_id]).await;
+    let known = wait_until(|| async {
+        let nodes = text(MASTER_PORT, &["CLUSTER", "NODES"]).await;
+        nodes.lines().any(|line| line.contains("slave") && line.contains(&master_id))
+    })
+    .await;
+    assert!(known, "master did not learn about its replica");
+
+    // A channel served by the first shard
+    let channel = (0..)
+        .map(|i| format!("orders:{}", i))
+        .find(|c| key_hash_slot(c.as_bytes()) < 8192)
+        .unwrap();
+    let slot = key_hash_slot(channel.as_bytes());
+
+    // MOVED applies to both sides, and channels must share a slot
+    assert_eq!(error_code(&command(OTHER_PORT, &["SSUBSCRIBE", &channel]).await), "MOVED");
+    assert_eq!(error_code(&command(OTHER_PORT, &["SPUBLISH", &channel, "x"]).await), "MOVED");
+    assert_eq!(error_code(&command(MASTER_PORT, &["SSUBSCRIBE", "{a}x", "{b}x"]).await), "CROSSSLOT");
+
+    let mut on_master = connect(MASTER_PORT).await;
+    let mut on_replica = connect(REPLICA_PORT).await;
+    for sub in [&mut on_master, &mut on_replica] {
+        let reply = sub.command(&["SSUBSCRIBE", &channel]).await.unwrap();
+        assert_eq!(reply, RespValue::Array(Some(vec![bulk("ssubscribe"), bulk(&channel), RespValue::Integer(1)])));
+    }
+    assert_eq!(
+        command(MASTER_PORT, &["PUBSUB", "SHARDNUMSUB", &channel]).await,
+        RespValue::Array(Some(vec![bulk(&channel), RespValue::Integer(1)]))
+    );
+
+    // Plain PUBLISH does not reach shard channels; SPUBLISH reaches the
+    // whole shard
+    assert_eq!(command(MASTER_PORT, &["PUBLISH", &channel, "plain"]).await, RespValue::Integer(0));
+    assert_eq!(command(MASTER_PORT, &["SPUBLISH", &channel, "hello"]).await, RespValue::Integer(1));
+    let message = RespValue::Array(Some(vec![bulk("smessage"), bulk(&channel), bulk("hello")]));
+    assert_eq!(on_master.read_reply().await.unwrap(), message);
+    assert_eq!(on_replica.read_reply().await.unwrap(), message);
+
+    // Once the slot belongs to the other shard, subscribers are dropped
+    text(OTHER_PORT, &["CLUSTER", "SETSLOT", &slot.to_string(), "NODE", &other_id]).await;
+    text(MASTER_PORT, &["CLUSTER", "SETSLOT", &slot.to_string(), "NODE", &other_id]).await;
+    let unsubscribed = RespValue::Array(Some(vec![bulk("sunsubscribe"), bulk(&channel), RespValue::Integer(0)]));
+    assert_eq!(on_master.read_reply().await.unwrap(), unsubscribed);
+    assert_eq!(on_master.command(&["PING"]).await.unwrap(), RespValue::SimpleString("PONG".to_string()));
+    assert_eq!(on_replica.read_reply().await.unwrap(), unsubscribed);
+    assert_eq!(error_code(&command(MASTER_PORT, &["SSUBSCRIBE", &channel]).await), "MOVED");
+}
@@ -0,0 +1,70 @@
+// Shared integration test harness
+//
+// Servers run in-process on a port the OS picks: the listener is bound to
+// port 0 before the server is built, so suites running side by side never
+// fight over a port.
+
+use redis_rust::protocol::{RespClient, RespValue};
+use redis_rust::server::{RedisServer, ServerConfig};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// A server running in this process
+pub struct TestServer {
+    pub port: u16,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Stop accepting clients; connections already open stay up until
+    /// their clients close them
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+/// Start a server with persistence off, keeping its files in `dir`
+pub async fn start_server(dir: &TempDir) -> TestServer {
+    start_server_with(dir, |_| {}).await
+}
+
+/// Start a server whose config `configure` adjusts; the port is already
+/// set, and AOF and RDB loading are off unless it turns them on
+pub async fn start_server_with(dir: &TempDir, configure: impl FnOnce(&mut ServerConfig)) -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut config = ServerConfig::default().with_port(port);
+    config.aof_enabled = false;
+    config.rdb_enabled = false;
+    config.rdb_filename = dir.path().join(format!("dump-{}.rdb", port)).to_string_lossy().to_string();
+    config.aof_filename = dir.path().join(format!("appendonly-{}.aof", port)).to_string_lossy().to_string();
+    configure(&mut config);
+
+    let server = RedisServer::new(config).await.unwrap();
+    let task = tokio::spawn(async move {
+        let _ = server.serve(listener).await;
+    });
+    TestServer { port, task }
+}
+
+pub async fn connect(port: u16) -> RespClient {
+    RespClient::connect(&format!("127.0.0.1:{}", port), Duration::from_secs(5))
+        .await
+        .unwrap()
+}
+
+/// Run one command on a fresh connection
+pub async fn command(port: u16, args: &[&str]) -> RespValue {
+    connect(port).await.command(args).await.unwrap()
+}
+
+pub fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.as_bytes().to_vec()))
+}
+
+pub fn ok() -> RespValue {
+    RespValue::SimpleString("OK".to_string())
+}
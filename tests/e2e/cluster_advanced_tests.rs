@@ -31,7 +31,7 @@ mod redirection_tests {
         let actual_slot = key_hash_slot(key);
 
         if actual_slot == 100 {
-            let result = check_slot_ownership(&cluster, key, false);
+            let result = check_slot_ownership(&cluster, &MigrationManager::new(), key, false);
 
             // Should return MOVED error since we don't own slot 100
             assert!(result.is_some(), "Should return MOVED error");
@@ -139,7 +139,7 @@ mod migration_tests {
         assert!(migration.is_migrating(500), "Slot should be migrating");
 
         // Step 2: Mark slot as IMPORTING on target
-        migration.set_importing(500, cluster.my_id.clone());
+        migration.set_importing(500, cluster.my_id());
 
         // Step 3: Complete migration - mark as STABLE
         migration.set_stable(500);
@@ -511,7 +511,7 @@ mod stress_tests {
         let migration = Arc::new(MigrationManager::new());
 
         // Setup 2 nodes
-        cluster.assign_slots_to_node(&cluster.my_id, (0..16384).collect());
+        cluster.assign_slots_to_node(&cluster.my_id(), (0..16384).collect());
 
         let target = ClusterNode::new_master(
             "target".to_string(),
@@ -0,0 +1,65 @@
+// Hash Field Expiration Integration Test
+//
+// Runs a master and a replica in-process. Field TTLs set before the replica
+// attaches arrive with the snapshot; later ones are propagated as absolute
+// times, and the master removes expired fields without them being read.
+
+pub mod common;
+
+use common::{command, start_server};
+use redis_rust::protocol::RespValue;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// HPEXPIRETIME of one field
+async fn expire_time(port: u16, field: &str) -> i64 {
+    match command(port, &["HPEXPIRETIME", "h", "FIELDS", "1", field]).await {
+        RespValue::Array(Some(times)) => match times[0] {
+            RespValue::Integer(t) => t,
+            ref other => panic!("unexpected time {:?}", other),
+        },
+        other => panic!("unexpected HPEXPIRETIME reply {:?}", other),
+    }
+}
+
+/// Wait until the replica's expiration time of `field` matches `expected`,
+/// give or take the millisecond the master may tick while propagating
+async fn wait_for_expire_time(port: u16, field: &str, expected: i64) -> bool {
+    for _ in 0..50 {
+        if (expire_time(port, field).await - expected).abs() <= 5 {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_field_ttls_replicate_and_expire() {
+    let dir = TempDir::new().unwrap();
+    let master = start_server(&dir).await.port;
+    let replica = start_server(&dir).await.port;
+
+    command(master, &["HSET", "h", "a", "1", "b", "2", "c", "3"]).await;
+    command(master, &["HEXPIRE", "h", "1000", "FIELDS", "1", "a"]).await;
+
+    // The snapshot carries the field TTL
+    command(replica, &["REPLICAOF", "127.0.0.1", &master.to_string()]).await;
+    let a = expire_time(master, "a").await;
+    assert!(a > 0);
+    assert!(wait_for_expire_time(replica, "a", a).await, "snapshot lost the field TTL");
+
+    // Relative TTLs replay as the same absolute time
+    command(master, &["HSETEX", "h", "EX", "500", "FIELDS", "1", "b", "20"]).await;
+    let b = expire_time(master, "b").await;
+    assert!(wait_for_expire_time(replica, "b", b).await, "HSETEX TTL diverged");
+    command(master, &["HPERSIST", "h", "FIELDS", "1", "a"]).await;
+    assert!(wait_for_expire_time(replica, "a", -1).await, "HPERSIST was not propagated");
+
+    // Hashes whose fields all expire are removed without being read
+    command(master, &["HSET", "gone", "f", "v"]).await;
+    command(master, &["HPEXPIRE", "gone", "100", "FIELDS", "1", "f"]).await;
+    assert_eq!(command(master, &["DBSIZE"]).await, RespValue::Integer(2));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(command(master, &["DBSIZE"]).await, RespValue::Integer(1));
+}
@@ -0,0 +1,104 @@
+// Keyspace Notifications Integration Test
+//
+// Enables notify-keyspace-events at runtime and follows the events of a
+// database through PSUBSCRIBE, checking that each write publishes on both
+// the keyspace and keyevent channels and that disabled classes stay quiet.
+
+pub mod common;
+
+use common::{connect, start_server};
+use redis_rust::protocol::{RespClient, RespValue};
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn text(value: &RespValue) -> String {
+    match value {
+        RespValue::BulkString(Some(bytes)) => String::from_utf8_lossy(bytes).to_string(),
+        other => panic!("expected a bulk string, got {:?}", other),
+    }
+}
+
+/// The (channel, message) of the next pmessage
+async fn next_event(subscriber: &mut RespClient) -> (String, String) {
+    match subscriber.read_reply().await.unwrap() {
+        RespValue::Array(Some(parts)) if parts.len() == 4 && text(&parts[0]) == "pmessage" => {
+            (text(&parts[2]), text(&parts[3]))
+        }
+        other => panic!("expected a pmessage, got {:?}", other),
+    }
+}
+
+fn event(channel: &str, message: &str) -> (String, String) {
+    (channel.to_string(), message.to_string())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keyspace_and_keyevent_notifications() {
+    let dir = TempDir::new().unwrap();
+    let port = start_server(&dir).await.port;
+
+    let mut client = connect(port).await;
+    let mut subscriber = connect(port).await;
+    subscriber.command(&["PSUBSCRIBE", "__key*@0__:*"]).await.unwrap();
+
+    // Off by default
+    client.command(&["SET", "quiet", "v"]).await.unwrap();
+    assert_eq!(
+        client.command(&["CONFIG", "GET", "notify-keyspace-events"]).await.unwrap(),
+        RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"notify-keyspace-events".to_vec())),
+            RespValue::BulkString(Some(Vec::new())),
+        ]))
+    );
+
+    assert!(matches!(
+        client.command(&["CONFIG", "SET", "notify-keyspace-events", "KEq"]).await.unwrap(),
+        RespValue::Error(_)
+    ));
+    // e (evicted) is accepted for compatibility, though nothing is evicted
+    assert_eq!(
+        client.command(&["CONFIG", "SET", "notify-keyspace-events", "KEe"]).await.unwrap(),
+        RespValue::SimpleString("OK".to_string())
+    );
+    assert_eq!(
+        client.command(&["CONFIG", "GET", "notify-keyspace-events"]).await.unwrap(),
+        RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"notify-keyspace-events".to_vec())),
+            RespValue::BulkString(Some(b"KEe".to_vec())),
+        ]))
+    );
+    client.command(&["CONFIG", "SET", "notify-keyspace-events", "KEA"]).await.unwrap();
+
+    client.command(&["LPUSH", "mylist", "a"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:mylist", "lpush"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:lpush", "mylist"));
+
+    // Rotating a one element list never deletes the key
+    client.command(&["LMOVE", "mylist", "mylist", "LEFT", "RIGHT"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:mylist", "lpop"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:lpop", "mylist"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:mylist", "rpush"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:rpush", "mylist"));
+
+    // Popping the last element also reports the key as deleted
+    client.command(&["RPOP", "mylist"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:mylist", "rpop"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:rpop", "mylist"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:mylist", "del"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:del", "mylist"));
+
+    // Keyspace events only, and expiry once the TTL has passed
+    client.command(&["CONFIG", "SET", "notify-keyspace-events", "K$gx"]).await.unwrap();
+    client.command(&["SET", "temp", "v", "PX", "50"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:temp", "set"));
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:temp", "expire"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client.command(&["GET", "temp"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyspace@0__:temp", "expired"));
+
+    // Key misses are opt-in and not part of A
+    client.command(&["CONFIG", "SET", "notify-keyspace-events", "Em"]).await.unwrap();
+    client.command(&["SADD", "myset", "m"]).await.unwrap();
+    client.command(&["GET", "missing"]).await.unwrap();
+    assert_eq!(next_event(&mut subscriber).await, event("__keyevent@0__:keymiss", "missing"));
+}
@@ -0,0 +1,55 @@
+// MIGRATE Persistence Integration Test
+//
+// Migrates keys between two in-process servers with the AOF on, then
+// restarts both from their AOFs: each key must come back on the target only,
+// with its TTL, and keys that were not migrated must stay on the source.
+
+pub mod common;
+
+use common::{bulk, command, ok, start_server_with, TestServer};
+use redis_rust::persistence::aof::AofSyncPolicy;
+use redis_rust::protocol::RespValue;
+use tempfile::TempDir;
+
+async fn start_with_aof(dir: &TempDir, name: &str) -> TestServer {
+    let aof = dir.path().join(name).to_string_lossy().to_string();
+    start_server_with(dir, |config| {
+        config.aof_enabled = true;
+        config.aof_filename = aof;
+        config.aof_sync_policy = AofSyncPolicy::Always;
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_migrated_keys_survive_restart_on_target_only() {
+    let dir = TempDir::new().unwrap();
+    let source = start_with_aof(&dir, "source.aof").await;
+    let target = start_with_aof(&dir, "target.aof").await;
+
+    assert_eq!(command(source.port, &["SET", "moved", "v"]).await, ok());
+    assert_eq!(command(source.port, &["SET", "expiring", "v", "PX", "600000"]).await, ok());
+    assert_eq!(command(source.port, &["SET", "kept", "v"]).await, ok());
+    let port = target.port.to_string();
+    let reply = command(
+        source.port,
+        &["MIGRATE", "127.0.0.1", &port, "", "0", "5000", "KEYS", "moved", "expiring"],
+    )
+    .await;
+    assert_eq!(reply, ok());
+
+    source.stop();
+    target.stop();
+    let source = start_with_aof(&dir, "source.aof").await;
+    let target = start_with_aof(&dir, "target.aof").await;
+
+    assert_eq!(command(source.port, &["GET", "moved"]).await, RespValue::BulkString(None));
+    assert_eq!(command(source.port, &["GET", "expiring"]).await, RespValue::BulkString(None));
+    assert_eq!(command(source.port, &["GET", "kept"]).await, bulk("v"));
+    assert_eq!(command(target.port, &["GET", "moved"]).await, bulk("v"));
+    assert_eq!(command(target.port, &["GET", "kept"]).await, RespValue::BulkString(None));
+    match command(target.port, &["PTTL", "expiring"]).await {
+        RespValue::Integer(ttl) => assert!(ttl > 500_000, "TTL lost on replay: {}", ttl),
+        other => panic!("unexpected PTTL reply {:?}", other),
+    }
+}
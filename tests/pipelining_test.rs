@@ -0,0 +1,52 @@
+// Pipelining Integration Test
+//
+// Sends deep pipelines in a single write and checks that every reply comes
+// back in order, that arguments larger than one socket read are assembled
+// correctly, and that replies queued ahead of a blocking command are not
+// held back while it waits.
+
+pub mod common;
+
+use common::{connect, ok, start_server};
+use redis_rust::protocol::RespValue;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipelined_replies() {
+    let dir = TempDir::new().unwrap();
+    let port = start_server(&dir).await.port;
+    let mut client = connect(port).await;
+
+    // A thousand commands before reading anything
+    for _ in 0..1000 {
+        client.send(&["INCR", "counter"]).await.unwrap();
+    }
+    for i in 1..=1000 {
+        assert_eq!(client.read_reply().await.unwrap(), RespValue::Integer(i));
+    }
+
+    // A value spanning many reads, pipelined between small commands
+    let big = vec![b'x'; 3 * 1024 * 1024];
+    client.send(&[&b"SET"[..], b"big", &big]).await.unwrap();
+    client.send(&["STRLEN", "big"]).await.unwrap();
+    client.send(&["GET", "counter"]).await.unwrap();
+    assert_eq!(client.read_reply().await.unwrap(), ok());
+    assert_eq!(client.read_reply().await.unwrap(), RespValue::Integer(big.len() as i64));
+    assert_eq!(client.read_reply().await.unwrap(), RespValue::BulkString(Some(b"1000".to_vec())));
+
+    // The SET reply arrives while BLPOP is still waiting
+    client.send(&["SET", "before", "1"]).await.unwrap();
+    client.send(&["BLPOP", "empty", "2"]).await.unwrap();
+    let early = tokio::time::timeout(Duration::from_millis(500), client.read_frame()).await;
+    assert_eq!(early.unwrap().unwrap(), ok());
+    let mut other = connect(port).await;
+    other.command(&["RPUSH", "empty", "v"]).await.unwrap();
+    assert_eq!(
+        client.read_reply().await.unwrap(),
+        RespValue::Array(Some(vec![
+            RespValue::BulkString(Some(b"empty".to_vec())),
+            RespValue::BulkString(Some(b"v".to_vec())),
+        ]))
+    );
+}
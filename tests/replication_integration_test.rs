@@ -0,0 +1,155 @@
+// Replication Integration Test
+//
+// Runs a master and a replica in-process and checks that a full resync
+// transfers the dataset, both with a disk-based and a diskless payload
+
+pub mod common;
+
+use common::start_server;
+use std::time::Duration;
+use tempfile::TempDir;
+
+async fn connect(port: u16) -> redis::aio::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    client.get_async_connection().await.unwrap()
+}
+
+async fn wait_for_key(conn: &mut redis::aio::Connection, key: &str) -> Option<String> {
+    for _ in 0..50 {
+        let value: Option<String> = redis::cmd("GET").arg(key).query_async(conn).await.unwrap();
+        if value.is_some() {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    None
+}
+
+async fn run_full_sync(diskless: &str, load: &str) {
+    let dir = TempDir::new().unwrap();
+    let master_port = start_server(&dir).await.port;
+    let replica_port = start_server(&dir).await.port;
+
+    let mut master = connect(master_port).await;
+    for i in 0..100 {
+        let _: () = redis::cmd("SET")
+            .arg(format!("key:{}", i))
+            .arg(format!("value:{}", i))
+            .query_async(&mut master)
+            .await
+            .unwrap();
+    }
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("repl-diskless-sync")
+        .arg(diskless)
+        .query_async(&mut master)
+        .await
+        .unwrap();
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("repl-diskless-sync-delay")
+        .arg("0")
+        .query_async(&mut master)
+        .await
+        .unwrap();
+
+    let mut replica = connect(replica_port).await;
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("repl-diskless-load")
+        .arg(load)
+        .query_async(&mut replica)
+        .await
+        .unwrap();
+    let _: () = redis::cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query_async(&mut replica)
+        .await
+        .unwrap();
+
+    assert_eq!(wait_for_key(&mut replica, "key:99").await, Some("value:99".to_string()));
+    let size: i64 = redis::cmd("DBSIZE").query_async(&mut replica).await.unwrap();
+    assert_eq!(size, 100);
+}
+
+#[tokio::test]
+async fn test_full_sync_disk_based() {
+    run_full_sync("no", "disabled").await;
+}
+
+#[tokio::test]
+async fn test_full_sync_diskless_swapdb() {
+    run_full_sync("yes", "swapdb").await;
+}
+
+#[tokio::test]
+async fn test_full_sync_diskless_on_empty_db() {
+    run_full_sync("yes", "on-empty-db").await;
+}
+
+async fn role_offset(conn: &mut redis::aio::Connection) -> i64 {
+    let role: Vec<redis::Value> = redis::cmd("ROLE").query_async(conn).await.unwrap();
+    let index = if role.len() == 3 { 1 } else { 4 };
+    redis::from_redis_value(&role[index]).unwrap()
+}
+
+#[tokio::test]
+async fn test_writes_during_diskless_sync_reach_replica() {
+    let dir = TempDir::new().unwrap();
+    let master_port = start_server(&dir).await.port;
+    let replica_port = start_server(&dir).await.port;
+
+    let mut master = connect(master_port).await;
+    // Enough data that the transfer takes a while
+    let value = "v".repeat(1024);
+    for batch in 0..20 {
+        let mut mset = redis::cmd("MSET");
+        for i in 0..1000 {
+            mset.arg(format!("key:{}:{}", batch, i)).arg(&value);
+        }
+        let _: () = mset.query_async(&mut master).await.unwrap();
+    }
+    for (name, value) in [("repl-diskless-sync", "yes"), ("repl-diskless-sync-delay", "1")] {
+        let _: () = redis::cmd("CONFIG")
+            .arg("SET")
+            .arg(name)
+            .arg(value)
+            .query_async(&mut master)
+            .await
+            .unwrap();
+    }
+
+    let mut replica = connect(replica_port).await;
+    let _: () = redis::cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query_async(&mut replica)
+        .await
+        .unwrap();
+
+    // Writes before the snapshot is taken, while it is sent and after
+    let started = std::time::Instant::now();
+    let mut writes = 0;
+    while started.elapsed() < Duration::from_secs(3) {
+        let _: i64 = redis::cmd("INCR").arg("counter").query_async(&mut master).await.unwrap();
+        let _: i64 = redis::cmd("RPUSH").arg("list").arg(writes).query_async(&mut master).await.unwrap();
+        writes += 1;
+    }
+
+    // Every write arrives exactly once
+    let expected = writes.to_string();
+    let mut counter = None;
+    for _ in 0..50 {
+        counter = redis::cmd("GET").arg("counter").query_async(&mut replica).await.unwrap();
+        if counter.as_deref() == Some(expected.as_str()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(counter, Some(expected));
+    let list: Vec<i64> = redis::cmd("LRANGE").arg("list").arg(0).arg(-1).query_async(&mut replica).await.unwrap();
+    assert_eq!(list, (0..writes).collect::<Vec<_>>());
+    assert_eq!(role_offset(&mut replica).await, role_offset(&mut master).await);
+}
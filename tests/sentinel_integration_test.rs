@@ -0,0 +1,209 @@
+// Sentinel Integration Test
+//
+// Runs a master, two replicas and three sentinels as separate processes,
+// kills the master and checks that the sentinels promote a replica
+
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const MASTER_PORT: u16 = 16501;
+const REPLICA_PORTS: [u16; 2] = [16502, 16503];
+const SENTINEL_PORTS: [u16; 3] = [16511, 16512, 16513];
+
+/// Kills the spawned processes when the test ends
+struct Processes(Vec<(u16, Child)>);
+
+impl Processes {
+    fn spawn(&mut self, port: u16, args: &[&str], dir: &TempDir) {
+        let child = Command::new(env!("CARGO_BIN_EXE_redis-rust"))
+            .args(args)
+            .current_dir(dir.path())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        self.0.push((port, child));
+    }
+
+    fn kill(&mut self, port: u16) {
+        if let Some(pos) = self.0.iter().position(|(p, _)| *p == port) {
+            let (_, mut child) = self.0.remove(pos);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Processes {
+    fn drop(&mut self) {
+        for (_, child) in &mut self.0 {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+async fn connect(port: u16) -> redis::aio::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match client.get_async_connection().await {
+            Ok(conn) => return conn,
+            Err(e) if Instant::now() > deadline => panic!("cannot connect to {}: {}", port, e),
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+}
+
+/// Poll `check` every 200ms until it returns true or `timeout` passes
+async fn wait_until<F, Fut>(timeout: Duration, mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    false
+}
+
+async fn master_addr(sentinel_port: u16) -> Option<(String, u16)> {
+    let mut conn = connect(sentinel_port).await;
+    let addr: Option<(String, String)> = redis::cmd("SENTINEL")
+        .arg("get-master-addr-by-name")
+        .arg("mymaster")
+        .query_async(&mut conn)
+        .await
+        .ok()?;
+    addr.map(|(ip, port)| (ip, port.parse().unwrap()))
+}
+
+/// Replicas a sentinel knows with master-link-status ok
+async fn healthy_replicas(sentinel_port: u16) -> usize {
+    let mut conn = connect(sentinel_port).await;
+    let replicas: Vec<Vec<String>> = redis::cmd("SENTINEL")
+        .arg("replicas")
+        .arg("mymaster")
+        .query_async(&mut conn)
+        .await
+        .unwrap_or_default();
+    replicas
+        .iter()
+        .filter(|fields| fields.windows(2).any(|kv| kv[0] == "master-link-status" && kv[1] == "ok"))
+        .count()
+}
+
+async fn known_sentinels(sentinel_port: u16) -> usize {
+    let mut conn = connect(sentinel_port).await;
+    let sentinels: Vec<Vec<String>> = redis::cmd("SENTINEL")
+        .arg("sentinels")
+        .arg("mymaster")
+        .query_async(&mut conn)
+        .await
+        .unwrap_or_default();
+    sentinels.len()
+}
+
+async fn info_field(port: u16, field: &str) -> Option<String> {
+    let mut conn = connect(port).await;
+    let info: String = redis::cmd("INFO").arg("replication").query_async(&mut conn).await.ok()?;
+    info.lines()
+        .find_map(|line| line.strip_prefix(&format!("{}:", field)).map(|v| v.trim().to_string()))
+}
+
+#[tokio::test]
+async fn test_sentinel_failover() {
+    let dir = TempDir::new().unwrap();
+    let mut processes = Processes(Vec::new());
+
+    // Data nodes
+    processes.spawn(MASTER_PORT, &["--port", &MASTER_PORT.to_string()], &dir);
+    for port in REPLICA_PORTS {
+        processes.spawn(port, &["--port", &port.to_string()], &dir);
+    }
+    for port in REPLICA_PORTS {
+        let mut conn = connect(port).await;
+        let _: () = redis::cmd("REPLICAOF")
+            .arg("127.0.0.1")
+            .arg(MASTER_PORT)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    // Sentinels
+    for port in SENTINEL_PORTS {
+        let path = dir.path().join(format!("sentinel-{}.conf", port));
+        std::fs::write(
+            &path,
+            format!(
+                "port {}\n\
+                 sentinel monitor mymaster 127.0.0.1 {} 2\n\
+                 sentinel down-after-milliseconds mymaster 1000\n\
+                 sentinel failover-timeout mymaster 5000\n",
+                port, MASTER_PORT
+            ),
+        )
+        .unwrap();
+        processes.spawn(port, &["--sentinel", path.to_str().unwrap()], &dir);
+    }
+
+    // Every sentinel discovers both replicas and the other sentinels
+    for port in SENTINEL_PORTS {
+        assert!(
+            wait_until(Duration::from_secs(20), || async move { healthy_replicas(port).await == 2 }).await,
+            "sentinel {} did not discover the replicas",
+            port
+        );
+        assert!(
+            wait_until(Duration::from_secs(20), || async move { known_sentinels(port).await == 2 }).await,
+            "sentinel {} did not discover the other sentinels",
+            port
+        );
+    }
+    assert_eq!(
+        master_addr(SENTINEL_PORTS[0]).await,
+        Some(("127.0.0.1".to_string(), MASTER_PORT))
+    );
+
+    // Kill the master and wait for every sentinel to switch
+    processes.kill(MASTER_PORT);
+    for port in SENTINEL_PORTS {
+        assert!(
+            wait_until(Duration::from_secs(40), || async move {
+                matches!(master_addr(port).await, Some((_, p)) if p != MASTER_PORT)
+            })
+            .await,
+            "sentinel {} did not fail over",
+            port
+        );
+    }
+
+    let (_, new_master) = master_addr(SENTINEL_PORTS[0]).await.unwrap();
+    assert!(REPLICA_PORTS.contains(&new_master));
+    for port in &SENTINEL_PORTS[1..] {
+        assert_eq!(master_addr(*port).await.unwrap().1, new_master);
+    }
+    assert_eq!(info_field(new_master, "role").await.as_deref(), Some("master"));
+
+    // The other replica follows the promoted one
+    let other = *REPLICA_PORTS.iter().find(|p| **p != new_master).unwrap();
+    let expected = new_master.to_string();
+    assert!(
+        wait_until(Duration::from_secs(20), || {
+            let expected = expected.clone();
+            async move {
+                info_field(other, "master_port").await == Some(expected)
+                    && info_field(other, "master_link_status").await.as_deref() == Some("up")
+            }
+        })
+        .await,
+        "replica {} was not reconfigured",
+        other
+    );
+}
@@ -0,0 +1,88 @@
+// Stream Consumer Groups Integration Test
+//
+// Runs a master and a replica in-process. Group state built before the
+// replica attaches arrives with the snapshot; later reads, claims and
+// acknowledgements are propagated in a form that replays to the same PEL.
+
+pub mod common;
+
+use common::{command, start_server};
+use redis_rust::protocol::RespValue;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Pending entries as (id, consumer, delivery count), leaving out idle times
+async fn pending(port: u16) -> Vec<(RespValue, RespValue, RespValue)> {
+    match command(port, &["XPENDING", "s", "g", "-", "+", "100"]).await {
+        RespValue::Array(Some(entries)) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                RespValue::Array(Some(parts)) => (parts[0].clone(), parts[1].clone(), parts[3].clone()),
+                other => panic!("unexpected pending entry {:?}", other),
+            })
+            .collect(),
+        RespValue::Error(_) => vec![],
+        other => panic!("unexpected XPENDING reply {:?}", other),
+    }
+}
+
+async fn wait_for_pending(port: u16, expected: &[(RespValue, RespValue, RespValue)]) -> bool {
+    for _ in 0..50 {
+        if pending(port).await == expected {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_consumer_groups_replicate() {
+    let dir = TempDir::new().unwrap();
+    let master = start_server(&dir).await.port;
+    let replica = start_server(&dir).await.port;
+
+    for i in 0..3 {
+        command(master, &["XADD", "s", "*", "n", &i.to_string()]).await;
+    }
+    command(master, &["XGROUP", "CREATE", "s", "g", "0"]).await;
+    command(master, &["XREADGROUP", "GROUP", "g", "alice", "COUNT", "2", "STREAMS", "s", ">"]).await;
+
+    // The snapshot carries the group and its PEL
+    command(replica, &["REPLICAOF", "127.0.0.1", &master.to_string()]).await;
+    let before = pending(master).await;
+    assert_eq!(before.len(), 2);
+    assert!(wait_for_pending(replica, &before).await, "snapshot did not carry the group");
+
+    // Generated IDs and claims replay identically on the replica
+    command(master, &["XADD", "s", "*", "n", "3"]).await;
+    command(master, &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", ">"]).await;
+    command(master, &["XAUTOCLAIM", "s", "g", "carol", "0", "0", "COUNT", "1"]).await;
+    command(master, &["XCLAIM", "s", "g", "dave", "3600000", "0-1"]).await;
+    let RespValue::Array(Some(bob)) = command(master, &["XPENDING", "s", "g", "-", "+", "1", "bob"]).await
+    else {
+        panic!("bob has no pending entries");
+    };
+    let RespValue::Array(Some(first)) = &bob[0] else {
+        panic!("unexpected pending entry");
+    };
+    let RespValue::BulkString(Some(id)) = &first[0] else {
+        panic!("unexpected ID");
+    };
+    command(master, &["XACK", "s", "g", &String::from_utf8_lossy(id)]).await;
+
+    let after = pending(master).await;
+    assert_eq!(after.len(), 3);
+    assert!(wait_for_pending(replica, &after).await, "replica PEL diverged");
+
+    let last_id = |reply: RespValue| match reply {
+        RespValue::Array(Some(fields)) => fields[7].clone(),
+        other => panic!("unexpected XINFO reply {:?}", other),
+    };
+    assert_eq!(
+        last_id(command(replica, &["XINFO", "STREAM", "s"]).await),
+        last_id(command(master, &["XINFO", "STREAM", "s"]).await)
+    );
+    let consumers = command(replica, &["XINFO", "CONSUMERS", "s", "g"]).await;
+    assert!(matches!(consumers, RespValue::Array(Some(ref c)) if c.len() == 4));
+}